@@ -0,0 +1,100 @@
+//! Compiler throughput on a large generated program: `cargo bench`, or
+//! `cargo bench -- <name>` for the benchmarks whose names contain `name`.
+//!
+//! Each benchmark runs for about a second and reports the fastest run,
+//! which is the least disturbed by the rest of the machine.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use rcc::driver::{self, Options};
+use rcc::lexer::Lexer;
+use rcc::opt::OptLevel;
+use rcc::parser::Parser;
+use rcc::stats::Stats;
+
+/// How long to keep repeating each benchmark.
+const BUDGET: Duration = Duration::from_secs(1);
+
+/// A program of `functions` functions, each calling the one before it.
+fn program(functions: usize) -> String {
+    let mut source = String::from("int f0() { return 1; }\n");
+    for i in 1..functions {
+        source.push_str(&format!(
+            "int f{i}() {{\n    return f{}() + {i} * 3 - {i} / 2 % 7 + (f{}() - {i});\n}}\n",
+            i - 1,
+            i - 1
+        ));
+    }
+    source.push_str(&format!(
+        "int main() {{ return f{}() % 2; }}\n",
+        functions - 1
+    ));
+    source
+}
+
+/// The fastest of repeated runs of `f`.
+fn fastest(mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    let mut best = Duration::MAX;
+    while start.elapsed() < BUDGET {
+        let run = Instant::now();
+        f();
+        best = best.min(run.elapsed());
+    }
+    best
+}
+
+fn report(name: &str, time: Duration, amount: f64, unit: &str) {
+    println!(
+        "{name:<16} {:>10.3} ms {:>12.1} {unit}/s",
+        time.as_secs_f64() * 1e3,
+        amount / time.as_secs_f64()
+    );
+}
+
+fn main() {
+    // Cargo passes `--bench`; anything else selects benchmarks.
+    let filter: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--"))
+        .collect();
+    let selected = |name: &str| filter.is_empty() || filter.iter().any(|f| name.contains(f));
+
+    let source = program(2000);
+    let options = Options::default();
+    let artifacts = driver::compile(&source, &options).expect("the program compiles");
+    let nodes = Stats::collect(&artifacts, &options).ast_nodes;
+    println!(
+        "input: {} functions, {} bytes, {} tokens, {nodes} AST nodes",
+        artifacts.ir.functions.len(),
+        source.len(),
+        artifacts.tokens.len()
+    );
+
+    if selected("lex") {
+        let time = fastest(|| {
+            black_box(Lexer::new(black_box(&source)).lex().unwrap());
+        });
+        report("lex", time, source.len() as f64 / 1e6, "MB");
+    }
+    if selected("parse") {
+        let tokens = artifacts.tokens.clone();
+        let time = fastest(|| {
+            black_box(Parser::new(black_box(tokens.clone())).parse().unwrap());
+        });
+        report("parse", time, nodes as f64 / 1e6, "M nodes");
+    }
+    for (name, opt_level) in [("compile-O0", OptLevel::O0), ("compile-O2", OptLevel::O2)] {
+        if selected(name) {
+            let options = Options {
+                opt_level,
+                ..Options::default()
+            };
+            let time = fastest(|| {
+                black_box(driver::compile(black_box(&source), &options).unwrap());
+            });
+            report(name, time, source.len() as f64 / 1e6, "MB");
+        }
+    }
+}
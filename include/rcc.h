@@ -0,0 +1,47 @@
+/* The C interface to rcc, for compiling C to assembly in-process.
+ *
+ * Link against librcc.a or librcc.so, built by `cargo build`. */
+
+#ifndef RCC_H
+#define RCC_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Optimization levels for rcc_compile. */
+#define RCC_O0 0
+#define RCC_O1 1
+#define RCC_O2 2
+#define RCC_OS 3
+
+/* The outcome of a compilation, owning the strings it returns. */
+typedef struct RccResult rcc_result;
+
+/* Compiles the `len` bytes at `source`, naming the file `file` in
+ * diagnostics (may be NULL), for the target triple `target` (NULL for the
+ * host) at the optimization level `opt_level`.
+ *
+ * Never returns NULL; free the result with rcc_result_free. */
+rcc_result *rcc_compile(const char *source, size_t len, const char *file,
+                        const char *target, int opt_level);
+
+/* The generated assembly, or NULL if compilation failed. */
+const char *rcc_result_assembly(const rcc_result *result);
+
+/* The diagnostics, one per line, or "" if there are none. */
+const char *rcc_result_diagnostics(const rcc_result *result);
+
+/* Frees `result` and its strings. Does nothing if it is NULL. */
+void rcc_result_free(rcc_result *result);
+
+/* The version of rcc, as a static string. */
+const char *rcc_version(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,1490 @@
+//! Semantic analysis: name resolution and type checking over the AST.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{
+    AsmOperand, BinaryOp, DataModel, Enum, Expression, Function, Global, Initializer, Program,
+    Statement, Type, UnaryOp,
+};
+use crate::error::{Error, Result};
+use crate::ice;
+use crate::lexer::Position;
+use crate::standard::Standard;
+use crate::warning::{Diagnostic, Warning};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Symbol {
+    Function {
+        return_type: Type,
+        params: Vec<Type>,
+        /// Whether more arguments may follow those for `params`.
+        variadic: bool,
+        /// Whether `params` were declared, rather than left out by `()`
+        /// before C23.
+        prototyped: bool,
+    },
+    Variable {
+        ty: Type,
+    },
+    /// An enumeration constant, which is an `int`.
+    Constant {
+        value: i64,
+    },
+}
+
+/// A stack of lexical scopes; lookups search from the innermost scope outwards.
+/// Each symbol is kept with where it was last declared.
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    scopes: Vec<HashMap<String, (Symbol, Position)>>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+        }
+    }
+
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    pub fn insert(&mut self, name: String, symbol: Symbol, pos: Position) {
+        self.scopes
+            .last_mut()
+            .expect("symbol table always has a global scope")
+            .insert(name, (symbol, pos));
+    }
+
+    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .map(|(symbol, _)| symbol)
+    }
+
+    /// Where the `name` that [`lookup`](Self::lookup) finds was declared.
+    pub fn declared_at(&self, name: &str) -> Option<Position> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .map(|&(_, pos)| pos)
+    }
+
+    /// How many scopes out from the global scope `name` is declared in,
+    /// the global scope being 0.
+    pub fn depth_of(&self, name: &str) -> Option<usize> {
+        self.scopes
+            .iter()
+            .rposition(|scope| scope.contains_key(name))
+    }
+
+    /// Looks `name` up in the innermost scope only.
+    pub fn lookup_local(&self, name: &str) -> Option<&Symbol> {
+        self.scopes
+            .last()
+            .and_then(|scope| scope.get(name))
+            .map(|(symbol, _)| symbol)
+    }
+}
+
+/// The labels of a `switch` seen so far.
+#[derive(Debug)]
+struct SwitchLabels {
+    /// The type of the promoted condition, which each `case` value is
+    /// converted to.
+    ty: Type,
+    /// Each `case` value, converted, with where.
+    cases: HashMap<i64, Position>,
+    default: Option<Position>,
+}
+
+#[derive(Debug, Default)]
+pub struct Analyzer {
+    symbols: SymbolTable,
+    standard: Standard,
+    /// The first declaration of each function and global in the file,
+    /// collected before anything is analyzed so that a name can be used
+    /// above its declaration.
+    later: HashMap<String, Symbol>,
+    /// The globals defined with an initializer, each of which may be
+    /// initialized once, with where. Definitions without one are
+    /// tentative and may be repeated.
+    defined_globals: HashMap<String, Position>,
+    /// The globals declared `_Thread_local`, whose addresses aren't
+    /// constants, since each thread has its own.
+    thread_locals: HashSet<String>,
+    /// The functions defined so far, with where.
+    defined_functions: HashMap<String, Position>,
+    /// For each open scope, the local variables declared there that
+    /// nothing has used yet, with where they were declared.
+    unused: Vec<HashMap<String, Position>>,
+    /// The `switch` statements around the statement being analyzed,
+    /// innermost last.
+    switches: Vec<SwitchLabels>,
+    /// How many loops are around the statement being analyzed.
+    loops: usize,
+    /// Every warning found so far, whether or not it is turned on.
+    warnings: Vec<Diagnostic>,
+    model: DataModel,
+}
+
+impl Analyzer {
+    pub fn new() -> Self {
+        Self {
+            symbols: SymbolTable::new(),
+            standard: Standard::default(),
+            later: HashMap::new(),
+            defined_globals: HashMap::new(),
+            thread_locals: HashSet::new(),
+            defined_functions: HashMap::new(),
+            unused: vec![HashMap::new()],
+            switches: Vec::new(),
+            loops: 0,
+            warnings: Vec::new(),
+            model: DataModel::default(),
+        }
+    }
+
+    pub fn with_standard(mut self, standard: Standard) -> Self {
+        self.standard = standard;
+        self
+    }
+
+    /// Sizes types as `model` does, for `sizeof`, constants and the
+    /// usual arithmetic conversions.
+    pub fn with_data_model(mut self, model: DataModel) -> Self {
+        self.model = model;
+        self
+    }
+
+    pub fn analyze(self, program: &Program) -> Result<()> {
+        self.analyze_with_warnings(program).map(drop)
+    }
+
+    /// Like [`analyze`](Self::analyze), returning every warning about the
+    /// program in source order. Which of them to report is up to the
+    /// caller.
+    pub fn analyze_with_warnings(mut self, program: &Program) -> Result<Vec<Diagnostic>> {
+        self.declare_ahead(program);
+        for item in in_source_order(program) {
+            self.analyze_item(item)?;
+        }
+        self.warnings.sort_by_key(|diagnostic| diagnostic.pos);
+        Ok(self.warnings)
+    }
+
+    /// Analyzes every declaration, reporting the first error in each
+    /// rather than stopping at the first one overall.
+    pub fn analyze_recovering(mut self, program: &Program) -> Vec<Error> {
+        self.declare_ahead(program);
+        in_source_order(program)
+            .into_iter()
+            .filter_map(|item| self.analyze_item(item).err())
+            .collect()
+    }
+
+    /// The first pass: notes the type of every function and global, so
+    /// that calls and uses may come before the declaration, and functions
+    /// may call each other in any order. The second pass checks each
+    /// declaration against the first one of its name.
+    fn declare_ahead(&mut self, program: &Program) {
+        for item in in_source_order(program) {
+            let (name, symbol) = match item {
+                Item::Function(function) => (&function.name, signature(function)),
+                Item::Global(global) => {
+                    if global.thread_local {
+                        self.thread_locals.insert(global.name.clone());
+                    }
+                    (
+                        &global.name,
+                        Symbol::Variable {
+                            ty: global.ty.clone(),
+                        },
+                    )
+                }
+                Item::Enum(_) => continue,
+            };
+            // A declaration with parameters says more than one without.
+            match self.later.get(name) {
+                Some(Symbol::Function {
+                    prototyped: false, ..
+                }) if matches!(
+                    symbol,
+                    Symbol::Function {
+                        prototyped: true,
+                        ..
+                    }
+                ) =>
+                {
+                    self.later.insert(name.clone(), symbol);
+                }
+                Some(_) => {}
+                None => {
+                    self.later.insert(name.clone(), symbol);
+                }
+            }
+        }
+    }
+
+    /// The symbol `name` refers to: the innermost declaration of it in
+    /// scope, or else a function or global declared further down the file.
+    fn lookup(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.lookup(name).or_else(|| self.later.get(name))
+    }
+
+    fn analyze_item(&mut self, item: Item) -> Result<()> {
+        match item {
+            Item::Function(function) => self.analyze_function(function),
+            Item::Enum(decl) => self.declare_enum(decl),
+            Item::Global(global) => self.declare_global(global),
+        }
+    }
+
+    /// Declares `global` in the file scope. Its initializer is stored in
+    /// the object file, so it has to be a constant.
+    fn declare_global(&mut self, global: &Global) -> Result<()> {
+        let Global { name, ty, pos, .. } = global;
+        check_object_type(name, ty, *pos, self.model)?;
+        // Any number of `extern` declarations and tentative definitions
+        // (`int x;`) may go with one definition, as long as they agree on
+        // the type.
+        match self.symbols.lookup_local(name) {
+            Some(Symbol::Variable { ty: previous }) if previous != ty => {
+                let error = Error::new(*pos, format!("conflicting types for `{name}`"));
+                return Err(self.with_previous(error, name));
+            }
+            Some(Symbol::Variable { .. }) if global.is_extern || global.init.is_none() => {}
+            Some(Symbol::Variable { .. }) => {
+                if let Some(&previous) = self.defined_globals.get(name) {
+                    return Err(Error::new(*pos, format!("redefinition of `{name}`"))
+                        .with_note(previous, "previous definition is here"));
+                }
+            }
+            Some(_) => {
+                let error = Error::new(
+                    *pos,
+                    format!("redefinition of `{name}` as a different kind of symbol"),
+                );
+                return Err(self.with_previous(error, name));
+            }
+            None => {}
+        }
+        if !global.is_extern && global.init.is_some() {
+            self.defined_globals.insert(name.clone(), *pos);
+        }
+        crate::log!(Trace, "analyzer", "declaring global `{name}` of `{ty}`");
+        self.symbols
+            .insert(name.clone(), Symbol::Variable { ty: ty.clone() }, *pos);
+        let Some(init) = &global.init else {
+            return Ok(());
+        };
+        self.check_initializer(init, ty)?;
+        self.check_static_initializer(init, name, *pos)
+    }
+
+    /// Checks that every value in `init`, which initializes the global
+    /// `name`, is an integer constant or an address constant.
+    fn check_static_initializer(
+        &self,
+        init: &Initializer,
+        name: &str,
+        pos: Position,
+    ) -> Result<()> {
+        let value = match init {
+            Initializer::List { elements, .. } => {
+                return elements
+                    .iter()
+                    .try_for_each(|init| self.check_static_initializer(init, name, pos));
+            }
+            Initializer::Expr(value) => value,
+        };
+        let object = |name: &str| match self.lookup(name) {
+            Some(Symbol::Variable { ty }) if !self.thread_locals.contains(name) => Some(ty.clone()),
+            _ => None,
+        };
+        match value.static_value(&object, &|name| self.constant(name), self.model) {
+            Some(_) => Ok(()),
+            None => Err(Error::new(
+                pos,
+                format!("initializer of `{name}` is not a constant"),
+            )),
+        }
+    }
+
+    /// Declares the enumerators of `decl` in the current scope, each in
+    /// scope from its own declaration on.
+    fn declare_enum(&mut self, decl: &Enum) -> Result<()> {
+        let mut next = 0;
+        for enumerator in &decl.enumerators {
+            let name = &enumerator.name;
+            if self.symbols.lookup_local(name).is_some() {
+                let error = Error::new(enumerator.pos, format!("redefinition of `{name}`"));
+                return Err(self.with_previous(error, name));
+            }
+            let value = match &enumerator.value {
+                Some(value) => {
+                    self.expect_integer(value)?;
+                    value
+                        .constant_value(&|name| self.constant(name), self.model)
+                        .ok_or_else(|| {
+                            Error::new(
+                                enumerator.pos,
+                                format!("value of enumerator `{name}` is not an integer constant"),
+                            )
+                        })?
+                }
+                None => next,
+            };
+            if i32::try_from(value).is_err() {
+                return Err(Error::new(
+                    enumerator.pos,
+                    format!("value of enumerator `{name}` is out of range of `int`"),
+                ));
+            }
+            crate::log!(Trace, "analyzer", "declaring `{name}` as {value}");
+            self.symbols
+                .insert(name.clone(), Symbol::Constant { value }, enumerator.pos);
+            next = value + 1;
+        }
+        Ok(())
+    }
+
+    /// The value of the enumeration constant `name`, if that's what it
+    /// names.
+    fn constant(&self, name: &str) -> Option<i64> {
+        match self.symbols.lookup(name) {
+            Some(Symbol::Constant { value }) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn analyze_function(&mut self, function: &Function) -> Result<()> {
+        crate::log!(
+            Trace,
+            "analyzer",
+            "declaring `{}` returning `{}`",
+            function.name,
+            function.return_type
+        );
+        ice::set_function(&function.name);
+        for param in &function.params {
+            if param.ty == Type::Void {
+                let message = match &param.name {
+                    Some(name) => format!("parameter `{name}` has incomplete type `void`"),
+                    None => "`void` must be the first and only parameter if specified".to_string(),
+                };
+                return Err(Error::new(param.pos, message));
+            }
+        }
+        let symbol = signature(function);
+        // Every declaration of a function must agree with the first, and
+        // only one may have a body.
+        let name = &function.name;
+        let message = match self.symbols.lookup(name) {
+            Some(previous @ Symbol::Function { .. }) if !agree(previous, &symbol) => {
+                Some(format!("conflicting types for `{name}`"))
+            }
+            Some(Symbol::Function { .. }) | None => None,
+            Some(_) => Some(format!(
+                "redefinition of `{name}` as a different kind of symbol"
+            )),
+        };
+        if let Some(message) = message {
+            return Err(self.with_previous(Error::new(function.pos, message), name));
+        }
+        // `()` leaves the parameters as an earlier declaration gave them.
+        let symbol = match self.symbols.lookup(name) {
+            Some(
+                previous @ Symbol::Function {
+                    prototyped: true, ..
+                },
+            ) if !function.prototyped => previous.clone(),
+            _ => symbol,
+        };
+        self.symbols.insert(name.clone(), symbol, function.pos);
+        let Some(body) = &function.body else {
+            return Ok(());
+        };
+        // Without `va_list` the body couldn't read the extra arguments.
+        if function.variadic {
+            return Err(Error::new(
+                function.pos,
+                format!("defining variadic function `{name}` is not supported yet"),
+            ));
+        }
+        if let Some(&previous) = self.defined_functions.get(name) {
+            return Err(
+                Error::new(function.pos, format!("redefinition of `{name}`"))
+                    .with_note(previous, "previous definition is here"),
+            );
+        }
+        self.defined_functions.insert(name.clone(), function.pos);
+        self.push_scope();
+        let analyzed = self.declare_params(function).and_then(|()| {
+            body.iter()
+                .try_for_each(|statement| self.analyze_statement(statement, function))
+        });
+        self.pop_scope();
+        analyzed?;
+        check_labels(body, &mut self.warnings)?;
+        let returns = check_reachability(body, self.model, &mut self.warnings);
+        // Reaching the end of `main` returns 0.
+        if !returns && function.return_type != Type::Void && function.name != "main" {
+            self.warnings.push(Diagnostic::new(
+                Warning::ReturnType,
+                function.pos,
+                format!(
+                    "non-void function `{}` does not return a value in all control paths",
+                    function.name
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// `error`, about `name` clashing with the declaration of it in scope,
+    /// with a note pointing to that declaration.
+    fn with_previous(&self, error: Error, name: &str) -> Error {
+        match self.symbols.declared_at(name) {
+            Some(previous) => error.with_note(previous, "previous declaration is here"),
+            None => error,
+        }
+    }
+
+    fn push_scope(&mut self) {
+        self.symbols.push_scope();
+        self.unused.push(HashMap::new());
+    }
+
+    /// Closes the innermost scope, warning about the variables declared in
+    /// it that were never used.
+    fn pop_scope(&mut self) {
+        self.symbols.pop_scope();
+        for (name, pos) in self.unused.pop().into_iter().flatten() {
+            self.warnings.push(Diagnostic::new(
+                Warning::UnusedVariable,
+                pos,
+                format!("unused variable `{name}`"),
+            ));
+        }
+    }
+
+    /// Declares the parameters in the function's outermost scope, which its
+    /// body shares.
+    fn declare_params(&mut self, function: &Function) -> Result<()> {
+        for param in &function.params {
+            let name = param
+                .name
+                .as_ref()
+                .expect("the parser requires definitions to name their parameters");
+            if self.symbols.lookup_local(name).is_some() {
+                let error = Error::new(param.pos, format!("redefinition of parameter `{name}`"));
+                return Err(self.with_previous(error, name));
+            }
+            self.symbols.insert(
+                name.clone(),
+                Symbol::Variable {
+                    ty: param.ty.clone(),
+                },
+                param.pos,
+            );
+        }
+        Ok(())
+    }
+
+    fn analyze_statement(&mut self, statement: &Statement, function: &Function) -> Result<()> {
+        ice::set_pos(statement.pos());
+        match statement {
+            Statement::Return { value, pos } => match (value, &function.return_type) {
+                (None, Type::Void) => Ok(()),
+                // Returning a `void` expression is a GNU extension that GCC
+                // and Clang accept in every mode.
+                (Some(value), Type::Void) if self.analyze_expression(value)? == Type::Void => {
+                    Ok(())
+                }
+                (Some(_), Type::Void) => Err(Error::new(
+                    *pos,
+                    format!(
+                        "void function `{}` should not return a value",
+                        function.name
+                    ),
+                )),
+                (Some(value), return_type) => self.expect_assignable(value, return_type),
+                (None, _) => Err(Error::new(
+                    *pos,
+                    format!(
+                        "non-void function `{}` should return a value",
+                        function.name
+                    ),
+                )),
+            },
+            Statement::Declaration {
+                name,
+                ty,
+                init,
+                pos,
+            } => {
+                check_object_type(name, ty, *pos, self.model)?;
+                if self.symbols.lookup_local(name).is_some() {
+                    let error = Error::new(*pos, format!("redefinition of `{name}`"));
+                    return Err(self.with_previous(error, name));
+                }
+                // A variable is in scope from its declarator on, so its
+                // initializer already sees it.
+                self.symbols
+                    .insert(name.clone(), Symbol::Variable { ty: ty.clone() }, *pos);
+                if let Some(unused) = self.unused.last_mut() {
+                    unused.insert(name.clone(), *pos);
+                }
+                match init {
+                    Some(init) => self.check_initializer(init, ty),
+                    None => Ok(()),
+                }
+            }
+            Statement::Expression { expr, .. } => self.analyze_expression(expr).map(drop),
+            Statement::Enum(decl) => self.declare_enum(decl),
+            // Blocks share their function's scope for now.
+            // A block is a scope of its own, in which a declaration may
+            // shadow one outside.
+            Statement::Block { body, .. } => {
+                self.push_scope();
+                let analyzed = body
+                    .iter()
+                    .try_for_each(|statement| self.analyze_statement(statement, function));
+                self.pop_scope();
+                analyzed
+            }
+            Statement::If {
+                cond,
+                then,
+                otherwise,
+                ..
+            } => {
+                self.check_scalar(cond)?;
+                self.analyze_statement(then, function)?;
+                match otherwise {
+                    Some(otherwise) => self.analyze_statement(otherwise, function),
+                    None => Ok(()),
+                }
+            }
+            Statement::While { cond, body, .. } | Statement::DoWhile { body, cond, .. } => {
+                self.check_scalar(cond)?;
+                self.analyze_loop_body(body, function)
+            }
+            Statement::For { .. } => {
+                // The loop is a scope of its own, holding what `init`
+                // declares.
+                self.push_scope();
+                let analyzed = self.analyze_for(statement, function);
+                self.pop_scope();
+                analyzed
+            }
+            Statement::Label { body, .. } => self.analyze_statement(body, function),
+            Statement::Switch { cond, body, .. } => {
+                let ty = self.analyze_expression(cond)?;
+                if !ty.is_integer() {
+                    return Err(type_error(cond, Type::Int, ty));
+                }
+                self.switches.push(SwitchLabels {
+                    ty: ty.promote(),
+                    cases: HashMap::new(),
+                    default: None,
+                });
+                let analyzed = self.analyze_statement(body, function);
+                self.switches.pop();
+                analyzed
+            }
+            Statement::Case { value, body, pos } => {
+                if self.switches.is_empty() {
+                    return Err(Error::new(*pos, "`case` statement not in switch statement"));
+                }
+                self.expect_integer(value)?;
+                let value = value
+                    .constant_value(&|name| self.constant(name), self.model)
+                    .ok_or_else(|| Error::new(*pos, "`case` value is not an integer constant"))?;
+                let switch = self.switches.last_mut().expect("checked above");
+                let value = match switch.ty {
+                    Type::Int => i64::from(value as i32),
+                    Type::UInt => i64::from(value as u32),
+                    _ => value,
+                };
+                if let Some(previous) = switch.cases.insert(value, *pos) {
+                    return Err(Error::new(*pos, format!("duplicate case value `{value}`"))
+                        .with_note(previous, "previous case is here"));
+                }
+                self.analyze_statement(body, function)
+            }
+            Statement::Default { body, pos } => {
+                let Some(switch) = self.switches.last_mut() else {
+                    return Err(Error::new(
+                        *pos,
+                        "`default` statement not in switch statement",
+                    ));
+                };
+                if let Some(previous) = switch.default.replace(*pos) {
+                    return Err(Error::new(*pos, "multiple default labels in one switch")
+                        .with_note(previous, "previous default is here"));
+                }
+                self.analyze_statement(body, function)
+            }
+            Statement::Break { pos } if self.loops == 0 && self.switches.is_empty() => Err(
+                Error::new(*pos, "`break` statement not in loop or switch statement"),
+            ),
+            Statement::Break { .. } => Ok(()),
+            // `check_labels` resolves the target once the whole body is seen.
+            Statement::Goto { .. } | Statement::Empty { .. } => Ok(()),
+            Statement::Asm {
+                template,
+                outputs,
+                inputs,
+                pos,
+                ..
+            } => {
+                let count = outputs.len() + inputs.len();
+                if count > MAX_ASM_OPERANDS {
+                    return Err(Error::new(
+                        *pos,
+                        format!("asm statement has more than {MAX_ASM_OPERANDS} operands"),
+                    ));
+                }
+                if outputs.len() > 1 {
+                    return Err(Error::new(
+                        outputs[1].pos,
+                        "asm statement has more than one output operand",
+                    ));
+                }
+                if let Some(n) = asm_operand_numbers(template).find(|&n| n >= count) {
+                    return Err(Error::new(
+                        *pos,
+                        format!("asm template refers to operand %{n}, which does not exist"),
+                    ));
+                }
+                for output in outputs {
+                    self.check_asm_operand(output, "=r")?;
+                    let array = matches!(self.analyze_object(&output.expr)?, Type::Array(..));
+                    if !self.is_lvalue(&output.expr) || array {
+                        return Err(Error::new(
+                            output.pos,
+                            "asm output operand is not assignable",
+                        ));
+                    }
+                }
+                for input in inputs {
+                    self.check_asm_operand(input, "r")?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Operands live in registers, so `constraint` is the only one accepted,
+    /// and the operand must be an `int` or a pointer to fit in one.
+    fn check_asm_operand(&mut self, operand: &AsmOperand, constraint: &str) -> Result<()> {
+        if operand.constraint != constraint {
+            return Err(Error::new(
+                operand.pos,
+                format!(
+                    "unsupported asm constraint \"{}\", expected \"{constraint}\"",
+                    operand.constraint
+                ),
+            ));
+        }
+        match self.analyze_expression(&operand.expr)? {
+            Type::Int | Type::Pointer(_) => Ok(()),
+            actual => Err(type_error(&operand.expr, Type::Int, actual)),
+        }
+    }
+
+    fn analyze_for(&mut self, statement: &Statement, function: &Function) -> Result<()> {
+        let Statement::For {
+            init,
+            cond,
+            step,
+            body,
+            ..
+        } = statement
+        else {
+            unreachable!("only called on `for` statements");
+        };
+        if let Some(init) = init {
+            self.analyze_statement(init, function)?;
+        }
+        if let Some(cond) = cond {
+            self.check_scalar(cond)?;
+        }
+        if let Some(step) = step {
+            self.analyze_expression(step)?;
+        }
+        self.analyze_loop_body(body, function)
+    }
+
+    /// Analyzes the body of a loop, which a `break` may leave.
+    fn analyze_loop_body(&mut self, body: &Statement, function: &Function) -> Result<()> {
+        self.loops += 1;
+        let analyzed = self.analyze_statement(body, function);
+        self.loops -= 1;
+        analyzed
+    }
+
+    /// Conditions are compared against zero, and arguments to functions
+    /// without a prototype are passed in registers, so both must be
+    /// scalars.
+    fn check_scalar(&mut self, expr: &Expression) -> Result<()> {
+        match self.analyze_expression(expr)? {
+            ty if ty.is_integer() => Ok(()),
+            Type::Pointer(_) => Ok(()),
+            actual => Err(type_error(expr, Type::Int, actual)),
+        }
+    }
+
+    /// Checks that `expr` converts to `ty` as if by assignment: any integer
+    /// to any integer type, `0` to any pointer, and pointers to and from
+    /// `void *`.
+    fn expect_assignable(&mut self, expr: &Expression, ty: &Type) -> Result<()> {
+        let actual = self.analyze_expression(expr)?;
+        let assignable = match (ty, &actual) {
+            _ if actual == *ty => true,
+            _ if ty.is_integer() && actual.is_integer() => true,
+            (Type::Pointer(_), Type::Int) => is_null_pointer_constant(expr),
+            (Type::Pointer(to), Type::Pointer(from)) => **to == Type::Void || **from == Type::Void,
+            _ => false,
+        };
+        if assignable {
+            Ok(())
+        } else {
+            Err(type_error(expr, ty.clone(), actual))
+        }
+    }
+
+    /// Checks that `init` can initialize an object of type `ty`: an array
+    /// takes a list of at most as many initializers as it has elements, and
+    /// anything else an expression assignable to it.
+    fn check_initializer(&mut self, init: &Initializer, ty: &Type) -> Result<()> {
+        match (init, ty) {
+            (Initializer::List { elements, pos }, Type::Array(element, len)) => {
+                if elements.len() > *len as usize {
+                    return Err(Error::new(*pos, "excess elements in array initializer"));
+                }
+                elements
+                    .iter()
+                    .try_for_each(|init| self.check_initializer(init, element))
+            }
+            (Initializer::List { pos, .. }, _) => Err(Error::new(
+                *pos,
+                format!("cannot initialize `{ty}` with an initializer list"),
+            )),
+            (Initializer::Expr(expr), Type::Array(..)) => {
+                let actual = self.analyze_expression(expr)?;
+                Err(type_error(expr, ty.clone(), actual))
+            }
+            (Initializer::Expr(expr), _) => self.expect_assignable(expr, ty),
+        }
+    }
+
+    /// Arithmetic and bitwise operands must be integers.
+    fn expect_integer(&mut self, expr: &Expression) -> Result<()> {
+        match self.analyze_expression(expr)? {
+            ty if ty.is_integer() => Ok(()),
+            actual => Err(type_error(expr, Type::Int, actual)),
+        }
+    }
+
+    /// Integers combine with any operator, in the type the usual arithmetic
+    /// conversions give them; a shift has the type of its promoted left
+    /// operand. Pointers can be compared with
+    /// pointers of the same type, `==` and `!=` also take `0` and `void *`,
+    /// an integer can be added to or subtracted from a pointer to an
+    /// object, and two such pointers subtracted to count the elements
+    /// between them, as a `long`. The left operand, of type `left`, has
+    /// been analyzed already.
+    fn analyze_binary(
+        &mut self,
+        op: BinaryOp,
+        operands: &[Expression; 2],
+        left: Type,
+        pos: Position,
+    ) -> Result<Type> {
+        let [lhs, rhs] = operands;
+        let right = self.analyze_expression(rhs)?;
+        let invalid = || {
+            Error::new(
+                pos,
+                format!(
+                    "invalid operands to binary `{}` (`{left}` and `{right}`)",
+                    op.as_str()
+                ),
+            )
+        };
+        let integer = Type::is_integer;
+        let object_pointer =
+            |ty: &Type| matches!(ty, Type::Pointer(pointee) if **pointee != Type::Void);
+        match op {
+            BinaryOp::LogicalAnd | BinaryOp::LogicalOr => {
+                for (operand, ty) in [(lhs, &left), (rhs, &right)] {
+                    if *ty == Type::Void {
+                        return Err(type_error(operand, Type::Int, Type::Void));
+                    }
+                }
+                Ok(Type::Int)
+            }
+            BinaryOp::Lt
+            | BinaryOp::Le
+            | BinaryOp::Gt
+            | BinaryOp::Ge
+            | BinaryOp::Eq
+            | BinaryOp::Ne
+                if integer(&left) && integer(&right) =>
+            {
+                Ok(Type::Int)
+            }
+            BinaryOp::Shl | BinaryOp::Shr if integer(&left) && integer(&right) => {
+                Ok(left.promote())
+            }
+            _ if integer(&left) && integer(&right) => Ok(Type::common(&left, &right, self.model)),
+            BinaryOp::Add if object_pointer(&left) && integer(&right) => Ok(left),
+            BinaryOp::Add if integer(&left) && object_pointer(&right) => Ok(right),
+            BinaryOp::Sub if object_pointer(&left) && integer(&right) => Ok(left),
+            // `ptrdiff_t`, which is `long`, as wide as a pointer.
+            BinaryOp::Sub if object_pointer(&left) && left == right => Ok(Type::Long),
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge
+                if matches!(left, Type::Pointer(_)) && left == right =>
+            {
+                Ok(Type::Int)
+            }
+            BinaryOp::Eq | BinaryOp::Ne => match (&left, &right) {
+                (Type::Pointer(a), Type::Pointer(b))
+                    if a == b || **a == Type::Void || **b == Type::Void =>
+                {
+                    Ok(Type::Int)
+                }
+                (Type::Pointer(_), Type::Int) if is_null_pointer_constant(rhs) => Ok(Type::Int),
+                (Type::Int, Type::Pointer(_)) if is_null_pointer_constant(lhs) => Ok(Type::Int),
+                _ => Err(invalid()),
+            },
+            _ if matches!(left, Type::Pointer(_)) || matches!(right, Type::Pointer(_)) => {
+                Err(invalid())
+            }
+            // One side is `void`.
+            _ if integer(&left) => Err(type_error(rhs, Type::Int, right)),
+            _ => Err(type_error(lhs, Type::Int, left)),
+        }
+    }
+
+    fn expect_type(&mut self, expr: &Expression, expected: Type) -> Result<()> {
+        let actual = self.analyze_expression(expr)?;
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(type_error(expr, expected, actual))
+        }
+    }
+
+    /// The type of `expr` as an rvalue, an array having decayed to a
+    /// pointer to its first element.
+    fn analyze_expression(&mut self, expr: &Expression) -> Result<Type> {
+        Ok(self.analyze_object(expr)?.decay())
+    }
+
+    /// The type of `expr` before any decay, as `&` and assignment see it.
+    fn analyze_object(&mut self, expr: &Expression) -> Result<Type> {
+        match expr {
+            Expression::IntLit { value, suffix, .. } => Ok(Type::of_int_literal(*value, *suffix)),
+            Expression::CharLit { .. } => Ok(Type::Int),
+            // The array of its characters and the terminating NUL.
+            Expression::StringLit { value, .. } => {
+                Ok(Type::Array(Box::new(Type::Char), value.len() as u32 + 1))
+            }
+            Expression::SizeofExpr { operand, pos } => {
+                let ty = self.analyze_object(operand)?;
+                check_sizeof(&ty, *pos, self.model)
+            }
+            Expression::SizeofType { ty, pos } => check_sizeof(ty, *pos, self.model),
+            Expression::Unary {
+                op: UnaryOp::Not,
+                operand,
+                ty,
+                ..
+            } => {
+                self.check_scalar(operand)?;
+                ty.set(Type::Int);
+                Ok(Type::Int)
+            }
+            Expression::Unary { operand, ty, .. } => match self.analyze_expression(operand)? {
+                operand_ty if operand_ty.is_integer() => {
+                    let result = operand_ty.promote();
+                    ty.set(result.clone());
+                    Ok(result)
+                }
+                actual => Err(type_error(operand, Type::Int, actual)),
+            },
+            Expression::Binary { .. } => {
+                let (first, links) = expr.binary_chain();
+                let mut result = self.analyze_expression(first)?;
+                for link in links {
+                    result = self.analyze_binary(link.op, link.operands, result, link.pos)?;
+                    link.ty.set(result.clone());
+                }
+                Ok(result)
+            }
+            Expression::AddressOf { operand, pos } => {
+                let ty = self.analyze_object(operand)?;
+                if !self.is_lvalue(operand) {
+                    return Err(Error::new(*pos, "cannot take the address of an rvalue"));
+                }
+                Ok(Type::pointer_to(ty))
+            }
+            Expression::Deref { operand, pos } => match self.analyze_expression(operand)? {
+                Type::Pointer(pointee) if *pointee == Type::Void => {
+                    Err(Error::new(*pos, "cannot dereference a `void *`"))
+                }
+                Type::Pointer(pointee) => Ok(*pointee),
+                actual => Err(Error::new(
+                    *pos,
+                    format!("indirection requires a pointer operand (`{actual}` invalid)"),
+                )),
+            },
+            Expression::Variable { name, pos } => match self.lookup(name) {
+                Some(Symbol::Variable { ty }) => {
+                    let ty = ty.clone();
+                    self.mark_used(name);
+                    Ok(ty)
+                }
+                Some(Symbol::Constant { .. }) => Ok(Type::Int),
+                Some(Symbol::Function { .. }) => Err(Error::new(
+                    *pos,
+                    format!("function `{name}` used as a value"),
+                )),
+                None => Err(Error::new(
+                    *pos,
+                    format!("use of undeclared identifier `{name}`"),
+                )),
+            },
+            Expression::Assign {
+                op,
+                operands,
+                pos,
+                target: span,
+            } => {
+                let [target, value] = &**operands;
+                let ty = self.analyze_object(target)?;
+                if !self.is_lvalue(target) {
+                    return Err(Error::spanning(*span, "expression is not assignable"));
+                }
+                if let Type::Array(..) = ty {
+                    return Err(Error::spanning(
+                        *span,
+                        format!("array type `{ty}` is not assignable"),
+                    ));
+                }
+                let Some(op) = op else {
+                    self.expect_assignable(value, &ty)?;
+                    return Ok(ty);
+                };
+                // The result of `target op value` is stored back, so it
+                // has to be assignable to the target: `p += 1` is fine but
+                // `i += p` is not.
+                let result = self.analyze_binary(*op, operands, ty.clone(), *pos)?;
+                if result == ty || (result.is_integer() && ty.is_integer()) {
+                    Ok(ty)
+                } else {
+                    Err(Error::new(
+                        *pos,
+                        format!("expected `{ty}` but found `{result}`"),
+                    ))
+                }
+            }
+            Expression::IncDec { op, operand, pos } => {
+                let ty = self.analyze_object(operand)?;
+                if !self.is_lvalue(operand) {
+                    return Err(Error::new(*pos, "expression is not assignable"));
+                }
+                match &ty {
+                    ty if ty.is_integer() => Ok(ty.clone()),
+                    Type::Pointer(pointee) if **pointee != Type::Void => Ok(ty),
+                    _ => {
+                        let verb = match op.binary_op() {
+                            BinaryOp::Add => "increment",
+                            _ => "decrement",
+                        };
+                        Err(Error::new(
+                            *pos,
+                            format!("cannot {verb} value of type `{ty}`"),
+                        ))
+                    }
+                }
+            }
+            Expression::FunctionCall { name, args, pos } => match self.lookup(name) {
+                // Any arguments may be passed to a function whose
+                // parameters weren't declared, after the integer
+                // promotions.
+                Some(Symbol::Function {
+                    return_type,
+                    prototyped: false,
+                    ..
+                }) => {
+                    let return_type = return_type.clone();
+                    for arg in args.iter() {
+                        self.check_scalar(arg)?;
+                    }
+                    Ok(return_type)
+                }
+                Some(Symbol::Function {
+                    return_type,
+                    params,
+                    variadic,
+                    ..
+                }) => {
+                    let (return_type, params, variadic) =
+                        (return_type.clone(), params.clone(), *variadic);
+                    let counted = if variadic {
+                        args.len() >= params.len()
+                    } else {
+                        args.len() == params.len()
+                    };
+                    if !counted {
+                        return Err(Error::new(
+                            *pos,
+                            format!(
+                                "`{name}` takes {}{} argument{}, but {} {} given",
+                                if variadic { "at least " } else { "" },
+                                params.len(),
+                                if params.len() == 1 { "" } else { "s" },
+                                args.len(),
+                                if args.len() == 1 { "was" } else { "were" }
+                            ),
+                        ));
+                    }
+                    for (arg, param) in args.iter().zip(&params) {
+                        self.expect_assignable(arg, param)?;
+                    }
+                    // The rest are passed as they are, after the integer
+                    // promotions.
+                    for arg in &args[params.len()..] {
+                        self.check_scalar(arg)?;
+                    }
+                    Ok(return_type)
+                }
+                Some(Symbol::Variable { .. } | Symbol::Constant { .. }) => Err(Error::new(
+                    *pos,
+                    format!("called object `{name}` is not a function"),
+                )),
+                // C89 declares the function implicitly, returning `int` and
+                // taking whatever it is passed.
+                None if self.standard.implicit_int() => {
+                    crate::log!(Debug, "analyzer", "implicitly declaring `{name}`");
+                    for arg in args.iter() {
+                        self.check_scalar(arg)?;
+                    }
+                    Ok(Type::Int)
+                }
+                None => Err(Error::new(
+                    *pos,
+                    format!("call to undeclared function `{name}`"),
+                )),
+            },
+            Expression::BuiltinCall { builtin, args, pos } => {
+                let (params, result) = builtin.signature();
+                if args.len() != params.len() {
+                    return Err(Error::new(
+                        *pos,
+                        format!(
+                            "`{}` takes {} argument{}, but {} {} given",
+                            builtin.name(),
+                            params.len(),
+                            if params.len() == 1 { "" } else { "s" },
+                            args.len(),
+                            if args.len() == 1 { "was" } else { "were" }
+                        ),
+                    ));
+                }
+                for (arg, param) in args.iter().zip(params) {
+                    self.expect_type(arg, param.clone())?;
+                }
+                Ok(result)
+            }
+        }
+    }
+
+    /// Notes that the variable `name` is used, so it isn't reported as
+    /// unused.
+    fn mark_used(&mut self, name: &str) {
+        let depth = self.symbols.depth_of(name);
+        if let Some(unused) = depth.and_then(|depth| self.unused.get_mut(depth)) {
+            unused.remove(name);
+        }
+    }
+
+    /// Whether `expr` names storage that can be assigned to, which an
+    /// enumeration constant doesn't.
+    fn is_lvalue(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Variable { name, .. } => {
+                matches!(self.lookup(name), Some(Symbol::Variable { .. }))
+            }
+            Expression::Deref { .. } => true,
+            Expression::IntLit { .. }
+            | Expression::CharLit { .. }
+            | Expression::StringLit { .. }
+            | Expression::Unary { .. }
+            | Expression::Binary { .. }
+            | Expression::AddressOf { .. }
+            | Expression::Assign { .. }
+            | Expression::IncDec { .. }
+            | Expression::SizeofExpr { .. }
+            | Expression::SizeofType { .. }
+            | Expression::FunctionCall { .. }
+            | Expression::BuiltinCall { .. } => false,
+        }
+    }
+}
+
+/// Checks that the labels of a function's `body` are unique and that every
+/// `goto` names one of them, warning about labels that none does. Labels
+/// have a namespace of their own, which is the whole function, so a `goto`
+/// can jump forward to a label not yet seen.
+fn check_labels(body: &[Statement], warnings: &mut Vec<Diagnostic>) -> Result<()> {
+    fn walk<'a>(
+        statement: &'a Statement,
+        labels: &mut HashMap<&'a str, Position>,
+        gotos: &mut Vec<(&'a str, Position)>,
+    ) -> Result<()> {
+        match statement {
+            Statement::Label { name, body, pos } => {
+                if let Some(previous) = labels.insert(name, *pos) {
+                    return Err(Error::new(*pos, format!("redefinition of label `{name}`"))
+                        .with_note(previous, "previous definition is here"));
+                }
+                walk(body, labels, gotos)
+            }
+            Statement::Goto { label, pos } => {
+                gotos.push((label, *pos));
+                Ok(())
+            }
+            Statement::Block { body, .. } => body
+                .iter()
+                .try_for_each(|statement| walk(statement, labels, gotos)),
+            Statement::If {
+                then, otherwise, ..
+            } => {
+                walk(then, labels, gotos)?;
+                match otherwise {
+                    Some(otherwise) => walk(otherwise, labels, gotos),
+                    None => Ok(()),
+                }
+            }
+            Statement::While { body, .. }
+            | Statement::DoWhile { body, .. }
+            | Statement::For { body, .. }
+            | Statement::Switch { body, .. }
+            | Statement::Case { body, .. }
+            | Statement::Default { body, .. } => walk(body, labels, gotos),
+            Statement::Return { .. }
+            | Statement::Break { .. }
+            | Statement::Declaration { .. }
+            | Statement::Expression { .. }
+            | Statement::Empty { .. }
+            | Statement::Enum(_)
+            | Statement::Asm { .. } => Ok(()),
+        }
+    }
+    let mut labels = HashMap::new();
+    let mut gotos = Vec::new();
+    for statement in body {
+        walk(statement, &mut labels, &mut gotos)?;
+    }
+    if let Some((label, pos)) = gotos.iter().find(|(label, _)| !labels.contains_key(label)) {
+        return Err(Error::new(
+            *pos,
+            format!("use of undeclared label `{label}`"),
+        ));
+    }
+    for (label, pos) in labels {
+        if !gotos.iter().any(|(target, _)| *target == label) {
+            warnings.push(Diagnostic::new(
+                Warning::UnusedLabel,
+                pos,
+                format!("label `{label}` defined but not used"),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Warns about the first statement in each run of `body` that follows a
+/// `return`, `goto` or `break`, which never runs unless a label, `case` or
+/// `default` comes first.
+/// Returns whether control never reaches the end of `body`.
+fn check_reachability(
+    body: &[Statement],
+    model: DataModel,
+    warnings: &mut Vec<Diagnostic>,
+) -> bool {
+    let mut reachable = true;
+    for statement in body {
+        match statement {
+            Statement::Label { .. } | Statement::Case { .. } | Statement::Default { .. } => {
+                reachable = true
+            }
+            // Declarations without code don't run.
+            Statement::Enum(_) | Statement::Declaration { init: None, .. } => {}
+            _ if !reachable => {
+                warnings.push(Diagnostic::new(
+                    Warning::UnreachableCode,
+                    statement.pos(),
+                    "code will never be executed",
+                ));
+                // Once is enough for the run.
+                reachable = true;
+                continue;
+            }
+            _ => {}
+        }
+        if !falls_through(statement, model, warnings) {
+            reachable = false;
+        }
+    }
+    !reachable
+}
+
+/// Whether a loop condition is a nonzero constant, or left out of a `for`.
+fn always_true(cond: Option<&Expression>, model: DataModel) -> bool {
+    cond.is_none_or(|cond| {
+        cond.constant_value(&|_| None, model)
+            .is_some_and(|value| value != 0)
+    })
+}
+
+/// Whether control can go on to whatever follows `statement`, checking the
+/// statements inside it on the way.
+fn falls_through(statement: &Statement, model: DataModel, warnings: &mut Vec<Diagnostic>) -> bool {
+    match statement {
+        Statement::Return { .. } | Statement::Goto { .. } | Statement::Break { .. } => false,
+        Statement::Label { body, .. }
+        | Statement::Case { body, .. }
+        | Statement::Default { body, .. } => falls_through(body, model, warnings),
+        Statement::Block { body, .. } => !check_reachability(body, model, warnings),
+        // Without an `else`, the condition may be false.
+        Statement::If {
+            then, otherwise, ..
+        } => {
+            let then = falls_through(then, model, warnings);
+            let otherwise = otherwise
+                .as_ref()
+                .is_none_or(|otherwise| falls_through(otherwise, model, warnings));
+            then || otherwise
+        }
+        // A loop may run its body no times, or leave it by jumping, which
+        // goes to a label, or by `break`. Only a loop whose condition is
+        // always true and which doesn't break has no way out otherwise.
+        Statement::While { cond, body, .. } => {
+            falls_through(body, model, warnings);
+            !always_true(Some(cond), model) || breaks(body)
+        }
+        Statement::For { cond, body, .. } => {
+            falls_through(body, model, warnings);
+            !always_true(cond.as_ref(), model) || breaks(body)
+        }
+        // The body runs at least once.
+        Statement::DoWhile { body, cond, .. } => {
+            (falls_through(body, model, warnings) && !always_true(Some(cond), model))
+                || breaks(body)
+        }
+        // Without a `default`, no case may match.
+        Statement::Switch { body, .. } => {
+            falls_through(body, model, warnings) || breaks(body) || !has_default(body)
+        }
+        Statement::Declaration { .. }
+        | Statement::Expression { .. }
+        | Statement::Empty { .. }
+        | Statement::Enum(_)
+        | Statement::Asm { .. } => true,
+    }
+}
+
+/// Whether `statement` contains a `break` that leaves it, rather than a
+/// loop or `switch` inside it.
+fn breaks(statement: &Statement) -> bool {
+    match statement {
+        Statement::Break { .. } => true,
+        Statement::Label { body, .. }
+        | Statement::Case { body, .. }
+        | Statement::Default { body, .. } => breaks(body),
+        Statement::Block { body, .. } => body.iter().any(breaks),
+        Statement::If {
+            then, otherwise, ..
+        } => breaks(then) || otherwise.as_deref().is_some_and(breaks),
+        _ => false,
+    }
+}
+
+/// Whether the body of a `switch` has a `default` of its own, rather than
+/// one of a `switch` inside it.
+fn has_default(statement: &Statement) -> bool {
+    match statement {
+        Statement::Default { .. } => true,
+        Statement::Label { body, .. } | Statement::Case { body, .. } => has_default(body),
+        Statement::Block { body, .. } => body.iter().any(has_default),
+        Statement::If {
+            then, otherwise, ..
+        } => has_default(then) || otherwise.as_deref().is_some_and(has_default),
+        Statement::While { body, .. }
+        | Statement::DoWhile { body, .. }
+        | Statement::For { body, .. } => has_default(body),
+        _ => false,
+    }
+}
+
+/// Checks that the variable `name` of type `ty`, declared at `pos`, is a
+/// complete object small enough to allocate.
+fn check_object_type(name: &str, ty: &Type, pos: Position, model: DataModel) -> Result<()> {
+    match ty.size(model) {
+        None if *ty == Type::Void => Err(Error::new(
+            pos,
+            format!("variable `{name}` has incomplete type `void`"),
+        )),
+        None => Err(Error::new(
+            pos,
+            format!("array `{name}` has incomplete element type `void`"),
+        )),
+        Some(size) if size > MAX_OBJECT_SIZE => {
+            Err(Error::new(pos, format!("array `{name}` is too large")))
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+/// `sizeof` gives an `int`, so it applies to types whose size is known and
+/// fits in one.
+fn check_sizeof(ty: &Type, pos: Position, model: DataModel) -> Result<Type> {
+    match ty.size(model) {
+        None => Err(Error::new(
+            pos,
+            format!("invalid application of `sizeof` to incomplete type `{ty}`"),
+        )),
+        Some(size) if size > i32::MAX as u64 => Err(Error::new(
+            pos,
+            format!("size of `{ty}` does not fit in `int`"),
+        )),
+        Some(_) => Ok(Type::Int),
+    }
+}
+
+/// Whether `expr` is the constant `0`, which converts to a null pointer.
+fn is_null_pointer_constant(expr: &Expression) -> bool {
+    matches!(expr, Expression::IntLit { value: 0, .. })
+}
+
+/// The largest array a variable may hold, in bytes, so that frame offsets
+/// fit in 32 bits.
+const MAX_OBJECT_SIZE: u64 = i32::MAX as u64;
+
+/// A declaration at file scope.
+#[derive(Clone, Copy)]
+enum Item<'a> {
+    Function(&'a Function),
+    Enum(&'a Enum),
+    Global(&'a Global),
+}
+
+/// The type of `function`, as a symbol.
+fn signature(function: &Function) -> Symbol {
+    Symbol::Function {
+        return_type: function.return_type.clone(),
+        params: function
+            .params
+            .iter()
+            .map(|param| param.ty.clone())
+            .collect(),
+        variadic: function.variadic,
+        prototyped: function.prototyped,
+    }
+}
+
+/// Whether two declarations of a function agree. If one leaves out the
+/// parameters, the other's must be ones the integer promotions leave
+/// alone, without `...`, since that is how the arguments are passed.
+fn agree(previous: &Symbol, symbol: &Symbol) -> bool {
+    match (previous, symbol) {
+        (
+            Symbol::Function {
+                return_type,
+                prototyped: false,
+                ..
+            },
+            Symbol::Function {
+                return_type: other,
+                params,
+                variadic,
+                ..
+            },
+        )
+        | (
+            Symbol::Function {
+                return_type,
+                params,
+                variadic,
+                ..
+            },
+            Symbol::Function {
+                return_type: other,
+                prototyped: false,
+                ..
+            },
+        ) => {
+            return_type == other
+                && !variadic
+                && params.iter().all(|param| param.clone().promote() == *param)
+        }
+        _ => previous == symbol,
+    }
+}
+
+/// The declarations of `program` in the order they appear, so each enum
+/// comes into scope before the functions after it.
+fn in_source_order(program: &Program) -> Vec<Item<'_>> {
+    let mut items: Vec<Item> = program
+        .functions
+        .iter()
+        .map(Item::Function)
+        .chain(program.enums.iter().map(Item::Enum))
+        .chain(program.globals.iter().map(Item::Global))
+        .collect();
+    items.sort_by_key(|item| match item {
+        Item::Function(function) => function.pos,
+        Item::Enum(decl) => decl.pos,
+        Item::Global(global) => global.pos,
+    });
+    items
+}
+
+/// Operands an `asm` statement may have: one per register the x86-64 backend
+/// passes them in.
+const MAX_ASM_OPERANDS: usize = 6;
+
+/// The `N` of each `%N` in an asm template, skipping escaped `%%`.
+fn asm_operand_numbers(template: &str) -> impl Iterator<Item = usize> + '_ {
+    let mut chars = template.chars();
+    std::iter::from_fn(move || loop {
+        if chars.next()? != '%' {
+            continue;
+        }
+        if let Some(n) = chars.next()?.to_digit(10) {
+            return Some(n as usize);
+        }
+    })
+}
+
+fn type_error(expr: &Expression, expected: Type, actual: Type) -> Error {
+    Error::new(
+        expr.pos(),
+        format!("expected `{expected}` but found `{actual}`"),
+    )
+}
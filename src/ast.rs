@@ -0,0 +1,1020 @@
+//! Abstract syntax tree produced by the parser.
+
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::lexer::{IntSuffix, Operator, Position, Span};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Program {
+    pub functions: Vec<Function>,
+    /// The enums declared at file scope.
+    pub enums: Vec<Enum>,
+    pub globals: Vec<Global>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    pub return_type: Type,
+    pub params: Box<[Param]>,
+    /// Whether the parameters end with `, ...`, so that any number of
+    /// further arguments may follow them.
+    pub variadic: bool,
+    /// Whether the parameters are declared, which `()` doesn't do before
+    /// C23: such a function may be called with any arguments, and
+    /// declared again with parameters.
+    pub prototyped: bool,
+    /// `None` for a prototype, which declares the function without
+    /// defining it.
+    pub body: Option<Box<[Statement]>>,
+    pub pos: Position,
+}
+
+impl Function {
+    /// The parameter list as a prototype writes it: `(void)` when there are
+    /// none.
+    pub fn param_list(&self) -> String {
+        if self.params.is_empty() {
+            return "(void)".to_string();
+        }
+        let mut params: Vec<String> = self
+            .params
+            .iter()
+            .map(|param| match &param.name {
+                // `int *p`, not `int * p`.
+                Some(name) if matches!(param.ty, Type::Pointer(_)) => {
+                    format!("{}{name}", param.ty)
+                }
+                Some(name) => format!("{} {name}", param.ty),
+                None => param.ty.to_string(),
+            })
+            .collect();
+        if self.variadic {
+            params.push("...".to_string());
+        }
+        format!("({})", params.join(", "))
+    }
+}
+
+/// `enum [tag] { A, B = value, ... };`, declaring integer constants. Each
+/// enumerator without a value is one more than the one before it, and the
+/// first is 0. Variables of an `enum` type are `int`s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Enum {
+    pub tag: Option<String>,
+    pub enumerators: Box<[Enumerator]>,
+    pub pos: Position,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Enumerator {
+    pub name: String,
+    /// An integer constant expression.
+    pub value: Option<Expression>,
+    pub pos: Position,
+}
+
+/// `[_Thread_local] ty name [= init];` at file scope, declaring a variable
+/// every function after it can use. The initializer, if any, must be an
+/// integer constant; without one the variable starts out zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Global {
+    pub name: String,
+    pub ty: Type,
+    pub init: Option<Initializer>,
+    /// Whether each thread has its own instance.
+    pub thread_local: bool,
+    /// Whether this only declares a variable defined elsewhere, perhaps in
+    /// another translation unit: `extern` without an initializer.
+    pub is_extern: bool,
+    pub pos: Position,
+}
+
+/// A parameter of a function. Only prototypes may leave it unnamed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Param {
+    pub name: Option<String>,
+    pub ty: Type,
+    pub pos: Position,
+}
+
+/// The integer types are sized by the target's [`DataModel`]: `char` is
+/// one byte, `short` two, `int` four and `long` four or eight, as wide as a
+/// pointer. Plain `char` is signed, as on x86-64 and Apple's AArch64;
+/// `signed char` is the same type, and `long long` the same as `long`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    Int,
+    Void,
+    Char,
+    UChar,
+    Short,
+    UShort,
+    UInt,
+    Long,
+    ULong,
+    Pointer(Box<Type>),
+    /// A fixed number of elements, laid out one after another.
+    Array(Box<Type>, u32),
+}
+
+/// How wide `long` and pointers are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DataModel {
+    /// Eight bytes, as on x86-64 and AArch64.
+    #[default]
+    Lp64,
+    /// Four bytes, as on `wasm32`.
+    Ilp32,
+}
+
+impl Type {
+    pub fn pointer_to(pointee: Type) -> Self {
+        Type::Pointer(Box::new(pointee))
+    }
+
+    /// The type of an integer literal: the first of `int` and `long`, or
+    /// with `u` of `unsigned int` and `unsigned long`, that holds `value`,
+    /// and at least `long` with `l`. The lexer rejects literals that fit
+    /// none of these.
+    pub fn of_int_literal(value: u64, suffix: IntSuffix) -> Type {
+        match suffix {
+            IntSuffix::Plain if i32::try_from(value).is_ok() => Type::Int,
+            IntSuffix::Unsigned if u32::try_from(value).is_ok() => Type::UInt,
+            IntSuffix::Plain | IntSuffix::Long => Type::Long,
+            IntSuffix::Unsigned | IntSuffix::UnsignedLong => Type::ULong,
+        }
+    }
+
+    /// The size of an object of this type in bytes under `model`, or
+    /// `None` for `void`.
+    pub fn size(&self, model: DataModel) -> Option<u64> {
+        match self {
+            Type::Char | Type::UChar => Some(1),
+            Type::Short | Type::UShort => Some(2),
+            Type::Int | Type::UInt => Some(4),
+            Type::Long | Type::ULong | Type::Pointer(_) => match model {
+                DataModel::Lp64 => Some(8),
+                DataModel::Ilp32 => Some(4),
+            },
+            Type::Void => None,
+            Type::Array(element, len) => Some(element.size(model)? * u64::from(*len)),
+        }
+    }
+
+    /// The alignment of an object of this type in bytes under `model`, or
+    /// `None` for `void`. Every scalar is aligned to its size.
+    pub fn align(&self, model: DataModel) -> Option<u64> {
+        match self {
+            Type::Array(element, _) => element.align(model),
+            ty => ty.size(model),
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            Type::Char
+                | Type::UChar
+                | Type::Short
+                | Type::UShort
+                | Type::Int
+                | Type::UInt
+                | Type::Long
+                | Type::ULong
+        )
+    }
+
+    pub fn is_unsigned(&self) -> bool {
+        matches!(self, Type::UChar | Type::UShort | Type::UInt | Type::ULong)
+    }
+
+    /// The integer promotions: types narrower than `int` compute as `int`,
+    /// which holds all their values.
+    pub fn promote(self) -> Type {
+        match self {
+            Type::Char | Type::UChar | Type::Short | Type::UShort => Type::Int,
+            ty => ty,
+        }
+    }
+
+    /// The usual arithmetic conversions: the type two integer operands are
+    /// converted to before an operator combines them. After promotion, the
+    /// wider type wins; at equal widths, `long` outranks `int` and unsigned
+    /// wins. Under LP64 `long` holds every `unsigned int`, so the two make
+    /// a `long`; under ILP32 they make an `unsigned long`.
+    pub fn common(left: &Type, right: &Type, model: DataModel) -> Type {
+        let (left, right) = (left.clone().promote(), right.clone().promote());
+        match (left.size(model), right.size(model)) {
+            (l, r) if l > r => left,
+            (l, r) if l < r => right,
+            _ => {
+                let long = |ty: &Type| matches!(ty, Type::Long | Type::ULong);
+                match (
+                    long(&left) || long(&right),
+                    left.is_unsigned() || right.is_unsigned(),
+                ) {
+                    (true, true) => Type::ULong,
+                    (true, false) => Type::Long,
+                    (false, true) => Type::UInt,
+                    (false, false) => Type::Int,
+                }
+            }
+        }
+    }
+
+    /// The type an expression of this type has as an rvalue: an array
+    /// decays to a pointer to its first element.
+    pub fn decay(self) -> Type {
+        match self {
+            Type::Array(element, _) => Type::Pointer(element),
+            ty => ty,
+        }
+    }
+
+    /// A declaration of `name` with this type: `int *p`, or `int a[3]`.
+    pub fn declare(&self, name: &str) -> String {
+        struct Declaration<'a>(&'a Type, &'a str);
+
+        impl fmt::Display for Declaration<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.write_declarator(f, self.1)
+            }
+        }
+
+        Declaration(self, name).to_string()
+    }
+
+    /// Writes the type around `declarator`, the part of a declaration
+    /// that names it: `*` binds looser than `[]`, so a pointer to an array
+    /// is `int (*)[3]`.
+    fn write_declarator(&self, f: &mut fmt::Formatter<'_>, declarator: &str) -> fmt::Result {
+        match self {
+            Type::Pointer(pointee) if matches!(**pointee, Type::Array(..)) => {
+                pointee.write_declarator(f, &format!("(*{declarator})"))
+            }
+            Type::Pointer(pointee) => pointee.write_declarator(f, &format!("*{declarator}")),
+            Type::Array(element, len) => {
+                element.write_declarator(f, &format!("{declarator}[{len}]"))
+            }
+            _ => {
+                let name = match self {
+                    Type::Int => "int",
+                    Type::Void => "void",
+                    Type::Char => "char",
+                    Type::UChar => "unsigned char",
+                    Type::Short => "short",
+                    Type::UShort => "unsigned short",
+                    Type::UInt => "unsigned int",
+                    Type::Long => "long",
+                    Type::ULong => "unsigned long",
+                    Type::Pointer(_) | Type::Array(..) => unreachable!("matched above"),
+                };
+                if declarator.is_empty() || declarator.starts_with('[') {
+                    write!(f, "{name}{declarator}")
+                } else {
+                    write!(f, "{name} {declarator}")
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_declarator(f, "")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    Return {
+        value: Option<Expression>,
+        pos: Position,
+    },
+    /// `ty name [= init];`, declaring a local variable.
+    Declaration {
+        name: String,
+        ty: Type,
+        init: Option<Initializer>,
+        pos: Position,
+    },
+    /// An expression evaluated for its effects, such as an assignment.
+    Expression { expr: Expression, pos: Position },
+    /// `;` alone, doing nothing, as the body of a loop that does its work
+    /// in its clauses.
+    Empty { pos: Position },
+    /// An enum declared in a block.
+    Enum(Enum),
+    /// `{ ... }`.
+    Block {
+        body: Box<[Statement]>,
+        pos: Position,
+    },
+    /// `if (cond) then else otherwise`, where an `else` belongs to the
+    /// nearest `if` without one.
+    If {
+        cond: Expression,
+        then: Box<Statement>,
+        otherwise: Option<Box<Statement>>,
+        pos: Position,
+    },
+    /// `while (cond) body`, testing `cond` before each iteration.
+    While {
+        cond: Expression,
+        body: Box<Statement>,
+        pos: Position,
+    },
+    /// `do body while (cond);`, testing `cond` after each iteration.
+    DoWhile {
+        body: Box<Statement>,
+        cond: Expression,
+        pos: Position,
+    },
+    /// `for (init; cond; step) body`, where any clause may be left out.
+    /// `init` is a declaration or an expression statement, whose scope is
+    /// the loop.
+    For {
+        init: Option<Box<Statement>>,
+        cond: Option<Expression>,
+        step: Option<Expression>,
+        body: Box<Statement>,
+        pos: Position,
+    },
+    /// `name: body`, a target for `goto`.
+    Label {
+        name: String,
+        body: Box<Statement>,
+        pos: Position,
+    },
+    /// `goto label;`, jumping to a label in the same function.
+    Goto { label: String, pos: Position },
+    /// `switch (cond) body`, jumping to the `case` in `body` whose value
+    /// is `cond`'s, or else to its `default`, if it has one, or past it.
+    Switch {
+        cond: Expression,
+        body: Box<Statement>,
+        pos: Position,
+    },
+    /// `case value: body`, a target of the innermost `switch`. `value` is
+    /// an integer constant expression.
+    Case {
+        value: Expression,
+        body: Box<Statement>,
+        pos: Position,
+    },
+    /// `default: body`, where the innermost `switch` goes when no `case`
+    /// matches.
+    Default { body: Box<Statement>, pos: Position },
+    /// `break;`, leaving the innermost loop or `switch`.
+    Break { pos: Position },
+    /// An extended `asm` statement. Operands are numbered from `%0` across
+    /// the outputs and then the inputs.
+    Asm {
+        template: String,
+        outputs: Box<[AsmOperand]>,
+        inputs: Box<[AsmOperand]>,
+        clobbers: Box<[String]>,
+        pos: Position,
+    },
+}
+
+impl Statement {
+    pub fn pos(&self) -> Position {
+        match self {
+            Statement::Return { pos, .. }
+            | Statement::Declaration { pos, .. }
+            | Statement::Expression { pos, .. }
+            | Statement::Empty { pos }
+            | Statement::Block { pos, .. }
+            | Statement::If { pos, .. }
+            | Statement::While { pos, .. }
+            | Statement::DoWhile { pos, .. }
+            | Statement::For { pos, .. }
+            | Statement::Label { pos, .. }
+            | Statement::Goto { pos, .. }
+            | Statement::Switch { pos, .. }
+            | Statement::Case { pos, .. }
+            | Statement::Default { pos, .. }
+            | Statement::Break { pos }
+            | Statement::Asm { pos, .. } => *pos,
+            Statement::Enum(decl) => decl.pos,
+        }
+    }
+}
+
+/// The value a declaration gives its variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Initializer {
+    Expr(Expression),
+    /// `{a, b, ...}`, initializing an array's elements in order. Any left
+    /// over are zero.
+    List {
+        elements: Box<[Initializer]>,
+        pos: Position,
+    },
+}
+
+/// An `asm` operand: `"constraint" (expr)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmOperand {
+    pub constraint: String,
+    pub expr: Expression,
+    pub pos: Position,
+}
+
+/// The type the analyzer found an expression to have, after the integer
+/// promotions and the usual arithmetic conversions, for the stages after
+/// it to use. Unset until the expression is analyzed.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Resolved(OnceLock<Type>);
+
+impl Resolved {
+    pub fn get(&self) -> Option<&Type> {
+        self.0.get()
+    }
+
+    /// Records `ty`, unless a type was recorded already.
+    pub fn set(&self, ty: Type) {
+        self.0.get_or_init(|| ty);
+    }
+}
+
+impl fmt::Debug for Resolved {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.get(), f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expression {
+    /// An integer literal's value and suffix; see [`Type::of_int_literal`].
+    IntLit {
+        value: u64,
+        suffix: IntSuffix,
+        pos: Position,
+    },
+    /// A character constant, which has type `int`.
+    CharLit {
+        value: u8,
+        pos: Position,
+    },
+    /// A string literal, its adjacent pieces joined, without the
+    /// terminating NUL.
+    StringLit {
+        value: Vec<u8>,
+        pos: Position,
+    },
+    Variable {
+        name: String,
+        pos: Position,
+    },
+    /// `pos` is the operator's.
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expression>,
+        ty: Resolved,
+        pos: Position,
+    },
+    /// `++x`, `--x`, `x++` or `x--`, stepping an integer or pointer lvalue
+    /// by one.
+    IncDec {
+        op: IncDecOp,
+        operand: Box<Expression>,
+        pos: Position,
+    },
+    /// Both operands share one allocation, left then right. `pos` is the
+    /// operator's, or the `[` of a subscript.
+    Binary {
+        op: BinaryOp,
+        operands: Box<[Expression; 2]>,
+        ty: Resolved,
+        pos: Position,
+    },
+    /// `&operand`, the address of an lvalue.
+    AddressOf {
+        operand: Box<Expression>,
+        pos: Position,
+    },
+    /// `*operand`, the object a pointer points to.
+    Deref {
+        operand: Box<Expression>,
+        pos: Position,
+    },
+    /// `target = value`, which has the value stored, or with `op` the
+    /// compound `target op= value`, which evaluates `target` only once.
+    /// The target comes first, and `target` is where it is in the source.
+    Assign {
+        op: Option<BinaryOp>,
+        operands: Box<[Expression; 2]>,
+        pos: Position,
+        target: Span,
+    },
+    FunctionCall {
+        name: String,
+        args: Box<[Expression]>,
+        pos: Position,
+    },
+    /// `sizeof operand`, the size of the operand's type in bytes. The
+    /// operand isn't evaluated.
+    SizeofExpr {
+        operand: Box<Expression>,
+        pos: Position,
+    },
+    /// `sizeof(type)`.
+    SizeofType {
+        ty: Type,
+        pos: Position,
+    },
+    /// A call to one of the compiler's builtins, which need no declaration.
+    BuiltinCall {
+        builtin: Builtin,
+        args: Box<[Expression]>,
+        pos: Position,
+    },
+}
+
+impl Expression {
+    /// Where diagnostics about the expression point: its first token, or
+    /// the operator that makes it.
+    pub fn pos(&self) -> Position {
+        match self {
+            Expression::IntLit { pos, .. }
+            | Expression::CharLit { pos, .. }
+            | Expression::StringLit { pos, .. }
+            | Expression::Variable { pos, .. }
+            | Expression::Unary { pos, .. }
+            | Expression::IncDec { pos, .. }
+            | Expression::Binary { pos, .. }
+            | Expression::AddressOf { pos, .. }
+            | Expression::Deref { pos, .. }
+            | Expression::Assign { pos, .. }
+            | Expression::FunctionCall { pos, .. }
+            | Expression::SizeofExpr { pos, .. }
+            | Expression::SizeofType { pos, .. }
+            | Expression::BuiltinCall { pos, .. } => *pos,
+        }
+    }
+
+    /// The value of `self` if it is an integer constant expression: one
+    /// built from integer literals, enumeration constants, `sizeof` of a
+    /// type and operators on integers, which doesn't divide by zero or
+    /// shift out of range.
+    /// `constant` gives the value of each enumeration constant in scope,
+    /// and `model` the sizes `sizeof` gives.
+    pub fn constant_value(
+        &self,
+        constant: &impl Fn(&str) -> Option<i64>,
+        model: DataModel,
+    ) -> Option<i64> {
+        match self {
+            // An `unsigned long` above `LONG_MAX` wraps, as converting it would.
+            Expression::IntLit { value, .. } => Some(*value as i64),
+            Expression::CharLit { value, .. } => Some(i64::from(*value as i8)),
+            Expression::Variable { name, .. } => constant(name),
+            Expression::SizeofType { ty, .. } => ty.size(model).map(|size| size as i64),
+            Expression::Unary { op, operand, .. } => {
+                let value = operand.constant_value(constant, model)?;
+                Some(match op {
+                    UnaryOp::Neg => value.checked_neg()?,
+                    UnaryOp::Not => i64::from(value == 0),
+                    UnaryOp::Complement => !value,
+                })
+            }
+            Expression::Binary { .. } => {
+                let (first, links) = self.binary_chain();
+                let mut value = first.constant_value(constant, model)?;
+                for link in links {
+                    value = constant_binary(link.op, value, &link.operands[1], constant, model)?;
+                }
+                Some(value)
+            }
+            Expression::StringLit { .. }
+            | Expression::AddressOf { .. }
+            | Expression::Deref { .. }
+            | Expression::Assign { .. }
+            | Expression::IncDec { .. }
+            | Expression::SizeofExpr { .. }
+            | Expression::FunctionCall { .. }
+            | Expression::BuiltinCall { .. } => None,
+        }
+    }
+
+    /// The value of `self` if it may initialize an object with static
+    /// storage: an integer constant expression, or an address constant.
+    /// That is a string literal or the address of an object with static
+    /// storage, offset by an integer constant. `object` gives the type of
+    /// each object with static storage in scope.
+    pub fn static_value(
+        &self,
+        object: &impl Fn(&str) -> Option<Type>,
+        constant: &impl Fn(&str) -> Option<i64>,
+        model: DataModel,
+    ) -> Option<StaticValue<'_>> {
+        if let Some(value) = self.constant_value(constant, model) {
+            return Some(StaticValue::Int(value));
+        }
+        match self {
+            Expression::StringLit { value, .. } => {
+                Some(StaticValue::Address(Base::String(value), 0))
+            }
+            // An array decays to the address of its first element.
+            Expression::Variable { name, .. } => match object(name)? {
+                Type::Array(..) => Some(StaticValue::Address(Base::Object(name), 0)),
+                _ => None,
+            },
+            Expression::AddressOf { operand, .. } => {
+                operand.static_address(object, constant, model)
+            }
+            Expression::Binary { .. } => {
+                let (first, links) = self.binary_chain();
+                let mut value = first.static_value(object, constant, model)?;
+                for link in links {
+                    let rhs = &link.operands[1];
+                    if let StaticValue::Int(lhs) = value {
+                        if let Some(folded) = constant_binary(link.op, lhs, rhs, constant, model) {
+                            value = StaticValue::Int(folded);
+                            continue;
+                        }
+                    }
+                    let rhs = rhs.static_value(object, constant, model)?;
+                    // What is left is adding to or subtracting from an
+                    // address, in elements of the type it points to.
+                    let Some(Type::Pointer(pointee)) = link.ty.get() else {
+                        return None;
+                    };
+                    let scale = pointee.size(model)? as i64;
+                    value = match (link.op, value, rhs) {
+                        (
+                            BinaryOp::Add,
+                            StaticValue::Address(base, offset),
+                            StaticValue::Int(n),
+                        )
+                        | (
+                            BinaryOp::Add,
+                            StaticValue::Int(n),
+                            StaticValue::Address(base, offset),
+                        ) => StaticValue::Address(base, offset.checked_add(n.checked_mul(scale)?)?),
+                        (
+                            BinaryOp::Sub,
+                            StaticValue::Address(base, offset),
+                            StaticValue::Int(n),
+                        ) => StaticValue::Address(base, offset.checked_sub(n.checked_mul(scale)?)?),
+                        _ => return None,
+                    };
+                }
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    /// The address of the object `self` designates, if it is an address
+    /// constant; see [`static_value`](Self::static_value).
+    fn static_address(
+        &self,
+        object: &impl Fn(&str) -> Option<Type>,
+        constant: &impl Fn(&str) -> Option<i64>,
+        model: DataModel,
+    ) -> Option<StaticValue<'_>> {
+        match self {
+            Expression::Variable { name, .. } => {
+                object(name)?;
+                Some(StaticValue::Address(Base::Object(name), 0))
+            }
+            // `&*p` and `&a[i]` are `p` and `a + i`.
+            Expression::Deref { operand, .. } => {
+                match operand.static_value(object, constant, model)? {
+                    StaticValue::Int(_) => None,
+                    address => Some(address),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// The leftmost operand of the chain of binary operators `self` ends,
+    /// and each operator in it from the innermost out: `a`, then the `+`
+    /// and the `-` of `a - b + c`. Stages walk chains this way rather than
+    /// recursing down their left sides, which long ones would overflow the
+    /// stack doing, so that only right operands nest.
+    pub fn binary_chain(&self) -> (&Expression, Vec<Link<'_>>) {
+        self.binary_chain_of(|_| true)
+    }
+
+    /// Like [`binary_chain`](Self::binary_chain), but the chain only goes
+    /// on through the operators `joins` accepts.
+    pub fn binary_chain_of(
+        &self,
+        joins: impl Fn(BinaryOp) -> bool,
+    ) -> (&Expression, Vec<Link<'_>>) {
+        let mut links = Vec::new();
+        let mut expr = self;
+        loop {
+            match expr {
+                Expression::Binary {
+                    op,
+                    operands,
+                    ty,
+                    pos,
+                } if joins(*op) => {
+                    links.push(Link {
+                        op: *op,
+                        operands,
+                        ty,
+                        pos: *pos,
+                    });
+                    expr = &operands[0];
+                }
+                _ => break,
+            }
+        }
+        links.reverse();
+        (expr, links)
+    }
+}
+
+/// Chains of binary operators are taken apart a link at a time, rather than
+/// by the recursion dropping each field would do.
+impl Drop for Expression {
+    fn drop(&mut self) {
+        let mut next = take_left_binary(self);
+        while let Some(mut expr) = next {
+            next = take_left_binary(&mut expr);
+        }
+    }
+}
+
+/// The left operand of `expr` if both are binary expressions, with a
+/// placeholder left in its place.
+fn take_left_binary(expr: &mut Expression) -> Option<Expression> {
+    let Expression::Binary { operands, pos, .. } = expr else {
+        return None;
+    };
+    let [lhs, _] = &mut **operands;
+    if !matches!(lhs, Expression::Binary { .. }) {
+        return None;
+    }
+    let placeholder = Expression::IntLit {
+        value: 0,
+        suffix: IntSuffix::Plain,
+        pos: *pos,
+    };
+    Some(std::mem::replace(lhs, placeholder))
+}
+
+/// `lhs op rhs` if it is an integer constant expression, given the value of
+/// its left operand.
+fn constant_binary(
+    op: BinaryOp,
+    lhs: i64,
+    rhs: &Expression,
+    constant: &impl Fn(&str) -> Option<i64>,
+    model: DataModel,
+) -> Option<i64> {
+    // `&&` and `||` only look at the right operand if they need to.
+    match op {
+        BinaryOp::LogicalAnd if lhs == 0 => return Some(0),
+        BinaryOp::LogicalOr if lhs != 0 => return Some(1),
+        _ => {}
+    }
+    let rhs = rhs.constant_value(constant, model)?;
+    Some(match op {
+        BinaryOp::Add => lhs.checked_add(rhs)?,
+        BinaryOp::Sub => lhs.checked_sub(rhs)?,
+        BinaryOp::Mul => lhs.checked_mul(rhs)?,
+        BinaryOp::Div => lhs.checked_div(rhs)?,
+        BinaryOp::Rem => lhs.checked_rem(rhs)?,
+        BinaryOp::Lt => i64::from(lhs < rhs),
+        BinaryOp::Le => i64::from(lhs <= rhs),
+        BinaryOp::Gt => i64::from(lhs > rhs),
+        BinaryOp::Ge => i64::from(lhs >= rhs),
+        BinaryOp::Eq => i64::from(lhs == rhs),
+        BinaryOp::Ne => i64::from(lhs != rhs),
+        BinaryOp::BitAnd => lhs & rhs,
+        BinaryOp::BitOr => lhs | rhs,
+        BinaryOp::BitXor => lhs ^ rhs,
+        BinaryOp::Shl if (0..32).contains(&rhs) => lhs.checked_shl(rhs as u32)?,
+        BinaryOp::Shr if (0..32).contains(&rhs) => lhs >> rhs,
+        BinaryOp::Shl | BinaryOp::Shr => return None,
+        BinaryOp::LogicalAnd | BinaryOp::LogicalOr => i64::from(rhs != 0),
+    })
+}
+
+/// The value of an initializer of an object with static storage; see
+/// [`Expression::static_value`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StaticValue<'a> {
+    Int(i64),
+    /// `base`'s address, offset by a number of bytes.
+    Address(Base<'a>, i64),
+}
+
+/// What an address constant points into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Base<'a> {
+    /// A string literal's array, without the terminating NUL.
+    String(&'a [u8]),
+    /// The object with static storage of this name.
+    Object(&'a str),
+}
+
+/// One operator of a chain like `a + b - c`, whose left operand is the
+/// chain before it; see [`Expression::binary_chain`].
+pub struct Link<'a> {
+    pub op: BinaryOp,
+    pub operands: &'a [Expression; 2],
+    pub ty: &'a Resolved,
+    pub pos: Position,
+}
+
+/// The functions the compiler provides itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    /// `__builtin_expect(value, expected)`: `value`, which is probably
+    /// `expected`.
+    Expect,
+    /// `__builtin_trap()`: ends the program abnormally.
+    Trap,
+    /// `__builtin_unreachable()`: promises control never gets here.
+    Unreachable,
+}
+
+impl Builtin {
+    pub fn lookup(name: &str) -> Option<Self> {
+        match name {
+            "__builtin_expect" => Some(Builtin::Expect),
+            "__builtin_trap" => Some(Builtin::Trap),
+            "__builtin_unreachable" => Some(Builtin::Unreachable),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Builtin::Expect => "__builtin_expect",
+            Builtin::Trap => "__builtin_trap",
+            Builtin::Unreachable => "__builtin_unreachable",
+        }
+    }
+
+    /// The types of the arguments and of the result.
+    pub fn signature(self) -> (&'static [Type], Type) {
+        match self {
+            Builtin::Expect => (&[Type::Int, Type::Int], Type::Int),
+            Builtin::Trap | Builtin::Unreachable => (&[], Type::Void),
+        }
+    }
+}
+
+/// An increment or decrement. The prefix forms have the value stored, the
+/// postfix forms the value from before.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncDecOp {
+    PreInc,
+    PreDec,
+    PostInc,
+    PostDec,
+}
+
+impl IncDecOp {
+    pub fn is_postfix(self) -> bool {
+        matches!(self, IncDecOp::PostInc | IncDecOp::PostDec)
+    }
+
+    /// `+` for an increment, `-` for a decrement.
+    pub fn binary_op(self) -> BinaryOp {
+        match self {
+            IncDecOp::PreInc | IncDecOp::PostInc => BinaryOp::Add,
+            IncDecOp::PreDec | IncDecOp::PostDec => BinaryOp::Sub,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self.binary_op() {
+            BinaryOp::Add => "++",
+            _ => "--",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    /// `-`
+    Neg,
+    /// `!`
+    Not,
+    /// `~`
+    Complement,
+}
+
+impl UnaryOp {
+    /// The prefix operator `op` stands for, if it can be one.
+    pub fn from_operator(op: Operator) -> Option<Self> {
+        match op {
+            Operator::Minus => Some(UnaryOp::Neg),
+            Operator::Bang => Some(UnaryOp::Not),
+            Operator::Tilde => Some(UnaryOp::Complement),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UnaryOp::Neg => "-",
+            UnaryOp::Not => "!",
+            UnaryOp::Complement => "~",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    LogicalAnd,
+    LogicalOr,
+}
+
+impl BinaryOp {
+    /// The binary operator `op` stands for, or `None` if it is only unary
+    /// or an assignment.
+    pub fn from_operator(op: Operator) -> Option<Self> {
+        Some(match op {
+            Operator::Plus => BinaryOp::Add,
+            Operator::Minus => BinaryOp::Sub,
+            Operator::Star => BinaryOp::Mul,
+            Operator::Slash => BinaryOp::Div,
+            Operator::Percent => BinaryOp::Rem,
+            Operator::Less => BinaryOp::Lt,
+            Operator::LessEqual => BinaryOp::Le,
+            Operator::Greater => BinaryOp::Gt,
+            Operator::GreaterEqual => BinaryOp::Ge,
+            Operator::EqualEqual => BinaryOp::Eq,
+            Operator::BangEqual => BinaryOp::Ne,
+            Operator::Amp => BinaryOp::BitAnd,
+            Operator::Pipe => BinaryOp::BitOr,
+            Operator::Caret => BinaryOp::BitXor,
+            Operator::LessLess => BinaryOp::Shl,
+            Operator::GreaterGreater => BinaryOp::Shr,
+            Operator::AmpAmp => BinaryOp::LogicalAnd,
+            Operator::PipePipe => BinaryOp::LogicalOr,
+            _ => return None,
+        })
+    }
+
+    /// The operator a compound assignment `op` applies, if it is one.
+    pub fn from_compound_assignment(op: Operator) -> Option<Self> {
+        Some(match op {
+            Operator::PlusEqual => BinaryOp::Add,
+            Operator::MinusEqual => BinaryOp::Sub,
+            Operator::StarEqual => BinaryOp::Mul,
+            Operator::SlashEqual => BinaryOp::Div,
+            Operator::PercentEqual => BinaryOp::Rem,
+            Operator::AmpEqual => BinaryOp::BitAnd,
+            Operator::PipeEqual => BinaryOp::BitOr,
+            Operator::CaretEqual => BinaryOp::BitXor,
+            Operator::LessLessEqual => BinaryOp::Shl,
+            Operator::GreaterGreaterEqual => BinaryOp::Shr,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::BitXor => "^",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+            BinaryOp::LogicalAnd => "&&",
+            BinaryOp::LogicalOr => "||",
+        }
+    }
+}
@@ -0,0 +1,348 @@
+//! A structural diff of two programs' syntax trees (`rcc ast-diff`).
+//!
+//! Each function is rendered as an indented tree without positions, so
+//! changes to whitespace, comments or layout don't show. Functions are
+//! matched by name, prototypes separately from definitions; a changed one
+//! is shown as a line diff of its tree, with `-` for the old side and `+`
+//! for the new.
+
+use std::fmt::Write;
+
+use crate::ast::{AsmOperand, Expression, Function, Initializer, Program, Statement};
+use crate::lexer::Position;
+
+/// The tree of `function`, one node per line.
+pub fn render(function: &Function) -> Vec<String> {
+    render_with_positions(function)
+        .into_iter()
+        .map(|(line, _)| line)
+        .collect()
+}
+
+/// The tree of `function` with the source position of each node, or of
+/// the nearest enclosing node that records one.
+pub fn render_with_positions(function: &Function) -> Vec<(String, Position)> {
+    let kind = match function.body {
+        Some(_) => "function",
+        None => "prototype",
+    };
+    let mut lines = vec![(
+        format!("{kind} {} -> {}", function.name, function.return_type),
+        function.pos,
+    )];
+    for param in &*function.params {
+        let line = match &param.name {
+            Some(name) => format!("  param {} {name}", param.ty),
+            None => format!("  param {}", param.ty),
+        };
+        lines.push((line, param.pos));
+    }
+    if function.variadic {
+        lines.push(("  param ...".to_string(), function.pos));
+    }
+    for statement in function.body.iter().flatten() {
+        statement_lines(statement, 1, &mut lines);
+    }
+    lines
+}
+
+fn statement_lines(statement: &Statement, depth: usize, lines: &mut Vec<(String, Position)>) {
+    let indent = "  ".repeat(depth);
+    let pos = statement.pos();
+    match statement {
+        Statement::Return { value, .. } => {
+            lines.push((format!("{indent}return"), pos));
+            if let Some(value) = value {
+                expression_lines(value, depth + 1, pos, lines);
+            }
+        }
+        Statement::Declaration { name, ty, init, .. } => {
+            lines.push((format!("{indent}declare {ty} {name}"), pos));
+            if let Some(init) = init {
+                initializer_lines(init, depth + 1, pos, lines);
+            }
+        }
+        Statement::Enum(decl) => {
+            let tag = decl
+                .tag
+                .as_deref()
+                .map_or(String::new(), |tag| format!(" {tag}"));
+            lines.push((format!("{indent}enum{tag}"), pos));
+            for enumerator in &decl.enumerators {
+                lines.push((
+                    format!("{indent}  enumerator {}", enumerator.name),
+                    enumerator.pos,
+                ));
+                if let Some(value) = &enumerator.value {
+                    expression_lines(value, depth + 2, enumerator.pos, lines);
+                }
+            }
+        }
+        Statement::Expression { expr, .. } => {
+            lines.push((format!("{indent}expression"), pos));
+            expression_lines(expr, depth + 1, pos, lines);
+        }
+        Statement::Block { body, .. } => {
+            lines.push((format!("{indent}block"), pos));
+            for statement in body.iter() {
+                statement_lines(statement, depth + 1, lines);
+            }
+        }
+        Statement::If {
+            cond,
+            then,
+            otherwise,
+            ..
+        } => {
+            lines.push((format!("{indent}if"), pos));
+            expression_lines(cond, depth + 1, pos, lines);
+            statement_lines(then, depth + 1, lines);
+            if let Some(otherwise) = otherwise {
+                lines.push((format!("{indent}else"), pos));
+                statement_lines(otherwise, depth + 1, lines);
+            }
+        }
+        Statement::While { cond, body, .. } => {
+            lines.push((format!("{indent}while"), pos));
+            expression_lines(cond, depth + 1, pos, lines);
+            statement_lines(body, depth + 1, lines);
+        }
+        Statement::DoWhile { body, cond, .. } => {
+            lines.push((format!("{indent}do"), pos));
+            statement_lines(body, depth + 1, lines);
+            expression_lines(cond, depth + 1, pos, lines);
+        }
+        Statement::For {
+            init,
+            cond,
+            step,
+            body,
+            ..
+        } => {
+            lines.push((format!("{indent}for"), pos));
+            if let Some(init) = init {
+                statement_lines(init, depth + 1, lines);
+            }
+            for (clause, expr) in [("cond", cond), ("step", step)] {
+                if let Some(expr) = expr {
+                    lines.push((format!("{indent}  {clause}"), pos));
+                    expression_lines(expr, depth + 2, pos, lines);
+                }
+            }
+            statement_lines(body, depth + 1, lines);
+        }
+        Statement::Label { name, body, .. } => {
+            lines.push((format!("{indent}label {name}"), pos));
+            statement_lines(body, depth + 1, lines);
+        }
+        Statement::Goto { label, .. } => lines.push((format!("{indent}goto {label}"), pos)),
+        Statement::Switch { cond, body, .. } => {
+            lines.push((format!("{indent}switch"), pos));
+            expression_lines(cond, depth + 1, pos, lines);
+            statement_lines(body, depth + 1, lines);
+        }
+        Statement::Case { value, body, .. } => {
+            lines.push((format!("{indent}case"), pos));
+            expression_lines(value, depth + 1, pos, lines);
+            statement_lines(body, depth + 1, lines);
+        }
+        Statement::Default { body, .. } => {
+            lines.push((format!("{indent}default"), pos));
+            statement_lines(body, depth + 1, lines);
+        }
+        Statement::Break { .. } => lines.push((format!("{indent}break"), pos)),
+        Statement::Empty { .. } => lines.push((format!("{indent}empty"), pos)),
+        Statement::Asm {
+            template,
+            outputs,
+            inputs,
+            clobbers,
+            ..
+        } => {
+            lines.push((format!("{indent}asm {template:?}"), pos));
+            let operands =
+                |kind: &str, operands: &[AsmOperand], lines: &mut Vec<(String, Position)>| {
+                    for operand in operands {
+                        let line = format!("{indent}  {kind} {:?}", operand.constraint);
+                        lines.push((line, operand.pos));
+                        expression_lines(&operand.expr, depth + 2, operand.pos, lines);
+                    }
+                };
+            operands("output", outputs, lines);
+            operands("input", inputs, lines);
+            for clobber in clobbers {
+                lines.push((format!("{indent}  clobber {clobber:?}"), pos));
+            }
+        }
+    }
+}
+
+/// Adds the lines of `init`, part of a node at `pos`.
+fn initializer_lines(
+    init: &Initializer,
+    depth: usize,
+    pos: Position,
+    lines: &mut Vec<(String, Position)>,
+) {
+    match init {
+        Initializer::Expr(expr) => expression_lines(expr, depth, pos, lines),
+        Initializer::List { elements, .. } => {
+            lines.push((format!("{}initializer list", "  ".repeat(depth)), pos));
+            for element in elements.iter() {
+                initializer_lines(element, depth + 1, pos, lines);
+            }
+        }
+    }
+}
+
+/// Adds the lines of `expression`, part of a node at `pos`.
+fn expression_lines(
+    expression: &Expression,
+    depth: usize,
+    pos: Position,
+    lines: &mut Vec<(String, Position)>,
+) {
+    let indent = "  ".repeat(depth);
+    match expression {
+        Expression::IntLit { value, suffix, .. } => {
+            lines.push((format!("{indent}int {value}{}", suffix.as_str()), pos));
+        }
+        Expression::CharLit { value, .. } => lines.push((format!("{indent}char {value}"), pos)),
+        Expression::StringLit { value, pos } => lines.push((
+            format!("{indent}string {:?}", String::from_utf8_lossy(value)),
+            *pos,
+        )),
+        Expression::Variable { name, pos } => {
+            lines.push((format!("{indent}variable {name}"), *pos));
+        }
+        Expression::AddressOf { operand, pos } => {
+            lines.push((format!("{indent}address-of"), *pos));
+            expression_lines(operand, depth + 1, *pos, lines);
+        }
+        Expression::Deref { operand, pos } => {
+            lines.push((format!("{indent}deref"), *pos));
+            expression_lines(operand, depth + 1, *pos, lines);
+        }
+        Expression::SizeofExpr { operand, pos } => {
+            lines.push((format!("{indent}sizeof"), *pos));
+            expression_lines(operand, depth + 1, *pos, lines);
+        }
+        Expression::SizeofType { ty, pos } => {
+            lines.push((format!("{indent}sizeof {ty}"), *pos));
+        }
+        Expression::Assign {
+            op, operands, pos, ..
+        } => {
+            let op = op.map_or(String::new(), |op| format!(" {}=", op.as_str()));
+            lines.push((format!("{indent}assign{op}"), *pos));
+            for operand in operands.iter() {
+                expression_lines(operand, depth + 1, *pos, lines);
+            }
+        }
+        Expression::IncDec { op, operand, pos } => {
+            let fix = if op.is_postfix() { "postfix" } else { "prefix" };
+            lines.push((format!("{indent}{fix} {}", op.as_str()), *pos));
+            expression_lines(operand, depth + 1, *pos, lines);
+        }
+        Expression::Unary { op, operand, .. } => {
+            lines.push((format!("{indent}unary {}", op.as_str()), pos));
+            expression_lines(operand, depth + 1, pos, lines);
+        }
+        // Each operator's line, the outermost first, goes before its left
+        // operand's and its right operand's after.
+        Expression::Binary { .. } => {
+            let (first, links) = expression.binary_chain();
+            for (i, link) in links.iter().rev().enumerate() {
+                let indent = "  ".repeat(depth + i);
+                lines.push((format!("{indent}binary {}", link.op.as_str()), pos));
+            }
+            expression_lines(first, depth + links.len(), pos, lines);
+            for (i, link) in links.iter().enumerate() {
+                let depth = depth + links.len() - i;
+                expression_lines(&link.operands[1], depth, pos, lines);
+            }
+        }
+        Expression::FunctionCall { name, args, pos } => {
+            lines.push((format!("{indent}call {name}"), *pos));
+            for arg in args.iter() {
+                expression_lines(arg, depth + 1, *pos, lines);
+            }
+        }
+        Expression::BuiltinCall { builtin, args, pos } => {
+            lines.push((format!("{indent}call {}", builtin.name()), *pos));
+            for arg in args.iter() {
+                expression_lines(arg, depth + 1, *pos, lines);
+            }
+        }
+    }
+}
+
+/// The differences between `old` and `new`, or `None` if their trees are
+/// the same.
+pub fn diff(old: &Program, new: &Program) -> Option<String> {
+    let mut out = String::new();
+    let find = |program: &'_ Program, wanted: &Function| {
+        program
+            .functions
+            .iter()
+            .find(|function| {
+                function.name == wanted.name && function.body.is_some() == wanted.body.is_some()
+            })
+            .map(render)
+    };
+    for function in &old.functions {
+        let old_lines = render(function);
+        match find(new, function) {
+            None => writeln!(out, "removed function `{}`", function.name).unwrap(),
+            Some(new_lines) if new_lines != old_lines => {
+                writeln!(out, "changed function `{}`:", function.name).unwrap();
+                for line in line_diff(&old_lines, &new_lines) {
+                    writeln!(out, "{line}").unwrap();
+                }
+            }
+            Some(_) => {}
+        }
+    }
+    for function in &new.functions {
+        if find(old, function).is_none() {
+            writeln!(out, "added function `{}`:", function.name).unwrap();
+            for line in render(function) {
+                writeln!(out, "+ {line}").unwrap();
+            }
+        }
+    }
+    (!out.is_empty()).then_some(out)
+}
+
+/// `old` and `new` merged along their longest common subsequence, each line
+/// marked `-`, `+` or with a space if both have it.
+fn line_diff(old: &[String], new: &[String]) -> Vec<String> {
+    // common[i][j]: the length of the longest common subsequence of
+    // old[i..] and new[j..].
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(format!("  {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(format!("- {}", old[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", new[j]));
+            j += 1;
+        }
+    }
+    lines
+}
@@ -0,0 +1,368 @@
+//! The syntax tree as a Graphviz graph (`--emit=ast-dot`), to see how a
+//! program was parsed: `dot -Tsvg a.ast.dot -o a.svg` draws it.
+//!
+//! Every node of the tree is a box labeled with what it is, or just with
+//! its operator, literal or name where that says enough: `+`, `42`, `x`.
+//! A node's parts hang below it from left to right in source order, the
+//! edge to each labeled with its role (`lhs`, `cond`, `body`) where the
+//! node has parts of different kinds.
+
+use std::fmt::Write;
+
+use crate::ast::{AsmOperand, Enum, Expression, Function, Global, Initializer, Program, Statement};
+use crate::pretty;
+
+/// `program` as a DOT `digraph`, rooted at a `program` node whose children
+/// are its enums, globals and functions.
+pub fn emit(program: &Program) -> String {
+    let mut graph = Graph {
+        out: String::from("digraph ast {\n    ordering=out;\n    node [shape=box];\n"),
+        nodes: 0,
+    };
+    let root = graph.node("program");
+    for decl in &program.enums {
+        let child = graph.enum_decl(decl);
+        graph.edge(root, child, "");
+    }
+    for global in &program.globals {
+        let child = graph.global(global);
+        graph.edge(root, child, "");
+    }
+    for function in &program.functions {
+        let child = graph.function(function);
+        graph.edge(root, child, "");
+    }
+    graph.out.push_str("}\n");
+    graph.out
+}
+
+/// The graph so far, its nodes numbered in the order they were added.
+struct Graph {
+    out: String,
+    nodes: usize,
+}
+
+impl Graph {
+    /// Adds a node labeled `label`, returning its number.
+    fn node(&mut self, label: &str) -> usize {
+        let id = self.nodes;
+        self.nodes += 1;
+        writeln!(self.out, "    n{id} [label={}];", quoted(label)).unwrap();
+        id
+    }
+
+    /// Adds an edge from `parent` to its part `child`, labeled with the
+    /// part's `role` unless that is empty.
+    fn edge(&mut self, parent: usize, child: usize, role: &str) {
+        let label = if role.is_empty() {
+            String::new()
+        } else {
+            format!(" [label={}]", quoted(role))
+        };
+        writeln!(self.out, "    n{parent} -> n{child}{label};").unwrap();
+    }
+
+    /// Adds `expr` as the part of `parent` playing `role`.
+    fn part(&mut self, parent: usize, expr: &Expression, role: &str) {
+        let child = self.expression(expr);
+        self.edge(parent, child, role);
+    }
+
+    /// Adds `statement` as the part of `parent` playing `role`.
+    fn statement_part(&mut self, parent: usize, statement: &Statement, role: &str) {
+        let child = self.statement(statement);
+        self.edge(parent, child, role);
+    }
+
+    /// A `function` or `prototype` node, with a node for each parameter
+    /// and statement.
+    fn function(&mut self, function: &Function) -> usize {
+        let kind = if function.body.is_some() {
+            "function"
+        } else {
+            "prototype"
+        };
+        let id = self.node(&format!(
+            "{kind} {} {}",
+            function.return_type, function.name
+        ));
+        for param in function.params.iter() {
+            let name = param.name.as_deref().unwrap_or("");
+            let child = self.node(format!("param {} {name}", param.ty).trim_end());
+            self.edge(id, child, "");
+        }
+        if function.variadic {
+            let child = self.node("param ...");
+            self.edge(id, child, "");
+        }
+        for statement in function.body.iter().flatten() {
+            self.statement_part(id, statement, "");
+        }
+        id
+    }
+
+    fn enum_decl(&mut self, decl: &Enum) -> usize {
+        let id = self.node(format!("enum {}", decl.tag.as_deref().unwrap_or("")).trim_end());
+        for enumerator in &decl.enumerators {
+            let child = self.node(&enumerator.name);
+            self.edge(id, child, "");
+            if let Some(value) = &enumerator.value {
+                self.part(child, value, "");
+            }
+        }
+        id
+    }
+
+    fn global(&mut self, global: &Global) -> usize {
+        let mut label = String::new();
+        if global.is_extern {
+            label.push_str("extern ");
+        }
+        if global.thread_local {
+            label.push_str("_Thread_local ");
+        }
+        write!(label, "global {} {}", global.ty, global.name).unwrap();
+        let id = self.node(&label);
+        if let Some(init) = &global.init {
+            let child = self.initializer(init);
+            self.edge(id, child, "");
+        }
+        id
+    }
+
+    fn initializer(&mut self, init: &Initializer) -> usize {
+        match init {
+            Initializer::Expr(expr) => self.expression(expr),
+            Initializer::List { elements, .. } => {
+                let id = self.node("{}");
+                for element in elements.iter() {
+                    let child = self.initializer(element);
+                    self.edge(id, child, "");
+                }
+                id
+            }
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) -> usize {
+        match statement {
+            Statement::Return { value, .. } => {
+                let id = self.node("return");
+                if let Some(value) = value {
+                    self.part(id, value, "");
+                }
+                id
+            }
+            Statement::Declaration { name, ty, init, .. } => {
+                let id = self.node(&format!("declare {ty} {name}"));
+                if let Some(init) = init {
+                    let child = self.initializer(init);
+                    self.edge(id, child, "");
+                }
+                id
+            }
+            Statement::Expression { expr, .. } => {
+                let id = self.node("expression");
+                self.part(id, expr, "");
+                id
+            }
+            Statement::Enum(decl) => self.enum_decl(decl),
+            Statement::Block { body, .. } => {
+                let id = self.node("block");
+                for statement in body.iter() {
+                    self.statement_part(id, statement, "");
+                }
+                id
+            }
+            Statement::If {
+                cond,
+                then,
+                otherwise,
+                ..
+            } => {
+                let id = self.node("if");
+                self.part(id, cond, "cond");
+                self.statement_part(id, then, "then");
+                if let Some(otherwise) = otherwise {
+                    self.statement_part(id, otherwise, "else");
+                }
+                id
+            }
+            Statement::While { cond, body, .. } => {
+                let id = self.node("while");
+                self.part(id, cond, "cond");
+                self.statement_part(id, body, "body");
+                id
+            }
+            Statement::DoWhile { body, cond, .. } => {
+                let id = self.node("do");
+                self.statement_part(id, body, "body");
+                self.part(id, cond, "cond");
+                id
+            }
+            Statement::For {
+                init,
+                cond,
+                step,
+                body,
+                ..
+            } => {
+                let id = self.node("for");
+                if let Some(init) = init {
+                    self.statement_part(id, init, "init");
+                }
+                if let Some(cond) = cond {
+                    self.part(id, cond, "cond");
+                }
+                if let Some(step) = step {
+                    self.part(id, step, "step");
+                }
+                self.statement_part(id, body, "body");
+                id
+            }
+            Statement::Label { name, body, .. } => {
+                let id = self.node(&format!("label {name}"));
+                self.statement_part(id, body, "");
+                id
+            }
+            Statement::Goto { label, .. } => self.node(&format!("goto {label}")),
+            Statement::Switch { cond, body, .. } => {
+                let id = self.node("switch");
+                self.part(id, cond, "cond");
+                self.statement_part(id, body, "body");
+                id
+            }
+            Statement::Case { value, body, .. } => {
+                let id = self.node("case");
+                self.part(id, value, "value");
+                self.statement_part(id, body, "");
+                id
+            }
+            Statement::Default { body, .. } => {
+                let id = self.node("default");
+                self.statement_part(id, body, "");
+                id
+            }
+            Statement::Break { .. } => self.node("break"),
+            Statement::Empty { .. } => self.node("empty"),
+            Statement::Asm {
+                template,
+                outputs,
+                inputs,
+                clobbers,
+                ..
+            } => {
+                let id = self.node(&format!("asm {template:?}"));
+                for operand in outputs {
+                    self.asm_operand(id, operand, "output");
+                }
+                for operand in inputs {
+                    self.asm_operand(id, operand, "input");
+                }
+                for clobber in clobbers {
+                    let child = self.node(&format!("{clobber:?}"));
+                    self.edge(id, child, "clobber");
+                }
+                id
+            }
+        }
+    }
+
+    /// Adds `operand` as a node for its constraint, with its expression
+    /// below it.
+    fn asm_operand(&mut self, parent: usize, operand: &AsmOperand, role: &str) {
+        let id = self.node(&format!("{:?}", operand.constraint));
+        self.edge(parent, id, role);
+        self.part(id, &operand.expr, "");
+    }
+
+    fn expression(&mut self, expr: &Expression) -> usize {
+        match expr {
+            // Literals are labeled with how they would be written in C.
+            Expression::IntLit { .. }
+            | Expression::CharLit { .. }
+            | Expression::StringLit { .. } => self.node(&pretty::expression(expr)),
+            Expression::Variable { name, .. } => self.node(name),
+            Expression::Unary { op, operand, .. } => {
+                let id = self.node(op.as_str());
+                self.part(id, operand, "");
+                id
+            }
+            Expression::IncDec { op, operand, .. } => {
+                let fix = if op.is_postfix() { "postfix" } else { "prefix" };
+                let id = self.node(&format!("{fix} {}", op.as_str()));
+                self.part(id, operand, "");
+                id
+            }
+            // Numbered as if each operator's operands were added in
+            // turn, the outermost operator first.
+            Expression::Binary { .. } => {
+                let (first, links) = expr.binary_chain();
+                let ids: Vec<usize> = links
+                    .iter()
+                    .rev()
+                    .map(|link| self.node(link.op.as_str()))
+                    .collect();
+                let mut lhs = self.expression(first);
+                for (link, &id) in links.iter().zip(ids.iter().rev()) {
+                    self.edge(id, lhs, "lhs");
+                    self.part(id, &link.operands[1], "rhs");
+                    lhs = id;
+                }
+                lhs
+            }
+            Expression::AddressOf { operand, .. } => {
+                let id = self.node("&");
+                self.part(id, operand, "");
+                id
+            }
+            Expression::Deref { operand, .. } => {
+                let id = self.node("*");
+                self.part(id, operand, "");
+                id
+            }
+            Expression::Assign { op, operands, .. } => {
+                let op = op.map_or("=".to_string(), |op| format!("{}=", op.as_str()));
+                let id = self.node(&op);
+                self.part(id, &operands[0], "target");
+                self.part(id, &operands[1], "value");
+                id
+            }
+            Expression::FunctionCall { name, args, .. } => self.call(name, args),
+            Expression::BuiltinCall { builtin, args, .. } => self.call(builtin.name(), args),
+            Expression::SizeofExpr { operand, .. } => {
+                let id = self.node("sizeof");
+                self.part(id, operand, "");
+                id
+            }
+            Expression::SizeofType { ty, .. } => self.node(&format!("sizeof({ty})")),
+        }
+    }
+
+    /// A `call` node with the arguments below it in order.
+    fn call(&mut self, name: &str, args: &[Expression]) -> usize {
+        let id = self.node(&format!("call {name}"));
+        for arg in args {
+            self.part(id, arg, "");
+        }
+        id
+    }
+}
+
+/// `text` as a DOT string. Backslashes are escaped too, so that an escape
+/// in a C literal is shown as written rather than read by Graphviz.
+fn quoted(text: &str) -> String {
+    let mut out = String::from('"');
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,1043 @@
+//! A tree-walking interpreter for the analyzed syntax tree
+//! (`--interpret=ast`).
+//!
+//! It gives a program its meaning straight from the source, without
+//! lowering it, so it is a reference for the IR, the optimizer and each
+//! backend to be tested against: where they disagree with it, one of them
+//! is wrong. It also runs programs where there is no assembler.
+//!
+//! Values are kept in the range of their C type, and memory is a map of
+//! bytes laid out as the [IR interpreter](crate::ir::interp) lays it out.
+//! What that interpreter traps on, this one traps on too, with the same
+//! [`Trap`]s: division by zero, oversized shifts, reads of uninitialized
+//! memory and null pointers, calls to functions with no body, and running
+//! too long or recursing too deep.
+
+use std::collections::HashMap;
+use std::panic;
+use std::ptr;
+use std::thread;
+
+use crate::ast::{
+    Base, BinaryOp, Builtin, DataModel, Enum, Expression, Function, Initializer, Program,
+    Statement, StaticValue, Type, UnaryOp,
+};
+use crate::ir::interp::{Trap, DEFAULT_FUEL};
+
+/// Types are sized as the IR interpreter sizes them, as on the host.
+const MODEL: DataModel = DataModel::Lp64;
+
+/// Nested calls allowed before assuming runaway recursion.
+const MAX_CALL_DEPTH: usize = 1024;
+
+/// The host stack each nested call is given. Calls recurse on the host's
+/// stack, through the statements and expressions between them, and
+/// unoptimized builds use several kilobytes for each.
+const STACK_PER_CALL: usize = 32 * 1024;
+
+/// Where string literals are laid out, one after another.
+const STRINGS_BASE: i64 = 0x1000_0000;
+
+/// Likewise for the program's global variables.
+const GLOBALS_BASE: i64 = 0x2000_0000;
+
+/// The top of the stack that variables are carved from.
+const STACK_TOP: i64 = 0x7000_0000;
+
+/// What a name in scope stands for.
+#[derive(Clone, Copy)]
+enum Binding<'a> {
+    /// A variable: the address of its object, and its type.
+    Object(i64, &'a Type),
+    /// An enumeration constant, an `int`.
+    Constant(i64),
+}
+
+/// Where control goes after a statement.
+enum Flow<'a> {
+    Next,
+    Return(Option<i64>),
+    /// To the label, which some enclosing statement contains.
+    Goto(&'a str),
+    /// Out of the innermost loop or `switch`.
+    Break,
+}
+
+/// A statement control jumps to inside the statement being run, which
+/// starts running from there.
+#[derive(Clone, Copy)]
+enum Target<'a> {
+    /// The statement with this label.
+    Label(&'a str),
+    /// A `case` or `default` of the `switch` being run.
+    Case(&'a Statement),
+}
+
+/// An activation of a function.
+struct Frame<'a> {
+    /// The names declared in the function so far, in the innermost scope.
+    scope: HashMap<&'a str, Binding<'a>>,
+    /// The object of each declaration that has run, which it keeps for
+    /// the rest of the call, however often it runs again.
+    objects: HashMap<*const Statement, i64>,
+}
+
+pub struct Interpreter<'a> {
+    /// Every function declared, defined or not.
+    functions: HashMap<&'a str, &'a Function>,
+    /// The address and type of each global defined.
+    globals: HashMap<&'a str, (i64, &'a Type)>,
+    /// The file-scope enumeration constants.
+    constants: HashMap<&'a str, i64>,
+    /// The address of each string literal evaluated so far, by contents.
+    strings: HashMap<&'a [u8], i64>,
+    next_string: i64,
+    /// Every byte written so far; any other address reads as
+    /// uninitialized.
+    memory: HashMap<i64, u8>,
+    /// The lowest address handed out to a variable so far.
+    stack_pointer: i64,
+    depth: usize,
+    fuel: u64,
+}
+
+impl<'a> Interpreter<'a> {
+    /// An interpreter for `program`, which the analyzer accepted, with its
+    /// globals initialized.
+    pub fn new(program: &'a Program) -> Self {
+        let mut interpreter = Self {
+            functions: HashMap::new(),
+            globals: HashMap::new(),
+            constants: HashMap::new(),
+            strings: HashMap::new(),
+            next_string: STRINGS_BASE,
+            memory: HashMap::new(),
+            stack_pointer: STACK_TOP,
+            depth: 0,
+            fuel: DEFAULT_FUEL,
+        };
+        // A definition takes the place of any prototype.
+        for function in &program.functions {
+            if function.body.is_some() || !interpreter.functions.contains_key(&*function.name) {
+                interpreter.functions.insert(&function.name, function);
+            }
+        }
+        for decl in &program.enums {
+            let values = enumerator_values(decl, |name| interpreter.constants.get(name).copied());
+            interpreter.constants.extend(values);
+        }
+        // Each global starts out zero, and is then initialized once every
+        // address is known, so that it may point at one defined after it.
+        let mut next = GLOBALS_BASE;
+        let defined = program.globals.iter().filter(|global| !global.is_extern);
+        for global in defined.clone() {
+            if interpreter.globals.contains_key(&*global.name) {
+                continue;
+            }
+            let (size, align) = (size_of(&global.ty), align_of(&global.ty));
+            let address = (next + align - 1) & -align;
+            interpreter
+                .globals
+                .insert(&global.name, (address, &global.ty));
+            for offset in 0..size {
+                interpreter.memory.insert(address + offset, 0);
+            }
+            next = address + size;
+        }
+        for global in defined {
+            if let Some(init) = &global.init {
+                let (address, _) = interpreter.globals[&*global.name];
+                interpreter.initialize_global(address, &global.ty, init);
+            }
+        }
+        interpreter
+    }
+
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    /// Calls `name` with `args`, returning its result (`None` for `void`).
+    pub fn call(&mut self, name: &str, args: &[i64]) -> Result<Option<i64>, Trap> {
+        let function = self
+            .functions
+            .get(name)
+            .copied()
+            .ok_or_else(|| Trap::UnknownFunction(name.to_string()))?;
+        // The program runs on a thread of its own, with stack enough for
+        // the deepest recursion allowed.
+        thread::scope(|scope| {
+            thread::Builder::new()
+                .stack_size(MAX_CALL_DEPTH * STACK_PER_CALL)
+                .spawn_scoped(scope, || self.call_function(function, args))
+                .expect("cannot start the interpreter's thread")
+                .join()
+                .unwrap_or_else(|payload| panic::resume_unwind(payload))
+        })
+    }
+
+    /// Stores the integer and address constants `init` gives the `ty` at
+    /// `address`, which belongs to a global. An address of a global
+    /// defined elsewhere leaves its element uninitialized, since that has
+    /// no address here.
+    fn initialize_global(&mut self, address: i64, ty: &Type, init: &'a Initializer) {
+        let expr = match (ty, init) {
+            (Type::Array(element, _), Initializer::List { elements, .. }) => {
+                for (i, init) in elements.iter().enumerate() {
+                    let address = address + i as i64 * size_of(element);
+                    self.initialize_global(address, element, init);
+                }
+                return;
+            }
+            (_, Initializer::Expr(expr)) => expr,
+            (_, Initializer::List { .. }) => {
+                unreachable!("the analyzer only accepts lists for arrays")
+            }
+        };
+        let object = |name: &str| self.globals.get(name).map(|&(_, ty)| ty.clone());
+        let value = expr
+            .static_value(&object, &|name| self.constants.get(name).copied(), MODEL)
+            .expect("the analyzer checked global initializers are constant");
+        let value = match value {
+            StaticValue::Int(value) => value,
+            StaticValue::Address(Base::String(bytes), offset) => self.string(bytes) + offset,
+            StaticValue::Address(Base::Object(name), offset) => match self.globals.get(name) {
+                Some(&(base, _)) => base + offset,
+                None => {
+                    for offset in 0..size_of(ty) {
+                        self.memory.remove(&(address + offset));
+                    }
+                    return;
+                }
+            },
+        };
+        self.write(address, ty, fit(ty, value));
+    }
+
+    fn call_function(&mut self, function: &'a Function, args: &[i64]) -> Result<Option<i64>, Trap> {
+        let Some(body) = function.body.as_deref() else {
+            return Err(Trap::UnknownFunction(function.name.clone()));
+        };
+        if self.depth == MAX_CALL_DEPTH {
+            return Err(Trap::StackOverflow);
+        }
+        let stack_pointer = self.stack_pointer;
+        let mut frame = Frame {
+            scope: HashMap::new(),
+            objects: HashMap::new(),
+        };
+        for (param, &arg) in function.params.iter().zip(args) {
+            let name = param
+                .name
+                .as_deref()
+                .expect("definitions name their parameters");
+            let address = self.allocate(&param.ty)?;
+            self.write(address, &param.ty, fit(&param.ty, arg));
+            frame
+                .scope
+                .insert(name, Binding::Object(address, &param.ty));
+        }
+        self.depth += 1;
+        let flow = self.exec_list(&mut frame, body, None);
+        self.depth -= 1;
+        self.stack_pointer = stack_pointer;
+        let returned = match flow? {
+            Flow::Return(value) => value,
+            // Falling off the end returns 0, which is what `main` requires.
+            Flow::Next => Some(0),
+            Flow::Goto(_) => unreachable!("the analyzer checks every label is defined"),
+            Flow::Break => unreachable!("the analyzer checks every `break` is in a loop or switch"),
+        };
+        Ok(match function.return_type {
+            Type::Void => None,
+            ref ty => returned.map(|value| fit(ty, value)),
+        })
+    }
+
+    /// Runs `statements` in order from the one containing `seek`, if it is
+    /// given, or from the first.
+    fn exec_list(
+        &mut self,
+        frame: &mut Frame<'a>,
+        statements: &'a [Statement],
+        mut seek: Option<Target<'a>>,
+    ) -> Result<Flow<'a>, Trap> {
+        let mut next = match seek {
+            Some(target) => self.jump_into(frame, statements, target)?,
+            None => 0,
+        };
+        while let Some(statement) = statements.get(next) {
+            match self.exec(frame, statement, seek.take())? {
+                Flow::Next => next += 1,
+                Flow::Goto(label)
+                    if statements.iter().any(|s| contains(s, Target::Label(label))) =>
+                {
+                    next = self.jump_into(frame, statements, Target::Label(label))?;
+                    seek = Some(Target::Label(label));
+                }
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    /// The index of the statement among `statements` that contains
+    /// `target`. The names declared before it are in scope at the target,
+    /// even though their declarations are jumped over.
+    fn jump_into(
+        &mut self,
+        frame: &mut Frame<'a>,
+        statements: &'a [Statement],
+        target: Target<'a>,
+    ) -> Result<usize, Trap> {
+        let index = statements
+            .iter()
+            .position(|statement| contains(statement, target))
+            .expect("the target is in one of the statements");
+        for statement in &statements[..index] {
+            self.declare(frame, statement)?;
+        }
+        Ok(index)
+    }
+
+    /// Brings the names `statement` declares, if it is a declaration, into
+    /// scope, without initializing them.
+    fn declare(&mut self, frame: &mut Frame<'a>, statement: &'a Statement) -> Result<(), Trap> {
+        match statement {
+            Statement::Declaration { name, ty, .. } => {
+                let address = self.object(frame, statement, ty)?;
+                frame.scope.insert(name, Binding::Object(address, ty));
+            }
+            Statement::Enum(decl) => {
+                let values = enumerator_values(decl, |name| match self.lookup(frame, name) {
+                    Ok(Binding::Constant(value)) => Some(value),
+                    _ => None,
+                });
+                for (name, value) in values {
+                    frame.scope.insert(name, Binding::Constant(value));
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Runs `statement`, or with `seek` the part of it from `seek` on.
+    fn exec(
+        &mut self,
+        frame: &mut Frame<'a>,
+        statement: &'a Statement,
+        seek: Option<Target<'a>>,
+    ) -> Result<Flow<'a>, Trap> {
+        self.burn()?;
+        match statement {
+            Statement::Return { value, .. } => {
+                let value = match value {
+                    Some(value) => Some(self.eval(frame, value)?.0),
+                    None => None,
+                };
+                Ok(Flow::Return(value))
+            }
+            Statement::Declaration { ty, init, .. } => {
+                self.declare(frame, statement)?;
+                if let Some(init) = init {
+                    let address = frame.objects[&(statement as *const Statement)];
+                    // Elements an initializer list leaves out are zero.
+                    if let Type::Array(..) = ty {
+                        for offset in 0..size_of(ty) {
+                            self.memory.insert(address + offset, 0);
+                        }
+                    }
+                    self.initialize(frame, address, ty, init)?;
+                }
+                Ok(Flow::Next)
+            }
+            Statement::Expression { expr, .. } => {
+                self.eval(frame, expr)?;
+                Ok(Flow::Next)
+            }
+            Statement::Empty { .. } => Ok(Flow::Next),
+            Statement::Enum(_) => {
+                self.declare(frame, statement)?;
+                Ok(Flow::Next)
+            }
+            Statement::Block { body, .. } => {
+                let outer = frame.scope.clone();
+                let flow = self.exec_list(frame, body, seek);
+                frame.scope = outer;
+                flow
+            }
+            Statement::Label { name, body, .. } => {
+                let seek =
+                    seek.filter(|target| !matches!(target, Target::Label(label) if label == name));
+                self.exec(frame, body, seek)
+            }
+            Statement::Goto { label, .. } => Ok(Flow::Goto(label)),
+            Statement::Switch { cond, body, .. } => {
+                let mut seek = match seek {
+                    // Jumping into the body skips the condition.
+                    Some(target) => Some(target),
+                    None => {
+                        let (value, ty) = self.eval(frame, cond)?;
+                        let ty = ty.promote();
+                        let constant = |name: &str| match self.lookup(frame, name) {
+                            Ok(Binding::Constant(value)) => Some(value),
+                            _ => None,
+                        };
+                        match switch_target(body, &|case| fit(&ty, case) == value, &constant) {
+                            Some(case) => Some(Target::Case(case)),
+                            None => return Ok(Flow::Next),
+                        }
+                    }
+                };
+                loop {
+                    match self.exec(frame, body, seek.take())? {
+                        Flow::Next | Flow::Break => return Ok(Flow::Next),
+                        Flow::Goto(label) if contains(body, Target::Label(label)) => {
+                            seek = Some(Target::Label(label));
+                        }
+                        flow => return Ok(flow),
+                    }
+                }
+            }
+            Statement::Case { body, .. } | Statement::Default { body, .. } => {
+                let seek = seek.filter(
+                    |target| !matches!(target, Target::Case(case) if ptr::eq(*case, statement)),
+                );
+                self.exec(frame, body, seek)
+            }
+            Statement::Break { .. } => Ok(Flow::Break),
+            Statement::If {
+                cond,
+                then,
+                otherwise,
+                ..
+            } => {
+                // Jumping into a branch skips the condition.
+                let branch = match seek {
+                    Some(target) if contains(then, target) => Some(&**then),
+                    Some(_) => otherwise.as_deref(),
+                    None if self.truth(frame, cond)? => Some(&**then),
+                    None => otherwise.as_deref(),
+                };
+                match branch {
+                    Some(branch) => self.exec(frame, branch, seek),
+                    None => Ok(Flow::Next),
+                }
+            }
+            Statement::While { cond, body, .. } => {
+                self.exec_loop(frame, Some(cond), None, body, seek)
+            }
+            Statement::DoWhile { body, cond, .. } => {
+                let mut seek = seek;
+                loop {
+                    match self.exec(frame, body, seek.take())? {
+                        Flow::Next => {}
+                        Flow::Break => return Ok(Flow::Next),
+                        Flow::Goto(label) if contains(body, Target::Label(label)) => {
+                            seek = Some(Target::Label(label));
+                            continue;
+                        }
+                        flow => return Ok(flow),
+                    }
+                    if !self.truth(frame, cond)? {
+                        return Ok(Flow::Next);
+                    }
+                }
+            }
+            Statement::For {
+                init,
+                cond,
+                step,
+                body,
+                ..
+            } => {
+                let outer = frame.scope.clone();
+                let flow = match init {
+                    // Jumping into the loop skips the initialization, but
+                    // not the scope of what it declares.
+                    Some(init) if seek.is_some() => self.declare(frame, init),
+                    Some(init) => self.exec(frame, init, None).map(|_| ()),
+                    None => Ok(()),
+                }
+                .and_then(|()| self.exec_loop(frame, cond.as_ref(), step.as_ref(), body, seek));
+                frame.scope = outer;
+                flow
+            }
+            Statement::Asm { .. } => Err(Trap::InlineAsm),
+        }
+    }
+
+    /// Runs `body` then `step` for as long as `cond` holds, testing it
+    /// before each iteration. With `seek`, the loop is jumped into at the
+    /// label in `body`, past the first test.
+    fn exec_loop(
+        &mut self,
+        frame: &mut Frame<'a>,
+        cond: Option<&'a Expression>,
+        step: Option<&'a Expression>,
+        body: &'a Statement,
+        mut seek: Option<Target<'a>>,
+    ) -> Result<Flow<'a>, Trap> {
+        loop {
+            if let (None, Some(cond)) = (seek, cond) {
+                if !self.truth(frame, cond)? {
+                    return Ok(Flow::Next);
+                }
+            }
+            match self.exec(frame, body, seek.take())? {
+                Flow::Next => {}
+                Flow::Break => return Ok(Flow::Next),
+                Flow::Goto(label) if contains(body, Target::Label(label)) => {
+                    seek = Some(Target::Label(label));
+                    continue;
+                }
+                flow => return Ok(flow),
+            }
+            if let Some(step) = step {
+                self.eval(frame, step)?;
+            }
+        }
+    }
+
+    /// Stores `init` into the `ty` at `address`.
+    fn initialize(
+        &mut self,
+        frame: &mut Frame<'a>,
+        address: i64,
+        ty: &Type,
+        init: &'a Initializer,
+    ) -> Result<(), Trap> {
+        match (ty, init) {
+            (Type::Array(element, _), Initializer::List { elements, .. }) => {
+                for (i, init) in elements.iter().enumerate() {
+                    let address = address + i as i64 * size_of(element);
+                    self.initialize(frame, address, element, init)?;
+                }
+            }
+            (_, Initializer::Expr(expr)) => {
+                let (value, _) = self.eval(frame, expr)?;
+                self.write(address, ty, fit(ty, value));
+            }
+            (_, Initializer::List { .. }) => {
+                unreachable!("the analyzer only accepts lists for arrays")
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `cond` is nonzero.
+    fn truth(&mut self, frame: &mut Frame<'a>, cond: &'a Expression) -> Result<bool, Trap> {
+        Ok(self.eval(frame, cond)?.0 != 0)
+    }
+
+    /// The value of `expr`, in the range of its type, and its type. A
+    /// `void` call has the value 0.
+    fn eval(&mut self, frame: &mut Frame<'a>, expr: &'a Expression) -> Result<(i64, Type), Trap> {
+        self.burn()?;
+        Ok(match expr {
+            Expression::IntLit { value, suffix, .. } => {
+                let ty = Type::of_int_literal(*value, *suffix);
+                (fit(&ty, *value as i64), ty)
+            }
+            // Plain `char` is signed, so `'\xff'` is -1.
+            Expression::CharLit { value, .. } => (i64::from(*value as i8), Type::Int),
+            Expression::StringLit { value, .. } => {
+                (self.string(value), Type::pointer_to(Type::Char))
+            }
+            Expression::Variable { name, .. } => match self.lookup(frame, name)? {
+                Binding::Constant(value) => (value, Type::Int),
+                // An array decays to the address of its first element.
+                Binding::Object(address, ty @ Type::Array(..)) => (address, ty.clone().decay()),
+                Binding::Object(address, ty) => (self.load(address, ty)?, ty.clone()),
+            },
+            Expression::Deref { .. } => {
+                let (address, ty) = self.place(frame, expr)?;
+                match ty {
+                    Type::Array(..) => (address, ty.decay()),
+                    _ => (self.load(address, &ty)?, ty),
+                }
+            }
+            Expression::AddressOf { operand, .. } => {
+                let (address, ty) = self.place(frame, operand)?;
+                (address, Type::pointer_to(ty))
+            }
+            Expression::SizeofExpr { operand, .. } => {
+                (size_of(&self.type_of(frame, operand)), Type::Int)
+            }
+            Expression::SizeofType { ty, .. } => (size_of(ty), Type::Int),
+            Expression::Assign { op, operands, .. } => {
+                // The value is evaluated before the target, as the
+                // lowering does.
+                let [target, value] = &**operands;
+                let value = self.eval(frame, value)?;
+                let (address, ty) = self.place(frame, target)?;
+                let (value, _) = match op {
+                    Some(op) => {
+                        let current = self.load(address, &ty)?;
+                        binary(*op, (current, ty.clone()), value)?
+                    }
+                    None => value,
+                };
+                let value = fit(&ty, value);
+                self.write(address, &ty, value);
+                (value, ty)
+            }
+            Expression::IncDec { op, operand, .. } => {
+                let (address, ty) = self.place(frame, operand)?;
+                let old = self.load(address, &ty)?;
+                let (new, _) = binary(op.binary_op(), (old, ty.clone()), (1, Type::Int))?;
+                let new = fit(&ty, new);
+                self.write(address, &ty, new);
+                (if op.is_postfix() { old } else { new }, ty)
+            }
+            Expression::Unary { op, operand, .. } => {
+                let (value, ty) = self.eval(frame, operand)?;
+                let ty = ty.promote();
+                match op {
+                    UnaryOp::Neg => (fit(&ty, value.wrapping_neg()), ty),
+                    UnaryOp::Not => (i64::from(value == 0), Type::Int),
+                    UnaryOp::Complement => (fit(&ty, !value), ty),
+                }
+            }
+            Expression::Binary { .. } => {
+                let (first, links) = expr.binary_chain();
+                let mut result = self.eval(frame, first)?;
+                for link in links {
+                    self.burn()?;
+                    let rhs = &link.operands[1];
+                    // The right operand of `&&` and `||` only runs if the
+                    // left leaves the result open.
+                    let lhs = result.0 != 0;
+                    result = match link.op {
+                        BinaryOp::LogicalAnd => {
+                            (i64::from(lhs && self.truth(frame, rhs)?), Type::Int)
+                        }
+                        BinaryOp::LogicalOr => {
+                            (i64::from(lhs || self.truth(frame, rhs)?), Type::Int)
+                        }
+                        op => {
+                            let rhs = self.eval(frame, rhs)?;
+                            binary(op, result, rhs)?
+                        }
+                    };
+                }
+                result
+            }
+            Expression::FunctionCall { name, args, .. } => self.eval_call(frame, name, args)?,
+            Expression::BuiltinCall { builtin, args, .. } => match builtin {
+                Builtin::Expect => {
+                    let [value, expected] = &**args else {
+                        unreachable!("analyzer checks the argument count");
+                    };
+                    let (value, _) = self.eval(frame, value)?;
+                    self.eval(frame, expected)?;
+                    (fit(&Type::Int, value), Type::Int)
+                }
+                Builtin::Trap => return Err(Trap::Abort),
+                Builtin::Unreachable => return Err(Trap::Unreachable),
+            },
+        })
+    }
+
+    /// Evaluates the arguments left to right, then calls `name` with them,
+    /// returning the result and its type.
+    fn eval_call(
+        &mut self,
+        frame: &mut Frame<'a>,
+        name: &str,
+        args: &'a [Expression],
+    ) -> Result<(i64, Type), Trap> {
+        let function = self.functions.get(name).copied();
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            values.push(self.eval(frame, arg)?.0);
+        }
+        let Some(function) = function else {
+            // An implicitly declared function (C89) that is never defined.
+            return Err(Trap::UnknownFunction(name.to_string()));
+        };
+        let result = self.call_function(function, &values)?;
+        Ok((result.unwrap_or(0), function.return_type.clone()))
+    }
+
+    /// Where the object an lvalue the analyzer accepted is stored, and its
+    /// type. Evaluates the address of a dereferenced pointer.
+    fn place(&mut self, frame: &mut Frame<'a>, expr: &'a Expression) -> Result<(i64, Type), Trap> {
+        match expr {
+            Expression::Variable { name, .. } => match self.lookup(frame, name)? {
+                Binding::Object(address, ty) => Ok((address, ty.clone())),
+                Binding::Constant(_) => unreachable!("the analyzer rejects assigning constants"),
+            },
+            Expression::Deref { operand, .. } => match self.eval(frame, operand)? {
+                (address, Type::Pointer(pointee)) => Ok((address, *pointee)),
+                _ => unreachable!("the analyzer only dereferences pointers"),
+            },
+            _ => unreachable!("the analyzer only accepts variables and dereferences as lvalues"),
+        }
+    }
+
+    /// What `name` stands for where `frame` is.
+    fn lookup(&self, frame: &Frame<'a>, name: &str) -> Result<Binding<'a>, Trap> {
+        if let Some(&binding) = frame.scope.get(name) {
+            return Ok(binding);
+        }
+        if let Some(&value) = self.constants.get(name) {
+            return Ok(Binding::Constant(value));
+        }
+        match self.globals.get(name) {
+            Some(&(address, ty)) => Ok(Binding::Object(address, ty)),
+            // Declared `extern`, but defined in another translation unit.
+            None => Err(Trap::UnknownGlobal(name.to_string())),
+        }
+    }
+
+    /// The type of `expr` before any decay, as `sizeof` sees it, worked out
+    /// without evaluating it.
+    fn type_of(&self, frame: &Frame<'a>, expr: &Expression) -> Type {
+        match expr {
+            Expression::StringLit { value, .. } => {
+                Type::Array(Box::new(Type::Char), value.len() as u32 + 1)
+            }
+            Expression::Variable { name, .. } => match self.lookup(frame, name) {
+                Ok(Binding::Object(_, ty)) => ty.clone(),
+                Ok(Binding::Constant(_)) => Type::Int,
+                Err(_) => unreachable!("the analyzer checks every name is declared"),
+            },
+            Expression::AddressOf { operand, .. } => Type::pointer_to(self.type_of(frame, operand)),
+            Expression::Deref { operand, .. } => match self.type_of(frame, operand).decay() {
+                Type::Pointer(pointee) => *pointee,
+                _ => unreachable!("the analyzer only dereferences pointers"),
+            },
+            Expression::Assign { operands, .. } => self.type_of(frame, &operands[0]),
+            Expression::IncDec { operand, .. } => self.type_of(frame, operand),
+            Expression::Unary { ty, .. } | Expression::Binary { ty, .. } => ty
+                .get()
+                .cloned()
+                .expect("the analyzer types every operator"),
+            Expression::FunctionCall { name, .. } => self
+                .functions
+                .get(name.as_str())
+                .map_or(Type::Int, |function| function.return_type.clone()),
+            Expression::BuiltinCall { builtin, .. } => builtin.signature().1,
+            Expression::IntLit { value, suffix, .. } => Type::of_int_literal(*value, *suffix),
+            Expression::CharLit { .. }
+            | Expression::SizeofExpr { .. }
+            | Expression::SizeofType { .. } => Type::Int,
+        }
+    }
+
+    /// The object `declaration` declares in `frame`, carved from the stack
+    /// the first time it runs.
+    fn object(
+        &mut self,
+        frame: &mut Frame<'a>,
+        declaration: &'a Statement,
+        ty: &Type,
+    ) -> Result<i64, Trap> {
+        let key = declaration as *const Statement;
+        if let Some(&address) = frame.objects.get(&key) {
+            return Ok(address);
+        }
+        let address = self.allocate(ty)?;
+        frame.objects.insert(key, address);
+        Ok(address)
+    }
+
+    /// Room for a `ty` on the stack, given back when the function returns.
+    fn allocate(&mut self, ty: &Type) -> Result<i64, Trap> {
+        let align = ty
+            .align(MODEL)
+            .expect("the analyzer rejects `void` objects") as i64;
+        self.stack_pointer = (self.stack_pointer - size_of(ty)) & -align;
+        if self.stack_pointer < GLOBALS_BASE {
+            return Err(Trap::StackOverflow);
+        }
+        Ok(self.stack_pointer)
+    }
+
+    /// The address of a NUL-terminated copy of `value`, shared by every
+    /// literal with the same contents.
+    fn string(&mut self, value: &'a [u8]) -> i64 {
+        if let Some(&address) = self.strings.get(value) {
+            return address;
+        }
+        let address = self.next_string;
+        for (offset, &byte) in value.iter().chain([&0]).enumerate() {
+            self.memory.insert(address + offset as i64, byte);
+        }
+        self.next_string += value.len() as i64 + 1;
+        self.strings.insert(value, address);
+        address
+    }
+
+    /// Reads the `ty` stored at `address`.
+    fn load(&self, address: i64, ty: &Type) -> Result<i64, Trap> {
+        check_address(address)?;
+        let mut value = 0u64;
+        for offset in (0..size_of(ty)).rev() {
+            let byte = self.memory.get(&(address + offset)).ok_or_else(|| {
+                Trap::UndefinedBehavior(format!("read of uninitialized memory at {address:#x}"))
+            })?;
+            value = value << 8 | u64::from(*byte);
+        }
+        Ok(fit(ty, value as i64))
+    }
+
+    /// Stores `value` at `address` as a `ty`, least significant byte first.
+    fn write(&mut self, address: i64, ty: &Type, value: i64) {
+        for offset in 0..size_of(ty) {
+            self.memory
+                .insert(address + offset, (value >> (8 * offset)) as u8);
+        }
+    }
+
+    /// Spends one unit of fuel.
+    fn burn(&mut self) -> Result<(), Trap> {
+        self.fuel = self.fuel.checked_sub(1).ok_or(Trap::OutOfFuel)?;
+        Ok(())
+    }
+}
+
+/// `lhs op rhs`, for any operator but `&&` and `||`.
+fn binary(
+    op: BinaryOp,
+    (lhs, left): (i64, Type),
+    (rhs, right): (i64, Type),
+) -> Result<(i64, Type), Trap> {
+    match (op, &left, &right) {
+        (BinaryOp::Add | BinaryOp::Sub, Type::Pointer(pointee), count) if count.is_integer() => {
+            let offset = rhs.wrapping_mul(size_of(pointee));
+            let address = match op {
+                BinaryOp::Add => lhs.wrapping_add(offset),
+                _ => lhs.wrapping_sub(offset),
+            };
+            return Ok((address, left));
+        }
+        (BinaryOp::Add, count, Type::Pointer(pointee)) if count.is_integer() => {
+            return Ok((rhs.wrapping_add(lhs.wrapping_mul(size_of(pointee))), right));
+        }
+        // The distance in elements between two pointers into the same
+        // object, a `long`.
+        (BinaryOp::Sub, Type::Pointer(pointee), Type::Pointer(_)) => {
+            let elements = lhs.wrapping_sub(rhs) / size_of(pointee);
+            return Ok((elements, Type::Long));
+        }
+        // Addresses compare unsigned.
+        _ if matches!(left, Type::Pointer(_)) || matches!(right, Type::Pointer(_)) => {
+            return Ok((compare(op, lhs as u64, rhs as u64), Type::Int));
+        }
+        _ => {}
+    }
+    // A shift keeps the type of its left operand; other operators convert
+    // both sides to a common type.
+    let ty = match op {
+        BinaryOp::Shl | BinaryOp::Shr => left.promote(),
+        _ => Type::common(&left, &right, MODEL),
+    };
+    let (lhs, rhs) = (fit(&ty, lhs), fit(&ty, rhs));
+    let unsigned = ty.is_unsigned();
+    let bits = size_of(&ty) * 8;
+    let value = match op {
+        BinaryOp::Add => lhs.wrapping_add(rhs),
+        BinaryOp::Sub => lhs.wrapping_sub(rhs),
+        BinaryOp::Mul => lhs.wrapping_mul(rhs),
+        BinaryOp::Div | BinaryOp::Rem => {
+            if rhs == 0 {
+                return Err(Trap::UndefinedBehavior(format!(
+                    "{lhs} {op} 0",
+                    op = op.as_str()
+                )));
+            }
+            if unsigned {
+                let (lhs, rhs) = (lhs as u64, rhs as u64);
+                (if op == BinaryOp::Div {
+                    lhs / rhs
+                } else {
+                    lhs % rhs
+                }) as i64
+            } else if rhs == -1 && lhs == -1 << (bits - 1) {
+                return Err(Trap::UndefinedBehavior(format!(
+                    "{lhs} {op} -1 overflows",
+                    op = op.as_str()
+                )));
+            } else if op == BinaryOp::Div {
+                lhs / rhs
+            } else {
+                lhs % rhs
+            }
+        }
+        BinaryOp::BitAnd => lhs & rhs,
+        BinaryOp::BitOr => lhs | rhs,
+        BinaryOp::BitXor => lhs ^ rhs,
+        BinaryOp::Shl | BinaryOp::Shr => {
+            // A negative count is as out of range as one too large.
+            let count = rhs as u64;
+            if count >= bits as u64 {
+                return Err(Trap::UndefinedBehavior(format!(
+                    "{lhs} {op} {rhs} shifts by more than {ty} has bits",
+                    op = op.as_str()
+                )));
+            }
+            match op {
+                BinaryOp::Shl => lhs << count,
+                _ if unsigned => ((lhs as u64) >> count) as i64,
+                _ => lhs >> count,
+            }
+        }
+        _ if unsigned => return Ok((compare(op, lhs as u64, rhs as u64), Type::Int)),
+        _ => return Ok((compare(op, lhs, rhs), Type::Int)),
+    };
+    Ok((fit(&ty, value), ty))
+}
+
+/// 1 if the comparison `op` holds between `lhs` and `rhs`, 0 otherwise.
+fn compare<T: Ord>(op: BinaryOp, lhs: T, rhs: T) -> i64 {
+    let holds = match op {
+        BinaryOp::Lt => lhs < rhs,
+        BinaryOp::Le => lhs <= rhs,
+        BinaryOp::Gt => lhs > rhs,
+        BinaryOp::Ge => lhs >= rhs,
+        BinaryOp::Eq => lhs == rhs,
+        BinaryOp::Ne => lhs != rhs,
+        _ => unreachable!("the analyzer rejects {op:?} here"),
+    };
+    i64::from(holds)
+}
+
+/// `value` converted to `ty`: truncated to its width, then sign-extended
+/// if it is signed and zero-extended if not. `unsigned long` and pointers
+/// keep all 64 bits.
+fn fit(ty: &Type, value: i64) -> i64 {
+    match ty {
+        Type::Char => i64::from(value as i8),
+        Type::UChar => i64::from(value as u8),
+        Type::Short => i64::from(value as i16),
+        Type::UShort => i64::from(value as u16),
+        Type::Int => i64::from(value as i32),
+        Type::UInt => i64::from(value as u32),
+        _ => value,
+    }
+}
+
+fn size_of(ty: &Type) -> i64 {
+    ty.size(MODEL).expect("the analyzer rejects `void` objects") as i64
+}
+
+fn align_of(ty: &Type) -> i64 {
+    ty.align(MODEL)
+        .expect("the analyzer rejects `void` objects") as i64
+}
+
+/// The value of each enumerator of `decl`, which the analyzer checked
+/// are integer constants. `outer` gives the constants already in scope.
+fn enumerator_values(decl: &Enum, outer: impl Fn(&str) -> Option<i64>) -> Vec<(&str, i64)> {
+    let mut values: Vec<(&str, i64)> = Vec::new();
+    let mut next = 0;
+    for enumerator in &decl.enumerators {
+        let value = match &enumerator.value {
+            Some(value) => value
+                .constant_value(
+                    &|name| {
+                        let earlier = values.iter().rev().find(|(earlier, _)| *earlier == name);
+                        earlier.map(|&(_, value)| value).or_else(|| outer(name))
+                    },
+                    MODEL,
+                )
+                .expect("the analyzer checked enumerator values are constant"),
+            None => next,
+        };
+        values.push((&enumerator.name, value));
+        next = value + 1;
+    }
+    values
+}
+
+/// Whether `target` is `statement` or a statement inside it.
+fn contains(statement: &Statement, target: Target) -> bool {
+    let found = match target {
+        Target::Label(name) => {
+            matches!(statement, Statement::Label { name: label, .. } if label == name)
+        }
+        Target::Case(case) => ptr::eq(statement, case),
+    };
+    found
+        || match statement {
+            Statement::Block { body, .. } => body.iter().any(|s| contains(s, target)),
+            Statement::If {
+                then, otherwise, ..
+            } => {
+                contains(then, target)
+                    || otherwise
+                        .as_deref()
+                        .is_some_and(|otherwise| contains(otherwise, target))
+            }
+            Statement::Label { body, .. }
+            | Statement::While { body, .. }
+            | Statement::DoWhile { body, .. }
+            | Statement::For { body, .. }
+            | Statement::Switch { body, .. }
+            | Statement::Case { body, .. }
+            | Statement::Default { body, .. } => contains(body, target),
+            _ => false,
+        }
+}
+
+/// The `case` in the body of a `switch` whose value `matches`, or else its
+/// `default`, leaving out those of any `switch` inside it. `constant`
+/// gives the enumeration constants in scope.
+fn switch_target<'a>(
+    body: &'a Statement,
+    matches: &impl Fn(i64) -> bool,
+    constant: &impl Fn(&str) -> Option<i64>,
+) -> Option<&'a Statement> {
+    fn walk<'a>(statement: &'a Statement, labels: &mut Vec<&'a Statement>) {
+        match statement {
+            Statement::Case { body, .. } | Statement::Default { body, .. } => {
+                labels.push(statement);
+                walk(body, labels);
+            }
+            Statement::Block { body, .. } => body.iter().for_each(|s| walk(s, labels)),
+            Statement::If {
+                then, otherwise, ..
+            } => {
+                walk(then, labels);
+                if let Some(otherwise) = otherwise {
+                    walk(otherwise, labels);
+                }
+            }
+            Statement::Label { body, .. }
+            | Statement::While { body, .. }
+            | Statement::DoWhile { body, .. }
+            | Statement::For { body, .. } => walk(body, labels),
+            _ => {}
+        }
+    }
+    let mut labels = Vec::new();
+    walk(body, &mut labels);
+    let case = labels.iter().find(|label| match label {
+        Statement::Case { value, .. } => matches(
+            value
+                .constant_value(constant, MODEL)
+                .expect("the analyzer checked case values are constant"),
+        ),
+        _ => false,
+    });
+    case.or_else(|| {
+        labels
+            .iter()
+            .find(|label| matches!(label, Statement::Default { .. }))
+    })
+    .copied()
+}
+
+/// Traps on dereferencing a null pointer, which nothing is stored at.
+fn check_address(address: i64) -> Result<(), Trap> {
+    if address == 0 {
+        return Err(Trap::UndefinedBehavior(
+            "dereference of a null pointer".to_string(),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,399 @@
+//! The syntax tree as JSON (`--emit=ast-json`), for tools that would
+//! rather not parse C.
+//!
+//! The output is an object with the program's `enums`, `globals` and
+//! `functions`, one per line. Every statement and expression is an object
+//! whose `kind` says what it is, such as `"return"` or `"binary"`, and
+//! whose other members are its parts. Positions are `{"line", "column"}`
+//! objects, types are written as in C (`"unsigned int *"`), and operators
+//! by their spelling (`"+="`). Members are only ever added, never renamed
+//! or removed, so a tool written against this schema keeps working.
+
+use crate::ast::{
+    AsmOperand, Enum, Expression, Function, Global, Initializer, Param, Program, Statement, Type,
+};
+use crate::json::Value;
+
+/// `program` as JSON text, with each enum, global and function on a line
+/// of its own.
+pub fn emit(program: &Program) -> String {
+    let Value::Object(sections) = self::program(program) else {
+        unreachable!("a program is an object");
+    };
+    let sections: Vec<String> = sections
+        .iter()
+        .map(|(name, items)| match items.as_array() {
+            Some([]) | None => format!("  \"{name}\": []"),
+            Some(items) => {
+                let lines: Vec<String> = items.iter().map(|item| format!("    {item}")).collect();
+                format!("  \"{name}\": [\n{}\n  ]", lines.join(",\n"))
+            }
+        })
+        .collect();
+    format!("{{\n{}\n}}\n", sections.join(",\n"))
+}
+
+/// `program` as a JSON value: `{"enums", "globals", "functions"}`.
+pub fn program(program: &Program) -> Value {
+    Value::object([
+        ("enums", program.enums.iter().map(enum_decl).collect()),
+        ("globals", program.globals.iter().map(global).collect()),
+        (
+            "functions",
+            program.functions.iter().map(function).collect(),
+        ),
+    ])
+}
+
+/// `{"name", "return_type", "params", "variadic", "prototyped", "body",
+/// "pos"}`, with a `null` body for a prototype.
+fn function(function: &Function) -> Value {
+    Value::object([
+        ("name", function.name.as_str().into()),
+        ("return_type", function.return_type.to_string().into()),
+        ("params", function.params.iter().map(param).collect()),
+        ("variadic", function.variadic.into()),
+        ("prototyped", function.prototyped.into()),
+        (
+            "body",
+            function
+                .body
+                .as_ref()
+                .map(|body| body.iter().map(statement).collect::<Value>())
+                .into(),
+        ),
+        ("pos", function.pos.into()),
+    ])
+}
+
+/// `{"name", "type", "pos"}`, the name `null` in a prototype that leaves
+/// it out.
+fn param(param: &Param) -> Value {
+    Value::object([
+        ("name", param.name.as_deref().into()),
+        ("type", param.ty.to_string().into()),
+        ("pos", param.pos.into()),
+    ])
+}
+
+/// `{"tag", "enumerators": [{"name", "value", "pos"}], "pos"}`.
+fn enum_decl(decl: &Enum) -> Value {
+    let enumerators = decl
+        .enumerators
+        .iter()
+        .map(|enumerator| {
+            Value::object([
+                ("name", enumerator.name.as_str().into()),
+                ("value", enumerator.value.as_ref().map(expression).into()),
+                ("pos", enumerator.pos.into()),
+            ])
+        })
+        .collect();
+    Value::object([
+        ("tag", decl.tag.as_deref().into()),
+        ("enumerators", enumerators),
+        ("pos", decl.pos.into()),
+    ])
+}
+
+/// `{"name", "type", "init", "thread_local", "extern", "pos"}`.
+fn global(global: &Global) -> Value {
+    Value::object([
+        ("name", global.name.as_str().into()),
+        ("type", global.ty.to_string().into()),
+        ("init", global.init.as_ref().map(initializer).into()),
+        ("thread_local", global.thread_local.into()),
+        ("extern", global.is_extern.into()),
+        ("pos", global.pos.into()),
+    ])
+}
+
+/// An expression, or `{"kind": "list", "elements", "pos"}` for a braced
+/// list.
+fn initializer(init: &Initializer) -> Value {
+    match init {
+        Initializer::Expr(expr) => expression(expr),
+        Initializer::List { elements, pos } => node(
+            "list",
+            [
+                ("elements", elements.iter().map(initializer).collect()),
+                ("pos", (*pos).into()),
+            ],
+        ),
+    }
+}
+
+/// An object with `kind` first and then `members`.
+fn node<const N: usize>(kind: &str, members: [(&str, Value); N]) -> Value {
+    Value::object([("kind", kind.into())].into_iter().chain(members))
+}
+
+fn statement(statement: &Statement) -> Value {
+    match statement {
+        Statement::Return { value, pos } => node(
+            "return",
+            [
+                ("value", value.as_ref().map(expression).into()),
+                ("pos", (*pos).into()),
+            ],
+        ),
+        Statement::Declaration {
+            name,
+            ty,
+            init,
+            pos,
+        } => node(
+            "declaration",
+            [
+                ("name", name.as_str().into()),
+                ("type", ty.to_string().into()),
+                ("init", init.as_ref().map(initializer).into()),
+                ("pos", (*pos).into()),
+            ],
+        ),
+        Statement::Expression { expr, pos } => node(
+            "expression",
+            [("expr", expression(expr)), ("pos", (*pos).into())],
+        ),
+        Statement::Enum(decl) => node("enum", [("decl", enum_decl(decl))]),
+        Statement::Block { body, pos } => node(
+            "block",
+            [
+                ("body", body.iter().map(self::statement).collect()),
+                ("pos", (*pos).into()),
+            ],
+        ),
+        Statement::If {
+            cond,
+            then,
+            otherwise,
+            pos,
+        } => node(
+            "if",
+            [
+                ("cond", expression(cond)),
+                ("then", self::statement(then)),
+                (
+                    "else",
+                    otherwise.as_deref().map_or(Value::Null, self::statement),
+                ),
+                ("pos", (*pos).into()),
+            ],
+        ),
+        Statement::While { cond, body, pos } => node(
+            "while",
+            [
+                ("cond", expression(cond)),
+                ("body", self::statement(body)),
+                ("pos", (*pos).into()),
+            ],
+        ),
+        Statement::DoWhile { body, cond, pos } => node(
+            "do_while",
+            [
+                ("body", self::statement(body)),
+                ("cond", expression(cond)),
+                ("pos", (*pos).into()),
+            ],
+        ),
+        Statement::For {
+            init,
+            cond,
+            step,
+            body,
+            pos,
+        } => node(
+            "for",
+            [
+                ("init", init.as_deref().map(self::statement).into()),
+                ("cond", cond.as_ref().map(expression).into()),
+                ("step", step.as_ref().map(expression).into()),
+                ("body", self::statement(body)),
+                ("pos", (*pos).into()),
+            ],
+        ),
+        Statement::Label { name, body, pos } => node(
+            "label",
+            [
+                ("name", name.as_str().into()),
+                ("body", self::statement(body)),
+                ("pos", (*pos).into()),
+            ],
+        ),
+        Statement::Empty { pos } => node("empty", [("pos", (*pos).into())]),
+        Statement::Goto { label, pos } => node(
+            "goto",
+            [("label", label.as_str().into()), ("pos", (*pos).into())],
+        ),
+        Statement::Switch { cond, body, pos } => node(
+            "switch",
+            [
+                ("cond", expression(cond)),
+                ("body", self::statement(body)),
+                ("pos", (*pos).into()),
+            ],
+        ),
+        Statement::Case { value, body, pos } => node(
+            "case",
+            [
+                ("value", expression(value)),
+                ("body", self::statement(body)),
+                ("pos", (*pos).into()),
+            ],
+        ),
+        Statement::Default { body, pos } => node(
+            "default",
+            [("body", self::statement(body)), ("pos", (*pos).into())],
+        ),
+        Statement::Break { pos } => node("break", [("pos", (*pos).into())]),
+        Statement::Asm {
+            template,
+            outputs,
+            inputs,
+            clobbers,
+            pos,
+        } => node(
+            "asm",
+            [
+                ("template", template.as_str().into()),
+                ("outputs", outputs.iter().map(asm_operand).collect()),
+                ("inputs", inputs.iter().map(asm_operand).collect()),
+                (
+                    "clobbers",
+                    clobbers.iter().map(|c| Value::from(c.as_str())).collect(),
+                ),
+                ("pos", (*pos).into()),
+            ],
+        ),
+    }
+}
+
+/// `{"constraint", "expr", "pos"}`.
+fn asm_operand(operand: &AsmOperand) -> Value {
+    Value::object([
+        ("constraint", operand.constraint.as_str().into()),
+        ("expr", expression(&operand.expr)),
+        ("pos", operand.pos.into()),
+    ])
+}
+
+fn expression(expr: &Expression) -> Value {
+    let operand = |operand: &Expression| expression(operand);
+    let list = |exprs: &[Expression]| exprs.iter().map(expression).collect::<Value>();
+    match expr {
+        Expression::IntLit { value, suffix, pos } => node(
+            "integer",
+            [
+                ("value", Value::Number(*value as f64)),
+                (
+                    "type",
+                    Type::of_int_literal(*value, *suffix).to_string().into(),
+                ),
+                ("pos", (*pos).into()),
+            ],
+        ),
+        Expression::CharLit { value, pos } => node(
+            "character",
+            [("value", u32::from(*value).into()), ("pos", (*pos).into())],
+        ),
+        // Escapes may make bytes that aren't UTF-8, which are replaced.
+        Expression::StringLit { value, pos } => node(
+            "string",
+            [
+                ("value", String::from_utf8_lossy(value).into_owned().into()),
+                ("pos", (*pos).into()),
+            ],
+        ),
+        Expression::Variable { name, pos } => node(
+            "variable",
+            [("name", name.as_str().into()), ("pos", (*pos).into())],
+        ),
+        Expression::Unary {
+            op,
+            operand: x,
+            pos,
+            ..
+        } => node(
+            "unary",
+            [
+                ("op", op.as_str().into()),
+                ("operand", operand(x)),
+                ("pos", (*pos).into()),
+            ],
+        ),
+        Expression::IncDec {
+            op,
+            operand: x,
+            pos,
+        } => node(
+            "inc_dec",
+            [
+                ("op", op.as_str().into()),
+                ("postfix", op.is_postfix().into()),
+                ("operand", operand(x)),
+                ("pos", (*pos).into()),
+            ],
+        ),
+        // Built from the innermost operator out.
+        Expression::Binary { .. } => {
+            let (first, links) = expr.binary_chain();
+            links.iter().fold(operand(first), |lhs, link| {
+                node(
+                    "binary",
+                    [
+                        ("op", link.op.as_str().into()),
+                        ("lhs", lhs),
+                        ("rhs", operand(&link.operands[1])),
+                        ("pos", link.pos.into()),
+                    ],
+                )
+            })
+        }
+        Expression::AddressOf { operand: x, pos } => node(
+            "address_of",
+            [("operand", operand(x)), ("pos", (*pos).into())],
+        ),
+        Expression::Deref { operand: x, pos } => {
+            node("deref", [("operand", operand(x)), ("pos", (*pos).into())])
+        }
+        Expression::Assign {
+            op, operands, pos, ..
+        } => node(
+            "assign",
+            [
+                (
+                    "op",
+                    op.map_or("=".to_string(), |op| format!("{}=", op.as_str()))
+                        .into(),
+                ),
+                ("target", operand(&operands[0])),
+                ("value", operand(&operands[1])),
+                ("pos", (*pos).into()),
+            ],
+        ),
+        Expression::FunctionCall { name, args, pos } => node(
+            "call",
+            [
+                ("name", name.as_str().into()),
+                ("args", list(args)),
+                ("pos", (*pos).into()),
+            ],
+        ),
+        Expression::BuiltinCall { builtin, args, pos } => node(
+            "builtin_call",
+            [
+                ("name", builtin.name().into()),
+                ("args", list(args)),
+                ("pos", (*pos).into()),
+            ],
+        ),
+        Expression::SizeofExpr { operand: x, pos } => node(
+            "sizeof_expr",
+            [("operand", operand(x)), ("pos", (*pos).into())],
+        ),
+        Expression::SizeofType { ty, pos } => node(
+            "sizeof_type",
+            [("type", ty.to_string().into()), ("pos", (*pos).into())],
+        ),
+    }
+}
@@ -0,0 +1,229 @@
+//! The incremental compilation cache (`.rcc-cache`).
+//!
+//! Compiling an input that hasn't changed gives the same outputs as last
+//! time, so they are kept in a directory, one file per compilation, named
+//! by a [`Key`]: a hash of which build of the compiler made them and of
+//! everything else the outputs depend on, such as the preprocessed source
+//! and the flags.
+//! An input whose key has an entry isn't lexed, parsed or compiled again;
+//! its outputs are read back instead. Only successful compilations are
+//! kept, so errors are reported every time.
+//!
+//! Nothing is ever removed; deleting the directory empties the cache.
+
+use std::fmt;
+use std::fs;
+use std::hash::{DefaultHasher, Hasher};
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+/// Where the cache is kept, in the directory rcc runs in.
+pub const DIR: &str = ".rcc-cache";
+
+/// The first line of every entry, which changes when the format does.
+const HEADER: &str = "rcc-cache 1\n";
+
+/// The hash that names a compilation's entry: 128-bit FNV-1a over the
+/// build of rcc and each part given, so that a different build never reads
+/// another's outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key(u128);
+
+impl Key {
+    pub fn new<'a>(parts: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let mut hash = 0x6c62_272e_07bb_0142_62b8_2175_6295_c58d;
+        for part in [build_id()].into_iter().chain(parts) {
+            // Each part is preceded by its length, so that moving bytes
+            // from one part to the next changes the key.
+            let len = (part.len() as u64).to_le_bytes();
+            for &byte in len.iter().chain(part) {
+                hash = (hash ^ u128::from(byte))
+                    .wrapping_mul(0x0000_0000_0100_0000_0000_0000_0000_013b);
+            }
+        }
+        Key(hash)
+    }
+}
+
+/// What tells this build of rcc from every other: its version and the size
+/// and modification time of its executable, looked up once. The version
+/// alone isn't enough, since rebuilding rcc with a change doesn't bump it,
+/// and reading the whole executable would take longer than many of the
+/// compilations it saves. If the executable can't be looked up, the id is
+/// made from the process and the time instead, so that nothing is shared.
+fn build_id() -> &'static [u8] {
+    static ID: OnceLock<[u8; 8]> = OnceLock::new();
+    ID.get_or_init(|| {
+        let mut hasher = DefaultHasher::new();
+        hasher.write(env!("CARGO_PKG_VERSION").as_bytes());
+        let exe = std::env::current_exe().and_then(fs::metadata);
+        let (len, time) = exe
+            .and_then(|exe| Ok((exe.len(), exe.modified()?)))
+            .unwrap_or_else(|err| {
+                crate::log!(Warn, "cache", "can't look up rcc's executable: {err}");
+                (u64::from(std::process::id()), SystemTime::now())
+            });
+        hasher.write_u64(len);
+        let time = time.duration_since(SystemTime::UNIX_EPOCH);
+        hasher.write_u128(time.map_or(0, |time| time.as_nanos()));
+        hasher.finish().to_le_bytes()
+    })
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+/// The outputs of one compilation, each named by what it is, such as the
+/// extension it is written with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Entry {
+    outputs: Vec<(String, String)>,
+}
+
+impl Entry {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.outputs
+            .iter()
+            .find(|(output, _)| output == name)
+            .map(|(_, text)| text.as_str())
+    }
+
+    /// Records `text` as the output `name`, replacing any before it.
+    pub fn insert(&mut self, name: &str, text: String) {
+        match self.outputs.iter_mut().find(|(output, _)| output == name) {
+            Some((_, old)) => *old = text,
+            None => self.outputs.push((name.to_string(), text)),
+        }
+    }
+
+    /// The entry as it is stored: the header, then each output as its name
+    /// and length in bytes on a line, followed by its text.
+    pub fn encode(&self) -> String {
+        let mut out = HEADER.to_string();
+        for (name, text) in &self.outputs {
+            out.push_str(&format!("{name} {}\n{text}", text.len()));
+        }
+        out
+    }
+
+    /// Reads back what [`Entry::encode`] wrote, or `None` if it is damaged
+    /// or from another version of the format.
+    pub fn decode(mut stored: &str) -> Option<Self> {
+        stored = stored.strip_prefix(HEADER)?;
+        let mut entry = Entry::default();
+        while !stored.is_empty() {
+            let (line, rest) = stored.split_once('\n')?;
+            let (name, len) = line.rsplit_once(' ')?;
+            let len = len.parse().ok()?;
+            let text = rest.get(..len)?;
+            entry.insert(name, text.to_string());
+            stored = &rest[len..];
+        }
+        Some(entry)
+    }
+}
+
+/// A cache directory, shared by the inputs compiled at once, and what it
+/// has been used for.
+pub struct Cache {
+    dir: PathBuf,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    stored: AtomicUsize,
+}
+
+impl Cache {
+    /// The cache in `dir`, which is created when the first entry is stored.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+            stored: AtomicUsize::new(0),
+        }
+    }
+
+    /// The entry for `key`, if there is a readable one.
+    pub fn get(&self, key: Key) -> Option<Entry> {
+        let entry = fs::read_to_string(self.path(key))
+            .ok()
+            .and_then(|stored| Entry::decode(&stored));
+        let counter = if entry.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        crate::log!(
+            Debug,
+            "cache",
+            "{} for {key}",
+            if entry.is_some() { "hit" } else { "miss" }
+        );
+        entry
+    }
+
+    /// Stores `entry` for `key`. It is written to a temporary file and
+    /// renamed into place, so that an interrupted write or another rcc
+    /// reading at the same time never sees half of it.
+    pub fn put(&self, key: Key, entry: &Entry) -> io::Result<()> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        fs::create_dir_all(&self.dir)?;
+        let temp = self.dir.join(format!(
+            "{key}.{}.{}.tmp",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&temp, entry.encode())?;
+        if let Err(err) = fs::rename(&temp, self.path(key)) {
+            let _ = fs::remove_file(&temp);
+            return Err(err);
+        }
+        self.stored.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// How the cache has been used since the last call, starting the count
+    /// again.
+    pub fn take_stats(&self) -> Stats {
+        Stats {
+            hits: self.hits.swap(0, Ordering::Relaxed),
+            misses: self.misses.swap(0, Ordering::Relaxed),
+            stored: self.stored.swap(0, Ordering::Relaxed),
+        }
+    }
+
+    fn path(&self, key: Key) -> PathBuf {
+        self.dir.join(key.to_string())
+    }
+}
+
+/// How many inputs the cache had outputs for, how many it didn't, and how
+/// many entries were stored.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub hits: usize,
+    pub misses: usize,
+    pub stored: usize,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = |count: usize, one: &str, many: &str| {
+            format!("{count} {}", if count == 1 { one } else { many })
+        };
+        write!(
+            f,
+            "{}, {}, {} stored",
+            plural(self.hits, "hit", "hits"),
+            plural(self.misses, "miss", "misses"),
+            plural(self.stored, "entry", "entries")
+        )
+    }
+}
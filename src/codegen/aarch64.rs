@@ -0,0 +1,928 @@
+//! AArch64 instruction selection, for Apple's arm64 (Mach-O) and for Linux
+//! (ELF).
+//!
+//! Values live wherever the register allocator put them; spilled values get a
+//! stack slot of their size below the frame pointer and constants are
+//! rematerialized at each use. Stack slots sit below the spills and are
+//! addressed relative to `x29`. `i32` values use `w` registers and pointers
+//! the `x` view of the same register.
+
+use super::asm::{imm, mem, reg, sym, Address, Assembly, Instruction, Operand, Syntax};
+use super::regalloc::{Location, RegisterSet};
+use super::{
+    asciz, data_section, global_data, macho_thread_local, peephole, string_label, Backend,
+    CallingConvention, Emitter, ObjectFormat, Visibility,
+};
+use crate::ir::{BinOp, CastOp, CmpOp, Function, Global, Instr, IrType, StringPool, Value};
+
+/// Registers used to pass the first integer arguments.
+const ARG_REGS: [&str; 8] = ["w0", "w1", "w2", "w3", "w4", "w5", "w6", "w7"];
+
+const CALLING_CONVENTION: CallingConvention = CallingConvention {
+    arg_regs: ARG_REGS.len(),
+    // AAPCS64 gives each stack argument an 8-byte slot.
+    stack_arg_size: 8,
+    // Saved registers are stored in pairs.
+    save_area_align: 16,
+    variadic_on_stack: false,
+};
+
+/// Offset from `x29` of the first stack-passed parameter, just above the
+/// saved frame pointer and link register.
+const INCOMING_ARGS_OFFSET: u32 = 16;
+
+/// Registers handed out by the register allocator. `w8`, `w9` and `w16` are
+/// kept free as scratch registers for operands and results, and `x18` is
+/// reserved by the platform.
+const ALLOCATABLE_REGS: RegisterSet<'static> = RegisterSet {
+    caller_saved: &[10, 11, 12, 13, 14, 15],
+    callee_saved: &[19, 20, 21, 22, 23, 24, 25, 26, 27, 28],
+};
+
+pub struct Aarch64 {
+    format: ObjectFormat,
+}
+
+impl Aarch64 {
+    pub const APPLE: Self = Self {
+        format: ObjectFormat::MachO,
+    };
+    pub const LINUX: Self = Self {
+        format: ObjectFormat::Elf,
+    };
+}
+
+impl Aarch64 {
+    /// The operand of `adrp` that yields the 4 KiB page holding `symbol`.
+    fn page(&self, symbol: &str) -> String {
+        match self.format {
+            ObjectFormat::MachO => format!("{symbol}@PAGE"),
+            ObjectFormat::Elf => symbol.to_string(),
+        }
+    }
+
+    /// The immediate that adds the offset of `symbol` within its page.
+    fn page_offset(&self, symbol: &str) -> String {
+        match self.format {
+            ObjectFormat::MachO => format!("{symbol}@PAGEOFF"),
+            ObjectFormat::Elf => format!(":lo12:{symbol}"),
+        }
+    }
+
+    /// Loads the address of `symbol` from its GOT entry into `dst`.
+    fn got_entry(&self, e: &mut Emitter, dst: &str, symbol: &str) {
+        let (page, offset) = match self.format {
+            ObjectFormat::MachO => (format!("{symbol}@GOTPAGE"), format!("{symbol}@GOTPAGEOFF")),
+            ObjectFormat::Elf => (format!(":got:{symbol}"), format!(":got_lo12:{symbol}")),
+        };
+        emit!(e, "adrp", reg(dst), sym(page));
+        let entry = Operand::Mem(Address::Symbol {
+            base: dst.to_string(),
+            symbol: offset,
+        });
+        emit!(e, "ldr", reg(dst), entry);
+    }
+
+    /// Computes the address of this thread's instance of the thread-local
+    /// `name` into `dst`. ELF adds the variable's offset to the thread
+    /// pointer in `tpidr_el0`, a link-time constant for one defined here
+    /// (local-exec) and read from the GOT otherwise (initial-exec); Mach-O
+    /// calls the accessor in the variable's descriptor, which returns the
+    /// address in `x0`.
+    fn thread_local_addr(&self, e: &mut Emitter, dst: &str, name: &str) {
+        let symbol = self.format.symbol(name);
+        match self.format {
+            ObjectFormat::Elf if e.pic || !e.defined_globals.contains(name) => {
+                emit!(e, "adrp", reg(dst), sym(format!(":gottprel:{symbol}")));
+                let entry = Operand::Mem(Address::Symbol {
+                    base: dst.to_string(),
+                    symbol: format!(":gottprel_lo12:{symbol}"),
+                });
+                emit!(e, "ldr", reg(dst), entry);
+                emit!(e, "mrs", reg("x16"), sym("tpidr_el0"));
+                emit!(e, "add", reg(dst), reg("x16"), reg(dst));
+            }
+            ObjectFormat::Elf => {
+                emit!(e, "mrs", reg(dst), sym("tpidr_el0"));
+                let high = sym(format!(":tprel_hi12:{symbol}"));
+                emit!(e, "add", reg(dst), reg(dst), high);
+                let low = sym(format!(":tprel_lo12_nc:{symbol}"));
+                emit!(e, "add", reg(dst), reg(dst), low);
+            }
+            ObjectFormat::MachO => {
+                emit!(e, "adrp", reg("x0"), sym(format!("{symbol}@TLVPPAGE")));
+                let descriptor = Operand::Mem(Address::Symbol {
+                    base: "x0".to_string(),
+                    symbol: format!("{symbol}@TLVPPAGEOFF"),
+                });
+                emit!(e, "ldr", reg("x0"), descriptor);
+                emit!(e, "ldr", reg("x16"), mem("x0", 0));
+                emit!(e, "blr", reg("x16"));
+                if dst != "x0" {
+                    emit!(e, "mov", reg(dst), reg("x0"));
+                }
+            }
+        }
+    }
+
+    /// Loads the C library's stack guard value into `dst`.
+    fn load_stack_guard(&self, e: &mut Emitter, dst: &str) {
+        self.got_entry(e, dst, &self.format.symbol("__stack_chk_guard"));
+        emit!(e, "ldr", reg(dst), mem(dst, 0));
+    }
+}
+
+impl Backend for Aarch64 {
+    fn registers(&self) -> RegisterSet<'static> {
+        ALLOCATABLE_REGS
+    }
+
+    fn calling_convention(&self) -> CallingConvention {
+        match self.format {
+            // Apple's arm64 ABI packs stack arguments at their natural size
+            // rather than in 8-byte slots, and passes variable arguments on
+            // the stack.
+            ObjectFormat::MachO => CallingConvention {
+                stack_arg_size: 4,
+                variadic_on_stack: true,
+                ..CALLING_CONVENTION
+            },
+            ObjectFormat::Elf => CALLING_CONVENTION,
+        }
+    }
+
+    fn begin_module(&self, out: &mut Assembly) {
+        out.directive(".text");
+    }
+
+    fn end_module(&self, out: &mut Assembly) {
+        if self.format == ObjectFormat::Elf {
+            // Without this note the linker assumes the code needs an
+            // executable stack.
+            out.blank();
+            out.directive(".section .note.GNU-stack,\"\",%progbits");
+        }
+    }
+
+    fn begin_function(&self, out: &mut Assembly, func: &Function, visibility: Visibility) {
+        let symbol = self.format.symbol(&func.name);
+        out.blank();
+        self.format.export(out, &symbol, visibility);
+        out.directive(".p2align 2");
+        if self.format == ObjectFormat::Elf {
+            out.directive(format!(".type {symbol}, %function"));
+        }
+        out.label(symbol);
+    }
+
+    fn end_function(&self, out: &mut Assembly, func: &Function) {
+        if self.format == ObjectFormat::Elf {
+            let symbol = self.format.symbol(&func.name);
+            out.directive(format!(".size {symbol}, .-{symbol}"));
+        }
+    }
+
+    fn string_pool(&self, out: &mut Assembly, strings: &StringPool) {
+        out.blank();
+        out.directive(match self.format {
+            ObjectFormat::MachO => ".section __TEXT,__cstring,cstring_literals",
+            ObjectFormat::Elf => ".section .rodata.str1.1,\"aMS\",%progbits,1",
+        });
+        for (id, bytes) in strings.iter() {
+            out.label(string_label(self.format, id));
+            out.directive(asciz(bytes));
+        }
+    }
+
+    fn global(&self, out: &mut Assembly, global: &Global, visibility: Visibility) {
+        let symbol = self.format.symbol(&global.name);
+        let data = data_directive(global.ty.bytes());
+        out.blank();
+        if global.thread_local && self.format == ObjectFormat::MachO {
+            macho_thread_local(out, &symbol, global, visibility, data);
+            return;
+        }
+        out.directive(data_section(global, '%'));
+        self.format.export(out, &symbol, visibility);
+        out.directive(format!(".p2align {}", global.ty.bytes().trailing_zeros()));
+        if self.format == ObjectFormat::Elf {
+            let kind = if global.thread_local {
+                "tls_object"
+            } else {
+                "object"
+            };
+            out.directive(format!(".type {symbol}, %{kind}"));
+            out.directive(format!(".size {symbol}, {}", global.size()));
+        }
+        out.label(symbol);
+        global_data(out, global, self.format, data);
+    }
+
+    fn object_format(&self) -> ObjectFormat {
+        self.format
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::Arm
+    }
+
+    fn dwarf_frame_register(&self) -> u8 {
+        // x0-x30 keep their own numbers, so this is x29.
+        29
+    }
+
+    /// Saves the frame pointer and link register, then reserves the frame.
+    fn prologue(&self, e: &mut Emitter) {
+        if !e.frame.pointer {
+            return;
+        }
+        let push = Operand::Mem(Address::PreIndex {
+            base: "sp".to_string(),
+            offset: -16,
+        });
+        emit!(e, "stp", reg("x29"), reg("x30"), push);
+        e.cfi(".cfi_def_cfa_offset 16");
+        e.cfi(".cfi_offset x30, -8");
+        e.cfi(".cfi_offset x29, -16");
+        emit!(e, "mov", reg("x29"), reg("sp"));
+        e.cfi(".cfi_def_cfa x29, 16");
+        match e.frame.size {
+            0 => {}
+            size @ 1..=4095 => emit!(e, "sub", reg("sp"), reg("sp"), imm(size)),
+            // Beyond the reach of an immediate, as with large arrays.
+            size => {
+                for instruction in materialize("x16", i64::from(size)) {
+                    e.out.instruction(instruction);
+                }
+                emit!(e, "sub", reg("sp"), reg("sp"), reg("x16"));
+            }
+        }
+        for (offset, pair) in saved_pairs(e) {
+            match pair[..] {
+                [a, b] => emit!(e, "stp", x(a), x(b), save_slot(offset)),
+                [a] => emit!(e, "str", x(a), save_slot(offset)),
+                _ => unreachable!(),
+            }
+            // The save area starts 16 bytes below the canonical frame
+            // address, under the frame record.
+            for (i, &saved) in pair.iter().enumerate() {
+                let below = offset + 16 - 8 * i as u32;
+                e.cfi(format!(".cfi_offset x{saved}, -{below}"));
+            }
+        }
+        if let Some(offset) = e.frame.canary {
+            self.load_stack_guard(e, "x16");
+            let slot = frame_slot(e, offset);
+            emit!(e, "str", reg("x16"), slot);
+        }
+    }
+
+    fn count(&self, e: &mut Emitter, symbol: &str, index: usize) {
+        let symbol = self.format.symbol(symbol);
+        emit!(e, "adrp", reg("x16"), sym(self.page(&symbol)));
+        emit!(
+            e,
+            "add",
+            reg("x16"),
+            reg("x16"),
+            sym(self.page_offset(&symbol))
+        );
+        let mut offset = 8 * index as i64;
+        // `ldr` and `str` reach 32760 bytes; further counters are addressed
+        // from a base moved up to them.
+        if offset > 32760 {
+            for instruction in materialize("x17", offset) {
+                e.out.instruction(instruction);
+            }
+            emit!(e, "add", reg("x16"), reg("x16"), reg("x17"));
+            offset = 0;
+        }
+        emit!(e, "ldr", reg("x17"), mem("x16", offset));
+        emit!(e, "add", reg("x17"), reg("x17"), imm(1));
+        emit!(e, "str", reg("x17"), mem("x16", offset));
+    }
+
+    /// Calls `_mcount` with the return address. It is an ordinary C
+    /// function, so the argument registers still holding parameters are
+    /// saved around the call.
+    fn mcount(&self, e: &mut Emitter) {
+        let params = e.func.params.len().min(ARG_REGS.len()) as u8;
+        let pairs: Vec<u8> = (0..params).step_by(2).collect();
+        let size = 16 * pairs.len() as i64;
+        for (i, &first) in pairs.iter().enumerate() {
+            let slot = match i {
+                0 => Operand::Mem(Address::PreIndex {
+                    base: "sp".to_string(),
+                    offset: -size,
+                }),
+                _ => mem("sp", 16 * i as i64),
+            };
+            emit!(e, "stp", x(first), x(first + 1), slot);
+        }
+        emit!(e, "mov", reg("x0"), reg("x30"));
+        emit!(e, "bl", sym(self.format.symbol("_mcount")));
+        for (i, &first) in pairs.iter().enumerate().rev() {
+            let slot = match i {
+                0 => Operand::Mem(Address::PostIndex {
+                    base: "sp".to_string(),
+                    offset: size,
+                }),
+                _ => mem("sp", 16 * i as i64),
+            };
+            emit!(e, "ldp", x(first), x(first + 1), slot);
+        }
+    }
+
+    /// Restores saved registers, releases the frame and returns; the result
+    /// is already in `w0`.
+    fn epilogue(&self, e: &mut Emitter) {
+        let smashed = e.frame.canary.map(|offset| {
+            let smashed = e.labels.fresh();
+            self.load_stack_guard(e, "x16");
+            let slot = frame_slot(e, offset);
+            emit!(e, "ldr", reg("x17"), slot);
+            emit!(e, "cmp", reg("x16"), reg("x17"));
+            emit!(e, "b.ne", sym(&smashed));
+            smashed
+        });
+        for (offset, pair) in saved_pairs(e) {
+            match pair[..] {
+                [a, b] => emit!(e, "ldp", x(a), x(b), save_slot(offset)),
+                [a] => emit!(e, "ldr", x(a), save_slot(offset)),
+                _ => unreachable!(),
+            }
+        }
+        if e.frame.size > 0 || e.frame.dynamic {
+            emit!(e, "mov", reg("sp"), reg("x29"));
+        }
+        if e.frame.pointer {
+            // The code after the `ret` still runs with the frame set up.
+            e.cfi(".cfi_remember_state");
+            let pop = Operand::Mem(Address::PostIndex {
+                base: "sp".to_string(),
+                offset: 16,
+            });
+            emit!(e, "ldp", reg("x29"), reg("x30"), pop);
+            e.cfi(".cfi_def_cfa sp, 0");
+            e.cfi(".cfi_restore x30");
+            e.cfi(".cfi_restore x29");
+        }
+        emit!(e, "ret");
+        if e.frame.pointer {
+            e.cfi(".cfi_restore_state");
+        }
+        if let Some(smashed) = smashed {
+            e.label(&smashed);
+            emit!(e, "bl", sym(self.format.symbol("__stack_chk_fail")));
+        }
+    }
+
+    fn receive_param(&self, e: &mut Emitter, index: usize, value: Value) {
+        match ARG_REGS.get(index) {
+            Some(reg) => define(e, value, reg),
+            None => {
+                let offset = INCOMING_ARGS_OFFSET + e.stack_arg_offset(index);
+                let dst = result_reg(e, value);
+                emit!(e, "ldr", reg(&dst), mem("x29", offset));
+                spill_result(e, value, &dst);
+            }
+        }
+    }
+
+    fn instr(&self, e: &mut Emitter, instr: &Instr) {
+        match instr {
+            Instr::Const { .. } => {}
+            Instr::Loc { .. } => unreachable!("the code generator emits source locations"),
+            Instr::Binary { dst, op, lhs, rhs } => {
+                // `0 - x` and `x ^ -1` are how the front end spells `-x`
+                // and `~x`.
+                let unary = match (op, e.location(*lhs), e.location(*rhs)) {
+                    (BinOp::Sub, Location::Imm(0), _) if !e.checks_overflow(*dst) => {
+                        Some(("neg", *rhs))
+                    }
+                    (BinOp::Xor, _, Location::Imm(-1)) => Some(("mvn", *lhs)),
+                    _ => None,
+                };
+                if let Some((mnemonic, value)) = unary {
+                    let src = operand(e, value, "w8");
+                    let dst_reg = result_reg(e, *dst);
+                    emit!(e, mnemonic, reg(&dst_reg), reg(src));
+                    spill_result(e, *dst, &dst_reg);
+                    return;
+                }
+                let lhs = operand(e, *lhs, "w8");
+                let dst_reg = result_reg(e, *dst);
+                let mnemonic = match op {
+                    BinOp::Add => "add",
+                    BinOp::Sub => "sub",
+                    BinOp::Mul => "mul",
+                    BinOp::SDiv | BinOp::SRem => "sdiv",
+                    BinOp::UDiv | BinOp::URem => "udiv",
+                    BinOp::And => "and",
+                    BinOp::Or => "orr",
+                    BinOp::Xor => "eor",
+                    BinOp::Shl => "lsl",
+                    BinOp::AShr => "asr",
+                    BinOp::LShr => "lsr",
+                };
+                match (op, e.location(*rhs)) {
+                    (BinOp::Shl | BinOp::AShr | BinOp::LShr, Location::Imm(amount)) => {
+                        emit!(e, mnemonic, reg(&dst_reg), reg(&lhs), imm(amount));
+                    }
+                    (BinOp::Add | BinOp::Sub | BinOp::Mul, _) if e.checks_overflow(*dst) => {
+                        let rhs = operand(e, *rhs, "w9");
+                        let trap = e.overflow_trap();
+                        if *op == BinOp::Mul {
+                            // The product overflowed if the full 64 bits
+                            // differ from the low half sign-extended.
+                            emit!(e, "smull", reg("x16"), reg(lhs), reg(rhs));
+                            emit!(e, "sxtw", reg("x17"), reg("w16"));
+                            emit!(e, "cmp", reg("x16"), reg("x17"));
+                            emit!(e, "b.ne", sym(trap));
+                            emit!(e, "mov", reg(&dst_reg), reg("w16"));
+                        } else {
+                            let flags = format!("{mnemonic}s");
+                            emit!(e, flags, reg(&dst_reg), reg(lhs), reg(rhs));
+                            emit!(e, "b.vs", sym(trap));
+                        }
+                    }
+                    (BinOp::SRem | BinOp::URem, _) => {
+                        let rhs = operand(e, *rhs, "w9");
+                        let quotient = sized(e, *dst, "w16");
+                        emit!(e, mnemonic, reg(&quotient), reg(&lhs), reg(&rhs));
+                        emit!(e, "msub", reg(&dst_reg), reg(quotient), reg(rhs), reg(lhs));
+                    }
+                    _ => {
+                        let rhs = operand(e, *rhs, "w9");
+                        emit!(e, mnemonic, reg(&dst_reg), reg(lhs), reg(rhs));
+                    }
+                }
+                spill_result(e, *dst, &dst_reg);
+            }
+            Instr::Cmp { dst, op, lhs, rhs } => {
+                compare(e, *lhs, *rhs);
+                let dst_reg = result_reg(e, *dst);
+                emit!(e, "cset", reg(&dst_reg), Operand::Cond(condition(*op)));
+                spill_result(e, *dst, &dst_reg);
+            }
+            Instr::Call {
+                dst,
+                callee,
+                args,
+                fixed,
+            } => {
+                // Stack arguments go first, while `w8` is still free as scratch.
+                let conv = e.conv;
+                for (i, &arg) in args.iter().enumerate() {
+                    if !conv.in_register(i, *fixed) {
+                        let value = operand(e, arg, "w8");
+                        let slot = mem("sp", conv.call_arg_offset(i, *fixed));
+                        emit!(e, "str", reg(value), slot);
+                    }
+                }
+                for (i, (reg, &arg)) in ARG_REGS.iter().zip(args).enumerate() {
+                    if conv.in_register(i, *fixed) {
+                        move_to(e, reg, arg);
+                    }
+                }
+                emit!(e, "bl", sym(self.format.symbol(callee)));
+                if let Some(dst) = dst {
+                    define(e, *dst, "w0");
+                }
+            }
+            Instr::InlineAsm {
+                dst,
+                template,
+                inputs,
+            } => {
+                // Operands take the argument registers in order, which the
+                // allocator never hands out.
+                let mut regs = ARG_REGS.iter();
+                let mut operands = Vec::new();
+                if let Some(dst) = dst {
+                    operands.push(sized(e, *dst, regs.next().expect("eight operands at most")));
+                }
+                for (&input, reg) in inputs.iter().zip(regs) {
+                    move_to(e, reg, input);
+                    operands.push(sized(e, input, reg));
+                }
+                let names: Vec<&str> = operands.iter().map(String::as_str).collect();
+                e.inline_asm(template, &names);
+                if let (Some(dst), Some(reg)) = (dst, operands.first()) {
+                    define(e, *dst, reg);
+                }
+            }
+            Instr::Cast { dst, op, value } => {
+                let from = e.func.value_type(*value);
+                let src = operand(e, *value, "w9");
+                let dst_reg = result_reg(e, *dst);
+                let (src32, dst32) = (reg(w_view(&src)), reg(w_view(&dst_reg)));
+                match (op, from) {
+                    (CastOp::SExt, IrType::I8) => emit!(e, "sxtb", reg(&dst_reg), src32),
+                    (CastOp::SExt, IrType::I16) => emit!(e, "sxth", reg(&dst_reg), src32),
+                    (CastOp::SExt, _) => emit!(e, "sxtw", reg(&dst_reg), src32),
+                    // Writing a `w` register clears the upper half of the `x`
+                    // register.
+                    (CastOp::ZExt, IrType::I8) => emit!(e, "uxtb", dst32, src32),
+                    (CastOp::ZExt, IrType::I16) => emit!(e, "uxth", dst32, src32),
+                    (CastOp::ZExt, _) => emit!(e, "mov", dst32, src32),
+                    // Narrow values may carry garbage above their width, so
+                    // truncating only drops the upper half of a pointer.
+                    (CastOp::Trunc, _) => emit!(e, "mov", dst32, src32),
+                }
+                spill_result(e, *dst, &dst_reg);
+            }
+            Instr::Load { dst, slot } => {
+                let dst_reg = result_reg(e, *dst);
+                let mnemonic = memory_mnemonic("ldr", e.func.value_type(*dst));
+                let slot = frame_slot(e, e.slot_offset(*slot));
+                emit!(e, mnemonic, reg(&dst_reg), slot);
+                spill_result(e, *dst, &dst_reg);
+            }
+            Instr::Store { slot, value } => {
+                let mnemonic = memory_mnemonic("str", e.func.value_type(*value));
+                let value = operand(e, *value, "w8");
+                let slot = frame_slot(e, e.slot_offset(*slot));
+                emit!(e, mnemonic, reg(value), slot);
+            }
+            Instr::LoadPtr { dst, ptr } => {
+                let ptr = operand(e, *ptr, "w9");
+                let dst_reg = result_reg(e, *dst);
+                let mnemonic = memory_mnemonic("ldr", e.func.value_type(*dst));
+                emit!(e, mnemonic, reg(&dst_reg), mem(ptr, 0));
+                spill_result(e, *dst, &dst_reg);
+            }
+            Instr::StorePtr { ptr, value } => {
+                let ptr = operand(e, *ptr, "w9");
+                let mnemonic = memory_mnemonic("str", e.func.value_type(*value));
+                let value = operand(e, *value, "w8");
+                emit!(e, mnemonic, reg(value), mem(ptr, 0));
+            }
+            Instr::SlotAddr { dst, slot } => {
+                let dst_reg = result_reg(e, *dst);
+                let offset = i64::from(e.slot_offset(*slot));
+                if offset <= 4095 {
+                    emit!(e, "sub", reg(&dst_reg), reg("x29"), imm(offset));
+                } else {
+                    for instruction in materialize("x16", offset) {
+                        e.out.instruction(instruction);
+                    }
+                    emit!(e, "sub", reg(&dst_reg), reg("x29"), reg("x16"));
+                }
+                spill_result(e, *dst, &dst_reg);
+            }
+            Instr::StringAddr { dst, string } => {
+                let dst_reg = result_reg(e, *dst);
+                let label = string_label(self.format, *string);
+                emit!(e, "adrp", reg(&dst_reg), sym(self.page(&label)));
+                let offset = sym(self.page_offset(&label));
+                emit!(e, "add", reg(&dst_reg), reg(&dst_reg), offset);
+                spill_result(e, *dst, &dst_reg);
+            }
+            Instr::Alloca { dst, size } => {
+                // Rounding the size up to 16 bytes keeps `sp` aligned. The
+                // outgoing argument area moves down to stay at `sp`.
+                move_to(e, "w9", *size);
+                emit!(e, "add", reg("x9"), reg("x9"), imm(15));
+                emit!(e, "and", reg("x9"), reg("x9"), imm(-16));
+                emit!(e, "sub", reg("sp"), reg("sp"), reg("x9"));
+                let dst_reg = result_reg(e, *dst);
+                emit!(e, "add", reg(&dst_reg), reg("sp"), imm(e.frame.outgoing));
+                spill_result(e, *dst, &dst_reg);
+            }
+            Instr::GlobalAddr {
+                dst,
+                name,
+                thread_local: true,
+            } => {
+                let dst_reg = result_reg(e, *dst);
+                self.thread_local_addr(e, &dst_reg, name);
+                spill_result(e, *dst, &dst_reg);
+            }
+            Instr::GlobalAddr { dst, name, .. } => {
+                let dst_reg = result_reg(e, *dst);
+                let symbol = self.format.symbol(name);
+                if e.global_via_got(self.format, name) {
+                    self.got_entry(e, &dst_reg, &symbol);
+                } else {
+                    emit!(e, "adrp", reg(&dst_reg), sym(self.page(&symbol)));
+                    let offset = sym(self.page_offset(&symbol));
+                    emit!(e, "add", reg(&dst_reg), reg(&dst_reg), offset);
+                }
+                spill_result(e, *dst, &dst_reg);
+            }
+        }
+    }
+
+    fn return_value(&self, e: &mut Emitter, value: Value) {
+        move_to(e, "w0", value);
+    }
+
+    fn jump(&self, e: &mut Emitter, label: &str) {
+        emit!(e, "b", sym(label));
+    }
+
+    fn branch(&self, e: &mut Emitter, cond: Value, nonzero: bool, label: &str) {
+        let cond = operand(e, cond, "w8");
+        let mnemonic = if nonzero { "cbnz" } else { "cbz" };
+        emit!(e, mnemonic, reg(cond), sym(label));
+    }
+
+    fn compare_and_branch(&self, e: &mut Emitter, op: CmpOp, lhs: Value, rhs: Value, label: &str) {
+        match (op, e.location(rhs)) {
+            (CmpOp::Eq | CmpOp::Ne, Location::Imm(0)) => {
+                self.branch(e, lhs, op == CmpOp::Ne, label)
+            }
+            _ => {
+                compare(e, lhs, rhs);
+                emit!(e, format!("b.{}", condition(op)), sym(label));
+            }
+        }
+    }
+
+    fn compare_imm_and_branch(
+        &self,
+        e: &mut Emitter,
+        op: CmpOp,
+        value: Value,
+        imm: i64,
+        label: &str,
+    ) {
+        let value = operand(e, value, "w8");
+        compare_imm(e, &value, imm);
+        emit!(e, format!("b.{}", condition(op)), sym(label));
+    }
+
+    fn jump_table(
+        &self,
+        e: &mut Emitter,
+        value: Value,
+        min: i64,
+        targets: &[String],
+        default: &str,
+    ) {
+        let mut index = operand(e, value, "w8");
+        if min != 0 {
+            match min {
+                1..=4095 => emit!(e, "sub", reg("w8"), reg(&index), imm(min)),
+                -4095..=-1 => emit!(e, "add", reg("w8"), reg(&index), imm(-min)),
+                _ => {
+                    for instruction in materialize("w9", min) {
+                        e.out.instruction(instruction);
+                    }
+                    emit!(e, "sub", reg("w8"), reg(&index), reg("w9"));
+                }
+            }
+            index = "w8".to_string();
+        }
+        // One unsigned comparison catches values below `min` as well.
+        compare_imm(e, &index, targets.len() as i64 - 1);
+        emit!(e, "b.hi", sym(default));
+        let table = e.labels.fresh();
+        emit!(e, "adr", reg("x9"), sym(&table));
+        let entry = Operand::Mem(Address::Indexed {
+            base: "x9".to_string(),
+            index,
+            scale: 4,
+        });
+        emit!(e, "ldrsw", reg("x16"), entry);
+        emit!(e, "add", reg("x9"), reg("x9"), reg("x16"));
+        emit!(e, "br", reg("x9"));
+        e.directive(".p2align 2".to_string());
+        e.label(&table);
+        for target in targets {
+            e.directive(format!(".long {target}-{table}"));
+        }
+    }
+
+    fn trap(&self, e: &mut Emitter, code: u16) {
+        emit!(e, "brk", imm(i64::from(code)));
+    }
+
+    fn peephole(&self, asm: Assembly) -> Assembly {
+        peephole::optimize(asm)
+    }
+}
+
+/// The directive that emits a `size`-byte integer.
+fn data_directive(size: u32) -> &'static str {
+    match size {
+        1 => ".byte",
+        2 => ".hword",
+        4 => ".word",
+        8 => ".quad",
+        _ => unreachable!("no {size}-byte integer type"),
+    }
+}
+
+/// Sets the flags from `lhs - rhs`, using an immediate operand when `rhs`
+/// fits in one.
+fn compare(e: &mut Emitter, lhs: Value, rhs: Value) {
+    let lhs = operand(e, lhs, "w8");
+    match e.location(rhs) {
+        Location::Imm(value @ 0..=4095) => emit!(e, "cmp", reg(lhs), imm(value)),
+        _ => {
+            let rhs = operand(e, rhs, "w9");
+            emit!(e, "cmp", reg(lhs), reg(rhs));
+        }
+    }
+}
+
+/// Sets the flags from `register - value`, materializing `value` in `w9`
+/// unless `cmp` or `cmn` can encode it.
+fn compare_imm(e: &mut Emitter, register: &str, value: i64) {
+    match value {
+        0..=4095 => emit!(e, "cmp", reg(register), imm(value)),
+        -4095..=-1 => emit!(e, "cmn", reg(register), imm(-value)),
+        _ => {
+            for instruction in materialize("w9", value) {
+                e.out.instruction(instruction);
+            }
+            emit!(e, "cmp", reg(register), reg("w9"));
+        }
+    }
+}
+
+/// The condition code that holds after `cmp` when `op` does.
+fn condition(op: CmpOp) -> &'static str {
+    match op {
+        CmpOp::Eq => "eq",
+        CmpOp::Ne => "ne",
+        CmpOp::Slt => "lt",
+        CmpOp::Sle => "le",
+        CmpOp::Sgt => "gt",
+        CmpOp::Sge => "ge",
+        CmpOp::Ult => "lo",
+        CmpOp::Ule => "ls",
+        CmpOp::Ugt => "hi",
+        CmpOp::Uge => "hs",
+    }
+}
+
+/// Pairs of saved registers with the offset below `x29` of each pair.
+fn saved_pairs(e: &Emitter) -> Vec<(u32, Vec<u8>)> {
+    e.frame
+        .saved
+        .chunks(2)
+        .enumerate()
+        .map(|(i, pair)| (16 * (i as u32 + 1), pair.to_vec()))
+        .collect()
+}
+
+/// The `x` register numbered `number`.
+fn x(number: u8) -> Operand {
+    reg(format!("x{number}"))
+}
+
+/// The save area memory `offset` bytes below `x29`, which sits just below
+/// it and so always within reach of an immediate offset.
+fn save_slot(offset: u32) -> Operand {
+    mem("x29", -i64::from(offset))
+}
+
+/// The frame memory `offset` bytes below `x29`. Offsets beyond the 256
+/// bytes a negative immediate reaches, as in large frames, are addressed
+/// through `x17`.
+fn frame_slot(e: &mut Emitter, offset: u32) -> Operand {
+    if offset <= 256 {
+        return mem("x29", -i64::from(offset));
+    }
+    if offset <= 4095 {
+        emit!(e, "sub", reg("x17"), reg("x29"), imm(i64::from(offset)));
+    } else {
+        for instruction in materialize("x17", i64::from(offset)) {
+            e.out.instruction(instruction);
+        }
+        emit!(e, "sub", reg("x17"), reg("x29"), reg("x17"));
+    }
+    mem("x17", 0)
+}
+
+/// `base` (`ldr` or `str`) with the suffix for a `ty`-sized access. Narrow
+/// loads zero-extend; a sign-extending use extends them explicitly.
+fn memory_mnemonic(base: &str, ty: IrType) -> String {
+    match ty {
+        IrType::I8 => format!("{base}b"),
+        IrType::I16 => format!("{base}h"),
+        IrType::I32 | IrType::Ptr => base.to_string(),
+    }
+}
+
+/// The `w` view of the register `reg`.
+fn w_view(reg: &str) -> String {
+    match reg.strip_prefix('x') {
+        Some(number) => format!("w{number}"),
+        None => reg.to_string(),
+    }
+}
+
+/// `reg`, named by its `w` form, in the width `value` needs.
+fn sized(e: &Emitter, value: Value, reg: &str) -> String {
+    match reg.strip_prefix('w') {
+        Some(number) if e.is_wide(value) => format!("x{number}"),
+        _ => reg.to_string(),
+    }
+}
+
+/// Returns a register holding `value`, loading it into `scratch` if needed.
+fn operand(e: &mut Emitter, value: Value, scratch: &str) -> String {
+    match e.location(value) {
+        Location::Reg(reg) => sized(e, value, &format!("w{reg}")),
+        _ => {
+            let scratch = sized(e, value, scratch);
+            move_to(e, &scratch, value);
+            scratch
+        }
+    }
+}
+
+/// Copies `value` into the register `dst`.
+fn move_to(e: &mut Emitter, dst: &str, value: Value) {
+    let dst = sized(e, value, dst);
+    match e.location(value) {
+        Location::Imm(value) => {
+            for instruction in materialize(&dst, value) {
+                e.out.instruction(instruction);
+            }
+        }
+        Location::Reg(src) => {
+            let src = sized(e, value, &format!("w{src}"));
+            emit!(e, "mov", reg(dst), reg(src));
+        }
+        Location::Stack(offset) => {
+            let slot = frame_slot(e, offset);
+            emit!(e, "ldr", reg(dst), slot);
+        }
+    }
+}
+
+/// Instructions that load the constant `value` into `dst`, which is a `w` or
+/// `x` register. A single `mov` covers values with at most one halfword that
+/// differs from all-zeros or all-ones; anything else is built a halfword at
+/// a time with `movz` or `movn` followed by `movk`.
+pub fn materialize(dst: &str, value: i64) -> Vec<Instruction> {
+    let halfwords = if dst.starts_with('x') { 4 } else { 2 };
+    let bits = if halfwords == 4 {
+        value as u64
+    } else {
+        u64::from(value as u32)
+    };
+    let halfword = |i: u32| (bits >> (16 * i)) & 0xffff;
+    let count = |fill: u64| (0..halfwords).filter(|&i| halfword(i) != fill).count();
+    let (zeros, ones) = (count(0), count(0xffff));
+    if zeros <= 1 || ones <= 1 {
+        let value = if halfwords == 4 {
+            value
+        } else {
+            i64::from(value as i32)
+        };
+        return vec![Instruction::new("mov", vec![reg(dst), imm(value)])];
+    }
+
+    // Start from whichever fill leaves fewer halfwords to patch.
+    let fill = if ones < zeros { 0xffff } else { 0 };
+    let mut instructions = Vec::new();
+    for i in (0..halfwords).filter(|&i| halfword(i) != fill) {
+        let shift = 16 * i;
+        let (mnemonic, value) = match (instructions.is_empty(), fill) {
+            (true, 0) => ("movz", halfword(i)),
+            (true, _) => ("movn", !halfword(i) & 0xffff),
+            (false, _) => ("movk", halfword(i)),
+        };
+        let operand = Operand::ShiftedImm { value, shift };
+        instructions.push(Instruction::new(mnemonic, vec![reg(dst), operand]));
+    }
+    instructions
+}
+
+/// Records that `value` is now held in the register `src`.
+fn define(e: &mut Emitter, value: Value, src: &str) {
+    let src = sized(e, value, src);
+    match e.location(value) {
+        Location::Reg(dst) => {
+            let dst = sized(e, value, &format!("w{dst}"));
+            emit!(e, "mov", reg(dst), reg(src));
+        }
+        Location::Stack(offset) => {
+            let slot = frame_slot(e, offset);
+            emit!(e, "str", reg(src), slot);
+        }
+        Location::Imm(_) => unreachable!("constants are never defined at runtime"),
+    }
+}
+
+/// Stores a result computed in `src` if its value lives on the stack.
+fn spill_result(e: &mut Emitter, value: Value, src: &str) {
+    if let Location::Stack(offset) = e.location(value) {
+        let slot = frame_slot(e, offset);
+        emit!(e, "str", reg(src), slot);
+    }
+}
+
+/// The register an instruction defining `value` should write to.
+fn result_reg(e: &Emitter, value: Value) -> String {
+    match e.location(value) {
+        Location::Reg(reg) => sized(e, value, &format!("w{reg}")),
+        _ => sized(e, value, "w8"),
+    }
+}
@@ -0,0 +1,377 @@
+//! A typed representation of generated assembly.
+//!
+//! Backends build [`Instruction`]s out of [`Operand`]s instead of formatting
+//! text, so later passes such as the peephole optimizer can match on
+//! registers and addresses rather than parse lines back apart. An
+//! [`Assembly`] is turned into text only at the end, in the [`Syntax`] of
+//! its target.
+
+use std::fmt;
+
+use crate::lexer::Position;
+
+/// One operand of a machine instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    /// A register, by its name in the target's syntax (`w8`, `%eax`).
+    Reg(String),
+    Imm(i64),
+    /// A 16-bit immediate shifted left by `shift` bits, as `movz`, `movn`
+    /// and `movk` take.
+    ShiftedImm {
+        value: u64,
+        shift: u32,
+    },
+    /// A label or symbol, including any relocation specifier
+    /// (`Lmain_1`, `_f`, `:lo12:x`, `f@PLT`).
+    Symbol(String),
+    /// A condition code, as `cset` takes.
+    Cond(&'static str),
+    Mem(Address),
+    /// A register holding the target of an indirect jump.
+    Indirect(String),
+}
+
+/// A memory operand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Address {
+    /// `base + offset`.
+    Offset { base: String, offset: i64 },
+    /// `base` plus the relocated address or offset `symbol`.
+    Symbol { base: String, symbol: String },
+    /// `base + index * scale`.
+    Indexed {
+        base: String,
+        index: String,
+        scale: u8,
+    },
+    /// `base + offset`, writing the address back to `base` first.
+    PreIndex { base: String, offset: i64 },
+    /// `base`, adding `offset` to `base` afterwards.
+    PostIndex { base: String, offset: i64 },
+    /// `offset` bytes into the segment `segment` (`%fs`), which x86-64
+    /// uses for thread-local storage.
+    Segment { segment: String, offset: i64 },
+}
+
+pub fn reg(name: impl Into<String>) -> Operand {
+    Operand::Reg(name.into())
+}
+
+pub fn imm(value: impl Into<i64>) -> Operand {
+    Operand::Imm(value.into())
+}
+
+pub fn sym(name: impl Into<String>) -> Operand {
+    Operand::Symbol(name.into())
+}
+
+/// The memory at `offset` bytes from the address in `base`.
+pub fn mem(base: impl Into<String>, offset: impl Into<i64>) -> Operand {
+    Operand::Mem(Address::Offset {
+        base: base.into(),
+        offset: offset.into(),
+    })
+}
+
+/// A machine instruction. Operands are in the order the target's syntax
+/// writes them: destination first on AArch64, last in AT&T syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    pub mnemonic: String,
+    pub operands: Vec<Operand>,
+    /// Where in the source the instruction comes from, when the generator
+    /// tracks it for a source map.
+    pub pos: Option<Position>,
+}
+
+impl Instruction {
+    pub fn new(mnemonic: impl Into<String>, operands: Vec<Operand>) -> Self {
+        Self {
+            mnemonic: mnemonic.into(),
+            operands,
+            pos: None,
+        }
+    }
+}
+
+/// One line of an assembly file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Line {
+    Instruction(Instruction),
+    Label(String),
+    /// An assembler directive such as `.p2align 2`.
+    Directive(String),
+    /// A comment on a line of its own.
+    Comment(String),
+    /// A line of inline assembly, passed through as written.
+    Verbatim(String),
+    Blank,
+}
+
+/// The textual conventions of an assembler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    /// The ARM syntax used for AArch64.
+    Arm,
+    /// AT&T syntax for x86-64: `%` registers, `$` immediates and the
+    /// destination last.
+    Att,
+    /// Intel syntax for x86-64, as `-masm=intel` selects: bare registers
+    /// and immediates, the destination first and sizes spelled on memory
+    /// operands rather than in the mnemonic. x86-64 code is always built
+    /// in AT&T form and translated as it is printed.
+    Intel,
+}
+
+impl Syntax {
+    /// What starts a comment that runs to the end of the line.
+    pub fn comment_prefix(self) -> &'static str {
+        match self {
+            Syntax::Arm => "//",
+            Syntax::Att | Syntax::Intel => "#",
+        }
+    }
+
+    fn instruction(self, f: &mut fmt::Formatter, instruction: &Instruction) -> fmt::Result {
+        if self == Syntax::Intel {
+            return intel_instruction(f, instruction);
+        }
+        write!(f, "    {}", instruction.mnemonic)?;
+        for (i, operand) in instruction.operands.iter().enumerate() {
+            f.write_str(if i == 0 { " " } else { ", " })?;
+            self.operand(f, operand)?;
+        }
+        Ok(())
+    }
+
+    fn operand(self, f: &mut fmt::Formatter, operand: &Operand) -> fmt::Result {
+        match (self, operand) {
+            (_, Operand::Reg(name) | Operand::Symbol(name)) => f.write_str(name),
+            (_, Operand::Cond(cond)) => f.write_str(cond),
+            (Syntax::Arm, Operand::Imm(value)) => write!(f, "#{value}"),
+            (Syntax::Att, Operand::Imm(value)) => write!(f, "${value}"),
+            (Syntax::Arm, Operand::ShiftedImm { value, shift }) => {
+                write!(f, "#{value:#x}, lsl #{shift}")
+            }
+            (Syntax::Att, Operand::ShiftedImm { value, shift }) => {
+                write!(f, "${}", value << shift)
+            }
+            (Syntax::Arm, Operand::Indirect(name)) => f.write_str(name),
+            (Syntax::Att, Operand::Indirect(name)) => write!(f, "*{name}"),
+            (Syntax::Arm, Operand::Mem(address)) => match address {
+                Address::Offset { base, offset } => write!(f, "[{base}, #{offset}]"),
+                Address::Symbol { base, symbol } => write!(f, "[{base}, {symbol}]"),
+                Address::Indexed { base, index, scale } => {
+                    // A 32-bit index is zero-extended before it is scaled.
+                    let extend = if index.starts_with('w') {
+                        "uxtw"
+                    } else {
+                        "lsl"
+                    };
+                    write!(f, "[{base}, {index}, {extend} #{}]", scale.trailing_zeros())
+                }
+                Address::PreIndex { base, offset } => write!(f, "[{base}, #{offset}]!"),
+                Address::PostIndex { base, offset } => write!(f, "[{base}], #{offset}"),
+                Address::Segment { .. } => unreachable!("AArch64 has no segments"),
+            },
+            (Syntax::Intel, _) => unreachable!("Intel operands depend on the instruction"),
+            (Syntax::Att, Operand::Mem(address)) => match address {
+                Address::Offset { base, offset } => write!(f, "{offset}({base})"),
+                Address::Symbol { base, symbol } => write!(f, "{symbol}({base})"),
+                Address::Indexed { base, index, scale } => write!(f, "({base},{index},{scale})"),
+                Address::Segment { segment, offset } => write!(f, "{segment}:{offset}"),
+                Address::PreIndex { .. } | Address::PostIndex { .. } => {
+                    unreachable!("x86-64 has no writeback addressing")
+                }
+            },
+        }
+    }
+}
+
+/// Prints an instruction built in AT&T form in Intel syntax.
+fn intel_instruction(f: &mut fmt::Formatter, instruction: &Instruction) -> fmt::Result {
+    let (mnemonic, size) = intel_mnemonic(&instruction.mnemonic);
+    write!(f, "    {mnemonic}")?;
+    for (i, operand) in instruction.operands.iter().rev().enumerate() {
+        f.write_str(if i == 0 { " " } else { ", " })?;
+        match operand {
+            Operand::Reg(name) | Operand::Indirect(name) => {
+                f.write_str(name.trim_start_matches('%'))?
+            }
+            Operand::Imm(value) => write!(f, "{value}")?,
+            Operand::ShiftedImm { value, shift } => write!(f, "{}", value << shift)?,
+            Operand::Symbol(name) => f.write_str(name)?,
+            Operand::Cond(cond) => f.write_str(cond)?,
+            Operand::Mem(address) => {
+                // `lea` computes an address without accessing memory.
+                if let (Some(size), false) = (size, mnemonic == "lea") {
+                    write!(f, "{size} ptr ")?;
+                }
+                intel_address(f, address)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn intel_address(f: &mut fmt::Formatter, address: &Address) -> fmt::Result {
+    let bare = |name: &str| name.trim_start_matches('%').to_string();
+    match address {
+        Address::Offset { base, offset } => match offset {
+            0 => write!(f, "[{}]", bare(base)),
+            ..=-1 => write!(f, "[{} - {}]", bare(base), -offset),
+            _ => write!(f, "[{} + {offset}]", bare(base)),
+        },
+        Address::Symbol { base, symbol } => write!(f, "[{} + {symbol}]", bare(base)),
+        Address::Indexed { base, index, scale } => {
+            write!(f, "[{} + {}*{scale}]", bare(base), bare(index))
+        }
+        Address::Segment { segment, offset } => write!(f, "{}:[{offset}]", bare(segment)),
+        Address::PreIndex { .. } | Address::PostIndex { .. } => {
+            unreachable!("x86-64 has no writeback addressing")
+        }
+    }
+}
+
+/// The Intel spelling of the AT&T mnemonic `mnemonic`, and the size of the
+/// memory it accesses when the mnemonic carried one.
+fn intel_mnemonic(mnemonic: &str) -> (&str, Option<&'static str>) {
+    match mnemonic {
+        "cltd" => return ("cdq", None),
+        "cqto" => return ("cqo", None),
+        "cltq" => return ("cdqe", None),
+        "movabsq" => return ("movabs", Some("qword")),
+        "movslq" => return ("movsxd", Some("dword")),
+        "movzbl" | "movzbq" => return ("movzx", Some("byte")),
+        "movzwl" | "movzwq" => return ("movzx", Some("word")),
+        "movsbl" | "movsbq" | "movsbw" => return ("movsx", Some("byte")),
+        "movswl" | "movswq" => return ("movsx", Some("word")),
+        _ => {}
+    }
+    const SIZED: &[&str] = &[
+        "add", "and", "cmp", "dec", "div", "idiv", "imul", "inc", "lea", "mov", "neg", "not", "or",
+        "pop", "push", "sar", "shl", "shr", "sub", "test", "xor",
+    ];
+    let Some((base, suffix)) = mnemonic.split_at_checked(mnemonic.len().saturating_sub(1)) else {
+        return (mnemonic, None);
+    };
+    let size = match suffix {
+        "b" => "byte",
+        "w" => "word",
+        "l" => "dword",
+        "q" => "qword",
+        _ => return (mnemonic, None),
+    };
+    if SIZED.contains(&base) {
+        (base, Some(size))
+    } else {
+        (mnemonic, None)
+    }
+}
+
+/// Generated assembly for a whole module, printed in its target's syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assembly {
+    pub syntax: Syntax,
+    pub lines: Vec<Line>,
+    /// The source position given to instructions added from now on.
+    pub pos: Option<Position>,
+}
+
+impl Assembly {
+    pub fn new(syntax: Syntax) -> Self {
+        Self {
+            syntax,
+            lines: Vec::new(),
+            pos: None,
+        }
+    }
+
+    pub fn instruction(&mut self, mut instruction: Instruction) {
+        instruction.pos = instruction.pos.or(self.pos);
+        self.lines.push(Line::Instruction(instruction));
+    }
+
+    pub fn label(&mut self, name: impl Into<String>) {
+        self.lines.push(Line::Label(name.into()));
+    }
+
+    pub fn directive(&mut self, text: impl Into<String>) {
+        self.lines.push(Line::Directive(text.into()));
+    }
+
+    pub fn blank(&mut self) {
+        self.lines.push(Line::Blank);
+    }
+
+    /// The source position of each line of the printed assembly: that of
+    /// its instruction, or `None` for every other kind of line. Follows the
+    /// layout of the `Display` implementation.
+    pub fn positions(&self) -> Vec<Option<Position>> {
+        let intel = self.syntax == Syntax::Intel;
+        let mut positions = Vec::with_capacity(self.lines.len() + 1);
+        if intel {
+            positions.push(None);
+        }
+        for (i, line) in self.lines.iter().enumerate() {
+            match line {
+                Line::Instruction(instruction) => positions.push(instruction.pos),
+                Line::Verbatim(_) => {
+                    let verbatim = |line: Option<&Line>| matches!(line, Some(Line::Verbatim(_)));
+                    let previous = i.checked_sub(1).map(|i| &self.lines[i]);
+                    let lines = if intel {
+                        1 + usize::from(!verbatim(previous))
+                            + usize::from(!verbatim(self.lines.get(i + 1)))
+                    } else {
+                        1
+                    };
+                    positions.extend(std::iter::repeat_n(None, lines));
+                }
+                _ => positions.push(None),
+            }
+        }
+        positions
+    }
+
+    /// The instructions, in order, skipping every other kind of line.
+    pub fn instructions(&self) -> impl Iterator<Item = &Instruction> {
+        self.lines.iter().filter_map(|line| match line {
+            Line::Instruction(instruction) => Some(instruction),
+            _ => None,
+        })
+    }
+}
+
+impl fmt::Display for Assembly {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let intel = self.syntax == Syntax::Intel;
+        if intel {
+            writeln!(f, "    .intel_syntax noprefix")?;
+        }
+        for (i, line) in self.lines.iter().enumerate() {
+            match line {
+                Line::Instruction(instruction) => self.syntax.instruction(f, instruction)?,
+                Line::Label(name) => write!(f, "{name}:")?,
+                Line::Directive(text) => write!(f, "    {text}")?,
+                Line::Comment(text) => write!(f, "    {} {text}", self.syntax.comment_prefix())?,
+                // Inline assembly is indented with a tab, so it stands out
+                // from generated code. Its operands are substituted in AT&T
+                // syntax, so Intel output switches around each run of it.
+                Line::Verbatim(text) => {
+                    let previous = i.checked_sub(1).map(|i| &self.lines[i]);
+                    if intel && !matches!(previous, Some(Line::Verbatim(_))) {
+                        writeln!(f, "    .att_syntax prefix")?;
+                    }
+                    write!(f, "\t{text}")?;
+                    if intel && !matches!(self.lines.get(i + 1), Some(Line::Verbatim(_))) {
+                        write!(f, "\n    .intel_syntax noprefix")?;
+                    }
+                }
+                Line::Blank => {}
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+//! Line coverage instrumentation (`--coverage`).
+//!
+//! Each source line holding a statement gets a 64-bit counter, which the
+//! code generator increments wherever the IR's source locations move onto
+//! that line. The module also gets two functions, built as IR and generated
+//! like any other: one that appends the counters to the data file in the
+//! format of [`crate::coverage`], and a constructor that registers it with
+//! `atexit`.
+
+use super::asm::Assembly;
+use super::ObjectFormat;
+use crate::coverage::{self, Files};
+use crate::ir::{CmpOp, FunctionBuilder, Instr, IrType, Module};
+
+/// The counters of one module and the names of its coverage symbols, which
+/// carry a hash of the data file so that several instrumented modules can
+/// be linked together.
+pub struct Coverage {
+    /// Lines with a counter, in ascending order.
+    pub lines: Vec<u32>,
+    /// The array of counters, one per line.
+    pub counters: String,
+    /// The function that writes out the counters.
+    pub dump: String,
+    /// The constructor that registers `dump`.
+    pub init: String,
+}
+
+impl Coverage {
+    pub fn new(module: &Module, files: &Files) -> Self {
+        let mut lines: Vec<u32> = module
+            .functions
+            .iter()
+            .flat_map(|f| &f.blocks)
+            .flat_map(|b| &b.instrs)
+            .filter_map(|instr| match instr {
+                Instr::Loc { pos } => Some(pos.line),
+                _ => None,
+            })
+            .collect();
+        lines.sort_unstable();
+        lines.dedup();
+        let suffix = fnv1a(files.data.as_bytes());
+        Self {
+            lines,
+            counters: format!("__rcc_coverage_counters_{suffix:016x}"),
+            dump: format!("__rcc_coverage_dump_{suffix:016x}"),
+            init: format!("__rcc_coverage_init_{suffix:016x}"),
+        }
+    }
+
+    /// The index of the counter for `line`.
+    pub fn index(&self, line: u32) -> Option<usize> {
+        self.lines.binary_search(&line).ok()
+    }
+
+    /// `module` with the functions that write out the counters added.
+    pub fn instrument(&self, module: &Module, files: &Files) -> Module {
+        let mut module = module.clone();
+        let header = coverage::header(&files.source, &self.lines);
+        let header_len = header.len() as i64;
+        let header = module.strings.intern(header.as_bytes());
+        let path = module.strings.intern(files.data.as_bytes());
+        let mode = module.strings.intern(b"ab");
+
+        // `size_t` arguments are pointer-sized constants, since the IR has
+        // no 64-bit integer type.
+        let mut f = FunctionBuilder::new(&self.dump, None);
+        let (path, mode) = (f.string_addr(path), f.string_addr(mode));
+        let file = f
+            .call("fopen", vec![path, mode], Some(IrType::Ptr))
+            .unwrap();
+        let null = f.iconst(IrType::Ptr, 0);
+        let opened = f.cmp(CmpOp::Ne, file, null);
+        let (write, done) = (f.create_block(), f.create_block());
+        f.branch(opened, write, done);
+        f.switch_to(write);
+        let header = f.string_addr(header);
+        let one = f.iconst(IrType::Ptr, 1);
+        let len = f.iconst(IrType::Ptr, header_len);
+        f.call("fwrite", vec![header, one, len, file], Some(IrType::Ptr));
+        let counters = f.global_addr(&self.counters);
+        let eight = f.iconst(IrType::Ptr, 8);
+        let count = f.iconst(IrType::Ptr, self.lines.len() as i64);
+        f.call(
+            "fwrite",
+            vec![counters, eight, count, file],
+            Some(IrType::Ptr),
+        );
+        f.call("fclose", vec![file], Some(IrType::I32));
+        f.jump(done);
+        f.switch_to(done);
+        f.ret(None);
+        module.functions.push(f.finish());
+
+        let mut f = FunctionBuilder::new(&self.init, None);
+        let dump = f.global_addr(&self.dump);
+        f.call("atexit", vec![dump], Some(IrType::I32));
+        f.ret(None);
+        module.functions.push(f.finish());
+        module
+    }
+
+    /// Emits the zeroed counters and the entry that has the C runtime call
+    /// the constructor before `main`.
+    pub fn emit_data(&self, out: &mut Assembly, format: ObjectFormat) {
+        out.blank();
+        out.directive(".bss");
+        out.directive(".p2align 3");
+        out.label(format.symbol(&self.counters));
+        out.directive(format!(".zero {}", 8 * self.lines.len().max(1)));
+        out.blank();
+        out.directive(match format {
+            ObjectFormat::Elf => ".section .init_array,\"aw\"",
+            ObjectFormat::MachO => ".section __DATA,__mod_init_func,mod_init_funcs",
+        });
+        out.directive(".p2align 3");
+        out.directive(format!(".quad {}", format.symbol(&self.init)));
+    }
+}
+
+/// The 64-bit FNV-1a hash of `bytes`.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
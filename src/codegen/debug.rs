@@ -0,0 +1,370 @@
+//! DWARF debug information for `-g`.
+//!
+//! The line table comes from the `.file` and `.loc` directives the code
+//! generator emits, which the assembler turns into `.debug_line`. This module
+//! writes the rest by hand: one DWARF 4 compile unit in `.debug_info` with a
+//! subprogram per function, whose variables are located relative to the
+//! frame pointer, and the abbreviations those entries use in `.debug_abbrev`.
+//! Strings are stored inline, so no `.debug_str` is needed.
+
+use super::{quoted, Assembly, ObjectFormat};
+use crate::ir::IrType;
+
+/// The source file being compiled, as debug info names it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    /// The path as given to the compiler.
+    pub name: String,
+    /// The directory the compiler ran in, which a relative `name` is
+    /// resolved against.
+    pub directory: String,
+}
+
+/// What the code generator learned about a function while emitting it.
+pub(super) struct FunctionInfo {
+    pub name: String,
+    pub symbol: String,
+    /// A label at the function's first instruction.
+    pub begin: String,
+    /// A label right after the function's last instruction.
+    pub end: String,
+    pub line: Option<u32>,
+    pub return_type: Option<IrType>,
+    pub variables: Vec<VariableInfo>,
+}
+
+pub(super) struct VariableInfo {
+    pub name: String,
+    pub line: u32,
+    pub ty: IrType,
+    /// Bytes below the frame pointer.
+    pub offset: u32,
+}
+
+const DW_TAG_POINTER_TYPE: u8 = 0x0f;
+const DW_TAG_COMPILE_UNIT: u8 = 0x11;
+const DW_TAG_BASE_TYPE: u8 = 0x24;
+const DW_TAG_SUBPROGRAM: u8 = 0x2e;
+const DW_TAG_VARIABLE: u8 = 0x34;
+
+const DW_AT_LOCATION: u8 = 0x02;
+const DW_AT_NAME: u8 = 0x03;
+const DW_AT_BYTE_SIZE: u8 = 0x0b;
+const DW_AT_STMT_LIST: u8 = 0x10;
+const DW_AT_LOW_PC: u8 = 0x11;
+const DW_AT_HIGH_PC: u8 = 0x12;
+const DW_AT_LANGUAGE: u8 = 0x13;
+const DW_AT_COMP_DIR: u8 = 0x1b;
+const DW_AT_PRODUCER: u8 = 0x25;
+const DW_AT_DECL_FILE: u8 = 0x3a;
+const DW_AT_DECL_LINE: u8 = 0x3b;
+const DW_AT_ENCODING: u8 = 0x3e;
+const DW_AT_EXTERNAL: u8 = 0x3f;
+const DW_AT_FRAME_BASE: u8 = 0x40;
+const DW_AT_TYPE: u8 = 0x49;
+
+const DW_ATE_SIGNED: u8 = 0x05;
+const DW_LANG_C99: u16 = 0x0c;
+const DW_OP_REG0: u8 = 0x50;
+const DW_OP_FBREG: u8 = 0x91;
+
+/// An attribute value; its variant decides the form.
+enum Attr {
+    String(String),
+    Data1(u8),
+    Data2(u16),
+    Data4(u32),
+    /// An address, relocated by the linker.
+    Addr(String),
+    /// `end - start`, the form DWARF 4 uses for a `high_pc` relative to
+    /// `low_pc`.
+    Length {
+        end: String,
+        start: String,
+    },
+    /// The offset of `label` within its section, relocated by the linker
+    /// unless given as the difference from `base`, the section's start.
+    SecOffset {
+        label: String,
+        base: Option<String>,
+    },
+    /// A location expression.
+    Exprloc(Vec<u8>),
+    /// The entry labelled `label`, as an offset from the start of the unit.
+    Ref(String),
+    /// An attribute that is true by being present.
+    Flag,
+}
+
+impl Attr {
+    fn form(&self) -> u8 {
+        match self {
+            Attr::Addr(_) => 0x01,
+            Attr::Data2(_) => 0x05,
+            Attr::Data4(_) | Attr::Length { .. } => 0x06,
+            Attr::String(_) => 0x08,
+            Attr::Data1(_) => 0x0b,
+            Attr::Ref(_) => 0x13,
+            Attr::SecOffset { .. } => 0x17,
+            Attr::Exprloc(_) => 0x18,
+            Attr::Flag => 0x19,
+        }
+    }
+}
+
+/// The tag, whether there are children, and each attribute's name and form:
+/// what an abbreviation records.
+type Shape = (u8, bool, Vec<(u8, u8)>);
+
+/// A debugging information entry.
+struct Die {
+    tag: u8,
+    /// Emitted before the entry so references can find it.
+    label: Option<String>,
+    attrs: Vec<(u8, Attr)>,
+    children: Vec<Die>,
+}
+
+impl Die {
+    fn new(tag: u8) -> Self {
+        Self {
+            tag,
+            label: None,
+            attrs: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn attr(mut self, name: u8, value: Attr) -> Self {
+        self.attrs.push((name, value));
+        self
+    }
+
+    /// The abbreviation describing this entry's shape.
+    fn shape(&self) -> Shape {
+        let attrs = self
+            .attrs
+            .iter()
+            .map(|(name, value)| (*name, value.form()))
+            .collect();
+        (self.tag, !self.children.is_empty(), attrs)
+    }
+}
+
+/// Writes `.debug_abbrev`, `.debug_info` and the start of `.debug_line` for
+/// `functions`, whose frames are addressed from DWARF register
+/// `frame_register`.
+pub(super) fn emit(
+    out: &mut Assembly,
+    format: ObjectFormat,
+    source: &SourceFile,
+    functions: &[FunctionInfo],
+    frame_register: u8,
+) {
+    let label = |name: &str| format!("{}.debug_{name}", format.local_label_prefix());
+    let (abbrev, info, line) = (label("abbrev"), label("info"), label("line"));
+    let type_label = |ty: IrType| label(&format!("type.{ty}"));
+
+    let mut unit = Die::new(DW_TAG_COMPILE_UNIT)
+        .attr(
+            DW_AT_PRODUCER,
+            Attr::String(format!("rcc {}", env!("CARGO_PKG_VERSION"))),
+        )
+        .attr(DW_AT_LANGUAGE, Attr::Data2(DW_LANG_C99))
+        .attr(DW_AT_NAME, Attr::String(source.name.clone()))
+        .attr(DW_AT_STMT_LIST, section_offset(format, &line, &line))
+        .attr(DW_AT_COMP_DIR, Attr::String(source.directory.clone()));
+    // Functions are emitted back to back, so they cover one range of `.text`.
+    if let (Some(first), Some(last)) = (functions.first(), functions.last()) {
+        unit = unit
+            .attr(DW_AT_LOW_PC, Attr::Addr(first.symbol.clone()))
+            .attr(
+                DW_AT_HIGH_PC,
+                Attr::Length {
+                    end: last.end.clone(),
+                    start: first.begin.clone(),
+                },
+            );
+    }
+
+    let mut types = Vec::new();
+    for function in functions {
+        let mut subprogram = Die::new(DW_TAG_SUBPROGRAM)
+            .attr(DW_AT_LOW_PC, Attr::Addr(function.symbol.clone()))
+            .attr(
+                DW_AT_HIGH_PC,
+                Attr::Length {
+                    end: function.end.clone(),
+                    start: function.begin.clone(),
+                },
+            )
+            .attr(
+                DW_AT_FRAME_BASE,
+                Attr::Exprloc(vec![DW_OP_REG0 + frame_register]),
+            )
+            .attr(DW_AT_NAME, Attr::String(function.name.clone()))
+            .attr(DW_AT_DECL_FILE, Attr::Data1(1));
+        if let Some(line) = function.line {
+            subprogram = subprogram.attr(DW_AT_DECL_LINE, Attr::Data4(line));
+        }
+        if let Some(ty) = function.return_type {
+            subprogram = subprogram.attr(DW_AT_TYPE, Attr::Ref(type_label(ty)));
+            types.push(ty);
+        }
+        subprogram = subprogram.attr(DW_AT_EXTERNAL, Attr::Flag);
+        for variable in &function.variables {
+            let mut location = vec![DW_OP_FBREG];
+            location.extend(sleb128(-i64::from(variable.offset)));
+            subprogram.children.push(
+                Die::new(DW_TAG_VARIABLE)
+                    .attr(DW_AT_NAME, Attr::String(variable.name.clone()))
+                    .attr(DW_AT_DECL_FILE, Attr::Data1(1))
+                    .attr(DW_AT_DECL_LINE, Attr::Data4(variable.line))
+                    .attr(DW_AT_TYPE, Attr::Ref(type_label(variable.ty)))
+                    .attr(DW_AT_LOCATION, Attr::Exprloc(location)),
+            );
+            types.push(variable.ty);
+        }
+        unit.children.push(subprogram);
+    }
+    for ty in [IrType::I8, IrType::I16, IrType::I32, IrType::Ptr] {
+        if !types.contains(&ty) {
+            continue;
+        }
+        let mut die = match ty {
+            IrType::I8 | IrType::I16 | IrType::I32 => {
+                let name = match ty {
+                    IrType::I8 => "signed char",
+                    IrType::I16 => "short",
+                    _ => "int",
+                };
+                Die::new(DW_TAG_BASE_TYPE)
+                    .attr(DW_AT_NAME, Attr::String(name.to_string()))
+                    .attr(DW_AT_ENCODING, Attr::Data1(DW_ATE_SIGNED))
+            }
+            // Pointers are untyped in the IR, so describe them as `void *`.
+            IrType::Ptr => Die::new(DW_TAG_POINTER_TYPE),
+        }
+        .attr(DW_AT_BYTE_SIZE, Attr::Data1(ty.bytes() as u8));
+        die.label = Some(type_label(ty));
+        unit.children.push(die);
+    }
+
+    let mut shapes = Vec::new();
+    let mut body = Assembly::new(out.syntax);
+    write_die(&mut body, &unit, &mut shapes, &info);
+
+    out.blank();
+    out.directive(section(format, "abbrev"));
+    out.label(&abbrev);
+    for (code, (tag, children, attrs)) in shapes.iter().enumerate() {
+        out.directive(format!(".uleb128 {}", code + 1));
+        out.directive(format!(".uleb128 {tag:#x}"));
+        out.directive(format!(".byte {}", u8::from(*children)));
+        for (name, form) in attrs {
+            out.directive(format!(".uleb128 {name:#x}"));
+            out.directive(format!(".uleb128 {form:#x}"));
+        }
+        out.directive(".byte 0");
+        out.directive(".byte 0");
+    }
+    out.directive(".byte 0");
+
+    out.directive(section(format, "info"));
+    let (start, end) = (label("info_start"), label("info_end"));
+    out.label(&info);
+    out.directive(format!(".long {end}-{start}"));
+    out.label(&start);
+    out.directive(".short 4");
+    write_attr(out, &section_offset(format, &abbrev, &abbrev), &info);
+    out.directive(".byte 8");
+    out.lines.append(&mut body.lines);
+    out.label(&end);
+
+    // The assembler appends the line table it builds to this section.
+    out.directive(section(format, "line"));
+    out.label(&line);
+}
+
+/// The directive switching to the debug section `.debug_<name>`.
+fn section(format: ObjectFormat, name: &str) -> String {
+    match format {
+        ObjectFormat::MachO => format!(".section __DWARF,__debug_{name},regular,debug"),
+        ObjectFormat::Elf => format!(".section .debug_{name},\"\",%progbits"),
+    }
+}
+
+/// A reference to `label` in another debug section that starts at
+/// `section`. ELF objects need a relocation, since the linker concatenates
+/// sections; Mach-O debug sections stay in the object files, so the plain
+/// offset is enough.
+fn section_offset(format: ObjectFormat, label: &str, section: &str) -> Attr {
+    Attr::SecOffset {
+        label: label.to_string(),
+        base: (format == ObjectFormat::MachO).then(|| section.to_string()),
+    }
+}
+
+/// Writes `die` and its children, adding any new abbreviations to `shapes`.
+/// References are measured from `unit`, the label of the unit header.
+fn write_die(out: &mut Assembly, die: &Die, shapes: &mut Vec<Shape>, unit: &str) {
+    let shape = die.shape();
+    let code = match shapes.iter().position(|s| *s == shape) {
+        Some(index) => index + 1,
+        None => {
+            shapes.push(shape);
+            shapes.len()
+        }
+    };
+    if let Some(label) = &die.label {
+        out.label(label);
+    }
+    out.directive(format!(".uleb128 {code}"));
+    for (_, value) in &die.attrs {
+        write_attr(out, value, unit);
+    }
+    if !die.children.is_empty() {
+        for child in &die.children {
+            write_die(out, child, shapes, unit);
+        }
+        out.directive(".byte 0");
+    }
+}
+
+fn write_attr(out: &mut Assembly, value: &Attr, unit: &str) {
+    let directive = match value {
+        Attr::String(s) => format!(".asciz {}", quoted(s.as_bytes())),
+        Attr::Data1(v) => format!(".byte {v}"),
+        Attr::Data2(v) => format!(".short {v}"),
+        Attr::Data4(v) => format!(".long {v}"),
+        Attr::Addr(label) => format!(".quad {label}"),
+        Attr::Length { end, start } => format!(".long {end}-{start}"),
+        Attr::SecOffset { label, base: None } => format!(".long {label}"),
+        Attr::SecOffset {
+            label,
+            base: Some(base),
+        } => format!(".long {label}-{base}"),
+        Attr::Exprloc(bytes) => {
+            let bytes: Vec<String> = bytes.iter().map(|b| format!("{b:#04x}")).collect();
+            out.directive(format!(".uleb128 {}", bytes.len()));
+            format!(".byte {}", bytes.join(", "))
+        }
+        Attr::Ref(label) => format!(".long {label}-{unit}"),
+        Attr::Flag => return,
+    };
+    out.directive(directive);
+}
+
+/// `value` in signed LEB128.
+fn sleb128(mut value: i64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        bytes.push(if done { byte } else { byte | 0x80 });
+        if done {
+            return bytes;
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Assembler-local labels.
+//!
+//! Every label a function uses is `<prefix><func>_<suffix>`, where the
+//! prefix keeps it out of the symbol table (`L` on Mach-O, `.L` on ELF) and
+//! the suffix never contains `_`. A label therefore names exactly one
+//! function, and since C identifiers cannot start with `.` and Mach-O
+//! symbols for C names start with `_`, no label can collide with a user
+//! symbol. Pool strings are `.str.<n>` and debug info labels `.debug_<name>`
+//! after the prefix, which no function name can produce.
+
+use crate::ir::{BlockId, Function, StringId};
+
+use super::ObjectFormat;
+
+/// Hands out the labels of one function. Blocks keep their number as the
+/// suffix; [`Labels::fresh`] counts on from there.
+#[derive(Debug, Clone)]
+pub struct Labels {
+    prefix: String,
+    next: u32,
+}
+
+impl Labels {
+    pub fn new(format: ObjectFormat, func: &Function) -> Self {
+        Self {
+            prefix: format!("{}{}_", format.local_label_prefix(), func.name),
+            next: func.blocks.len() as u32,
+        }
+    }
+
+    /// The label of a block other than the entry block.
+    pub fn block(&self, block: BlockId) -> String {
+        format!("{}{}", self.prefix, block.0)
+    }
+
+    /// The label of the epilogue shared by every return.
+    pub fn epilogue(&self) -> String {
+        format!("{}epilogue", self.prefix)
+    }
+
+    /// A label at the function's first instruction. Sizes are measured from
+    /// it rather than from the symbol because Mach-O assemblers cannot
+    /// subtract a global symbol from a label.
+    pub fn begin(&self) -> String {
+        format!("{}begin", self.prefix)
+    }
+
+    /// The label after the function's last instruction.
+    pub fn end(&self) -> String {
+        format!("{}end", self.prefix)
+    }
+
+    /// A label no other call returns.
+    pub fn fresh(&mut self) -> String {
+        let label = format!("{}{}", self.prefix, self.next);
+        self.next += 1;
+        label
+    }
+}
+
+/// The label of a pool string. Mach-O uses a linker-private `l` label rather
+/// than a temporary one so that arm64 page relocations have a symbol to
+/// refer to; the linker still drops it from the output.
+pub fn string_label(format: ObjectFormat, id: StringId) -> String {
+    match format {
+        ObjectFormat::MachO => format!("l_.str.{}", id.0),
+        ObjectFormat::Elf => format!(".L.str.{}", id.0),
+    }
+}
@@ -0,0 +1,1035 @@
+//! Assembly generation from the IR.
+//!
+//! [`CodeGenerator`] walks each function, lays out its frame and decides
+//! where returns and branches go; everything that depends on the target —
+//! instruction selection, registers, calling convention and assembly
+//! syntax — comes from a [`Backend`].
+
+/// Appends an instruction, given its mnemonic and operands, to a generator's
+/// output.
+macro_rules! emit {
+    ($gen:expr, $mnemonic:expr $(, $operand:expr)* $(,)?) => {
+        $gen.out.instruction($crate::codegen::asm::Instruction::new(
+            $mnemonic,
+            vec![$($operand),*],
+        ))
+    };
+}
+
+pub mod aarch64;
+pub mod asm;
+mod coverage;
+mod debug;
+mod label;
+pub mod peephole;
+pub mod regalloc;
+mod target;
+pub mod wasm;
+pub mod x86_64;
+
+use asm::{Assembly, Line, Syntax};
+use coverage::Coverage;
+pub use debug::SourceFile;
+pub use label::{string_label, Labels};
+use regalloc::{Allocation, Location, RegisterSet};
+pub use target::{Arch, ObjectFormat, Os, Target, Visibility};
+
+use std::collections::{HashMap, HashSet};
+
+use debug::{FunctionInfo, VariableInfo};
+
+use crate::coverage::Files;
+use crate::ir::{
+    BlockId, CmpOp, Datum, Function, Global, Instr, IrType, Module, StackSlot, StringPool,
+    Terminator, Value,
+};
+
+/// Everything the code generator needs to know about a target.
+pub trait Backend {
+    /// Registers the allocator may hand out; the rest are scratch or reserved.
+    fn registers(&self) -> RegisterSet<'static>;
+
+    fn calling_convention(&self) -> CallingConvention;
+
+    /// Emits whatever precedes the first function.
+    fn begin_module(&self, out: &mut Assembly);
+
+    /// Emits whatever follows the last function.
+    fn end_module(&self, _out: &mut Assembly) {}
+
+    /// Emits the directives and label that start `func`, exported with
+    /// `visibility`.
+    fn begin_function(&self, out: &mut Assembly, func: &Function, visibility: Visibility);
+
+    /// Emits whatever follows the last instruction of `func`.
+    fn end_function(&self, _out: &mut Assembly, _func: &Function) {}
+
+    /// Emits the module's string literals into a read-only section.
+    fn string_pool(&self, out: &mut Assembly, strings: &StringPool);
+
+    /// Emits the definition of `global`, exported with `visibility`, in
+    /// `.bss` when it is all zeros and in `.data` otherwise.
+    fn global(&self, out: &mut Assembly, global: &Global, visibility: Visibility);
+
+    /// Decides symbol names and the form of local labels.
+    fn object_format(&self) -> ObjectFormat;
+
+    /// How the target's assembler spells instructions and operands.
+    fn syntax(&self) -> Syntax;
+
+    /// The DWARF number of the frame pointer, which debug info locates
+    /// stack slots from.
+    fn dwarf_frame_register(&self) -> u8;
+
+    /// Saves what the function must preserve, reserves its frame and stores
+    /// the canary if the frame has one.
+    fn prologue(&self, e: &mut Emitter);
+
+    /// Adds one to the 64-bit counter at `index` in the array `symbol`.
+    fn count(&self, e: &mut Emitter, symbol: &str, index: usize);
+
+    /// Calls the profiler's `mcount` hook, from just after the prologue and
+    /// before the parameters are moved out of their registers (`-pg`).
+    fn mcount(&self, e: &mut Emitter);
+
+    /// Checks the canary, restores saved registers, releases the frame and
+    /// returns.
+    fn epilogue(&self, e: &mut Emitter);
+
+    /// Moves the parameter at `index` from where the caller put it to the
+    /// location of `value`.
+    fn receive_param(&self, e: &mut Emitter, index: usize, value: Value);
+
+    fn instr(&self, e: &mut Emitter, instr: &Instr);
+
+    /// Puts a returned value where the caller expects it.
+    fn return_value(&self, e: &mut Emitter, value: Value);
+
+    fn jump(&self, e: &mut Emitter, label: &str);
+
+    /// Jumps to `label` if `cond` is nonzero, or zero when `nonzero` is
+    /// false, and falls through otherwise.
+    fn branch(&self, e: &mut Emitter, cond: Value, nonzero: bool, label: &str);
+
+    /// Jumps to `label` if `lhs op rhs` holds and falls through otherwise,
+    /// without materializing the comparison's result.
+    fn compare_and_branch(&self, e: &mut Emitter, op: CmpOp, lhs: Value, rhs: Value, label: &str);
+
+    /// Jumps to `label` if `value op imm` holds and falls through otherwise.
+    fn compare_imm_and_branch(
+        &self,
+        e: &mut Emitter,
+        op: CmpOp,
+        value: Value,
+        imm: i64,
+        label: &str,
+    );
+
+    /// Jumps to `targets[value - min]`, or to `default` if `value - min` is
+    /// outside the table, which is emitted in place.
+    fn jump_table(
+        &self,
+        e: &mut Emitter,
+        value: Value,
+        min: i64,
+        targets: &[String],
+        default: &str,
+    );
+
+    /// Traps; emitted with `code` 0 for `trap` and 1 for `unreachable` and
+    /// overflow. `code` is the immediate of AArch64's `brk`; x86-64's `ud2`
+    /// has none.
+    fn trap(&self, e: &mut Emitter, code: u16);
+
+    /// Runs the target's peephole optimizer over generated assembly.
+    fn peephole(&self, asm: Assembly) -> Assembly {
+        asm
+    }
+}
+
+/// How arguments are passed and registers are saved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallingConvention {
+    /// Integer arguments passed in registers; the rest go on the stack.
+    pub arg_regs: usize,
+    /// Bytes each stack-passed argument occupies.
+    pub stack_arg_size: u32,
+    /// Alignment of the area holding saved callee-saved registers.
+    pub save_area_align: u32,
+    /// Whether variable arguments all go on the stack in 8-byte slots,
+    /// even while argument registers are free.
+    pub variadic_on_stack: bool,
+}
+
+impl CallingConvention {
+    /// Bytes of stack used by the arguments before index `count`.
+    pub fn stack_args_size(&self, count: usize) -> u32 {
+        count.saturating_sub(self.arg_regs) as u32 * self.stack_arg_size
+    }
+
+    /// Whether argument `index` of a call is passed in a register, where
+    /// the call passes the arguments from `fixed` on as variable ones.
+    pub fn in_register(&self, index: usize, fixed: Option<usize>) -> bool {
+        index < self.arg_regs && !self.on_stack_as_variadic(index, fixed)
+    }
+
+    /// Offset from the stack pointer of argument `index` of a call, or
+    /// with `index` the argument count, the bytes all of them take.
+    pub fn call_arg_offset(&self, index: usize, fixed: Option<usize>) -> u32 {
+        match fixed {
+            Some(fixed) if self.on_stack_as_variadic(index, Some(fixed)) => {
+                self.stack_args_size(fixed).next_multiple_of(8) + 8 * (index - fixed) as u32
+            }
+            _ => self.stack_args_size(index),
+        }
+    }
+
+    fn on_stack_as_variadic(&self, index: usize, fixed: Option<usize>) -> bool {
+        self.variadic_on_stack && fixed.is_some_and(|fixed| index >= fixed)
+    }
+}
+
+/// The layout of a function's stack frame below the frame pointer: saved
+/// callee-saved registers, spilled values, stack slots, and finally the
+/// outgoing argument area at the stack pointer for calls that pass
+/// arguments on the stack.
+struct Frame {
+    alloc: Allocation,
+    /// Callee-saved registers the function uses, in ascending order.
+    saved: Vec<u8>,
+    /// Offset below the frame pointer where spilled values start, past the
+    /// saved registers and the canary.
+    spill_base: u32,
+    /// Offset below the frame pointer of the stack protector's canary, in
+    /// functions that have one.
+    canary: Option<u32>,
+    /// Offset below the frame pointer of each stack slot.
+    slot_offsets: Vec<u32>,
+    /// Bytes reserved below the frame pointer, kept a multiple of 16 as both
+    /// supported ABIs require at calls.
+    size: u32,
+    /// Bytes at the bottom of the frame for stack-passed call arguments.
+    outgoing: u32,
+    /// Whether the function makes dynamic allocations, so the stack pointer
+    /// has to be restored from the frame pointer on return.
+    dynamic: bool,
+    /// Whether the prologue sets up a frame pointer. Only a leaf function
+    /// with nothing on the stack can do without one.
+    pointer: bool,
+}
+
+impl Frame {
+    fn new(
+        func: &Function,
+        registers: RegisterSet,
+        conv: CallingConvention,
+        stack_protector: bool,
+    ) -> Self {
+        let alloc = Allocation::compute(func, registers);
+        let saved: Vec<u8> = alloc
+            .used_registers()
+            .into_iter()
+            .filter(|reg| registers.callee_saved.contains(reg))
+            .collect();
+        let saved_size = (saved.len() as u32 * 8).next_multiple_of(conv.save_area_align);
+        let dynamic = func
+            .blocks
+            .iter()
+            .flat_map(|b| &b.instrs)
+            .any(|instr| matches!(instr, Instr::Alloca { .. }));
+        let buffers = dynamic || func.slots.iter().any(|slot| slot.is_array());
+        // The canary sits between the locals and the saved registers and
+        // return address, so an overflowing buffer reaches it first.
+        let canary = (stack_protector && buffers).then_some(saved_size + 8);
+        let spill_base = canary.unwrap_or(saved_size);
+        let mut offset = spill_base + alloc.spill_size;
+        // Scalars go nearest the frame pointer, where AArch64 loads and
+        // stores reach them with an immediate offset; arrays are only
+        // addressed, which reaches further.
+        let mut slot_offsets = vec![0; func.slots.len()];
+        let (scalars, arrays): (Vec<_>, Vec<_>) = func
+            .slots
+            .iter()
+            .enumerate()
+            .partition(|(_, slot)| !slot.is_array());
+        for (i, slot) in scalars.into_iter().chain(arrays) {
+            offset = (offset + slot.size()).next_multiple_of(slot.ty.bytes());
+            slot_offsets[i] = offset;
+        }
+        let outgoing = func
+            .blocks
+            .iter()
+            .flat_map(|b| &b.instrs)
+            .map(|instr| match instr {
+                Instr::Call { args, fixed, .. } => conv.call_arg_offset(args.len(), *fixed),
+                _ => 0,
+            })
+            .max()
+            .unwrap_or(0);
+        Self {
+            alloc,
+            saved,
+            spill_base,
+            canary,
+            slot_offsets,
+            size: (offset + outgoing).next_multiple_of(16),
+            outgoing,
+            dynamic,
+            pointer: true,
+        }
+    }
+}
+
+/// The state a backend sees while generating one function.
+pub struct Emitter<'a> {
+    out: &'a mut Assembly,
+    func: &'a Function,
+    frame: Frame,
+    conv: CallingConvention,
+    labels: Labels,
+    /// Whether the code may be loaded at any address, including into a
+    /// shared object whose globals another module can preempt.
+    pic: bool,
+    /// Globals defined in the module being generated.
+    defined_globals: &'a HashSet<&'a str>,
+    visibility: Visibility,
+    /// Whether signed `i32` arithmetic traps when it overflows.
+    overflow_checks: bool,
+    /// The label of the function's overflow trap, once something branches
+    /// to it.
+    overflow_trap: Option<String>,
+    /// The module's line counters, when collecting coverage.
+    coverage: Option<&'a Coverage>,
+    /// Whether to describe each change to the frame with CFI directives,
+    /// so a debugger can unwind the stack from any instruction.
+    cfi: bool,
+}
+
+impl Emitter<'_> {
+    /// Where `value` lives, with stack offsets measured below the frame
+    /// pointer.
+    fn location(&self, value: Value) -> Location {
+        match self.frame.alloc.location(value) {
+            Location::Stack(offset) => Location::Stack(self.frame.spill_base + offset),
+            location => location,
+        }
+    }
+
+    /// Whether `value` needs a 64-bit register.
+    fn is_wide(&self, value: Value) -> bool {
+        self.func.value_type(value).bits() == 64
+    }
+
+    fn slot_offset(&self, slot: StackSlot) -> u32 {
+        self.frame.slot_offsets[slot.0 as usize]
+    }
+
+    /// Offset from the stack pointer of the stack-passed argument at `index`.
+    fn stack_arg_offset(&self, index: usize) -> u32 {
+        self.conv.stack_args_size(index)
+    }
+
+    /// Whether the address of the global `name` has to be loaded from the
+    /// GOT. On ELF that is any global in position-independent code, since
+    /// another module may preempt it, unless it is hidden and defined here;
+    /// on Mach-O, which never preempts, it is any global defined outside
+    /// the module.
+    fn global_via_got(&self, format: ObjectFormat, name: &str) -> bool {
+        match format {
+            ObjectFormat::Elf => {
+                self.pic
+                    && !(self.visibility == Visibility::Hidden
+                        && self.defined_globals.contains(name))
+            }
+            ObjectFormat::MachO => !self.defined_globals.contains(name),
+        }
+    }
+
+    /// Whether an `add`, `sub` or `mul` defining `value` must check for
+    /// signed overflow. Only `i32` is C's `int`; pointers wrap.
+    fn checks_overflow(&self, value: Value) -> bool {
+        self.overflow_checks && self.func.value_type(value) == IrType::I32
+    }
+
+    /// The label that overflowing arithmetic branches to. The trap itself is
+    /// emitted after the function's last block.
+    fn overflow_trap(&mut self) -> String {
+        let labels = &mut self.labels;
+        self.overflow_trap
+            .get_or_insert_with(|| labels.fresh())
+            .clone()
+    }
+
+    fn label(&mut self, name: &str) {
+        self.out.label(name);
+    }
+
+    fn directive(&mut self, text: String) {
+        self.out.directive(text);
+    }
+
+    /// Emits the CFI directive `text` if the frame is being described.
+    fn cfi(&mut self, text: impl Into<String>) {
+        if self.cfi {
+            self.out.directive(text);
+        }
+    }
+
+    /// Emits an inline assembly template with its operands substituted.
+    /// The lines are kept verbatim, so the peephole optimizer, which only
+    /// rewrites generated instructions, leaves them alone.
+    fn inline_asm(&mut self, template: &str, operands: &[&str]) {
+        for line in expand_asm(template, operands) {
+            self.out.lines.push(Line::Verbatim(line));
+        }
+    }
+}
+
+pub struct CodeGenerator<'a> {
+    backend: &'a dyn Backend,
+    allocate_registers: bool,
+    debug_info: Option<SourceFile>,
+    /// Whether to describe functions and variables as well as lines.
+    full_debug_info: bool,
+    /// The source text, to quote in comments at each source location.
+    source: Option<&'a str>,
+    pic: bool,
+    omit_frame_pointer: bool,
+    overflow_checks: bool,
+    stack_protector: bool,
+    profile: bool,
+    coverage: Option<Files>,
+    /// Whether to record each instruction's source position.
+    source_map: bool,
+    visibility: Visibility,
+}
+
+impl Default for CodeGenerator<'_> {
+    fn default() -> Self {
+        Self {
+            backend: Target::default().backend().unwrap(),
+            allocate_registers: false,
+            debug_info: None,
+            full_debug_info: false,
+            source: None,
+            pic: false,
+            omit_frame_pointer: false,
+            overflow_checks: false,
+            stack_protector: false,
+            profile: false,
+            coverage: None,
+            source_map: false,
+            visibility: Visibility::Default,
+        }
+    }
+}
+
+impl<'a> CodeGenerator<'a> {
+    /// A generator for the default target that keeps every value on the stack.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_backend(mut self, backend: &'a dyn Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Keeps values in registers where possible instead of on the stack.
+    pub fn with_register_allocation(mut self, enabled: bool) -> Self {
+        self.allocate_registers = enabled;
+        self
+    }
+
+    /// Emits DWARF describing `source`: a line table built from the IR's
+    /// source locations, and the functions and their variables.
+    pub fn with_debug_info(mut self, source: SourceFile) -> Self {
+        self.debug_info = Some(source);
+        self.full_debug_info = true;
+        self
+    }
+
+    /// Emits only `.file` and `.loc` directives mapping instructions back to
+    /// `source`, from which the assembler builds a line table.
+    pub fn with_line_tables(mut self, source: SourceFile) -> Self {
+        self.debug_info = Some(source);
+        self.full_debug_info = false;
+        self
+    }
+
+    /// Annotates the assembly with the lines of `source` that each group of
+    /// instructions came from, wherever the IR records a source location.
+    pub fn with_source_comments(mut self, source: &'a str) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Exports the module's functions and globals with `visibility`.
+    pub fn with_visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    /// Records on each instruction the source position the IR gives for
+    /// it, for [`Assembly::positions`].
+    pub fn with_source_map(mut self, enabled: bool) -> Self {
+        self.source_map = enabled;
+        self
+    }
+
+    /// Generates position-independent code, which can be linked into a
+    /// shared object: globals are addressed through the GOT and, on x86-64
+    /// ELF, calls go through the PLT.
+    pub fn with_pic(mut self, enabled: bool) -> Self {
+        self.pic = enabled;
+        self
+    }
+
+    /// Skips the frame pointer in leaf functions that keep nothing on the
+    /// stack, leaving them without a frame record for backtraces.
+    pub fn with_omit_frame_pointer(mut self, enabled: bool) -> Self {
+        self.omit_frame_pointer = enabled;
+        self
+    }
+
+    /// Checks signed `int` addition, subtraction and multiplication for
+    /// overflow, trapping instead of wrapping
+    /// (`-fsanitize=signed-integer-overflow`).
+    pub fn with_overflow_checks(mut self, enabled: bool) -> Self {
+        self.overflow_checks = enabled;
+        self
+    }
+
+    /// Guards functions with stack-allocated buffers against overflows
+    /// that would overwrite the return address: the prologue stores a copy
+    /// of the C library's stack guard and the epilogue calls
+    /// `__stack_chk_fail` if it has changed (`-fstack-protector`).
+    pub fn with_stack_protector(mut self, enabled: bool) -> Self {
+        self.stack_protector = enabled;
+        self
+    }
+
+    /// Calls `mcount` on entry to every function, so gprof-style tools can
+    /// build a call graph (`-pg`).
+    pub fn with_profiling(mut self, enabled: bool) -> Self {
+        self.profile = enabled;
+        self
+    }
+
+    /// Counts how many times each source line runs, writing the counts to
+    /// `files.data` when the program exits (`--coverage`). Only lines the
+    /// IR has source locations for are counted.
+    pub fn with_coverage(mut self, files: Files) -> Self {
+        self.coverage = Some(files);
+        self
+    }
+
+    /// Generates `module` as assembly text, built in memory in one pass;
+    /// nothing is written to disk.
+    pub fn generate(&self, module: &Module) -> String {
+        self.generate_assembly(module).to_string()
+    }
+
+    /// Generates `module` without printing it, for passes that work on
+    /// instructions.
+    pub fn generate_assembly(&self, module: &Module) -> Assembly {
+        let mut out = Assembly::new(self.backend.syntax());
+        if let Some(source) = &self.debug_info {
+            out.directive(format!(".file 1 {}", quoted(source.name.as_bytes())));
+        }
+        self.backend.begin_module(&mut out);
+        let coverage = self
+            .coverage
+            .as_ref()
+            .map(|files| (Coverage::new(module, files), files));
+        let instrumented;
+        let module = match &coverage {
+            Some((coverage, files)) => {
+                instrumented = coverage.instrument(module, files);
+                &instrumented
+            }
+            None => module,
+        };
+        let coverage = coverage.as_ref().map(|(coverage, _)| coverage);
+        let mut defined_globals: HashSet<&str> =
+            module.globals.iter().map(|g| g.name.as_str()).collect();
+        defined_globals.extend(coverage.map(|coverage| coverage.counters.as_str()));
+        let functions: Vec<_> = module
+            .functions
+            .iter()
+            .map(|function| self.generate_function(&mut out, function, &defined_globals, coverage))
+            .collect();
+        if !module.strings.is_empty() {
+            self.backend.string_pool(&mut out, &module.strings);
+        }
+        for global in &module.globals {
+            self.backend.global(&mut out, global, self.visibility);
+        }
+        if let Some(coverage) = coverage {
+            coverage.emit_data(&mut out, self.backend.object_format());
+        }
+        if let (Some(source), true) = (&self.debug_info, self.full_debug_info) {
+            let format = self.backend.object_format();
+            let frame_register = self.backend.dwarf_frame_register();
+            debug::emit(&mut out, format, source, &functions, frame_register);
+        }
+        self.backend.end_module(&mut out);
+        out
+    }
+
+    fn generate_function(
+        &self,
+        out: &mut Assembly,
+        func: &Function,
+        defined_globals: &HashSet<&str>,
+        coverage: Option<&Coverage>,
+    ) -> FunctionInfo {
+        crate::ice::set_function(&func.name);
+        let backend = self.backend;
+        let conv = backend.calling_convention();
+        let registers = if self.allocate_registers {
+            backend.registers()
+        } else {
+            RegisterSet::default()
+        };
+        out.pos = func.pos.filter(|_| self.source_map);
+        backend.begin_function(out, func, self.visibility);
+        let mut frame = Frame::new(func, registers, conv, self.stack_protector);
+        // The call to `mcount` makes every function a caller, as does
+        // calling a thread-local's accessor on Mach-O.
+        let macho = backend.object_format() == ObjectFormat::MachO;
+        let is_leaf = !self.profile
+            && !func
+                .blocks
+                .iter()
+                .flat_map(|b| &b.instrs)
+                .any(|instr| match instr {
+                    Instr::Call { .. } => true,
+                    Instr::GlobalAddr { thread_local, .. } => *thread_local && macho,
+                    _ => false,
+                });
+        // Stack-passed parameters are read relative to the frame pointer.
+        let stack_params = func.params.len() > conv.arg_regs;
+        let frameless = frame.size == 0 && !frame.dynamic && !stack_params;
+        if self.omit_frame_pointer && is_leaf && frameless {
+            frame.pointer = false;
+        }
+        let mut e = Emitter {
+            out,
+            func,
+            frame,
+            conv,
+            labels: Labels::new(backend.object_format(), func),
+            pic: self.pic,
+            defined_globals,
+            visibility: self.visibility,
+            overflow_checks: self.overflow_checks,
+            overflow_trap: None,
+            coverage,
+            cfi: self.debug_info.is_some(),
+        };
+
+        let debug = self.debug_info.is_some();
+        let begin = e.labels.begin();
+        if self.full_debug_info {
+            e.label(&begin);
+        }
+        e.cfi(".cfi_startproc");
+        let mut commented_line = None;
+        if let Some(pos) = func.pos {
+            if debug {
+                e.directive(format!(".loc 1 {} {}", pos.line, pos.column));
+            }
+            self.source_comment(&mut e, pos.line, &mut commented_line);
+        }
+        // The first line after the prologue is where a breakpoint on the
+        // function should stop.
+        let mut prologue_end = " prologue_end";
+
+        backend.prologue(&mut e);
+        if self.profile {
+            backend.mcount(&mut e);
+        }
+        for (i, &param) in func.params.iter().enumerate() {
+            backend.receive_param(&mut e, i, param);
+        }
+
+        // Every return shares one epilogue, placed after the last block so the
+        // final return can fall into it.
+        let order = layout(func);
+        let uses = use_counts(func);
+        let (mut returns, mut branches_to_epilogue) = (false, false);
+        for (i, &id) in order.iter().enumerate() {
+            let block = &func.blocks[id.index()];
+            let next = order.get(i + 1).copied();
+            if id != BlockId::ENTRY {
+                let label = e.labels.block(id);
+                e.label(&label);
+            }
+            // A block can be entered from anywhere, so its first line is
+            // always counted.
+            let mut counted_line = None;
+            // A comparison used only by the branch right after it becomes a
+            // conditional branch instead of a 0 or 1 in a register.
+            let fused = match (block.instrs.last(), &block.term) {
+                (Some(&Instr::Cmp { dst, op, lhs, rhs }), &Terminator::Branch { cond, .. })
+                    if cond == dst && uses[&dst] == 1 =>
+                {
+                    Some((op, lhs, rhs))
+                }
+                _ => None,
+            };
+            let body = &block.instrs[..block.instrs.len() - usize::from(fused.is_some())];
+            for instr in body {
+                match instr {
+                    Instr::Loc { pos } => {
+                        if self.source_map {
+                            e.out.pos = Some(*pos);
+                        }
+                        if debug {
+                            e.directive(format!(
+                                ".loc 1 {} {}{prologue_end}",
+                                pos.line, pos.column
+                            ));
+                            prologue_end = "";
+                        }
+                        self.source_comment(&mut e, pos.line, &mut commented_line);
+                        let index = e.coverage.and_then(|coverage| coverage.index(pos.line));
+                        if let (Some(coverage), Some(index)) = (e.coverage, index) {
+                            if counted_line.replace(pos.line) != Some(pos.line) {
+                                backend.count(&mut e, &coverage.counters, index);
+                            }
+                        }
+                    }
+                    _ => backend.instr(&mut e, instr),
+                }
+            }
+            match block.term {
+                Terminator::Ret(value) => {
+                    if let Some(value) = value {
+                        backend.return_value(&mut e, value);
+                    }
+                    if next.is_some() {
+                        let label = e.labels.epilogue();
+                        backend.jump(&mut e, &label);
+                        branches_to_epilogue = true;
+                    }
+                    returns = true;
+                }
+                Terminator::Jump(target) => {
+                    if next != Some(target) {
+                        let label = e.labels.block(target);
+                        backend.jump(&mut e, &label);
+                    }
+                }
+                Terminator::Branch {
+                    cond,
+                    then_block,
+                    else_block,
+                } => {
+                    // Branch on whichever outcome does not fall into the next
+                    // block, and only jump unconditionally if neither does.
+                    let (target, holds) = if next == Some(then_block) {
+                        (else_block, false)
+                    } else {
+                        (then_block, true)
+                    };
+                    let label = e.labels.block(target);
+                    match fused {
+                        Some((op, lhs, rhs)) => {
+                            let op = if holds { op } else { op.inverse() };
+                            backend.compare_and_branch(&mut e, op, lhs, rhs, &label);
+                        }
+                        None => backend.branch(&mut e, cond, holds, &label),
+                    }
+                    if holds && next != Some(else_block) {
+                        let label = e.labels.block(else_block);
+                        backend.jump(&mut e, &label);
+                    }
+                }
+                Terminator::Switch {
+                    value,
+                    ref cases,
+                    default,
+                } => {
+                    let mut cases: Vec<_> = cases
+                        .iter()
+                        .map(|&(case, target)| (case, e.labels.block(target)))
+                        .collect();
+                    cases.sort_unstable_by_key(|&(case, _)| case);
+                    let default = e.labels.block(default);
+                    switch(backend, &mut e, value, &cases, &default);
+                }
+                Terminator::Trap => backend.trap(&mut e, 0),
+                Terminator::Unreachable => backend.trap(&mut e, 1),
+            }
+        }
+        if branches_to_epilogue {
+            let label = e.labels.epilogue();
+            e.label(&label);
+        }
+        if returns {
+            backend.epilogue(&mut e);
+        }
+        if let Some(label) = e.overflow_trap.take() {
+            e.label(&label);
+            backend.trap(&mut e, 1);
+        }
+        e.cfi(".cfi_endproc");
+        let end = e.labels.end();
+        let variables = func
+            .variables
+            .iter()
+            .map(|variable| VariableInfo {
+                name: variable.name.clone(),
+                line: variable.pos.line,
+                ty: func.slot_type(variable.slot),
+                offset: e.slot_offset(variable.slot),
+            })
+            .collect();
+        if self.full_debug_info {
+            e.label(&end);
+        }
+        backend.end_function(out, func);
+        out.pos = None;
+        FunctionInfo {
+            name: func.name.clone(),
+            symbol: backend.object_format().symbol(&func.name),
+            begin,
+            end,
+            line: func.pos.map(|pos| pos.line),
+            return_type: func.return_type,
+            variables,
+        }
+    }
+}
+
+impl CodeGenerator<'_> {
+    /// Quotes source line `line` in a comment, unless it was the last line
+    /// quoted.
+    fn source_comment(&self, e: &mut Emitter, line: u32, last: &mut Option<u32>) {
+        let Some(source) = self.source else {
+            return;
+        };
+        if *last == Some(line) {
+            return;
+        }
+        *last = Some(line);
+        let text = source.lines().nth(line as usize - 1).unwrap_or("").trim();
+        e.out.lines.push(Line::Comment(format!("{line}: {text}")));
+    }
+}
+
+/// The lines of an inline assembly `template`, with `%N` replaced by
+/// `operands[N]` and `%%` by a single `%`.
+fn expand_asm(template: &str, operands: &[&str]) -> Vec<String> {
+    let mut expanded = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.peek().and_then(|d| d.to_digit(10)) {
+            Some(n) if (n as usize) < operands.len() => {
+                chars.next();
+                expanded.push_str(operands[n as usize]);
+            }
+            _ => {
+                if chars.peek() == Some(&'%') {
+                    chars.next();
+                }
+                expanded.push('%');
+            }
+        }
+    }
+    expanded
+        .split('\n')
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// The section directive for `global` on ELF or, unless it is
+/// thread-local, Mach-O: `.bss` or `.data`, or their thread-local
+/// counterparts `.tbss` and `.tdata`. `marker` prefixes section types, `@`
+/// on x86-64 and `%` on AArch64.
+fn data_section(global: &Global, marker: char) -> String {
+    match (global.thread_local, global.is_zero()) {
+        (false, true) => ".bss".to_string(),
+        (false, false) => ".data".to_string(),
+        (true, true) => format!(".section .tbss,\"awT\",{marker}nobits"),
+        (true, false) => format!(".section .tdata,\"awT\",{marker}progbits"),
+    }
+}
+
+/// Defines the thread-local `global` on Mach-O, where `symbol` names a
+/// descriptor that `__tlv_bootstrap` uses to find each thread's instance,
+/// and the initial value lives under `symbol$tlv$init`. `data` is the
+/// directive for a value of the size of the global's elements.
+fn macho_thread_local(
+    out: &mut Assembly,
+    symbol: &str,
+    global: &Global,
+    visibility: Visibility,
+    data: &str,
+) {
+    let align = global.ty.bytes().trailing_zeros();
+    let init = format!("{symbol}$tlv$init");
+    if global.is_zero() {
+        out.directive(format!(".tbss {init}, {}, {align}", global.size()));
+    } else {
+        out.directive(".section __DATA,__thread_data,thread_local_regular");
+        out.directive(format!(".p2align {align}"));
+        out.label(init.clone());
+        global_data(out, global, ObjectFormat::MachO, data);
+    }
+    out.blank();
+    out.directive(".section __DATA,__thread_vars,thread_local_variables");
+    ObjectFormat::MachO.export(out, symbol, visibility);
+    out.label(symbol);
+    out.directive(".quad __tlv_bootstrap");
+    out.directive(".quad 0");
+    out.directive(format!(".quad {init}"));
+}
+
+/// The elements of `global`: those its initializer gives with `data`, the
+/// directive for a value of their size, and the rest zeroed.
+fn global_data(out: &mut Assembly, global: &Global, format: ObjectFormat, data: &str) {
+    if global.is_zero() {
+        out.directive(format!(".zero {}", global.size()));
+        return;
+    }
+    let offset = |symbol: String, offset: i64| match offset {
+        0 => symbol,
+        _ => format!("{symbol}{offset:+}"),
+    };
+    let values: Vec<String> = global
+        .init
+        .iter()
+        .map(|datum| match datum {
+            Datum::Int(value) => value.to_string(),
+            Datum::String { string, offset: by } => offset(string_label(format, *string), *by),
+            Datum::Global { name, offset: by } => offset(format.symbol(name), *by),
+        })
+        .collect();
+    out.directive(format!("{data} {}", values.join(", ")));
+    let rest = global.size() - global.ty.bytes() * values.len() as u32;
+    if rest > 0 {
+        out.directive(format!(".zero {rest}"));
+    }
+}
+
+/// `bytes` as an `.asciz` directive.
+fn asciz(bytes: &[u8]) -> String {
+    format!(".asciz {}", quoted(bytes))
+}
+
+/// `bytes` as a quoted assembler string. Octal escapes are used because a
+/// hex escape would swallow any hex digits that follow it.
+fn quoted(bytes: &[u8]) -> String {
+    let mut out = String::from("\"");
+    for &byte in bytes {
+        match byte {
+            b'"' => out.push_str("\\\""),
+            b'\\' => out.push_str("\\\\"),
+            b'\n' => out.push_str("\\n"),
+            b'\t' => out.push_str("\\t"),
+            b' '..=b'~' => out.push(char::from(byte)),
+            _ => out.push_str(&format!("\\{byte:03o}")),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// A switch needs at least this many cases for a jump table...
+const MIN_JUMP_TABLE_CASES: usize = 4;
+
+/// ...and no more than this many table entries per case.
+const MAX_JUMP_TABLE_ENTRIES_PER_CASE: i64 = 3;
+
+/// Most cases a comparison tree tests one by one instead of splitting.
+const MAX_LINEAR_CASES: usize = 3;
+
+/// Emits a switch over `cases`, sorted by value: a bounds-checked jump table
+/// when the values are dense, and a binary tree of comparisons otherwise.
+fn switch(
+    backend: &dyn Backend,
+    e: &mut Emitter,
+    value: Value,
+    cases: &[(i64, String)],
+    default: &str,
+) {
+    let (Some((min, _)), Some((max, _))) = (cases.first(), cases.last()) else {
+        backend.jump(e, default);
+        return;
+    };
+    let entries = max - min + 1;
+    let dense = entries <= MAX_JUMP_TABLE_ENTRIES_PER_CASE * cases.len() as i64;
+    if cases.len() >= MIN_JUMP_TABLE_CASES && dense {
+        let mut targets = vec![default.to_string(); entries as usize];
+        for (case, label) in cases {
+            targets[(case - min) as usize] = label.clone();
+        }
+        backend.jump_table(e, value, *min, &targets, default);
+    } else {
+        case_tree(backend, e, value, cases, default);
+    }
+}
+
+fn case_tree(
+    backend: &dyn Backend,
+    e: &mut Emitter,
+    value: Value,
+    cases: &[(i64, String)],
+    default: &str,
+) {
+    if cases.len() <= MAX_LINEAR_CASES {
+        for (case, label) in cases {
+            backend.compare_imm_and_branch(e, CmpOp::Eq, value, *case, label);
+        }
+        backend.jump(e, default);
+        return;
+    }
+    let (lower, upper) = cases.split_at(cases.len() / 2);
+    let upper_label = e.labels.fresh();
+    backend.compare_imm_and_branch(e, CmpOp::Sge, value, upper[0].0, &upper_label);
+    case_tree(backend, e, value, lower, default);
+    e.label(&upper_label);
+    case_tree(backend, e, value, upper, default);
+}
+
+/// Orders blocks so that each is followed, where possible, by the successor
+/// it would otherwise jump to: a jump's target or a branch's `then` block,
+/// falling back to the `else` block. Chains start in block order.
+fn layout(func: &Function) -> Vec<BlockId> {
+    let mut placed = vec![false; func.blocks.len()];
+    let mut order = Vec::with_capacity(func.blocks.len());
+    for start in func.block_ids() {
+        let mut block = Some(start);
+        while let Some(id) = block.filter(|id| !placed[id.index()]) {
+            placed[id.index()] = true;
+            order.push(id);
+            block = func.blocks[id.index()]
+                .term
+                .successors()
+                .into_iter()
+                .find(|succ| !placed[succ.index()]);
+        }
+    }
+    order
+}
+
+/// How many times each value is used by an instruction or terminator.
+fn use_counts(func: &Function) -> HashMap<Value, usize> {
+    let mut uses = HashMap::new();
+    for block in &func.blocks {
+        let operands = block.instrs.iter().flat_map(Instr::operands);
+        for value in operands.chain(block.term.operands()) {
+            *uses.entry(value).or_default() += 1;
+        }
+    }
+    uses
+}
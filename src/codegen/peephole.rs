@@ -0,0 +1,90 @@
+//! Peephole optimization over generated AArch64 instructions.
+//!
+//! Works on adjacent instruction pairs only, so a label between two
+//! instructions (a potential branch target) always blocks a rewrite. Inline
+//! assembly is kept as verbatim lines and never matches.
+
+use super::asm::{Address, Assembly, Instruction, Line, Operand};
+
+/// Rewrites `asm` until no pattern applies.
+pub fn optimize(mut asm: Assembly) -> Assembly {
+    while let Some(rewritten) = rewrite(&asm.lines) {
+        asm.lines = rewritten;
+    }
+    asm
+}
+
+/// One pass over `lines`, or `None` if nothing changed.
+fn rewrite(lines: &[Line]) -> Option<Vec<Line>> {
+    let mut out = Vec::with_capacity(lines.len());
+    let mut changed = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = &lines[i];
+        let next = lines.get(i + 1);
+        let current = instruction(line);
+        let following = next.and_then(instruction);
+
+        match (current, following) {
+            // `mov w0, w0` does nothing.
+            (Some(("mov", [Operand::Reg(dst), Operand::Reg(src)])), _) if dst == src => {
+                i += 1;
+            }
+            // `b L` immediately followed by `L:` falls through anyway.
+            (Some(("b", [Operand::Symbol(target)])), _)
+                if next == Some(&Line::Label(target.clone())) =>
+            {
+                i += 1;
+            }
+            // A reload of the slot just stored can read the register instead.
+            (
+                Some(("str", [Operand::Reg(src), Operand::Mem(slot)])),
+                Some(("ldr", [Operand::Reg(dst), Operand::Mem(reloaded)])),
+            ) if matches!(slot, Address::Offset { .. })
+                && slot == reloaded
+                && same_width(src, dst) =>
+            {
+                out.push(line.clone());
+                if src != dst {
+                    out.push(Line::Instruction(Instruction::new(
+                        "mov",
+                        vec![Operand::Reg(dst.clone()), Operand::Reg(src.clone())],
+                    )));
+                }
+                i += 2;
+            }
+            // A push immediately undone by the matching pop.
+            (
+                Some(("stp", [a, b, Operand::Mem(Address::PreIndex { base, offset })])),
+                Some((
+                    "ldp",
+                    [c, d, Operand::Mem(Address::PostIndex {
+                        base: popped,
+                        offset: back,
+                    })],
+                )),
+            ) if (a, b) == (c, d) && base == popped && *offset == -back => {
+                i += 2;
+            }
+            _ => {
+                out.push(line.clone());
+                i += 1;
+                continue;
+            }
+        }
+        changed = true;
+    }
+    changed.then_some(out)
+}
+
+/// The mnemonic and operands of `line` if it is a generated instruction.
+fn instruction(line: &Line) -> Option<(&str, &[Operand])> {
+    match line {
+        Line::Instruction(instruction) => Some((&instruction.mnemonic, &instruction.operands)),
+        _ => None,
+    }
+}
+
+fn same_width(a: &str, b: &str) -> bool {
+    a.as_bytes().first() == b.as_bytes().first()
+}
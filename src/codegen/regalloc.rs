@@ -0,0 +1,282 @@
+//! Linear-scan register allocation over live intervals.
+//!
+//! Instructions are numbered in block layout order and each value gets a
+//! single interval from its first to its last appearance, widened to block
+//! boundaries wherever liveness says it flows in or out. Values that do not
+//! get a register are spilled to a stack slot of their own size, which is
+//! handed on to another spilled value of that size once its owner is dead.
+
+use std::collections::HashMap;
+
+use crate::ir::liveness::Liveness;
+use crate::ir::{Function, Instr, Value};
+
+/// Where a value lives for its whole lifetime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    /// A constant, rematerialized at each use.
+    Imm(i64),
+    /// A general-purpose register number.
+    Reg(u8),
+    /// Byte offset below the frame pointer.
+    Stack(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval {
+    pub value: Value,
+    pub start: u32,
+    pub end: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Allocation {
+    locations: HashMap<Value, Location>,
+    /// Bytes of stack needed for spilled values.
+    pub spill_size: u32,
+}
+
+/// The registers the allocator may hand out.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RegisterSet<'a> {
+    /// Clobbered by calls, so only usable by values not live across one.
+    pub caller_saved: &'a [u8],
+    /// Preserved across calls; a function that uses one must save it.
+    pub callee_saved: &'a [u8],
+}
+
+impl Allocation {
+    /// Assigns a location to every value of `func`, using only `registers`.
+    /// Passing no registers spills everything, which is what `-O0` wants.
+    pub fn compute(func: &Function, registers: RegisterSet) -> Self {
+        let mut locations = HashMap::new();
+        for instr in func.blocks.iter().flat_map(|b| &b.instrs) {
+            if let Instr::Const { dst, value } = *instr {
+                locations.insert(dst, Location::Imm(value));
+            }
+        }
+
+        let (intervals, calls) = build_intervals(func);
+        crate::log!(
+            Trace,
+            "regalloc",
+            "`{}`: intervals {intervals:?}, calls at {calls:?}",
+            func.name
+        );
+        let mut allocation = Self {
+            locations,
+            spill_size: 0,
+        };
+        let mut free_caller: Vec<u8> = registers.caller_saved.iter().rev().copied().collect();
+        let mut free_callee: Vec<u8> = registers.callee_saved.iter().rev().copied().collect();
+        let mut active: Vec<Interval> = Vec::new();
+        // Spilled intervals still holding their slot, and slots whose owner
+        // has died. At `-O0` every value keeps its own slot so it stays
+        // inspectable in a debugger.
+        let reuse_slots = !registers.caller_saved.is_empty() || !registers.callee_saved.is_empty();
+        let mut spilled: Vec<(Interval, u32)> = Vec::new();
+        let mut free_slots: Vec<(u32, u32)> = Vec::new();
+        // Narrow values are held, and so spilled, as 32 bits.
+        let bytes = |value: Value| func.value_type(value).bytes().max(4);
+        for interval in intervals {
+            if allocation.locations.contains_key(&interval.value) {
+                continue;
+            }
+            if reuse_slots {
+                spilled.retain(|&(owner, offset)| {
+                    let expired = owner.end <= interval.start;
+                    if expired {
+                        free_slots.push((offset, bytes(owner.value)));
+                    }
+                    !expired
+                });
+            }
+            active.retain(|a| {
+                let expired = a.end <= interval.start;
+                if expired {
+                    if let Location::Reg(reg) = allocation.locations[&a.value] {
+                        if registers.callee_saved.contains(&reg) {
+                            free_callee.push(reg);
+                        } else {
+                            free_caller.push(reg);
+                        }
+                    }
+                }
+                !expired
+            });
+
+            // Values live across a call need a register the callee preserves.
+            // Everything else prefers caller-saved registers, which cost
+            // nothing to use.
+            let crosses_call = calls
+                .iter()
+                .any(|&call| interval.start < call && call < interval.end);
+            let reg = if crosses_call {
+                free_callee.pop()
+            } else {
+                free_caller.pop().or_else(|| free_callee.pop())
+            };
+            if let Some(reg) = reg {
+                crate::log!(
+                    Debug,
+                    "regalloc",
+                    "`{}`: {} gets r{reg}{}",
+                    func.name,
+                    interval.value,
+                    if crosses_call {
+                        ", preserved across a call"
+                    } else {
+                        ""
+                    }
+                );
+                allocation
+                    .locations
+                    .insert(interval.value, Location::Reg(reg));
+                active.push(interval);
+                continue;
+            }
+
+            // Out of registers: spill whichever usable interval ends last.
+            let victim = active
+                .iter()
+                .enumerate()
+                .filter(|(_, a)| match allocation.locations[&a.value] {
+                    Location::Reg(reg) => !crosses_call || registers.callee_saved.contains(&reg),
+                    _ => false,
+                })
+                .max_by_key(|(_, a)| a.end)
+                .map(|(i, a)| (i, *a));
+            match victim {
+                Some((index, victim)) if victim.end > interval.end => {
+                    // The victim has been live since before any freed slot's
+                    // owner died, so it needs a fresh slot.
+                    let reg = allocation.locations[&victim.value];
+                    let offset = allocation.spill(victim.value, bytes(victim.value), None);
+                    crate::log!(
+                        Debug,
+                        "regalloc",
+                        "`{}`: {} spilled to offset {offset}, handing its register to {}",
+                        func.name,
+                        victim.value,
+                        interval.value
+                    );
+                    spilled.push((victim, offset));
+                    allocation.locations.insert(interval.value, reg);
+                    active.swap_remove(index);
+                    active.push(interval);
+                }
+                _ => {
+                    let size = bytes(interval.value);
+                    let slot = free_slots
+                        .iter()
+                        .rposition(|&(_, slot_size)| slot_size == size)
+                        .map(|i| free_slots.remove(i).0);
+                    let offset = allocation.spill(interval.value, size, slot);
+                    crate::log!(
+                        Debug,
+                        "regalloc",
+                        "`{}`: {} spilled to offset {offset}{}",
+                        func.name,
+                        interval.value,
+                        if slot.is_some() {
+                            ", reusing a dead value's slot"
+                        } else {
+                            ""
+                        }
+                    );
+                    spilled.push((interval, offset));
+                }
+            }
+        }
+        allocation
+    }
+
+    pub fn location(&self, value: Value) -> Location {
+        self.locations[&value]
+    }
+
+    /// How many values live on the stack.
+    pub fn spills(&self) -> usize {
+        self.locations
+            .values()
+            .filter(|loc| matches!(loc, Location::Stack(_)))
+            .count()
+    }
+
+    /// Registers holding at least one value.
+    pub fn used_registers(&self) -> Vec<u8> {
+        let mut regs: Vec<u8> = self
+            .locations
+            .values()
+            .filter_map(|loc| match loc {
+                Location::Reg(reg) => Some(*reg),
+                _ => None,
+            })
+            .collect();
+        regs.sort_unstable();
+        regs.dedup();
+        regs
+    }
+
+    /// Moves `value` to the stack, into `slot` if given or else a new
+    /// `bytes`-sized slot, and returns the slot's offset.
+    fn spill(&mut self, value: Value, bytes: u32, slot: Option<u32>) -> u32 {
+        let offset = slot.unwrap_or_else(|| {
+            self.spill_size = (self.spill_size + bytes).next_multiple_of(bytes);
+            self.spill_size
+        });
+        self.locations.insert(value, Location::Stack(offset));
+        offset
+    }
+}
+
+/// Computes one interval per non-constant value, sorted by start, plus the
+/// positions of every call.
+pub fn build_intervals(func: &Function) -> (Vec<Interval>, Vec<u32>) {
+    let liveness = Liveness::compute(func);
+    let mut ranges: HashMap<Value, (u32, u32)> = HashMap::new();
+    let mut touch = |value: Value, pos: u32| {
+        let range = ranges.entry(value).or_insert((pos, pos));
+        range.0 = range.0.min(pos);
+        range.1 = range.1.max(pos);
+    };
+
+    // Parameters are defined at position 0, before the first instruction.
+    for &param in &func.params {
+        touch(param, 0);
+    }
+    let mut calls = Vec::new();
+    let mut pos = 0;
+    for (id, block) in func.block_ids().zip(&func.blocks) {
+        let block_start = pos + 1;
+        for instr in &block.instrs {
+            pos += 1;
+            for operand in instr.operands() {
+                touch(operand, pos);
+            }
+            if let Some(dst) = instr.dst() {
+                touch(dst, pos);
+            }
+            if instr.clobbers_like_a_call() {
+                calls.push(pos);
+            }
+        }
+        pos += 1;
+        for operand in block.term.operands() {
+            touch(operand, pos);
+        }
+        for &value in &liveness.live_in[id.index()] {
+            touch(value, block_start);
+        }
+        for &value in &liveness.live_out[id.index()] {
+            touch(value, pos);
+        }
+    }
+
+    let mut intervals: Vec<Interval> = ranges
+        .into_iter()
+        .map(|(value, (start, end))| Interval { value, start, end })
+        .collect();
+    intervals.sort_by_key(|i| (i.start, i.value));
+    (intervals, calls)
+}
@@ -0,0 +1,223 @@
+//! Target triples: which backend to use and which platform conventions the
+//! assembly follows.
+
+use std::fmt;
+
+use super::aarch64::Aarch64;
+use super::asm::Assembly;
+use super::x86_64::X86_64;
+use super::Backend;
+use crate::ast::DataModel;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arch {
+    Aarch64,
+    /// WebAssembly, which is written as text by its own emitter rather
+    /// than by a backend.
+    Wasm32,
+    X86_64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Os {
+    /// macOS and Apple's other platforms.
+    Darwin,
+    Linux,
+    /// No operating system: bare metal, or a WebAssembly host, which
+    /// provides whatever the module imports.
+    Unknown,
+}
+
+/// The object file format the assembly is written for, which decides how
+/// symbols are named and which directives describe them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectFormat {
+    /// Apple's format: C symbols get a leading underscore.
+    MachO,
+    /// Linux's format: symbols keep their C names and carry `.type` and
+    /// `.size` directives.
+    Elf,
+}
+
+impl ObjectFormat {
+    /// The assembly name of the C function or object `name`.
+    pub fn symbol(self, name: &str) -> String {
+        match self {
+            ObjectFormat::MachO => format!("_{name}"),
+            ObjectFormat::Elf => name.to_string(),
+        }
+    }
+
+    /// Declares `symbol` visible outside the object file, and with
+    /// `visibility` whether it is visible outside the linked program too.
+    pub fn export(self, out: &mut Assembly, symbol: &str, visibility: Visibility) {
+        out.directive(format!(".globl {symbol}"));
+        if visibility == Visibility::Hidden {
+            out.directive(match self {
+                ObjectFormat::MachO => format!(".private_extern {symbol}"),
+                ObjectFormat::Elf => format!(".hidden {symbol}"),
+            });
+        }
+    }
+
+    /// The prefix that keeps a label out of the object's symbol table.
+    pub fn local_label_prefix(self) -> &'static str {
+        match self {
+            ObjectFormat::MachO => "L",
+            ObjectFormat::Elf => ".L",
+        }
+    }
+}
+
+/// Whether the program's symbols are visible to other shared objects
+/// (`-fvisibility=`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Visibility {
+    #[default]
+    Default,
+    /// Only visible within the executable or shared object the symbol is
+    /// linked into, so it can't be preempted.
+    Hidden,
+}
+
+impl Visibility {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Visibility::Default),
+            "hidden" => Some(Visibility::Hidden),
+            _ => None,
+        }
+    }
+}
+
+/// The architecture and operating system to generate code for. The default
+/// is Apple arm64, the compiler's original target; the command line
+/// defaults to the host instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Target {
+    pub arch: Arch,
+    pub os: Os,
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Target::AARCH64_APPLE
+    }
+}
+
+impl Target {
+    pub const AARCH64_APPLE: Self = Self::new(Arch::Aarch64, Os::Darwin);
+    pub const AARCH64_LINUX: Self = Self::new(Arch::Aarch64, Os::Linux);
+    pub const X86_64_APPLE: Self = Self::new(Arch::X86_64, Os::Darwin);
+    pub const X86_64_LINUX: Self = Self::new(Arch::X86_64, Os::Linux);
+    pub const WASM32: Self = Self::new(Arch::Wasm32, Os::Unknown);
+
+    pub const fn new(arch: Arch, os: Os) -> Self {
+        Self { arch, os }
+    }
+
+    /// The machine the compiler is running on, if it can generate code for it.
+    pub fn host() -> Option<Self> {
+        let arch = if cfg!(target_arch = "aarch64") {
+            Arch::Aarch64
+        } else if cfg!(target_arch = "x86_64") {
+            Arch::X86_64
+        } else {
+            return None;
+        };
+        let os = if cfg!(target_vendor = "apple") {
+            Os::Darwin
+        } else if cfg!(target_os = "linux") {
+            Os::Linux
+        } else {
+            return None;
+        };
+        Some(Self::new(arch, os))
+    }
+
+    /// Parses a target triple such as `aarch64-apple-darwin` or
+    /// `x86_64-unknown-linux-gnu`. The vendor and environment may be left
+    /// out, and a bare architecture picks the platform that architecture
+    /// was first supported on. `wasm32` runs on no operating system, and
+    /// `none` says the other architectures don't either.
+    pub fn from_triple(triple: &str) -> Option<Self> {
+        let mut parts = triple.split('-');
+        let arch = match parts.next()? {
+            "aarch64" | "arm64" => Arch::Aarch64,
+            "x86_64" | "x86-64" | "amd64" => Arch::X86_64,
+            "wasm32" => Arch::Wasm32,
+            _ => return None,
+        };
+        let mut os = None;
+        for part in parts {
+            match part {
+                "apple" => os = os.or(Some(Os::Darwin)),
+                "unknown" | "pc" | "gnu" | "musl" => {}
+                _ if part.starts_with("darwin") || part.starts_with("macos") => {
+                    os = Some(Os::Darwin);
+                }
+                "linux" => os = Some(Os::Linux),
+                "none" => os = Some(Os::Unknown),
+                _ => return None,
+            }
+        }
+        let os = os.unwrap_or(match arch {
+            Arch::Aarch64 => Os::Darwin,
+            Arch::Wasm32 => Os::Unknown,
+            Arch::X86_64 => Os::Linux,
+        });
+        if arch == Arch::Wasm32 && os != Os::Unknown {
+            return None;
+        }
+        Some(Self::new(arch, os))
+    }
+
+    /// The canonical triple naming this target.
+    pub fn triple(self) -> &'static str {
+        match (self.arch, self.os) {
+            (Arch::Aarch64, Os::Darwin) => "aarch64-apple-darwin",
+            (Arch::Aarch64, Os::Linux) => "aarch64-unknown-linux-gnu",
+            (Arch::Aarch64, Os::Unknown) => "aarch64-unknown-none",
+            (Arch::X86_64, Os::Darwin) => "x86_64-apple-darwin",
+            (Arch::X86_64, Os::Linux) => "x86_64-unknown-linux-gnu",
+            (Arch::X86_64, Os::Unknown) => "x86_64-unknown-none",
+            (Arch::Wasm32, _) => "wasm32-unknown-unknown",
+        }
+    }
+
+    /// How wide `long` and pointers are: four bytes on `wasm32`, whose
+    /// addresses are 32 bits, and eight everywhere else.
+    pub fn data_model(self) -> DataModel {
+        match self.arch {
+            Arch::Wasm32 => DataModel::Ilp32,
+            Arch::Aarch64 | Arch::X86_64 => DataModel::Lp64,
+        }
+    }
+
+    /// The object file format, which is ELF for a target with no
+    /// operating system.
+    pub fn object_format(self) -> ObjectFormat {
+        match self.os {
+            Os::Darwin => ObjectFormat::MachO,
+            Os::Linux | Os::Unknown => ObjectFormat::Elf,
+        }
+    }
+
+    /// The backend writing assembly for this target, or `None` for
+    /// WebAssembly, which [`wasm::emit`](super::wasm::emit) writes instead.
+    pub fn backend(self) -> Option<&'static dyn Backend> {
+        Some(match (self.arch, self.object_format()) {
+            (Arch::Wasm32, _) => return None,
+            (Arch::Aarch64, ObjectFormat::MachO) => &Aarch64::APPLE,
+            (Arch::Aarch64, ObjectFormat::Elf) => &Aarch64::LINUX,
+            (Arch::X86_64, ObjectFormat::MachO) => &X86_64::APPLE,
+            (Arch::X86_64, ObjectFormat::Elf) => &X86_64::LINUX,
+        })
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.triple())
+    }
+}
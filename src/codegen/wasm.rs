@@ -0,0 +1,563 @@
+//! WebAssembly text for a module (`--target wasm32`, `--emit=wat`), which
+//! `wasmtime` runs directly and `wat2wasm` turns into a binary for
+//! browsers.
+//!
+//! WebAssembly is a stack machine without registers or assembly, so the
+//! module is written straight from the IR rather than through a
+//! [`Backend`](super::Backend). The target is ILP32, as C compiled to
+//! `wasm32` is elsewhere: `long` and pointers are four bytes, so every IR
+//! value is an `i32` local, and a `ptr` is a 32-bit address into linear
+//! memory, stored in four bytes.
+//!
+//! Blocks can't jump to each other freely, so a function's blocks are
+//! nested `block`s inside a `loop` that starts each one by its number with
+//! a `br_table`. A block ends by setting the number of the next one and
+//! branching back to the top of the loop, or by falling through to the
+//! block after it.
+//!
+//! Linear memory holds the string literals and globals from address 16, so
+//! that no object is at null, and then the stack, which grows down from
+//! the top of the memory. Stack slots live in a frame below
+//! `$__stack_pointer`, as in C compiled to WebAssembly by other compilers.
+//! `main` and the memory are exported; functions and globals used but not
+//! defined are imported from `env`, a global as its `i32` address.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::error::{Error, Result};
+use crate::ir::{
+    BinOp, BlockId, CastOp, CmpOp, Datum, Function, Instr, IrType, Module, StringId, Terminator,
+    Value,
+};
+
+/// Where the first string or global goes.
+const DATA_START: u64 = 16;
+
+/// Bytes of stack above the data, one 64 KiB page.
+const STACK_SIZE: u64 = 65536;
+
+const PAGE_SIZE: u64 = 65536;
+
+/// The module as WebAssembly text. Inline assembly, written for some other
+/// machine, is an error.
+pub fn emit(module: &Module) -> Result<String> {
+    let mut out = String::from("(module\n");
+    imports(&mut out, module);
+
+    let mut next = DATA_START;
+    let mut data = String::new();
+    let mut strings = HashMap::new();
+    for (id, bytes) in module.strings.iter() {
+        strings.insert(id, next);
+        writeln!(
+            data,
+            "  (data (i32.const {next}) \"{}\\00\")",
+            escape(bytes)
+        )
+        .unwrap();
+        next += bytes.len() as u64 + 1;
+    }
+    let mut globals = String::new();
+    let mut addresses = HashMap::new();
+    for global in &module.globals {
+        next = next.next_multiple_of(bytes(global.ty));
+        writeln!(
+            globals,
+            "  (global ${} i32 (i32.const {next}))",
+            global.name
+        )
+        .unwrap();
+        addresses.insert(global.name.as_str(), next);
+        next += bytes(global.ty) * u64::from(global.len);
+    }
+    // Every address is known once they all are, so a global may point at
+    // one defined after it.
+    for global in module.globals.iter().filter(|global| !global.is_zero()) {
+        let size = bytes(global.ty) as usize;
+        let mut bytes = Vec::new();
+        for datum in &global.init {
+            let value = match datum {
+                Datum::Int(value) => *value,
+                Datum::String { string, offset } => strings[string] as i64 + offset,
+                Datum::Global { name, offset } => match addresses.get(name.as_str()) {
+                    Some(&address) => address as i64 + offset,
+                    None => {
+                        return Err(Error::msg(format!(
+                            "`{}` cannot be initialized with the address of `{name}` in \
+                             WebAssembly, where `{name}` is imported",
+                            global.name
+                        )))
+                    }
+                },
+            };
+            bytes.extend_from_slice(&value.to_le_bytes()[..size]);
+        }
+        writeln!(
+            data,
+            "  (data (i32.const {}) \"{}\")",
+            addresses[global.name.as_str()],
+            escape(&bytes)
+        )
+        .unwrap();
+    }
+    let top = (next.next_multiple_of(16) + STACK_SIZE).next_multiple_of(PAGE_SIZE);
+    writeln!(out, "  (memory (export \"memory\") {})", top / PAGE_SIZE).unwrap();
+    writeln!(
+        out,
+        "  (global $__stack_pointer (mut i32) (i32.const {top}))"
+    )
+    .unwrap();
+    out.push_str(&globals);
+    out.push_str(&data);
+    for func in &module.functions {
+        out.push('\n');
+        Printer::new(func, &strings).function(&mut out)?;
+    }
+    out.push_str(")\n");
+    Ok(out)
+}
+
+/// The bytes a `ty` takes in memory, where a `ptr` is a 32-bit address.
+fn bytes(ty: IrType) -> u64 {
+    match ty {
+        IrType::Ptr => 4,
+        ty => u64::from(ty.bytes()),
+    }
+}
+
+/// `(param ..) (result ..)` for a function taking `params` values and
+/// returning one if `result` says so, each part left out when empty.
+fn signature(params: usize, result: bool) -> String {
+    let mut text = String::new();
+    if params > 0 {
+        write!(text, " (param{})", " i32".repeat(params)).unwrap();
+    }
+    if result {
+        text.push_str(" (result i32)");
+    }
+    text
+}
+
+/// Imports each function called and each global addressed that the module
+/// doesn't define. A function's type comes from its first call; a call to
+/// a variadic one passes the variable arguments in memory, as the address
+/// after the named ones.
+fn imports(out: &mut String, module: &Module) {
+    let mut imported: Vec<&str> = Vec::new();
+    for func in &module.functions {
+        for instr in func.blocks.iter().flat_map(|b| &b.instrs) {
+            match instr {
+                Instr::GlobalAddr { name, .. } => {
+                    let defined = module.globals.iter().any(|g| &g.name == name);
+                    if defined || imported.contains(&name.as_str()) {
+                        continue;
+                    }
+                    writeln!(out, "  (import \"env\" \"{name}\" (global ${name} i32))").unwrap();
+                    imported.push(name);
+                }
+                Instr::Call {
+                    dst,
+                    callee,
+                    args,
+                    fixed,
+                } => {
+                    let defined = module.functions.iter().any(|f| &f.name == callee);
+                    if defined || imported.contains(&callee.as_str()) {
+                        continue;
+                    }
+                    let named = fixed.unwrap_or(args.len());
+                    let params = named + usize::from(fixed.is_some());
+                    writeln!(
+                        out,
+                        "  (import \"env\" \"{callee}\" (func ${callee}{}))",
+                        signature(params, dst.is_some())
+                    )
+                    .unwrap();
+                    imported.push(callee);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+struct Printer<'a> {
+    func: &'a Function,
+    strings: &'a HashMap<StringId, u64>,
+    /// The offset of each stack slot from `$fp`.
+    slot_offsets: Vec<u64>,
+    /// Bytes the frame takes below the caller's stack pointer.
+    frame_size: u64,
+    /// Whether the function moves the stack pointer, so it needs `$fp`.
+    uses_stack: bool,
+    body: String,
+}
+
+impl<'a> Printer<'a> {
+    fn new(func: &'a Function, strings: &'a HashMap<StringId, u64>) -> Self {
+        let mut size = 0;
+        let slot_offsets = func
+            .slots
+            .iter()
+            .map(|slot| {
+                let offset = u64::next_multiple_of(size, bytes(slot.ty));
+                size = offset + bytes(slot.ty) * u64::from(slot.len);
+                offset
+            })
+            .collect();
+        let uses_stack = size > 0
+            || func
+                .blocks
+                .iter()
+                .flat_map(|b| &b.instrs)
+                .any(|instr| matches!(instr, Instr::Alloca { .. }));
+        Self {
+            func,
+            strings,
+            slot_offsets,
+            frame_size: size.next_multiple_of(16),
+            uses_stack,
+            body: String::new(),
+        }
+    }
+
+    fn line(&mut self, text: impl AsRef<str>) {
+        writeln!(self.body, "    {}", text.as_ref()).unwrap();
+    }
+
+    fn get(&mut self, value: Value) {
+        self.line(format!("local.get $v{}", value.0));
+    }
+
+    fn set(&mut self, value: Value) {
+        self.line(format!("local.set $v{}", value.0));
+    }
+
+    fn function(mut self, out: &mut String) -> Result<()> {
+        let func = self.func;
+        let export = if func.name == "main" {
+            " (export \"main\")"
+        } else {
+            ""
+        };
+        let result = if func.return_type.is_some() {
+            " (result i32)"
+        } else {
+            ""
+        };
+        write!(out, "  (func ${}{export}", func.name).unwrap();
+        for &param in &func.params {
+            write!(out, " (param $v{} i32)", param.0).unwrap();
+        }
+        writeln!(out, "{result}").unwrap();
+        for index in 0..func.value_types.len() {
+            if !func.params.contains(&Value(index as u32)) {
+                writeln!(out, "    (local $v{index} i32)").unwrap();
+            }
+        }
+        if self.uses_stack {
+            out.push_str("    (local $fp i32)\n");
+        }
+        let dispatch = func.blocks.len() > 1;
+        if dispatch {
+            out.push_str("    (local $block i32)\n");
+        }
+
+        if self.uses_stack {
+            self.line("global.get $__stack_pointer");
+            self.line(format!("i32.const {}", self.frame_size));
+            self.line("i32.sub");
+            self.line("local.tee $fp");
+            self.line("global.set $__stack_pointer");
+        }
+        if dispatch {
+            self.line("loop $dispatch");
+            for id in func.block_ids().rev() {
+                self.line(format!("block $bb{}", id.0));
+            }
+            let targets: Vec<String> = func.block_ids().map(|id| format!("$bb{}", id.0)).collect();
+            self.line("local.get $block");
+            self.line(format!("br_table {} $bb0", targets.join(" ")));
+        }
+        for (id, block) in func.block_ids().zip(&func.blocks) {
+            if dispatch {
+                self.line("end");
+                self.line(format!(";; bb{}", id.0));
+            }
+            for instr in &block.instrs {
+                self.instr(instr)?;
+            }
+            self.terminator(&block.term, id);
+        }
+        if dispatch {
+            self.line("end");
+            self.line("unreachable");
+        }
+        out.push_str(&self.body);
+        out.push_str("  )\n");
+        Ok(())
+    }
+
+    fn instr(&mut self, instr: &Instr) -> Result<()> {
+        match instr {
+            Instr::Const { dst, value } => {
+                // A `ptr` constant is an address, which wraps to 32 bits.
+                let ty = self.func.value_type(*dst);
+                self.line(format!("i32.const {}", ty.normalize(*value) as i32));
+                self.set(*dst);
+            }
+            Instr::Binary { dst, op, lhs, rhs } => {
+                self.get(*lhs);
+                self.get(*rhs);
+                self.line(format!("i32.{}", binary(*op)));
+                self.set(*dst);
+            }
+            Instr::Cmp { dst, op, lhs, rhs } => {
+                self.get(*lhs);
+                self.get(*rhs);
+                self.line(format!("i32.{}", compare(*op)));
+                self.set(*dst);
+            }
+            Instr::Cast { dst, op, value } => {
+                let (from, to) = (self.func.value_type(*value), self.func.value_type(*dst));
+                self.get(*value);
+                self.cast(*op, from, to);
+                self.set(*dst);
+            }
+            Instr::Call {
+                dst,
+                callee,
+                args,
+                fixed,
+            } => {
+                let named = &args[..fixed.unwrap_or(args.len())];
+                for &arg in named {
+                    self.get(arg);
+                }
+                // The variable arguments, in 4-byte slots on the stack.
+                let extra = &args[named.len()..];
+                let size = (4 * extra.len() as u64).next_multiple_of(16);
+                if fixed.is_some() {
+                    self.line("global.get $__stack_pointer");
+                    self.line(format!("i32.const {size}"));
+                    self.line("i32.sub");
+                    self.line("global.set $__stack_pointer");
+                    for (index, &arg) in extra.iter().enumerate() {
+                        self.line("global.get $__stack_pointer");
+                        self.get(arg);
+                        self.line(format!("i32.store offset={}", 4 * index));
+                    }
+                    self.line("global.get $__stack_pointer");
+                }
+                self.line(format!("call ${callee}"));
+                if fixed.is_some() {
+                    self.line("global.get $__stack_pointer");
+                    self.line(format!("i32.const {size}"));
+                    self.line("i32.add");
+                    self.line("global.set $__stack_pointer");
+                }
+                if let Some(dst) = dst {
+                    self.set(*dst);
+                }
+            }
+            Instr::Load { dst, slot } => {
+                self.line("local.get $fp");
+                let offset = self.slot_offsets[slot.0 as usize];
+                self.line(format!(
+                    "{} offset={offset}",
+                    load(self.func.value_type(*dst))
+                ));
+                self.set(*dst);
+            }
+            Instr::Store { slot, value } => {
+                self.line("local.get $fp");
+                self.get(*value);
+                let offset = self.slot_offsets[slot.0 as usize];
+                self.line(format!(
+                    "{} offset={offset}",
+                    store(self.func.value_type(*value))
+                ));
+            }
+            Instr::LoadPtr { dst, ptr } => {
+                self.get(*ptr);
+                self.line(load(self.func.value_type(*dst)));
+                self.set(*dst);
+            }
+            Instr::StorePtr { ptr, value } => {
+                self.get(*ptr);
+                self.get(*value);
+                self.line(store(self.func.value_type(*value)));
+            }
+            Instr::SlotAddr { dst, slot } => {
+                self.line("local.get $fp");
+                self.line(format!("i32.const {}", self.slot_offsets[slot.0 as usize]));
+                self.line("i32.add");
+                self.set(*dst);
+            }
+            Instr::Alloca { dst, size } => {
+                self.line("global.get $__stack_pointer");
+                self.get(*size);
+                self.line("i32.sub");
+                self.line("i32.const -16");
+                self.line("i32.and");
+                self.line("global.set $__stack_pointer");
+                self.line("global.get $__stack_pointer");
+                self.set(*dst);
+            }
+            Instr::StringAddr { dst, string } => {
+                self.line(format!("i32.const {}", self.strings[string]));
+                self.set(*dst);
+            }
+            // With one thread, a thread-local is an ordinary global.
+            Instr::GlobalAddr { dst, name, .. } => {
+                self.line(format!("global.get ${name}"));
+                self.set(*dst);
+            }
+            Instr::Loc { .. } => {}
+            Instr::InlineAsm { .. } => {
+                return Err(Error::msg(format!(
+                    "inline assembly in `{}` cannot be compiled to WebAssembly",
+                    self.func.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts the value on top of the stack from `from` to `to`. Values
+    /// narrower than `i32` are kept sign-extended, as the IR normalizes
+    /// them; between `i32` and `ptr`, which are the same width, there is
+    /// nothing to do.
+    fn cast(&mut self, op: CastOp, from: IrType, to: IrType) {
+        if op == CastOp::ZExt && from.is_narrow() {
+            let mask = from.as_unsigned(-1);
+            self.line(format!("i32.const {mask}"));
+            self.line("i32.and");
+        }
+        if op == CastOp::Trunc && to.is_narrow() {
+            self.line(format!("i32.extend{}_s", to.bits()));
+        }
+    }
+
+    /// Goes on to `target`: by falling through if it is the next block, and
+    /// otherwise by dispatching to it.
+    fn jump(&mut self, target: BlockId, from: BlockId) {
+        if target.0 != from.0 + 1 {
+            self.line(format!("i32.const {}", target.0));
+            self.line("local.set $block");
+            self.line("br $dispatch");
+        }
+    }
+
+    fn terminator(&mut self, term: &Terminator, id: BlockId) {
+        match term {
+            Terminator::Ret(value) => {
+                if let Some(value) = value {
+                    self.get(*value);
+                }
+                if self.uses_stack {
+                    self.line("local.get $fp");
+                    self.line(format!("i32.const {}", self.frame_size));
+                    self.line("i32.add");
+                    self.line("global.set $__stack_pointer");
+                }
+                self.line("return");
+            }
+            Terminator::Jump(target) => self.jump(*target, id),
+            Terminator::Branch {
+                cond,
+                then_block,
+                else_block,
+            } => {
+                self.line(format!("i32.const {}", then_block.0));
+                self.line(format!("i32.const {}", else_block.0));
+                self.get(*cond);
+                self.line("select");
+                self.line("local.set $block");
+                self.line("br $dispatch");
+            }
+            Terminator::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                for &(case, target) in cases {
+                    self.get(*value);
+                    self.line(format!("i32.const {}", case as i32));
+                    self.line("i32.eq");
+                    self.line("if");
+                    self.line(format!("i32.const {}", target.0));
+                    self.line("local.set $block");
+                    self.line("br $dispatch");
+                    self.line("end");
+                }
+                self.jump(*default, id);
+            }
+            Terminator::Trap | Terminator::Unreachable => self.line("unreachable"),
+        }
+    }
+}
+
+fn binary(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "add",
+        BinOp::Sub => "sub",
+        BinOp::Mul => "mul",
+        BinOp::SDiv => "div_s",
+        BinOp::UDiv => "div_u",
+        BinOp::SRem => "rem_s",
+        BinOp::URem => "rem_u",
+        BinOp::And => "and",
+        BinOp::Or => "or",
+        BinOp::Xor => "xor",
+        BinOp::Shl => "shl",
+        BinOp::AShr => "shr_s",
+        BinOp::LShr => "shr_u",
+    }
+}
+
+fn compare(op: CmpOp) -> &'static str {
+    match op {
+        CmpOp::Eq => "eq",
+        CmpOp::Ne => "ne",
+        CmpOp::Slt => "lt_s",
+        CmpOp::Sle => "le_s",
+        CmpOp::Sgt => "gt_s",
+        CmpOp::Sge => "ge_s",
+        CmpOp::Ult => "lt_u",
+        CmpOp::Ule => "le_u",
+        CmpOp::Ugt => "gt_u",
+        CmpOp::Uge => "ge_u",
+    }
+}
+
+/// The instruction loading a `ty`, sign-extending narrow ones.
+fn load(ty: IrType) -> &'static str {
+    match ty {
+        IrType::I8 => "i32.load8_s",
+        IrType::I16 => "i32.load16_s",
+        IrType::I32 | IrType::Ptr => "i32.load",
+    }
+}
+
+fn store(ty: IrType) -> &'static str {
+    match ty {
+        IrType::I8 => "i32.store8",
+        IrType::I16 => "i32.store16",
+        IrType::I32 | IrType::Ptr => "i32.store",
+    }
+}
+
+/// `bytes` for a WebAssembly string, with anything other than printable
+/// ASCII, `"` and `\` written as a hex escape.
+fn escape(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for &byte in bytes {
+        match byte {
+            b' '..=b'~' if byte != b'"' && byte != b'\\' => out.push(char::from(byte)),
+            _ => write!(out, "\\{byte:02x}").unwrap(),
+        }
+    }
+    out
+}
@@ -0,0 +1,891 @@
+//! x86-64 (System V) instruction selection, in AT&T syntax, for Linux (ELF)
+//! and macOS (Mach-O).
+//!
+//! The frame mirrors the AArch64 backend's: values live wherever the
+//! register allocator put them, spilled values and stack slots sit below
+//! `%rbp`, and calls with more than six arguments pass the rest in an
+//! outgoing area at `%rsp`. Instructions are two-address, so results are
+//! computed in `%eax` unless they can go straight to their own register.
+//! Pointers use the 64-bit registers and `q`-suffixed instructions.
+
+use super::asm::{imm, mem, reg, sym, Address, Assembly, Operand, Syntax};
+use super::regalloc::{Location, RegisterSet};
+use super::{
+    asciz, data_section, global_data, macho_thread_local, string_label, Backend, CallingConvention,
+    Emitter, ObjectFormat, Visibility,
+};
+use crate::ir::{BinOp, CastOp, CmpOp, Function, Global, Instr, IrType, StringPool, Value};
+
+/// 32-bit register names, indexed by hardware encoding.
+const REGS32: [&str; 16] = [
+    "%eax", "%ecx", "%edx", "%ebx", "%esp", "%ebp", "%esi", "%edi", "%r8d", "%r9d", "%r10d",
+    "%r11d", "%r12d", "%r13d", "%r14d", "%r15d",
+];
+
+/// 8-bit register names, indexed by hardware encoding.
+const REGS8: [&str; 16] = [
+    "%al", "%cl", "%dl", "%bl", "%spl", "%bpl", "%sil", "%dil", "%r8b", "%r9b", "%r10b", "%r11b",
+    "%r12b", "%r13b", "%r14b", "%r15b",
+];
+
+/// 16-bit register names, indexed by hardware encoding.
+const REGS16: [&str; 16] = [
+    "%ax", "%cx", "%dx", "%bx", "%sp", "%bp", "%si", "%di", "%r8w", "%r9w", "%r10w", "%r11w",
+    "%r12w", "%r13w", "%r14w", "%r15w",
+];
+
+/// 64-bit register names, indexed by hardware encoding.
+const REGS64: [&str; 16] = [
+    "%rax", "%rcx", "%rdx", "%rbx", "%rsp", "%rbp", "%rsi", "%rdi", "%r8", "%r9", "%r10", "%r11",
+    "%r12", "%r13", "%r14", "%r15",
+];
+
+/// Registers used to pass the first integer arguments.
+const ARG_REGS: [&str; 6] = ["%edi", "%esi", "%edx", "%ecx", "%r8d", "%r9d"];
+
+const CALLING_CONVENTION: CallingConvention = CallingConvention {
+    arg_regs: ARG_REGS.len(),
+    // System V rounds every stack argument up to an eightbyte.
+    stack_arg_size: 8,
+    save_area_align: 8,
+    variadic_on_stack: false,
+};
+
+/// Offset from `%rbp` of the first stack-passed parameter, just above the
+/// saved frame pointer and return address.
+const INCOMING_ARGS_OFFSET: u32 = 16;
+
+/// Registers handed out by the register allocator. `%eax`, `%ecx` and `%edx`
+/// are kept free for results, shift counts and division, and the argument
+/// registers are left alone so calls can be set up without shuffling.
+const ALLOCATABLE_REGS: RegisterSet<'static> = RegisterSet {
+    caller_saved: &[10, 11],
+    callee_saved: &[3, 12, 13, 14, 15],
+};
+
+pub struct X86_64 {
+    format: ObjectFormat,
+}
+
+impl X86_64 {
+    pub const APPLE: Self = Self {
+        format: ObjectFormat::MachO,
+    };
+    pub const LINUX: Self = Self {
+        format: ObjectFormat::Elf,
+    };
+}
+
+impl X86_64 {
+    /// The operand of a `call` to the function `name`.
+    fn call_target(&self, e: &Emitter, name: &str) -> Operand {
+        let symbol = self.format.symbol(name);
+        // Calls from position-independent code may have to reach a
+        // function in another shared object.
+        if e.pic && self.format == ObjectFormat::Elf {
+            sym(format!("{symbol}@PLT"))
+        } else {
+            sym(symbol)
+        }
+    }
+
+    /// Computes the address of this thread's instance of the thread-local
+    /// `name` into `dst`. ELF adds the variable's offset to the thread
+    /// pointer in `%fs:0`, a link-time constant for one defined here
+    /// (local-exec) and read from the GOT otherwise (initial-exec); Mach-O
+    /// calls the accessor in the variable's descriptor, which returns the
+    /// address in `%rax`.
+    fn thread_local_addr(&self, e: &mut Emitter, dst: &str, name: &str) {
+        let symbol = self.format.symbol(name);
+        match self.format {
+            ObjectFormat::Elf => {
+                let thread_pointer = Operand::Mem(Address::Segment {
+                    segment: "%fs".to_string(),
+                    offset: 0,
+                });
+                emit!(e, "movq", thread_pointer, reg(dst));
+                if e.pic || !e.defined_globals.contains(name) {
+                    let offset = rip_relative(format!("{symbol}@GOTTPOFF"));
+                    emit!(e, "addq", offset, reg(dst));
+                } else {
+                    let address = Operand::Mem(Address::Symbol {
+                        base: dst.to_string(),
+                        symbol: format!("{symbol}@TPOFF"),
+                    });
+                    emit!(e, "leaq", address, reg(dst));
+                }
+            }
+            ObjectFormat::MachO => {
+                let descriptor = rip_relative(format!("{symbol}@TLVP"));
+                emit!(e, "movq", descriptor, reg("%rdi"));
+                emit!(e, "movq", mem("%rdi", 0), reg("%rax"));
+                emit!(e, "call", Operand::Indirect("%rax".to_string()));
+                if dst != "%rax" {
+                    emit!(e, "movq", reg("%rax"), reg(dst));
+                }
+            }
+        }
+    }
+
+    /// Loads the C library's stack guard value into `dst`. glibc keeps it
+    /// in the thread control block at `%fs:40`; Darwin exports it as
+    /// `___stack_chk_guard`.
+    fn load_stack_guard(&self, e: &mut Emitter, dst: &str) {
+        match self.format {
+            ObjectFormat::Elf => {
+                let guard = Operand::Mem(Address::Segment {
+                    segment: "%fs".to_string(),
+                    offset: 40,
+                });
+                emit!(e, "movq", guard, reg(dst));
+            }
+            ObjectFormat::MachO => {
+                let entry = rip_relative(format!(
+                    "{}@GOTPCREL",
+                    self.format.symbol("__stack_chk_guard")
+                ));
+                emit!(e, "movq", entry, reg(dst));
+                emit!(e, "movq", mem(dst, 0), reg(dst));
+            }
+        }
+    }
+}
+
+impl Backend for X86_64 {
+    fn registers(&self) -> RegisterSet<'static> {
+        ALLOCATABLE_REGS
+    }
+
+    fn calling_convention(&self) -> CallingConvention {
+        CALLING_CONVENTION
+    }
+
+    fn begin_module(&self, out: &mut Assembly) {
+        out.directive(".text");
+    }
+
+    fn end_module(&self, out: &mut Assembly) {
+        if self.format == ObjectFormat::Elf {
+            // Without this note the linker assumes the code needs an
+            // executable stack.
+            out.blank();
+            out.directive(".section .note.GNU-stack,\"\",@progbits");
+        }
+    }
+
+    fn begin_function(&self, out: &mut Assembly, func: &Function, visibility: Visibility) {
+        let symbol = self.format.symbol(&func.name);
+        out.blank();
+        self.format.export(out, &symbol, visibility);
+        out.directive(".p2align 4");
+        if self.format == ObjectFormat::Elf {
+            out.directive(format!(".type {symbol}, @function"));
+        }
+        out.label(symbol);
+    }
+
+    fn end_function(&self, out: &mut Assembly, func: &Function) {
+        if self.format == ObjectFormat::Elf {
+            let symbol = self.format.symbol(&func.name);
+            out.directive(format!(".size {symbol}, .-{symbol}"));
+        }
+    }
+
+    fn string_pool(&self, out: &mut Assembly, strings: &StringPool) {
+        out.blank();
+        out.directive(match self.format {
+            ObjectFormat::MachO => ".section __TEXT,__cstring,cstring_literals",
+            ObjectFormat::Elf => ".section .rodata.str1.1,\"aMS\",@progbits,1",
+        });
+        for (id, bytes) in strings.iter() {
+            out.label(string_label(self.format, id));
+            out.directive(asciz(bytes));
+        }
+    }
+
+    fn global(&self, out: &mut Assembly, global: &Global, visibility: Visibility) {
+        let symbol = self.format.symbol(&global.name);
+        let data = data_directive(global.ty.bytes());
+        out.blank();
+        if global.thread_local && self.format == ObjectFormat::MachO {
+            macho_thread_local(out, &symbol, global, visibility, data);
+            return;
+        }
+        out.directive(data_section(global, '@'));
+        self.format.export(out, &symbol, visibility);
+        out.directive(format!(".p2align {}", global.ty.bytes().trailing_zeros()));
+        if self.format == ObjectFormat::Elf {
+            let kind = if global.thread_local {
+                "tls_object"
+            } else {
+                "object"
+            };
+            out.directive(format!(".type {symbol}, @{kind}"));
+            out.directive(format!(".size {symbol}, {}", global.size()));
+        }
+        out.label(symbol);
+        global_data(out, global, self.format, data);
+    }
+
+    fn object_format(&self) -> ObjectFormat {
+        self.format
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::Att
+    }
+
+    fn dwarf_frame_register(&self) -> u8 {
+        // DWARF numbers %rax, %rdx, %rcx, %rbx, %rsi and %rdi before %rbp.
+        6
+    }
+
+    /// Saves the frame pointer, then reserves the frame. The canonical
+    /// frame address is the stack pointer before the call, 16 bytes above
+    /// the saved `%rbp`.
+    fn prologue(&self, e: &mut Emitter) {
+        if !e.frame.pointer {
+            return;
+        }
+        emit!(e, "pushq", reg("%rbp"));
+        e.cfi(".cfi_def_cfa_offset 16");
+        e.cfi(".cfi_offset %rbp, -16");
+        emit!(e, "movq", reg("%rsp"), reg("%rbp"));
+        e.cfi(".cfi_def_cfa_register %rbp");
+        if e.frame.size > 0 {
+            emit!(e, "subq", imm(e.frame.size), reg("%rsp"));
+        }
+        for (offset, saved) in saved_slots(e) {
+            let name = REGS64[saved as usize];
+            emit!(e, "movq", reg(name), frame_slot(offset));
+            e.cfi(format!(".cfi_offset {name}, -{}", offset + 16));
+        }
+        if let Some(offset) = e.frame.canary {
+            // `%rax` is free until the body; the arguments are still live.
+            self.load_stack_guard(e, "%rax");
+            emit!(e, "movq", reg("%rax"), frame_slot(offset));
+        }
+    }
+
+    fn count(&self, e: &mut Emitter, symbol: &str, index: usize) {
+        let symbol = self.format.symbol(symbol);
+        let counter = match index {
+            0 => symbol,
+            _ => format!("{symbol}+{}", 8 * index),
+        };
+        emit!(e, "incq", rip_relative(counter));
+    }
+
+    /// Calls `mcount`, which saves the argument registers itself.
+    fn mcount(&self, e: &mut Emitter) {
+        let mcount = self.call_target(e, "mcount");
+        emit!(e, "call", mcount);
+    }
+
+    /// Restores saved registers, releases the frame and returns; the result
+    /// is already in `%eax`.
+    fn epilogue(&self, e: &mut Emitter) {
+        let smashed = e.frame.canary.map(|offset| {
+            let smashed = e.labels.fresh();
+            self.load_stack_guard(e, "%rcx");
+            emit!(e, "cmpq", frame_slot(offset), reg("%rcx"));
+            emit!(e, "jne", sym(&smashed));
+            smashed
+        });
+        for (offset, saved) in saved_slots(e) {
+            emit!(e, "movq", frame_slot(offset), reg(REGS64[saved as usize]));
+        }
+        // The code after the `ret` still runs with the frame set up.
+        if e.frame.pointer {
+            e.cfi(".cfi_remember_state");
+        }
+        if e.frame.size > 0 || e.frame.dynamic {
+            emit!(e, "leave");
+        } else if e.frame.pointer {
+            emit!(e, "popq", reg("%rbp"));
+        }
+        if e.frame.pointer {
+            e.cfi(".cfi_def_cfa %rsp, 8");
+        }
+        emit!(e, "ret");
+        if e.frame.pointer {
+            e.cfi(".cfi_restore_state");
+        }
+        if let Some(smashed) = smashed {
+            e.label(&smashed);
+            let fail = self.call_target(e, "__stack_chk_fail");
+            emit!(e, "call", fail);
+        }
+    }
+
+    fn receive_param(&self, e: &mut Emitter, index: usize, value: Value) {
+        match ARG_REGS.get(index) {
+            Some(reg) => define(e, value, reg),
+            None => {
+                let offset = INCOMING_ARGS_OFFSET + e.stack_arg_offset(index);
+                let dst = result_reg(e, value);
+                let mnemonic = format!("mov{}", suffix(e, value));
+                emit!(e, mnemonic, mem("%rbp", offset), reg(dst));
+                spill_result(e, value, dst);
+            }
+        }
+    }
+
+    fn instr(&self, e: &mut Emitter, instr: &Instr) {
+        match instr {
+            Instr::Const { .. } => {}
+            Instr::Loc { .. } => unreachable!("the code generator emits source locations"),
+            Instr::Binary { dst, op, lhs, rhs } => binary(e, *dst, *op, *lhs, *rhs),
+            Instr::Cmp { dst, op, lhs, rhs } => {
+                compare(e, *lhs, *rhs);
+                emit!(e, format!("set{}", condition(*op)), reg("%al"));
+                let dst_reg = result_reg(e, *dst);
+                emit!(e, "movzbl", reg("%al"), reg(dst_reg));
+                spill_result(e, *dst, dst_reg);
+            }
+            Instr::Call {
+                dst,
+                callee,
+                args,
+                fixed,
+            } => {
+                // Stack arguments go first, while `%eax` is still free as scratch.
+                for (i, &arg) in args.iter().enumerate().skip(ARG_REGS.len()) {
+                    let value = register(e, arg, "%eax");
+                    let suffix = suffix(e, arg);
+                    let slot = mem("%rsp", e.stack_arg_offset(i));
+                    emit!(e, format!("mov{suffix}"), reg(value), slot);
+                }
+                for (reg, &arg) in ARG_REGS.iter().zip(args) {
+                    move_to(e, reg, arg);
+                }
+                // A variadic callee finds in `%al` how many vector registers
+                // hold arguments, which is none.
+                if fixed.is_some() {
+                    emit!(e, "xorl", reg("%eax"), reg("%eax"));
+                }
+                let callee = self.call_target(e, callee);
+                emit!(e, "call", callee);
+                if let Some(dst) = dst {
+                    define(e, *dst, "%eax");
+                }
+            }
+            Instr::InlineAsm {
+                dst,
+                template,
+                inputs,
+            } => {
+                // Operands take the argument registers in order, which the
+                // allocator never hands out.
+                let mut regs = ARG_REGS.iter().copied();
+                let mut operands = Vec::new();
+                if let Some(dst) = dst {
+                    operands.push(sized(e, *dst, regs.next().expect("six operands at most")));
+                }
+                for (&input, reg) in inputs.iter().zip(regs) {
+                    move_to(e, reg, input);
+                    operands.push(sized(e, input, reg));
+                }
+                e.inline_asm(template, &operands);
+                if let (Some(dst), Some(&reg)) = (dst, operands.first()) {
+                    define(e, *dst, reg);
+                }
+            }
+            Instr::Cast { dst, op, value } => {
+                let from = e.func.value_type(*value);
+                let src = register(e, *value, "%eax");
+                let dst_reg = result_reg(e, *dst);
+                let s = suffix(e, *dst);
+                match (op, from) {
+                    (CastOp::SExt, IrType::I8) => {
+                        emit!(e, format!("movsb{s}"), reg(view(src, 8)), reg(dst_reg));
+                    }
+                    (CastOp::SExt, IrType::I16) => {
+                        emit!(e, format!("movsw{s}"), reg(view(src, 16)), reg(dst_reg));
+                    }
+                    (CastOp::SExt, _) => emit!(e, "movslq", reg(src), reg(dst_reg)),
+                    // Writing a 32-bit register clears the upper half of the
+                    // 64-bit one.
+                    (CastOp::ZExt, IrType::I8) => {
+                        emit!(e, "movzbl", reg(view(src, 8)), reg(view(dst_reg, 32)));
+                    }
+                    (CastOp::ZExt, IrType::I16) => {
+                        emit!(e, "movzwl", reg(view(src, 16)), reg(view(dst_reg, 32)));
+                    }
+                    // Narrow values may carry garbage above their width, so
+                    // truncating only drops the upper half of a pointer.
+                    (CastOp::ZExt | CastOp::Trunc, _) => {
+                        emit!(e, "movl", reg(view(src, 32)), reg(view(dst_reg, 32)));
+                    }
+                }
+                spill_result(e, *dst, dst_reg);
+            }
+            Instr::Load { dst, slot } => {
+                let dst_reg = result_reg(e, *dst);
+                let slot = frame_slot(e.slot_offset(*slot));
+                // Narrow loads zero-extend; a sign-extending use extends
+                // them explicitly.
+                let mnemonic = match e.func.value_type(*dst) {
+                    IrType::I8 => "movzbl".to_string(),
+                    IrType::I16 => "movzwl".to_string(),
+                    _ => format!("mov{}", suffix(e, *dst)),
+                };
+                emit!(e, mnemonic, slot, reg(dst_reg));
+                spill_result(e, *dst, dst_reg);
+            }
+            Instr::Store { slot, value } => {
+                let ty = e.func.value_type(*value);
+                let suffix = match ty {
+                    IrType::I8 => 'b',
+                    IrType::I16 => 'w',
+                    _ => suffix(e, *value),
+                };
+                let src = register(e, *value, "%eax");
+                let src = if ty.is_narrow() {
+                    view(src, ty.bits())
+                } else {
+                    src
+                };
+                let slot = frame_slot(e.slot_offset(*slot));
+                emit!(e, format!("mov{suffix}"), reg(src), slot);
+            }
+            Instr::LoadPtr { dst, ptr } => {
+                let ptr = register(e, *ptr, "%ecx");
+                let dst_reg = result_reg(e, *dst);
+                let mnemonic = match e.func.value_type(*dst) {
+                    IrType::I8 => "movzbl".to_string(),
+                    IrType::I16 => "movzwl".to_string(),
+                    _ => format!("mov{}", suffix(e, *dst)),
+                };
+                emit!(e, mnemonic, mem(ptr, 0), reg(dst_reg));
+                spill_result(e, *dst, dst_reg);
+            }
+            Instr::StorePtr { ptr, value } => {
+                let ptr = register(e, *ptr, "%ecx");
+                let ty = e.func.value_type(*value);
+                let suffix = match ty {
+                    IrType::I8 => 'b',
+                    IrType::I16 => 'w',
+                    _ => suffix(e, *value),
+                };
+                let src = register(e, *value, "%eax");
+                let src = if ty.is_narrow() {
+                    view(src, ty.bits())
+                } else {
+                    src
+                };
+                emit!(e, format!("mov{suffix}"), reg(src), mem(ptr, 0));
+            }
+            Instr::SlotAddr { dst, slot } => {
+                let dst_reg = result_reg(e, *dst);
+                emit!(e, "leaq", frame_slot(e.slot_offset(*slot)), reg(dst_reg));
+                spill_result(e, *dst, dst_reg);
+            }
+            Instr::StringAddr { dst, string } => {
+                let dst_reg = result_reg(e, *dst);
+                let label = string_label(self.format, *string);
+                emit!(e, "leaq", rip_relative(label), reg(dst_reg));
+                spill_result(e, *dst, dst_reg);
+            }
+            Instr::Alloca { dst, size } => {
+                // Rounding the size up to 16 bytes keeps `%rsp` aligned. The
+                // outgoing argument area moves down to stay at `%rsp`.
+                move_to(e, "%eax", *size);
+                emit!(e, "addq", imm(15), reg("%rax"));
+                emit!(e, "andq", imm(-16), reg("%rax"));
+                emit!(e, "subq", reg("%rax"), reg("%rsp"));
+                let dst_reg = result_reg(e, *dst);
+                emit!(e, "leaq", mem("%rsp", e.frame.outgoing), reg(dst_reg));
+                spill_result(e, *dst, dst_reg);
+            }
+            Instr::GlobalAddr {
+                dst,
+                name,
+                thread_local: true,
+            } => {
+                let dst_reg = result_reg(e, *dst);
+                self.thread_local_addr(e, dst_reg, name);
+                spill_result(e, *dst, dst_reg);
+            }
+            Instr::GlobalAddr { dst, name, .. } => {
+                let dst_reg = result_reg(e, *dst);
+                let symbol = self.format.symbol(name);
+                if e.global_via_got(self.format, name) {
+                    let entry = rip_relative(format!("{symbol}@GOTPCREL"));
+                    emit!(e, "movq", entry, reg(dst_reg));
+                } else {
+                    emit!(e, "leaq", rip_relative(symbol), reg(dst_reg));
+                }
+                spill_result(e, *dst, dst_reg);
+            }
+        }
+    }
+
+    fn return_value(&self, e: &mut Emitter, value: Value) {
+        move_to(e, "%eax", value);
+    }
+
+    fn jump(&self, e: &mut Emitter, label: &str) {
+        emit!(e, "jmp", sym(label));
+    }
+
+    fn branch(&self, e: &mut Emitter, cond: Value, nonzero: bool, label: &str) {
+        let suffix = suffix(e, cond);
+        match e.location(cond) {
+            Location::Stack(_) => emit!(e, format!("cmp{suffix}"), imm(0), operand(e, cond)),
+            _ => {
+                let cond = register(e, cond, "%eax");
+                emit!(e, format!("test{suffix}"), reg(cond), reg(cond));
+            }
+        }
+        let mnemonic = if nonzero { "jne" } else { "je" };
+        emit!(e, mnemonic, sym(label));
+    }
+
+    fn compare_and_branch(&self, e: &mut Emitter, op: CmpOp, lhs: Value, rhs: Value, label: &str) {
+        compare(e, lhs, rhs);
+        emit!(e, format!("j{}", condition(op)), sym(label));
+    }
+
+    fn compare_imm_and_branch(
+        &self,
+        e: &mut Emitter,
+        op: CmpOp,
+        value: Value,
+        imm: i64,
+        label: &str,
+    ) {
+        let suffix = suffix(e, value);
+        let value = register(e, value, "%eax");
+        let imm = if i32::try_from(imm).is_ok() {
+            Operand::Imm(imm)
+        } else {
+            emit!(e, "movabsq", Operand::Imm(imm), reg("%rcx"));
+            reg("%rcx")
+        };
+        emit!(e, format!("cmp{suffix}"), imm, reg(value));
+        emit!(e, format!("j{}", condition(op)), sym(label));
+    }
+
+    fn jump_table(
+        &self,
+        e: &mut Emitter,
+        value: Value,
+        min: i64,
+        targets: &[String],
+        default: &str,
+    ) {
+        let value = register(e, value, "%eax");
+        if value != "%eax" {
+            emit!(e, "movl", reg(value), reg("%eax"));
+        }
+        if min != 0 {
+            emit!(e, "subl", imm(min), reg("%eax"));
+        }
+        // One unsigned comparison catches values below `min` as well.
+        emit!(e, "cmpl", imm(targets.len() as i64 - 1), reg("%eax"));
+        emit!(e, "ja", sym(default));
+        let table = e.labels.fresh();
+        emit!(e, "leaq", rip_relative(&table), reg("%rcx"));
+        let entry = Operand::Mem(Address::Indexed {
+            base: "%rcx".to_string(),
+            index: "%rax".to_string(),
+            scale: 4,
+        });
+        emit!(e, "movslq", entry, reg("%rdx"));
+        emit!(e, "addq", reg("%rcx"), reg("%rdx"));
+        emit!(e, "jmp", Operand::Indirect("%rdx".to_string()));
+        e.directive(".p2align 2".to_string());
+        e.label(&table);
+        for target in targets {
+            e.directive(format!(".long {target}-{table}"));
+        }
+    }
+
+    fn trap(&self, e: &mut Emitter, _code: u16) {
+        emit!(e, "ud2");
+    }
+}
+
+fn binary(e: &mut Emitter, dst: Value, op: BinOp, lhs: Value, rhs: Value) {
+    let s = suffix(e, dst);
+    let divide = match op {
+        BinOp::SDiv | BinOp::SRem => Some("idiv"),
+        BinOp::UDiv | BinOp::URem => Some("div"),
+        _ => None,
+    };
+    let (eax, ecx, edx) = (
+        sized(e, dst, "%eax"),
+        sized(e, dst, "%ecx"),
+        sized(e, dst, "%edx"),
+    );
+    if let Some(mnemonic) = divide {
+        // The dividend is `%edx:%eax`; the quotient lands in `%eax` and the
+        // remainder in `%edx`.
+        move_to(e, eax, lhs);
+        if mnemonic == "idiv" {
+            emit!(e, if s == 'q' { "cqto" } else { "cltd" });
+        } else {
+            emit!(e, format!("xor{s}"), reg(edx), reg(edx));
+        }
+        let divisor = match e.location(rhs) {
+            Location::Imm(_) => {
+                move_to(e, ecx, rhs);
+                reg(ecx)
+            }
+            _ => operand(e, rhs),
+        };
+        emit!(e, format!("{mnemonic}{s}"), divisor);
+        let result = match op {
+            BinOp::SDiv | BinOp::UDiv => eax,
+            _ => edx,
+        };
+        define(e, dst, result);
+        return;
+    }
+
+    // `0 - x` and `x ^ -1` are how the front end spells `-x` and `~x`.
+    // `neg` sets the overflow flag for the most negative value like `sub`.
+    let unary = match (op, e.location(lhs), e.location(rhs)) {
+        (BinOp::Sub, Location::Imm(0), _) => Some(("neg", rhs)),
+        (BinOp::Xor, _, Location::Imm(-1)) => Some(("not", lhs)),
+        _ => None,
+    };
+    if let Some((mnemonic, value)) = unary {
+        let work = result_reg(e, dst);
+        move_to(e, work, value);
+        emit!(e, format!("{mnemonic}{s}"), reg(work));
+        if mnemonic == "neg" && e.checks_overflow(dst) {
+            let trap = e.overflow_trap();
+            emit!(e, "jo", sym(trap));
+        }
+        spill_result(e, dst, work);
+        return;
+    }
+
+    // Two-address instructions overwrite their left operand, so compute in
+    // the destination register unless that would clobber `rhs`.
+    let work = match (e.location(dst), e.location(rhs)) {
+        (Location::Reg(d), Location::Reg(r)) if d == r => eax,
+        _ => result_reg(e, dst),
+    };
+    move_to(e, work, lhs);
+    let rhs_location = e.location(rhs);
+    let rhs = source(e, rhs, "%ecx");
+    match op {
+        BinOp::Add => emit!(e, format!("add{s}"), rhs, reg(work)),
+        BinOp::Sub => emit!(e, format!("sub{s}"), rhs, reg(work)),
+        BinOp::And => emit!(e, format!("and{s}"), rhs, reg(work)),
+        BinOp::Or => emit!(e, format!("or{s}"), rhs, reg(work)),
+        BinOp::Xor => emit!(e, format!("xor{s}"), rhs, reg(work)),
+        BinOp::Mul => match rhs {
+            Operand::Imm(_) => emit!(e, format!("imul{s}"), rhs, reg(work), reg(work)),
+            _ => emit!(e, format!("imul{s}"), rhs, reg(work)),
+        },
+        BinOp::Shl | BinOp::AShr | BinOp::LShr => {
+            let mnemonic = match op {
+                BinOp::Shl => "shl",
+                BinOp::AShr => "sar",
+                _ => "shr",
+            };
+            match rhs_location {
+                Location::Imm(amount) => emit!(e, format!("{mnemonic}{s}"), imm(amount), reg(work)),
+                _ => {
+                    // Variable shift counts must be in `%cl`.
+                    emit!(e, format!("mov{s}"), rhs, reg(ecx));
+                    emit!(e, format!("{mnemonic}{s}"), reg("%cl"), reg(work));
+                }
+            }
+        }
+        BinOp::SDiv | BinOp::SRem | BinOp::UDiv | BinOp::URem => unreachable!(),
+    }
+    if matches!(op, BinOp::Add | BinOp::Sub | BinOp::Mul) && e.checks_overflow(dst) {
+        let trap = e.overflow_trap();
+        emit!(e, "jo", sym(trap));
+    }
+    if work == eax {
+        define(e, dst, work);
+    } else {
+        spill_result(e, dst, work);
+    }
+}
+
+/// The directive that emits a `size`-byte integer.
+fn data_directive(size: u32) -> &'static str {
+    match size {
+        1 => ".byte",
+        2 => ".short",
+        4 => ".long",
+        8 => ".quad",
+        _ => unreachable!("no {size}-byte integer type"),
+    }
+}
+
+/// Sets the flags from `lhs - rhs`. The left operand must be a register so
+/// that at most one operand is in memory.
+fn compare(e: &mut Emitter, lhs: Value, rhs: Value) {
+    let suffix = suffix(e, lhs);
+    let lhs = register(e, lhs, "%eax");
+    let rhs = source(e, rhs, "%ecx");
+    emit!(e, format!("cmp{suffix}"), rhs, reg(lhs));
+}
+
+/// The condition-code suffix that holds after `cmp` when `op` does.
+fn condition(op: CmpOp) -> &'static str {
+    match op {
+        CmpOp::Eq => "e",
+        CmpOp::Ne => "ne",
+        CmpOp::Slt => "l",
+        CmpOp::Sle => "le",
+        CmpOp::Sgt => "g",
+        CmpOp::Sge => "ge",
+        CmpOp::Ult => "b",
+        CmpOp::Ule => "be",
+        CmpOp::Ugt => "a",
+        CmpOp::Uge => "ae",
+    }
+}
+
+/// Saved registers with the offset below `%rbp` of each.
+fn saved_slots(e: &Emitter) -> Vec<(u32, u8)> {
+    e.frame
+        .saved
+        .iter()
+        .enumerate()
+        .map(|(i, &reg)| (8 * (i as u32 + 1), reg))
+        .collect()
+}
+
+/// The operand-size suffix for instructions on `value`.
+fn suffix(e: &Emitter, value: Value) -> char {
+    if e.is_wide(value) {
+        'q'
+    } else {
+        'l'
+    }
+}
+
+/// The `bits`-wide view of the register named `reg` in any width.
+fn view(reg: &str, bits: u32) -> &'static str {
+    let index = [REGS8, REGS16, REGS32, REGS64]
+        .iter()
+        .find_map(|names| names.iter().position(|&r| r == reg))
+        .expect("a general-purpose register");
+    match bits {
+        8 => REGS8[index],
+        16 => REGS16[index],
+        32 => REGS32[index],
+        _ => REGS64[index],
+    }
+}
+
+/// `reg`, named by its 32-bit form, in the width `value` needs.
+fn sized(e: &Emitter, value: Value, reg: &'static str) -> &'static str {
+    match REGS32.iter().position(|&r| r == reg) {
+        Some(index) if e.is_wide(value) => REGS64[index],
+        _ => reg,
+    }
+}
+
+/// The register numbered `reg` in the width `value` needs.
+fn reg_name(e: &Emitter, value: Value, reg: u8) -> &'static str {
+    sized(e, value, REGS32[reg as usize])
+}
+
+/// Returns a register holding `value`, loading it into `scratch` if needed.
+fn register(e: &mut Emitter, value: Value, scratch: &'static str) -> &'static str {
+    match e.location(value) {
+        Location::Reg(reg) => reg_name(e, value, reg),
+        _ => {
+            let scratch = sized(e, value, scratch);
+            move_to(e, scratch, value);
+            scratch
+        }
+    }
+}
+
+/// Copies `value` into the register `dst`.
+fn move_to(e: &mut Emitter, dst: &'static str, value: Value) {
+    let dst = sized(e, value, dst);
+    match e.location(value) {
+        // Only `movabsq` takes an immediate that does not fit in 32 bits.
+        Location::Imm(value) if i32::try_from(value).is_err() => {
+            emit!(e, "movabsq", imm(value), reg(dst));
+        }
+        _ => {
+            let src = operand(e, value);
+            if src != reg(dst) {
+                emit!(e, format!("mov{}", suffix(e, value)), src, reg(dst));
+            }
+        }
+    }
+}
+
+/// Records that `value` is now held in the register `src`.
+fn define(e: &mut Emitter, value: Value, src: &'static str) {
+    let src = sized(e, value, src);
+    match e.location(value) {
+        Location::Imm(_) => unreachable!("constants are never defined at runtime"),
+        _ => emit!(
+            e,
+            format!("mov{}", suffix(e, value)),
+            reg(src),
+            operand(e, value)
+        ),
+    }
+}
+
+/// Stores a result computed in `src` if its value lives on the stack.
+fn spill_result(e: &mut Emitter, value: Value, src: &str) {
+    if let Location::Stack(offset) = e.location(value) {
+        emit!(
+            e,
+            format!("mov{}", suffix(e, value)),
+            reg(src),
+            frame_slot(offset)
+        );
+    }
+}
+
+/// The operand naming `value` where it lives.
+fn operand(e: &Emitter, value: Value) -> Operand {
+    match e.location(value) {
+        Location::Imm(value) => imm(value),
+        Location::Reg(number) => reg(reg_name(e, value, number)),
+        Location::Stack(offset) => frame_slot(offset),
+    }
+}
+
+/// The operand naming `value` as the source of an instruction, whose
+/// immediate can only be 32 bits; a wider constant is loaded into
+/// `scratch` first.
+fn source(e: &mut Emitter, value: Value, scratch: &'static str) -> Operand {
+    match e.location(value) {
+        Location::Imm(imm) if i32::try_from(imm).is_err() => {
+            move_to(e, scratch, value);
+            reg(sized(e, value, scratch))
+        }
+        _ => operand(e, value),
+    }
+}
+
+/// The frame memory `offset` bytes below `%rbp`.
+fn frame_slot(offset: u32) -> Operand {
+    mem("%rbp", -i64::from(offset))
+}
+
+/// The memory at `symbol`, addressed relative to the instruction pointer.
+fn rip_relative(symbol: impl Into<String>) -> Operand {
+    Operand::Mem(Address::Symbol {
+        base: "%rip".to_string(),
+        symbol: symbol.into(),
+    })
+}
+
+/// The register an instruction defining `value` should write to.
+fn result_reg(e: &Emitter, value: Value) -> &'static str {
+    match e.location(value) {
+        Location::Reg(reg) => reg_name(e, value, reg),
+        _ => sized(e, value, "%eax"),
+    }
+}
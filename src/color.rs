@@ -0,0 +1,89 @@
+//! Terminal colors for diagnostics and printed artifacts (`--color`).
+//!
+//! Colors are ANSI escape sequences. By default they are used only when the
+//! output is a terminal and `NO_COLOR` is unset or empty, following
+//! <https://no-color.org>.
+
+use std::io::IsTerminal;
+
+/// When to color output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// When writing to a terminal and `NO_COLOR` is not set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "auto" => ColorChoice::Auto,
+            "always" => ColorChoice::Always,
+            "never" => ColorChoice::Never,
+            _ => return None,
+        })
+    }
+
+    /// Whether to color what is written to `stream`.
+    pub fn enabled(self, stream: &impl IsTerminal) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                stream.is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+            }
+        }
+    }
+}
+
+/// An ANSI text style.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style(&'static str);
+
+impl Style {
+    pub const BOLD: Style = Style("1");
+    pub const ERROR: Style = Style("1;31");
+    pub const WARNING: Style = Style("1;35");
+    pub const NOTE: Style = Style("1;36");
+    pub const DIM: Style = Style("2");
+    pub const DIRECTIVE: Style = Style("36");
+
+    /// `text` in this style, or as it is if `enabled` is false.
+    pub fn paint(self, text: &str, enabled: bool) -> String {
+        if enabled && !text.is_empty() {
+            format!("\x1b[{}m{text}\x1b[0m", self.0)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Colors a listing of assembly, IR or tokens line by line: labels in
+/// bold, comments dimmed and directives in cyan.
+pub fn highlight(text: &str, comment: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+        let style = if !comment.is_empty() && trimmed.starts_with(comment) {
+            Some(Style::DIM)
+        } else if trimmed.starts_with('.') && !indent.is_empty() {
+            Some(Style::DIRECTIVE)
+        } else if indent.is_empty() && line.ends_with(':') {
+            Some(Style::BOLD)
+        } else {
+            None
+        };
+        match style {
+            Some(style) => {
+                out.push_str(indent);
+                out.push_str(&style.paint(trimmed, true));
+            }
+            None => out.push_str(line),
+        }
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,99 @@
+//! Line coverage data, as written by programs compiled with `--coverage`
+//! and read back by `rcc cov report`.
+//!
+//! At exit an instrumented program appends one record to its data file: a
+//! text header naming the source file and the lines that have counters,
+//! followed by one little-endian `u64` per line counting how many times a
+//! statement on it ran. Each run adds a record, and a report sums them.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Where an instrumented program's source is and where it writes its counts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Files {
+    /// The source file, as the report should find it.
+    pub source: String,
+    /// The data file the program appends to at exit.
+    pub data: String,
+}
+
+const MAGIC: &str = "rcccov";
+
+/// The header of a record for `source` with a counter for each of `lines`.
+pub fn header(source: &str, lines: &[u32]) -> String {
+    let lines: Vec<String> = lines.iter().map(u32::to_string).collect();
+    format!("{MAGIC}\n{source}\n{}\n{}\n", lines.len(), lines.join(" "))
+}
+
+/// Execution counts for the lines of one source file, summed over every
+/// record in a data file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    pub source: String,
+    pub counts: BTreeMap<u32, u64>,
+    /// How many runs the data file recorded.
+    pub runs: u32,
+}
+
+impl Profile {
+    pub fn parse(mut data: &[u8]) -> Result<Self, String> {
+        let mut profile = Profile::default();
+        while !data.is_empty() {
+            let mut fields = Vec::new();
+            for _ in 0..4 {
+                let end = data
+                    .iter()
+                    .position(|&b| b == b'\n')
+                    .ok_or("truncated record header")?;
+                let field = std::str::from_utf8(&data[..end]).map_err(|_| "header is not UTF-8")?;
+                fields.push(field);
+                data = &data[end + 1..];
+            }
+            if fields[0] != MAGIC {
+                return Err("not a coverage data file".to_string());
+            }
+            if profile.runs > 0 && fields[1] != profile.source {
+                return Err(format!(
+                    "records for both `{}` and `{}`",
+                    profile.source, fields[1]
+                ));
+            }
+            profile.source = fields[1].to_string();
+            let count: usize = fields[2].parse().map_err(|_| "bad counter count")?;
+            let lines = fields[3]
+                .split_whitespace()
+                .map(|line| line.parse::<u32>().map_err(|_| "bad line number"))
+                .collect::<Result<Vec<_>, _>>()?;
+            if lines.len() != count || data.len() < count * 8 {
+                return Err("truncated record".to_string());
+            }
+            let (counters, rest) = data.split_at(count * 8);
+            for (line, counter) in lines.iter().zip(counters.chunks_exact(8)) {
+                let counter = u64::from_le_bytes(counter.try_into().unwrap());
+                *profile.counts.entry(*line).or_default() += counter;
+            }
+            data = rest;
+            profile.runs += 1;
+        }
+        Ok(profile)
+    }
+
+    /// `source` with each line prefixed by its count in gcov's style: `-`
+    /// for lines without statements and `#####` for lines that never ran.
+    pub fn annotate(&self, source: &str) -> String {
+        let mut out = String::new();
+        writeln!(out, "{:>9}:{:>5}:Source:{}", "-", 0, self.source).unwrap();
+        writeln!(out, "{:>9}:{:>5}:Runs:{}", "-", 0, self.runs).unwrap();
+        for (i, text) in source.lines().enumerate() {
+            let line = i as u32 + 1;
+            let count = match self.counts.get(&line) {
+                None => "-".to_string(),
+                Some(0) => "#####".to_string(),
+                Some(count) => count.to_string(),
+            };
+            writeln!(out, "{count:>9}:{line:>5}:{text}").unwrap();
+        }
+        out
+    }
+}
@@ -0,0 +1,36 @@
+//! Differential testing against the system C compiler (`rcc diff`): a
+//! program is built by both compilers, and the two executables should
+//! behave the same.
+
+use std::path::Path;
+
+use crate::driver::{self, Options};
+use crate::toolchain::{self, Execution, Toolchain};
+
+/// How the two builds of one program compared.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Same(Execution),
+    Differ { rcc: Execution, cc: Execution },
+}
+
+/// Builds and runs `source`, read from `input`, with rcc and with the
+/// system compiler.
+pub fn compare(input: &Path, source: &str, options: &Options) -> Result<Verdict, String> {
+    let toolchain = Toolchain::new(options.target);
+    let artifacts =
+        driver::compile(source, options).map_err(|err| err.render(&input.display().to_string()))?;
+    let rcc = toolchain.run_assembly(input, &artifacts.assembly)?;
+
+    let exe = toolchain::temp_path(input, "out");
+    let cc = toolchain
+        .link(&[input.to_path_buf()], &exe)
+        .and_then(|()| toolchain::execute(&exe));
+    let _ = std::fs::remove_file(&exe);
+    let cc = cc?;
+    Ok(if rcc == cc {
+        Verdict::Same(rcc)
+    } else {
+        Verdict::Differ { rcc, cc }
+    })
+}
@@ -0,0 +1,304 @@
+//! Runs the compiler stages in order over one translation unit.
+
+use crate::analyzer::Analyzer;
+use crate::ast::Program;
+use crate::codegen::asm::Syntax;
+use crate::codegen::{self, CodeGenerator, SourceFile, Target, Visibility};
+use crate::coverage;
+use crate::error::{Error, Result};
+use crate::ice;
+use crate::ir::{self, Module};
+use crate::lexer::{Lexer, Position, Token};
+use crate::opt::{OptLevel, PassManager};
+use crate::parser::Parser;
+use crate::standard::Standard;
+use crate::stats;
+use crate::timings::Timings;
+use crate::warning::{Diagnostic, Warnings};
+
+/// How far a compilation goes. Stopping early lets the output of the
+/// first stages be printed even when a later one would reject the program.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stage {
+    Lex,
+    Parse,
+    /// Checks the program and finds its warnings, without generating code
+    /// (`--check`).
+    Analyze,
+    #[default]
+    Codegen,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub opt_level: OptLevel,
+    pub target: Target,
+    /// The file to describe in DWARF debug info (`-g`), if any.
+    pub debug_info: Option<SourceFile>,
+    /// Limits debug info to the line table (`-gline-tables-only`).
+    pub line_tables_only: bool,
+    /// Quotes the source in comments in the assembly (`-fverbose-asm`).
+    pub verbose_asm: bool,
+    /// Generates position-independent code (`-fPIC`, `-fPIE`).
+    pub pic: bool,
+    /// Whether the defined functions and globals are visible to other
+    /// shared objects (`-fvisibility=`).
+    pub visibility: Visibility,
+    /// Overrides whether leaf functions may skip the frame pointer
+    /// (`-f[no-]omit-frame-pointer`); by default they do when optimizing.
+    pub omit_frame_pointer: Option<bool>,
+    /// Traps on signed integer overflow
+    /// (`-fsanitize=signed-integer-overflow`).
+    pub sanitize_overflow: bool,
+    /// Checks a canary before returning from functions with stack buffers
+    /// (`-fstack-protector`).
+    pub stack_protector: bool,
+    /// Calls `mcount` on entry to every function (`-pg`).
+    pub profile: bool,
+    /// Counts how often each line runs (`--coverage`).
+    pub coverage: Option<coverage::Files>,
+    /// The language to accept (`--std=`).
+    pub standard: Standard,
+    /// Prints x86-64 assembly in Intel rather than AT&T syntax
+    /// (`-masm=intel`). Has no effect on AArch64.
+    pub intel_syntax: bool,
+    /// Records where in the source each line of assembly comes from
+    /// (`--emit=asm-map`).
+    pub source_map: bool,
+    /// The last stage to run; the artifacts of later ones are left empty.
+    pub last_stage: Stage,
+    /// The warnings to report (`-W`).
+    pub warnings: Warnings,
+}
+
+/// Everything produced while compiling one translation unit.
+#[derive(Debug)]
+pub struct Artifacts<'a> {
+    pub tokens: Vec<Token<'a>>,
+    pub program: Program,
+    /// The warnings [`Options::warnings`] turns on, in source order.
+    pub warnings: Vec<Diagnostic>,
+    pub ir: Module,
+    pub assembly: String,
+    /// The source position of each line of `assembly`, if
+    /// [`Options::source_map`] asked for them; empty otherwise.
+    pub positions: Vec<Option<Position>>,
+}
+
+impl<'a> Artifacts<'a> {
+    /// The artifacts of a compilation stopped after analysis, parsing or
+    /// lexing.
+    fn partial(tokens: Vec<Token<'a>>, program: Program) -> Self {
+        Artifacts {
+            tokens,
+            program,
+            warnings: Vec::new(),
+            ir: Module::default(),
+            assembly: String::new(),
+            positions: Vec::new(),
+        }
+    }
+}
+
+/// Where two compilations of the same source first differ, if they do: the
+/// artifact and its first differing line (`--deterministic`), along with
+/// the source line that line of assembly came from if positions were
+/// recorded. Nothing the compiler emits may depend on hash map iteration
+/// order, which changes from one compilation to the next.
+pub fn first_difference(first: &Artifacts, second: &Artifacts) -> Option<String> {
+    let texts = |artifacts: &Artifacts| {
+        [
+            ("tokens", format!("{:#?}", artifacts.tokens)),
+            ("syntax tree", format!("{:#?}", artifacts.program)),
+            ("IR", artifacts.ir.to_string()),
+            ("assembly", artifacts.assembly.clone()),
+        ]
+    };
+    let positions = &first.positions;
+    texts(first)
+        .into_iter()
+        .zip(texts(second))
+        .find(|((_, first), (_, second))| first != second)
+        .map(|((name, first), (_, second))| {
+            let first: Vec<&str> = first.lines().collect();
+            let second: Vec<&str> = second.lines().collect();
+            let line = first
+                .iter()
+                .zip(&second)
+                .position(|(a, b)| a != b)
+                .unwrap_or(first.len().min(second.len()));
+            let mut difference = format!(
+                "the {name} differs at line {}:\n    first:  {}\n    second: {}",
+                line + 1,
+                first.get(line).unwrap_or(&"<end>"),
+                second.get(line).unwrap_or(&"<end>")
+            );
+            let source = positions.get(line).copied().flatten();
+            if let (Some(pos), "assembly") = (source, name) {
+                difference.push_str(&format!("\n    from source line {}", pos.line));
+            }
+            difference
+        })
+}
+
+pub fn compile<'a>(source: &'a str, options: &Options) -> Result<Artifacts<'a>> {
+    compile_timed(source, options, &mut Timings::new())
+}
+
+/// Every error in `source` rather than just the first, in the order they
+/// appear: the lexer's error, or all of the syntax errors, or if there are
+/// none of those, the first error in each declaration.
+pub fn diagnose(source: &str, standard: Standard) -> Vec<Error> {
+    let tokens = match Lexer::new(source).with_standard(standard).lex() {
+        Ok(tokens) => tokens,
+        Err(err) => return vec![err],
+    };
+    let (program, errors) = Parser::new(tokens)
+        .with_standard(standard)
+        .parse_recovering();
+    // Declarations that failed to parse would look undeclared.
+    if !errors.is_empty() {
+        return errors;
+    }
+    Analyzer::new()
+        .with_standard(standard)
+        .analyze_recovering(&program)
+}
+
+/// Runs one stage under `name`, so a panic in it can say where it was.
+fn stage<T>(timings: &mut Timings, name: &'static str, f: impl FnOnce() -> T) -> T {
+    ice::enter_stage(name);
+    timings.time(name, f)
+}
+
+/// IR instructions in `module`, not counting terminators.
+fn ir_instructions(module: &Module) -> usize {
+    module
+        .functions
+        .iter()
+        .flat_map(|function| &function.blocks)
+        .map(|block| block.instrs.len())
+        .sum()
+}
+
+/// Like [`compile`], recording each stage in `timings`.
+pub fn compile_timed<'a>(
+    source: &'a str,
+    options: &Options,
+    timings: &mut Timings,
+) -> Result<Artifacts<'a>> {
+    crate::log!(
+        Info,
+        "driver",
+        "compiling for {} at {:?} as {}",
+        options.target.triple(),
+        options.opt_level,
+        options.standard
+    );
+    let passes = PassManager::for_level(options.opt_level);
+    let standard = options.standard;
+    let tokens = stage(timings, "lex", || {
+        Lexer::new(source).with_standard(standard).lex()
+    })?;
+    timings.count(tokens.len(), "tokens");
+    if options.last_stage == Stage::Lex {
+        return Ok(Artifacts::partial(tokens, Program::default()));
+    }
+    let program = stage(timings, "parse", || {
+        Parser::new(tokens.clone()).with_standard(standard).parse()
+    })?;
+    timings.count(stats::ast_nodes(&program), "AST nodes");
+    if options.last_stage == Stage::Parse {
+        return Ok(Artifacts::partial(tokens, program));
+    }
+    let warnings = stage(timings, "analyze", || {
+        Analyzer::new()
+            .with_standard(standard)
+            .with_data_model(options.target.data_model())
+            .analyze_with_warnings(&program)
+    })?
+    .into_iter()
+    .filter(|diagnostic| options.warnings.is_enabled(diagnostic.warning))
+    .collect();
+    if options.last_stage == Stage::Analyze {
+        return Ok(Artifacts {
+            warnings,
+            ..Artifacts::partial(tokens, program)
+        });
+    }
+    let mut ir = stage(timings, "lower", || {
+        if options.debug_info.is_some()
+            || options.verbose_asm
+            || options.coverage.is_some()
+            || options.source_map
+        {
+            ir::lower::lower_program_with_debug_info(&program, options.target.data_model())
+        } else {
+            ir::lower::lower_program(&program, options.target.data_model())
+        }
+    });
+    timings.count(ir_instructions(&ir), "IR instructions");
+    stage(timings, "optimize", || passes.run_ir(&mut ir));
+    timings.count(ir_instructions(&ir), "IR instructions");
+    let Some(backend) = options.target.backend() else {
+        let assembly = stage(timings, "codegen", || codegen::wasm::emit(&ir))?;
+        return Ok(Artifacts {
+            tokens,
+            program,
+            warnings,
+            ir,
+            assembly,
+            positions: Vec::new(),
+        });
+    };
+    // Debuggers find a function's frame through its frame pointer.
+    let omit_frame_pointer = options
+        .omit_frame_pointer
+        .unwrap_or(passes.omit_frame_pointer && options.debug_info.is_none());
+    let mut generator = CodeGenerator::new()
+        .with_backend(backend)
+        .with_register_allocation(passes.allocate_registers)
+        .with_pic(options.pic)
+        .with_visibility(options.visibility)
+        .with_omit_frame_pointer(omit_frame_pointer)
+        .with_overflow_checks(options.sanitize_overflow)
+        .with_stack_protector(options.stack_protector)
+        .with_profiling(options.profile)
+        .with_source_map(options.source_map);
+    if let Some(source) = &options.debug_info {
+        generator = if options.line_tables_only {
+            generator.with_line_tables(source.clone())
+        } else {
+            generator.with_debug_info(source.clone())
+        };
+    }
+    if options.verbose_asm {
+        generator = generator.with_source_comments(source);
+    }
+    if let Some(files) = &options.coverage {
+        generator = generator.with_coverage(files.clone());
+    }
+    let (assembly, positions) = stage(timings, "codegen", || {
+        let assembly = generator.generate_assembly(&ir);
+        let mut assembly = passes.run_asm(assembly, backend);
+        if options.intel_syntax && assembly.syntax == Syntax::Att {
+            assembly.syntax = Syntax::Intel;
+        }
+        let positions = if options.source_map {
+            assembly.positions()
+        } else {
+            Vec::new()
+        };
+        (assembly, positions)
+    });
+    timings.count(assembly.instructions().count(), "instructions");
+    let assembly = assembly.to_string();
+    Ok(Artifacts {
+        tokens,
+        program,
+        warnings,
+        ir,
+        assembly,
+        positions,
+    })
+}
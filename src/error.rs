@@ -0,0 +1,220 @@
+//! The error type shared by every compiler stage.
+
+use std::fmt;
+
+use crate::color::Style;
+use crate::lexer::{Position, Span};
+
+/// A compilation error, optionally anchored to a source position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub pos: Option<Position>,
+    /// Where the offending source ends, if known; otherwise the token at
+    /// `pos` is underlined.
+    pub end: Option<Position>,
+    pub message: String,
+    /// Another place in the source the error involves, such as an earlier
+    /// declaration, with what it is.
+    pub note: Option<Box<Error>>,
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// How serious a diagnostic is, which decides how it is labeled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    /// The severity in lowercase, as JSON diagnostics give it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Severity::Error => "error:",
+            Severity::Warning => "warning:",
+            Severity::Note => "note:",
+        }
+    }
+
+    fn style(self) -> Style {
+        match self {
+            Severity::Error => Style::ERROR,
+            Severity::Warning => Style::WARNING,
+            Severity::Note => Style::NOTE,
+        }
+    }
+}
+
+impl Error {
+    pub fn new(pos: Position, message: impl Into<String>) -> Self {
+        Self {
+            pos: Some(pos),
+            end: None,
+            message: message.into(),
+            note: None,
+        }
+    }
+
+    /// An error about all of the source in `span`.
+    pub fn spanning(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            pos: Some(span.start),
+            end: Some(span.end),
+            message: message.into(),
+            note: None,
+        }
+    }
+
+    /// An error that is not tied to any particular location in the source.
+    pub fn msg(message: impl Into<String>) -> Self {
+        Self {
+            pos: None,
+            end: None,
+            message: message.into(),
+            note: None,
+        }
+    }
+
+    /// Adds a note about `pos`, elsewhere in the source.
+    pub fn with_note(mut self, pos: Position, message: impl Into<String>) -> Self {
+        self.note = Some(Box::new(Error::new(pos, message)));
+        self
+    }
+
+    /// Formats the error the way the command-line driver reports it.
+    pub fn render(&self, file: &str) -> String {
+        self.render_colored(file, false)
+    }
+
+    /// Like [`render`](Self::render), coloring the location, the word
+    /// `error` and the message if `color` is set.
+    pub fn render_colored(&self, file: &str, color: bool) -> String {
+        let mut out = self.render_heading(Severity::Error, file, color);
+        if let Some(note) = &self.note {
+            out.push('\n');
+            out.push_str(&note.render_heading(Severity::Note, file, color));
+        }
+        out
+    }
+
+    /// The first line of the diagnostic, labeled with `severity`.
+    fn render_heading(&self, severity: Severity, file: &str, color: bool) -> String {
+        let location = match self.pos {
+            Some(pos) => format!("{file}:{pos}:"),
+            None => format!("{file}:"),
+        };
+        format!(
+            "{} {} {}",
+            Style::BOLD.paint(&location, color),
+            severity.style().paint(severity.label(), color),
+            Style::BOLD.paint(&self.message, color)
+        )
+    }
+
+    /// Where the underlined part of the error ends on `line`, the line it
+    /// starts on: at `end` if it is known, and otherwise after the token
+    /// at `pos`.
+    pub fn underline_end(&self, line: Option<&str>) -> Option<Position> {
+        let pos = self.pos?;
+        if self.end.is_some() {
+            return self.end;
+        }
+        let rest: String = line?.chars().skip(pos.column as usize - 1).collect();
+        Some(Position {
+            column: pos.column + token_width(&rest) as u32,
+            ..pos
+        })
+    }
+
+    /// Like [`render_colored`](Self::render_colored), followed by the line
+    /// of `source` the error is on with the offending part underlined.
+    pub fn render_snippet(&self, file: &str, source: &str, color: bool) -> String {
+        let line_of = |error: &Error| {
+            error
+                .pos
+                .and_then(|pos| source.lines().nth(pos.line as usize - 1))
+        };
+        let mut out = self.render_line(file, line_of(self), color);
+        if let Some(note) = &self.note {
+            out.push('\n');
+            out.push_str(&note.render_line_as(Severity::Note, file, line_of(note), color));
+        }
+        out
+    }
+
+    /// Like [`render_snippet`](Self::render_snippet), given just the line
+    /// the error is on, if it is known. The note is left to the caller,
+    /// which knows where its line is.
+    pub fn render_line(&self, file: &str, line: Option<&str>, color: bool) -> String {
+        self.render_line_as(Severity::Error, file, line, color)
+    }
+
+    /// Like [`render_line`](Self::render_line), labeled with `severity`
+    /// rather than as an error.
+    pub fn render_line_as(
+        &self,
+        severity: Severity,
+        file: &str,
+        line: Option<&str>,
+        color: bool,
+    ) -> String {
+        let mut out = self.render_heading(severity, file, color);
+        let (Some(pos), Some(line)) = (self.pos, line) else {
+            return out;
+        };
+        let number = pos.line.to_string();
+        let gutter = " ".repeat(number.len());
+        let bar = Style::DIM.paint("|", color);
+        // Tabs are kept so that the underline lines up with the text.
+        let indent: String = line
+            .chars()
+            .take(pos.column as usize - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let rest: String = line.chars().skip(pos.column as usize - 1).collect();
+        let width = match self.end {
+            Some(end) if end.line == pos.line => end.column.saturating_sub(pos.column) as usize,
+            Some(_) => rest.chars().count(),
+            None => token_width(&rest),
+        };
+        let underline = format!("^{}", "~".repeat(width.max(1) - 1));
+        out.push_str(&format!(
+            "\n {} {bar} {line}\n {gutter} {bar} {indent}{}",
+            Style::DIM.paint(&number, color),
+            severity.style().paint(&underline, color)
+        ));
+        out
+    }
+}
+
+/// How many characters the token at the start of `text` takes, as far as
+/// can be told without lexing it: a whole identifier or number, and
+/// otherwise one character.
+fn token_width(text: &str) -> usize {
+    let word = |c: char| c.is_alphanumeric() || c == '_';
+    match text.chars().next() {
+        Some(c) if word(c) => text.chars().take_while(|&c| word(c)).count(),
+        _ => 1,
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.pos {
+            Some(pos) => write!(f, "{pos}: {}", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
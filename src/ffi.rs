@@ -0,0 +1,156 @@
+//! A C interface for compiling in-process, declared in `include/rcc.h`.
+//!
+//! A compilation returns an opaque `rcc_result` owning the assembly or the
+//! rendered diagnostics as NUL-terminated strings, which stay valid until
+//! the result is freed with `rcc_result_free`.
+
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+use crate::codegen::Target;
+use crate::driver::{self, Options};
+use crate::opt::OptLevel;
+
+/// The outcome of `rcc_compile`.
+pub struct RccResult {
+    assembly: Option<CString>,
+    diagnostics: CString,
+}
+
+impl RccResult {
+    fn failed(diagnostics: String) -> Self {
+        Self {
+            assembly: None,
+            diagnostics: c_string(diagnostics),
+        }
+    }
+}
+
+/// `text` as a C string, with any interior NULs dropped.
+fn c_string(text: String) -> CString {
+    CString::new(text.replace('\0', "")).expect("NULs were removed")
+}
+
+/// # Safety
+///
+/// `ptr` must be null or point to a NUL-terminated string.
+unsafe fn str_arg<'a>(ptr: *const c_char, name: &str) -> Result<Option<&'a str>, String> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map(Some)
+        .map_err(|_| format!("{name} is not UTF-8"))
+}
+
+/// Compiles the `len` bytes at `source`, naming the file `file` in
+/// diagnostics, for the target triple `target` (the host if null) at the
+/// optimization level `opt_level` (0, 1, 2, or 3 for `-Os`).
+///
+/// Never returns null; the result must be freed with `rcc_result_free`.
+///
+/// # Safety
+///
+/// `source` must point to `len` readable bytes, and `file` and `target`
+/// must each be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn rcc_compile(
+    source: *const c_char,
+    len: usize,
+    file: *const c_char,
+    target: *const c_char,
+    opt_level: c_int,
+) -> *mut RccResult {
+    let result = compile(source, len, file, target, opt_level).unwrap_or_else(RccResult::failed);
+    Box::into_raw(Box::new(result))
+}
+
+unsafe fn compile(
+    source: *const c_char,
+    len: usize,
+    file: *const c_char,
+    target: *const c_char,
+    opt_level: c_int,
+) -> Result<RccResult, String> {
+    let file = str_arg(file, "the file name")?.unwrap_or("<input>");
+    let source = if source.is_null() {
+        if len > 0 {
+            return Err(format!("{file}: error: the source is null"));
+        }
+        ""
+    } else {
+        std::str::from_utf8(std::slice::from_raw_parts(source.cast::<u8>(), len))
+            .map_err(|_| format!("{file}: error: the source is not UTF-8"))?
+    };
+    let target = match str_arg(target, "the target")? {
+        Some(triple) => {
+            Target::from_triple(triple).ok_or_else(|| format!("unknown target `{triple}`"))?
+        }
+        None => Target::host().unwrap_or_default(),
+    };
+    let opt_level = match opt_level {
+        0 => OptLevel::O0,
+        1 => OptLevel::O1,
+        2 => OptLevel::O2,
+        3 => OptLevel::Os,
+        _ => return Err(format!("unknown optimization level {opt_level}")),
+    };
+    let options = Options {
+        opt_level,
+        target,
+        ..Options::default()
+    };
+    // Unwinding into C is undefined, so a compiler bug becomes a diagnostic.
+    let compiled = panic::catch_unwind(AssertUnwindSafe(|| driver::compile(source, &options)))
+        .map_err(|_| format!("{file}: error: internal compiler error"))?;
+    match compiled {
+        Ok(artifacts) => Ok(RccResult {
+            assembly: Some(c_string(artifacts.assembly)),
+            diagnostics: CString::default(),
+        }),
+        Err(err) => Err(err.render(file)),
+    }
+}
+
+/// The generated assembly, or null if compilation failed.
+///
+/// # Safety
+///
+/// `result` must come from `rcc_compile` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn rcc_result_assembly(result: *const RccResult) -> *const c_char {
+    (*result)
+        .assembly
+        .as_ref()
+        .map_or(ptr::null(), |assembly| assembly.as_ptr())
+}
+
+/// The diagnostics, one per line, or an empty string if there are none.
+///
+/// # Safety
+///
+/// `result` must come from `rcc_compile` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn rcc_result_diagnostics(result: *const RccResult) -> *const c_char {
+    (*result).diagnostics.as_ptr()
+}
+
+/// Frees `result` and the strings it owns. Does nothing if it is null.
+///
+/// # Safety
+///
+/// `result` must be null or come from `rcc_compile` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn rcc_result_free(result: *mut RccResult) {
+    if !result.is_null() {
+        drop(Box::from_raw(result));
+    }
+}
+
+/// The version of rcc, as a static string.
+#[no_mangle]
+pub extern "C" fn rcc_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
@@ -0,0 +1,124 @@
+//! Execution fixtures for `rcc test`: C programs that carry their expected
+//! behavior in directive comments.
+//!
+//! - `// expect-exit: N` gives the exit status, 0 if absent.
+//! - `// expect-stdout: TEXT` gives a line of standard output; several
+//!   directives give several lines in order.
+//! - `// expect-error: TEXT` says compilation fails with a diagnostic
+//!   containing `TEXT`, and the program is never run.
+//!
+//! Directive lines are blanked before compiling, which keeps the line
+//! numbers of diagnostics and lets fixtures compile under standards
+//! without `//` comments. Fixtures are preprocessed as the file they are
+//! in, so a quoted `#include` finds headers beside them.
+
+use std::path::Path;
+
+use crate::driver::{self, Artifacts, Options};
+use crate::preprocess::Preprocessor;
+use crate::toolchain::Toolchain;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fixture {
+    /// The source with its directive lines blanked.
+    pub source: String,
+    pub exit: i32,
+    /// The expected output, if any `expect-stdout` directive was given.
+    pub stdout: Option<String>,
+    pub error: Option<String>,
+}
+
+impl Fixture {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut fixture = Fixture::default();
+        let mut source = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let Some(directive) = line.trim().strip_prefix("//") else {
+                source.push(line);
+                continue;
+            };
+            source.push("");
+            let Some((name, value)) = directive.split_once(':') else {
+                return Err(format!("line {}: malformed directive", i + 1));
+            };
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match name.trim() {
+                "expect-exit" => {
+                    fixture.exit = value
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("line {}: bad exit status `{value}`", i + 1))?;
+                }
+                "expect-stdout" => {
+                    let stdout = fixture.stdout.get_or_insert_with(String::new);
+                    stdout.push_str(value);
+                    stdout.push('\n');
+                }
+                "expect-error" => fixture.error = Some(value.to_string()),
+                other => return Err(format!("line {}: unknown directive `{other}`", i + 1)),
+            }
+        }
+        fixture.source = source.join("\n");
+        Ok(fixture)
+    }
+
+    /// Preprocesses the fixture as the file at `path` and compiles it,
+    /// passing the artifacts to `output`, or gives the first error as the
+    /// driver reports it.
+    pub fn compile<T>(
+        &self,
+        path: &Path,
+        options: &Options,
+        output: impl FnOnce(Artifacts) -> T,
+    ) -> Result<T, String> {
+        let preprocessed = Preprocessor::new()
+            .run(path, &path.display().to_string(), &self.source)
+            .map_err(|err| err.error.render(&err.file))?;
+        match driver::compile(&preprocessed.source, options) {
+            Ok(artifacts) => Ok(output(artifacts)),
+            Err(err) => {
+                let err = preprocessed.locate(err);
+                Err(err.error.render(&err.file))
+            }
+        }
+    }
+
+    /// Holds how compiling the fixture went against its `expect-error`:
+    /// what was compiled if it is to be run, `None` if it failed as
+    /// expected, or how it fell short.
+    pub fn expect<T>(&self, compiled: Result<T, String>) -> Result<Option<T>, String> {
+        match (compiled, &self.error) {
+            (Ok(compiled), None) => Ok(Some(compiled)),
+            (Ok(_), Some(expected)) => Err(format!(
+                "expected an error containing `{expected}`, but it compiled"
+            )),
+            (Err(rendered), Some(expected)) if rendered.contains(expected.as_str()) => Ok(None),
+            (Err(rendered), Some(expected)) => Err(format!(
+                "expected an error containing `{expected}`, got `{rendered}`"
+            )),
+            (Err(rendered), None) => Err(format!("compilation failed: {rendered}")),
+        }
+    }
+
+    /// Compiles, links and runs the fixture at `path`, describing the first
+    /// way it fell short of its expectations.
+    pub fn check(&self, path: &Path, options: &Options) -> Result<(), String> {
+        let compiled = self.compile(path, options, |artifacts| artifacts.assembly);
+        let Some(assembly) = self.expect(compiled)? else {
+            return Ok(());
+        };
+
+        let execution = Toolchain::new(options.target).run_assembly(path, &assembly)?;
+        let (status, stdout) = (execution.status, execution.stdout);
+        if status != Some(self.exit) {
+            let status = status.map_or("a signal".to_string(), |code| code.to_string());
+            return Err(format!("expected exit {}, got {status}", self.exit));
+        }
+        match &self.stdout {
+            Some(expected) if *expected != stdout => {
+                Err(format!("expected stdout {expected:?}, got {stdout:?}"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,24 @@
+//! Entry points for fuzzing the front end, used by the targets in `fuzz/`.
+//!
+//! Each takes arbitrary bytes and must return normally whatever they are:
+//! malformed input is an error for the user, never a panic or a stack
+//! overflow. Bytes that aren't UTF-8 are replaced rather than rejected, so
+//! that the lexer still sees the rest of the input.
+
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+pub fn fuzz_lex(data: &[u8]) {
+    let source = String::from_utf8_lossy(data);
+    let _ = Lexer::new(&source).lex();
+}
+
+/// Lexes and parses `data`, both stopping at the first error and
+/// recovering from errors.
+pub fn fuzz_parse(data: &[u8]) {
+    let source = String::from_utf8_lossy(data);
+    if let Ok(tokens) = Lexer::new(&source).lex() {
+        let _ = Parser::new(tokens.clone()).parse();
+        let _ = Parser::new(tokens).parse_recovering();
+    }
+}
@@ -0,0 +1,54 @@
+//! A header declaring what a translation unit defines (`--emit=header`),
+//! so other translation units can call into it.
+//!
+//! Every function defined but `main` gets a prototype, in the order
+//! defined, inside an include guard named after the source file.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::ast::Program;
+
+/// The header for `program`, compiled from `file`.
+pub fn emit(program: &Program, file: &str) -> String {
+    let guard = guard(file);
+    let mut out =
+        format!("/* Generated by rcc from {file}. */\n#ifndef {guard}\n#define {guard}\n\n");
+    for function in &program.functions {
+        if function.body.is_some() && function.name != "main" {
+            writeln!(
+                out,
+                "{} {}{};",
+                function.return_type,
+                function.name,
+                function.param_list()
+            )
+            .unwrap();
+        }
+    }
+    writeln!(out, "\n#endif /* {guard} */").unwrap();
+    out
+}
+
+/// The include guard for the header of `file`: its name in capitals, with
+/// anything that can't be in an identifier replaced by `_`.
+fn guard(file: &str) -> String {
+    let stem = Path::new(file)
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let mut guard: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if guard.starts_with(|c: char| c.is_ascii_digit()) {
+        guard.insert(0, '_');
+    }
+    guard + "_H"
+}
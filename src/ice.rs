@@ -0,0 +1,216 @@
+//! Internal compiler errors: what rcc reports when it panics.
+//!
+//! The stages record what they are working on in a per-thread context,
+//! which the panic hook from [`install`] prints along with the panic, so a
+//! report says where the compiler was. The caller of the driver then writes
+//! the compiler's state for the input with [`dump`], to attach to the bug.
+//!
+//! Setting `RCC_DEBUG_CRASH` to a stage's name makes that stage panic, to
+//! exercise all of this.
+
+use std::any::Any;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::cell::{Cell, RefCell};
+use std::fmt::{self, Write};
+use std::panic::{self, AssertUnwindSafe, PanicHookInfo};
+
+use crate::analyzer::Analyzer;
+use crate::driver::Options;
+use crate::ir::lower;
+use crate::lexer::{Lexer, Position};
+use crate::opt::PassManager;
+use crate::parser::Parser;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// What the compiler is working on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Context {
+    pub file: Option<String>,
+    pub stage: Option<&'static str>,
+    pub function: Option<String>,
+    /// The statement or expression being processed.
+    pub pos: Option<Position>,
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.stage {
+            Some(stage) => write!(f, "in stage `{stage}`")?,
+            None => f.write_str("outside any stage")?,
+        }
+        if let Some(file) = &self.file {
+            write!(f, " of `{file}`")?;
+        }
+        if let Some(function) = &self.function {
+            write!(f, ", in function `{function}`")?;
+        }
+        if let Some(pos) = self.pos {
+            write!(f, " near {pos}")?;
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Context> = RefCell::default();
+    /// Set while [`dump`] reruns the stages, whose panics it reports itself.
+    static QUIET: Cell<bool> = const { Cell::new(false) };
+}
+
+/// What this thread is working on.
+pub fn context() -> Context {
+    CONTEXT.with(|context| context.borrow().clone())
+}
+
+/// Starts on a new input, forgetting the last one.
+pub fn set_file(file: &str) {
+    CONTEXT.with(|context| {
+        *context.borrow_mut() = Context {
+            file: Some(file.to_string()),
+            ..Context::default()
+        }
+    });
+}
+
+/// Starts `stage`, panicking if `RCC_DEBUG_CRASH` names it.
+pub fn enter_stage(stage: &'static str) {
+    CONTEXT.with(|context| {
+        let mut context = context.borrow_mut();
+        context.stage = Some(stage);
+        context.function = None;
+        context.pos = None;
+    });
+    if std::env::var("RCC_DEBUG_CRASH").is_ok_and(|crash| crash == stage) {
+        panic!("crashing in stage `{stage}` because RCC_DEBUG_CRASH is set");
+    }
+}
+
+pub fn set_function(name: &str) {
+    CONTEXT.with(|context| {
+        let mut context = context.borrow_mut();
+        context.function = Some(name.to_string());
+        context.pos = None;
+    });
+}
+
+pub fn set_pos(pos: Position) {
+    CONTEXT.with(|context| context.borrow_mut().pos = Some(pos));
+}
+
+/// Replaces the default panic message with an internal compiler error
+/// report.
+pub fn install() {
+    panic::set_hook(Box::new(|info| {
+        if !QUIET.get() {
+            eprint!("{}", report(info));
+        }
+    }));
+}
+
+fn report(info: &PanicHookInfo) -> String {
+    let mut out = format!(
+        "rcc: internal compiler error: {}\n",
+        message(info.payload())
+    );
+    if let Some(location) = info.location() {
+        writeln!(out, "note: panicked at {location}").unwrap();
+    }
+    writeln!(out, "note: rcc {VERSION} was {}", context()).unwrap();
+    let backtrace = Backtrace::capture();
+    if backtrace.status() == BacktraceStatus::Captured {
+        writeln!(out, "backtrace:\n{backtrace}").unwrap();
+    }
+    out.push_str("note: this is a bug in rcc; please report it\n");
+    out
+}
+
+/// The message a panic was raised with.
+pub fn message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+/// The compiler's state for `source`: the options, the source, and each
+/// stage's output up to the one that panics again.
+pub fn dump(source: &str, options: &Options) -> String {
+    let mut out = format!("rcc {VERSION} internal compiler error\n");
+    writeln!(out, "\n== context ==\n{}", context()).unwrap();
+    writeln!(out, "\n== options ==\n{options:#?}").unwrap();
+    writeln!(out, "\n== source ==\n{}", source.trim_end()).unwrap();
+    let standard = options.standard;
+    let Some(program) = section(&mut out, "ast", |out| {
+        let program = Lexer::new(source)
+            .with_standard(standard)
+            .lex()
+            .and_then(|tokens| Parser::new(tokens).with_standard(standard).parse());
+        match program {
+            Ok(program) => {
+                let _ = write!(out, "{program:#?}");
+                Some(program)
+            }
+            Err(err) => {
+                let _ = write!(out, "error: {err}");
+                None
+            }
+        }
+    })
+    .flatten() else {
+        return out;
+    };
+    let analyzed = section(&mut out, "analysis", |out| {
+        match Analyzer::new()
+            .with_standard(standard)
+            .with_data_model(options.target.data_model())
+            .analyze(&program)
+        {
+            Ok(()) => out.push_str("ok"),
+            Err(err) => {
+                let _ = write!(out, "error: {err}");
+                return false;
+            }
+        }
+        true
+    });
+    if analyzed != Some(true) {
+        return out;
+    }
+    let Some(mut ir) = section(&mut out, "ir", |out| {
+        let ir = lower::lower_program(&program, options.target.data_model());
+        let _ = write!(out, "{}", ir.to_string().trim_end());
+        ir
+    }) else {
+        return out;
+    };
+    section(&mut out, "optimized ir", |out| {
+        PassManager::for_level(options.opt_level).run_ir(&mut ir);
+        let _ = write!(out, "{}", ir.to_string().trim_end());
+    });
+    out
+}
+
+/// Writes a section headed `name` with what `f` writes, or the message it
+/// panics with.
+fn section<T>(out: &mut String, name: &str, f: impl FnOnce(&mut String) -> T) -> Option<T> {
+    writeln!(out, "\n== {name} ==").unwrap();
+    let mut text = String::new();
+    let quiet = QUIET.replace(true);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| f(&mut text)));
+    QUIET.set(quiet);
+    out.push_str(&text);
+    let result = result
+        .map_err(|payload| {
+            if !text.is_empty() {
+                out.push('\n');
+            }
+            write!(out, "panicked: {}", message(payload.as_ref())).unwrap();
+        })
+        .ok();
+    out.push('\n');
+    result
+}
@@ -0,0 +1,170 @@
+//! Control-flow graph analyses: predecessors, dominators and natural loops.
+
+use std::collections::BTreeSet;
+
+use crate::ir::{BlockId, Function};
+
+pub fn predecessors(func: &Function) -> Vec<Vec<BlockId>> {
+    let mut preds = vec![Vec::new(); func.blocks.len()];
+    for (id, block) in func.block_ids().zip(&func.blocks) {
+        for succ in block.term.successors() {
+            if !preds[succ.index()].contains(&id) {
+                preds[succ.index()].push(id);
+            }
+        }
+    }
+    preds
+}
+
+/// Blocks reachable from the entry, in reverse postorder.
+pub fn reverse_postorder(func: &Function) -> Vec<BlockId> {
+    let mut visited = vec![false; func.blocks.len()];
+    let mut postorder = Vec::with_capacity(func.blocks.len());
+    // Explicit stack of (block, next successor index) to avoid recursion on deep CFGs.
+    let mut stack = vec![(BlockId::ENTRY, 0)];
+    visited[0] = true;
+    while let Some((block, next)) = stack.last_mut() {
+        let succs = func.block(*block).term.successors();
+        if let Some(&succ) = succs.get(*next) {
+            *next += 1;
+            if !std::mem::replace(&mut visited[succ.index()], true) {
+                stack.push((succ, 0));
+            }
+        } else {
+            postorder.push(*block);
+            stack.pop();
+        }
+    }
+    postorder.reverse();
+    postorder
+}
+
+/// The dominator tree, computed with the Cooper–Harvey–Kennedy algorithm.
+#[derive(Debug, Clone)]
+pub struct Dominators {
+    /// Immediate dominator of each block; `None` for the entry and unreachable blocks.
+    idom: Vec<Option<BlockId>>,
+    reachable: Vec<bool>,
+}
+
+impl Dominators {
+    pub fn compute(func: &Function) -> Self {
+        let rpo = reverse_postorder(func);
+        let preds = predecessors(func);
+        let mut order = vec![usize::MAX; func.blocks.len()];
+        for (i, block) in rpo.iter().enumerate() {
+            order[block.index()] = i;
+        }
+
+        let mut idom: Vec<Option<BlockId>> = vec![None; func.blocks.len()];
+        idom[0] = Some(BlockId::ENTRY);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in rpo.iter().skip(1) {
+                let mut processed = preds[block.index()]
+                    .iter()
+                    .copied()
+                    .filter(|p| idom[p.index()].is_some());
+                let Some(first) = processed.next() else {
+                    continue;
+                };
+                let new_idom = processed.fold(first, |a, b| intersect(&idom, &order, a, b));
+                if idom[block.index()] != Some(new_idom) {
+                    idom[block.index()] = Some(new_idom);
+                    changed = true;
+                }
+            }
+        }
+
+        let reachable = idom.iter().map(Option::is_some).collect();
+        idom[0] = None;
+        Self { idom, reachable }
+    }
+
+    pub fn idom(&self, block: BlockId) -> Option<BlockId> {
+        self.idom[block.index()]
+    }
+
+    pub fn is_reachable(&self, block: BlockId) -> bool {
+        self.reachable[block.index()]
+    }
+
+    /// Whether every path from the entry to `b` passes through `a`.
+    pub fn dominates(&self, a: BlockId, mut b: BlockId) -> bool {
+        if !self.is_reachable(b) {
+            return false;
+        }
+        loop {
+            if a == b {
+                return true;
+            }
+            match self.idom(b) {
+                Some(parent) => b = parent,
+                None => return false,
+            }
+        }
+    }
+}
+
+fn intersect(idom: &[Option<BlockId>], order: &[usize], mut a: BlockId, mut b: BlockId) -> BlockId {
+    while a != b {
+        while order[a.index()] > order[b.index()] {
+            a = idom[a.index()].expect("processed blocks have an idom");
+        }
+        while order[b.index()] > order[a.index()] {
+            b = idom[b.index()].expect("processed blocks have an idom");
+        }
+    }
+    a
+}
+
+/// A natural loop: the header plus every block that can reach a back edge
+/// into the header without passing through it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loop {
+    pub header: BlockId,
+    pub blocks: BTreeSet<BlockId>,
+}
+
+impl Loop {
+    pub fn contains(&self, block: BlockId) -> bool {
+        self.blocks.contains(&block)
+    }
+}
+
+/// Finds the natural loops of `func`, innermost (smallest) first. Back edges
+/// sharing a header are merged into one loop.
+pub fn natural_loops(func: &Function, doms: &Dominators) -> Vec<Loop> {
+    let preds = predecessors(func);
+    let mut loops: Vec<Loop> = Vec::new();
+    for (latch, block) in func.block_ids().zip(&func.blocks) {
+        if !doms.is_reachable(latch) {
+            continue;
+        }
+        for header in block.term.successors() {
+            if !doms.dominates(header, latch) {
+                continue;
+            }
+            let index = match loops.iter().position(|l| l.header == header) {
+                Some(index) => index,
+                None => {
+                    loops.push(Loop {
+                        header,
+                        blocks: BTreeSet::from([header]),
+                    });
+                    loops.len() - 1
+                }
+            };
+            let body = &mut loops[index].blocks;
+            let mut worklist = vec![latch];
+            while let Some(block) = worklist.pop() {
+                if body.insert(block) {
+                    worklist.extend(preds[block.index()].iter().copied());
+                }
+            }
+        }
+    }
+    loops.sort_by_key(|l| l.blocks.len());
+    loops
+}
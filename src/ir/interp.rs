@@ -0,0 +1,370 @@
+//! A direct interpreter for the IR.
+//!
+//! Used to evaluate code at compile time and as a reference for what a
+//! program should compute, independent of any backend. Execution is bounded
+//! by a fuel budget and a call depth limit so evaluation always terminates.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ir::{BlockId, Datum, Function, Instr, IrType, Module, Terminator, Value};
+
+/// Instructions an interpreter may execute before giving up.
+pub const DEFAULT_FUEL: u64 = 1_000_000;
+
+/// Nested calls allowed before assuming runaway recursion.
+const MAX_CALL_DEPTH: usize = 1024;
+
+/// Where the pool strings are laid out, one after another.
+const STRINGS_BASE: i64 = 0x1000_0000;
+
+/// Likewise for the module's global variables.
+const GLOBALS_BASE: i64 = 0x2000_0000;
+
+/// The top of the stack that frames and dynamic allocations are carved
+/// from.
+const STACK_TOP: i64 = 0x7000_0000;
+
+/// Why execution stopped before returning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trap {
+    /// Division by zero, overflowing division, an oversized shift, a read
+    /// of uninitialized memory or a dereference of a null pointer.
+    UndefinedBehavior(String),
+    /// A call to a function with no body in the module.
+    UnknownFunction(String),
+    /// The address of a global the module does not define.
+    UnknownGlobal(String),
+    /// Control reached a `trap` terminator.
+    Abort,
+    /// Control reached an `unreachable` terminator.
+    Unreachable,
+    /// Inline assembly, which only the target can run.
+    InlineAsm,
+    OutOfFuel,
+    StackOverflow,
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Trap::UndefinedBehavior(what) => write!(f, "undefined behavior: {what}"),
+            Trap::UnknownFunction(name) => write!(f, "call to unknown function `{name}`"),
+            Trap::UnknownGlobal(name) => write!(f, "reference to unknown global `{name}`"),
+            Trap::Abort => f.write_str("trapped"),
+            Trap::Unreachable => f.write_str("reached unreachable code"),
+            Trap::InlineAsm => f.write_str("cannot interpret inline assembly"),
+            Trap::OutOfFuel => f.write_str("evaluation took too long"),
+            Trap::StackOverflow => f.write_str("call stack overflow"),
+        }
+    }
+}
+
+pub struct Interpreter<'a> {
+    functions: HashMap<&'a str, &'a Function>,
+    /// The address of each pool string, by id.
+    strings: Vec<i64>,
+    /// The address of each global in the module.
+    globals: HashMap<&'a str, i64>,
+    /// Every byte written so far; any other address reads as
+    /// uninitialized.
+    memory: HashMap<i64, u8>,
+    /// The lowest address handed out to a frame or dynamic allocation so
+    /// far.
+    stack_pointer: i64,
+    fuel: u64,
+}
+
+/// An activation of a function. Calls push frames onto an explicit stack, so
+/// deep recursion in the interpreted program cannot overflow the host stack.
+struct Frame<'a> {
+    func: &'a Function,
+    values: Vec<Option<i64>>,
+    /// The address of each stack slot.
+    slots: Vec<i64>,
+    block: BlockId,
+    /// Index of the next instruction to execute in `block`.
+    next: usize,
+    /// The interpreter's stack pointer on entry, restored on return to
+    /// release the frame's dynamic allocations.
+    stack_pointer: i64,
+}
+
+impl<'a> Frame<'a> {
+    fn get(&self, value: Value) -> i64 {
+        self.values[value.0 as usize].expect("verified IR defines values before use")
+    }
+
+    fn set(&mut self, value: Value, result: i64) {
+        self.values[value.0 as usize] = Some(result);
+    }
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(module: &'a Module) -> Self {
+        let mut interpreter = Self {
+            functions: module
+                .functions
+                .iter()
+                .map(|f| (f.name.as_str(), f))
+                .collect(),
+            strings: Vec::new(),
+            globals: HashMap::new(),
+            memory: HashMap::new(),
+            stack_pointer: STACK_TOP,
+            fuel: DEFAULT_FUEL,
+        };
+        let mut next = STRINGS_BASE;
+        for (_, bytes) in module.strings.iter() {
+            interpreter.strings.push(next);
+            for &byte in bytes.iter().chain(&[0]) {
+                interpreter.memory.insert(next, byte);
+                next += 1;
+            }
+        }
+        let mut next = GLOBALS_BASE;
+        for global in &module.globals {
+            let align = i64::from(global.ty.bytes());
+            next = (next + align - 1) & -align;
+            interpreter.globals.insert(&global.name, next);
+            next += i64::from(global.size());
+        }
+        // Every address is known once they all are, so a global may point
+        // at one defined after it. One defined elsewhere has no address,
+        // and the element pointing at it is left uninitialized.
+        for global in &module.globals {
+            let base = interpreter.globals[global.name.as_str()];
+            let elements = (0..global.len).map(|i| base + i64::from(i * global.ty.bytes()));
+            let zero = Datum::Int(0);
+            let init = global.init.iter().chain(std::iter::repeat(&zero));
+            for (address, datum) in elements.zip(init) {
+                let value = match datum {
+                    Datum::Int(value) => Some(*value),
+                    Datum::String { string, offset } => {
+                        Some(interpreter.strings[string.0 as usize] + offset)
+                    }
+                    Datum::Global { name, offset } => interpreter
+                        .globals
+                        .get(name.as_str())
+                        .map(|address| address + offset),
+                };
+                if let Some(value) = value {
+                    interpreter.write(address, global.ty, value);
+                }
+            }
+        }
+        interpreter
+    }
+
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = fuel;
+        self
+    }
+
+    /// Calls `name` with `args`, returning its result (`None` for `void`).
+    pub fn call(&mut self, name: &str, args: &[i64]) -> Result<Option<i64>, Trap> {
+        let mut stack = vec![self.enter(self.function(name)?, args)?];
+        loop {
+            self.fuel = self.fuel.checked_sub(1).ok_or(Trap::OutOfFuel)?;
+            let depth = stack.len();
+            let frame = stack.last_mut().expect("the stack is never empty here");
+            let block = frame.func.block(frame.block);
+            let Some(instr) = block.instrs.get(frame.next) else {
+                let returned = match &block.term {
+                    Terminator::Ret(value) => value.map(|v| frame.get(v)),
+                    &Terminator::Jump(target) => {
+                        frame.block = target;
+                        frame.next = 0;
+                        continue;
+                    }
+                    &Terminator::Branch {
+                        cond,
+                        then_block,
+                        else_block,
+                    } => {
+                        frame.block = if frame.get(cond) != 0 {
+                            then_block
+                        } else {
+                            else_block
+                        };
+                        frame.next = 0;
+                        continue;
+                    }
+                    Terminator::Switch {
+                        value,
+                        cases,
+                        default,
+                    } => {
+                        let value = frame.get(*value);
+                        frame.block = cases
+                            .iter()
+                            .find(|&&(case, _)| case == value)
+                            .map_or(*default, |&(_, target)| target);
+                        frame.next = 0;
+                        continue;
+                    }
+                    Terminator::Trap => return Err(Trap::Abort),
+                    Terminator::Unreachable => return Err(Trap::Unreachable),
+                };
+                let returning = stack.pop().expect("the stack is never empty here");
+                self.stack_pointer = returning.stack_pointer;
+                let Some(caller) = stack.last_mut() else {
+                    return Ok(returned);
+                };
+                // The caller's current instruction is the call that just returned.
+                let call = &caller.func.block(caller.block).instrs[caller.next];
+                if let (Some(dst), Some(result)) = (call.dst(), returned) {
+                    caller.set(dst, result);
+                }
+                caller.next += 1;
+                continue;
+            };
+
+            match instr {
+                Instr::Const { dst, value } => frame.set(*dst, *value),
+                Instr::Binary { dst, op, lhs, rhs } => {
+                    let (l, r) = (frame.get(*lhs), frame.get(*rhs));
+                    let result = op
+                        .evaluate(frame.func.value_type(*dst), l, r)
+                        .ok_or_else(|| Trap::UndefinedBehavior(format!("{op} {l}, {r}")))?;
+                    frame.set(*dst, result);
+                }
+                Instr::Cmp { dst, op, lhs, rhs } => {
+                    let ty = frame.func.value_type(*lhs);
+                    let result = op.evaluate(ty, frame.get(*lhs), frame.get(*rhs));
+                    frame.set(*dst, i64::from(result));
+                }
+                Instr::Cast { dst, op, value } => {
+                    let (from, to) = (frame.func.value_type(*value), frame.func.value_type(*dst));
+                    frame.set(*dst, op.evaluate(from, to, frame.get(*value)));
+                }
+                Instr::Load { dst, slot } => {
+                    let ty = frame.func.value_type(*dst);
+                    let value = self.read(frame.slots[slot.0 as usize], ty).ok_or_else(|| {
+                        Trap::UndefinedBehavior(format!("read of uninitialized {slot}"))
+                    })?;
+                    frame.set(*dst, value);
+                }
+                Instr::Store { slot, value } => {
+                    let ty = frame.func.value_type(*value);
+                    self.write(frame.slots[slot.0 as usize], ty, frame.get(*value));
+                }
+                Instr::LoadPtr { dst, ptr } => {
+                    let (ty, address) = (frame.func.value_type(*dst), frame.get(*ptr));
+                    check_address(address)?;
+                    let value = self.read(address, ty).ok_or_else(|| {
+                        Trap::UndefinedBehavior(format!(
+                            "read of uninitialized memory at {address:#x}"
+                        ))
+                    })?;
+                    frame.set(*dst, value);
+                }
+                Instr::StorePtr { ptr, value } => {
+                    let (ty, address) = (frame.func.value_type(*value), frame.get(*ptr));
+                    check_address(address)?;
+                    self.write(address, ty, frame.get(*value));
+                }
+                Instr::SlotAddr { dst, slot } => frame.set(*dst, frame.slots[slot.0 as usize]),
+                Instr::StringAddr { dst, string } => {
+                    frame.set(*dst, self.strings[string.0 as usize]);
+                }
+                Instr::Alloca { dst, size } => {
+                    let size = frame.get(*size);
+                    if size < 0 {
+                        return Err(Trap::UndefinedBehavior(format!("alloca of {size} bytes")));
+                    }
+                    self.stack_pointer = (self.stack_pointer - size) & !15;
+                    if self.stack_pointer < GLOBALS_BASE {
+                        return Err(Trap::StackOverflow);
+                    }
+                    frame.set(*dst, self.stack_pointer);
+                }
+                // There is only the one thread.
+                Instr::GlobalAddr { dst, name, .. } => {
+                    let address = self.globals.get(name.as_str()).copied();
+                    let address = address.ok_or_else(|| Trap::UnknownGlobal(name.clone()))?;
+                    frame.set(*dst, address);
+                }
+                Instr::Loc { .. } => {}
+                Instr::InlineAsm { .. } => return Err(Trap::InlineAsm),
+                Instr::Call { callee, args, .. } => {
+                    if depth == MAX_CALL_DEPTH {
+                        return Err(Trap::StackOverflow);
+                    }
+                    let args: Vec<i64> = args.iter().map(|&a| frame.get(a)).collect();
+                    let frame = self.enter(self.function(callee)?, &args)?;
+                    stack.push(frame);
+                    // Resume after the call once the callee returns.
+                    continue;
+                }
+            }
+            frame.next += 1;
+        }
+    }
+
+    /// A frame for a call to `func`, with its stack slots carved from the
+    /// stack. Until stored to, the slots read as uninitialized even if an
+    /// earlier frame used the same memory.
+    fn enter(&mut self, func: &'a Function, args: &[i64]) -> Result<Frame<'a>, Trap> {
+        let stack_pointer = self.stack_pointer;
+        let mut values = vec![None; func.value_types.len()];
+        for (&param, &arg) in func.params.iter().zip(args) {
+            values[param.0 as usize] = Some(func.value_type(param).normalize(arg));
+        }
+        let mut slots = Vec::with_capacity(func.slots.len());
+        for slot in &func.slots {
+            let size = i64::from(slot.size());
+            self.stack_pointer = (self.stack_pointer - size) & -i64::from(slot.ty.bytes());
+            if self.stack_pointer < GLOBALS_BASE {
+                return Err(Trap::StackOverflow);
+            }
+            for address in self.stack_pointer..self.stack_pointer + size {
+                self.memory.remove(&address);
+            }
+            slots.push(self.stack_pointer);
+        }
+        Ok(Frame {
+            func,
+            values,
+            slots,
+            block: BlockId::ENTRY,
+            next: 0,
+            stack_pointer,
+        })
+    }
+
+    /// The `ty` stored at `address`, or `None` if any of its bytes is
+    /// uninitialized.
+    fn read(&self, address: i64, ty: IrType) -> Option<i64> {
+        let mut value = 0u64;
+        for offset in (0..i64::from(ty.bytes())).rev() {
+            value = value << 8 | u64::from(*self.memory.get(&(address + offset))?);
+        }
+        Some(ty.normalize(value as i64))
+    }
+
+    /// Stores `value` at `address` as a `ty`, least significant byte first.
+    fn write(&mut self, address: i64, ty: IrType, value: i64) {
+        for offset in 0..i64::from(ty.bytes()) {
+            self.memory
+                .insert(address + offset, (value >> (8 * offset)) as u8);
+        }
+    }
+
+    fn function(&self, name: &str) -> Result<&'a Function, Trap> {
+        self.functions
+            .get(name)
+            .copied()
+            .ok_or_else(|| Trap::UnknownFunction(name.to_string()))
+    }
+}
+
+/// Traps on dereferencing a null pointer, which nothing is stored at.
+fn check_address(address: i64) -> Result<(), Trap> {
+    if address == 0 {
+        return Err(Trap::UndefinedBehavior(
+            "dereference of a null pointer".to_string(),
+        ));
+    }
+    Ok(())
+}
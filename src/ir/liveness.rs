@@ -0,0 +1,69 @@
+//! Backward dataflow liveness analysis over the IR.
+
+use std::collections::BTreeSet;
+
+use crate::ir::{BlockId, Function, Value};
+
+/// The values live on entry to and exit from each block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Liveness {
+    pub live_in: Vec<BTreeSet<Value>>,
+    pub live_out: Vec<BTreeSet<Value>>,
+}
+
+impl Liveness {
+    pub fn compute(func: &Function) -> Self {
+        let n = func.blocks.len();
+        // Upward-exposed uses and definitions of each block.
+        let mut uses = vec![BTreeSet::new(); n];
+        let mut defs = vec![BTreeSet::new(); n];
+        for (i, block) in func.blocks.iter().enumerate() {
+            for instr in &block.instrs {
+                for operand in instr.operands() {
+                    if !defs[i].contains(&operand) {
+                        uses[i].insert(operand);
+                    }
+                }
+                defs[i].extend(instr.dst());
+            }
+            for operand in block.term.operands() {
+                if !defs[i].contains(&operand) {
+                    uses[i].insert(operand);
+                }
+            }
+        }
+
+        let mut live_in = vec![BTreeSet::new(); n];
+        let mut live_out: Vec<BTreeSet<Value>> = vec![BTreeSet::new(); n];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for id in func.block_ids().rev() {
+                let i = id.index();
+                let out: BTreeSet<Value> = func
+                    .block(id)
+                    .term
+                    .successors()
+                    .iter()
+                    .flat_map(|s| live_in[s.index()].iter().copied())
+                    .collect();
+                let mut inn = uses[i].clone();
+                inn.extend(out.difference(&defs[i]).copied());
+                if inn != live_in[i] || out != live_out[i] {
+                    live_in[i] = inn;
+                    live_out[i] = out;
+                    changed = true;
+                }
+            }
+        }
+        Self { live_in, live_out }
+    }
+
+    pub fn is_live_in(&self, block: BlockId, value: Value) -> bool {
+        self.live_in[block.index()].contains(&value)
+    }
+
+    pub fn is_live_out(&self, block: BlockId, value: Value) -> bool {
+        self.live_out[block.index()].contains(&value)
+    }
+}
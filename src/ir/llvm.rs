@@ -0,0 +1,418 @@
+//! LLVM IR text for a module, used by `--emit=llvm`.
+//!
+//! The output is a `.ll` file that `clang`, `opt` and `llc` accept, for
+//! comparing against LLVM's optimizations or compiling for targets rcc has
+//! no backend for. Values keep their numbers as `%vN` and blocks their
+//! names; stack slots become `alloca`s in an `entry` block that branches to
+//! `bb0`, since LLVM's entry block cannot be a branch target. Constants and
+//! the addresses of globals and strings are written inline at each use.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::ir::{Datum, Function, Global, Instr, IrType, Module, Terminator, Value};
+
+/// The module as LLVM IR, with declarations for any functions it calls but
+/// does not define.
+pub fn emit(module: &Module) -> String {
+    let mut out = String::new();
+    for (id, bytes) in module.strings.iter() {
+        writeln!(
+            out,
+            "@.{id} = private unnamed_addr constant [{} x i8] c\"{}\\00\"",
+            bytes.len() + 1,
+            escape(bytes)
+        )
+        .unwrap();
+    }
+    for global in &module.globals {
+        let tls = if global.thread_local {
+            "thread_local "
+        } else {
+            ""
+        };
+        let (ty, init) = global_init(global);
+        writeln!(out, "@{} = {tls}global {ty} {init}", global.name).unwrap();
+    }
+    for func in &module.functions {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        function(&mut out, func);
+    }
+
+    let mut declared = Vec::new();
+    for global in &module.globals {
+        for datum in &global.init {
+            let Datum::Global { name, .. } = datum else {
+                continue;
+            };
+            let defined = module.globals.iter().any(|g| &g.name == name);
+            if !defined && !declared.contains(name) {
+                writeln!(out, "\n@{name} = external global i8").unwrap();
+                declared.push(name.clone());
+            }
+        }
+    }
+    for func in &module.functions {
+        for instr in func.blocks.iter().flat_map(|b| &b.instrs) {
+            // A global defined elsewhere. Only its address is used, so the
+            // type declared for it doesn't matter.
+            if let Instr::GlobalAddr {
+                name, thread_local, ..
+            } = instr
+            {
+                let defined = module.globals.iter().any(|g| &g.name == name);
+                if !defined && !declared.contains(name) {
+                    let tls = if *thread_local { "thread_local " } else { "" };
+                    writeln!(out, "\n@{name} = external {tls}global i8").unwrap();
+                    declared.push(name.clone());
+                }
+                continue;
+            }
+            let Instr::Call {
+                dst,
+                callee,
+                args,
+                fixed,
+            } = instr
+            else {
+                continue;
+            };
+            let defined = module.functions.iter().any(|f| &f.name == callee);
+            if defined || declared.contains(callee) {
+                continue;
+            }
+            writeln!(
+                out,
+                "\ndeclare {} @{callee}{}",
+                return_type(dst.map(|dst| func.value_type(dst))),
+                param_types(func, args, *fixed)
+            )
+            .unwrap();
+            declared.push(callee.clone());
+        }
+    }
+    let traps = module
+        .functions
+        .iter()
+        .flat_map(|func| &func.blocks)
+        .any(|block| block.term == Terminator::Trap);
+    if traps {
+        out.push_str("\ndeclare void @llvm.trap()\n");
+    }
+    out
+}
+
+fn return_type(ty: Option<IrType>) -> String {
+    ty.map_or_else(|| "void".to_string(), |ty| ty.to_string())
+}
+
+/// The parameter list of a function called with `args`, taking only the
+/// first `fixed` of them as named parameters if it is variadic.
+fn param_types(func: &Function, args: &[Value], fixed: Option<usize>) -> String {
+    let named = &args[..fixed.unwrap_or(args.len())];
+    let mut params: Vec<String> = named
+        .iter()
+        .map(|&arg| func.value_type(arg).to_string())
+        .collect();
+    if fixed.is_some() {
+        params.push("...".to_string());
+    }
+    format!("({})", params.join(", "))
+}
+
+/// The type of `global` and its initial value: every element, since LLVM
+/// takes no partial initializers.
+fn global_init(global: &Global) -> (String, String) {
+    let datum = |datum: &Datum| {
+        let (symbol, offset) = match datum {
+            Datum::Int(0) if global.ty == IrType::Ptr => return "null".to_string(),
+            Datum::Int(value) if global.ty == IrType::Ptr => {
+                return format!("inttoptr (i64 {value} to ptr)");
+            }
+            Datum::Int(value) => return value.to_string(),
+            Datum::String { string, offset } => (format!("@.{string}"), *offset),
+            Datum::Global { name, offset } => (format!("@{name}"), *offset),
+        };
+        match offset {
+            0 => symbol,
+            _ => format!("getelementptr (i8, ptr {symbol}, i64 {offset})"),
+        }
+    };
+    if global.len == 1 {
+        let init = global.init.first().unwrap_or(&Datum::Int(0));
+        return (global.ty.to_string(), datum(init));
+    }
+    let ty = format!("[{} x {}]", global.len, global.ty);
+    if global.is_zero() {
+        return (ty, "zeroinitializer".to_string());
+    }
+    let zero = Datum::Int(0);
+    let elements: Vec<String> = (0..global.len as usize)
+        .map(|i| {
+            format!(
+                "{} {}",
+                global.ty,
+                datum(global.init.get(i).unwrap_or(&zero))
+            )
+        })
+        .collect();
+    (ty, format!("[{}]", elements.join(", ")))
+}
+
+fn function(out: &mut String, func: &Function) {
+    let params: Vec<String> = func
+        .params
+        .iter()
+        .map(|&param| format!("{} %v{}", func.value_type(param), param.0))
+        .collect();
+    writeln!(
+        out,
+        "define {} @{}({}) {{",
+        return_type(func.return_type),
+        func.name,
+        params.join(", ")
+    )
+    .unwrap();
+    out.push_str("entry:\n");
+    for (i, ty) in func.slots.iter().enumerate() {
+        writeln!(out, "  %ss{i} = alloca {ty}").unwrap();
+    }
+    out.push_str("  br label %bb0\n");
+
+    let mut printer = Printer {
+        out,
+        func,
+        inline: HashMap::new(),
+    };
+    for instr in func.blocks.iter().flat_map(|b| &b.instrs) {
+        let (dst, text) = match instr {
+            Instr::Const { dst, value } => (dst, printer.constant(func.value_type(*dst), *value)),
+            Instr::StringAddr { dst, string } => (dst, format!("@.{string}")),
+            Instr::GlobalAddr { dst, name, .. } => (dst, format!("@{name}")),
+            Instr::SlotAddr { dst, slot } => (dst, format!("%ss{}", slot.0)),
+            _ => continue,
+        };
+        printer.inline.insert(*dst, text);
+    }
+    for (id, block) in func.block_ids().zip(&func.blocks) {
+        writeln!(printer.out, "{id}:").unwrap();
+        for instr in &block.instrs {
+            printer.instr(instr);
+        }
+        printer.terminator(&block.term, id.0);
+    }
+    printer.out.push_str("}\n");
+}
+
+struct Printer<'a> {
+    out: &'a mut String,
+    func: &'a Function,
+    /// Values written in place at their uses instead of being defined.
+    inline: HashMap<Value, String>,
+}
+
+impl Printer<'_> {
+    fn line(&mut self, text: String) {
+        writeln!(self.out, "  {text}").unwrap();
+    }
+
+    fn constant(&self, ty: IrType, value: i64) -> String {
+        match ty {
+            IrType::Ptr if value == 0 => "null".to_string(),
+            IrType::Ptr => format!("inttoptr (i64 {value} to ptr)"),
+            _ => ty.normalize(value).to_string(),
+        }
+    }
+
+    /// `value` as an operand, without its type.
+    fn value(&self, value: Value) -> String {
+        match self.inline.get(&value) {
+            Some(text) => text.clone(),
+            None => format!("%v{}", value.0),
+        }
+    }
+
+    /// `value` as an operand, preceded by its type.
+    fn typed(&self, value: Value) -> String {
+        format!("{} {}", self.func.value_type(value), self.value(value))
+    }
+
+    fn instr(&mut self, instr: &Instr) {
+        match instr {
+            Instr::Const { .. }
+            | Instr::StringAddr { .. }
+            | Instr::GlobalAddr { .. }
+            | Instr::SlotAddr { .. }
+            | Instr::Loc { .. } => {}
+            Instr::Binary { dst, op, lhs, rhs } => {
+                let (l, r) = (self.value(*lhs), self.value(*rhs));
+                let d = dst.0;
+                if self.func.value_type(*dst) == IrType::Ptr {
+                    // LLVM only does arithmetic on integers.
+                    self.line(format!("%v{d}.l = ptrtoint ptr {l} to i64"));
+                    self.line(format!("%v{d}.r = ptrtoint ptr {r} to i64"));
+                    self.line(format!("%v{d}.i = {op} i64 %v{d}.l, %v{d}.r"));
+                    self.line(format!("%v{d} = inttoptr i64 %v{d}.i to ptr"));
+                } else {
+                    let ty = self.func.value_type(*dst);
+                    self.line(format!("%v{d} = {op} {ty} {l}, {r}"));
+                }
+            }
+            Instr::Cmp { dst, op, lhs, rhs } => {
+                let d = dst.0;
+                let (l, r) = (self.typed(*lhs), self.value(*rhs));
+                self.line(format!("%v{d}.c = icmp {op} {l}, {r}"));
+                self.line(format!("%v{d} = zext i1 %v{d}.c to i32"));
+            }
+            Instr::Cast { dst, op, value } => {
+                let d = dst.0;
+                let (from, to) = (self.func.value_type(*value), self.func.value_type(*dst));
+                let src = self.value(*value);
+                match (from, to) {
+                    (IrType::Ptr, _) => self.line(format!("%v{d} = ptrtoint ptr {src} to {to}")),
+                    (_, IrType::Ptr) => {
+                        self.line(format!("%v{d}.i = {op} {from} {src} to i64"));
+                        self.line(format!("%v{d} = inttoptr i64 %v{d}.i to ptr"));
+                    }
+                    _ => self.line(format!("%v{d} = {op} {from} {src} to {to}")),
+                }
+            }
+            Instr::Load { dst, slot } => {
+                let ty = self.func.value_type(*dst);
+                self.line(format!("%v{} = load {ty}, ptr %ss{}", dst.0, slot.0));
+            }
+            Instr::Store { slot, value } => {
+                let value = self.typed(*value);
+                self.line(format!("store {value}, ptr %ss{}", slot.0));
+            }
+            Instr::LoadPtr { dst, ptr } => {
+                let ty = self.func.value_type(*dst);
+                let ptr = self.value(*ptr);
+                self.line(format!("%v{} = load {ty}, ptr {ptr}", dst.0));
+            }
+            Instr::StorePtr { ptr, value } => {
+                let (value, ptr) = (self.typed(*value), self.value(*ptr));
+                self.line(format!("store {value}, ptr {ptr}"));
+            }
+
+            Instr::Alloca { dst, size } => {
+                let size = self.typed(*size);
+                self.line(format!("%v{} = alloca i8, {size}, align 16", dst.0));
+            }
+            // A call to a variadic function spells out the function's type.
+            Instr::Call {
+                dst,
+                callee,
+                args,
+                fixed,
+            } => {
+                let ty = return_type(dst.map(|dst| self.func.value_type(dst)));
+                let ty = match fixed {
+                    Some(_) => format!("{ty} {}", param_types(self.func, args, *fixed)),
+                    None => ty.to_string(),
+                };
+                let args: Vec<String> = args.iter().map(|&arg| self.typed(arg)).collect();
+                let call = format!("call {ty} @{callee}({})", args.join(", "));
+                match dst {
+                    Some(dst) => self.line(format!("%v{} = {call}", dst.0)),
+                    None => self.line(call),
+                }
+            }
+            Instr::InlineAsm {
+                dst,
+                template,
+                inputs,
+            } => {
+                let mut constraints: Vec<&str> = dst.iter().map(|_| "=r").collect();
+                constraints.extend(inputs.iter().map(|_| "r"));
+                let args: Vec<String> = inputs.iter().map(|&arg| self.typed(arg)).collect();
+                let ty = return_type(dst.map(|dst| self.func.value_type(dst)));
+                let call = format!(
+                    "call {ty} asm sideeffect \"{}\", \"{}\"({})",
+                    escape(asm_template(template).as_bytes()),
+                    constraints.join(","),
+                    args.join(", ")
+                );
+                match dst {
+                    Some(dst) => self.line(format!("%v{} = {call}", dst.0)),
+                    None => self.line(call),
+                }
+            }
+        }
+    }
+
+    fn terminator(&mut self, term: &Terminator, block: u32) {
+        match term {
+            Terminator::Ret(None) => self.line("ret void".to_string()),
+            Terminator::Ret(Some(value)) => {
+                let value = self.typed(*value);
+                self.line(format!("ret {value}"));
+            }
+            Terminator::Jump(target) => self.line(format!("br label %{target}")),
+            Terminator::Branch {
+                cond,
+                then_block,
+                else_block,
+            } => {
+                let zero = self.constant(self.func.value_type(*cond), 0);
+                let cond = self.typed(*cond);
+                self.line(format!("%bb{block}.cond = icmp ne {cond}, {zero}"));
+                self.line(format!(
+                    "br i1 %bb{block}.cond, label %{then_block}, label %{else_block}"
+                ));
+            }
+            Terminator::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                let ty = self.func.value_type(*value);
+                let mut text = format!("switch {}, label %{default} [", self.typed(*value));
+                for (case, target) in cases {
+                    write!(text, " {ty} {}, label %{target}", ty.normalize(*case)).unwrap();
+                }
+                text.push_str(" ]");
+                self.line(text);
+            }
+            Terminator::Trap => {
+                self.line("call void @llvm.trap()".to_string());
+                self.line("unreachable".to_string());
+            }
+            Terminator::Unreachable => self.line("unreachable".to_string()),
+        }
+    }
+}
+
+/// An inline assembly template in LLVM's syntax, where operands are `$N`
+/// and a literal `$` is `$$`, rather than `%N` and `%%`.
+fn asm_template(template: &str) -> String {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '$' => out.push_str("$$"),
+            '%' if chars.peek() == Some(&'%') => {
+                chars.next();
+                out.push('%');
+            }
+            '%' if chars.peek().is_some_and(char::is_ascii_digit) => out.push('$'),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// `bytes` for an LLVM string constant, with anything other than printable
+/// ASCII, `"` and `\` written as a hex escape.
+fn escape(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for &byte in bytes {
+        match byte {
+            b' '..=b'~' if byte != b'"' && byte != b'\\' => out.push(char::from(byte)),
+            _ => write!(out, "\\{byte:02X}").unwrap(),
+        }
+    }
+    out
+}
@@ -0,0 +1,1218 @@
+//! Lowering from the analyzed AST to the IR.
+
+use std::collections::HashMap;
+
+use crate::ast::{
+    Base, BinaryOp, Builtin, DataModel, Enum, Expression, Function, Global, Initializer, Program,
+    Statement, StaticValue, Type, UnaryOp,
+};
+use crate::ice;
+use crate::ir::{
+    BinOp, BlockId, CastOp, CmpOp, Datum, FunctionBuilder, IrType, Module, StackSlot, StringPool,
+    Value,
+};
+use crate::lexer::Position;
+
+/// Lowers `program` for a target with the data model `model`, which the
+/// analyzer checked it under.
+pub fn lower_program(program: &Program, model: DataModel) -> Module {
+    lower(program, model, false)
+}
+
+/// Like [`lower_program`], but records where each function, statement and
+/// call is in the source so the code generator can emit debug info.
+pub fn lower_program_with_debug_info(program: &Program, model: DataModel) -> Module {
+    lower(program, model, true)
+}
+
+fn lower(program: &Program, model: DataModel, debug_info: bool) -> Module {
+    // Arguments are converted to the parameter types of a declaration
+    // that gives them, if any does.
+    let mut signatures: HashMap<&str, &Function> = HashMap::new();
+    for function in &program.functions {
+        match signatures.get(function.name.as_str()) {
+            Some(previous) if previous.prototyped && !function.prototyped => {}
+            _ => {
+                signatures.insert(&function.name, function);
+            }
+        }
+    }
+
+    let mut constants = HashMap::new();
+    for decl in &program.enums {
+        declare_constants(&mut constants, decl, model);
+    }
+
+    // A definition takes the place of the `extern` declarations of the
+    // same variable, and one with an initializer that of the tentative
+    // definitions (`int x;`) too.
+    let mut globals: HashMap<&str, &Global> = HashMap::new();
+    let rank = |global: &Global| (!global.is_extern, global.init.is_some());
+    for global in &program.globals {
+        match globals.get(global.name.as_str()) {
+            Some(previous) if rank(previous) >= rank(global) => {}
+            _ => {
+                globals.insert(&global.name, global);
+            }
+        }
+    }
+
+    let mut strings = StringPool::default();
+    let functions = program
+        .functions
+        .iter()
+        .filter_map(|function| {
+            // Prototypes only contribute their signatures.
+            let body = function.body.as_deref()?;
+            let lowered = FunctionLowering {
+                builder: FunctionBuilder::new(
+                    &function.name,
+                    lower_type(&function.return_type, model),
+                ),
+                signatures: &signatures,
+                return_type: &function.return_type,
+                strings: &mut strings,
+                variables: HashMap::new(),
+                globals: &globals,
+                constants: constants.clone(),
+                labels: HashMap::new(),
+                breaks: Vec::new(),
+                switches: Vec::new(),
+                model,
+                debug_info,
+            }
+            .lower(function, body);
+            Some(lowered)
+        })
+        .collect();
+    // Variables only declared `extern` are defined in another translation
+    // unit, and the rest are defined once.
+    let defined = program
+        .globals
+        .iter()
+        .filter(|global| !global.is_extern && std::ptr::eq(globals[global.name.as_str()], *global))
+        .map(|global| lower_global(global, &globals, &constants, &mut strings, model))
+        .collect();
+    Module {
+        functions,
+        globals: defined,
+        strings,
+    }
+}
+
+/// The IR definition of `global`, an array being laid out element by
+/// element, whatever its nesting.
+fn lower_global(
+    global: &Global,
+    globals: &HashMap<&str, &Global>,
+    constants: &HashMap<String, i64>,
+    strings: &mut StringPool,
+    model: DataModel,
+) -> crate::ir::Global {
+    let element = element_type(&global.ty);
+    let mut init = Vec::new();
+    if let Some(value) = &global.init {
+        let mut datum = |expr: &Expression, ty: IrType| {
+            // Thread-locals have no one address, so the analyzer checked
+            // no initializer takes one.
+            let object = |name: &str| {
+                let global = globals.get(name).filter(|global| !global.thread_local)?;
+                Some(global.ty.clone())
+            };
+            let value = expr
+                .static_value(&object, &|name| constants.get(name).copied(), model)
+                .expect("the analyzer checked global initializers are constant");
+            match value {
+                StaticValue::Int(value) => Datum::Int(ty.normalize(value)),
+                StaticValue::Address(Base::String(bytes), offset) => Datum::String {
+                    string: strings.intern(bytes),
+                    offset,
+                },
+                StaticValue::Address(Base::Object(name), offset) => Datum::Global {
+                    name: name.to_string(),
+                    offset,
+                },
+            }
+        };
+        lower_static_initializer(value, &global.ty, model, &mut init, &mut datum);
+    }
+    crate::ir::Global {
+        name: global.name.clone(),
+        ty: lower_type(element, model).expect("the analyzer rejects `void` variables"),
+        len: (size_of(&global.ty, model) / size_of(element, model)) as u32,
+        init,
+        thread_local: global.thread_local,
+    }
+}
+
+/// Appends the value of each element `init` gives the `ty` it initializes
+/// to `data`, which `datum` works out. An inner array the initializer
+/// leaves elements of out is filled in with zeros, so that the elements
+/// after it land in place.
+fn lower_static_initializer(
+    init: &Initializer,
+    ty: &Type,
+    model: DataModel,
+    data: &mut Vec<Datum>,
+    datum: &mut impl FnMut(&Expression, IrType) -> Datum,
+) {
+    match (ty, init) {
+        (Type::Array(element, _), Initializer::List { elements, .. }) => {
+            let len = (size_of(element, model) / size_of(element_type(element), model)) as usize;
+            for init in elements {
+                let start = data.len();
+                lower_static_initializer(init, element, model, data, datum);
+                data.resize(start + len, Datum::Int(0));
+            }
+        }
+        (_, Initializer::Expr(expr)) => {
+            let ty = lower_type(ty, model).expect("the analyzer rejects `void` variables");
+            data.push(datum(expr, ty));
+        }
+        (_, Initializer::List { .. }) => {
+            unreachable!("the analyzer only accepts lists for arrays")
+        }
+    }
+}
+
+/// Records the value of each enumerator of `decl`, which the analyzer
+/// checked are integer constants.
+fn declare_constants(constants: &mut HashMap<String, i64>, decl: &Enum, model: DataModel) {
+    let mut next = 0;
+    for enumerator in &decl.enumerators {
+        let value = match &enumerator.value {
+            Some(value) => value
+                .constant_value(&|name| constants.get(name).copied(), model)
+                .expect("the analyzer checked enumerator values are constant"),
+            None => next,
+        };
+        constants.insert(enumerator.name.clone(), value);
+        next = value + 1;
+    }
+}
+
+fn lower_type(ty: &Type, model: DataModel) -> Option<IrType> {
+    match ty {
+        Type::Char | Type::UChar => Some(IrType::I8),
+        Type::Short | Type::UShort => Some(IrType::I16),
+        Type::Int | Type::UInt => Some(IrType::I32),
+        // There is no separate 64-bit integer type: `long` computes in
+        // `ptr`, which has the same width, or in `i32` where that does.
+        Type::Long | Type::ULong if model == DataModel::Ilp32 => Some(IrType::I32),
+        Type::Long | Type::ULong | Type::Pointer(_) => Some(IrType::Ptr),
+        Type::Void => None,
+        Type::Array(..) => unreachable!("arrays are stored in array slots"),
+    }
+}
+
+/// The scalar type at the bottom of any nesting of arrays.
+fn element_type(ty: &Type) -> &Type {
+    match ty {
+        Type::Array(element, _) => element_type(element),
+        ty => ty,
+    }
+}
+
+/// The size of an object of type `ty`, which the analyzer checked is
+/// complete, in bytes.
+fn size_of(ty: &Type, model: DataModel) -> i64 {
+    let size = ty
+        .size(model)
+        .expect("the analyzer rejects objects of type `void`");
+    size as i64
+}
+
+/// Arrays with at most this many elements are zeroed one store at a time;
+/// larger ones in a loop.
+const MAX_UNROLLED_ZEROING: i64 = 16;
+
+/// The IR type expressions of type `ty` are computed in: narrow integers
+/// are promoted to `i32`.
+fn rvalue_type(ty: &Type, model: DataModel) -> IrType {
+    match ty {
+        Type::Long | Type::ULong if model == DataModel::Ilp32 => IrType::I32,
+        Type::Long | Type::ULong | Type::Pointer(_) => IrType::Ptr,
+        _ => IrType::I32,
+    }
+}
+
+/// The cast that widens a value of type `ty`: zero-filling for unsigned
+/// types and sign-filling otherwise.
+fn extension(ty: &Type) -> CastOp {
+    if ty.is_unsigned() {
+        CastOp::ZExt
+    } else {
+        CastOp::SExt
+    }
+}
+
+/// Where the object an lvalue designates is stored.
+#[derive(Clone, Copy)]
+enum Place {
+    Slot(StackSlot),
+    /// The memory at the address in a value.
+    Ptr(Value),
+}
+
+struct FunctionLowering<'a> {
+    builder: FunctionBuilder,
+    /// A declaration or definition of every function declared.
+    signatures: &'a HashMap<&'a str, &'a Function>,
+    return_type: &'a Type,
+    strings: &'a mut StringPool,
+    /// The stack slot holding each local variable, and its type.
+    variables: HashMap<String, (StackSlot, Type)>,
+    /// The global variables, for names not in `variables` or `constants`.
+    globals: &'a HashMap<&'a str, &'a Global>,
+    /// The value of each enumeration constant, for names not in
+    /// `variables`.
+    constants: HashMap<String, i64>,
+    /// The block each label starts, created by the label or by the first
+    /// `goto` to it, whichever comes first.
+    labels: HashMap<String, BlockId>,
+    /// Where a `break` goes in each loop and `switch` being lowered,
+    /// innermost last.
+    breaks: Vec<BlockId>,
+    /// The targets of each `switch` being lowered, innermost last.
+    switches: Vec<SwitchTargets>,
+    model: DataModel,
+    debug_info: bool,
+}
+
+/// The blocks the `case`s and `default` of a `switch` start, gathered as
+/// its body is lowered.
+struct SwitchTargets {
+    /// The type of the promoted condition, which the case values are
+    /// normalized to.
+    ty: IrType,
+    cases: Vec<(i64, BlockId)>,
+    default: Option<BlockId>,
+}
+
+impl FunctionLowering<'_> {
+    fn lower(mut self, function: &Function, body: &[Statement]) -> crate::ir::Function {
+        ice::set_function(&function.name);
+        if self.debug_info {
+            self.builder.set_pos(function.pos);
+        }
+        // Parameters arrive in registers; each gets a slot like any other
+        // local so it can be assigned and have its address taken.
+        for param in &function.params {
+            let name = param
+                .name
+                .as_ref()
+                .expect("definitions name their parameters");
+            let ty =
+                lower_type(&param.ty, self.model).expect("the analyzer rejects `void` parameters");
+            let value = self.builder.param(ty);
+            let slot = self.builder.stack_slot(ty);
+            if self.debug_info {
+                self.builder.variable(name, slot, param.pos);
+            }
+            self.builder.store(slot, value);
+            self.variables
+                .insert(name.clone(), (slot, param.ty.clone()));
+        }
+        for statement in body {
+            self.lower_statement(statement);
+        }
+        if !self.builder.is_terminated() {
+            // Falling off the end returns 0, which is what `main` requires.
+            let value =
+                lower_type(&function.return_type, self.model).map(|ty| self.builder.iconst(ty, 0));
+            self.builder.ret(value);
+        }
+        self.builder.finish()
+    }
+
+    fn lower_statement(&mut self, statement: &Statement) {
+        ice::set_pos(statement.pos());
+        match statement {
+            Statement::Return { value, pos } => {
+                if self.debug_info {
+                    self.builder.loc(*pos);
+                }
+                match (value, self.builder.return_type()) {
+                    (Some(expr), Some(ty)) => {
+                        let (value, from) = self.lower_typed(expr);
+                        let value = self.convert(value, &from, self.return_type);
+                        let value = self.narrow(value, ty);
+                        self.builder.ret(Some(value));
+                    }
+                    // A `void` expression, evaluated for its effects.
+                    (Some(expr), None) => {
+                        self.lower_effects(expr);
+                        self.builder.ret(None);
+                    }
+                    (None, _) => self.builder.ret(None),
+                }
+                self.start_unreachable_block();
+            }
+            Statement::Declaration {
+                name,
+                ty,
+                init,
+                pos,
+            } => {
+                let element = element_type(ty);
+                let storage =
+                    lower_type(element, self.model).expect("the analyzer rejects `void` variables");
+                let slot = match ty {
+                    Type::Array(..) => {
+                        let len = size_of(ty, self.model) / size_of(element, self.model);
+                        self.builder.array_slot(storage, len as u32)
+                    }
+                    _ => self.builder.stack_slot(storage),
+                };
+                // Debug info only describes scalar variables so far.
+                if self.debug_info && element == ty {
+                    self.builder.variable(name, slot, *pos);
+                }
+                self.constants.remove(name);
+                self.variables.insert(name.clone(), (slot, ty.clone()));
+                if let Some(init) = init {
+                    if self.debug_info {
+                        self.builder.loc(*pos);
+                    }
+                    // Elements an initializer list leaves out are zero.
+                    if let Type::Array(..) = ty {
+                        let base = self.builder.slot_addr(slot);
+                        self.zero(base, ty);
+                    }
+                    self.initialize(Place::Slot(slot), ty, init);
+                }
+            }
+            Statement::Expression { expr, pos } => {
+                if self.debug_info {
+                    self.builder.loc(*pos);
+                }
+                self.lower_effects(expr);
+            }
+            Statement::Enum(decl) => {
+                declare_constants(&mut self.constants, decl, self.model);
+                for enumerator in &decl.enumerators {
+                    self.variables.remove(&enumerator.name);
+                }
+            }
+            Statement::Label { name, body, .. } => {
+                let block = self.label_block(name);
+                self.builder.jump(block);
+                self.builder.switch_to(block);
+                self.lower_statement(body);
+            }
+            Statement::Goto { label, .. } => {
+                let block = self.label_block(label);
+                self.builder.jump(block);
+                self.start_unreachable_block();
+            }
+            // The body is lowered first, to find the blocks its cases
+            // start, and the block that evaluated `cond` dispatches to them
+            // after.
+            Statement::Switch { cond, body, pos } => {
+                if self.debug_info {
+                    self.builder.loc(*pos);
+                }
+                let (value, ty) = self.lower_typed(cond);
+                let dispatch = self.builder.current_block();
+                let exit = self.builder.create_block();
+                self.switches.push(SwitchTargets {
+                    ty: rvalue_type(&ty, self.model),
+                    cases: Vec::new(),
+                    default: None,
+                });
+                self.start_unreachable_block();
+                self.breaks.push(exit);
+                self.lower_statement(body);
+                self.breaks.pop();
+                self.builder.jump(exit);
+                let targets = self.switches.pop().expect("pushed above");
+                self.builder.switch_to(dispatch);
+                self.builder
+                    .switch(value, targets.cases, targets.default.unwrap_or(exit));
+                self.builder.switch_to(exit);
+            }
+            Statement::Case { value, body, .. } => {
+                let value = value
+                    .constant_value(&|name| self.constants.get(name).copied(), self.model)
+                    .expect("the analyzer checked case values are constant");
+                let block = self.builder.create_block();
+                self.builder.jump(block);
+                self.builder.switch_to(block);
+                let targets = self
+                    .switches
+                    .last_mut()
+                    .expect("the analyzer checked cases are in a switch");
+                targets.cases.push((targets.ty.normalize(value), block));
+                self.lower_statement(body);
+            }
+            Statement::Default { body, .. } => {
+                let block = self.builder.create_block();
+                self.builder.jump(block);
+                self.builder.switch_to(block);
+                self.switches
+                    .last_mut()
+                    .expect("the analyzer checked defaults are in a switch")
+                    .default = Some(block);
+                self.lower_statement(body);
+            }
+            Statement::Break { .. } => {
+                let exit = *self
+                    .breaks
+                    .last()
+                    .expect("the analyzer checked breaks are in a loop or switch");
+                self.builder.jump(exit);
+                self.start_unreachable_block();
+            }
+            Statement::Empty { .. } => {}
+            // Each declaration has a slot of its own, so a shadowed
+            // variable keeps its value for after the block.
+            Statement::Block { body, .. } => {
+                let outer = (self.variables.clone(), self.constants.clone());
+                for statement in body.iter() {
+                    self.lower_statement(statement);
+                }
+                (self.variables, self.constants) = outer;
+            }
+            Statement::If {
+                cond,
+                then,
+                otherwise,
+                pos,
+            } => {
+                let then_block = self.builder.create_block();
+                let exit = self.builder.create_block();
+                let else_block = match otherwise {
+                    Some(_) => self.builder.create_block(),
+                    None => exit,
+                };
+                self.lower_condition(cond, *pos, then_block, else_block);
+                self.builder.switch_to(then_block);
+                self.lower_statement(then);
+                self.builder.jump(exit);
+                if let Some(otherwise) = otherwise {
+                    self.builder.switch_to(else_block);
+                    self.lower_statement(otherwise);
+                    self.builder.jump(exit);
+                }
+                self.builder.switch_to(exit);
+            }
+            Statement::While { cond, body, pos } => {
+                let header = self.builder.create_block();
+                let body_block = self.builder.create_block();
+                let exit = self.builder.create_block();
+                self.builder.jump(header);
+                self.builder.switch_to(header);
+                self.lower_condition(cond, *pos, body_block, exit);
+                self.builder.switch_to(body_block);
+                self.lower_loop_body(body, exit);
+                self.builder.jump(header);
+                self.builder.switch_to(exit);
+            }
+            Statement::DoWhile { body, cond, pos } => {
+                let body_block = self.builder.create_block();
+                let latch = self.builder.create_block();
+                let exit = self.builder.create_block();
+                self.builder.jump(body_block);
+                self.builder.switch_to(body_block);
+                self.lower_loop_body(body, exit);
+                self.builder.jump(latch);
+                self.builder.switch_to(latch);
+                self.lower_condition(cond, *pos, body_block, exit);
+                self.builder.switch_to(exit);
+            }
+            Statement::For {
+                init,
+                cond,
+                step,
+                body,
+                pos,
+            } => {
+                // A variable declared in `init` goes out of scope after the
+                // loop, uncovering any it shadowed.
+                let outer = self.variables.clone();
+                if let Some(init) = init {
+                    self.lower_statement(init);
+                }
+                let header = self.builder.create_block();
+                let body_block = self.builder.create_block();
+                let step_block = self.builder.create_block();
+                let exit = self.builder.create_block();
+                self.builder.jump(header);
+                self.builder.switch_to(header);
+                match cond {
+                    Some(cond) => self.lower_condition(cond, *pos, body_block, exit),
+                    None => self.builder.jump(body_block),
+                }
+                self.builder.switch_to(body_block);
+                self.lower_loop_body(body, exit);
+                self.builder.jump(step_block);
+                self.builder.switch_to(step_block);
+                if let Some(step) = step {
+                    self.lower_effects(step);
+                }
+                self.builder.jump(header);
+                self.builder.switch_to(exit);
+                self.variables = outer;
+            }
+            Statement::Asm {
+                template,
+                outputs,
+                inputs,
+                pos,
+                ..
+            } => {
+                if self.debug_info {
+                    self.builder.loc(*pos);
+                }
+                let inputs = inputs
+                    .iter()
+                    .map(|input| self.lower_expression(&input.expr))
+                    .collect();
+                // The analyzer allows at most one output.
+                let output = outputs.first().map(|output| self.place(&output.expr));
+                let ty = output
+                    .as_ref()
+                    .and_then(|(_, ty)| lower_type(ty, self.model));
+                let result = self.builder.inline_asm(template.clone(), inputs, ty);
+                if let (Some((place, _)), Some(result)) = (output, result) {
+                    self.store_raw(place, result);
+                }
+            }
+        }
+    }
+
+    /// Where the object an lvalue the analyzer accepted is stored, and its
+    /// type. Evaluates the address of a dereferenced pointer.
+    fn place(&mut self, expr: &Expression) -> (Place, Type) {
+        match expr {
+            Expression::Variable { name, .. } => match self.variables.get(name) {
+                Some((slot, ty)) => (Place::Slot(*slot), ty.clone()),
+                None => {
+                    let global = self.globals[name.as_str()];
+                    let address = if global.thread_local {
+                        self.builder.thread_local_addr(name)
+                    } else {
+                        self.builder.global_addr(name)
+                    };
+                    (Place::Ptr(address), global.ty.clone())
+                }
+            },
+            Expression::Deref { operand, .. } => {
+                let (ptr, ty) = self.lower_typed(operand);
+                let Type::Pointer(pointee) = ty else {
+                    unreachable!("the analyzer only dereferences pointers");
+                };
+                (Place::Ptr(ptr), *pointee)
+            }
+            _ => unreachable!("the analyzer only accepts variables and dereferences as lvalues"),
+        }
+    }
+
+    /// The address of `place`. `&*p` is `p`, without reading `*p`.
+    fn address(&mut self, place: Place) -> Value {
+        match place {
+            Place::Slot(slot) => self.builder.slot_addr(slot),
+            Place::Ptr(ptr) => ptr,
+        }
+    }
+
+    /// `address` advanced by `offset` bytes.
+    fn offset(&mut self, address: Value, offset: i64) -> Value {
+        if offset == 0 {
+            return address;
+        }
+        let offset = self.builder.iconst(IrType::Ptr, offset);
+        self.builder.binary(BinOp::Add, address, offset)
+    }
+
+    /// Stores `init` into the `ty` at `place`. Elements of an array the
+    /// initializer leaves out are left as they are.
+    fn initialize(&mut self, place: Place, ty: &Type, init: &Initializer) {
+        match (ty, init) {
+            (Type::Array(element, _), Initializer::List { elements, .. }) => {
+                let base = self.address(place);
+                for (i, init) in elements.iter().enumerate() {
+                    let address = self.offset(base, i as i64 * size_of(element, self.model));
+                    self.initialize(Place::Ptr(address), element, init);
+                }
+            }
+            (_, Initializer::Expr(expr)) => {
+                let (value, from) = self.lower_typed(expr);
+                let value = self.convert(value, &from, ty);
+                self.store(place, ty, value);
+            }
+            (_, Initializer::List { .. }) => {
+                unreachable!("the analyzer only accepts lists for arrays")
+            }
+        }
+    }
+
+    /// Zeroes every element of the array `ty` at `base`.
+    fn zero(&mut self, base: Value, ty: &Type) {
+        let element = element_type(ty);
+        let size = size_of(element, self.model);
+        let len = size_of(ty, self.model) / size;
+        let storage =
+            lower_type(element, self.model).expect("the analyzer rejects `void` elements");
+        let zero = self.builder.iconst(storage, 0);
+        if len <= MAX_UNROLLED_ZEROING {
+            for i in 0..len {
+                let address = self.offset(base, i * size);
+                self.builder.store_ptr(address, zero);
+            }
+            return;
+        }
+        // A pointer walks from the first element to one past the last.
+        let cursor = self.builder.stack_slot(IrType::Ptr);
+        self.builder.store(cursor, base);
+        let end = self.offset(base, len * size);
+        let header = self.builder.create_block();
+        let body = self.builder.create_block();
+        let exit = self.builder.create_block();
+        self.builder.jump(header);
+        self.builder.switch_to(header);
+        let address = self.builder.load(cursor);
+        let more = self.builder.cmp(CmpOp::Ult, address, end);
+        self.builder.branch(more, body, exit);
+        self.builder.switch_to(body);
+        self.builder.store_ptr(address, zero);
+        let next = self.offset(address, size);
+        self.builder.store(cursor, next);
+        self.builder.jump(header);
+        self.builder.switch_to(exit);
+    }
+
+    /// Reads the `ty` stored at `place`, promoted to its rvalue type.
+    fn load(&mut self, place: Place, ty: &Type) -> Value {
+        let storage = lower_type(ty, self.model).expect("the analyzer rejects `void` objects");
+        let value = match place {
+            Place::Slot(slot) => self.builder.load(slot),
+            Place::Ptr(ptr) => self.builder.load_ptr(storage, ptr),
+        };
+        if storage.is_narrow() {
+            self.builder.cast(extension(ty), value, IrType::I32)
+        } else {
+            value
+        }
+    }
+
+    /// Stores the rvalue `value` as the `ty` at `place`, returning the value
+    /// the object then has.
+    fn store(&mut self, place: Place, ty: &Type, value: Value) -> Value {
+        let storage = lower_type(ty, self.model).expect("the analyzer rejects `void` objects");
+        if !storage.is_narrow() {
+            self.store_raw(place, value);
+            return value;
+        }
+        let narrowed = self.narrow(value, storage);
+        self.store_raw(place, narrowed);
+        self.builder.cast(extension(ty), narrowed, IrType::I32)
+    }
+
+    /// Stores `value`, already of the storage type, at `place`.
+    fn store_raw(&mut self, place: Place, value: Value) {
+        match place {
+            Place::Slot(slot) => self.builder.store(slot, value),
+            Place::Ptr(ptr) => self.builder.store_ptr(ptr, value),
+        }
+    }
+
+    /// Truncates the `i32` rvalue `value` to fit storage of type `ty`.
+    fn narrow(&mut self, value: Value, ty: IrType) -> Value {
+        if ty.is_narrow() {
+            self.builder.cast(CastOp::Trunc, value, ty)
+        } else {
+            value
+        }
+    }
+
+    /// Converts the rvalue `value` of type `from` to type `to`, as the
+    /// analyzer allowed. Only a change of rvalue width changes the
+    /// representation: an `int` widens to a `long` (or a null pointer
+    /// constant to a pointer) by the signedness of `from`, and a `long`
+    /// narrows by dropping its high bits. Narrower targets are truncated
+    /// when stored.
+    fn convert(&mut self, value: Value, from: &Type, to: &Type) -> Value {
+        match (rvalue_type(from, self.model), rvalue_type(to, self.model)) {
+            (IrType::I32, IrType::Ptr) => self.builder.cast(extension(from), value, IrType::Ptr),
+            (IrType::Ptr, IrType::I32) => self.builder.cast(CastOp::Trunc, value, IrType::I32),
+            _ => value,
+        }
+    }
+
+    /// 1 if the scalar `value` of type `ty` is nonzero, 0 otherwise.
+    fn truth(&mut self, value: Value, ty: &Type) -> Value {
+        let zero = self.builder.iconst(rvalue_type(ty, self.model), 0);
+        self.builder.cmp(CmpOp::Ne, value, zero)
+    }
+
+    /// Branches to `then_block` if `cond` is nonzero and to `else_block`
+    /// otherwise.
+    fn lower_condition(
+        &mut self,
+        cond: &Expression,
+        pos: Position,
+        then_block: BlockId,
+        else_block: BlockId,
+    ) {
+        if self.debug_info {
+            self.builder.loc(pos);
+        }
+        self.branch_on(cond, then_block, else_block);
+    }
+
+    /// Branches on `cond` as `lower_condition` does. The operands of `&&`
+    /// and `||` become branches of their own, so the right one is only
+    /// evaluated when the left one leaves the result open.
+    fn branch_on(&mut self, cond: &Expression, then_block: BlockId, else_block: BlockId) {
+        let (first, links) =
+            cond.binary_chain_of(|op| matches!(op, BinaryOp::LogicalAnd | BinaryOp::LogicalOr));
+        // From the outermost operator in: the block its right operand
+        // starts and the blocks that operand branches to. Its left operand
+        // branches to that block when it leaves the result open.
+        let mut rights = Vec::with_capacity(links.len());
+        let (mut then_block, mut else_block) = (then_block, else_block);
+        for link in links.iter().rev() {
+            let rhs_block = self.builder.create_block();
+            rights.push((rhs_block, then_block, else_block));
+            if link.op == BinaryOp::LogicalAnd {
+                then_block = rhs_block;
+            } else {
+                else_block = rhs_block;
+            }
+        }
+        self.branch_on_value(first, then_block, else_block);
+        for (link, (rhs_block, then_block, else_block)) in
+            links.iter().zip(rights.into_iter().rev())
+        {
+            self.builder.switch_to(rhs_block);
+            self.branch_on(&link.operands[1], then_block, else_block);
+        }
+    }
+
+    /// Branches on the value of `cond`, which isn't an `&&` or `||`.
+    ///
+    /// Code generation lets a branch's `then` block fall through where it
+    /// can, so when `cond` is `__builtin_expect(value, 0)` the branch tests
+    /// `value == 0` with the blocks swapped, putting the expected one first.
+    fn branch_on_value(&mut self, cond: &Expression, then_block: BlockId, else_block: BlockId) {
+        if let Expression::BuiltinCall {
+            builtin: Builtin::Expect,
+            args,
+            ..
+        } = cond
+        {
+            let [value, expected] = &**args else {
+                unreachable!("analyzer checks the argument count");
+            };
+            let unlikely =
+                expected.constant_value(&|name| self.constants.get(name).copied(), self.model);
+            if unlikely == Some(0) {
+                let (value, ty) = self.lower_typed(value);
+                self.lower_expression(expected);
+                let zero = self.builder.iconst(rvalue_type(&ty, self.model), 0);
+                let value = self.builder.cmp(CmpOp::Eq, value, zero);
+                self.builder.branch(value, else_block, then_block);
+                return;
+            }
+        }
+        let (value, ty) = self.lower_typed(cond);
+        // Branches test `i32`s; a pointer or `long` is compared against
+        // zero first.
+        let value = match rvalue_type(&ty, self.model) {
+            IrType::I32 => value,
+            _ => self.truth(value, &ty),
+        };
+        self.builder.branch(value, then_block, else_block);
+    }
+
+    /// Evaluates the arguments left to right, then calls `name` with them,
+    /// returning the result and its type.
+    fn lower_call(
+        &mut self,
+        name: &str,
+        args: &[Expression],
+        pos: Position,
+    ) -> Option<(Value, Type)> {
+        let function = self.signatures.get(name).copied();
+        let args = args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| {
+                let (value, from) = self.lower_typed(arg);
+                // Arguments to implicitly declared functions (C89), to
+                // ones declared with `()` and variable arguments are
+                // passed as they are.
+                let Some(param) = function.and_then(|function| function.params.get(i)) else {
+                    return value;
+                };
+                let param = &param.ty;
+                let value = self.convert(value, &from, param);
+                let storage =
+                    lower_type(param, self.model).expect("the analyzer rejects `void` parameters");
+                self.narrow(value, storage)
+            })
+            .collect();
+        if self.debug_info {
+            self.builder.loc(pos);
+        }
+        // Implicitly declared functions return `int`.
+        let return_type = function.map_or(Type::Int, |function| function.return_type.clone());
+        let fixed = function
+            .filter(|function| function.variadic)
+            .map(|function| function.params.len());
+        let result =
+            self.builder
+                .call_variadic(name, args, fixed, lower_type(&return_type, self.model))?;
+        let result = if lower_type(&return_type, self.model).is_some_and(IrType::is_narrow) {
+            self.builder
+                .cast(extension(&return_type), result, IrType::I32)
+        } else {
+            result
+        };
+        Some((result, return_type))
+    }
+
+    /// The block the label `name` starts.
+    fn label_block(&mut self, name: &str) -> BlockId {
+        if let Some(&block) = self.labels.get(name) {
+            return block;
+        }
+        let block = self.builder.create_block();
+        self.labels.insert(name.to_string(), block);
+        block
+    }
+
+    /// Lowers the body of a loop, which a `break` leaves for `exit`.
+    fn lower_loop_body(&mut self, body: &Statement, exit: BlockId) {
+        self.breaks.push(exit);
+        self.lower_statement(body);
+        self.breaks.pop();
+    }
+
+    /// Moves on to a block nothing branches to, which `finish` drops along
+    /// with any code lowered into it.
+    fn start_unreachable_block(&mut self) {
+        let rest = self.builder.create_block();
+        self.builder.switch_to(rest);
+    }
+
+    /// Lowers `expr`, which may be `void`, for its effects alone.
+    fn lower_effects(&mut self, expr: &Expression) {
+        match expr {
+            Expression::FunctionCall { name, args, pos } => {
+                self.lower_call(name, args, *pos);
+            }
+            Expression::BuiltinCall {
+                builtin: Builtin::Trap,
+                ..
+            } => {
+                self.builder.trap();
+                self.start_unreachable_block();
+            }
+            // The current block keeps the `unreachable` terminator it
+            // starts with.
+            Expression::BuiltinCall {
+                builtin: Builtin::Unreachable,
+                ..
+            } => self.start_unreachable_block(),
+            _ => {
+                self.lower_expression(expr);
+            }
+        }
+    }
+
+    fn lower_expression(&mut self, expr: &Expression) -> Value {
+        self.lower_typed(expr).0
+    }
+
+    /// Lowers `expr` to its value, along with its type.
+    fn lower_typed(&mut self, expr: &Expression) -> (Value, Type) {
+        match expr {
+            Expression::IntLit { value, suffix, .. } => {
+                let ty = Type::of_int_literal(*value, *suffix);
+                (
+                    self.builder
+                        .iconst(rvalue_type(&ty, self.model), *value as i64),
+                    ty,
+                )
+            }
+            // `char` is signed on x86-64 and Apple's AArch64, so `'\xff'` is
+            // -1 there. AArch64 Linux, whose `char` is unsigned, differs.
+            Expression::CharLit { value, .. } => (
+                self.builder.iconst(IrType::I32, i64::from(*value as i8)),
+                Type::Int,
+            ),
+            Expression::StringLit { value, .. } => {
+                let string = self.strings.intern(value);
+                (
+                    self.builder.string_addr(string),
+                    Type::pointer_to(Type::Char),
+                )
+            }
+            Expression::Variable { name, .. }
+                if !self.variables.contains_key(name) && self.constants.contains_key(name) =>
+            {
+                let value = self.constants[name.as_str()];
+                (self.builder.iconst(IrType::I32, value), Type::Int)
+            }
+            Expression::Variable { .. } | Expression::Deref { .. } => {
+                let (place, ty) = self.place(expr);
+                match ty {
+                    // An array decays to the address of its first element.
+                    Type::Array(..) => (self.address(place), ty.decay()),
+                    _ => (self.load(place, &ty), ty),
+                }
+            }
+            Expression::AddressOf { operand, .. } => {
+                let (place, ty) = self.place(operand);
+                (self.address(place), Type::pointer_to(ty))
+            }
+            Expression::SizeofExpr { operand, .. } => {
+                let size = size_of(&self.type_of(operand), self.model);
+                (self.builder.iconst(IrType::I32, size), Type::Int)
+            }
+            Expression::SizeofType { ty, .. } => (
+                self.builder.iconst(IrType::I32, size_of(ty, self.model)),
+                Type::Int,
+            ),
+            Expression::Assign { op, operands, .. } => {
+                let [target, value] = &**operands;
+                let (value, from) = self.lower_typed(value);
+                let (place, ty) = self.place(target);
+                // The target's address is worked out once, for both the
+                // read and the write.
+                let (value, from) = match op {
+                    Some(op) => {
+                        let current = self.load(place, &ty);
+                        self.lower_binary(*op, (current, ty.clone()), (value, from))
+                    }
+                    None => (value, from),
+                };
+                let value = self.convert(value, &from, &ty);
+                (self.store(place, &ty, value), ty)
+            }
+            Expression::IncDec { op, operand, .. } => {
+                let (place, ty) = self.place(operand);
+                let old = self.load(place, &ty);
+                let one = self.builder.iconst(IrType::I32, 1);
+                let (new, from) =
+                    self.lower_binary(op.binary_op(), (old, ty.clone()), (one, Type::Int));
+                let new = self.convert(new, &from, &ty);
+                let new = self.store(place, &ty, new);
+                // A postfix result is the value loaded before the store.
+                let value = if op.is_postfix() { old } else { new };
+                (value, ty)
+            }
+            Expression::Unary { op, operand, .. } => {
+                let (value, ty) = self.lower_typed(operand);
+                let zero = self.builder.iconst(rvalue_type(&ty, self.model), 0);
+                match op {
+                    UnaryOp::Neg => (self.builder.binary(BinOp::Sub, zero, value), ty.promote()),
+                    UnaryOp::Not => (self.builder.cmp(CmpOp::Eq, value, zero), Type::Int),
+                    UnaryOp::Complement => {
+                        let ones = self.builder.iconst(rvalue_type(&ty, self.model), -1);
+                        (self.builder.binary(BinOp::Xor, value, ones), ty.promote())
+                    }
+                }
+            }
+            // The 0 or 1 is stored on each path and loaded where they
+            // meet, since the IR has no phis.
+            Expression::Binary {
+                op: BinaryOp::LogicalAnd | BinaryOp::LogicalOr,
+                ..
+            } => {
+                let result = self.builder.stack_slot(IrType::I32);
+                let [true_block, false_block, exit] = [(); 3].map(|()| self.builder.create_block());
+                self.branch_on(expr, true_block, false_block);
+                for (block, value) in [(true_block, 1), (false_block, 0)] {
+                    self.builder.switch_to(block);
+                    let value = self.builder.iconst(IrType::I32, value);
+                    self.builder.store(result, value);
+                    self.builder.jump(exit);
+                }
+                self.builder.switch_to(exit);
+                (self.builder.load(result), Type::Int)
+            }
+            // A chain stops at an `&&` or `||`, which branch.
+            Expression::Binary { .. } => {
+                let (first, links) = expr.binary_chain_of(|op| {
+                    !matches!(op, BinaryOp::LogicalAnd | BinaryOp::LogicalOr)
+                });
+                let mut result = self.lower_typed(first);
+                for link in links {
+                    let rhs = self.lower_typed(&link.operands[1]);
+                    result = self.lower_binary(link.op, result, rhs);
+                }
+                result
+            }
+            Expression::FunctionCall { name, args, pos } => self
+                .lower_call(name, args, *pos)
+                .expect("analyzer rejects void calls used as values"),
+            // Only a branch can make use of the expected value, which
+            // `branch_on_value` does; as a value it only has to be evaluated.
+            Expression::BuiltinCall {
+                builtin: Builtin::Expect,
+                args,
+                ..
+            } => {
+                let [value, expected] = &**args else {
+                    unreachable!("analyzer checks the argument count");
+                };
+                let value = self.lower_expression(value);
+                self.lower_expression(expected);
+                (value, Type::Int)
+            }
+            Expression::BuiltinCall { .. } => {
+                unreachable!("analyzer rejects void builtins used as values")
+            }
+        }
+    }
+
+    /// The type of `expr` before any decay, as `sizeof` sees it, worked out
+    /// without evaluating it.
+    fn type_of(&self, expr: &Expression) -> Type {
+        match expr {
+            Expression::StringLit { value, .. } => {
+                Type::Array(Box::new(Type::Char), value.len() as u32 + 1)
+            }
+            Expression::Variable { name, .. } => match self.variables.get(name) {
+                Some((_, ty)) => ty.clone(),
+                None if self.constants.contains_key(name) => Type::Int,
+                None => self.globals[name.as_str()].ty.clone(),
+            },
+            Expression::AddressOf { operand, .. } => Type::pointer_to(self.type_of(operand)),
+            Expression::Deref { operand, .. } => match self.type_of(operand).decay() {
+                Type::Pointer(pointee) => *pointee,
+                _ => unreachable!("the analyzer only dereferences pointers"),
+            },
+            Expression::Assign { operands, .. } => self.type_of(&operands[0]),
+            Expression::IncDec { operand, .. } => self.type_of(operand),
+            // The analyzer has already applied the usual arithmetic
+            // conversions and recorded the result.
+            Expression::Unary { ty, .. } | Expression::Binary { ty, .. } => ty
+                .get()
+                .cloned()
+                .expect("the analyzer types every operator"),
+            Expression::FunctionCall { name, .. } => self
+                .signatures
+                .get(name.as_str())
+                .map_or(Type::Int, |function| function.return_type.clone()),
+            Expression::BuiltinCall { builtin, .. } => builtin.signature().1,
+            Expression::IntLit { value, suffix, .. } => Type::of_int_literal(*value, *suffix),
+            Expression::CharLit { .. }
+            | Expression::SizeofExpr { .. }
+            | Expression::SizeofType { .. } => Type::Int,
+        }
+    }
+
+    fn lower_binary(
+        &mut self,
+        op: BinaryOp,
+        (lhs, left): (Value, Type),
+        (rhs, right): (Value, Type),
+    ) -> (Value, Type) {
+        match (op, &left, &right) {
+            (BinaryOp::LogicalAnd | BinaryOp::LogicalOr, _, _) => {
+                unreachable!("`&&` and `||` are lowered to branches")
+            }
+            (BinaryOp::Add | BinaryOp::Sub, Type::Pointer(pointee), count)
+                if count.is_integer() =>
+            {
+                let offset = self.scale(rhs, count, pointee);
+                let op = if op == BinaryOp::Add {
+                    BinOp::Add
+                } else {
+                    BinOp::Sub
+                };
+                (self.builder.binary(op, lhs, offset), left)
+            }
+            (BinaryOp::Add, count, Type::Pointer(pointee)) if count.is_integer() => {
+                let offset = self.scale(lhs, count, pointee);
+                (self.builder.binary(BinOp::Add, rhs, offset), right)
+            }
+            // The distance in elements between two pointers into the same
+            // object, a `long`.
+            (BinaryOp::Sub, Type::Pointer(pointee), Type::Pointer(_)) => {
+                let bytes = self.builder.binary(BinOp::Sub, lhs, rhs);
+                let size = self
+                    .builder
+                    .iconst(IrType::Ptr, size_of(pointee, self.model));
+                let elements = self.builder.binary(BinOp::SDiv, bytes, size);
+                let elements = self.convert(elements, &left, &Type::Long);
+                (elements, Type::Long)
+            }
+            _ if matches!(left, Type::Pointer(_)) || matches!(right, Type::Pointer(_)) => {
+                // Addresses compare unsigned; a null pointer constant
+                // widens to a pointer first.
+                let lhs = self.convert(lhs, &left, &Type::pointer_to(Type::Void));
+                let rhs = self.convert(rhs, &right, &Type::pointer_to(Type::Void));
+                let op = match op {
+                    BinaryOp::Lt => CmpOp::Ult,
+                    BinaryOp::Le => CmpOp::Ule,
+                    BinaryOp::Gt => CmpOp::Ugt,
+                    BinaryOp::Ge => CmpOp::Uge,
+                    BinaryOp::Eq => CmpOp::Eq,
+                    BinaryOp::Ne => CmpOp::Ne,
+                    _ => unreachable!("the analyzer rejects {op:?} on pointers"),
+                };
+                (self.builder.cmp(op, lhs, rhs), Type::Int)
+            }
+            _ => {
+                // A shift keeps the type of its left operand; other
+                // operators convert both sides to a common type.
+                let ty = match op {
+                    BinaryOp::Shl | BinaryOp::Shr => left.clone().promote(),
+                    _ => Type::common(&left, &right, self.model),
+                };
+                let lhs = self.convert(lhs, &left, &ty);
+                let rhs = self.convert(rhs, &right, &ty);
+                let unsigned = ty.is_unsigned();
+                let op = match op {
+                    BinaryOp::Add => BinOp::Add,
+                    BinaryOp::Sub => BinOp::Sub,
+                    BinaryOp::Mul => BinOp::Mul,
+                    BinaryOp::Div if unsigned => BinOp::UDiv,
+                    BinaryOp::Div => BinOp::SDiv,
+                    BinaryOp::Rem if unsigned => BinOp::URem,
+                    BinaryOp::Rem => BinOp::SRem,
+                    BinaryOp::BitAnd => BinOp::And,
+                    BinaryOp::BitOr => BinOp::Or,
+                    BinaryOp::BitXor => BinOp::Xor,
+                    BinaryOp::Shl => BinOp::Shl,
+                    BinaryOp::Shr if unsigned => BinOp::LShr,
+                    BinaryOp::Shr => BinOp::AShr,
+                    _ => {
+                        let op = match op {
+                            BinaryOp::Lt if unsigned => CmpOp::Ult,
+                            BinaryOp::Lt => CmpOp::Slt,
+                            BinaryOp::Le if unsigned => CmpOp::Ule,
+                            BinaryOp::Le => CmpOp::Sle,
+                            BinaryOp::Gt if unsigned => CmpOp::Ugt,
+                            BinaryOp::Gt => CmpOp::Sgt,
+                            BinaryOp::Ge if unsigned => CmpOp::Uge,
+                            BinaryOp::Ge => CmpOp::Sge,
+                            BinaryOp::Eq => CmpOp::Eq,
+                            BinaryOp::Ne => CmpOp::Ne,
+                            _ => unreachable!("arithmetic is handled above"),
+                        };
+                        return (self.builder.cmp(op, lhs, rhs), Type::Int);
+                    }
+                };
+                (self.builder.binary(op, lhs, rhs), ty)
+            }
+        }
+    }
+
+    /// The element count `count`, an integer of type `ty`, as a byte
+    /// offset between `pointee`s.
+    fn scale(&mut self, count: Value, ty: &Type, pointee: &Type) -> Value {
+        let count = self.convert(count, ty, &Type::pointer_to(Type::Void));
+        let size = size_of(pointee, self.model);
+        if size == 1 {
+            return count;
+        }
+        let size = self.builder.iconst(IrType::Ptr, size);
+        self.builder.binary(BinOp::Mul, count, size)
+    }
+}
@@ -0,0 +1,990 @@
+//! Intermediate representation shared by the optimizer and the backends.
+//!
+//! A function is a control-flow graph of basic blocks. Every `Value` is
+//! defined exactly once; anything mutable, like a local variable, lives in a
+//! `StackSlot` and is accessed with loads and stores.
+
+pub mod cfg;
+pub mod interp;
+pub mod liveness;
+pub mod llvm;
+pub mod lower;
+mod print;
+pub mod range;
+pub mod verify;
+
+use crate::lexer::Position;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Value(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub u32);
+
+/// A stack-allocated memory location, such as a local variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct StackSlot(pub u32);
+
+impl BlockId {
+    pub const ENTRY: BlockId = BlockId(0);
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrType {
+    /// Narrow integers are only loaded, stored and converted; arithmetic
+    /// happens after extending them to `i32`.
+    I8,
+    I16,
+    I32,
+    /// An address, and `long` under LP64. It is 64 bits wide everywhere
+    /// but WebAssembly, whose backend keeps it in 32.
+    Ptr,
+}
+
+impl IrType {
+    pub fn bits(self) -> u32 {
+        match self {
+            IrType::I8 => 8,
+            IrType::I16 => 16,
+            IrType::I32 => 32,
+            IrType::Ptr => 64,
+        }
+    }
+
+    pub fn bytes(self) -> u32 {
+        self.bits() / 8
+    }
+
+    pub fn min_signed(self) -> i64 {
+        i64::MIN >> (64 - self.bits())
+    }
+
+    /// Truncates `value` to this type's width and sign-extends it back.
+    pub fn normalize(self, value: i64) -> i64 {
+        match self {
+            IrType::I8 => value as i8 as i64,
+            IrType::I16 => value as i16 as i64,
+            IrType::I32 => value as i32 as i64,
+            IrType::Ptr => value,
+        }
+    }
+
+    /// Whether the type is narrower than `i32`.
+    pub fn is_narrow(self) -> bool {
+        self.bits() < 32
+    }
+
+    /// Reinterprets a normalized `value` as an unsigned integer of this width.
+    pub fn as_unsigned(self, value: i64) -> u64 {
+        value as u64 & (u64::MAX >> (64 - self.bits()))
+    }
+}
+
+/// What a stack slot holds: one `ty`, or for an array, `len` of them in a
+/// row, which are only accessed through the slot's address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slot {
+    pub ty: IrType,
+    pub len: u32,
+}
+
+impl Slot {
+    pub fn scalar(ty: IrType) -> Self {
+        Self { ty, len: 1 }
+    }
+
+    pub fn array(ty: IrType, len: u32) -> Self {
+        Self { ty, len }
+    }
+
+    pub fn is_array(self) -> bool {
+        self.len != 1
+    }
+
+    pub fn size(self) -> u32 {
+        self.ty.bytes() * self.len
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    SDiv,
+    UDiv,
+    SRem,
+    URem,
+    And,
+    Or,
+    Xor,
+    Shl,
+    /// Arithmetic (sign-filling) right shift.
+    AShr,
+    /// Logical (zero-filling) right shift.
+    LShr,
+}
+
+impl BinOp {
+    /// Evaluates `lhs self rhs` in `ty`, or `None` if the operation has no defined result.
+    pub fn evaluate(self, ty: IrType, lhs: i64, rhs: i64) -> Option<i64> {
+        let (ulhs, urhs) = (ty.as_unsigned(lhs), ty.as_unsigned(rhs));
+        let value = match self {
+            BinOp::Add => lhs.wrapping_add(rhs),
+            BinOp::Sub => lhs.wrapping_sub(rhs),
+            BinOp::Mul => lhs.wrapping_mul(rhs),
+            BinOp::SDiv | BinOp::SRem => {
+                if rhs == 0 || (rhs == -1 && lhs == ty.min_signed()) {
+                    return None;
+                }
+                if self == BinOp::SDiv {
+                    lhs / rhs
+                } else {
+                    lhs % rhs
+                }
+            }
+            BinOp::UDiv => ulhs.checked_div(urhs)? as i64,
+            BinOp::URem => ulhs.checked_rem(urhs)? as i64,
+            BinOp::And => lhs & rhs,
+            BinOp::Or => lhs | rhs,
+            BinOp::Xor => lhs ^ rhs,
+            BinOp::Shl | BinOp::AShr | BinOp::LShr => {
+                if urhs >= u64::from(ty.bits()) {
+                    return None;
+                }
+                match self {
+                    BinOp::Shl => lhs << urhs,
+                    BinOp::AShr => lhs >> urhs,
+                    _ => (ulhs >> urhs) as i64,
+                }
+            }
+        };
+        Some(ty.normalize(value))
+    }
+}
+
+/// An integer comparison. Equality is sign-agnostic; the ordered
+/// comparisons come in signed (`S`) and unsigned (`U`) forms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Slt,
+    Sle,
+    Sgt,
+    Sge,
+    Ult,
+    Ule,
+    Ugt,
+    Uge,
+}
+
+impl CmpOp {
+    /// Whether `lhs self rhs` holds for operands of type `ty`.
+    pub fn evaluate(self, ty: IrType, lhs: i64, rhs: i64) -> bool {
+        let (ulhs, urhs) = (ty.as_unsigned(lhs), ty.as_unsigned(rhs));
+        match self {
+            CmpOp::Eq => lhs == rhs,
+            CmpOp::Ne => lhs != rhs,
+            CmpOp::Slt => lhs < rhs,
+            CmpOp::Sle => lhs <= rhs,
+            CmpOp::Sgt => lhs > rhs,
+            CmpOp::Sge => lhs >= rhs,
+            CmpOp::Ult => ulhs < urhs,
+            CmpOp::Ule => ulhs <= urhs,
+            CmpOp::Ugt => ulhs > urhs,
+            CmpOp::Uge => ulhs >= urhs,
+        }
+    }
+
+    /// The comparison that holds exactly when this one does not.
+    pub fn inverse(self) -> Self {
+        match self {
+            CmpOp::Eq => CmpOp::Ne,
+            CmpOp::Ne => CmpOp::Eq,
+            CmpOp::Slt => CmpOp::Sge,
+            CmpOp::Sle => CmpOp::Sgt,
+            CmpOp::Sgt => CmpOp::Sle,
+            CmpOp::Sge => CmpOp::Slt,
+            CmpOp::Ult => CmpOp::Uge,
+            CmpOp::Ule => CmpOp::Ugt,
+            CmpOp::Ugt => CmpOp::Ule,
+            CmpOp::Uge => CmpOp::Ult,
+        }
+    }
+
+    /// The comparison that gives the same result with the operands swapped.
+    pub fn swapped(self) -> Self {
+        match self {
+            CmpOp::Eq | CmpOp::Ne => self,
+            CmpOp::Slt => CmpOp::Sgt,
+            CmpOp::Sle => CmpOp::Sge,
+            CmpOp::Sgt => CmpOp::Slt,
+            CmpOp::Sge => CmpOp::Sle,
+            CmpOp::Ult => CmpOp::Ugt,
+            CmpOp::Ule => CmpOp::Uge,
+            CmpOp::Ugt => CmpOp::Ult,
+            CmpOp::Uge => CmpOp::Ule,
+        }
+    }
+
+    pub fn is_unsigned(self) -> bool {
+        matches!(self, CmpOp::Ult | CmpOp::Ule | CmpOp::Ugt | CmpOp::Uge)
+    }
+}
+
+/// A conversion between integer types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CastOp {
+    /// Widens, filling the new bits with zeros.
+    ZExt,
+    /// Widens, filling the new bits with copies of the sign bit.
+    SExt,
+    /// Narrows, dropping the high bits.
+    Trunc,
+}
+
+impl CastOp {
+    /// Converts the normalized `value` of type `from` to type `to`.
+    pub fn evaluate(self, from: IrType, to: IrType, value: i64) -> i64 {
+        match self {
+            CastOp::ZExt => from.as_unsigned(value) as i64,
+            CastOp::SExt => value,
+            CastOp::Trunc => to.normalize(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instr {
+    Const {
+        dst: Value,
+        value: i64,
+    },
+    Binary {
+        dst: Value,
+        op: BinOp,
+        lhs: Value,
+        rhs: Value,
+    },
+    /// Compares two values of the same type, producing an `i32` 1 or 0.
+    Cmp {
+        dst: Value,
+        op: CmpOp,
+        lhs: Value,
+        rhs: Value,
+    },
+    /// Converts `value` to the type of `dst`.
+    Cast {
+        dst: Value,
+        op: CastOp,
+        value: Value,
+    },
+    /// With `fixed` for a call to a variadic function: how many of `args`
+    /// go to its named parameters, the rest being its variable arguments.
+    Call {
+        dst: Option<Value>,
+        callee: String,
+        args: Vec<Value>,
+        fixed: Option<usize>,
+    },
+    Load {
+        dst: Value,
+        slot: StackSlot,
+    },
+    Store {
+        slot: StackSlot,
+        value: Value,
+    },
+    /// Reads a value of the type of `dst` from the address in `ptr`.
+    LoadPtr {
+        dst: Value,
+        ptr: Value,
+    },
+    /// Writes `value` to the address in `ptr`.
+    StorePtr {
+        ptr: Value,
+        value: Value,
+    },
+    /// The address of a stack slot, which stays valid until the function
+    /// returns.
+    SlotAddr {
+        dst: Value,
+        slot: StackSlot,
+    },
+    /// Reserves `size` bytes on the stack until the function returns, like
+    /// `alloca`. The block is 16-byte aligned.
+    Alloca {
+        dst: Value,
+        size: Value,
+    },
+    /// The address of a string in the module's pool.
+    StringAddr {
+        dst: Value,
+        string: StringId,
+    },
+    /// The address of a global variable, which may be defined in another
+    /// module, or of this thread's instance of it if it is thread-local.
+    GlobalAddr {
+        dst: Value,
+        name: String,
+        thread_local: bool,
+    },
+    /// Attributes the instructions that follow to `pos` in the source, for
+    /// debug info. Does nothing at run time.
+    Loc {
+        pos: Position,
+    },
+    /// Inline assembly. `%0` names the register holding `dst`, if there is
+    /// one, and the inputs follow. The template may clobber any register the
+    /// calling convention lets a call clobber.
+    InlineAsm {
+        dst: Option<Value>,
+        template: String,
+        inputs: Vec<Value>,
+    },
+}
+
+impl Instr {
+    pub fn dst(&self) -> Option<Value> {
+        match self {
+            Instr::Const { dst, .. }
+            | Instr::Binary { dst, .. }
+            | Instr::Cmp { dst, .. }
+            | Instr::Cast { dst, .. }
+            | Instr::Load { dst, .. }
+            | Instr::LoadPtr { dst, .. }
+            | Instr::SlotAddr { dst, .. }
+            | Instr::Alloca { dst, .. }
+            | Instr::StringAddr { dst, .. }
+            | Instr::GlobalAddr { dst, .. } => Some(*dst),
+            Instr::Call { dst, .. } | Instr::InlineAsm { dst, .. } => *dst,
+            Instr::Store { .. } | Instr::StorePtr { .. } | Instr::Loc { .. } => None,
+        }
+    }
+
+    pub fn operands(&self) -> Vec<Value> {
+        match self {
+            Instr::Const { .. }
+            | Instr::Load { .. }
+            | Instr::SlotAddr { .. }
+            | Instr::StringAddr { .. }
+            | Instr::GlobalAddr { .. }
+            | Instr::Loc { .. } => Vec::new(),
+            Instr::Binary { lhs, rhs, .. } | Instr::Cmp { lhs, rhs, .. } => vec![*lhs, *rhs],
+            Instr::StorePtr { ptr, value } => vec![*ptr, *value],
+            Instr::Call { args, .. } | Instr::InlineAsm { inputs: args, .. } => args.clone(),
+            Instr::Store { value, .. }
+            | Instr::LoadPtr { ptr: value, .. }
+            | Instr::Alloca { size: value, .. }
+            | Instr::Cast { value, .. } => vec![*value],
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Instr::Const { .. }
+            | Instr::Load { .. }
+            | Instr::SlotAddr { .. }
+            | Instr::StringAddr { .. }
+            | Instr::GlobalAddr { .. }
+            | Instr::Loc { .. } => Vec::new(),
+            Instr::Binary { lhs, rhs, .. } | Instr::Cmp { lhs, rhs, .. } => vec![lhs, rhs],
+            Instr::StorePtr { ptr, value } => vec![ptr, value],
+            Instr::Call { args, .. } | Instr::InlineAsm { inputs: args, .. } => {
+                args.iter_mut().collect()
+            }
+            Instr::Store { value, .. }
+            | Instr::LoadPtr { ptr: value, .. }
+            | Instr::Alloca { size: value, .. }
+            | Instr::Cast { value, .. } => vec![value],
+        }
+    }
+
+    /// Whether the instruction must be kept even if its result is unused.
+    pub fn has_side_effects(&self) -> bool {
+        matches!(
+            self,
+            Instr::Call { .. }
+                | Instr::Store { .. }
+                | Instr::StorePtr { .. }
+                | Instr::Alloca { .. }
+                | Instr::Loc { .. }
+                | Instr::InlineAsm { .. }
+        )
+    }
+
+    /// Whether the instruction may clobber the registers a call may. On
+    /// Mach-O a thread-local's address comes from calling its accessor.
+    pub fn clobbers_like_a_call(&self) -> bool {
+        matches!(
+            self,
+            Instr::Call { .. }
+                | Instr::InlineAsm { .. }
+                | Instr::GlobalAddr {
+                    thread_local: true,
+                    ..
+                }
+        )
+    }
+
+    /// Whether the instruction reads memory, so its result may differ between
+    /// two executions with the same operands.
+    pub fn reads_memory(&self) -> bool {
+        matches!(
+            self,
+            Instr::Call { .. }
+                | Instr::Load { .. }
+                | Instr::LoadPtr { .. }
+                | Instr::InlineAsm { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Terminator {
+    Ret(Option<Value>),
+    Jump(BlockId),
+    /// Transfers control to `then_block` if `cond` is non-zero.
+    Branch {
+        cond: Value,
+        then_block: BlockId,
+        else_block: BlockId,
+    },
+    /// Transfers control to the block of the case equal to `value`, or to
+    /// `default` if there is none. Case values are distinct.
+    Switch {
+        value: Value,
+        cases: Vec<(i64, BlockId)>,
+        default: BlockId,
+    },
+    /// Ends the program abnormally (`__builtin_trap`).
+    Trap,
+    /// Control never gets here; the backends trap in case it does.
+    Unreachable,
+}
+
+impl Terminator {
+    pub fn operands(&self) -> Vec<Value> {
+        match self {
+            Terminator::Ret(Some(value)) => vec![*value],
+            Terminator::Branch { cond, .. } => vec![*cond],
+            Terminator::Switch { value, .. } => vec![*value],
+            Terminator::Ret(None)
+            | Terminator::Jump(_)
+            | Terminator::Trap
+            | Terminator::Unreachable => Vec::new(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Terminator::Ret(Some(value)) => vec![value],
+            Terminator::Branch { cond, .. } => vec![cond],
+            Terminator::Switch { value, .. } => vec![value],
+            Terminator::Ret(None)
+            | Terminator::Jump(_)
+            | Terminator::Trap
+            | Terminator::Unreachable => Vec::new(),
+        }
+    }
+
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch {
+                then_block,
+                else_block,
+                ..
+            } => vec![*then_block, *else_block],
+            Terminator::Switch { cases, default, .. } => cases
+                .iter()
+                .map(|&(_, target)| target)
+                .chain([*default])
+                .collect(),
+            Terminator::Ret(_) | Terminator::Trap | Terminator::Unreachable => Vec::new(),
+        }
+    }
+
+    pub fn successors_mut(&mut self) -> Vec<&mut BlockId> {
+        match self {
+            Terminator::Jump(target) => vec![target],
+            Terminator::Branch {
+                then_block,
+                else_block,
+                ..
+            } => vec![then_block, else_block],
+            Terminator::Switch { cases, default, .. } => cases
+                .iter_mut()
+                .map(|(_, target)| target)
+                .chain([default])
+                .collect(),
+            Terminator::Ret(_) | Terminator::Trap | Terminator::Unreachable => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub instrs: Vec<Instr>,
+    pub term: Terminator,
+}
+
+impl Block {
+    fn new() -> Self {
+        Self {
+            instrs: Vec::new(),
+            term: Terminator::Unreachable,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<Value>,
+    pub return_type: Option<IrType>,
+    /// `blocks[0]` is the entry block; a `BlockId` is an index into this list.
+    pub blocks: Vec<Block>,
+    pub value_types: Vec<IrType>,
+    /// What each stack slot holds; a `StackSlot` is an index into this list.
+    pub slots: Vec<Slot>,
+    /// Where the function is defined, when compiling with debug info.
+    pub pos: Option<Position>,
+    /// Source variables to describe in debug info.
+    pub variables: Vec<DebugVariable>,
+}
+
+/// A named source variable and the stack slot holding it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugVariable {
+    pub name: String,
+    pub slot: StackSlot,
+    pub pos: Position,
+}
+
+impl Function {
+    pub fn new(name: impl Into<String>, return_type: Option<IrType>) -> Self {
+        Self {
+            name: name.into(),
+            params: Vec::new(),
+            return_type,
+            blocks: vec![Block::new()],
+            value_types: Vec::new(),
+            slots: Vec::new(),
+            pos: None,
+            variables: Vec::new(),
+        }
+    }
+
+    pub fn new_value(&mut self, ty: IrType) -> Value {
+        self.value_types.push(ty);
+        Value(self.value_types.len() as u32 - 1)
+    }
+
+    pub fn value_type(&self, value: Value) -> IrType {
+        self.value_types[value.0 as usize]
+    }
+
+    pub fn new_slot(&mut self, slot: Slot) -> StackSlot {
+        self.slots.push(slot);
+        StackSlot(self.slots.len() as u32 - 1)
+    }
+
+    /// The type of the slot's value, or of each of its elements.
+    pub fn slot_type(&self, slot: StackSlot) -> IrType {
+        self.slots[slot.0 as usize].ty
+    }
+
+    pub fn add_block(&mut self) -> BlockId {
+        self.blocks.push(Block::new());
+        BlockId(self.blocks.len() as u32 - 1)
+    }
+
+    pub fn block(&self, id: BlockId) -> &Block {
+        &self.blocks[id.index()]
+    }
+
+    pub fn block_mut(&mut self, id: BlockId) -> &mut Block {
+        &mut self.blocks[id.index()]
+    }
+
+    pub fn block_ids(&self) -> impl DoubleEndedIterator<Item = BlockId> {
+        (0..self.blocks.len() as u32).map(BlockId)
+    }
+
+    /// Rewrites every use of `from` to refer to `to` instead.
+    pub fn replace_uses(&mut self, from: Value, to: Value) {
+        for block in &mut self.blocks {
+            let operands = block
+                .instrs
+                .iter_mut()
+                .flat_map(Instr::operands_mut)
+                .chain(block.term.operands_mut());
+            for operand in operands {
+                if *operand == from {
+                    *operand = to;
+                }
+            }
+        }
+    }
+
+    /// Drops blocks that cannot be reached from the entry and renumbers the rest.
+    pub fn remove_unreachable_blocks(&mut self) {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut worklist = vec![BlockId::ENTRY];
+        while let Some(id) = worklist.pop() {
+            if std::mem::replace(&mut reachable[id.index()], true) {
+                continue;
+            }
+            worklist.extend(self.block(id).term.successors());
+        }
+
+        let mut renumbered = Vec::with_capacity(self.blocks.len());
+        let mut next = 0;
+        for &live in &reachable {
+            renumbered.push(BlockId(next));
+            next += u32::from(live);
+        }
+
+        let blocks = std::mem::take(&mut self.blocks);
+        self.blocks = blocks
+            .into_iter()
+            .zip(&reachable)
+            .filter_map(|(block, &live)| live.then_some(block))
+            .collect();
+        for block in &mut self.blocks {
+            for target in block.term.successors_mut() {
+                *target = renumbered[target.index()];
+            }
+        }
+    }
+}
+
+/// A global variable: `len` elements of type `ty`, one unless it is an
+/// array. The first ones start out as `init` says and the rest zero.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Global {
+    pub name: String,
+    pub ty: IrType,
+    pub len: u32,
+    pub init: Vec<Datum>,
+    /// Whether each thread has its own instance (`_Thread_local`).
+    pub thread_local: bool,
+}
+
+impl Global {
+    pub fn size(&self) -> u32 {
+        self.ty.bytes() * self.len
+    }
+
+    /// Whether every byte starts out zero, so the object can live in `.bss`.
+    pub fn is_zero(&self) -> bool {
+        self.init.iter().all(|datum| *datum == Datum::Int(0))
+    }
+}
+
+/// The starting value of an element of a global. Addresses are resolved
+/// when the program is linked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Datum {
+    Int(i64),
+    /// The address of a pool string, `offset` bytes in.
+    String {
+        string: StringId,
+        offset: i64,
+    },
+    /// The address of the global `name`, `offset` bytes in.
+    Global {
+        name: String,
+        offset: i64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StringId(pub u32);
+
+/// A module's string literals, each stored once.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StringPool {
+    strings: Vec<Vec<u8>>,
+}
+
+impl StringPool {
+    /// Adds `bytes`, without the terminating NUL, unless an identical string
+    /// is already in the pool.
+    pub fn intern(&mut self, bytes: &[u8]) -> StringId {
+        let index = match self.strings.iter().position(|s| s == bytes) {
+            Some(index) => index,
+            None => {
+                self.strings.push(bytes.to_vec());
+                self.strings.len() - 1
+            }
+        };
+        StringId(index as u32)
+    }
+
+    pub fn get(&self, id: StringId) -> Option<&[u8]> {
+        self.strings.get(id.0 as usize).map(Vec::as_slice)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (StringId, &[u8])> {
+        self.strings
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (StringId(i as u32), s.as_slice()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Module {
+    pub functions: Vec<Function>,
+    pub globals: Vec<Global>,
+    pub strings: StringPool,
+}
+
+/// Appends instructions to a function under construction.
+pub struct FunctionBuilder {
+    func: Function,
+    current: BlockId,
+}
+
+impl FunctionBuilder {
+    pub fn new(name: impl Into<String>, return_type: Option<IrType>) -> Self {
+        Self {
+            func: Function::new(name, return_type),
+            current: BlockId::ENTRY,
+        }
+    }
+
+    pub fn param(&mut self, ty: IrType) -> Value {
+        let value = self.func.new_value(ty);
+        self.func.params.push(value);
+        value
+    }
+
+    pub fn create_block(&mut self) -> BlockId {
+        self.func.add_block()
+    }
+
+    pub fn switch_to(&mut self, block: BlockId) {
+        self.current = block;
+    }
+
+    pub fn current_block(&self) -> BlockId {
+        self.current
+    }
+
+    pub fn return_type(&self) -> Option<IrType> {
+        self.func.return_type
+    }
+
+    /// Whether the current block already ends in a terminator.
+    pub fn is_terminated(&self) -> bool {
+        self.func.block(self.current).term != Terminator::Unreachable
+    }
+
+    pub fn iconst(&mut self, ty: IrType, value: i64) -> Value {
+        let dst = self.func.new_value(ty);
+        self.push(Instr::Const {
+            dst,
+            value: ty.normalize(value),
+        });
+        dst
+    }
+
+    pub fn binary(&mut self, op: BinOp, lhs: Value, rhs: Value) -> Value {
+        let dst = self.func.new_value(self.func.value_type(lhs));
+        self.push(Instr::Binary { dst, op, lhs, rhs });
+        dst
+    }
+
+    pub fn cmp(&mut self, op: CmpOp, lhs: Value, rhs: Value) -> Value {
+        let dst = self.func.new_value(IrType::I32);
+        self.push(Instr::Cmp { dst, op, lhs, rhs });
+        dst
+    }
+
+    pub fn call(
+        &mut self,
+        callee: impl Into<String>,
+        args: Vec<Value>,
+        return_type: Option<IrType>,
+    ) -> Option<Value> {
+        self.call_variadic(callee, args, None, return_type)
+    }
+
+    /// A call that, if `fixed` is given, passes the arguments after the
+    /// first `fixed` as variable arguments.
+    pub fn call_variadic(
+        &mut self,
+        callee: impl Into<String>,
+        args: Vec<Value>,
+        fixed: Option<usize>,
+        return_type: Option<IrType>,
+    ) -> Option<Value> {
+        let dst = return_type.map(|ty| self.func.new_value(ty));
+        self.push(Instr::Call {
+            dst,
+            callee: callee.into(),
+            args,
+            fixed,
+        });
+        dst
+    }
+
+    pub fn stack_slot(&mut self, ty: IrType) -> StackSlot {
+        self.func.new_slot(Slot::scalar(ty))
+    }
+
+    /// A slot for `len` consecutive `ty`s.
+    pub fn array_slot(&mut self, ty: IrType, len: u32) -> StackSlot {
+        self.func.new_slot(Slot::array(ty, len))
+    }
+
+    pub fn load(&mut self, slot: StackSlot) -> Value {
+        let dst = self.func.new_value(self.func.slot_type(slot));
+        self.push(Instr::Load { dst, slot });
+        dst
+    }
+
+    pub fn string_addr(&mut self, string: StringId) -> Value {
+        let dst = self.func.new_value(IrType::Ptr);
+        self.push(Instr::StringAddr { dst, string });
+        dst
+    }
+
+    pub fn cast(&mut self, op: CastOp, value: Value, ty: IrType) -> Value {
+        let dst = self.func.new_value(ty);
+        self.push(Instr::Cast { dst, op, value });
+        dst
+    }
+
+    pub fn alloca(&mut self, size: Value) -> Value {
+        let dst = self.func.new_value(IrType::Ptr);
+        self.push(Instr::Alloca { dst, size });
+        dst
+    }
+
+    pub fn global_addr(&mut self, name: impl Into<String>) -> Value {
+        let dst = self.func.new_value(IrType::Ptr);
+        self.push(Instr::GlobalAddr {
+            dst,
+            name: name.into(),
+            thread_local: false,
+        });
+        dst
+    }
+
+    /// The address of this thread's instance of the thread-local `name`.
+    pub fn thread_local_addr(&mut self, name: impl Into<String>) -> Value {
+        let dst = self.func.new_value(IrType::Ptr);
+        self.push(Instr::GlobalAddr {
+            dst,
+            name: name.into(),
+            thread_local: true,
+        });
+        dst
+    }
+
+    pub fn store(&mut self, slot: StackSlot, value: Value) {
+        self.push(Instr::Store { slot, value });
+    }
+
+    pub fn load_ptr(&mut self, ty: IrType, ptr: Value) -> Value {
+        let dst = self.func.new_value(ty);
+        self.push(Instr::LoadPtr { dst, ptr });
+        dst
+    }
+
+    pub fn store_ptr(&mut self, ptr: Value, value: Value) {
+        self.push(Instr::StorePtr { ptr, value });
+    }
+
+    pub fn slot_addr(&mut self, slot: StackSlot) -> Value {
+        let dst = self.func.new_value(IrType::Ptr);
+        self.push(Instr::SlotAddr { dst, slot });
+        dst
+    }
+
+    pub fn inline_asm(
+        &mut self,
+        template: impl Into<String>,
+        inputs: Vec<Value>,
+        output: Option<IrType>,
+    ) -> Option<Value> {
+        let dst = output.map(|ty| self.func.new_value(ty));
+        self.push(Instr::InlineAsm {
+            dst,
+            template: template.into(),
+            inputs,
+        });
+        dst
+    }
+
+    pub fn loc(&mut self, pos: Position) {
+        self.push(Instr::Loc { pos });
+    }
+
+    pub fn set_pos(&mut self, pos: Position) {
+        self.func.pos = Some(pos);
+    }
+
+    /// Describes `slot` as the variable `name` in debug info.
+    pub fn variable(&mut self, name: impl Into<String>, slot: StackSlot, pos: Position) {
+        self.func.variables.push(DebugVariable {
+            name: name.into(),
+            slot,
+            pos,
+        });
+    }
+
+    pub fn ret(&mut self, value: Option<Value>) {
+        self.terminate(Terminator::Ret(value));
+    }
+
+    pub fn jump(&mut self, target: BlockId) {
+        self.terminate(Terminator::Jump(target));
+    }
+
+    pub fn branch(&mut self, cond: Value, then_block: BlockId, else_block: BlockId) {
+        self.terminate(Terminator::Branch {
+            cond,
+            then_block,
+            else_block,
+        });
+    }
+
+    pub fn trap(&mut self) {
+        self.terminate(Terminator::Trap);
+    }
+
+    pub fn switch(&mut self, value: Value, cases: Vec<(i64, BlockId)>, default: BlockId) {
+        self.terminate(Terminator::Switch {
+            value,
+            cases,
+            default,
+        });
+    }
+
+    pub fn finish(mut self) -> Function {
+        self.func.remove_unreachable_blocks();
+        self.func
+    }
+
+    fn push(&mut self, instr: Instr) {
+        self.func.block_mut(self.current).instrs.push(instr);
+    }
+
+    fn terminate(&mut self, term: Terminator) {
+        self.func.block_mut(self.current).term = term;
+    }
+}
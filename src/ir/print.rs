@@ -0,0 +1,321 @@
+//! Stable textual form of the IR, used by `--emit=ir` and snapshot tests.
+//!
+//! ```text
+//! fn add3(i32 %0) -> i32 {
+//!     ss0 = stack_slot i32
+//! bb0:
+//!     %1 = const i32 3
+//!     %2 = add i32 %0, %1
+//!     store ss0, %2
+//!     %3 = load i32 ss0
+//!     ret %3
+//! }
+//! ```
+//!
+//! Pool strings and globals come first, one per line: `str0 = string "hi\n"`
+//! and `@count = global i32 0`.
+
+use std::fmt;
+
+use crate::ir::{
+    BinOp, BlockId, CastOp, CmpOp, Datum, Function, Global, Instr, IrType, Module, Slot, StackSlot,
+    StringId, Terminator, Value,
+};
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+impl fmt::Display for StringId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "str{}", self.0)
+    }
+}
+
+impl fmt::Display for StackSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ss{}", self.0)
+    }
+}
+
+impl fmt::Display for IrType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            IrType::I8 => "i8",
+            IrType::I16 => "i16",
+            IrType::I32 => "i32",
+            IrType::Ptr => "ptr",
+        })
+    }
+}
+
+/// `i32`, or `[4 x i32]` for an array.
+impl fmt::Display for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_array() {
+            write!(f, "[{} x {}]", self.len, self.ty)
+        } else {
+            write!(f, "{}", self.ty)
+        }
+    }
+}
+
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BinOp::Add => "add",
+            BinOp::Sub => "sub",
+            BinOp::Mul => "mul",
+            BinOp::SDiv => "sdiv",
+            BinOp::UDiv => "udiv",
+            BinOp::SRem => "srem",
+            BinOp::URem => "urem",
+            BinOp::And => "and",
+            BinOp::Or => "or",
+            BinOp::Xor => "xor",
+            BinOp::Shl => "shl",
+            BinOp::AShr => "ashr",
+            BinOp::LShr => "lshr",
+        })
+    }
+}
+
+impl fmt::Display for CastOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CastOp::ZExt => "zext",
+            CastOp::SExt => "sext",
+            CastOp::Trunc => "trunc",
+        })
+    }
+}
+
+impl fmt::Display for CmpOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CmpOp::Eq => "eq",
+            CmpOp::Ne => "ne",
+            CmpOp::Slt => "slt",
+            CmpOp::Sle => "sle",
+            CmpOp::Sgt => "sgt",
+            CmpOp::Sge => "sge",
+            CmpOp::Ult => "ult",
+            CmpOp::Ule => "ule",
+            CmpOp::Ugt => "ugt",
+            CmpOp::Uge => "uge",
+        })
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (id, bytes) in self.strings.iter() {
+            writeln!(f, "{id} = string \"{}\"", bytes.escape_ascii())?;
+        }
+        for global in &self.globals {
+            writeln!(f, "{global}")?;
+        }
+        let header = !self.strings.is_empty() || !self.globals.is_empty();
+        for (i, function) in self.functions.iter().enumerate() {
+            if i > 0 || header {
+                writeln!(f)?;
+            }
+            write!(f, "{function}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Global {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tls = if self.thread_local {
+            "thread_local "
+        } else {
+            ""
+        };
+        write!(f, "@{} = {tls}global ", self.name)?;
+        if self.len == 1 {
+            write!(f, "{}", self.ty)?;
+            match self.init.first() {
+                Some(datum) => write!(f, " {datum}"),
+                None => Ok(()),
+            }
+        } else {
+            write!(f, "[{} x {}]", self.len, self.ty)?;
+            if self.init.is_empty() {
+                return Ok(());
+            }
+            f.write_str(" [")?;
+            for (i, datum) in self.init.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{datum}")?;
+            }
+            f.write_str("]")
+        }
+    }
+}
+
+impl fmt::Display for Datum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Datum::Int(value) => write!(f, "{value}"),
+            Datum::String { string, offset: 0 } => write!(f, "{string}"),
+            Datum::String { string, offset } => write!(f, "{string}{offset:+}"),
+            Datum::Global { name, offset: 0 } => write!(f, "@{name}"),
+            Datum::Global { name, offset } => write!(f, "@{name}{offset:+}"),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fn {}(", self.name)?;
+        for (i, param) in self.params.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{} {param}", self.value_type(*param))?;
+        }
+        f.write_str(")")?;
+        if let Some(ty) = self.return_type {
+            write!(f, " -> {ty}")?;
+        }
+        writeln!(f, " {{")?;
+        for (i, ty) in self.slots.iter().enumerate() {
+            writeln!(f, "    {} = stack_slot {ty}", StackSlot(i as u32))?;
+        }
+        for (id, block) in self.block_ids().zip(&self.blocks) {
+            writeln!(f, "{id}:")?;
+            for instr in &block.instrs {
+                f.write_str("    ")?;
+                self.fmt_instr(instr, f)?;
+                writeln!(f)?;
+            }
+            writeln!(f, "    {}", block.term)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl Function {
+    fn fmt_instr(&self, instr: &Instr, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match instr {
+            Instr::Const { dst, value } => {
+                write!(f, "{dst} = const {} {value}", self.value_type(*dst))
+            }
+            Instr::Binary { dst, op, lhs, rhs } => {
+                write!(f, "{dst} = {op} {} {lhs}, {rhs}", self.value_type(*dst))
+            }
+            Instr::Cmp { dst, op, lhs, rhs } => {
+                write!(
+                    f,
+                    "{dst} = icmp {op} {} {lhs}, {rhs}",
+                    self.value_type(*lhs)
+                )
+            }
+            Instr::Cast { dst, op, value } => write!(
+                f,
+                "{dst} = {op} {} {value} to {}",
+                self.value_type(*value),
+                self.value_type(*dst)
+            ),
+            // Variable arguments follow a `...`, as in the prototype.
+            Instr::Call {
+                dst,
+                callee,
+                args,
+                fixed,
+            } => {
+                match dst {
+                    Some(dst) => write!(f, "{dst} = call {} ", self.value_type(*dst))?,
+                    None => f.write_str("call void ")?,
+                }
+                write!(f, "@{callee}(")?;
+                let mut args: Vec<String> = args.iter().map(Value::to_string).collect();
+                if let Some(fixed) = *fixed {
+                    args.insert(fixed, "...".to_string());
+                }
+                write!(f, "{})", args.join(", "))
+            }
+            Instr::Load { dst, slot } => {
+                write!(f, "{dst} = load {} {slot}", self.value_type(*dst))
+            }
+            Instr::Store { slot, value } => write!(f, "store {slot}, {value}"),
+            Instr::LoadPtr { dst, ptr } => {
+                write!(f, "{dst} = load {} [{ptr}]", self.value_type(*dst))
+            }
+            Instr::StorePtr { ptr, value } => write!(f, "store [{ptr}], {value}"),
+            Instr::SlotAddr { dst, slot } => write!(f, "{dst} = addr ptr {slot}"),
+            Instr::Alloca { dst, size } => write!(f, "{dst} = alloca ptr {size}"),
+            Instr::StringAddr { dst, string } => write!(f, "{dst} = addr ptr {string}"),
+            Instr::GlobalAddr {
+                dst,
+                name,
+                thread_local,
+            } => {
+                let tls = if *thread_local { "thread_local " } else { "" };
+                write!(f, "{dst} = addr ptr {tls}@{name}")
+            }
+            Instr::Loc { pos } => write!(f, "loc {pos}"),
+            Instr::InlineAsm {
+                dst,
+                template,
+                inputs,
+            } => {
+                match dst {
+                    Some(dst) => write!(f, "{dst} = asm {} ", self.value_type(*dst))?,
+                    None => f.write_str("asm void ")?,
+                }
+                write!(f, "{template:?}(")?;
+                for (i, input) in inputs.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{input}")?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Terminator::Ret(Some(value)) => write!(f, "ret {value}"),
+            Terminator::Ret(None) => f.write_str("ret"),
+            Terminator::Jump(target) => write!(f, "jump {target}"),
+            Terminator::Branch {
+                cond,
+                then_block,
+                else_block,
+            } => write!(f, "br {cond}, {then_block}, {else_block}"),
+            Terminator::Switch {
+                value,
+                cases,
+                default,
+            } => {
+                write!(f, "switch {value}, {default} [")?;
+                for (i, (case, target)) in cases.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{case}: {target}")?;
+                }
+                f.write_str("]")
+            }
+            Terminator::Trap => f.write_str("trap"),
+            Terminator::Unreachable => f.write_str("unreachable"),
+        }
+    }
+}
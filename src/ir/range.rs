@@ -0,0 +1,223 @@
+//! Integer range analysis: a conservative `[min, max]` interval for every value.
+//!
+//! Each value is defined exactly once, so the range of a definition only
+//! depends on the ranges of its operands. Parameters and call results are
+//! unknown and get the full range of their type.
+
+use std::collections::HashMap;
+
+use crate::ir::{BinOp, CastOp, CmpOp, Function, Instr, IrType, Value};
+
+/// An inclusive interval of signed values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub min: i64,
+    pub max: i64,
+}
+
+impl Range {
+    pub fn constant(value: i64) -> Self {
+        Self {
+            min: value,
+            max: value,
+        }
+    }
+
+    /// Every value representable in `ty`.
+    pub fn full(ty: IrType) -> Self {
+        Self {
+            min: ty.min_signed(),
+            max: -(ty.min_signed() + 1),
+        }
+    }
+
+    pub fn contains(self, value: i64) -> bool {
+        self.min <= value && value <= self.max
+    }
+
+    pub fn is_non_negative(self) -> bool {
+        self.min >= 0
+    }
+
+    /// The smallest range covering all `bounds`, or the full range of `ty` if
+    /// any of them overflows it.
+    fn spanning(ty: IrType, bounds: impl IntoIterator<Item = i128>) -> Self {
+        let full = Self::full(ty);
+        let mut bounds = bounds.into_iter();
+        let first = bounds.next().expect("at least one bound");
+        let (min, max) = bounds.fold((first, first), |(lo, hi), b| (lo.min(b), hi.max(b)));
+        if min < i128::from(full.min) || max > i128::from(full.max) {
+            return full;
+        }
+        Self {
+            min: min as i64,
+            max: max as i64,
+        }
+    }
+}
+
+/// The range of every value in a function.
+#[derive(Debug, Clone)]
+pub struct Ranges {
+    ranges: HashMap<Value, Range>,
+}
+
+impl Ranges {
+    pub fn compute(func: &Function) -> Self {
+        let mut ranges = HashMap::new();
+        for &param in &func.params {
+            ranges.insert(param, Range::full(func.value_type(param)));
+        }
+        // Blocks are not necessarily in dominance order, so iterate until every
+        // operand has been seen; each value settles the first time it is computed.
+        loop {
+            let mut progress = false;
+            for instr in func.blocks.iter().flat_map(|b| &b.instrs) {
+                let Some(dst) = instr.dst() else {
+                    continue;
+                };
+                if ranges.contains_key(&dst) {
+                    continue;
+                }
+                let ty = func.value_type(dst);
+                let range = match *instr {
+                    Instr::Const { value, .. } => Range::constant(value),
+                    Instr::Binary { op, lhs, rhs, .. } => {
+                        let (Some(&lhs), Some(&rhs)) = (ranges.get(&lhs), ranges.get(&rhs)) else {
+                            continue;
+                        };
+                        binary(op, ty, lhs, rhs)
+                    }
+                    Instr::Cast { op, value, .. } => {
+                        let Some(&range) = ranges.get(&value) else {
+                            continue;
+                        };
+                        cast(op, func.value_type(value), ty, range)
+                    }
+                    Instr::Cmp { op, lhs, rhs, .. } => {
+                        let (Some(&lhs), Some(&rhs)) = (ranges.get(&lhs), ranges.get(&rhs)) else {
+                            continue;
+                        };
+                        match compare(op, lhs, rhs) {
+                            Some(result) => Range::constant(i64::from(result)),
+                            None => Range { min: 0, max: 1 },
+                        }
+                    }
+                    Instr::Call { .. }
+                    | Instr::Load { .. }
+                    | Instr::Store { .. }
+                    | Instr::LoadPtr { .. }
+                    | Instr::StorePtr { .. }
+                    | Instr::SlotAddr { .. }
+                    | Instr::Alloca { .. }
+                    | Instr::StringAddr { .. }
+                    | Instr::GlobalAddr { .. }
+                    | Instr::Loc { .. }
+                    | Instr::InlineAsm { .. } => Range::full(ty),
+                };
+                ranges.insert(dst, range);
+                progress = true;
+            }
+            if !progress {
+                return Self { ranges };
+            }
+        }
+    }
+
+    /// The range of `value`, or its type's full range if an operand was never
+    /// defined, which only happens in malformed IR.
+    pub fn range(&self, func: &Function, value: Value) -> Range {
+        self.ranges
+            .get(&value)
+            .copied()
+            .unwrap_or_else(|| Range::full(func.value_type(value)))
+    }
+}
+
+/// The outcome of comparing any value in `lhs` with any value in `rhs`, if
+/// it is always the same.
+fn compare(op: CmpOp, lhs: Range, rhs: Range) -> Option<bool> {
+    // Unsigned comparisons order negative values above positive ones.
+    if op.is_unsigned() && !(lhs.is_non_negative() && rhs.is_non_negative()) {
+        return None;
+    }
+    match op {
+        CmpOp::Eq if lhs.min == lhs.max && lhs == rhs => Some(true),
+        CmpOp::Eq if lhs.max < rhs.min || rhs.max < lhs.min => Some(false),
+        CmpOp::Eq => None,
+        CmpOp::Ne => compare(CmpOp::Eq, lhs, rhs).map(|equal| !equal),
+        CmpOp::Slt | CmpOp::Ult if lhs.max < rhs.min => Some(true),
+        CmpOp::Slt | CmpOp::Ult if lhs.min >= rhs.max => Some(false),
+        CmpOp::Sle | CmpOp::Ule if lhs.max <= rhs.min => Some(true),
+        CmpOp::Sle | CmpOp::Ule if lhs.min > rhs.max => Some(false),
+        CmpOp::Slt | CmpOp::Ult | CmpOp::Sle | CmpOp::Ule => None,
+        CmpOp::Sgt | CmpOp::Ugt | CmpOp::Sge | CmpOp::Uge => compare(op.swapped(), rhs, lhs),
+    }
+}
+
+/// The range of `op` applied to a value of type `from` in `range`.
+fn cast(op: CastOp, from: IrType, to: IrType, range: Range) -> Range {
+    match op {
+        CastOp::SExt => range,
+        CastOp::ZExt if range.is_non_negative() => range,
+        CastOp::ZExt => Range {
+            min: 0,
+            max: from.as_unsigned(-1) as i64,
+        },
+        CastOp::Trunc => {
+            let full = Range::full(to);
+            if full.contains(range.min) && full.contains(range.max) {
+                range
+            } else {
+                full
+            }
+        }
+    }
+}
+
+fn binary(op: BinOp, ty: IrType, lhs: Range, rhs: Range) -> Range {
+    let full = Range::full(ty);
+    let (a, b) = (i128::from(lhs.min), i128::from(lhs.max));
+    let (c, d) = (i128::from(rhs.min), i128::from(rhs.max));
+    match op {
+        BinOp::Add => Range::spanning(ty, [a + c, b + d]),
+        BinOp::Sub => Range::spanning(ty, [a - d, b - c]),
+        BinOp::Mul => Range::spanning(ty, [a * c, a * d, b * c, b * d]),
+        BinOp::SDiv if rhs.min > 0 || rhs.max < 0 => {
+            Range::spanning(ty, [a / c, a / d, b / c, b / d])
+        }
+        BinOp::SRem if !rhs.contains(0) => {
+            let bound = c.abs().max(d.abs()) - 1;
+            let lo = if lhs.is_non_negative() {
+                0
+            } else {
+                -bound.min(-a)
+            };
+            let hi = if lhs.max < 0 { 0 } else { bound.min(b) };
+            Range::spanning(ty, [lo, hi])
+        }
+        BinOp::UDiv if lhs.is_non_negative() && rhs.min > 0 => Range::spanning(ty, [a / d, b / c]),
+        BinOp::URem if lhs.is_non_negative() && rhs.min > 0 => {
+            Range::spanning(ty, [0, b.min(d - 1)])
+        }
+        BinOp::And if lhs.is_non_negative() || rhs.is_non_negative() => {
+            let max = match (lhs.is_non_negative(), rhs.is_non_negative()) {
+                (true, true) => b.min(d),
+                (true, false) => b,
+                _ => d,
+            };
+            Range::spanning(ty, [0, max])
+        }
+        BinOp::Shl | BinOp::AShr | BinOp::LShr
+            if rhs.min >= 0 && rhs.max < i64::from(ty.bits()) =>
+        {
+            match op {
+                BinOp::Shl => Range::spanning(ty, [a << c, a << d, b << c, b << d]),
+                BinOp::AShr => Range::spanning(ty, [a >> c, a >> d, b >> c, b >> d]),
+                _ if lhs.is_non_negative() => Range::spanning(ty, [a >> d, b >> c]),
+                _ => full,
+            }
+        }
+        _ => full,
+    }
+}
@@ -0,0 +1,291 @@
+//! Structural checks on the IR, used to catch optimizer bugs early.
+//!
+//! The verifier checks that branch targets exist, every value is defined
+//! once and dominates its uses, operand, comparison and stack slot types
+//! agree, arithmetic and conversions use suitable types, returns match the
+//! function's signature, calls within the module
+//! pass the right number of arguments and string addresses name a string in
+//! the module's pool.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::ir::cfg::Dominators;
+use crate::ir::{BlockId, CastOp, Function, Instr, IrType, Module, Terminator, Value};
+
+/// A broken invariant, with enough context to find it in the printed IR.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyError {
+    pub function: String,
+    pub block: Option<BlockId>,
+    pub message: String,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "in function `{}`", self.function)?;
+        if let Some(block) = self.block {
+            write!(f, ", {block}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+pub fn verify_module(module: &Module) -> Result<(), VerifyError> {
+    let signatures: HashMap<&str, (usize, bool)> = module
+        .functions
+        .iter()
+        .map(|f| (f.name.as_str(), (f.params.len(), f.return_type.is_some())))
+        .collect();
+    for func in &module.functions {
+        verify_function(func)?;
+        for (id, block) in func.block_ids().zip(&func.blocks) {
+            let error = |message| VerifyError {
+                function: func.name.clone(),
+                block: Some(id),
+                message,
+            };
+            for instr in &block.instrs {
+                if let Instr::StringAddr { string, .. } = instr {
+                    if module.strings.get(*string).is_none() {
+                        return Err(error(format!("{string} is not in the string pool")));
+                    }
+                }
+                let Instr::Call {
+                    dst, callee, args, ..
+                } = instr
+                else {
+                    continue;
+                };
+                let Some(&(arity, returns)) = signatures.get(callee.as_str()) else {
+                    continue;
+                };
+                if args.len() != arity {
+                    return Err(error(format!(
+                        "call to @{callee} passes {} arguments, expected {arity}",
+                        args.len()
+                    )));
+                }
+                if dst.is_some() != returns {
+                    return Err(error(format!(
+                        "call to @{callee} does not match its return type"
+                    )));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn verify_function(func: &Function) -> Result<(), VerifyError> {
+    Verifier {
+        func,
+        block: None,
+        defs: HashMap::new(),
+    }
+    .run()
+}
+
+struct Verifier<'a> {
+    func: &'a Function,
+    block: Option<BlockId>,
+    /// Where each value is defined: its block and instruction index, with
+    /// parameters at index 0 of the entry block.
+    defs: HashMap<Value, (BlockId, usize)>,
+}
+
+impl Verifier<'_> {
+    fn run(mut self) -> Result<(), VerifyError> {
+        let func = self.func;
+        if func.blocks.is_empty() {
+            return Err(self.error("function has no blocks"));
+        }
+        for (id, block) in func.block_ids().zip(&func.blocks) {
+            self.block = Some(id);
+            for target in block.term.successors() {
+                if target.index() >= func.blocks.len() {
+                    return Err(self.error(format!("jump to nonexistent block {target}")));
+                }
+            }
+        }
+
+        self.block = None;
+        for &param in &func.params {
+            self.define(param, BlockId::ENTRY, 0)?;
+        }
+        for (id, block) in func.block_ids().zip(&func.blocks) {
+            self.block = Some(id);
+            for (index, instr) in block.instrs.iter().enumerate() {
+                if let Some(dst) = instr.dst() {
+                    // Definitions sit after the parameters in the entry block.
+                    self.define(dst, id, index + 1)?;
+                }
+            }
+        }
+
+        let doms = Dominators::compute(func);
+        for (id, block) in func.block_ids().zip(&func.blocks) {
+            if !doms.is_reachable(id) {
+                continue;
+            }
+            self.block = Some(id);
+            for (index, instr) in block.instrs.iter().enumerate() {
+                for operand in instr.operands() {
+                    self.check_use(&doms, operand, id, index + 1)?;
+                }
+                self.check_types(instr)?;
+            }
+            for operand in block.term.operands() {
+                self.check_use(&doms, operand, id, block.instrs.len() + 1)?;
+            }
+            self.check_terminator(&block.term)?;
+        }
+        Ok(())
+    }
+
+    fn define(&mut self, value: Value, block: BlockId, index: usize) -> Result<(), VerifyError> {
+        if value.0 as usize >= self.func.value_types.len() {
+            return Err(self.error(format!("{value} has no type")));
+        }
+        if self.defs.insert(value, (block, index)).is_some() {
+            return Err(self.error(format!("{value} is defined more than once")));
+        }
+        Ok(())
+    }
+
+    fn check_use(
+        &self,
+        doms: &Dominators,
+        value: Value,
+        block: BlockId,
+        index: usize,
+    ) -> Result<(), VerifyError> {
+        let Some(&(def_block, def_index)) = self.defs.get(&value) else {
+            return Err(self.error(format!("use of undefined value {value}")));
+        };
+        let dominated = if def_block == block {
+            def_index < index
+        } else {
+            doms.dominates(def_block, block)
+        };
+        if dominated {
+            Ok(())
+        } else {
+            Err(self.error(format!(
+                "use of {value} is not dominated by its definition in {def_block}"
+            )))
+        }
+    }
+
+    fn check_types(&self, instr: &Instr) -> Result<(), VerifyError> {
+        let (value, slot) = match *instr {
+            Instr::Binary { dst, op, lhs, rhs } => {
+                let ty = self.func.value_type(dst);
+                if self.func.value_type(lhs) != ty || self.func.value_type(rhs) != ty {
+                    return Err(self.error(format!("operand types of {dst} = {op} do not match")));
+                }
+                if ty.is_narrow() {
+                    return Err(self.error(format!("{dst} = {op} operates on narrow {ty}")));
+                }
+                return Ok(());
+            }
+            Instr::Cmp { dst, op, lhs, rhs } => {
+                let ty = self.func.value_type(lhs);
+                if self.func.value_type(rhs) != ty {
+                    return Err(
+                        self.error(format!("operand types of {dst} = icmp {op} do not match"))
+                    );
+                }
+                if ty.is_narrow() {
+                    return Err(self.error(format!("{dst} = icmp {op} compares narrow {ty}")));
+                }
+                if self.func.value_type(dst) != IrType::I32 {
+                    return Err(self.error(format!("result of {dst} = icmp {op} is not i32")));
+                }
+                return Ok(());
+            }
+            Instr::Cast { dst, op, value } => {
+                let (from, to) = (self.func.value_type(value), self.func.value_type(dst));
+                let valid = match op {
+                    CastOp::ZExt | CastOp::SExt => from.bits() < to.bits(),
+                    CastOp::Trunc => from.bits() > to.bits(),
+                };
+                if !valid {
+                    return Err(self.error(format!("{dst} = {op} cannot convert {from} to {to}")));
+                }
+                return Ok(());
+            }
+            Instr::Load { dst, slot } => (dst, slot),
+            Instr::Store { slot, value } => (value, slot),
+            Instr::LoadPtr { ptr, .. } | Instr::StorePtr { ptr, .. } => {
+                if self.func.value_type(ptr) != IrType::Ptr {
+                    return Err(self.error(format!("{ptr} is dereferenced but is not ptr")));
+                }
+                return Ok(());
+            }
+            Instr::SlotAddr { dst, slot } => {
+                if self.func.value_type(dst) != IrType::Ptr {
+                    return Err(self.error(format!("{dst} holds an address but is not ptr")));
+                }
+                if slot.0 as usize >= self.func.slots.len() {
+                    return Err(self.error(format!("access to nonexistent stack slot {slot}")));
+                }
+                return Ok(());
+            }
+            Instr::Alloca { dst, size } => {
+                if self.func.value_type(dst) != IrType::Ptr {
+                    return Err(self.error(format!("{dst} holds an address but is not ptr")));
+                }
+                if self.func.value_type(size) != IrType::I32 {
+                    return Err(self.error(format!("size of {dst} = alloca is not i32")));
+                }
+                return Ok(());
+            }
+            Instr::StringAddr { dst, .. } | Instr::GlobalAddr { dst, .. } => {
+                if self.func.value_type(dst) != IrType::Ptr {
+                    return Err(self.error(format!("{dst} holds an address but is not ptr")));
+                }
+                return Ok(());
+            }
+            Instr::Const { .. }
+            | Instr::Call { .. }
+            | Instr::Loc { .. }
+            | Instr::InlineAsm { .. } => return Ok(()),
+        };
+        if slot.0 as usize >= self.func.slots.len() {
+            return Err(self.error(format!("access to nonexistent stack slot {slot}")));
+        }
+        if self.func.slots[slot.0 as usize].is_array() {
+            return Err(self.error(format!("{slot} holds an array, accessed only by address")));
+        }
+        if self.func.value_type(value) != self.func.slot_type(slot) {
+            return Err(self.error(format!("type of {value} does not match {slot}")));
+        }
+        Ok(())
+    }
+
+    fn check_terminator(&self, term: &Terminator) -> Result<(), VerifyError> {
+        if let Terminator::Switch { cases, .. } = term {
+            let mut seen = HashSet::new();
+            if let Some((case, _)) = cases.iter().find(|(case, _)| !seen.insert(*case)) {
+                return Err(self.error(format!("duplicate switch case {case}")));
+            }
+        }
+        let Terminator::Ret(value) = term else {
+            return Ok(());
+        };
+        let returned = value.map(|v| self.func.value_type(v));
+        if returned != self.func.return_type {
+            return Err(self.error("return value does not match the function's return type"));
+        }
+        Ok(())
+    }
+
+    fn error(&self, message: impl Into<String>) -> VerifyError {
+        VerifyError {
+            function: self.func.name.clone(),
+            block: self.block,
+            message: message.into(),
+        }
+    }
+}
@@ -0,0 +1,902 @@
+//! Lexical analysis: turns C source text into a flat list of tokens.
+
+use std::fmt;
+
+use crate::error::{Error, Result};
+use crate::json::Value;
+use crate::standard::{Standard, Version};
+
+/// A 1-based line/column location in the source text. Positions order as
+/// they appear in the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Position {
+    pub const START: Position = Position { line: 1, column: 1 };
+}
+
+impl From<Position> for Value {
+    fn from(pos: Position) -> Self {
+        Value::object([("line", pos.line.into()), ("column", pos.column.into())])
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A range of the source text, from `start` up to but not including
+/// `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keyword {
+    Int,
+    Void,
+    Char,
+    Short,
+    Long,
+    Signed,
+    Unsigned,
+    Return,
+    If,
+    Else,
+    While,
+    Do,
+    For,
+    Goto,
+    Switch,
+    Case,
+    Default,
+    Break,
+    /// `asm`, or its reserved spellings `__asm` and `__asm__`.
+    Asm,
+    /// `volatile`, or `__volatile` and `__volatile__`.
+    Volatile,
+    /// `_Thread_local`, or `thread_local` from C23.
+    ThreadLocal,
+    Extern,
+    Atomic,
+    Enum,
+    Sizeof,
+}
+
+impl Keyword {
+    fn lookup(word: &str, standard: Standard) -> Option<Self> {
+        // Plain `asm` is a GNU extension; the reserved spellings always work.
+        if word == "asm" && !standard.gnu {
+            return None;
+        }
+        if word == "thread_local" && standard.version < Version::C23 {
+            return None;
+        }
+        Some(match word {
+            "int" => Keyword::Int,
+            "void" => Keyword::Void,
+            "char" => Keyword::Char,
+            "short" => Keyword::Short,
+            "long" => Keyword::Long,
+            "signed" => Keyword::Signed,
+            "unsigned" => Keyword::Unsigned,
+            "return" => Keyword::Return,
+            "if" => Keyword::If,
+            "else" => Keyword::Else,
+            "while" => Keyword::While,
+            "do" => Keyword::Do,
+            "for" => Keyword::For,
+            "goto" => Keyword::Goto,
+            "switch" => Keyword::Switch,
+            "case" => Keyword::Case,
+            "default" => Keyword::Default,
+            "break" => Keyword::Break,
+            "asm" | "__asm" | "__asm__" => Keyword::Asm,
+            "volatile" | "__volatile" | "__volatile__" => Keyword::Volatile,
+            "_Thread_local" | "thread_local" => Keyword::ThreadLocal,
+            "extern" => Keyword::Extern,
+            "_Atomic" => Keyword::Atomic,
+            "enum" => Keyword::Enum,
+            "sizeof" => Keyword::Sizeof,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Keyword::Int => "int",
+            Keyword::Void => "void",
+            Keyword::Char => "char",
+            Keyword::Short => "short",
+            Keyword::Long => "long",
+            Keyword::Signed => "signed",
+            Keyword::Unsigned => "unsigned",
+            Keyword::Return => "return",
+            Keyword::If => "if",
+            Keyword::Else => "else",
+            Keyword::While => "while",
+            Keyword::Do => "do",
+            Keyword::For => "for",
+            Keyword::Goto => "goto",
+            Keyword::Switch => "switch",
+            Keyword::Case => "case",
+            Keyword::Default => "default",
+            Keyword::Break => "break",
+            Keyword::Asm => "asm",
+            Keyword::Volatile => "volatile",
+            Keyword::ThreadLocal => "_Thread_local",
+            Keyword::Extern => "extern",
+            Keyword::Atomic => "_Atomic",
+            Keyword::Enum => "enum",
+            Keyword::Sizeof => "sizeof",
+        }
+    }
+
+    /// Whether a type name can start with this keyword.
+    pub fn starts_type(self) -> bool {
+        matches!(
+            self,
+            Keyword::Int
+                | Keyword::Void
+                | Keyword::Char
+                | Keyword::Short
+                | Keyword::Long
+                | Keyword::Signed
+                | Keyword::Unsigned
+                | Keyword::Atomic
+                | Keyword::Enum
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    EqualEqual,
+    BangEqual,
+    Bang,
+    Tilde,
+    Equal,
+    Amp,
+    AmpAmp,
+    Pipe,
+    PipePipe,
+    Caret,
+    LessLess,
+    GreaterGreater,
+    PlusPlus,
+    MinusMinus,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    PercentEqual,
+    AmpEqual,
+    PipeEqual,
+    CaretEqual,
+    LessLessEqual,
+    GreaterGreaterEqual,
+}
+
+impl Operator {
+    /// Binding power for binary use, following C; higher binds tighter.
+    /// `None` for operators that are only unary.
+    pub fn precedence(self) -> Option<u8> {
+        Some(match self {
+            Operator::PipePipe => 1,
+            Operator::AmpAmp => 2,
+            Operator::Pipe => 3,
+            Operator::Caret => 4,
+            Operator::Amp => 5,
+            Operator::EqualEqual | Operator::BangEqual => 6,
+            Operator::Less | Operator::LessEqual | Operator::Greater | Operator::GreaterEqual => 7,
+            Operator::LessLess | Operator::GreaterGreater => 8,
+            Operator::Plus | Operator::Minus => 9,
+            Operator::Star | Operator::Slash | Operator::Percent => 10,
+            Operator::Bang
+            | Operator::Tilde
+            | Operator::Equal
+            | Operator::PlusPlus
+            | Operator::MinusMinus
+            | Operator::PlusEqual
+            | Operator::MinusEqual
+            | Operator::StarEqual
+            | Operator::SlashEqual
+            | Operator::PercentEqual
+            | Operator::AmpEqual
+            | Operator::PipeEqual
+            | Operator::CaretEqual
+            | Operator::LessLessEqual
+            | Operator::GreaterGreaterEqual => return None,
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Operator::Plus => "+",
+            Operator::Minus => "-",
+            Operator::Star => "*",
+            Operator::Slash => "/",
+            Operator::Percent => "%",
+            Operator::Less => "<",
+            Operator::LessEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterEqual => ">=",
+            Operator::EqualEqual => "==",
+            Operator::BangEqual => "!=",
+            Operator::Bang => "!",
+            Operator::Tilde => "~",
+            Operator::Equal => "=",
+            Operator::Amp => "&",
+            Operator::AmpAmp => "&&",
+            Operator::Pipe => "|",
+            Operator::PipePipe => "||",
+            Operator::Caret => "^",
+            Operator::LessLess => "<<",
+            Operator::GreaterGreater => ">>",
+            Operator::PlusPlus => "++",
+            Operator::MinusMinus => "--",
+            Operator::PlusEqual => "+=",
+            Operator::MinusEqual => "-=",
+            Operator::StarEqual => "*=",
+            Operator::SlashEqual => "/=",
+            Operator::PercentEqual => "%=",
+            Operator::AmpEqual => "&=",
+            Operator::PipeEqual => "|=",
+            Operator::CaretEqual => "^=",
+            Operator::LessLessEqual => "<<=",
+            Operator::GreaterGreaterEqual => ">>=",
+        }
+    }
+}
+
+/// An integer literal's suffix, which with its value decides the literal's
+/// type. `ll` is the same as `l`, as `long long` is the same as `long`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntSuffix {
+    Plain,
+    Unsigned,
+    Long,
+    UnsignedLong,
+}
+
+impl IntSuffix {
+    /// The suffix spelled `text`, in either case and either order.
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "" => Some(IntSuffix::Plain),
+            "u" | "U" => Some(IntSuffix::Unsigned),
+            "l" | "L" | "ll" | "LL" => Some(IntSuffix::Long),
+            "ul" | "uL" | "Ul" | "UL" | "lu" | "lU" | "Lu" | "LU" | "ull" | "uLL" | "Ull"
+            | "ULL" | "llu" | "llU" | "LLu" | "LLU" => Some(IntSuffix::UnsignedLong),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            IntSuffix::Plain => "",
+            IntSuffix::Unsigned => "u",
+            IntSuffix::Long => "l",
+            IntSuffix::UnsignedLong => "ul",
+        }
+    }
+
+    pub fn is_unsigned(self) -> bool {
+        matches!(self, IntSuffix::Unsigned | IntSuffix::UnsignedLong)
+    }
+}
+
+/// A token's kind and value. Identifiers and string literals borrow their
+/// text from the source, so lexing one allocates nothing and tokens are
+/// `Copy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind<'a> {
+    Identifier(&'a str),
+    IntLit(u64, IntSuffix),
+    /// A string literal's contents as written, between the quotes; see
+    /// [`unescape`].
+    StringLit(&'a str),
+    /// A character literal's contents as written, between the quotes; see
+    /// [`char_value`].
+    CharLit(&'a str),
+    Keyword(Keyword),
+    Operator(Operator),
+    OpenParen,
+    CloseParen,
+    OpenBrace,
+    CloseBrace,
+    OpenBracket,
+    CloseBracket,
+    Semicolon,
+    Colon,
+    Comma,
+    /// `...`, ending the parameters of a variadic function.
+    Ellipsis,
+    Eof,
+}
+
+impl fmt::Display for TokenKind<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Identifier(name) => write!(f, "identifier `{name}`"),
+            TokenKind::IntLit(value, suffix) => {
+                write!(f, "integer literal `{value}{}`", suffix.as_str())
+            }
+            TokenKind::StringLit(raw) => write!(f, "string literal \"{raw}\""),
+            TokenKind::CharLit(raw) => write!(f, "character literal '{raw}'"),
+            TokenKind::Keyword(kw) => write!(f, "`{}`", kw.as_str()),
+            TokenKind::Operator(op) => write!(f, "`{}`", op.as_str()),
+            TokenKind::OpenParen => f.write_str("`(`"),
+            TokenKind::CloseParen => f.write_str("`)`"),
+            TokenKind::OpenBrace => f.write_str("`{`"),
+            TokenKind::CloseBrace => f.write_str("`}`"),
+            TokenKind::OpenBracket => f.write_str("`[`"),
+            TokenKind::CloseBracket => f.write_str("`]`"),
+            TokenKind::Semicolon => f.write_str("`;`"),
+            TokenKind::Colon => f.write_str("`:`"),
+            TokenKind::Comma => f.write_str("`,`"),
+            TokenKind::Ellipsis => f.write_str("`...`"),
+            TokenKind::Eof => f.write_str("end of file"),
+        }
+    }
+}
+
+impl TokenKind<'_> {
+    /// The name of this kind of token, as it appears in JSON.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenKind::Identifier(_) => "identifier",
+            TokenKind::IntLit(..) => "integer",
+            TokenKind::StringLit(_) => "string",
+            TokenKind::CharLit(_) => "character",
+            TokenKind::Keyword(_) => "keyword",
+            TokenKind::Operator(_) => "operator",
+            TokenKind::Eof => "eof",
+            _ => "punctuation",
+        }
+    }
+
+    /// The token's value: an identifier's name, a literal's value with
+    /// escapes resolved, or the spelling of anything else.
+    pub fn value(&self) -> Value {
+        match *self {
+            TokenKind::Identifier(name) => name.into(),
+            // Above 2^53 this rounds, as a JSON reader would.
+            TokenKind::IntLit(value, _) => Value::Number(value as f64),
+            TokenKind::StringLit(raw) => unescape(raw).into(),
+            TokenKind::CharLit(raw) => u32::from(char_value(raw)).into(),
+            TokenKind::Keyword(kw) => kw.as_str().into(),
+            TokenKind::Operator(op) => op.as_str().into(),
+            TokenKind::OpenParen => "(".into(),
+            TokenKind::CloseParen => ")".into(),
+            TokenKind::OpenBrace => "{".into(),
+            TokenKind::CloseBrace => "}".into(),
+            TokenKind::OpenBracket => "[".into(),
+            TokenKind::CloseBracket => "]".into(),
+            TokenKind::Semicolon => ";".into(),
+            TokenKind::Colon => ":".into(),
+            TokenKind::Comma => ",".into(),
+            TokenKind::Ellipsis => "...".into(),
+            TokenKind::Eof => Value::Null,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind<'a>,
+    pub pos: Position,
+    /// Where the token's text ends.
+    pub end: Position,
+}
+
+impl Token<'_> {
+    /// The source text the token was lexed from.
+    pub fn span(&self) -> Span {
+        Span {
+            start: self.pos,
+            end: self.end,
+        }
+    }
+}
+
+/// `{"kind": .., "value": .., "pos": {"line": .., "column": ..}, "end":
+/// {..}}`, for tools reading the tokens (`--emit=tokens-json`). `end` is
+/// just past the token's last character.
+impl From<&Token<'_>> for Value {
+    fn from(token: &Token<'_>) -> Self {
+        Value::object([
+            ("kind", token.kind.name().into()),
+            ("value", token.kind.value()),
+            ("pos", token.pos.into()),
+            ("end", token.end.into()),
+        ])
+    }
+}
+
+/// `tokens` as a JSON array, one token per line.
+pub fn tokens_json(tokens: &[Token<'_>]) -> String {
+    let lines: Vec<String> = tokens
+        .iter()
+        .map(|token| format!("  {}", Value::from(token)))
+        .collect();
+    format!("[\n{}\n]\n", lines.join(",\n"))
+}
+
+/// Scans the source a byte at a time: everything outside literals and
+/// comments is ASCII, and the bytes of other characters only count toward
+/// columns once each.
+pub struct Lexer<'a> {
+    source: &'a str,
+    /// The byte offset of the next byte in `source`.
+    offset: usize,
+    pos: Position,
+    standard: Standard,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            offset: 0,
+            pos: Position::START,
+            standard: Standard::default(),
+        }
+    }
+
+    /// Lexes the language of `standard`, which decides the keywords.
+    pub fn with_standard(mut self, standard: Standard) -> Self {
+        self.standard = standard;
+        self
+    }
+
+    /// Lexes the whole input. The returned list always ends with `TokenKind::Eof`.
+    pub fn lex(mut self) -> Result<Vec<Token<'a>>> {
+        // C averages about one token for every three bytes.
+        let mut tokens = Vec::with_capacity(self.source.len() / 3);
+        loop {
+            self.skip_trivia()?;
+            let pos = self.pos;
+            let Some(b) = self.peek() else {
+                tokens.push(Token {
+                    kind: TokenKind::Eof,
+                    pos,
+                    end: pos,
+                });
+                return Ok(tokens);
+            };
+
+            let kind = if b.is_ascii_digit() {
+                self.lex_number()?
+            } else if b.is_ascii_alphabetic() || b == b'_' {
+                self.lex_word()
+            } else if b == b'"' {
+                self.lex_string()?
+            } else if b == b'\'' {
+                self.lex_char()?
+            } else {
+                let start = self.offset;
+                self.bump();
+                match b {
+                    b'(' => TokenKind::OpenParen,
+                    b')' => TokenKind::CloseParen,
+                    b'{' => TokenKind::OpenBrace,
+                    b'}' => TokenKind::CloseBrace,
+                    b'[' => TokenKind::OpenBracket,
+                    b']' => TokenKind::CloseBracket,
+                    b';' => TokenKind::Semicolon,
+                    b':' => TokenKind::Colon,
+                    b',' => TokenKind::Comma,
+                    b'.' if self.peek() == Some(b'.') && self.peek_second() == Some(b'.') => {
+                        self.bump();
+                        self.bump();
+                        TokenKind::Ellipsis
+                    }
+                    b'+' if self.eat(b'+') => TokenKind::Operator(Operator::PlusPlus),
+                    b'+' if self.eat(b'=') => TokenKind::Operator(Operator::PlusEqual),
+                    b'+' => TokenKind::Operator(Operator::Plus),
+                    b'-' if self.eat(b'-') => TokenKind::Operator(Operator::MinusMinus),
+                    b'-' if self.eat(b'=') => TokenKind::Operator(Operator::MinusEqual),
+                    b'-' => TokenKind::Operator(Operator::Minus),
+                    b'*' if self.eat(b'=') => TokenKind::Operator(Operator::StarEqual),
+                    b'*' => TokenKind::Operator(Operator::Star),
+                    b'/' if self.eat(b'=') => TokenKind::Operator(Operator::SlashEqual),
+                    b'/' => TokenKind::Operator(Operator::Slash),
+                    b'%' if self.eat(b'=') => TokenKind::Operator(Operator::PercentEqual),
+                    b'%' => TokenKind::Operator(Operator::Percent),
+                    b'^' if self.eat(b'=') => TokenKind::Operator(Operator::CaretEqual),
+                    b'^' => TokenKind::Operator(Operator::Caret),
+                    b'~' => TokenKind::Operator(Operator::Tilde),
+                    b'=' if self.eat(b'=') => TokenKind::Operator(Operator::EqualEqual),
+                    b'=' => TokenKind::Operator(Operator::Equal),
+                    b'!' if self.eat(b'=') => TokenKind::Operator(Operator::BangEqual),
+                    b'!' => TokenKind::Operator(Operator::Bang),
+                    b'<' if self.eat(b'<') => TokenKind::Operator(if self.eat(b'=') {
+                        Operator::LessLessEqual
+                    } else {
+                        Operator::LessLess
+                    }),
+                    b'<' if self.eat(b'=') => TokenKind::Operator(Operator::LessEqual),
+                    b'<' => TokenKind::Operator(Operator::Less),
+                    b'>' if self.eat(b'>') => TokenKind::Operator(if self.eat(b'=') {
+                        Operator::GreaterGreaterEqual
+                    } else {
+                        Operator::GreaterGreater
+                    }),
+                    b'>' if self.eat(b'=') => TokenKind::Operator(Operator::GreaterEqual),
+                    b'>' => TokenKind::Operator(Operator::Greater),
+                    b'&' if self.eat(b'&') => TokenKind::Operator(Operator::AmpAmp),
+                    b'&' if self.eat(b'=') => TokenKind::Operator(Operator::AmpEqual),
+                    b'&' => TokenKind::Operator(Operator::Amp),
+                    b'|' if self.eat(b'|') => TokenKind::Operator(Operator::PipePipe),
+                    b'|' if self.eat(b'=') => TokenKind::Operator(Operator::PipeEqual),
+                    b'|' => TokenKind::Operator(Operator::Pipe),
+                    _ => {
+                        let c = self.source[start..].chars().next();
+                        let c = c.expect("a byte starts a character");
+                        return Err(Error::new(pos, format!("unexpected character `{c}`")));
+                    }
+                }
+            };
+            tokens.push(Token {
+                kind,
+                pos,
+                end: self.pos,
+            });
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.source.as_bytes().get(self.offset).copied()
+    }
+
+    fn peek_second(&self) -> Option<u8> {
+        self.source.as_bytes().get(self.offset + 1).copied()
+    }
+
+    /// The character starting at the next byte, for messages about it.
+    fn peek_char(&self) -> Option<char> {
+        self.source[self.offset..].chars().next()
+    }
+
+    /// Consumes a byte. Only the first byte of a character moves the
+    /// column on; the rest of its UTF-8 encoding are continuation bytes.
+    fn bump(&mut self) -> Option<u8> {
+        let b = self.peek()?;
+        self.offset += 1;
+        if b == b'\n' {
+            self.pos.line += 1;
+            self.pos.column = 1;
+        } else if b & 0xC0 != 0x80 {
+            self.pos.column += 1;
+        }
+        Some(b)
+    }
+
+    /// Consumes the whole character starting at the next byte.
+    fn bump_char(&mut self) -> Option<char> {
+        let c = self.peek_char()?;
+        for _ in 0..c.len_utf8() {
+            self.bump();
+        }
+        Some(c)
+    }
+
+    /// Consumes `b` if it comes next.
+    fn eat(&mut self, b: u8) -> bool {
+        let matches = self.peek() == Some(b);
+        if matches {
+            self.bump();
+        }
+        matches
+    }
+
+    /// Skips whitespace and comments. `//` comments arrived with C99, but
+    /// GNU C has always had them.
+    fn skip_trivia(&mut self) -> Result<()> {
+        loop {
+            match (self.peek(), self.peek_second()) {
+                (Some(b' ' | b'\t' | b'\n' | b'\r' | 0x0B | 0x0C), _) => {
+                    self.bump();
+                }
+                // Whitespace outside ASCII, such as a no-break space.
+                (Some(b), _)
+                    if !b.is_ascii() && self.peek_char().is_some_and(char::is_whitespace) =>
+                {
+                    self.bump_char();
+                }
+                (Some(b'/'), Some(b'/')) => {
+                    if !self.standard.gnu {
+                        self.standard
+                            .require(Version::C99, "a `//` comment", self.pos)?;
+                    }
+                    self.take_while(|b| b != b'\n');
+                }
+                (Some(b'/'), Some(b'*')) => {
+                    let start = self.pos;
+                    self.bump();
+                    self.bump();
+                    loop {
+                        match self.bump() {
+                            Some(b'*') if self.peek() == Some(b'/') => {
+                                self.bump();
+                                break;
+                            }
+                            Some(_) => {}
+                            None => return Err(Error::new(start, "unterminated comment")),
+                        }
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// The source from `start` up to the next character.
+    fn span_from(&self, start: Position) -> Span {
+        Span {
+            start,
+            end: self.pos,
+        }
+    }
+
+    /// Consumes bytes while `pred` holds, returning the slice of the source
+    /// they span. `pred` must hold for every byte of a character or none.
+    fn take_while(&mut self, pred: impl Fn(u8) -> bool) -> &'a str {
+        let start = self.offset;
+        while self.peek().is_some_and(&pred) {
+            self.bump();
+        }
+        &self.source[start..self.offset]
+    }
+
+    /// Lexes a decimal integer literal and its suffix. A literal without
+    /// `u` must fit in `long`, and any literal in `unsigned long`.
+    fn lex_number(&mut self) -> Result<TokenKind<'a>> {
+        let start = self.pos;
+        let text = self.take_while(|b| b.is_ascii_alphanumeric());
+        let error = |message: String| Error::spanning(self.span_from(start), message);
+        // A leading `0x` makes the literal hexadecimal and a leading `0`
+        // octal; `0` itself is octal too, which makes no difference.
+        let (radix, body) = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            Some(hex) => (16, hex),
+            None if text.starts_with('0') => (8, &text[1..]),
+            None => (10, text),
+        };
+        let (digits, suffix) = body.split_at(
+            body.find(|c: char| !c.is_digit(radix.max(10)))
+                .unwrap_or(body.len()),
+        );
+        let Some(mut suffix) = IntSuffix::parse(suffix) else {
+            return Err(error(format!("invalid integer literal `{text}`")));
+        };
+        if radix == 16 && digits.is_empty() {
+            return Err(error(format!("hexadecimal literal `{text}` has no digits")));
+        }
+        if let Some(digit) = digits.chars().find(|c| !c.is_digit(radix)) {
+            return Err(error(format!(
+                "invalid digit `{digit}` in octal literal `{text}`"
+            )));
+        }
+        let value = match digits {
+            "" => Ok(0),
+            digits => u64::from_str_radix(digits, radix),
+        };
+        let Ok(value) = value else {
+            return Err(error(format!(
+                "integer literal `{text}` does not fit in `unsigned long`"
+            )));
+        };
+        if radix != 10 {
+            // Unlike decimal ones, octal and hexadecimal literals take the
+            // unsigned type of each size before the next size up.
+            suffix = match suffix {
+                IntSuffix::Plain
+                    if i32::try_from(value).is_err() && u32::try_from(value).is_ok() =>
+                {
+                    IntSuffix::Unsigned
+                }
+                IntSuffix::Plain | IntSuffix::Long if i64::try_from(value).is_err() => {
+                    IntSuffix::UnsignedLong
+                }
+                suffix => suffix,
+            };
+        }
+        if !suffix.is_unsigned() && i64::try_from(value).is_err() {
+            return Err(error(format!(
+                "integer literal `{text}` does not fit in `long`; add a `u` suffix to make it `unsigned long`"
+            )));
+        }
+        Ok(TokenKind::IntLit(value, suffix))
+    }
+
+    /// Lexes a string literal, checking its escape sequences but leaving
+    /// them for [`unescape`].
+    fn lex_string(&mut self) -> Result<TokenKind<'a>> {
+        self.lex_quoted(b'"', "string literal")
+            .map(|(raw, _)| TokenKind::StringLit(raw))
+    }
+
+    /// Lexes a character literal, which must hold exactly one character or
+    /// escape sequence.
+    fn lex_char(&mut self) -> Result<TokenKind<'a>> {
+        let start = self.pos;
+        match self.lex_quoted(b'\'', "character literal")? {
+            (raw, 1) => Ok(TokenKind::CharLit(raw)),
+            (_, 0) => Err(Error::spanning(
+                self.span_from(start),
+                "empty character literal",
+            )),
+            (raw, _) => Err(Error::spanning(
+                self.span_from(start),
+                format!("multi-character literal '{raw}' is not supported"),
+            )),
+        }
+    }
+
+    /// Lexes text between `quote`s, returning it as written along with the
+    /// number of bytes it stands for.
+    fn lex_quoted(&mut self, quote: u8, what: &str) -> Result<(&'a str, usize)> {
+        let start = self.pos;
+        self.bump();
+        let contents = self.offset;
+        let mut len = 0;
+        loop {
+            let escape_pos = self.pos;
+            match self.bump() {
+                Some(b) if b == quote => {
+                    return Ok((&self.source[contents..self.offset - 1], len));
+                }
+                None | Some(b'\n') => {
+                    return Err(Error::new(start, format!("unterminated {what}")));
+                }
+                Some(b'\\') => {
+                    let rest = &self.source[self.offset..];
+                    match decode_escape(rest) {
+                        Some((value, escape_len)) if value <= 0xFF => {
+                            for _ in 0..escape_len {
+                                self.bump();
+                            }
+                        }
+                        Some((_, escape_len)) => {
+                            for _ in 0..escape_len {
+                                self.bump();
+                            }
+                            return Err(Error::spanning(
+                                self.span_from(escape_pos),
+                                format!(
+                                    "escape sequence `\\{}` is out of range",
+                                    &rest[..escape_len]
+                                ),
+                            ));
+                        }
+                        None => match self.bump_char() {
+                            Some(c) => {
+                                return Err(Error::spanning(
+                                    self.span_from(escape_pos),
+                                    format!("unknown escape sequence `\\{c}`"),
+                                ));
+                            }
+                            None => return Err(Error::new(start, format!("unterminated {what}"))),
+                        },
+                    }
+                    len += 1;
+                }
+                Some(_) => len += 1,
+            }
+        }
+    }
+
+    fn lex_word(&mut self) -> TokenKind<'a> {
+        let word = self.take_while(|b| b.is_ascii_alphanumeric() || b == b'_');
+        match Keyword::lookup(word, self.standard) {
+            Some(kw) => TokenKind::Keyword(kw),
+            None => TokenKind::Identifier(word),
+        }
+    }
+}
+
+/// Decodes the escape sequence at the start of `rest`, just after its
+/// backslash: the value it stands for and its length in bytes. Octal
+/// escapes take up to three digits and hex escapes any number.
+fn decode_escape(rest: &str) -> Option<(u32, usize)> {
+    let value = match rest.chars().next()? {
+        'n' => 0x0A,
+        't' => 0x09,
+        'r' => 0x0D,
+        'a' => 0x07,
+        'b' => 0x08,
+        'f' => 0x0C,
+        'v' => 0x0B,
+        c @ ('\\' | '"' | '\'' | '?') => u32::from(c),
+        '0'..='7' => {
+            let len = rest
+                .bytes()
+                .take(3)
+                .take_while(|b| (b'0'..=b'7').contains(b))
+                .count();
+            let value = u32::from_str_radix(&rest[..len], 8).expect("octal digits");
+            return Some((value, len));
+        }
+        'x' => {
+            let len = rest[1..].bytes().take_while(u8::is_ascii_hexdigit).count();
+            if len == 0 {
+                return None;
+            }
+            // Too many digits for a `u32` is out of range all the same.
+            let value = u32::from_str_radix(&rest[1..=len], 16).unwrap_or(u32::MAX);
+            return Some((value, len + 1));
+        }
+        _ => return None,
+    };
+    Some((value, 1))
+}
+
+/// The values of the characters and escape sequences of a literal lexed
+/// as `raw`.
+fn literal_values(raw: &str) -> impl Iterator<Item = u32> + '_ {
+    let mut rest = raw;
+    std::iter::from_fn(move || {
+        let c = rest.chars().next()?;
+        if c == '\\' {
+            let (value, len) =
+                decode_escape(&rest[1..]).expect("the lexer checks escape sequences");
+            rest = &rest[1 + len..];
+            Some(value)
+        } else {
+            rest = &rest[c.len_utf8()..];
+            Some(u32::from(c))
+        }
+    })
+}
+
+/// The value of a string literal lexed as `raw`, with its escape sequences
+/// resolved. Escapes above 0x7F become the character with that code point.
+pub fn unescape(raw: &str) -> String {
+    literal_values(raw)
+        .map(|value| char::from_u32(value).expect("the lexer checks escape ranges"))
+        .collect()
+}
+
+/// The bytes of a string literal lexed as `raw`: each escape sequence is
+/// the one byte it names, and every other character is its UTF-8 encoding.
+pub fn string_bytes(raw: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(c) = rest.chars().next() {
+        if c == '\\' {
+            let (value, len) =
+                decode_escape(&rest[1..]).expect("the lexer checks escape sequences");
+            bytes.push(u8::try_from(value).expect("the lexer checks escape ranges"));
+            rest = &rest[1 + len..];
+        } else {
+            bytes.extend_from_slice(&rest.as_bytes()[..c.len_utf8()]);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    bytes
+}
+
+/// The byte a character literal lexed as `raw` stands for.
+pub fn char_value(raw: &str) -> u8 {
+    let value = literal_values(raw)
+        .next()
+        .expect("the lexer rejects empty literals");
+    u8::try_from(value).expect("the lexer checks character literals hold one byte")
+}
@@ -0,0 +1,39 @@
+//! rcc: a small C compiler targeting AArch64.
+
+pub mod analyzer;
+pub mod ast;
+pub mod ast_diff;
+pub mod ast_dot;
+pub mod ast_interp;
+pub mod ast_json;
+pub mod cache;
+pub mod codegen;
+pub mod color;
+pub mod coverage;
+pub mod differential;
+pub mod driver;
+pub mod error;
+pub mod ffi;
+pub mod fixtures;
+pub mod fuzz;
+pub mod header;
+pub mod ice;
+pub mod ir;
+pub mod json;
+pub mod lexer;
+pub mod log;
+pub mod lsp;
+pub mod manual;
+pub mod opt;
+pub mod parser;
+pub mod preprocess;
+pub mod pretty;
+pub mod repl;
+pub mod report;
+pub mod source_map;
+pub mod standard;
+pub mod stats;
+pub mod tags;
+pub mod timings;
+pub mod toolchain;
+pub mod warning;
@@ -0,0 +1,148 @@
+//! Logging of the compiler's internal decisions, for debugging rcc itself
+//! (`-v`, `RCC_LOG`).
+//!
+//! Messages are written to stderr as `[LEVEL target] message` by the
+//! [`log!`](crate::log!) macro, which costs one atomic load when its level
+//! is disabled. `RCC_LOG` holds comma-separated directives, each a level
+//! (`debug`) or a target and a level (`regalloc=trace`); the targets are
+//! `driver`, `analyzer`, `opt`, `inline`, `regalloc` and `cache`.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "error" => Level::Error,
+            "warn" => Level::Warn,
+            "info" => Level::Info,
+            "debug" => Level::Debug,
+            "trace" => Level::Trace,
+            _ => return None,
+        })
+    }
+
+    /// The level `-v` repeated `count` times enables: `-v` for info, `-vv`
+    /// for debug and `-vvv` for trace.
+    pub fn from_verbosity(count: u8) -> Option<Self> {
+        match count {
+            0 => None,
+            1 => Some(Level::Info),
+            2 => Some(Level::Debug),
+            _ => Some(Level::Trace),
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        })
+    }
+}
+
+/// Which messages to write: the most detailed level for each target.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    /// The level for targets without a directive of their own.
+    pub default: Option<Level>,
+    pub targets: Vec<(String, Level)>,
+}
+
+impl Filter {
+    /// Parses `RCC_LOG` directives; see the module docs.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut filter = Filter::default();
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let (target, level) = match directive.split_once('=') {
+                Some((target, level)) => (Some(target), level),
+                None => (None, directive),
+            };
+            let level =
+                Level::parse(level).ok_or_else(|| format!("unknown log level `{level}`"))?;
+            match target {
+                Some(target) => filter.targets.push((target.to_string(), level)),
+                None => filter.default = Some(level),
+            }
+        }
+        Ok(filter)
+    }
+
+    /// The most detailed level written for `target`.
+    pub fn level(&self, target: &str) -> Option<Level> {
+        self.targets
+            .iter()
+            .rev()
+            .find(|(name, _)| name == target)
+            .map(|&(_, level)| level)
+            .or(self.default)
+    }
+
+    /// The most detailed level written for any target.
+    fn max(&self) -> Option<Level> {
+        self.targets
+            .iter()
+            .map(|&(_, level)| level)
+            .chain(self.default)
+            .max()
+    }
+}
+
+static FILTER: Mutex<Filter> = Mutex::new(Filter {
+    default: None,
+    targets: Vec::new(),
+});
+
+/// `FILTER.max()`, or 0 when nothing is written, so disabled messages are
+/// skipped without taking the lock.
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(0);
+
+/// Replaces the process-wide filter.
+pub fn set_filter(filter: Filter) {
+    MAX_LEVEL.store(
+        filter.max().map_or(0, |level| level as u8),
+        Ordering::Relaxed,
+    );
+    *FILTER.lock().unwrap_or_else(|err| err.into_inner()) = filter;
+}
+
+pub fn enabled(level: Level, target: &str) -> bool {
+    level as u8 <= MAX_LEVEL.load(Ordering::Relaxed)
+        && FILTER
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .level(target)
+            .is_some_and(|max| level <= max)
+}
+
+/// Writes one message; use [`log!`](crate::log!), which checks
+/// [`enabled`] first.
+pub fn write(level: Level, target: &str, args: fmt::Arguments) {
+    eprintln!("[{level} {target}] {args}");
+}
+
+/// `log!(Debug, "regalloc", "format", args...)` writes a message if the
+/// filter enables it.
+#[macro_export]
+macro_rules! log {
+    ($level:ident, $target:expr, $($arg:tt)+) => {
+        if $crate::log::enabled($crate::log::Level::$level, $target) {
+            $crate::log::write($crate::log::Level::$level, $target, format_args!($($arg)+));
+        }
+    };
+}
@@ -0,0 +1,371 @@
+//! A language server (`rcc lsp`) speaking the Language Server Protocol over
+//! stdin and stdout.
+//!
+//! Documents are synchronized in full on every change and re-analyzed with
+//! the parser's and analyzer's error recovery, so one mistake doesn't hide
+//! the rest. The server publishes diagnostics and answers go-to-definition
+//! and document-symbol requests for functions.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use crate::analyzer::Analyzer;
+use crate::ast::Program;
+use crate::error::Error;
+use crate::json::Value;
+use crate::lexer::{Lexer, Position, TokenKind};
+use crate::parser::Parser;
+
+/// JSON-RPC's error code for an unknown method.
+const METHOD_NOT_FOUND: i32 = -32601;
+/// JSON-RPC's error code for a request the server can't take now.
+const INVALID_REQUEST: i32 = -32600;
+/// The LSP `SymbolKind` of a function.
+const SYMBOL_FUNCTION: u32 = 12;
+/// The LSP `DiagnosticSeverity` of an error.
+const SEVERITY_ERROR: u32 = 1;
+
+/// What the compiler knows about one open document.
+struct Document {
+    text: String,
+    program: Program,
+    errors: Vec<Error>,
+}
+
+impl Document {
+    fn analyze(text: &str) -> Self {
+        let tokens = match Lexer::new(text).lex() {
+            Ok(tokens) => tokens,
+            Err(err) => {
+                return Self {
+                    text: text.to_string(),
+                    program: Program {
+                        functions: Vec::new(),
+                        enums: Vec::new(),
+                        globals: Vec::new(),
+                    },
+                    errors: vec![err],
+                }
+            }
+        };
+        let (program, mut errors) = Parser::new(tokens.clone()).parse_recovering();
+        // Functions that failed to parse would look undeclared.
+        if errors.is_empty() {
+            errors = Analyzer::new().analyze_recovering(&program);
+        }
+        Self {
+            text: text.to_string(),
+            program,
+            errors,
+        }
+    }
+
+    /// The name of the identifier at the 0-based `line` and `character`.
+    fn identifier_at(&self, line: u32, character: u32) -> Option<&str> {
+        // Tokens borrow the text, so they are lexed again rather than kept.
+        let tokens = Lexer::new(&self.text).lex().ok()?;
+        tokens.into_iter().find_map(|token| match token.kind {
+            TokenKind::Identifier(name)
+                if token.pos.line == line + 1
+                    && (token.pos.column - 1..=token.pos.column - 1 + name.len() as u32)
+                        .contains(&character) =>
+            {
+                Some(name)
+            }
+            _ => None,
+        })
+    }
+}
+
+/// The server's state across messages.
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, Document>,
+    shut_down: bool,
+    exited: bool,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the client has sent `exit`.
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Handles one message, returning the responses and notifications to
+    /// send back.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message.get("method").and_then(Value::as_str).unwrap_or("");
+        let params = message.get("params").unwrap_or(&Value::Null);
+        let id = message.get("id");
+        if method == "exit" {
+            self.exited = true;
+            return Vec::new();
+        }
+        if self.shut_down {
+            return id
+                .map(|id| error(id, INVALID_REQUEST, "the server has shut down"))
+                .into_iter()
+                .collect();
+        }
+        let result = match method {
+            "initialize" => Value::object([(
+                "capabilities",
+                Value::object([
+                    // Full synchronization.
+                    ("textDocumentSync", Value::from(1)),
+                    ("definitionProvider", Value::from(true)),
+                    ("documentSymbolProvider", Value::from(true)),
+                ]),
+            )]),
+            "shutdown" => {
+                self.shut_down = true;
+                Value::Null
+            }
+            "textDocument/didOpen" => {
+                let document = params.get("textDocument");
+                let uri = document.and_then(|d| d.get("uri")).and_then(Value::as_str);
+                let text = document.and_then(|d| d.get("text")).and_then(Value::as_str);
+                return match (uri, text) {
+                    (Some(uri), Some(text)) => vec![self.update(uri, text)],
+                    _ => Vec::new(),
+                };
+            }
+            "textDocument/didChange" => {
+                let uri = document_uri(params);
+                // With full synchronization the last change holds the text.
+                let text = params
+                    .get("contentChanges")
+                    .and_then(Value::as_array)
+                    .and_then(<[Value]>::last)
+                    .and_then(|change| change.get("text"))
+                    .and_then(Value::as_str);
+                return match (uri, text) {
+                    (Some(uri), Some(text)) => vec![self.update(uri, text)],
+                    _ => Vec::new(),
+                };
+            }
+            "textDocument/didClose" => {
+                let Some(uri) = document_uri(params) else {
+                    return Vec::new();
+                };
+                self.documents.remove(uri);
+                return vec![publish_diagnostics(uri, Vec::new())];
+            }
+            "textDocument/definition" => self.definition(params),
+            "textDocument/documentSymbol" => self.document_symbols(params),
+            _ => match id {
+                Some(id) => {
+                    return vec![error(
+                        id,
+                        METHOD_NOT_FOUND,
+                        &format!("unknown method `{method}`"),
+                    )]
+                }
+                // Notifications the server doesn't handle are ignored.
+                None => return Vec::new(),
+            },
+        };
+        match id {
+            Some(id) => vec![Value::object([
+                ("jsonrpc", Value::from("2.0")),
+                ("id", id.clone()),
+                ("result", result),
+            ])],
+            None => Vec::new(),
+        }
+    }
+
+    /// Re-analyzes the document `uri`, returning its new diagnostics.
+    fn update(&mut self, uri: &str, text: &str) -> Value {
+        let document = Document::analyze(text);
+        let diagnostics = document.errors.iter().map(diagnostic).collect();
+        self.documents.insert(uri.to_string(), document);
+        publish_diagnostics(uri, diagnostics)
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        let Some(uri) = document_uri(params) else {
+            return Value::Null;
+        };
+        let position = params.get("position");
+        let line = position.and_then(|p| p.get("line")).and_then(Value::as_u64);
+        let character = position
+            .and_then(|p| p.get("character"))
+            .and_then(Value::as_u64);
+        let (Some(document), Some(line), Some(character)) =
+            (self.documents.get(uri), line, character)
+        else {
+            return Value::Null;
+        };
+        let Some(name) = document.identifier_at(line as u32, character as u32) else {
+            return Value::Null;
+        };
+        match document
+            .program
+            .functions
+            .iter()
+            .find(|f| f.name == name && f.body.is_some())
+        {
+            Some(function) => Value::object([
+                ("uri", Value::from(uri)),
+                ("range", range(function.pos, function.name.len())),
+            ]),
+            None => Value::Null,
+        }
+    }
+
+    fn document_symbols(&self, params: &Value) -> Value {
+        let Some(document) = document_uri(params).and_then(|uri| self.documents.get(uri)) else {
+            return Value::Null;
+        };
+        let symbols = document
+            .program
+            .functions
+            .iter()
+            .map(|function| {
+                let range = range(function.pos, function.name.len());
+                // The type of the function, as clangd shows it.
+                let mut params: Vec<String> = function
+                    .params
+                    .iter()
+                    .map(|param| param.ty.to_string())
+                    .collect();
+                if function.variadic {
+                    params.push("...".to_string());
+                }
+                Value::object([
+                    ("name", Value::from(function.name.as_str())),
+                    (
+                        "detail",
+                        Value::from(format!("{} ({})", function.return_type, params.join(", "))),
+                    ),
+                    ("kind", Value::from(SYMBOL_FUNCTION)),
+                    ("range", range.clone()),
+                    ("selectionRange", range),
+                ])
+            })
+            .collect::<Vec<_>>();
+        Value::from(symbols)
+    }
+}
+
+fn document_uri(params: &Value) -> Option<&str> {
+    params
+        .get("textDocument")
+        .and_then(|document| document.get("uri"))
+        .and_then(Value::as_str)
+}
+
+fn error(id: &Value, code: i32, message: &str) -> Value {
+    Value::object([
+        ("jsonrpc", Value::from("2.0")),
+        ("id", id.clone()),
+        (
+            "error",
+            Value::object([
+                ("code", Value::Number(f64::from(code))),
+                ("message", Value::from(message)),
+            ]),
+        ),
+    ])
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    Value::object([
+        ("jsonrpc", Value::from("2.0")),
+        ("method", Value::from("textDocument/publishDiagnostics")),
+        (
+            "params",
+            Value::object([
+                ("uri", Value::from(uri)),
+                ("diagnostics", Value::from(diagnostics)),
+            ]),
+        ),
+    ])
+}
+
+/// An LSP position: 0-based, where ours are 1-based.
+fn position(pos: Position, offset: u32) -> Value {
+    Value::object([
+        ("line", Value::from(pos.line - 1)),
+        ("character", Value::from(pos.column - 1 + offset)),
+    ])
+}
+
+/// The range of `len` characters starting at `pos`.
+fn range(pos: Position, len: usize) -> Value {
+    Value::object([
+        ("start", position(pos, 0)),
+        ("end", position(pos, len as u32)),
+    ])
+}
+
+fn diagnostic(err: &Error) -> Value {
+    // Errors without a position are shown at the start of the file.
+    let pos = err.pos.unwrap_or(Position::START);
+    let range = match err.end {
+        Some(end) => Value::object([("start", position(pos, 0)), ("end", position(end, 0))]),
+        None => range(pos, 1),
+    };
+    Value::object([
+        ("range", range),
+        ("severity", Value::from(SEVERITY_ERROR)),
+        ("source", Value::from("rcc")),
+        ("message", Value::from(err.message.as_str())),
+    ])
+}
+
+/// Reads one message, framed by a `Content-Length` header, or `None` at the
+/// end of the input.
+pub fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| invalid_data("missing Content-Length".into()))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    let body = String::from_utf8(body).map_err(|_| invalid_data("message is not UTF-8".into()))?;
+    Value::parse(&body).map(Some).map_err(invalid_data)
+}
+
+pub fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Serves messages from `input` until the client exits, returning whether
+/// it shut the server down first, as the protocol asks.
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> io::Result<bool> {
+    let mut server = Server::new();
+    while let Some(message) = read_message(&mut input)? {
+        for reply in server.handle(&message) {
+            write_message(&mut output, &reply)?;
+        }
+        if server.exited() {
+            break;
+        }
+    }
+    Ok(server.shut_down)
+}
@@ -1,3 +1,1652 @@
-fn main() {
-    println!("Hello, world!");
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use rcc::ast::Program;
+use rcc::ast_diff;
+use rcc::ast_dot;
+use rcc::ast_interp;
+use rcc::ast_json;
+use rcc::cache::{self, Cache, Entry, Key};
+use rcc::codegen::{Arch, SourceFile, Target, Visibility};
+use rcc::color::{self, ColorChoice, Style};
+use rcc::coverage::{self, Profile};
+use rcc::differential::{self, Verdict};
+use rcc::driver::{self, Artifacts, Options, Stage};
+use rcc::error::{Error, Severity};
+use rcc::fixtures::Fixture;
+use rcc::header;
+use rcc::ice;
+use rcc::ir::interp::Interpreter;
+use rcc::ir::llvm;
+use rcc::lexer::{self, Lexer};
+use rcc::log;
+use rcc::lsp;
+use rcc::manual;
+use rcc::opt::OptLevel;
+use rcc::parser::Parser;
+use rcc::preprocess::{FileError, Preprocessed, Preprocessor};
+use rcc::pretty;
+use rcc::repl;
+use rcc::report;
+use rcc::source_map;
+use rcc::standard::Standard;
+use rcc::stats::Stats;
+use rcc::tags;
+use rcc::timings::{self, Timings};
+use rcc::toolchain::{self, TempDir, Toolchain};
+use rcc::warning::Diagnostic;
+
+#[global_allocator]
+static ALLOCATOR: timings::Counting = timings::Counting;
+
+const USAGE: &str =
+    "usage: rcc [-O0|-O1|-O2|-Os] [-g|-gline-tables-only] [-fverbose-asm] [-fPIC|-fPIE|-fno-pic] [-fvisibility=default|hidden] [-f[no-]omit-frame-pointer] [-fsanitize=signed-integer-overflow] [-f[no-]stack-protector] [-pg] [--coverage] [--check] [--timings|--time-passes] [--stats] [--deterministic] [--save-temps] [--watch] [--run] [-i|--interpret[=ir|ast]] [--no-cache] [--cache-stats] [-v|-vv|-vvv] [-j <jobs>] [-I <dir>]... [-D <name>[=<value>]]... [-U <name>]... [-W<warning>|-Wno-<warning>|-Wall|-Werror|-w]... [-masm=att|intel] [--std=c89|c99|c11|c17|c23|gnu..] [--target <triple>] [--emit=tokens|tokens-json|ast|ast-json|ast-dot|c|tags|header|ir|llvm|wat|asm|asm-map|report|obj|exe|all]... [-S|-c] [-o <file>|--stdout] [--cc] [--color=auto|always|never] [--diagnostics-format=text|json] [--max-errors=<count>] <file.c|file.o|-|--stdin>...
+       rcc cov report <file.rcccov>
+       rcc test [-O0|-O1|-O2|-Os] <dir>
+       rcc diff [-O0|-O1|-O2|-Os] <file.c>...
+       rcc ast-diff <old.c> <new.c>
+       rcc repl
+       rcc lsp
+       rcc completions bash|zsh|fish
+       rcc man
+
+Any argument `@file` is replaced by the arguments in the file.
+
+exit status: 0 on success, 1 if the input has errors, 2 for a malformed command
+line, 3 if a file cannot be read or written or the assembler or linker fails,
+and 101 if rcc itself crashed";
+
+/// Why rcc failed, which its exit status tells scripts. Ordered by how
+/// serious each is, so a build that fails in several ways reports the
+/// worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Failure {
+    /// The input has errors, which were reported as diagnostics.
+    Diagnostics = 1,
+    /// The command line is malformed.
+    Usage = 2,
+    /// A file could not be read or written, or an external tool failed.
+    Io = 3,
+    /// A bug in rcc: a panic, or output that differs between runs. An
+    /// uncaught panic exits with the same status.
+    Ice = 101,
+}
+
+impl From<Failure> for ExitCode {
+    fn from(failure: Failure) -> Self {
+        ExitCode::from(failure as u8)
+    }
+}
+
+/// An artifact the compiler can produce. Each is requested with its own
+/// `--emit`, and the textual ones are written next to the input, as
+/// `<input>.<extension>`, or printed with `-o -`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Emit {
+    /// The tokens, one per line with its position.
+    Tokens,
+    /// The tokens as a JSON array.
+    TokensJson,
+    /// The syntax tree.
+    Ast,
+    /// The syntax tree as JSON.
+    AstJson,
+    /// The syntax tree as a Graphviz graph.
+    AstDot,
+    /// The syntax tree printed back as C, after preprocessing.
+    C,
+    /// A ctags index of the definitions.
+    Tags,
+    /// A header declaring the functions.
+    Header,
+    /// The IR, after any optimizations.
+    Ir,
+    /// The optimized IR as LLVM IR.
+    Llvm,
+    /// The WebAssembly module as text, for `--target wasm32`.
+    Wat,
+    /// The assembly (`-S`).
+    Asm,
+    /// Where each line of the assembly comes from in the source, as JSON.
+    AsmMap,
+    /// Every stage's output side by side in an HTML page.
+    Report,
+    /// An object file made with the system assembler (`-c`).
+    Object,
+    /// The executable `a.out`, linked from every input, by default.
+    Executable,
+}
+
+impl Emit {
+    /// The stages `--emit=all` prints: each step of the pipeline from
+    /// tokens to assembly.
+    const ALL: [Emit; 4] = [Emit::Tokens, Emit::Ast, Emit::Ir, Emit::Asm];
+
+    /// Adds the comma-separated `kinds` to `emit`.
+    fn parse_list(kinds: &str, emit: &mut BTreeSet<Emit>) -> Result<(), String> {
+        for kind in kinds.split(',') {
+            match kind {
+                "all" => emit.extend(Emit::ALL),
+                _ => {
+                    emit.insert(Emit::parse(kind)?);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn parse(kind: &str) -> Result<Self, String> {
+        Ok(match kind {
+            "tokens" => Emit::Tokens,
+            "tokens-json" => Emit::TokensJson,
+            "ast" => Emit::Ast,
+            "ast-json" => Emit::AstJson,
+            "ast-dot" => Emit::AstDot,
+            "c" => Emit::C,
+            "tags" => Emit::Tags,
+            "header" => Emit::Header,
+            "ir" => Emit::Ir,
+            "llvm" => Emit::Llvm,
+            "wat" => Emit::Wat,
+            "asm" => Emit::Asm,
+            "asm-map" => Emit::AsmMap,
+            "report" => Emit::Report,
+            "obj" => Emit::Object,
+            "exe" => Emit::Executable,
+            _ => return Err(format!("unknown `--emit` kind `{kind}`")),
+        })
+    }
+
+    /// The extension of the file a textual artifact is written to.
+    fn extension(self) -> Option<&'static str> {
+        match self {
+            Emit::Tokens => Some("tokens"),
+            Emit::TokensJson => Some("tokens.json"),
+            Emit::Ast => Some("ast"),
+            Emit::AstJson => Some("ast.json"),
+            Emit::AstDot => Some("ast.dot"),
+            // `.c` would be the input itself.
+            Emit::C => Some("pretty.c"),
+            Emit::Tags => Some("tags"),
+            Emit::Header => Some("h"),
+            Emit::Ir => Some("ir"),
+            Emit::Llvm => Some("ll"),
+            Emit::Wat => Some("wat"),
+            Emit::Asm => Some("s"),
+            Emit::AsmMap => Some("s.map"),
+            Emit::Report => Some("html"),
+            Emit::Object | Emit::Executable => None,
+        }
+    }
+
+    /// The last stage of the compiler this artifact needs.
+    fn stage(self) -> Stage {
+        match self {
+            Emit::Tokens | Emit::TokensJson => Stage::Lex,
+            Emit::Ast | Emit::AstJson | Emit::AstDot | Emit::C => Stage::Parse,
+            _ => Stage::Codegen,
+        }
+    }
+
+    /// What starts a comment in this artifact, for highlighting.
+    fn comment_prefix(self, options: &Options) -> &'static str {
+        match self {
+            Emit::Llvm => ";",
+            Emit::Wat => ";;",
+            Emit::Asm => options
+                .target
+                .backend()
+                .map_or("", |backend| backend.syntax().comment_prefix()),
+            _ => "",
+        }
+    }
+
+    /// This artifact of `artifacts`, compiled from `source` in `file`, as
+    /// text, if it is textual.
+    fn text(self, artifacts: &Artifacts, file: &str, source: &str) -> Option<String> {
+        Some(match self {
+            Emit::Tokens => artifacts
+                .tokens
+                .iter()
+                .map(|token| format!("{}: {:?}\n", token.pos, token.kind))
+                .collect(),
+            Emit::TokensJson => lexer::tokens_json(&artifacts.tokens),
+            Emit::Ast => format!("{:#?}\n", artifacts.program),
+            Emit::AstJson => ast_json::emit(&artifacts.program),
+            Emit::AstDot => ast_dot::emit(&artifacts.program),
+            Emit::C => pretty::emit(&artifacts.program),
+            Emit::Tags => tags::emit(&artifacts.program, file),
+            Emit::Header => header::emit(&artifacts.program, file),
+            Emit::Ir => artifacts.ir.to_string(),
+            Emit::Llvm => llvm::emit(&artifacts.ir),
+            Emit::Wat | Emit::Asm => artifacts.assembly.clone(),
+            Emit::AsmMap => source_map::to_json(file, &artifacts.positions),
+            Emit::Report => report::html(file, source, artifacts),
+            Emit::Object | Emit::Executable => return None,
+        })
+    }
+}
+
+struct Args {
+    /// The translation units to compile, each to its own output. `-` is
+    /// standard input.
+    inputs: Vec<PathBuf>,
+    /// Object files, archives and assembly to pass to the linker as they
+    /// are.
+    objects: Vec<PathBuf>,
+    /// Where to write the final output (`-o`) instead of next to the
+    /// input, or to `a.out` for an executable.
+    output: Option<PathBuf>,
+    /// Extra arguments for the linker (`-l`, `-L`).
+    link_args: Vec<String>,
+    /// The include directories and macros to start each input with (`-I`,
+    /// `-D`, `-U`).
+    preprocessor: Preprocessor,
+    /// Notes about ignored flags, printed before compiling.
+    notes: Vec<String>,
+    /// Whether to color diagnostics (`--color`).
+    color_stderr: bool,
+    /// Whether to print diagnostics as JSON objects, one per line
+    /// (`--diagnostics-format=json`).
+    json_diagnostics: bool,
+    /// How many errors to report for each input, or 0 for all of them
+    /// (`--max-errors`).
+    max_errors: usize,
+    /// Whether to highlight artifacts printed with `-o -`.
+    color_stdout: bool,
+    /// The artifacts to produce, in pipeline order.
+    emit: BTreeSet<Emit>,
+    /// Whether to print the output instead of writing a file (`-o -`).
+    stdout: bool,
+    /// Whether to emit debug info (`-g`), which names each input.
+    debug_info: bool,
+    /// Whether to instrument for coverage, which writes next to each input.
+    coverage: bool,
+    /// Whether to report how long each stage takes and what it produces
+    /// (`--timings` or `--time-passes`).
+    timings: bool,
+    /// Whether to report what the compiler made of each input (`--stats`).
+    stats: bool,
+    /// Whether to keep intermediate files next to each input
+    /// (`--save-temps`).
+    save_temps: bool,
+    /// Whether to compile each input twice and check that the outputs
+    /// match (`--deterministic`).
+    deterministic: bool,
+    /// Whether to rebuild whenever an input changes (`--watch`).
+    watch: bool,
+    /// Which interpreter to run `main` in instead, if any (`-i`,
+    /// `--interpret[=ir|ast]`).
+    interpret: Option<Interpret>,
+    /// Whether to run the executable once it is linked (`--run`).
+    run: bool,
+    /// How many inputs to compile at once (`-j`).
+    jobs: usize,
+    /// Where the outputs of unchanged inputs are kept, unless `--no-cache`.
+    cache: Option<Cache>,
+    /// Whether to report how often the cache had an input's outputs
+    /// (`--cache-stats`).
+    cache_stats: bool,
+    /// Options shared by every input.
+    options: Options,
+}
+
+impl Args {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let args: Vec<String> = args.into_iter().collect();
+        // Accepts the flags Makefiles pass to `cc` (`--cc`).
+        let cc = args.iter().any(|arg| arg == "--cc");
+        let mut inputs = Vec::new();
+        let mut objects = Vec::new();
+        let mut output = None;
+        let mut link_args = Vec::new();
+        let mut include_dirs = Vec::new();
+        let mut preprocessor = Preprocessor::new();
+        let mut notes = Vec::new();
+        let mut color = ColorChoice::Auto;
+        let mut max_errors = DEFAULT_MAX_ERRORS;
+        let mut json_diagnostics = false;
+        let mut emit = BTreeSet::new();
+        let mut stdout = false;
+        let mut debug_info = false;
+        let mut coverage = false;
+        let mut timings = false;
+        let mut check = false;
+        let mut stats = false;
+        let mut save_temps = false;
+        let mut deterministic = false;
+        let mut watch = false;
+        let mut run = false;
+        let mut interpret = None;
+        let mut no_cache = false;
+        let mut cache_stats = false;
+        let mut verbosity = 0;
+        let mut jobs = None;
+        let mut options = Options {
+            target: Target::host().unwrap_or_default(),
+            ..Options::default()
+        };
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--cc" => {}
+                "-" | "--stdin" => inputs.push(PathBuf::from(STDIN)),
+                "--stdout" => stdout = true,
+                flag if flag.starts_with("--color=") => {
+                    let name = &flag["--color=".len()..];
+                    color = ColorChoice::parse(name)
+                        .ok_or_else(|| format!("unknown color choice `{name}`"))?;
+                }
+                "-o" => match args.next().as_deref() {
+                    Some("-") => stdout = true,
+                    Some(path) => output = Some(PathBuf::from(path)),
+                    None => return Err("`-o` needs a value".into()),
+                },
+                "-g" => {
+                    debug_info = true;
+                    options.line_tables_only = false;
+                }
+                "-gline-tables-only" => {
+                    debug_info = true;
+                    options.line_tables_only = true;
+                }
+                "-fverbose-asm" => options.verbose_asm = true,
+                "-fPIC" | "-fpic" | "-fPIE" | "-fpie" => options.pic = true,
+                "-fno-PIC" | "-fno-pic" | "-fno-PIE" | "-fno-pie" => options.pic = false,
+                flag if flag.starts_with("-fvisibility=") => {
+                    let name = &flag["-fvisibility=".len()..];
+                    options.visibility = Visibility::parse(name)
+                        .ok_or_else(|| format!("unknown visibility `{name}`"))?;
+                }
+                "-fomit-frame-pointer" => options.omit_frame_pointer = Some(true),
+                "-fno-omit-frame-pointer" => options.omit_frame_pointer = Some(false),
+                "-fsanitize=signed-integer-overflow" => options.sanitize_overflow = true,
+                "-fno-sanitize=signed-integer-overflow" => options.sanitize_overflow = false,
+                "-fstack-protector" => options.stack_protector = true,
+                "-fno-stack-protector" => options.stack_protector = false,
+                "-pg" => options.profile = true,
+                "--coverage" => coverage = true,
+                "--check" | "-fsyntax-only" => check = true,
+                "--timings" | "--time-passes" => timings = true,
+                "--stats" => stats = true,
+                "--save-temps" => save_temps = true,
+                "--deterministic" => deterministic = true,
+                "--watch" => watch = true,
+                "--run" => run = true,
+                "--no-cache" => no_cache = true,
+                "--cache-stats" => cache_stats = true,
+                "-i" | "--interpret" => interpret = Some(Interpret::Ir),
+                flag if flag.starts_with("--interpret=") => {
+                    let name = &flag["--interpret=".len()..];
+                    interpret = Some(
+                        Interpret::parse(name)
+                            .ok_or_else(|| format!("unknown interpreter `{name}`"))?,
+                    );
+                }
+                "-j" => {
+                    let count = args.next().ok_or("`-j` needs a value")?;
+                    jobs = Some(parse_jobs(&count)?);
+                }
+                flag if flag.starts_with("-j") => jobs = Some(parse_jobs(&flag[2..])?),
+                flag if flag.len() > 1 && flag[1..].bytes().all(|b| b == b'v') => {
+                    verbosity += flag.len() as u8 - 1;
+                }
+                "-masm=att" => options.intel_syntax = false,
+                "-masm=intel" => options.intel_syntax = true,
+                "-O0" => options.opt_level = OptLevel::O0,
+                "-O" | "-O1" => options.opt_level = OptLevel::O1,
+                "-O2" => options.opt_level = OptLevel::O2,
+                "-Os" => options.opt_level = OptLevel::Os,
+                "-c" => {
+                    emit.insert(Emit::Object);
+                }
+                "-S" => {
+                    emit.insert(Emit::Asm);
+                }
+                flag if flag.starts_with("--emit=") => {
+                    Emit::parse_list(&flag["--emit=".len()..], &mut emit)?;
+                }
+                "--emit" => {
+                    let kinds = args.next().ok_or("`--emit` needs a value")?;
+                    Emit::parse_list(&kinds, &mut emit)?;
+                }
+                "--target" => {
+                    let triple = args.next().ok_or("`--target` needs a value")?;
+                    options.target = parse_target(&triple)?;
+                }
+                flag if flag.starts_with("--std=") || flag.starts_with("-std=") => {
+                    let name = &flag[flag.find('=').unwrap() + 1..];
+                    options.standard = Standard::parse(name)
+                        .ok_or_else(|| format!("unknown language standard `{name}`"))?;
+                }
+                flag if flag.starts_with("--max-errors=") || flag.starts_with("-fmax-errors=") => {
+                    let count = &flag[flag.find('=').unwrap() + 1..];
+                    max_errors = count
+                        .parse()
+                        .map_err(|_| format!("invalid error count `{count}`"))?;
+                }
+                flag if flag.starts_with("--diagnostics-format=") => {
+                    json_diagnostics = diagnostics_format(&flag["--diagnostics-format=".len()..])?;
+                }
+                "--diagnostics-format" => {
+                    let format = args.next().ok_or("`--diagnostics-format` needs a value")?;
+                    json_diagnostics = diagnostics_format(&format)?;
+                }
+                flag if flag.starts_with("--target=") => {
+                    options.target = parse_target(&flag["--target=".len()..])?;
+                }
+                "-O3" | "-Ofast" if cc => options.opt_level = OptLevel::O2,
+                "-Og" if cc => options.opt_level = OptLevel::O1,
+                "-Oz" if cc => options.opt_level = OptLevel::Os,
+                "-g0" if cc => debug_info = false,
+                "-save-temps" if cc => save_temps = true,
+                "-g1" | "-g2" | "-g3" | "-ggdb" if cc => {
+                    debug_info = true;
+                    options.line_tables_only = false;
+                }
+                "-I" => {
+                    let dir = args.next().ok_or("`-I` needs a value")?;
+                    include_dirs.push(PathBuf::from(dir));
+                }
+                flag if flag.starts_with("-I") => include_dirs.push(PathBuf::from(&flag[2..])),
+                "-D" => {
+                    let definition = args.next().ok_or("`-D` needs a value")?;
+                    define(&mut preprocessor, &definition)?;
+                }
+                flag if flag.starts_with("-D") => define(&mut preprocessor, &flag[2..])?,
+                "-U" => preprocessor.undefine(&args.next().ok_or("`-U` needs a value")?),
+                flag if flag.starts_with("-U") => preprocessor.undefine(&flag[2..]),
+                "-include" | "-MF" | "-MT" | "-MQ" if cc => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| format!("`{arg}` needs a value"))?;
+                    notes.push(ignored(&format!("{arg} {value}")));
+                }
+                "-pthread" | "-static" | "-rdynamic" if cc => link_args.push(arg),
+                flag if cc && ["-l", "-L", "-Wl,"].iter().any(|p| flag.starts_with(p)) => {
+                    link_args.push(arg)
+                }
+                "-w" => options.warnings.suppress(),
+                // Not `-Wl,` and the like, which pass options on to tools.
+                flag if flag.starts_with("-W") && !flag.contains(',') => {
+                    if !options.warnings.apply(&flag[2..]) {
+                        if !cc {
+                            return Err(format!("unknown warning option `{flag}`"));
+                        }
+                        notes.push(ignored(flag));
+                    }
+                }
+                flag if cc && flag.len() > 1 && flag.starts_with('-') => notes.push(ignored(flag)),
+                flag if flag.starts_with('-') => return Err(format!("unknown option `{flag}`")),
+                _ if is_linker_input(Path::new(&arg)) => objects.push(PathBuf::from(arg)),
+                _ => inputs.push(PathBuf::from(arg)),
+            }
+        }
+        if inputs.is_empty() && objects.is_empty() {
+            return Err("no input file".into());
+        }
+        let mut filter = match std::env::var("RCC_LOG") {
+            Ok(spec) => log::Filter::parse(&spec).map_err(|err| format!("RCC_LOG: {err}"))?,
+            Err(_) => log::Filter::default(),
+        };
+        if let Some(level) = log::Level::from_verbosity(verbosity) {
+            filter.default = filter.default.max(Some(level));
+        }
+        log::set_filter(filter);
+        if inputs.iter().filter(|input| is_stdin(input)).count() > 1 {
+            return Err("standard input can only be read once".into());
+        }
+        if watch && inputs.iter().any(|input| is_stdin(input)) {
+            return Err("`--watch` cannot watch standard input".into());
+        }
+        if no_cache && cache_stats {
+            return Err("`--cache-stats` has nothing to report with `--no-cache`".into());
+        }
+        if interpret.is_some() && inputs.len() != 1 {
+            return Err("`--interpret` takes a single input".into());
+        }
+        if options.intel_syntax && options.target.arch != Arch::X86_64 {
+            return Err("`-masm=intel` needs an x86-64 target".into());
+        }
+        if check && (!emit.is_empty() || output.is_some() || stdout || run || interpret.is_some()) {
+            return Err(
+                "`--check` writes nothing; drop `--emit`, `-S`, `-c`, `-o`, `--stdout`, `--run` and `--interpret`"
+                    .into(),
+            );
+        }
+        let wasm = options.target.arch == Arch::Wasm32;
+        if wasm {
+            let native = [Emit::Asm, Emit::AsmMap, Emit::Object, Emit::Executable];
+            if native.iter().any(|kind| emit.contains(kind)) {
+                return Err(
+                    "`--target wasm32` writes WebAssembly text with `--emit=wat`, not assembly, objects or executables"
+                        .into(),
+                );
+            }
+            // The code generator adds these, and it doesn't write WebAssembly.
+            if coverage || options.profile || options.sanitize_overflow {
+                return Err(
+                    "`--coverage`, `-pg` and `-fsanitize` are not supported for `wasm32`".into(),
+                );
+            }
+            // The interpreters lay memory out with 8-byte pointers.
+            if interpret.is_some() {
+                return Err("`--interpret` is not supported for `wasm32`".into());
+            }
+        } else if emit.contains(&Emit::Wat) {
+            return Err("`--emit=wat` needs the `wasm32` target".into());
+        }
+        if emit.is_empty() && !check {
+            // Printing the output means printing the assembly, and there
+            // is nothing to link a WebAssembly module with.
+            emit.insert(if wasm {
+                Emit::Wat
+            } else if stdout {
+                Emit::Asm
+            } else {
+                Emit::Executable
+            });
+        }
+        if run && (emit.len() != 1 || !emit.contains(&Emit::Executable) || interpret.is_some()) {
+            return Err(
+                "`--run` only builds an executable; drop `--emit`, `-S`, `-c` and `--interpret`"
+                    .into(),
+            );
+        }
+        options.source_map = emit.contains(&Emit::AsmMap) || emit.contains(&Emit::Report);
+        // Statistics, interpreting the IR and the determinism check look at
+        // every stage's output; the syntax tree is interpreted once it is
+        // analyzed.
+        options.last_stage = if stats || deterministic || interpret == Some(Interpret::Ir) {
+            Stage::Codegen
+        } else if check || interpret == Some(Interpret::Ast) {
+            Stage::Analyze
+        } else {
+            emit.iter()
+                .map(|emit| emit.stage())
+                .max()
+                .unwrap_or_default()
+        };
+        if !emit.contains(&Emit::Executable) {
+            if output.is_some() && inputs.len() > 1 {
+                return Err("cannot use `-o` with multiple inputs unless linking".into());
+            }
+            for object in &objects {
+                notes.push(format!(
+                    "ignoring `{}`, which is only used when linking",
+                    object.display()
+                ));
+            }
+        }
+        if stdout {
+            if emit.contains(&Emit::Object) {
+                return Err("cannot write an object file to stdout".into());
+            }
+            if emit.contains(&Emit::Executable) {
+                return Err("cannot write an executable to stdout".into());
+            }
+        }
+        Ok(Self {
+            inputs,
+            objects,
+            output,
+            link_args,
+            preprocessor: preprocessor.with_include_dirs(include_dirs),
+            notes,
+            color_stderr: color.enabled(&io::stderr()) && !json_diagnostics,
+            json_diagnostics,
+            max_errors,
+            color_stdout: color.enabled(&io::stdout()),
+            emit,
+            stdout,
+            debug_info,
+            coverage,
+            timings,
+            stats,
+            save_temps,
+            deterministic,
+            watch,
+            interpret,
+            run,
+            // Timings measure memory across the whole process, so they
+            // need the inputs compiled one at a time.
+            jobs: match jobs {
+                _ if timings => 1,
+                Some(jobs) => jobs,
+                None => thread::available_parallelism().map_or(1, |n| n.get()),
+            },
+            cache: (!no_cache).then(|| Cache::new(cache::DIR)),
+            cache_stats,
+            options,
+        })
+    }
+
+    /// Where to link the executable: `-o`'s path, or `a.out`.
+    fn executable(&self) -> &Path {
+        self.output.as_deref().unwrap_or(Path::new("a.out"))
+    }
+
+    /// Where to write `emit`'s artifact of `input`: `-o`'s path if it is
+    /// the final output, and otherwise next to the input.
+    fn output_path(&self, input: &Path, emit: Emit, extension: &str) -> PathBuf {
+        match &self.output {
+            Some(output)
+                if !self.emit.contains(&Emit::Executable) && self.emit.last() == Some(&emit) =>
+            {
+                output.clone()
+            }
+            _ => input.with_extension(extension),
+        }
+    }
+
+    /// What names the cache entry for compiling `source`, preprocessed
+    /// from `file`, with `options`: everything the outputs and the
+    /// rendered warnings depend on.
+    fn cache_key(&self, file: &str, options: &Options, source: &str) -> Key {
+        let flags = format!(
+            "{options:?} {:?} {} {}",
+            self.emit, self.color_stderr, self.json_diagnostics
+        );
+        Key::new([source.as_bytes(), file.as_bytes(), flags.as_bytes()])
+    }
+
+    /// The options for compiling `input`.
+    fn options_for(&self, input: &Path) -> Result<Options, String> {
+        let mut options = self.options.clone();
+        if self.debug_info {
+            let directory = std::env::current_dir()
+                .map_err(|err| format!("cannot find the current directory: {err}"))?;
+            options.debug_info = Some(SourceFile {
+                name: display_name(input),
+                directory: directory.display().to_string(),
+            });
+        }
+        if self.coverage {
+            // The program writes its counts wherever it runs from, so both
+            // paths are made absolute.
+            let source = std::path::absolute(input)
+                .map_err(|err| format!("cannot resolve `{}`: {err}", input.display()))?;
+            options.coverage = Some(coverage::Files {
+                data: source.with_extension("rcccov").display().to_string(),
+                source: source.display().to_string(),
+            });
+        }
+        Ok(options)
+    }
+}
+
+/// The input that stands for standard input, as for `cc`.
+const STDIN: &str = "-";
+
+fn is_stdin(input: &Path) -> bool {
+    input == Path::new(STDIN)
+}
+
+/// The name of `input` in diagnostics: its path, or `<stdin>`.
+fn display_name(input: &Path) -> String {
+    if is_stdin(input) {
+        "<stdin>".into()
+    } else {
+        input.display().to_string()
+    }
+}
+
+/// Reads the source of `input`, from standard input for `-`.
+fn read_source(input: &Path) -> io::Result<String> {
+    if is_stdin(input) {
+        io::read_to_string(io::stdin())
+    } else {
+        fs::read_to_string(input)
+    }
+}
+
+/// The note for a `cc` flag that `--cc` accepts but does nothing with.
+fn ignored(flag: &str) -> String {
+    let reason = if flag.starts_with("-W") || flag.starts_with("-pedantic") {
+        "rcc has no such warning"
+    } else if flag.starts_with("-M") {
+        "rcc does not write dependency files"
+    } else {
+        "rcc does not support it"
+    };
+    format!("ignoring `{flag}`: {reason}")
+}
+
+/// Adds a `-D` definition to `preprocessor`.
+fn define(preprocessor: &mut Preprocessor, definition: &str) -> Result<(), String> {
+    preprocessor
+        .define(definition)
+        .map_err(|message| format!("invalid `-D{definition}`: {message}"))
+}
+
+/// Whether `path` goes straight to the linker rather than being compiled,
+/// as `cc` decides by its extension.
+fn is_linker_input(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| matches!(ext, "o" | "a" | "so" | "dylib" | "s" | "S"))
+}
+
+/// How many errors are reported for each input unless `--max-errors` says
+/// otherwise.
+const DEFAULT_MAX_ERRORS: usize = 20;
+
+/// The stack each thread compiling an input has: as much as the main
+/// thread's, which the parser's nesting limits leave room for.
+const STACK_SIZE: usize = 8 << 20;
+
+fn parse_jobs(count: &str) -> Result<usize, String> {
+    match count.parse() {
+        Ok(jobs) if jobs > 0 => Ok(jobs),
+        _ => Err(format!("invalid job count `{count}`")),
+    }
+}
+
+fn parse_target(triple: &str) -> Result<Target, String> {
+    Target::from_triple(triple).ok_or_else(|| format!("unknown target `{triple}`"))
+}
+
+/// `rcc cov report <file.rcccov>`: prints the source annotated with the
+/// counts in the data file.
+fn coverage_report(args: &[String]) -> ExitCode {
+    let [command, data] = args else {
+        eprintln!("rcc: usage: rcc cov report <file.rcccov>");
+        return Failure::Usage.into();
+    };
+    if command != "report" {
+        eprintln!("rcc: unknown coverage command `{command}`");
+        return Failure::Usage.into();
+    }
+    let profile = match fs::read(data)
+        .map_err(|err| err.to_string())
+        .and_then(|bytes| Profile::parse(&bytes))
+    {
+        Ok(profile) => profile,
+        Err(err) => {
+            eprintln!("rcc: cannot read `{data}`: {err}");
+            return Failure::Io.into();
+        }
+    };
+    match fs::read_to_string(&profile.source) {
+        Ok(source) => {
+            print!("{}", profile.annotate(&source));
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("rcc: cannot read `{}`: {err}", profile.source);
+            Failure::Io.into()
+        }
+    }
+}
+
+/// The optimization level a flag like `-O2` selects.
+fn opt_level(flag: &str) -> Option<OptLevel> {
+    Some(match flag {
+        "-O0" => OptLevel::O0,
+        "-O" | "-O1" => OptLevel::O1,
+        "-O2" => OptLevel::O2,
+        "-Os" => OptLevel::Os,
+        _ => return None,
+    })
+}
+
+/// `rcc diff [-O<level>] <file.c>...`: builds each file with rcc and with
+/// the system compiler and reports any difference in how they run.
+fn differential(args: &[String]) -> ExitCode {
+    const DIFF_USAGE: &str = "rcc: usage: rcc diff [-O0|-O1|-O2|-Os] <file.c>...";
+    let mut options = Options {
+        target: Target::host().unwrap_or_default(),
+        ..Options::default()
+    };
+    let mut inputs = Vec::new();
+    for arg in args {
+        match opt_level(arg) {
+            Some(level) => options.opt_level = level,
+            None if arg.starts_with('-') => {
+                eprintln!("{DIFF_USAGE}");
+                return Failure::Usage.into();
+            }
+            None => inputs.push(PathBuf::from(arg)),
+        }
+    }
+    if inputs.is_empty() {
+        eprintln!("{DIFF_USAGE}");
+        return Failure::Usage.into();
+    }
+
+    let mut failed = 0;
+    for input in &inputs {
+        let name = input.display();
+        let compared = fs::read_to_string(input)
+            .map_err(|err| format!("cannot read: {err}"))
+            .and_then(|source| differential::compare(input, &source, &options));
+        match compared {
+            Ok(Verdict::Same(execution)) => println!("same {name}: {execution}"),
+            Ok(Verdict::Differ { rcc, cc }) => {
+                println!("DIFFER {name}:\n    rcc: {rcc}\n    cc:  {cc}");
+                failed += 1;
+            }
+            Err(reason) => {
+                println!("ERROR {name}: {reason}");
+                failed += 1;
+            }
+        }
+    }
+    println!(
+        "\n{} same; {failed} differ or failed",
+        inputs.len() - failed
+    );
+    if failed == 0 {
+        ExitCode::SUCCESS
+    } else {
+        Failure::Diagnostics.into()
+    }
+}
+
+/// `rcc ast-diff <old.c> <new.c>`: prints how the syntax trees of two
+/// files differ, ignoring formatting. Exits with 1 if they differ, like
+/// `diff`.
+fn ast_diff(args: &[String]) -> ExitCode {
+    let [old, new] = args else {
+        eprintln!("rcc: usage: rcc ast-diff <old.c> <new.c>");
+        return Failure::Usage.into();
+    };
+    let parse = |path: &String| -> Result<Program, Failure> {
+        let source = fs::read_to_string(path).map_err(|err| {
+            eprintln!("rcc: cannot read `{path}`: {err}");
+            Failure::Io
+        })?;
+        Lexer::new(&source)
+            .lex()
+            .and_then(|tokens| Parser::new(tokens).parse())
+            .map_err(|err| {
+                eprintln!("{}", err.render(path));
+                Failure::Diagnostics
+            })
+    };
+    let (old_program, new_program) = match (parse(old), parse(new)) {
+        (Ok(old), Ok(new)) => (old, new),
+        (Err(failure), _) | (_, Err(failure)) => return failure.into(),
+    };
+    match ast_diff::diff(&old_program, &new_program) {
+        None => ExitCode::SUCCESS,
+        Some(diff) => {
+            print!("--- {old}\n+++ {new}\n{diff}");
+            Failure::Diagnostics.into()
+        }
+    }
+}
+
+/// `rcc test [-O<level>] <dir>`: checks every `.c` fixture in the directory
+/// against its expectations and prints a summary.
+fn run_fixtures(args: &[String]) -> ExitCode {
+    let mut options = Options {
+        target: Target::host().unwrap_or_default(),
+        ..Options::default()
+    };
+    let mut dir = None;
+    for arg in args {
+        match opt_level(arg) {
+            Some(level) => options.opt_level = level,
+            None if dir.is_none() && !arg.starts_with('-') => dir = Some(PathBuf::from(arg)),
+            None => {
+                eprintln!("rcc: usage: rcc test [-O0|-O1|-O2|-Os] <dir>");
+                return Failure::Usage.into();
+            }
+        }
+    }
+    let Some(dir) = dir else {
+        eprintln!("rcc: usage: rcc test [-O0|-O1|-O2|-Os] <dir>");
+        return Failure::Usage.into();
+    };
+    let mut paths = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "c"))
+            .collect::<Vec<_>>(),
+        Err(err) => {
+            eprintln!("rcc: cannot read `{}`: {err}", dir.display());
+            return Failure::Io.into();
+        }
+    };
+    paths.sort();
+
+    let mut failures = Vec::new();
+    let mut crashed = false;
+    for path in &paths {
+        let name = path.display().to_string();
+        let checked = fs::read_to_string(path)
+            .map_err(|err| format!("cannot read: {err}"))
+            .and_then(|text| Fixture::parse(&text))
+            .and_then(|fixture| {
+                ice::set_file(&name);
+                panic::catch_unwind(|| fixture.check(path, &options)).unwrap_or_else(|payload| {
+                    crashed = true;
+                    Err(format!(
+                        "internal compiler error: {}",
+                        ice::message(payload.as_ref())
+                    ))
+                })
+            });
+        match checked {
+            Ok(()) => println!("test {name} ... ok"),
+            Err(reason) => {
+                println!("test {name} ... FAILED");
+                failures.push((name, reason));
+            }
+        }
+    }
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (name, reason) in &failures {
+            println!("    {name}: {reason}");
+        }
+    }
+    println!(
+        "\ntest result: {}. {} passed; {} failed",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        paths.len() - failures.len(),
+        failures.len()
+    );
+    if crashed {
+        Failure::Ice.into()
+    } else if failures.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        Failure::Diagnostics.into()
+    }
+}
+
+fn main() -> ExitCode {
+    ice::install();
+    let args = match expand_response_files(std::env::args().skip(1), &mut Vec::new()) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("rcc: {message}");
+            return Failure::Usage.into();
+        }
+    };
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+    if args.first().is_some_and(|arg| arg == "cov") {
+        return coverage_report(&args[1..]);
+    }
+    if args.first().is_some_and(|arg| arg == "diff") {
+        return differential(&args[1..]);
+    }
+    if args.first().is_some_and(|arg| arg == "ast-diff") {
+        return ast_diff(&args[1..]);
+    }
+    if args.first().is_some_and(|arg| arg == "test") {
+        return run_fixtures(&args[1..]);
+    }
+    if args.first().is_some_and(|arg| arg == "repl") {
+        let options = Options {
+            target: Target::host().unwrap_or_default(),
+            ..Options::default()
+        };
+        return match repl::run(io::stdin().lock(), io::stdout().lock(), options) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("rcc: repl: {err}");
+                Failure::Io.into()
+            }
+        };
+    }
+    if args.first().is_some_and(|arg| arg == "completions") {
+        let [shell] = &args[1..] else {
+            eprintln!("rcc: usage: rcc completions bash|zsh|fish");
+            return Failure::Usage.into();
+        };
+        return match manual::completions(shell) {
+            Some(script) => {
+                print!("{script}");
+                ExitCode::SUCCESS
+            }
+            None => {
+                eprintln!("rcc: unsupported shell `{shell}`; expected bash, zsh or fish");
+                Failure::Usage.into()
+            }
+        };
+    }
+    if args.first().is_some_and(|arg| arg == "man") {
+        print!("{}", manual::man_page());
+        return ExitCode::SUCCESS;
+    }
+    if args.first().is_some_and(|arg| arg == "lsp") {
+        return match lsp::serve(io::stdin().lock(), io::stdout().lock()) {
+            Ok(true) => ExitCode::SUCCESS,
+            // The client exited without asking the server to shut down.
+            Ok(false) => Failure::Diagnostics.into(),
+            Err(err) => {
+                eprintln!("rcc: language server: {err}");
+                Failure::Io.into()
+            }
+        };
+    }
+    let args = match Args::parse(args) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("rcc: {message}\n{USAGE}");
+            return Failure::Usage.into();
+        }
+    };
+    for note in &args.notes {
+        let label = Style::NOTE.paint("note:", args.color_stderr);
+        eprintln!("rcc: {label} {note}");
+    }
+    if let Some(mode) = args.interpret {
+        return interpret(&args, mode);
+    }
+    if args.run {
+        return run(&args);
+    }
+    if args.watch {
+        watch(&args);
+    }
+    match build(&args, args.executable()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => failure.into(),
+    }
+}
+
+/// Replaces each `@file` argument with the arguments in the file, as build
+/// systems pass long command lines. Like `cc`, an `@file` that cannot be
+/// read is left as it is. `open` holds the files being expanded, to catch
+/// one that includes itself.
+fn expand_response_files(
+    args: impl IntoIterator<Item = String>,
+    open: &mut Vec<PathBuf>,
+) -> Result<Vec<String>, String> {
+    let mut expanded = Vec::new();
+    for arg in args {
+        let Some(path) = arg.strip_prefix('@').map(PathBuf::from) else {
+            expanded.push(arg);
+            continue;
+        };
+        let Ok(text) = fs::read_to_string(&path) else {
+            expanded.push(arg);
+            continue;
+        };
+        if open.contains(&path) {
+            return Err(format!(
+                "response file `{}` includes itself",
+                path.display()
+            ));
+        }
+        open.push(path);
+        expanded.extend(expand_response_files(split_response_file(&text), open)?);
+        open.pop();
+    }
+    Ok(expanded)
+}
+
+/// The arguments in a response file: separated by whitespace, which single
+/// or double quotes or a backslash keep within an argument.
+fn split_response_file(text: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut arg: Option<String> = None;
+    let mut quote = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => arg.get_or_insert_with(String::new).extend(chars.next()),
+            '\'' | '"' if quote.is_none() => {
+                quote = Some(c);
+                arg.get_or_insert_with(String::new);
+            }
+            c if quote == Some(c) => quote = None,
+            c if c.is_whitespace() && quote.is_none() => args.extend(arg.take()),
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+    args
+}
+
+/// Runs the input's `main` in the interpreter `mode` picks, exiting with
+/// its result as a program would (`-i`, `--interpret[=ir|ast]`).
+fn interpret(args: &Args, mode: Interpret) -> ExitCode {
+    let input = &args.inputs[0];
+    let file = display_name(input);
+    let source = match read_source(input) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("rcc: cannot read `{file}`: {err}");
+            return Failure::Io.into();
+        }
+    };
+    let preprocessed = match args.preprocessor.run(input, &file, &source) {
+        Ok(preprocessed) => preprocessed,
+        Err(err) => {
+            eprintln!("{}", render_file_error(args, &err));
+            return Failure::Diagnostics.into();
+        }
+    };
+    let artifacts = match driver::compile(&preprocessed.source, &args.options) {
+        Ok(artifacts) => artifacts,
+        Err(err) => {
+            let errors = render_errors(args, &preprocessed, args.options.standard, err);
+            eprintln!("{errors}");
+            return Failure::Diagnostics.into();
+        }
+    };
+    if !artifacts.warnings.is_empty() {
+        let (warnings, fatal) =
+            render_warnings(args, &preprocessed, &args.options, &artifacts.warnings);
+        eprintln!("{warnings}");
+        if fatal {
+            return Failure::Diagnostics.into();
+        }
+    }
+    let result = match mode {
+        Interpret::Ir => Interpreter::new(&artifacts.ir).call("main", &[]),
+        Interpret::Ast => ast_interp::Interpreter::new(&artifacts.program).call("main", &[]),
+    };
+    match result {
+        Ok(status) => ExitCode::from(status.unwrap_or(0) as u8),
+        Err(trap) => {
+            eprintln!("rcc: {file}: {trap}");
+            Failure::Diagnostics.into()
+        }
+    }
+}
+
+/// Builds the executable, in a temporary directory unless `-o` names it,
+/// and runs it with rcc's standard streams, exiting with its status
+/// (`--run`).
+fn run(args: &Args) -> ExitCode {
+    let dir = match TempDir::new() {
+        Ok(dir) => dir,
+        Err(message) => {
+            eprintln!("rcc: {message}");
+            return Failure::Io.into();
+        }
+    };
+    let executable = match &args.output {
+        Some(output) => output.clone(),
+        None => dir.path().join("a.out"),
+    };
+    if let Err(failure) = build(args, &executable) {
+        return failure.into();
+    }
+    // The program's output follows anything rcc printed.
+    io::stdout().flush().ok();
+    // A path without a directory would be looked up in `PATH`.
+    let program = Path::new(".").join(&executable);
+    match Command::new(&program).status() {
+        Ok(status) => match status.code() {
+            Some(code) => ExitCode::from(code as u8),
+            // Killed by a signal, which shells report as 128 plus its
+            // number.
+            None => {
+                #[cfg(unix)]
+                let signal = std::os::unix::process::ExitStatusExt::signal(&status).unwrap_or(0);
+                #[cfg(not(unix))]
+                let signal = 0;
+                ExitCode::from(128 + signal as u8)
+            }
+        },
+        Err(err) => {
+            eprintln!("rcc: cannot run `{}`: {err}", executable.display());
+            Failure::Io.into()
+        }
+    }
+}
+
+/// Compiles every input and links them into `executable` if asked,
+/// returning the worst failure if any part of it failed.
+fn build(args: &Args, executable: &Path) -> Result<(), Failure> {
+    // Every input is compiled even if an earlier one fails, so one run
+    // reports the errors in all of them.
+    let intermediates = if args.save_temps {
+        Intermediates::Saved
+    } else {
+        TempDir::new().map(Intermediates::Temp).map_err(|message| {
+            eprintln!("rcc: {message}");
+            Failure::Io
+        })?
+    };
+    let mut failed = None;
+    let mut assembled = Vec::new();
+    for (result, report) in compile_all(args, &intermediates) {
+        print!("{}", report.stdout);
+        eprint!("{}", report.stderr);
+        match result {
+            Ok(asm) => assembled.extend(asm),
+            Err(failure) => failed = failed.max(Some(failure)),
+        }
+    }
+    if let Some(cache) = args.cache.as_ref().filter(|_| args.cache_stats) {
+        eprintln!("rcc: cache: {}", cache.take_stats());
+    }
+    if args.emit.contains(&Emit::Executable) && failed.is_none() {
+        let inputs = [&assembled[..], &args.objects[..]].concat();
+        let linked = Toolchain::new(args.options.target)
+            .with_link_args(args.link_args.clone())
+            .link(&inputs, executable);
+        if let Err(message) = linked {
+            eprintln!("rcc: {message}");
+            failed = Some(Failure::Io);
+        }
+    }
+    failed.map_or(Ok(()), Err)
+}
+
+/// Compiles every input on up to `args.jobs` threads, returning each
+/// one's result and report in input order.
+fn compile_all(
+    args: &Args,
+    intermediates: &Intermediates,
+) -> Vec<(Result<Option<PathBuf>, Failure>, Report)> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(args.inputs.len()));
+    let worker = || loop {
+        let index = next.fetch_add(1, Ordering::Relaxed);
+        let Some(input) = args.inputs.get(index) else {
+            return;
+        };
+        let mut report = Report::default();
+        let result = compile_file(args, input, intermediates, &mut report);
+        results.lock().unwrap().push((index, result, report));
+    };
+    thread::scope(|scope| {
+        for _ in 1..args.jobs.min(args.inputs.len()) {
+            thread::Builder::new()
+                .stack_size(STACK_SIZE)
+                .spawn_scoped(scope, worker)
+                .expect("cannot start a compiler thread");
+        }
+        worker();
+    });
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|&(index, ..)| index);
+    results
+        .into_iter()
+        .map(|(_, result, report)| (result, report))
+        .collect()
+}
+
+/// Builds whenever an input or a file it includes changes, until killed
+/// (`--watch`).
+fn watch(args: &Args) -> ! {
+    let mut files = args.inputs.clone();
+    loop {
+        // Each file is stamped before it is read, so that a change while
+        // it is read is seen as one after.
+        let mut before = stamps(&files);
+        let known = files.len();
+        add_includes(args, &mut files);
+        before.extend(stamps(&files[known..]));
+        if build(args, args.executable()).is_ok() {
+            eprintln!("rcc: build succeeded");
+        }
+        eprintln!("rcc: watching for changes");
+        while stamps(&files) == before {
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+}
+
+/// Adds the files the inputs include now to `files`. None are taken out,
+/// so that a header keeps being watched while an error stops the inputs
+/// from being preprocessed; of an input that can't be, the file the error
+/// is in is added.
+fn add_includes(args: &Args, files: &mut Vec<PathBuf>) {
+    for input in &args.inputs {
+        let Ok(source) = read_source(input) else {
+            continue;
+        };
+        let included = match args.preprocessor.run(input, &display_name(input), &source) {
+            Ok(preprocessed) => preprocessed.files()[1..].to_vec(),
+            Err(err) => vec![err.file],
+        };
+        for file in included.into_iter().map(PathBuf::from) {
+            if !files.contains(&file) {
+                files.push(file);
+            }
+        }
+    }
+}
+
+/// What identifies each file's contents cheaply: its modification time and
+/// size, or nothing while it doesn't exist.
+fn stamps(paths: &[PathBuf]) -> Vec<Option<(SystemTime, u64)>> {
+    paths
+        .iter()
+        .map(|path| {
+            let metadata = fs::metadata(path).ok()?;
+            Some((metadata.modified().ok()?, metadata.len()))
+        })
+        .collect()
+}
+
+/// Which representation `main` is interpreted from.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Interpret {
+    /// The optimized IR (`-i`, `--interpret`, `--interpret=ir`).
+    Ir,
+    /// The analyzed syntax tree, before lowering (`--interpret=ast`).
+    Ast,
+}
+
+impl Interpret {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "ir" => Interpret::Ir,
+            "ast" => Interpret::Ast,
+            _ => return None,
+        })
+    }
+}
+
+/// Where intermediate files go: the assembly and object files made on the
+/// way to an object file or executable.
+enum Intermediates {
+    /// A temporary directory, removed after the build.
+    Temp(TempDir),
+    /// Next to each input, kept for inspection along with the preprocessed
+    /// source (`--save-temps`).
+    Saved,
+}
+
+impl Intermediates {
+    fn path(&self, input: &Path, extension: &str) -> PathBuf {
+        match self {
+            Intermediates::Temp(dir) => dir.file(input, extension),
+            Intermediates::Saved => input.with_extension(extension),
+        }
+    }
+}
+
+/// What compiling one input printed, held back so that inputs compiled in
+/// parallel report in order.
+#[derive(Default)]
+struct Report {
+    stdout: String,
+    stderr: String,
+}
+
+impl Report {
+    /// Records an error message and returns `failure`, for `map_err`.
+    fn error(&mut self, failure: Failure, message: impl std::fmt::Display) -> Failure {
+        self.stderr.push_str(&format!("{message}\n"));
+        failure
+    }
+
+    fn write(&mut self, path: &Path, text: &str) -> Result<(), Failure> {
+        fs::write(path, text).map_err(|err| {
+            self.error(
+                Failure::Io,
+                format_args!("rcc: cannot write `{}`: {err}", path.display()),
+            )
+        })
+    }
+}
+
+/// Renders `first`, the error that stopped the compilation of
+/// `preprocessed`, along with the rest of the errors in it, up to
+/// `--max-errors` of them.
+fn render_errors(
+    args: &Args,
+    preprocessed: &Preprocessed,
+    standard: Standard,
+    first: Error,
+) -> String {
+    let mut errors = driver::diagnose(&preprocessed.source, standard);
+    if !errors.contains(&first) {
+        errors.insert(0, first);
+    }
+    let shown = match args.max_errors {
+        0 => errors.len(),
+        max => max.min(errors.len()),
+    };
+    let mut out: Vec<String> = errors[..shown]
+        .iter()
+        .map(|err| render_file_error(args, &preprocessed.locate(err.clone())))
+        .collect();
+    if shown < errors.len() {
+        let message = format!(
+            "{} more errors not shown; `--max-errors=0` shows them all",
+            errors.len() - shown
+        );
+        out.push(if args.json_diagnostics {
+            let note = preprocessed.locate(Error::msg(message));
+            note.to_json(Severity::Note, None).to_string()
+        } else {
+            format!("rcc: note: {message}")
+        });
+    }
+    out.join("\n")
+}
+
+/// Renders `err` as text or as JSON, as `--diagnostics-format` asks.
+fn render_file_error(args: &Args, err: &FileError) -> String {
+    if args.json_diagnostics {
+        err.to_json(Severity::Error, None).to_string()
+    } else {
+        err.render_colored(args.color_stderr)
+    }
+}
+
+/// Whether the `--diagnostics-format` named `name` is JSON.
+fn diagnostics_format(name: &str) -> Result<bool, String> {
+    match name {
+        "text" => Ok(false),
+        "json" => Ok(true),
+        _ => Err(format!("unknown diagnostics format `{name}`")),
+    }
+}
+
+/// Renders the `warnings` about `preprocessed`, with whether any of them
+/// is an error under `-Werror`.
+fn render_warnings(
+    args: &Args,
+    preprocessed: &Preprocessed,
+    options: &Options,
+    warnings: &[Diagnostic],
+) -> (String, bool) {
+    let mut fatal = false;
+    let rendered: Vec<String> = warnings
+        .iter()
+        .map(|warning| {
+            let as_error = options.warnings.is_error(warning.warning);
+            fatal |= as_error;
+            let severity = if as_error {
+                Severity::Error
+            } else {
+                Severity::Warning
+            };
+            if args.json_diagnostics {
+                // The code names the warning, so the message leaves out
+                // the flag.
+                let error = Error::new(warning.pos, warning.message.clone());
+                let code = Some(warning.warning.name());
+                return preprocessed
+                    .locate(error)
+                    .to_json(severity, code)
+                    .to_string();
+            }
+            preprocessed
+                .locate(warning.to_error(as_error))
+                .render_as(severity, args.color_stderr)
+        })
+        .collect();
+    (rendered.join("\n"), fatal)
+}
+
+/// Compiles `input` to the output `args` asks for, recording what it
+/// prints and any error in `report`. When building an executable, returns
+/// the temporary assembly file for the linker, which the caller removes.
+fn compile_file(
+    args: &Args,
+    input: &Path,
+    intermediates: &Intermediates,
+    report: &mut Report,
+) -> Result<Option<PathBuf>, Failure> {
+    let file = display_name(input);
+    let options = args
+        .options_for(input)
+        .map_err(|message| report.error(Failure::Io, format_args!("rcc: {message}")))?;
+    let source = read_source(input).map_err(|err| {
+        report.error(
+            Failure::Io,
+            format_args!("rcc: cannot read `{file}`: {err}"),
+        )
+    })?;
+    let preprocessed = args
+        .preprocessor
+        .run(input, &file, &source)
+        .map_err(|err| report.error(Failure::Diagnostics, render_file_error(args, &err)))?;
+    let source = &preprocessed.source;
+    if let Intermediates::Saved = intermediates {
+        report.write(&input.with_extension("i"), source)?;
+    }
+
+    // Timings, statistics, the determinism check and crashes asked for
+    // with `RCC_DEBUG_CRASH` come from compiling, so they bypass the cache,
+    // and `--check` (which emits nothing) writes nothing, not even to it.
+    let compile_anyway = args.emit.is_empty()
+        || args.timings
+        || args.stats
+        || args.deterministic
+        || std::env::var_os("RCC_DEBUG_CRASH").is_some();
+    let cache = args.cache.as_ref().filter(|_| !compile_anyway);
+    let key = cache.map(|_| args.cache_key(&file, &options, source));
+    if let Some(entry) = cache.zip(key).and_then(|(cache, key)| cache.get(key)) {
+        report.stderr.push_str(entry.get("warnings").unwrap_or(""));
+        return write_outputs(args, input, &options, intermediates, report, &entry);
+    }
+
+    let mut timings = Timings::new();
+    ice::set_file(&file);
+    let compiled = panic::catch_unwind(AssertUnwindSafe(|| {
+        driver::compile_timed(source, &options, &mut timings)
+    }))
+    .map_err(|_| {
+        // The panic hook has reported the panic itself.
+        let dump = toolchain::temp_path(input, "ice.txt");
+        if report.write(&dump, &ice::dump(source, &options)).is_ok() {
+            report.error(
+                Failure::Ice,
+                format_args!(
+                    "note: the compiler's state is in `{}`; please attach it to the report",
+                    dump.display()
+                ),
+            );
+        }
+        Failure::Ice
+    })?;
+    if args.timings {
+        report
+            .stderr
+            .push_str(&format!("rcc: timings for `{file}`:\n{timings}"));
+    }
+    let artifacts = compiled.map_err(|err| {
+        report.error(
+            Failure::Diagnostics,
+            render_errors(args, &preprocessed, options.standard, err),
+        )
+    })?;
+    let mut entry = Entry::default();
+    if !artifacts.warnings.is_empty() {
+        let (warnings, fatal) = render_warnings(args, &preprocessed, &options, &artifacts.warnings);
+        if fatal {
+            return Err(report.error(Failure::Diagnostics, warnings));
+        }
+        report.stderr.push_str(&format!("{warnings}\n"));
+        entry.insert("warnings", format!("{warnings}\n"));
+    }
+    if args.stats {
+        let stats = Stats::collect(&artifacts, &options);
+        report
+            .stderr
+            .push_str(&format!("rcc: statistics for `{file}`:\n{stats}"));
+    }
+    if args.deterministic {
+        let again = driver::compile(source, &options).expect("the first compilation succeeded");
+        if let Some(difference) = driver::first_difference(&artifacts, &again) {
+            // Nondeterministic output is a bug in rcc.
+            return Err(report.error(
+                Failure::Ice,
+                format_args!("rcc: compiling `{file}` twice gave different output: {difference}"),
+            ));
+        }
+    }
+
+    // Each textual output is named by its extension, and the assembly to
+    // make objects from is `s`, as it is for `-S`.
+    for &emit in &args.emit {
+        match emit.extension() {
+            Some(extension) => entry.insert(
+                extension,
+                emit.text(&artifacts, &file, source)
+                    .expect("artifact should be textual"),
+            ),
+            None => entry.insert("s", artifacts.assembly.clone()),
+        }
+    }
+    if let Some((cache, key)) = cache.zip(key) {
+        // Without the cache, the next build just compiles again.
+        if let Err(err) = cache.put(key, &entry) {
+            log!(Warn, "cache", "cannot store the outputs of `{file}`: {err}");
+        }
+    }
+    write_outputs(args, input, &options, intermediates, report, &entry)
+}
+
+/// Writes the outputs of compiling `input` in `entry` where `args` asks
+/// for them, assembling any objects. When building an executable, returns
+/// the temporary object file for the linker.
+fn write_outputs(
+    args: &Args,
+    input: &Path,
+    options: &Options,
+    intermediates: &Intermediates,
+    report: &mut Report,
+    entry: &Entry,
+) -> Result<Option<PathBuf>, Failure> {
+    let mut linker_input = None;
+    for &emit in &args.emit {
+        match emit {
+            Emit::Executable | Emit::Object => {
+                let asm = intermediates.path(input, "s");
+                report.write(&asm, entry.get("s").expect("the assembly was stored"))?;
+                let object = if emit == Emit::Object {
+                    args.output_path(input, emit, "o")
+                } else {
+                    intermediates.path(input, "o")
+                };
+                Toolchain::new(options.target)
+                    .assemble(&asm, &object)
+                    .map_err(|message| report.error(Failure::Io, format_args!("rcc: {message}")))?;
+                if emit == Emit::Executable {
+                    linker_input = Some(object);
+                }
+            }
+            _ => {
+                let extension = emit.extension().expect("artifact should be textual");
+                let text = entry.get(extension).expect("the artifact was stored");
+                if args.stdout && args.color_stdout {
+                    let highlighted = color::highlight(text, emit.comment_prefix(options));
+                    report.stdout.push_str(&highlighted);
+                } else if args.stdout {
+                    report.stdout.push_str(text);
+                } else {
+                    report.write(&args.output_path(input, emit, extension), text)?;
+                }
+            }
+        }
+    }
+    Ok(linker_input)
 }
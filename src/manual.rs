@@ -0,0 +1,601 @@
+//! Shell completions (`rcc completions <shell>`) and the man page
+//! (`rcc man`), both generated from one table of the command line.
+
+use std::fmt::Write;
+
+use crate::codegen::Target;
+
+/// A command-line option.
+pub struct Flag {
+    /// The spellings, the first being the one documented.
+    pub names: &'static [&'static str],
+    /// What the value is called, for options that take one.
+    pub value: Option<&'static str>,
+    /// The values it accepts, if there is a fixed set.
+    pub choices: fn() -> Vec<String>,
+    pub help: &'static str,
+}
+
+impl Flag {
+    /// Whether the value is attached with `=` rather than passed as the
+    /// next argument.
+    fn joined(&self) -> bool {
+        self.names[0].ends_with('=')
+    }
+}
+
+fn none() -> Vec<String> {
+    Vec::new()
+}
+
+fn emit_kinds() -> Vec<String> {
+    [
+        "tokens",
+        "tokens-json",
+        "ast",
+        "ast-json",
+        "ast-dot",
+        "c",
+        "tags",
+        "header",
+        "ir",
+        "llvm",
+        "wat",
+        "asm",
+        "asm-map",
+        "report",
+        "obj",
+        "exe",
+        "all",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+fn standards() -> Vec<String> {
+    [
+        "c89", "c99", "c11", "c17", "c23", "gnu89", "gnu99", "gnu11", "gnu17", "gnu23",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+fn syntaxes() -> Vec<String> {
+    vec!["att".to_string(), "intel".to_string()]
+}
+
+fn visibilities() -> Vec<String> {
+    vec!["default".to_string(), "hidden".to_string()]
+}
+
+fn color_choices() -> Vec<String> {
+    ["auto", "always", "never"].map(String::from).to_vec()
+}
+
+fn interpreters() -> Vec<String> {
+    ["ir", "ast"].map(String::from).to_vec()
+}
+
+fn diagnostics_formats() -> Vec<String> {
+    ["text", "json"].map(String::from).to_vec()
+}
+
+fn triples() -> Vec<String> {
+    [
+        Target::AARCH64_APPLE,
+        Target::AARCH64_LINUX,
+        Target::X86_64_APPLE,
+        Target::X86_64_LINUX,
+        Target::WASM32,
+    ]
+    .map(|target| target.triple().to_string())
+    .to_vec()
+}
+
+const fn flag(names: &'static [&'static str], help: &'static str) -> Flag {
+    Flag {
+        names,
+        value: None,
+        choices: none,
+        help,
+    }
+}
+
+const fn valued(
+    names: &'static [&'static str],
+    value: &'static str,
+    choices: fn() -> Vec<String>,
+    help: &'static str,
+) -> Flag {
+    Flag {
+        names,
+        value: Some(value),
+        choices,
+        help,
+    }
+}
+
+/// Every option of the compile command.
+pub const FLAGS: &[Flag] = &[
+    flag(&["-O0"], "Do not optimize (the default)."),
+    flag(&["-O1", "-O"], "Optimize."),
+    flag(&["-O2"], "Optimize more, inlining small functions."),
+    flag(&["-Os"], "Optimize for size."),
+    flag(&["-g"], "Emit DWARF debug info and unwind tables."),
+    flag(
+        &["-gline-tables-only"],
+        "Emit only the line table and unwind tables of the debug info.",
+    ),
+    flag(
+        &["-fverbose-asm"],
+        "Quote the source in comments in the assembly.",
+    ),
+    flag(
+        &["-fPIC", "-fpic", "-fPIE", "-fpie"],
+        "Generate position-independent code.",
+    ),
+    flag(
+        &["-fno-pic", "-fno-PIC", "-fno-pie", "-fno-PIE"],
+        "Generate position-dependent code.",
+    ),
+    valued(
+        &["-fvisibility="],
+        "visibility",
+        visibilities,
+        "Export symbols with this visibility.",
+    ),
+    flag(
+        &["-fomit-frame-pointer"],
+        "Let leaf functions skip the frame pointer.",
+    ),
+    flag(
+        &["-fno-omit-frame-pointer"],
+        "Keep the frame pointer in every function.",
+    ),
+    flag(
+        &["-fsanitize=signed-integer-overflow"],
+        "Trap on signed integer overflow.",
+    ),
+    flag(
+        &["-fstack-protector"],
+        "Check a canary before returning from functions with stack buffers.",
+    ),
+    flag(&["-fno-stack-protector"], "Do not check stack canaries."),
+    flag(
+        &["-pg"],
+        "Call mcount on entry to every function, for gprof.",
+    ),
+    flag(
+        &["--coverage"],
+        "Count how often each line runs, for rcc cov report.",
+    ),
+    flag(
+        &["--check", "-fsyntax-only"],
+        "Only check the input for errors and warnings; write nothing.",
+    ),
+    flag(
+        &["--timings", "--time-passes"],
+        "Report the time, memory and output of each stage.",
+    ),
+    flag(
+        &["--stats"],
+        "Report counts of tokens, nodes, IR and instructions.",
+    ),
+    flag(
+        &["--deterministic"],
+        "Compile twice and check that the outputs match.",
+    ),
+    flag(
+        &["--save-temps"],
+        "Keep the .i, .s and .o files next to each input.",
+    ),
+    flag(
+        &["--watch"],
+        "Rebuild whenever an input or a file it includes changes.",
+    ),
+    flag(
+        &["--run"],
+        "Run the executable once it is built, exiting with its status.",
+    ),
+    flag(
+        &["-i", "--interpret"],
+        "Run main in the IR interpreter instead of compiling.",
+    ),
+    valued(
+        &["--interpret="],
+        "interpreter",
+        interpreters,
+        "Run main in this interpreter instead of compiling: ir, or ast to evaluate the syntax tree before lowering.",
+    ),
+    flag(
+        &["--no-cache"],
+        "Compile every input, without reading or filling .rcc-cache.",
+    ),
+    flag(
+        &["--cache-stats"],
+        "Report how many inputs the cache had the outputs of.",
+    ),
+    flag(
+        &["-v", "-vv", "-vvv"],
+        "Log the compiler's decisions; repeat for more.",
+    ),
+    valued(
+        &["-j"],
+        "jobs",
+        none,
+        "Compile up to this many inputs at once.",
+    ),
+    valued(
+        &["-I"],
+        "dir",
+        none,
+        "Look for included files here too; repeat for more.",
+    ),
+    valued(
+        &["-D"],
+        "name[=value]",
+        none,
+        "Define a macro, as 1 if no value is given.",
+    ),
+    valued(&["-U"], "name", none, "Undefine a macro."),
+    flag(
+        &["-Wall"],
+        "Warn about unused variables and labels and missing returns; -Wno-<warning> turns one off.",
+    ),
+    flag(
+        &["-Wunused-variable"],
+        "Warn about local variables that are never used.",
+    ),
+    flag(&["-Wunused-label"], "Warn about labels no goto jumps to."),
+    flag(
+        &["-Wreturn-type"],
+        "Warn about functions that can end without returning a value.",
+    ),
+    flag(
+        &["-Wunreachable-code"],
+        "Warn about statements that can never run.",
+    ),
+    flag(
+        &["-Werror"],
+        "Treat warnings as errors, or just one with -Werror=<warning>.",
+    ),
+    flag(&["-w"], "Turn every warning off."),
+    valued(
+        &["-masm="],
+        "syntax",
+        syntaxes,
+        "Print x86-64 assembly in this syntax.",
+    ),
+    valued(
+        &["--std=", "-std="],
+        "standard",
+        standards,
+        "Accept this version of C.",
+    ),
+    valued(
+        &["--target"],
+        "triple",
+        triples,
+        "Generate code for this target.",
+    ),
+    valued(
+        &["--emit="],
+        "kinds",
+        emit_kinds,
+        "Produce these artifacts, comma-separated; `all` is the tokens, AST, IR and assembly.",
+    ),
+    flag(&["-S"], "Write assembly."),
+    flag(&["-c"], "Write an object file."),
+    valued(
+        &["-o"],
+        "file",
+        none,
+        "Write the output here; `-` prints it.",
+    ),
+    flag(&["--stdout"], "Print the output instead of writing a file."),
+    flag(
+        &["--stdin"],
+        "Read a program from standard input, as the input `-` does.",
+    ),
+    flag(&["--cc"], "Accept the flags Makefiles pass to cc."),
+    valued(
+        &["--color="],
+        "when",
+        color_choices,
+        "Color diagnostics and printed output: auto, always or never.",
+    ),
+    valued(
+        &["--diagnostics-format="],
+        "format",
+        diagnostics_formats,
+        "Print diagnostics as text or as JSON objects, one per line.",
+    ),
+    valued(
+        &["--max-errors=", "-fmax-errors="],
+        "count",
+        none,
+        "Report at most this many errors for each input, or all of them for 0.",
+    ),
+    flag(&["-h", "--help"], "Print usage."),
+];
+
+/// The subcommands, with their arguments and what they do.
+pub const COMMANDS: &[(&str, &str, &str)] = &[
+    (
+        "cov",
+        "report <file.rcccov>",
+        "Print the source annotated with coverage counts.",
+    ),
+    (
+        "test",
+        "[-O<level>] <dir>",
+        "Check every fixture in a directory.",
+    ),
+    (
+        "diff",
+        "[-O<level>] <file.c>...",
+        "Compare how programs run built by rcc and by cc.",
+    ),
+    (
+        "ast-diff",
+        "<old.c> <new.c>",
+        "Show how two files' syntax trees differ.",
+    ),
+    ("repl", "", "Evaluate C interactively."),
+    ("lsp", "", "Run a language server on stdin and stdout."),
+    (
+        "completions",
+        "<shell>",
+        "Print a completion script for bash, zsh or fish.",
+    ),
+    ("man", "", "Print this manual page."),
+];
+
+/// The shells [`completions`] supports.
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+/// The completion script for `shell`, if it is supported.
+pub fn completions(shell: &str) -> Option<String> {
+    match shell {
+        "bash" => Some(bash()),
+        "zsh" => Some(zsh()),
+        "fish" => Some(fish()),
+        _ => None,
+    }
+}
+
+fn bash() -> String {
+    let mut words = Vec::new();
+    for flag in FLAGS {
+        for name in flag.names {
+            if flag.joined() {
+                words.extend(
+                    (flag.choices)()
+                        .iter()
+                        .map(|choice| format!("{name}{choice}")),
+                );
+            } else {
+                words.push(name.to_string());
+            }
+        }
+    }
+    let commands: Vec<&str> = COMMANDS.iter().map(|(name, ..)| *name).collect();
+    let mut out = String::from("_rcc() {\n");
+    out.push_str("    local cur=${COMP_WORDS[COMP_CWORD]} prev=${COMP_WORDS[COMP_CWORD-1]}\n");
+    out.push_str("    case $prev in\n");
+    for flag in FLAGS
+        .iter()
+        .filter(|flag| flag.value.is_some() && !flag.joined())
+    {
+        let choices = (flag.choices)();
+        let reply = if !choices.is_empty() {
+            format!("$(compgen -W \"{}\" -- \"$cur\")", choices.join(" "))
+        } else if flag.value == Some("file") {
+            "$(compgen -f -- \"$cur\")".to_string()
+        } else {
+            String::new()
+        };
+        writeln!(
+            out,
+            "        {}) COMPREPLY=({reply}); return ;;",
+            flag.names.join("|")
+        )
+        .unwrap();
+    }
+    out.push_str("        completions) COMPREPLY=($(compgen -W \"");
+    out.push_str(&SHELLS.join(" "));
+    out.push_str("\" -- \"$cur\")); return ;;\n");
+    out.push_str("    esac\n");
+    out.push_str("    if [[ $cur == -* ]]; then\n");
+    writeln!(
+        out,
+        "        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+        words.join(" ")
+    )
+    .unwrap();
+    out.push_str("    elif [[ $COMP_CWORD -eq 1 ]]; then\n");
+    writeln!(
+        out,
+        "        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\") $(compgen -f -X '!*.c' -- \"$cur\"))",
+        commands.join(" ")
+    )
+    .unwrap();
+    out.push_str("    else\n");
+    out.push_str("        COMPREPLY=($(compgen -f -X '!*.[co]' -- \"$cur\"))\n");
+    out.push_str("    fi\n}\ncomplete -o filenames -o bashdefault -F _rcc rcc\n");
+    out
+}
+
+/// `text` for inside single quotes in a shell script.
+fn quote(text: &str) -> String {
+    text.replace('\'', "'\\''")
+}
+
+/// `text` for a description in an `_arguments` spec, where brackets and
+/// colons are special.
+fn zsh_escape(text: &str) -> String {
+    quote(text)
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn zsh() -> String {
+    let mut out = String::from("#compdef rcc\n\n_arguments \\\n");
+    for flag in FLAGS {
+        let help = zsh_escape(flag.help);
+        let action = match flag.value {
+            None => String::new(),
+            Some(value) => {
+                let choices = (flag.choices)();
+                if !choices.is_empty() {
+                    format!(":{value}:({})", choices.join(" "))
+                } else if value == "file" {
+                    format!(":{value}:_files")
+                } else {
+                    format!(":{value}: ")
+                }
+            }
+        };
+        let separator = if flag.joined() || flag.value.is_none() {
+            ""
+        } else {
+            "+"
+        };
+        for name in flag.names {
+            writeln!(out, "  '{name}{separator}[{help}]{action}' \\").unwrap();
+        }
+    }
+    let commands: Vec<String> = COMMANDS
+        .iter()
+        .map(|(name, _, help)| format!("{name}\\:\"{}\"", zsh_escape(help)))
+        .collect();
+    writeln!(out, "  '1::command:(({}))' \\", commands.join(" ")).unwrap();
+    out.push_str("  '*:input:_files -g \"*.[co]\"'\n");
+    out
+}
+
+fn fish() -> String {
+    let mut out = String::new();
+    for (name, _, help) in COMMANDS {
+        writeln!(
+            out,
+            "complete -c rcc -n __fish_use_subcommand -a {name} -d '{}'",
+            quote(help)
+        )
+        .unwrap();
+    }
+    for flag in FLAGS {
+        for name in flag.names {
+            let option = match name.strip_prefix("--") {
+                Some(long) => format!("-l {}", long.trim_end_matches('=')),
+                None => format!("-o {}", name[1..].trim_end_matches('=')),
+            };
+            let mut line = format!("complete -c rcc {option}");
+            if flag.value.is_some() {
+                line.push_str(" -r");
+                let choices = (flag.choices)();
+                if !choices.is_empty() {
+                    write!(line, " -f -a '{}'", choices.join(" ")).unwrap();
+                }
+            }
+            writeln!(out, "{line} -d '{}'", quote(flag.help)).unwrap();
+        }
+    }
+    writeln!(
+        out,
+        "complete -c rcc -n '__fish_seen_subcommand_from completions' -f -a '{}'",
+        SHELLS.join(" ")
+    )
+    .unwrap();
+    out
+}
+
+/// `text` with roff's special characters escaped.
+fn roff(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    match text.strip_prefix('.').or(text.strip_prefix('\'')) {
+        Some(_) => format!("\\&{text}"),
+        None => text,
+    }
+}
+
+/// The manual page, in roff.
+pub fn man_page() -> String {
+    let mut out = format!(
+        ".TH RCC 1 \"\" \"rcc {}\" \"User Commands\"\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    out.push_str(".SH NAME\nrcc \\- a small C compiler\n");
+    out.push_str(".SH SYNOPSIS\n.B rcc\n[\\fIoptions\\fR] \\fIfile.c\\fR|\\fIfile.o\\fR...\n");
+    for (name, args, _) in COMMANDS {
+        writeln!(out, ".br\n.B rcc {name}\n{}", roff(args)).unwrap();
+    }
+    out.push_str(".SH DESCRIPTION\n");
+    out.push_str(
+        "Compiles C translation units to assembly, object files or an executable, \
+         assembling and linking with the system C compiler.\n",
+    );
+    out.push_str("Any argument \\fB@\\fIfile\\fR is replaced by the arguments in the file.\n");
+    out.push_str(".SH OPTIONS\n");
+    for flag in FLAGS {
+        let names: Vec<String> = flag
+            .names
+            .iter()
+            .map(|name| {
+                let mut text = format!("\\fB{}\\fR", roff(name));
+                if let Some(value) = flag.value {
+                    let space = if flag.joined() { "" } else { " " };
+                    write!(text, "{space}\\fI{value}\\fR").unwrap();
+                }
+                text
+            })
+            .collect();
+        writeln!(out, ".TP\n{}\n{}", names.join(", "), roff(flag.help)).unwrap();
+        let choices = (flag.choices)();
+        if !choices.is_empty() {
+            writeln!(out, "One of: {}.", roff(&choices.join(", "))).unwrap();
+        }
+    }
+    out.push_str(".SH COMMANDS\n");
+    for (name, args, help) in COMMANDS {
+        writeln!(
+            out,
+            ".TP\n\\fBrcc {name}\\fR {}\n{}",
+            roff(args),
+            roff(help)
+        )
+        .unwrap();
+    }
+    out.push_str(".SH ENVIRONMENT\n");
+    for (name, help) in [
+        (
+            "RCC_CC",
+            "The C compiler that assembles and links, instead of cc.",
+        ),
+        (
+            "RCC_LOG",
+            "Which internal decisions to log, as in RCC_LOG=regalloc=trace.",
+        ),
+        (
+            "RCC_DEBUG_CRASH",
+            "Makes the named stage crash, to test crash reports.",
+        ),
+    ] {
+        writeln!(out, ".TP\n.B {name}\n{}", roff(help)).unwrap();
+    }
+    out.push_str(".SH EXIT STATUS\n");
+    for (status, help) in [
+        ("0", "Success."),
+        ("1", "The input has errors."),
+        ("2", "The command line is malformed."),
+        (
+            "3",
+            "A file cannot be read or written, or the assembler or linker failed.",
+        ),
+        ("101", "rcc crashed; please report it."),
+    ] {
+        writeln!(out, ".TP\n.B {status}\n{}", roff(help)).unwrap();
+    }
+    out
+}
@@ -0,0 +1,122 @@
+//! Control-flow cleanup: folds branches whose condition has a known outcome, threads jumps
+//! through empty blocks and merges blocks joined by a lone jump edge.
+
+use std::collections::HashSet;
+
+use crate::ir::cfg::predecessors;
+use crate::ir::range::{Range, Ranges};
+use crate::ir::{Block, BlockId, Function, Terminator};
+
+/// Returns whether the control-flow graph changed.
+pub fn run(func: &mut Function) -> bool {
+    let mut changed = false;
+    loop {
+        let progress = fold_branches(func) | thread_jumps(func) | merge_blocks(func);
+        if !progress {
+            return changed;
+        }
+        func.remove_unreachable_blocks();
+        changed = true;
+    }
+}
+
+/// Turns branches and switches whose outcome is known from the condition's
+/// range into jumps.
+fn fold_branches(func: &mut Function) -> bool {
+    let ranges = Ranges::compute(func);
+    let mut changed = false;
+    for id in func.block_ids() {
+        let target = match func.block(id).term {
+            Terminator::Branch {
+                cond,
+                then_block,
+                else_block,
+            } => {
+                let range = ranges.range(func, cond);
+                if range == Range::constant(0) {
+                    else_block
+                } else if !range.contains(0) || then_block == else_block {
+                    then_block
+                } else {
+                    continue;
+                }
+            }
+            Terminator::Switch {
+                value,
+                ref cases,
+                default,
+            } => {
+                let range = ranges.range(func, value);
+                if range.min != range.max {
+                    continue;
+                }
+                cases
+                    .iter()
+                    .find(|&&(case, _)| case == range.min)
+                    .map_or(default, |&(_, target)| target)
+            }
+            _ => continue,
+        };
+        func.block_mut(id).term = Terminator::Jump(target);
+        changed = true;
+    }
+    changed
+}
+
+/// Retargets edges into empty blocks that only jump elsewhere.
+fn thread_jumps(func: &mut Function) -> bool {
+    let forward = |func: &Function, mut target: BlockId| {
+        let mut seen = HashSet::new();
+        while let Block {
+            instrs,
+            term: Terminator::Jump(next),
+        } = func.block(target)
+        {
+            if !instrs.is_empty() || !seen.insert(target) {
+                break;
+            }
+            target = *next;
+        }
+        target
+    };
+
+    let mut changed = false;
+    for id in func.block_ids() {
+        let mut term = func.block(id).term.clone();
+        for target in term.successors_mut() {
+            let threaded = forward(func, *target);
+            if threaded != *target {
+                *target = threaded;
+                changed = true;
+            }
+        }
+        func.block_mut(id).term = term;
+    }
+    changed
+}
+
+/// Appends a block to its only predecessor when that predecessor jumps straight to it.
+fn merge_blocks(func: &mut Function) -> bool {
+    let preds = predecessors(func);
+    let mut changed = false;
+    for id in func.block_ids() {
+        let Terminator::Jump(next) = func.block(id).term else {
+            continue;
+        };
+        if next == id || next == BlockId::ENTRY || preds[next.index()] != [id] {
+            continue;
+        }
+        let emptied = Block {
+            instrs: Vec::new(),
+            term: Terminator::Unreachable,
+        };
+        let merged = std::mem::replace(func.block_mut(next), emptied);
+        let block = func.block_mut(id);
+        block.instrs.extend(merged.instrs);
+        block.term = merged.term;
+        changed = true;
+        // Predecessor lists are stale now; pick up further merges next round.
+        break;
+    }
+    changed
+}
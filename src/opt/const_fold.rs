@@ -0,0 +1,70 @@
+//! Constant folding: evaluates instructions whose operands are all constants.
+
+use std::collections::HashMap;
+
+use crate::ir::{Function, Instr, Value};
+
+/// Returns whether anything was folded.
+pub fn run(func: &mut Function) -> bool {
+    let mut known: HashMap<Value, i64> = HashMap::new();
+    let mut changed = false;
+    // Blocks are not necessarily in dominance order, so iterate to a fixed point.
+    loop {
+        let mut progress = false;
+        for instr in func.blocks.iter_mut().flat_map(|b| &mut b.instrs) {
+            match *instr {
+                Instr::Const { dst, value } => {
+                    known.insert(dst, value);
+                }
+                Instr::Binary { dst, op, lhs, rhs } => {
+                    let (Some(&lhs), Some(&rhs)) = (known.get(&lhs), known.get(&rhs)) else {
+                        continue;
+                    };
+                    let ty = func.value_types[dst.0 as usize];
+                    if let Some(value) = op.evaluate(ty, lhs, rhs) {
+                        *instr = Instr::Const { dst, value };
+                        known.insert(dst, value);
+                        progress = true;
+                    }
+                }
+                Instr::Cast { dst, op, value } => {
+                    let Some(&known_value) = known.get(&value) else {
+                        continue;
+                    };
+                    let (from, to) = (
+                        func.value_types[value.0 as usize],
+                        func.value_types[dst.0 as usize],
+                    );
+                    let value = op.evaluate(from, to, known_value);
+                    *instr = Instr::Const { dst, value };
+                    known.insert(dst, value);
+                    progress = true;
+                }
+                Instr::Cmp { dst, op, lhs, rhs } => {
+                    let (Some(&l), Some(&r)) = (known.get(&lhs), known.get(&rhs)) else {
+                        continue;
+                    };
+                    let value = i64::from(op.evaluate(func.value_types[lhs.0 as usize], l, r));
+                    *instr = Instr::Const { dst, value };
+                    known.insert(dst, value);
+                    progress = true;
+                }
+                Instr::Call { .. }
+                | Instr::Load { .. }
+                | Instr::Store { .. }
+                | Instr::LoadPtr { .. }
+                | Instr::StorePtr { .. }
+                | Instr::SlotAddr { .. }
+                | Instr::Alloca { .. }
+                | Instr::StringAddr { .. }
+                | Instr::GlobalAddr { .. }
+                | Instr::Loc { .. }
+                | Instr::InlineAsm { .. } => {}
+            }
+        }
+        if !progress {
+            return changed;
+        }
+        changed = true;
+    }
+}
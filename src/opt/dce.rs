@@ -0,0 +1,35 @@
+//! Dead code elimination: removes side-effect-free instructions whose results are unused.
+
+use std::collections::HashSet;
+
+use crate::ir::{Function, Instr};
+
+/// Returns whether any instruction was removed.
+pub fn run(func: &mut Function) -> bool {
+    let mut changed = false;
+    loop {
+        let used: HashSet<_> = func
+            .blocks
+            .iter()
+            .flat_map(|b| {
+                b.instrs
+                    .iter()
+                    .flat_map(Instr::operands)
+                    .chain(b.term.operands())
+            })
+            .collect();
+
+        let mut removed = false;
+        for block in &mut func.blocks {
+            let before = block.instrs.len();
+            block.instrs.retain(|instr| {
+                instr.has_side_effects() || instr.dst().is_some_and(|dst| used.contains(&dst))
+            });
+            removed |= block.instrs.len() != before;
+        }
+        if !removed {
+            return changed;
+        }
+        changed = true;
+    }
+}
@@ -0,0 +1,184 @@
+//! Inlining of calls to small leaf functions.
+
+use std::collections::HashMap;
+
+use crate::ir::{Block, BlockId, Function, Instr, Module, StackSlot, Terminator, Value};
+
+/// Callees with more non-constant instructions than this are never inlined.
+const INLINE_THRESHOLD: usize = 16;
+
+/// Returns whether any call was inlined.
+pub fn run(module: &mut Module) -> bool {
+    let candidates: HashMap<String, Function> = module
+        .functions
+        .iter()
+        .filter(|f| is_inlinable(f))
+        .map(|f| (f.name.clone(), f.clone()))
+        .collect();
+
+    let mut changed = false;
+    for caller in &mut module.functions {
+        while let Some((block, index, callee)) = find_call_site(caller, &candidates) {
+            crate::log!(
+                Debug,
+                "inline",
+                "inlining `{}` into `{}`",
+                callee.name,
+                caller.name
+            );
+            inline_call(caller, block, index, callee);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// A small leaf function with a single return, so its result needs no merging.
+/// Dynamic allocations are only released when the caller returns, so a callee
+/// that makes them is not inlined either.
+fn is_inlinable(func: &Function) -> bool {
+    let instrs = || func.blocks.iter().flat_map(|b| &b.instrs);
+    let size = instrs()
+        .filter(|instr| !matches!(instr, Instr::Const { .. } | Instr::Loc { .. }))
+        .count();
+    let returns = func
+        .blocks
+        .iter()
+        .filter(|b| matches!(b.term, Terminator::Ret(_)))
+        .count();
+    size <= INLINE_THRESHOLD
+        && returns == 1
+        && !instrs().any(|instr| matches!(instr, Instr::Call { .. } | Instr::Alloca { .. }))
+}
+
+fn find_call_site<'a>(
+    caller: &Function,
+    candidates: &'a HashMap<String, Function>,
+) -> Option<(BlockId, usize, &'a Function)> {
+    caller.block_ids().find_map(|id| {
+        caller
+            .block(id)
+            .instrs
+            .iter()
+            .enumerate()
+            .find_map(|(index, instr)| match instr {
+                Instr::Call { callee, .. } => Some((id, index, candidates.get(callee)?)),
+                _ => None,
+            })
+    })
+}
+
+/// Replaces the call at `caller.blocks[block].instrs[index]` with a copy of `callee`'s body.
+fn inline_call(caller: &mut Function, block: BlockId, index: usize, callee: &Function) {
+    let Instr::Call { dst, args, .. } = caller.block_mut(block).instrs.remove(index) else {
+        unreachable!("call site must point at a call");
+    };
+
+    let mut values: HashMap<Value, Value> = callee.params.iter().copied().zip(args).collect();
+    // Each inlined copy gets its own locals.
+    let slots: Vec<StackSlot> = callee.slots.iter().map(|&ty| caller.new_slot(ty)).collect();
+    let mut map_value = |caller: &mut Function, value: Value| {
+        *values
+            .entry(value)
+            .or_insert_with(|| caller.new_value(callee.value_type(value)))
+    };
+
+    // The callee's entry block is spliced into the caller's block; the rest get fresh ids.
+    let first_new = caller.blocks.len() as u32;
+    let map_block = |id: BlockId| {
+        if id == BlockId::ENTRY {
+            block
+        } else {
+            BlockId(first_new + id.0 - 1)
+        }
+    };
+
+    let mut copies = Vec::with_capacity(callee.blocks.len());
+    let mut returned = None;
+    for callee_block in &callee.blocks {
+        let mut instrs = callee_block.instrs.clone();
+        // The callee's source locations would describe the caller's code
+        // that follows the inlined body.
+        instrs.retain(|instr| !matches!(instr, Instr::Loc { .. }));
+        for instr in &mut instrs {
+            for operand in instr.operands_mut() {
+                *operand = map_value(caller, *operand);
+            }
+            match instr {
+                Instr::Const { dst, .. }
+                | Instr::Binary { dst, .. }
+                | Instr::Cmp { dst, .. }
+                | Instr::Cast { dst, .. }
+                | Instr::LoadPtr { dst, .. }
+                | Instr::Alloca { dst, .. }
+                | Instr::StringAddr { dst, .. }
+                | Instr::GlobalAddr { dst, .. } => {
+                    *dst = map_value(caller, *dst);
+                }
+                Instr::Load { dst, slot } | Instr::SlotAddr { dst, slot } => {
+                    *dst = map_value(caller, *dst);
+                    *slot = slots[slot.0 as usize];
+                }
+                Instr::Store { slot, .. } => *slot = slots[slot.0 as usize],
+                Instr::StorePtr { .. } => {}
+                Instr::InlineAsm { dst, .. } => {
+                    if let Some(dst) = dst {
+                        *dst = map_value(caller, *dst);
+                    }
+                }
+                Instr::Call { .. } => unreachable!("inlined functions are leaves"),
+                Instr::Loc { .. } => unreachable!("locations were dropped"),
+            }
+        }
+        let mut term = callee_block.term.clone();
+        for operand in term.operands_mut() {
+            *operand = map_value(caller, *operand);
+        }
+        for target in term.successors_mut() {
+            *target = map_block(*target);
+        }
+        if let Terminator::Ret(value) = term {
+            returned = value;
+        }
+        copies.push((instrs, term));
+    }
+
+    let tail = caller.block_mut(block).instrs.split_off(index);
+    let mut copies = copies.into_iter();
+    let (entry_instrs, entry_term) = copies.next().expect("functions have an entry block");
+    let caller_block = caller.block_mut(block);
+    caller_block.instrs.extend(entry_instrs);
+    let original_term = std::mem::replace(&mut caller_block.term, entry_term);
+
+    let continuation = if callee.blocks.len() == 1 {
+        // The callee returned from its entry block: resume right after the splice.
+        caller_block.term = original_term;
+        caller_block.instrs.extend(tail);
+        None
+    } else {
+        for (instrs, term) in copies {
+            caller.blocks.push(Block { instrs, term });
+        }
+        let continuation = caller.add_block();
+        *caller.block_mut(continuation) = Block {
+            instrs: tail,
+            term: original_term,
+        };
+        Some(continuation)
+    };
+
+    if let Some(continuation) = continuation {
+        for id in caller
+            .block_ids()
+            .filter(|&id| id == block || id.0 >= first_new)
+        {
+            let term = &mut caller.block_mut(id).term;
+            if matches!(term, Terminator::Ret(_)) && id != continuation {
+                *term = Terminator::Jump(continuation);
+            }
+        }
+    }
+    if let (Some(dst), Some(returned)) = (dst, returned) {
+        caller.replace_uses(dst, returned);
+    }
+}
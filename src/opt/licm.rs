@@ -0,0 +1,125 @@
+//! Loop-invariant code motion: hoists pure computations whose operands do not
+//! change inside a loop into the loop's preheader.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ir::cfg::{self, Dominators, Loop};
+use crate::ir::{BinOp, BlockId, Function, Instr, Terminator, Value};
+
+/// Returns whether any instruction was hoisted.
+pub fn run(func: &mut Function) -> bool {
+    let mut changed = false;
+    // Creating a preheader changes the CFG, so recompute loops after each one.
+    'restart: loop {
+        let doms = Dominators::compute(func);
+        for lp in cfg::natural_loops(func, &doms) {
+            let preheader = match find_preheader(func, &lp) {
+                Some(preheader) => preheader,
+                None => {
+                    insert_preheader(func, &lp);
+                    continue 'restart;
+                }
+            };
+            changed |= hoist(func, &lp, preheader);
+        }
+        return changed;
+    }
+}
+
+/// The unique out-of-loop predecessor of the header, if it flows only into the header.
+fn find_preheader(func: &Function, lp: &Loop) -> Option<BlockId> {
+    let preds = cfg::predecessors(func);
+    let mut outside = preds[lp.header.index()]
+        .iter()
+        .filter(|p| !lp.contains(**p));
+    let candidate = *outside.next()?;
+    let only_pred = outside.next().is_none();
+    (only_pred && func.block(candidate).term == Terminator::Jump(lp.header)).then_some(candidate)
+}
+
+fn insert_preheader(func: &mut Function, lp: &Loop) {
+    let preheader = func.add_block();
+    func.block_mut(preheader).term = Terminator::Jump(lp.header);
+    for id in func.block_ids() {
+        if id == preheader || lp.contains(id) {
+            continue;
+        }
+        for target in func.block_mut(id).term.successors_mut() {
+            if *target == lp.header {
+                *target = preheader;
+            }
+        }
+    }
+}
+
+fn hoist(func: &mut Function, lp: &Loop, preheader: BlockId) -> bool {
+    let defined_in_loop: HashSet<Value> = lp
+        .blocks
+        .iter()
+        .flat_map(|&b| &func.block(b).instrs)
+        .filter_map(Instr::dst)
+        .collect();
+    let constants: HashMap<Value, i64> = func
+        .blocks
+        .iter()
+        .flat_map(|b| &b.instrs)
+        .filter_map(|instr| match *instr {
+            Instr::Const { dst, value } => Some((dst, value)),
+            _ => None,
+        })
+        .collect();
+    let is_safe_divisor =
+        |value: Value| constants.get(&value).is_some_and(|c| !matches!(c, 0 | -1));
+
+    let mut hoisted: HashSet<Value> = HashSet::new();
+    let mut moved = Vec::new();
+    // Visit loop blocks in reverse postorder so definitions are seen before uses.
+    let order: Vec<BlockId> = cfg::reverse_postorder(func)
+        .into_iter()
+        .filter(|b| lp.contains(*b))
+        .collect();
+    for block in order {
+        let instrs = std::mem::take(&mut func.block_mut(block).instrs);
+        let mut kept = Vec::with_capacity(instrs.len());
+        for instr in instrs {
+            let invariant = instr
+                .operands()
+                .iter()
+                .all(|v| !defined_in_loop.contains(v) || hoisted.contains(v));
+            let movable = match &instr {
+                Instr::Const { .. }
+                | Instr::Cmp { .. }
+                | Instr::Cast { .. }
+                | Instr::StringAddr { .. }
+                | Instr::GlobalAddr { .. }
+                | Instr::SlotAddr { .. } => true,
+                Instr::Binary { op, rhs, .. } => match op {
+                    BinOp::SDiv | BinOp::UDiv | BinOp::SRem | BinOp::URem => is_safe_divisor(*rhs),
+                    _ => true,
+                },
+                // Loads might observe a store made later in the loop.
+                Instr::Call { .. }
+                | Instr::Load { .. }
+                | Instr::Store { .. }
+                | Instr::LoadPtr { .. }
+                | Instr::StorePtr { .. }
+                | Instr::InlineAsm { .. } => false,
+                // Each execution reserves another block.
+                Instr::Alloca { .. } => false,
+                // Source locations stay with the code they describe.
+                Instr::Loc { .. } => false,
+            };
+            if invariant && movable {
+                hoisted.extend(instr.dst());
+                moved.push(instr);
+            } else {
+                kept.push(instr);
+            }
+        }
+        func.block_mut(block).instrs = kept;
+    }
+
+    let changed = !moved.is_empty();
+    func.block_mut(preheader).instrs.extend(moved);
+    changed
+}
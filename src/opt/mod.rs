@@ -0,0 +1,148 @@
+//! IR optimization passes and the pass manager that schedules them.
+
+mod branch_fold;
+mod const_fold;
+mod dce;
+mod inline;
+mod licm;
+mod simplify;
+mod strength_reduce;
+
+use crate::codegen::asm::Assembly;
+use crate::codegen::Backend;
+use crate::ir::{verify, Module};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OptLevel {
+    /// Straight lowering with no IR or assembly transformations.
+    #[default]
+    O0,
+    O1,
+    O2,
+    /// Like `O1`, but skips passes that trade code size for speed.
+    Os,
+}
+
+/// A transformation over the IR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrPass {
+    Inline,
+    ConstFold,
+    Simplify,
+    BranchFold,
+    StrengthReduce,
+    Licm,
+    Dce,
+}
+
+/// A transformation over the emitted assembly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsmPass {
+    Peephole,
+}
+
+/// An ordered pipeline of passes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PassManager {
+    pub ir_passes: Vec<IrPass>,
+    pub asm_passes: Vec<AsmPass>,
+    /// Keep values in registers rather than giving each one a stack slot.
+    pub allocate_registers: bool,
+    /// Let leaf functions with nothing on the stack skip setting up a frame
+    /// pointer.
+    pub omit_frame_pointer: bool,
+}
+
+impl PassManager {
+    pub fn for_level(level: OptLevel) -> Self {
+        use IrPass::*;
+        let ir_passes = match level {
+            OptLevel::O0 => vec![],
+            OptLevel::O1 => vec![ConstFold, Simplify, BranchFold, StrengthReduce, Dce],
+            // Inlining first exposes the callee's constants to folding.
+            OptLevel::O2 => vec![
+                Inline,
+                ConstFold,
+                Simplify,
+                BranchFold,
+                StrengthReduce,
+                Licm,
+                Dce,
+            ],
+            // Shift sequences for division are longer than `sdiv`, and inlining duplicates code.
+            OptLevel::Os => vec![ConstFold, Simplify, BranchFold, Licm, Dce],
+        };
+        let asm_passes = match level {
+            OptLevel::O0 => vec![],
+            _ => vec![AsmPass::Peephole],
+        };
+        Self {
+            ir_passes,
+            asm_passes,
+            allocate_registers: level != OptLevel::O0,
+            omit_frame_pointer: level != OptLevel::O0,
+        }
+    }
+
+    /// Runs the IR passes in order. Debug builds verify the module before the
+    /// first pass and after every pass.
+    pub fn run_ir(&self, module: &mut Module) {
+        check(module, "lowering");
+        crate::log!(Info, "opt", "IR passes: {:?}", self.ir_passes);
+        for pass in &self.ir_passes {
+            if *pass == IrPass::Inline {
+                let changed = inline::run(module);
+                crate::log!(Debug, "opt", "{pass:?} changed the module: {changed}");
+            } else {
+                for function in &mut module.functions {
+                    crate::ice::set_function(&function.name);
+                    let changed = match pass {
+                        IrPass::ConstFold => const_fold::run(function),
+                        IrPass::Simplify => simplify::run(function),
+                        IrPass::BranchFold => branch_fold::run(function),
+                        IrPass::StrengthReduce => strength_reduce::run(function),
+                        IrPass::Licm => licm::run(function),
+                        IrPass::Dce => dce::run(function),
+                        IrPass::Inline => unreachable!("module passes are handled above"),
+                    };
+                    crate::log!(
+                        Debug,
+                        "opt",
+                        "{pass:?} changed `{}`: {changed}",
+                        function.name
+                    );
+                }
+            }
+            check(module, &format!("{pass:?}"));
+        }
+    }
+
+    /// Runs the assembly passes in order, using `backend`'s implementation of
+    /// each.
+    pub fn run_asm(&self, mut asm: Assembly, backend: &dyn Backend) -> Assembly {
+        for pass in &self.asm_passes {
+            asm = match pass {
+                AsmPass::Peephole => backend.peephole(asm),
+            };
+        }
+        asm
+    }
+}
+
+/// Panics with the offending function if `module` is malformed. Only active in
+/// debug builds, where a broken pass should fail loudly rather than emit bad code.
+fn check(module: &Module, after: &str) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    if let Err(err) = verify::verify_module(module) {
+        let function = module.functions.iter().find(|f| f.name == err.function);
+        let ir = function.map(ToString::to_string).unwrap_or_default();
+        panic!("internal compiler error: invalid IR after {after}: {err}\n{ir}");
+    }
+}
+
+/// Runs the IR passes selected for `level`.
+pub fn optimize(module: &mut Module, level: OptLevel) {
+    PassManager::for_level(level).run_ir(module);
+}
@@ -0,0 +1,124 @@
+//! Algebraic simplification: rewrites identities such as `x + 0`, `x * 1`,
+//! `x * 0`, `x - x` and `0 - (0 - x)`.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::ir::{BinOp, Function, Instr, Value};
+
+/// What an instruction simplifies to.
+enum Simplified {
+    Value(Value),
+    Const(i64),
+}
+
+/// Returns whether anything was simplified.
+pub fn run(func: &mut Function) -> bool {
+    let mut changed = false;
+    // Instructions whose uses were already redirected; DCE removes them.
+    let mut forwarded = HashSet::new();
+    loop {
+        let mut known = HashMap::new();
+        let mut defs = HashMap::new();
+        for instr in func.blocks.iter().flat_map(|b| &b.instrs) {
+            match *instr {
+                Instr::Const { dst, value } => {
+                    known.insert(dst, value);
+                }
+                Instr::Binary { dst, op, lhs, rhs } => {
+                    defs.insert(dst, (op, lhs, rhs));
+                }
+                Instr::Cmp { .. }
+                | Instr::Cast { .. }
+                | Instr::Alloca { .. }
+                | Instr::StringAddr { .. }
+                | Instr::GlobalAddr { .. }
+                | Instr::Call { .. }
+                | Instr::Load { .. }
+                | Instr::Store { .. }
+                | Instr::LoadPtr { .. }
+                | Instr::StorePtr { .. }
+                | Instr::SlotAddr { .. }
+                | Instr::Loc { .. }
+                | Instr::InlineAsm { .. } => {}
+            }
+        }
+
+        // Maps each newly forwarded value to its replacement, in order so
+        // that uses are rewritten the same way every time.
+        let mut aliases: BTreeMap<Value, Value> = BTreeMap::new();
+        let resolve = |aliases: &BTreeMap<Value, Value>, mut value| {
+            while let Some(&next) = aliases.get(&value) {
+                value = next;
+            }
+            value
+        };
+        for instr in func.blocks.iter_mut().flat_map(|b| &mut b.instrs) {
+            let Instr::Binary { dst, op, lhs, rhs } = *instr else {
+                continue;
+            };
+            if forwarded.contains(&dst) {
+                continue;
+            }
+            let (lhs, rhs) = (resolve(&aliases, lhs), resolve(&aliases, rhs));
+            match simplify(op, lhs, rhs, &known, &defs) {
+                Some(Simplified::Const(value)) => {
+                    *instr = Instr::Const { dst, value };
+                    known.insert(dst, value);
+                    defs.remove(&dst);
+                    changed = true;
+                }
+                Some(Simplified::Value(value)) => {
+                    aliases.insert(dst, value);
+                }
+                None => {}
+            }
+        }
+
+        if aliases.is_empty() {
+            return changed;
+        }
+        for &dst in aliases.keys() {
+            func.replace_uses(dst, resolve(&aliases, dst));
+            forwarded.insert(dst);
+        }
+        changed = true;
+    }
+}
+
+fn simplify(
+    op: BinOp,
+    lhs: Value,
+    rhs: Value,
+    known: &HashMap<Value, i64>,
+    defs: &HashMap<Value, (BinOp, Value, Value)>,
+) -> Option<Simplified> {
+    let (l, r) = (known.get(&lhs).copied(), known.get(&rhs).copied());
+    let simplified = match (op, l, r) {
+        (BinOp::Add, _, Some(0)) | (BinOp::Sub, _, Some(0)) => Simplified::Value(lhs),
+        (BinOp::Add, Some(0), _) => Simplified::Value(rhs),
+        (BinOp::Mul, _, Some(1)) | (BinOp::SDiv | BinOp::UDiv, _, Some(1)) => {
+            Simplified::Value(lhs)
+        }
+        (BinOp::Mul, Some(1), _) => Simplified::Value(rhs),
+        (BinOp::Mul, _, Some(0)) | (BinOp::Mul, Some(0), _) => Simplified::Const(0),
+        (BinOp::SRem | BinOp::URem, _, Some(1)) => Simplified::Const(0),
+        (BinOp::And, _, Some(0)) | (BinOp::And, Some(0), _) => Simplified::Const(0),
+        (BinOp::And, _, Some(-1)) => Simplified::Value(lhs),
+        (BinOp::And, Some(-1), _) => Simplified::Value(rhs),
+        (BinOp::Or | BinOp::Xor, _, Some(0)) => Simplified::Value(lhs),
+        (BinOp::Or | BinOp::Xor, Some(0), _) => Simplified::Value(rhs),
+        (BinOp::Or, _, Some(-1)) | (BinOp::Or, Some(-1), _) => Simplified::Const(-1),
+        (BinOp::Shl | BinOp::AShr | BinOp::LShr, _, Some(0)) => Simplified::Value(lhs),
+        (BinOp::Sub, Some(0), _) => match defs.get(&rhs) {
+            Some(&(BinOp::Sub, zero, inner)) if known.get(&zero) == Some(&0) => {
+                Simplified::Value(inner)
+            }
+            _ => return None,
+        },
+        (BinOp::Sub, _, _) if lhs == rhs => Simplified::Const(0),
+        (BinOp::And | BinOp::Or, _, _) if lhs == rhs => Simplified::Value(lhs),
+        (BinOp::Xor, _, _) if lhs == rhs => Simplified::Const(0),
+        _ => return None,
+    };
+    Some(simplified)
+}
@@ -0,0 +1,133 @@
+//! Strength reduction: replaces multiplication, division and remainder by
+//! powers of two with shifts and masks.
+
+use std::collections::HashMap;
+
+use crate::ir::{BinOp, Function, Instr, IrType, Value};
+
+/// Returns whether any instruction was rewritten.
+pub fn run(func: &mut Function) -> bool {
+    let constants: HashMap<Value, i64> = func
+        .blocks
+        .iter()
+        .flat_map(|b| &b.instrs)
+        .filter_map(|instr| match *instr {
+            Instr::Const { dst, value } => Some((dst, value)),
+            _ => None,
+        })
+        .collect();
+
+    let mut changed = false;
+    for index in 0..func.blocks.len() {
+        let instrs = std::mem::take(&mut func.blocks[index].instrs);
+        let mut out = Vec::with_capacity(instrs.len());
+        for instr in instrs {
+            if reduce(func, &constants, &instr, &mut out) {
+                changed = true;
+            } else {
+                out.push(instr);
+            }
+        }
+        func.blocks[index].instrs = out;
+    }
+    changed
+}
+
+/// Emits a cheaper replacement for `instr` into `out`, returning `false` if there is none.
+fn reduce(
+    func: &mut Function,
+    constants: &HashMap<Value, i64>,
+    instr: &Instr,
+    out: &mut Vec<Instr>,
+) -> bool {
+    let Instr::Binary { dst, op, lhs, rhs } = *instr else {
+        return false;
+    };
+    let ty = func.value_type(dst);
+    let log2 = |value: Value, signed: bool| {
+        let &constant = constants.get(&value)?;
+        let bits = if signed {
+            u64::try_from(constant).ok()?
+        } else {
+            ty.as_unsigned(constant)
+        };
+        (bits.is_power_of_two() && bits > 1).then(|| i64::from(bits.trailing_zeros()))
+    };
+
+    let (x, k) = match op {
+        BinOp::Mul => match (log2(lhs, false), log2(rhs, false)) {
+            (_, Some(k)) => (lhs, k),
+            (Some(k), None) => (rhs, k),
+            (None, None) => return false,
+        },
+        BinOp::UDiv | BinOp::URem => match log2(rhs, false) {
+            Some(k) => (lhs, k),
+            None => return false,
+        },
+        BinOp::SDiv | BinOp::SRem => match log2(rhs, true) {
+            Some(k) => (lhs, k),
+            None => return false,
+        },
+        _ => return false,
+    };
+
+    let mut emit = Emitter { func, out, ty };
+    match op {
+        BinOp::Mul => emit.shift(dst, BinOp::Shl, x, k),
+        BinOp::UDiv => emit.shift(dst, BinOp::LShr, x, k),
+        BinOp::URem => {
+            let mask = emit.constant((1 << k) - 1);
+            emit.binary(Some(dst), BinOp::And, x, mask);
+        }
+        BinOp::SDiv => {
+            let biased = emit.bias_toward_zero(x, k);
+            emit.shift(dst, BinOp::AShr, biased, k);
+        }
+        BinOp::SRem => {
+            let biased = emit.bias_toward_zero(x, k);
+            let mask = emit.constant(-(1 << k));
+            let rounded = emit.binary(None, BinOp::And, biased, mask);
+            emit.binary(Some(dst), BinOp::Sub, x, rounded);
+        }
+        _ => unreachable!(),
+    }
+    true
+}
+
+struct Emitter<'a> {
+    func: &'a mut Function,
+    out: &'a mut Vec<Instr>,
+    ty: IrType,
+}
+
+impl Emitter<'_> {
+    fn constant(&mut self, value: i64) -> Value {
+        let dst = self.func.new_value(self.ty);
+        self.out.push(Instr::Const {
+            dst,
+            value: self.ty.normalize(value),
+        });
+        dst
+    }
+
+    /// Emits `lhs op rhs`, defining `dst` if given or a fresh value otherwise.
+    fn binary(&mut self, dst: Option<Value>, op: BinOp, lhs: Value, rhs: Value) -> Value {
+        let dst = dst.unwrap_or_else(|| self.func.new_value(self.ty));
+        self.out.push(Instr::Binary { dst, op, lhs, rhs });
+        dst
+    }
+
+    fn shift(&mut self, dst: Value, op: BinOp, x: Value, amount: i64) {
+        let amount = self.constant(amount);
+        self.binary(Some(dst), op, x, amount);
+    }
+
+    /// Adds `2^k - 1` to negative `x` so an arithmetic shift rounds toward zero like `sdiv`.
+    fn bias_toward_zero(&mut self, x: Value, k: i64) -> Value {
+        let sign_shift = self.constant(i64::from(self.ty.bits()) - 1);
+        let sign = self.binary(None, BinOp::AShr, x, sign_shift);
+        let bias_shift = self.constant(i64::from(self.ty.bits()) - k);
+        let bias = self.binary(None, BinOp::LShr, sign, bias_shift);
+        self.binary(None, BinOp::Add, x, bias)
+    }
+}
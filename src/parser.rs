@@ -0,0 +1,1241 @@
+//! Recursive-descent parser producing the AST.
+
+use crate::ast::{
+    AsmOperand, BinaryOp, Builtin, Enum, Enumerator, Expression, Function, Global, IncDecOp,
+    Initializer, Param, Program, Resolved, Statement, Type, UnaryOp,
+};
+use crate::error::{Error, Result};
+use crate::lexer::{self, Keyword, Operator, Position, Span, Token, TokenKind};
+use crate::standard::{Standard, Version};
+
+/// How deeply expressions may nest, counting parentheses, prefix and
+/// postfix operators, subscripts and right operands, so that the stages
+/// walking them recursively can't overflow the stack. A chain grouping to
+/// the left like `a + b + c` counts once however long it is, since every
+/// stage walks those a link at a time.
+pub const MAX_EXPRESSION_DEPTH: u32 = 256;
+
+/// How deeply statements may nest in blocks, loops, labels and the like,
+/// for the same reason.
+pub const MAX_STATEMENT_DEPTH: u32 = 256;
+
+pub struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    current: usize,
+    /// How many parentheses enclose the expression being parsed.
+    paren_depth: u32,
+    /// How many statements enclose the statement being parsed.
+    statement_depth: u32,
+    standard: Standard,
+    /// The syntax errors skipped past so far.
+    errors: Vec<Error>,
+}
+
+impl<'a> Parser<'a> {
+    /// `tokens` must end with `TokenKind::Eof`, as produced by the lexer.
+    pub fn new(tokens: Vec<Token<'a>>) -> Self {
+        Self {
+            tokens,
+            current: 0,
+            paren_depth: 0,
+            statement_depth: 0,
+            standard: Standard::default(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn with_standard(mut self, standard: Standard) -> Self {
+        self.standard = standard;
+        self
+    }
+
+    /// The program, or the first syntax error in it.
+    pub fn parse(self) -> Result<Program> {
+        let (program, errors) = self.parse_recovering();
+        match errors.into_iter().next() {
+            Some(err) => Err(err),
+            None => Ok(program),
+        }
+    }
+
+    /// Parses everything it can, skipping past each syntax error to the
+    /// end of its statement, or of its declaration outside functions, to
+    /// report the errors in the rest too.
+    pub fn parse_recovering(mut self) -> (Program, Vec<Error>) {
+        let mut program = Program {
+            functions: Vec::new(),
+            enums: Vec::new(),
+            globals: Vec::new(),
+        };
+        while self.peek().kind != TokenKind::Eof {
+            if let Err(err) = self.parse_top_level(&mut program) {
+                self.errors.push(err);
+                self.synchronize();
+            }
+        }
+        (program, self.errors)
+    }
+
+    /// A function, an enum or a global variable declaration, added to
+    /// `program`.
+    fn parse_top_level(&mut self, program: &mut Program) -> Result<()> {
+        if self.at_enum_definition() {
+            program.enums.push(self.parse_enum()?);
+        } else if self.at_global() {
+            program.globals.push(self.parse_global()?);
+        } else {
+            program.functions.push(self.parse_function()?);
+        }
+        Ok(())
+    }
+
+    /// Skips to what looks like the start of the next declaration: a type
+    /// following a `}`.
+    fn synchronize(&mut self) {
+        loop {
+            if self.advance().kind == TokenKind::Eof {
+                return;
+            }
+            let after_brace = self.tokens[self.current - 1].kind == TokenKind::CloseBrace;
+            if after_brace
+                && matches!(
+                    self.peek().kind,
+                    TokenKind::Keyword(keyword) if keyword.starts_type()
+                        || keyword == Keyword::ThreadLocal
+                        || keyword == Keyword::Extern
+                )
+            {
+                return;
+            }
+        }
+    }
+
+    /// Skips past the rest of a statement with a syntax error: through its
+    /// `;`, or through the `}` of a block it opened, but not past the `}`
+    /// closing the block it is in.
+    fn skip_statement(&mut self) {
+        let mut depth = 0u32;
+        loop {
+            match self.peek().kind {
+                TokenKind::Eof => return,
+                TokenKind::Semicolon if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                TokenKind::OpenBrace => depth += 1,
+                TokenKind::CloseBrace if depth == 0 => return,
+                TokenKind::CloseBrace if depth == 1 => {
+                    self.advance();
+                    return;
+                }
+                TokenKind::CloseBrace => depth -= 1,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+
+    /// Whether a variable declaration comes next rather than a function:
+    /// the declarator's name isn't followed by `(`.
+    fn at_global(&self) -> bool {
+        let kind = |i: usize| self.tokens.get(i).map(|t| t.kind);
+        let mut i = self.current;
+        loop {
+            match kind(i) {
+                // Skip the tag, an identifier that isn't the name.
+                Some(TokenKind::Keyword(Keyword::Enum)) => i += 2,
+                Some(TokenKind::Identifier(_)) => return kind(i + 1) != Some(TokenKind::OpenParen),
+                Some(
+                    TokenKind::Keyword(_)
+                    | TokenKind::Operator(Operator::Star)
+                    | TokenKind::OpenParen
+                    | TokenKind::CloseParen,
+                ) => i += 1,
+                _ => return false,
+            }
+        }
+    }
+
+    /// `[extern] [_Thread_local] type name [= initializer];` at file
+    /// scope, the storage classes in either order.
+    fn parse_global(&mut self) -> Result<Global> {
+        let mut thread_local = false;
+        let mut is_extern = false;
+        loop {
+            let start = self.peek().pos;
+            if self.eat(&TokenKind::Keyword(Keyword::ThreadLocal)) {
+                self.standard
+                    .require(Version::C11, "`_Thread_local`", start)?;
+                thread_local = true;
+            } else if self.eat(&TokenKind::Keyword(Keyword::Extern)) {
+                is_extern = true;
+            } else {
+                break;
+            }
+        }
+        let ty = self.parse_type()?;
+        let (name, ty, init, pos) = self.parse_declarator(ty)?;
+        Ok(Global {
+            name,
+            ty,
+            // An initializer makes even an `extern` declaration a
+            // definition.
+            is_extern: is_extern && init.is_none(),
+            init,
+            thread_local,
+            pos,
+        })
+    }
+
+    /// A function definition, or a prototype if a `;` follows the
+    /// parameters.
+    fn parse_function(&mut self) -> Result<Function> {
+        // Functions have external linkage anyway.
+        self.eat(&TokenKind::Keyword(Keyword::Extern));
+        let implicit_int = matches!(self.peek().kind, TokenKind::Identifier(_))
+            && self.tokens.get(self.current + 1).map(|t| &t.kind) == Some(&TokenKind::OpenParen);
+        let return_type = if implicit_int {
+            if !self.standard.implicit_int() {
+                return Err(Error::spanning(
+                    self.peek().span(),
+                    "missing return type; implicit `int` was removed in C99",
+                ));
+            }
+            Type::Int
+        } else {
+            // A function's value isn't an object, so `_Atomic` has no
+            // effect on it, but it does on what a returned pointer points
+            // to.
+            let pos = self.peek().pos;
+            match self.parse_qualified_type()? {
+                (Type::Pointer(_), true) => {
+                    return Err(Error::new(pos, "`_Atomic` objects are not supported yet"))
+                }
+                (ty, _) => ty,
+            }
+        };
+        let (name, pos) = self.expect_identifier()?;
+        let prototyped = self.standard.empty_parens_mean_void()
+            || self.tokens.get(self.current + 1).map(|t| &t.kind) != Some(&TokenKind::CloseParen);
+        let (params, variadic) = self.parse_params()?;
+        let body = if self.eat(&TokenKind::Semicolon) {
+            None
+        } else {
+            if let Some(param) = params.iter().find(|param| param.name.is_none()) {
+                return Err(Error::new(param.pos, "parameter name omitted"));
+            }
+            self.expect(TokenKind::OpenBrace)?;
+            Some(self.parse_block_body()?)
+        };
+        Ok(Function {
+            name,
+            return_type,
+            params,
+            variadic,
+            prototyped,
+            body,
+            pos,
+        })
+    }
+
+    /// `()`, `(void)` or a comma-separated list of parameters, up to and
+    /// including the closing parenthesis, with whether the list ends with
+    /// `, ...`. An unnamed parameter is placed at its type.
+    fn parse_params(&mut self) -> Result<(Box<[Param]>, bool)> {
+        self.expect(TokenKind::OpenParen)?;
+        let void = self.peek().kind == TokenKind::Keyword(Keyword::Void)
+            && self.tokens.get(self.current + 1).map(|t| &t.kind) == Some(&TokenKind::CloseParen);
+        if void {
+            self.advance();
+        }
+        if self.eat(&TokenKind::CloseParen) {
+            return Ok((Box::default(), false));
+        }
+        let mut params = Vec::new();
+        loop {
+            let pos = self.peek().pos;
+            let ty = self.parse_type()?;
+            let (name, pos) = if let TokenKind::Identifier(_) = self.peek().kind {
+                let (name, pos) = self.expect_identifier()?;
+                (Some(name), pos)
+            } else {
+                (None, pos)
+            };
+            // A parameter declared as an array is a pointer to its first
+            // element, so the outermost size doesn't matter.
+            let sizes = self.parse_array_sizes()?;
+            let ty = match sizes.split_first() {
+                Some((_, inner)) => Type::pointer_to(array_of(ty, inner, 0)),
+                None => ty,
+            };
+            params.push(Param { name, ty, pos });
+            if !self.eat(&TokenKind::Comma) {
+                self.expect(TokenKind::CloseParen)?;
+                return Ok((params.into(), false));
+            }
+            if self.eat(&TokenKind::Ellipsis) {
+                self.expect(TokenKind::CloseParen)?;
+                return Ok((params.into(), true));
+            }
+        }
+    }
+
+    /// The type of an object, which can't be `_Atomic` or point to
+    /// something `_Atomic` until accesses to such objects are made atomic.
+    fn parse_type(&mut self) -> Result<Type> {
+        let pos = self.peek().pos;
+        match self.parse_qualified_type()? {
+            (_, true) => Err(Error::new(pos, "`_Atomic` objects are not supported yet")),
+            (ty, false) => Ok(ty),
+        }
+    }
+
+    /// A type, possibly qualified `_Atomic` or written `_Atomic(type)`,
+    /// then made a pointer by any `*`s after it, with whether it was
+    /// qualified.
+    fn parse_qualified_type(&mut self) -> Result<(Type, bool)> {
+        let pos = self.peek().pos;
+        if self.eat(&TokenKind::Keyword(Keyword::ThreadLocal)) {
+            self.standard
+                .require(Version::C11, "`_Thread_local`", pos)?;
+            return Err(Error::new(
+                pos,
+                "`_Thread_local` only applies to variables, not functions",
+            ));
+        }
+        let mut atomic = false;
+        while self.eat(&TokenKind::Keyword(Keyword::Atomic)) {
+            self.standard.require(Version::C11, "`_Atomic`", pos)?;
+            atomic = true;
+            if self.eat(&TokenKind::OpenParen) {
+                let ty = self.parse_type_specifier()?;
+                self.expect(TokenKind::CloseParen)?;
+                let ty = atomic_type(ty, pos)?;
+                return Ok((self.parse_pointers(ty), true));
+            }
+        }
+        let ty = self.parse_type_specifier()?;
+        let ty = if atomic { atomic_type(ty, pos)? } else { ty };
+        Ok((self.parse_pointers(ty), atomic))
+    }
+
+    /// `ty` followed by any number of `*`s, each making a pointer to what
+    /// comes before.
+    fn parse_pointers(&mut self, mut ty: Type) -> Type {
+        while self.eat(&TokenKind::Operator(Operator::Star)) {
+            ty = Type::pointer_to(ty);
+        }
+        ty
+    }
+
+    /// The `[N]`s after a declarator's name, outermost first: `int a[2][3]`
+    /// is two arrays of three `int`s. Only the outermost size may be left
+    /// out, as `[]`.
+    fn parse_array_sizes(&mut self) -> Result<Vec<Option<u32>>> {
+        let mut sizes = Vec::new();
+        while self.eat(&TokenKind::OpenBracket) {
+            let token = self.advance();
+            let size = match token.kind {
+                TokenKind::CloseBracket if sizes.is_empty() => {
+                    sizes.push(None);
+                    continue;
+                }
+                TokenKind::IntLit(0, _) => {
+                    return Err(Error::spanning(token.span(), "array size must be positive"));
+                }
+                TokenKind::IntLit(size, _) => u32::try_from(size).map_err(|_| {
+                    Error::spanning(token.span(), format!("array size `{size}` is too large"))
+                })?,
+                other => {
+                    return Err(Error::spanning(
+                        token.span(),
+                        format!("expected an array size, found {other}"),
+                    ));
+                }
+            };
+            self.expect(TokenKind::CloseBracket)?;
+            sizes.push(Some(size));
+        }
+        Ok(sizes)
+    }
+
+    /// `void`, `enum tag`, or integer type keywords in any order, such as
+    /// `unsigned long int` or `short signed`.
+    fn parse_type_specifier(&mut self) -> Result<Type> {
+        let mut signedness = None;
+        let mut size = None;
+        let mut int = false;
+        let mut long_long = false;
+        let mut last: Option<Keyword> = None;
+        while let TokenKind::Keyword(keyword) = self.peek().kind {
+            let conflict = match keyword {
+                Keyword::Void | Keyword::Enum if last.is_none() => {
+                    self.advance();
+                    if keyword == Keyword::Void {
+                        return Ok(Type::Void);
+                    }
+                    self.expect_identifier()?;
+                    return Ok(Type::Int);
+                }
+                Keyword::Void | Keyword::Enum => last,
+                Keyword::Signed | Keyword::Unsigned => signedness.replace(keyword),
+                Keyword::Int if int || size == Some(Keyword::Char) => last,
+                Keyword::Int => {
+                    int = true;
+                    None
+                }
+                // `long long` is the same type as `long`.
+                Keyword::Long if size == Some(Keyword::Long) && !long_long => {
+                    long_long = true;
+                    None
+                }
+                Keyword::Char if int => last,
+                Keyword::Char | Keyword::Short | Keyword::Long => size.replace(keyword),
+                _ => break,
+            };
+            if let Some(previous) = conflict {
+                return Err(Error::spanning(
+                    self.peek().span(),
+                    format!(
+                        "cannot combine `{}` with `{}`",
+                        keyword.as_str(),
+                        previous.as_str()
+                    ),
+                ));
+            }
+            last = Some(keyword);
+            self.advance();
+        }
+        if last.is_none() {
+            let token = self.peek();
+            return Err(Error::spanning(
+                token.span(),
+                format!("expected a type, found {}", token.kind),
+            ));
+        }
+        let unsigned = signedness == Some(Keyword::Unsigned);
+        Ok(match (size, unsigned) {
+            (Some(Keyword::Char), false) => Type::Char,
+            (Some(Keyword::Char), true) => Type::UChar,
+            (Some(Keyword::Short), false) => Type::Short,
+            (Some(Keyword::Short), true) => Type::UShort,
+            (Some(Keyword::Long), false) => Type::Long,
+            (Some(Keyword::Long), true) => Type::ULong,
+            (_, false) => Type::Int,
+            (_, true) => Type::UInt,
+        })
+    }
+
+    fn parse_statement(&mut self) -> Result<Statement> {
+        if self.statement_depth >= MAX_STATEMENT_DEPTH {
+            return Err(Error::new(
+                self.peek().pos,
+                "statement is nested too deeply",
+            ));
+        }
+        self.statement_depth += 1;
+        let statement = self.parse_nested_statement();
+        self.statement_depth -= 1;
+        statement
+    }
+
+    /// A statement, which may hold others.
+    fn parse_nested_statement(&mut self) -> Result<Statement> {
+        match &self.peek().kind {
+            TokenKind::Keyword(Keyword::Asm) => return self.parse_asm(),
+            TokenKind::Identifier("asm") => {
+                return Err(Error::spanning(
+                    self.peek().span(),
+                    "`asm` is a GNU extension; use `__asm__` in ISO C",
+                ));
+            }
+            TokenKind::Keyword(Keyword::Enum) if self.at_enum_definition() => {
+                return Ok(Statement::Enum(self.parse_enum()?));
+            }
+            TokenKind::Keyword(keyword)
+                if keyword.starts_type() || *keyword == Keyword::ThreadLocal =>
+            {
+                return self.parse_declaration()
+            }
+            TokenKind::Keyword(Keyword::Return) => {}
+            TokenKind::Semicolon => {
+                let pos = self.advance().pos;
+                return Ok(Statement::Empty { pos });
+            }
+            TokenKind::OpenBrace => {
+                let pos = self.advance().pos;
+                let body = self.parse_block_body()?;
+                return Ok(Statement::Block { body, pos });
+            }
+            TokenKind::Keyword(Keyword::If) => {
+                let pos = self.advance().pos;
+                let cond = self.parse_condition()?;
+                let then = Box::new(self.parse_statement()?);
+                let otherwise = if self.eat(&TokenKind::Keyword(Keyword::Else)) {
+                    Some(Box::new(self.parse_statement()?))
+                } else {
+                    None
+                };
+                return Ok(Statement::If {
+                    cond,
+                    then,
+                    otherwise,
+                    pos,
+                });
+            }
+            TokenKind::Keyword(Keyword::While) => {
+                let pos = self.advance().pos;
+                let cond = self.parse_condition()?;
+                let body = Box::new(self.parse_statement()?);
+                return Ok(Statement::While { cond, body, pos });
+            }
+            TokenKind::Keyword(Keyword::Do) => {
+                let pos = self.advance().pos;
+                let body = Box::new(self.parse_statement()?);
+                self.expect(TokenKind::Keyword(Keyword::While))?;
+                let cond = self.parse_condition()?;
+                self.expect(TokenKind::Semicolon)?;
+                return Ok(Statement::DoWhile { body, cond, pos });
+            }
+            TokenKind::Keyword(Keyword::For) => return self.parse_for(),
+            TokenKind::Keyword(Keyword::Goto) => {
+                let pos = self.advance().pos;
+                let (label, _) = self.expect_identifier()?;
+                self.expect(TokenKind::Semicolon)?;
+                return Ok(Statement::Goto { label, pos });
+            }
+            TokenKind::Keyword(Keyword::Switch) => {
+                let pos = self.advance().pos;
+                let cond = self.parse_condition()?;
+                let body = Box::new(self.parse_statement()?);
+                return Ok(Statement::Switch { cond, body, pos });
+            }
+            TokenKind::Keyword(Keyword::Case) => {
+                let pos = self.advance().pos;
+                let value = self.parse_expression()?;
+                self.expect(TokenKind::Colon)?;
+                let body = Box::new(self.parse_statement()?);
+                return Ok(Statement::Case { value, body, pos });
+            }
+            TokenKind::Keyword(Keyword::Default) => {
+                let pos = self.advance().pos;
+                self.expect(TokenKind::Colon)?;
+                let body = Box::new(self.parse_statement()?);
+                return Ok(Statement::Default { body, pos });
+            }
+            TokenKind::Keyword(Keyword::Break) => {
+                let pos = self.advance().pos;
+                self.expect(TokenKind::Semicolon)?;
+                return Ok(Statement::Break { pos });
+            }
+            TokenKind::Identifier(_)
+                if self.tokens.get(self.current + 1).map(|t| t.kind) == Some(TokenKind::Colon) =>
+            {
+                let (name, pos) = self.expect_identifier()?;
+                self.advance();
+                let body = Box::new(self.parse_statement()?);
+                return Ok(Statement::Label { name, body, pos });
+            }
+            _ => {
+                let pos = self.peek().pos;
+                let expr = self.parse_expression()?;
+                self.expect(TokenKind::Semicolon)?;
+                return Ok(Statement::Expression { expr, pos });
+            }
+        }
+        let pos = self.expect(TokenKind::Keyword(Keyword::Return))?;
+        let value = if self.peek().kind == TokenKind::Semicolon {
+            None
+        } else {
+            Some(self.parse_expression()?)
+        };
+        self.expect(TokenKind::Semicolon)?;
+        Ok(Statement::Return { value, pos })
+    }
+
+    /// The statements up to the `}` closing a block whose `{` was just
+    /// consumed.
+    fn parse_block_body(&mut self) -> Result<Box<[Statement]>> {
+        let mut body = Vec::new();
+        while !self.eat(&TokenKind::CloseBrace) {
+            match self.parse_statement() {
+                Ok(statement) => body.push(statement),
+                // Without a `}` the rest of the file is the block.
+                Err(err) if self.peek().kind == TokenKind::Eof => return Err(err),
+                Err(err) => {
+                    self.errors.push(err);
+                    self.skip_statement();
+                }
+            }
+        }
+        Ok(body.into())
+    }
+
+    /// `for ([init]; [cond]; [step]) body`
+    fn parse_for(&mut self) -> Result<Statement> {
+        let pos = self.expect(TokenKind::Keyword(Keyword::For))?;
+        self.expect(TokenKind::OpenParen)?;
+        let init = match self.peek().kind {
+            TokenKind::Semicolon => {
+                self.advance();
+                None
+            }
+            TokenKind::Keyword(keyword)
+                if keyword.starts_type() || keyword == Keyword::ThreadLocal =>
+            {
+                // Even GNU C89 has no declarations here.
+                self.standard.require(
+                    Version::C99,
+                    "a declaration in a `for` loop",
+                    self.peek().pos,
+                )?;
+                Some(Box::new(self.parse_declaration()?))
+            }
+            _ => {
+                let pos = self.peek().pos;
+                let expr = self.parse_expression()?;
+                self.expect(TokenKind::Semicolon)?;
+                Some(Box::new(Statement::Expression { expr, pos }))
+            }
+        };
+        let cond = self.parse_optional_expression(TokenKind::Semicolon)?;
+        let step = self.parse_optional_expression(TokenKind::CloseParen)?;
+        let body = Box::new(self.parse_statement()?);
+        Ok(Statement::For {
+            init,
+            cond,
+            step,
+            body,
+            pos,
+        })
+    }
+
+    /// An expression unless `end` comes first, followed by `end`.
+    fn parse_optional_expression(&mut self, end: TokenKind<'_>) -> Result<Option<Expression>> {
+        let expr = if self.peek().kind == end {
+            None
+        } else {
+            Some(self.parse_expression()?)
+        };
+        self.expect(end)?;
+        Ok(expr)
+    }
+
+    /// An `if`'s, loop's or `switch`'s parenthesized condition.
+    fn parse_condition(&mut self) -> Result<Expression> {
+        self.expect(TokenKind::OpenParen)?;
+        let cond = self.parse_expression()?;
+        self.expect(TokenKind::CloseParen)?;
+        Ok(cond)
+    }
+
+    /// `type name [sizes] [= initializer];`
+    fn parse_declaration(&mut self) -> Result<Statement> {
+        let start = self.peek().pos;
+        if self.eat(&TokenKind::Keyword(Keyword::ThreadLocal)) {
+            self.standard
+                .require(Version::C11, "`_Thread_local`", start)?;
+            return Err(Error::new(
+                start,
+                "`_Thread_local` local variables must also be `static`",
+            ));
+        }
+        let ty = self.parse_type()?;
+        let (name, ty, init, pos) = self.parse_declarator(ty)?;
+        Ok(Statement::Declaration {
+            name,
+            ty,
+            init,
+            pos,
+        })
+    }
+
+    /// `name [sizes] [= initializer];` declaring a variable of type `ty`:
+    /// its name, its type with the array sizes applied, its initializer
+    /// and where its name is.
+    fn parse_declarator(
+        &mut self,
+        ty: Type,
+    ) -> Result<(String, Type, Option<Initializer>, Position)> {
+        let (name, pos) = self.expect_identifier()?;
+        let sizes = self.parse_array_sizes()?;
+        let init = if self.eat(&TokenKind::Operator(Operator::Equal)) {
+            Some(self.parse_initializer()?)
+        } else {
+            None
+        };
+        // `int a[] = {1, 2}` takes its size from the initializer.
+        let outer = match (sizes.first(), &init) {
+            (Some(None), Some(Initializer::List { elements, .. })) => elements.len() as u32,
+            (Some(None), _) => {
+                return Err(Error::new(
+                    pos,
+                    format!("array `{name}` needs an explicit size or an initializer list"),
+                ));
+            }
+            _ => 0,
+        };
+        let ty = array_of(ty, &sizes, outer);
+        self.expect(TokenKind::Semicolon)?;
+        Ok((name, ty, init, pos))
+    }
+
+    /// An expression, or a braced list of initializers with an optional
+    /// trailing comma. Braces count towards the nesting limit like
+    /// parentheses.
+    fn parse_initializer(&mut self) -> Result<Initializer> {
+        let pos = self.peek().pos;
+        if !self.eat(&TokenKind::OpenBrace) {
+            return Ok(Initializer::Expr(self.parse_expression()?));
+        }
+        if self.paren_depth >= MAX_EXPRESSION_DEPTH {
+            return Err(Error::new(pos, "initializer is nested too deeply"));
+        }
+        self.paren_depth += 1;
+        let elements = self.parse_initializer_elements();
+        self.paren_depth -= 1;
+        Ok(Initializer::List {
+            elements: elements?.into(),
+            pos,
+        })
+    }
+
+    /// Initializers up to and including the closing brace.
+    fn parse_initializer_elements(&mut self) -> Result<Vec<Initializer>> {
+        let mut elements = Vec::new();
+        loop {
+            elements.push(self.parse_initializer()?);
+            if !self.eat(&TokenKind::Comma) {
+                self.expect(TokenKind::CloseBrace)?;
+                return Ok(elements);
+            }
+            if self.eat(&TokenKind::CloseBrace) {
+                return Ok(elements);
+            }
+        }
+    }
+
+    /// Whether an `enum` with a list of enumerators comes next, rather
+    /// than an `enum` type naming one declared earlier.
+    fn at_enum_definition(&self) -> bool {
+        let kind = |offset: usize| self.tokens.get(self.current + offset).map(|t| t.kind);
+        kind(0) == Some(TokenKind::Keyword(Keyword::Enum))
+            && match kind(1) {
+                Some(TokenKind::OpenBrace) => true,
+                Some(TokenKind::Identifier(_)) => kind(2) == Some(TokenKind::OpenBrace),
+                _ => false,
+            }
+    }
+
+    /// `enum [tag] { name [= value], ... };` with an optional trailing
+    /// comma after the last enumerator.
+    fn parse_enum(&mut self) -> Result<Enum> {
+        let pos = self.expect(TokenKind::Keyword(Keyword::Enum))?;
+        let tag = match self.peek().kind {
+            TokenKind::Identifier(_) => Some(self.expect_identifier()?.0),
+            _ => None,
+        };
+        self.expect(TokenKind::OpenBrace)?;
+        let mut enumerators = Vec::new();
+        loop {
+            let (name, pos) = self.expect_identifier()?;
+            let value = if self.eat(&TokenKind::Operator(Operator::Equal)) {
+                Some(self.parse_expression()?)
+            } else {
+                None
+            };
+            enumerators.push(Enumerator { name, value, pos });
+            if !self.eat(&TokenKind::Comma) {
+                self.expect(TokenKind::CloseBrace)?;
+                break;
+            }
+            if self.eat(&TokenKind::CloseBrace) {
+                break;
+            }
+        }
+        self.expect(TokenKind::Semicolon)?;
+        Ok(Enum {
+            tag,
+            enumerators: enumerators.into(),
+            pos,
+        })
+    }
+
+    /// `asm [volatile] ("template" [: outputs [: inputs [: clobbers]]]);`
+    fn parse_asm(&mut self) -> Result<Statement> {
+        let pos = self.expect(TokenKind::Keyword(Keyword::Asm))?;
+        // Every asm statement is kept, so `volatile` changes nothing.
+        self.eat(&TokenKind::Keyword(Keyword::Volatile));
+        self.expect(TokenKind::OpenParen)?;
+        let template = self.expect_string()?.0;
+        let mut outputs = Vec::new();
+        let mut inputs = Vec::new();
+        let mut clobbers = Vec::new();
+        if self.eat(&TokenKind::Colon) {
+            outputs = self.parse_asm_operands()?;
+            if self.eat(&TokenKind::Colon) {
+                inputs = self.parse_asm_operands()?;
+                if self.eat(&TokenKind::Colon) && self.peek().kind != TokenKind::CloseParen {
+                    clobbers.push(self.expect_string()?.0);
+                    while self.eat(&TokenKind::Comma) {
+                        clobbers.push(self.expect_string()?.0);
+                    }
+                }
+            }
+        }
+        self.expect(TokenKind::CloseParen)?;
+        self.expect(TokenKind::Semicolon)?;
+        Ok(Statement::Asm {
+            template,
+            outputs: outputs.into(),
+            inputs: inputs.into(),
+            clobbers: clobbers.into(),
+            pos,
+        })
+    }
+
+    /// A possibly empty, comma-separated list of `"constraint" (expr)`.
+    fn parse_asm_operands(&mut self) -> Result<Vec<AsmOperand>> {
+        let mut operands = Vec::new();
+        if !matches!(self.peek().kind, TokenKind::StringLit(_)) {
+            return Ok(operands);
+        }
+        loop {
+            let (constraint, pos) = self.expect_string()?;
+            self.expect(TokenKind::OpenParen)?;
+            let expr = self.parse_expression()?;
+            self.expect(TokenKind::CloseParen)?;
+            operands.push(AsmOperand {
+                constraint,
+                expr,
+                pos,
+            });
+            if !self.eat(&TokenKind::Comma) {
+                return Ok(operands);
+            }
+        }
+    }
+
+    pub fn parse_expression(&mut self) -> Result<Expression> {
+        Ok(self.parse_assignment()?.0)
+    }
+
+    /// An assignment, plain or compound, which binds loosest of all and
+    /// groups to the right, or else a binary expression.
+    fn parse_assignment(&mut self) -> Result<(Expression, u32)> {
+        let start = self.peek().pos;
+        let (target, height) = self.parse_binary(0)?;
+        let op = match self.peek().kind {
+            TokenKind::Operator(Operator::Equal) => None,
+            TokenKind::Operator(op) => match BinaryOp::from_compound_assignment(op) {
+                Some(op) => Some(op),
+                None => return Ok((target, height)),
+            },
+            _ => return Ok((target, height)),
+        };
+        let target_span = Span {
+            start,
+            end: self.tokens[self.current - 1].span().end,
+        };
+        let pos = self.advance().pos;
+        if self.paren_depth >= MAX_EXPRESSION_DEPTH {
+            return Err(Error::new(pos, "expression is nested too deeply"));
+        }
+        self.paren_depth += 1;
+        let value = self.parse_assignment();
+        self.paren_depth -= 1;
+        let (value, value_height) = value?;
+        let assign = Expression::Assign {
+            op,
+            operands: Box::new([target, value]),
+            pos,
+            target: target_span,
+        };
+        Ok((assign, height.max(value_height) + 1))
+    }
+
+    /// Precedence climbing: parses operators binding at least as tightly as `min_prec`.
+    /// Returns the expression with how deeply it nests, which only its
+    /// right operands add to.
+    fn parse_binary(&mut self, min_prec: u8) -> Result<(Expression, u32)> {
+        let (mut lhs, mut height) = self.parse_unary()?;
+        while let TokenKind::Operator(op) = self.peek().kind {
+            let (Some(binary), Some(prec)) = (BinaryOp::from_operator(op), op.precedence()) else {
+                break;
+            };
+            if prec < min_prec {
+                break;
+            }
+            let pos = self.advance().pos;
+            let (rhs, rhs_height) = self.parse_binary(prec + 1)?;
+            height = height.max(rhs_height + 1);
+            if height + self.paren_depth > MAX_EXPRESSION_DEPTH {
+                return Err(Error::new(pos, "expression is nested too deeply"));
+            }
+            lhs = Expression::Binary {
+                op: binary,
+                operands: Box::new([lhs, rhs]),
+                ty: Resolved::default(),
+                pos,
+            };
+        }
+        Ok((lhs, height))
+    }
+
+    /// A primary expression after any prefix `-`, `!`, `~`, `&`, `*`, `++`,
+    /// `--` and `sizeof`, which bind tighter than every binary operator. Each one
+    /// counts towards the nesting limit like a parenthesis.
+    fn parse_unary(&mut self) -> Result<(Expression, u32)> {
+        if self.peek().kind == TokenKind::Keyword(Keyword::Sizeof) {
+            return self.parse_sizeof();
+        }
+        let TokenKind::Operator(op) = self.peek().kind else {
+            return self.parse_postfix();
+        };
+        // `&` and `*` are binary operators too, so they aren't `UnaryOp`s,
+        // and neither are `++` and `--`, which assign.
+        let unary = match op {
+            Operator::Amp | Operator::Star | Operator::PlusPlus | Operator::MinusMinus => None,
+            _ => match UnaryOp::from_operator(op) {
+                Some(unary) => Some(unary),
+                None => return self.parse_postfix(),
+            },
+        };
+        let pos = self.advance().pos;
+        if self.paren_depth >= MAX_EXPRESSION_DEPTH {
+            return Err(Error::new(pos, "expression is nested too deeply"));
+        }
+        self.paren_depth += 1;
+        let operand = self.parse_unary();
+        self.paren_depth -= 1;
+        let (operand, height) = operand?;
+        let operand = Box::new(operand);
+        let expr = match unary {
+            Some(op) => Expression::Unary {
+                op,
+                operand,
+                ty: Resolved::default(),
+                pos,
+            },
+            None if op == Operator::Amp => Expression::AddressOf { operand, pos },
+            None if op == Operator::Star => Expression::Deref { operand, pos },
+            None => {
+                let op = if op == Operator::PlusPlus {
+                    IncDecOp::PreInc
+                } else {
+                    IncDecOp::PreDec
+                };
+                Expression::IncDec { op, operand, pos }
+            }
+        };
+        Ok((expr, height + 1))
+    }
+
+    /// `sizeof(type)`, or `sizeof` applied to a unary expression, which
+    /// counts towards the nesting limit like a parenthesis.
+    fn parse_sizeof(&mut self) -> Result<(Expression, u32)> {
+        let pos = self.expect(TokenKind::Keyword(Keyword::Sizeof))?;
+        let type_follows = matches!(
+            self.tokens.get(self.current + 1).map(|t| t.kind),
+            Some(TokenKind::Keyword(keyword)) if keyword.starts_type()
+        );
+        if self.peek().kind == TokenKind::OpenParen && type_follows {
+            self.advance();
+            // Sizes don't access anything, so they may be `_Atomic`.
+            let (ty, _) = self.parse_qualified_type()?;
+            let sizes = self.parse_array_sizes()?;
+            if sizes.first() == Some(&None) {
+                return Err(Error::new(pos, "`sizeof` needs an explicit array size"));
+            }
+            self.expect(TokenKind::CloseParen)?;
+            let ty = array_of(ty, &sizes, 0);
+            return Ok((Expression::SizeofType { ty, pos }, 0));
+        }
+        if self.paren_depth >= MAX_EXPRESSION_DEPTH {
+            return Err(Error::new(pos, "expression is nested too deeply"));
+        }
+        self.paren_depth += 1;
+        let operand = self.parse_unary();
+        self.paren_depth -= 1;
+        let (operand, height) = operand?;
+        let operand = Box::new(operand);
+        Ok((Expression::SizeofExpr { operand, pos }, height + 1))
+    }
+
+    /// A primary expression followed by any subscripts and postfix `++`
+    /// and `--`.
+    fn parse_postfix(&mut self) -> Result<(Expression, u32)> {
+        let (mut expr, mut height) = self.parse_primary()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::OpenBracket => None,
+                TokenKind::Operator(Operator::PlusPlus) => Some(IncDecOp::PostInc),
+                TokenKind::Operator(Operator::MinusMinus) => Some(IncDecOp::PostDec),
+                _ => break,
+            };
+            let pos = self.advance().pos;
+            let Some(op) = op else {
+                (expr, height) = self.parse_subscript(expr, height, pos)?;
+                continue;
+            };
+            height += 1;
+            if height + self.paren_depth > MAX_EXPRESSION_DEPTH {
+                return Err(Error::new(pos, "expression is nested too deeply"));
+            }
+            expr = Expression::IncDec {
+                op,
+                operand: Box::new(expr),
+                pos,
+            };
+        }
+        Ok((expr, height))
+    }
+
+    /// The index of `array[index]` after the `[` at `pos`, and the `]`.
+    /// `a[i]` is `*(a + i)`, and its brackets count towards the nesting
+    /// limit like parentheses.
+    fn parse_subscript(
+        &mut self,
+        array: Expression,
+        height: u32,
+        pos: Position,
+    ) -> Result<(Expression, u32)> {
+        if self.paren_depth >= MAX_EXPRESSION_DEPTH {
+            return Err(Error::new(pos, "expression is nested too deeply"));
+        }
+        self.paren_depth += 1;
+        let index = self.parse_assignment();
+        self.paren_depth -= 1;
+        let (index, index_height) = index?;
+        self.expect(TokenKind::CloseBracket)?;
+        let height = height.max(index_height) + 2;
+        if height + self.paren_depth > MAX_EXPRESSION_DEPTH {
+            return Err(Error::new(pos, "expression is nested too deeply"));
+        }
+        let address = Expression::Binary {
+            op: BinaryOp::Add,
+            operands: Box::new([array, index]),
+            ty: Resolved::default(),
+            pos,
+        };
+        let element = Expression::Deref {
+            operand: Box::new(address),
+            pos,
+        };
+        Ok((element, height))
+    }
+
+    /// Each arm that doesn't recurse is a function of its own, so that
+    /// its locals don't add to the frame of every level of nesting.
+    fn parse_primary(&mut self) -> Result<(Expression, u32)> {
+        let token = self.advance();
+        match token.kind {
+            TokenKind::IntLit(value, suffix) => {
+                let pos = token.pos;
+                Ok((Expression::IntLit { value, suffix, pos }, 0))
+            }
+            TokenKind::CharLit(raw) => {
+                let value = lexer::char_value(raw);
+                Ok((
+                    Expression::CharLit {
+                        value,
+                        pos: token.pos,
+                    },
+                    0,
+                ))
+            }
+            TokenKind::StringLit(raw) => Ok((self.parse_string(raw, token.pos), 0)),
+            TokenKind::Identifier(name) if Builtin::lookup(name).is_some() => {
+                let builtin = Builtin::lookup(name).expect("checked by the guard");
+                self.parse_builtin_call(builtin, token.pos)
+            }
+            TokenKind::Identifier(name) => self.parse_identifier(name, token.pos),
+            TokenKind::OpenParen => {
+                if self.paren_depth >= MAX_EXPRESSION_DEPTH {
+                    return Err(Error::spanning(
+                        token.span(),
+                        "expression is nested too deeply",
+                    ));
+                }
+                self.paren_depth += 1;
+                let expr = self.parse_assignment();
+                self.paren_depth -= 1;
+                let expr = expr?;
+                self.expect(TokenKind::CloseParen)?;
+                Ok(expr)
+            }
+            _ => Err(self.unexpected_expression(&token)),
+        }
+    }
+
+    /// A string literal and any adjacent ones, joined: `"ab" "c"` is
+    /// `"abc"`.
+    fn parse_string(&mut self, raw: &str, pos: Position) -> Expression {
+        let mut value = lexer::string_bytes(raw);
+        while let TokenKind::StringLit(raw) = self.peek().kind {
+            self.advance();
+            value.extend(lexer::string_bytes(raw));
+        }
+        Expression::StringLit { value, pos }
+    }
+
+    /// A variable, or a call if a `(` follows.
+    fn parse_identifier(&mut self, name: &str, pos: Position) -> Result<(Expression, u32)> {
+        if !self.eat(&TokenKind::OpenParen) {
+            let variable = Expression::Variable {
+                name: name.to_string(),
+                pos,
+            };
+            return Ok((variable, 0));
+        }
+        let (args, height) = self.parse_call_arguments(pos)?;
+        let call = Expression::FunctionCall {
+            name: name.to_string(),
+            args: args.into(),
+            pos,
+        };
+        Ok((call, height))
+    }
+
+    fn unexpected_expression(&mut self, token: &Token<'a>) -> Error {
+        self.put_back(token);
+        Error::spanning(
+            token.span(),
+            format!("expected an expression, found {}", token.kind),
+        )
+    }
+
+    fn parse_builtin_call(&mut self, builtin: Builtin, pos: Position) -> Result<(Expression, u32)> {
+        self.expect(TokenKind::OpenParen)?;
+        let (args, height) = self.parse_call_arguments(pos)?;
+        let call = Expression::BuiltinCall {
+            builtin,
+            args: args.into(),
+            pos,
+        };
+        Ok((call, height))
+    }
+
+    /// The comma-separated arguments of a call after its opening
+    /// parenthesis, which count towards the nesting limit like parentheses.
+    fn parse_call_arguments(&mut self, pos: Position) -> Result<(Vec<Expression>, u32)> {
+        if self.paren_depth >= MAX_EXPRESSION_DEPTH {
+            return Err(Error::new(pos, "expression is nested too deeply"));
+        }
+        self.paren_depth += 1;
+        let args = self.parse_arguments();
+        self.paren_depth -= 1;
+        args
+    }
+
+    /// Arguments up to and including the closing parenthesis, with the
+    /// height of the tallest. Each may be an assignment but not a comma
+    /// expression.
+    fn parse_arguments(&mut self) -> Result<(Vec<Expression>, u32)> {
+        let mut args = Vec::new();
+        let mut height = 0;
+        if self.eat(&TokenKind::CloseParen) {
+            return Ok((args, height));
+        }
+        loop {
+            let (arg, arg_height) = self.parse_assignment()?;
+            args.push(arg);
+            height = height.max(arg_height);
+            if !self.eat(&TokenKind::Comma) {
+                self.expect(TokenKind::CloseParen)?;
+                return Ok((args, height));
+            }
+        }
+    }
+
+    fn peek(&self) -> &Token<'a> {
+        &self.tokens[self.current]
+    }
+
+    fn advance(&mut self) -> Token<'a> {
+        let token = self.tokens[self.current];
+        if token.kind != TokenKind::Eof {
+            self.current += 1;
+        }
+        token
+    }
+
+    /// Undoes the [`advance`](Self::advance) that returned `token`, which
+    /// was unexpected, so that error recovery skips it with the rest of
+    /// the statement.
+    fn put_back(&mut self, token: &Token<'a>) {
+        if token.kind != TokenKind::Eof {
+            self.current -= 1;
+        }
+    }
+
+    /// Consumes the next token if it matches `kind`.
+    fn eat(&mut self, kind: &TokenKind<'_>) -> bool {
+        if &self.peek().kind == kind {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consumes the next token, which must match `kind`. A token that
+    /// doesn't is left for error recovery to skip.
+    fn expect(&mut self, kind: TokenKind<'_>) -> Result<Position> {
+        let token = *self.peek();
+        if token.kind == kind {
+            self.advance();
+            Ok(token.pos)
+        } else {
+            Err(Error::spanning(
+                token.span(),
+                format!("expected {kind}, found {}", token.kind),
+            ))
+        }
+    }
+
+    fn expect_string(&mut self) -> Result<(String, Position)> {
+        let token = self.advance();
+        match token.kind {
+            TokenKind::StringLit(raw) => Ok((lexer::unescape(raw), token.pos)),
+            other => {
+                self.put_back(&token);
+                Err(Error::spanning(
+                    token.span(),
+                    format!("expected a string literal, found {other}"),
+                ))
+            }
+        }
+    }
+
+    fn expect_identifier(&mut self) -> Result<(String, Position)> {
+        let token = self.advance();
+        match token.kind {
+            TokenKind::Identifier(name) => Ok((name.to_string(), token.pos)),
+            other => {
+                self.put_back(&token);
+                Err(Error::spanning(
+                    token.span(),
+                    format!("expected an identifier, found {other}"),
+                ))
+            }
+        }
+    }
+}
+
+/// `ty` in arrays of `sizes`, outermost first, taking `outer` for an
+/// outermost size left out.
+fn array_of(ty: Type, sizes: &[Option<u32>], outer: u32) -> Type {
+    sizes.iter().rev().fold(ty, |ty, size| {
+        Type::Array(Box::new(ty), size.unwrap_or(outer))
+    })
+}
+
+/// `ty` qualified `_Atomic`, which `void` can't be.
+fn atomic_type(ty: Type, pos: Position) -> Result<Type> {
+    if ty == Type::Void {
+        Err(Error::new(pos, "`_Atomic` cannot qualify `void`"))
+    } else {
+        Ok(ty)
+    }
+}
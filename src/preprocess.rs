@@ -0,0 +1,1244 @@
+//! The preprocessor, which runs before the lexer: splices the files named
+//! by `#include` directives into the source, expands macros and leaves out
+//! what conditional compilation skips, remembering where each line came
+//! from so diagnostics can point into the right file.
+//!
+//! Each line becomes one line of the output, so that positions only move
+//! where a file is included: directives and skipped lines are left blank,
+//! and a macro call whose arguments run onto later lines is expanded on
+//! its first line, leaving the others blank.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Severity};
+use crate::json::Value;
+use crate::lexer::{Position, Span};
+
+/// How many files can be open at once, counting the main one, as in gcc.
+/// Include guards are only seen once a file is read, so an include cycle
+/// is only an error when nothing stops it before this.
+pub const MAX_INCLUDE_DEPTH: usize = 200;
+
+/// An error in one of the files read while preprocessing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileError {
+    /// The file's name as diagnostics show it.
+    pub file: String,
+    /// The line the error is on, as the compiler saw it, to show under the
+    /// message.
+    pub line: Option<String>,
+    pub error: Error,
+    /// The error's note, in the file it is in.
+    pub note: Option<Box<FileError>>,
+}
+
+impl FileError {
+    /// Formats the error the way the command-line driver reports it.
+    pub fn render_colored(&self, color: bool) -> String {
+        self.render_as(Severity::Error, color)
+    }
+
+    /// Like [`render_colored`](Self::render_colored), labeled with
+    /// `severity`.
+    pub fn render_as(&self, severity: Severity, color: bool) -> String {
+        let mut out = self
+            .error
+            .render_line_as(severity, &self.file, self.line.as_deref(), color);
+        if let Some(note) = &self.note {
+            out.push('\n');
+            out.push_str(&note.render_as(Severity::Note, color));
+        }
+        out
+    }
+
+    /// The error as a JSON object, labeled with `severity` and `code`, the
+    /// name of the warning it is, if any: `{"severity", "code", "message",
+    /// "file", "line", "column", "span": {"start", "end"}, "notes"}`. The
+    /// location members are `null` for an error about no place in
+    /// particular.
+    pub fn to_json(&self, severity: Severity, code: Option<&str>) -> Value {
+        let pos = self.error.pos;
+        let span = pos.map(|start| {
+            Value::object([
+                ("start", start.into()),
+                ("end", self.error.underline_end(self.line.as_deref()).into()),
+            ])
+        });
+        let notes = self
+            .note
+            .iter()
+            .map(|note| note.to_json(Severity::Note, None));
+        Value::object([
+            ("severity", severity.name().into()),
+            ("code", code.into()),
+            ("message", self.error.message.as_str().into()),
+            ("file", self.file.as_str().into()),
+            ("line", pos.map(|pos| pos.line).into()),
+            ("column", pos.map(|pos| pos.column).into()),
+            ("span", span.into()),
+            ("notes", notes.collect()),
+        ])
+    }
+}
+
+/// A translation unit with its includes spliced in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Preprocessed {
+    pub source: String,
+    /// The name of every file read, the main file first.
+    files: Vec<String>,
+    /// For each line of `source`, the index of the file it came from and
+    /// its line there.
+    lines: Vec<(usize, u32)>,
+}
+
+impl Preprocessed {
+    /// The name of every file read, the main file first. A header included
+    /// more than once is named each time.
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    /// Where `pos` in the spliced source is in the file it came from.
+    pub fn origin(&self, pos: Position) -> (&str, Position) {
+        let index = pos.line as usize - 1;
+        // The end of the input is past its last line.
+        let (file, line) = match self.lines.get(index) {
+            Some(&origin) => origin,
+            None => match self.lines.last() {
+                Some(&(file, line)) => (file, line + (index + 1 - self.lines.len()) as u32),
+                None => (0, pos.line),
+            },
+        };
+        (&self.files[file], Position { line, ..pos })
+    }
+
+    /// `error`, found in the spliced source, moved to the file it is in.
+    pub fn locate(&self, mut error: Error) -> FileError {
+        let note = error.note.take().map(|note| Box::new(self.locate(*note)));
+        match error.pos {
+            Some(pos) => {
+                let (file, start) = self.origin(pos);
+                let line = self.source.lines().nth(pos.line as usize - 1);
+                FileError {
+                    file: file.to_string(),
+                    line: line.map(str::to_string),
+                    error: Error {
+                        pos: Some(start),
+                        end: error.end.map(|end| self.origin(end).1),
+                        ..error
+                    },
+                    note,
+                }
+            }
+            None => FileError {
+                file: self.files[0].clone(),
+                line: None,
+                error,
+                note,
+            },
+        }
+    }
+}
+
+/// A macro, from `#define` or `-D`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Macro {
+    /// The parameters of a function-like macro, the last being
+    /// `__VA_ARGS__` if it is variadic.
+    params: Option<Vec<String>>,
+    variadic: bool,
+    body: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Preprocessor {
+    /// Where to look for included files after the including file's
+    /// directory (`-I`).
+    include_dirs: Vec<PathBuf>,
+    /// The macros defined before the first line (`-D`).
+    macros: HashMap<String, Macro>,
+}
+
+impl Preprocessor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_include_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.include_dirs = dirs;
+        self
+    }
+
+    /// Defines a macro the way `-D` does: `NAME` as 1, or `NAME=body`,
+    /// where the name may have parameters.
+    pub fn define(&mut self, definition: &str) -> Result<(), String> {
+        let definition = match definition.split_once('=') {
+            Some((head, body)) => format!("{head} {body}"),
+            None => format!("{definition} 1"),
+        };
+        let (name, mac) = parse_define(&definition)?;
+        self.macros.insert(name, mac);
+        Ok(())
+    }
+
+    /// Removes a macro the way `-U` does.
+    pub fn undefine(&mut self, name: &str) {
+        self.macros.remove(name);
+    }
+
+    /// Preprocesses `source`, read from `path` and called `name` in
+    /// diagnostics. Quoted includes are looked up next to `path` first.
+    pub fn run(&self, path: &Path, name: &str, source: &str) -> Result<Preprocessed, FileError> {
+        let mut run = Run {
+            preprocessor: self,
+            macros: self.macros.clone(),
+            once: HashSet::new(),
+            open: vec![(canonical(path), name.to_string())],
+            out: Preprocessed {
+                source: String::with_capacity(source.len()),
+                files: vec![name.to_string()],
+                lines: Vec::new(),
+            },
+        };
+        run.splice(0, path, source)?;
+        Ok(run.out)
+    }
+
+    /// The file `#include` names: next to `from` for a quoted name, and
+    /// otherwise in the include directories.
+    fn find(&self, from: &Path, name: &str, quoted: bool) -> Option<PathBuf> {
+        let beside = quoted.then(|| from.parent().unwrap_or(Path::new("")).join(name));
+        beside
+            .into_iter()
+            .chain(self.include_dirs.iter().map(|dir| dir.join(name)))
+            .find(|path| path.is_file())
+    }
+}
+
+/// An `#if`, `#ifdef` or `#ifndef` whose `#endif` is still to come.
+struct Conditional {
+    /// The directive that opened it, in case it is never closed.
+    directive: &'static str,
+    pos: Position,
+    /// Whether the lines of the current branch are kept.
+    active: bool,
+    /// Whether a branch has been kept, or none will be because the whole
+    /// conditional is skipped.
+    done: bool,
+    seen_else: bool,
+}
+
+/// A directive line: what follows the `#`, with comments blanked out and
+/// continuation lines joined.
+struct Directive<'a> {
+    name: &'a str,
+    rest: &'a str,
+    /// Where the `#` is.
+    pos: Position,
+    /// Where `rest` starts, past any space.
+    rest_pos: Position,
+    /// Where the directive ends, before any trailing space.
+    end: Position,
+}
+
+impl<'a> Directive<'a> {
+    fn parse(text: &'a str, line: u32) -> Self {
+        let column = |slice: &str| (text.len() - slice.len()) as u32 + 1;
+        let after_hash = text.trim_start()[1..].trim_start();
+        let name_len = after_hash
+            .find(|c: char| !is_identifier_char(c))
+            .unwrap_or(after_hash.len());
+        let (name, rest) = after_hash.split_at(name_len);
+        Directive {
+            name,
+            rest,
+            pos: Position {
+                line,
+                column: column(text.trim_start()),
+            },
+            rest_pos: Position {
+                line,
+                column: column(rest.trim_start()),
+            },
+            end: Position {
+                line,
+                column: text.trim_end().len() as u32 + 1,
+            },
+        }
+    }
+
+    /// The whole directive, from the `#`.
+    fn span(&self) -> Span {
+        Span {
+            start: self.pos,
+            end: self.end,
+        }
+    }
+
+    /// An error about `rest`.
+    fn error(&self, message: impl Into<String>) -> Error {
+        let span = Span {
+            start: self.rest_pos,
+            end: self.end,
+        };
+        Error::spanning(span, message)
+    }
+
+    /// Checks that nothing follows the directive's name.
+    fn expect_end(&self) -> Result<(), Error> {
+        match self.rest.trim() {
+            "" => Ok(()),
+            _ => Err(self.error(format!("extra tokens after `#{}`", self.name))),
+        }
+    }
+}
+
+/// The state of one preprocessing run.
+struct Run<'p> {
+    preprocessor: &'p Preprocessor,
+    macros: HashMap<String, Macro>,
+    /// The files marked `#pragma once`.
+    once: HashSet<PathBuf>,
+    /// The files being spliced, innermost last, to catch a cycle that
+    /// goes on for [`MAX_INCLUDE_DEPTH`] files.
+    open: Vec<(PathBuf, String)>,
+    out: Preprocessed,
+}
+
+impl Run<'_> {
+    /// Appends `source`, file number `file` read from `path`, to the
+    /// output.
+    fn splice(&mut self, file: usize, path: &Path, source: &str) -> Result<(), FileError> {
+        let current = self.out.files[file].clone();
+        let lines: Vec<&str> = source.split_inclusive('\n').collect();
+        let fail = |error: Error| FileError {
+            file: current.clone(),
+            line: error
+                .pos
+                .and_then(|pos| lines.get(pos.line as usize - 1))
+                .map(|line| line_text(line).to_string()),
+            error,
+            note: None,
+        };
+        let mut conditionals: Vec<Conditional> = Vec::new();
+        let mut in_comment = false;
+        let mut index = 0;
+        while index < lines.len() {
+            let number = index as u32 + 1;
+            let starts_in_comment = in_comment;
+            let blanked = blank_comments(lines[index], &mut in_comment);
+            if starts_in_comment || !blanked.trim_start().starts_with('#') {
+                let skipping = conditionals.last().is_some_and(|c| !c.active);
+                index = if skipping {
+                    self.blank(file, number);
+                    index + 1
+                } else {
+                    let line = (index, &*blanked, starts_in_comment);
+                    self.text(file, &lines, line, &mut in_comment)
+                        .map_err(fail)?
+                };
+                continue;
+            }
+            // A directive goes on past a line ending in a backslash.
+            let mut text = line_text(&blanked).to_string();
+            let mut end = index + 1;
+            while text.ends_with('\\') && end < lines.len() {
+                text.pop();
+                text.push_str(line_text(&blank_comments(lines[end], &mut in_comment)));
+                end += 1;
+            }
+            let directive = Directive::parse(&text, number);
+            let skipping = conditionals.last().is_some_and(|c| !c.active);
+            if directive.name == "include" && !skipping {
+                // The included file takes the directive's place.
+                self.include(path, &directive).map_err(|err| match err {
+                    Included::Here(error) => fail(error),
+                    Included::Inside(error) => error,
+                })?;
+                for line in index + 1..end {
+                    self.blank(file, line as u32 + 1);
+                }
+            } else {
+                for line in index..end {
+                    self.blank(file, line as u32 + 1);
+                }
+                self.directive(&directive, &mut conditionals)
+                    .map_err(fail)?;
+            }
+            index = end;
+        }
+        if let Some(conditional) = conditionals.pop() {
+            let message = format!("unterminated `{}`", conditional.directive);
+            return Err(fail(Error::new(conditional.pos, message)));
+        }
+        Ok(())
+    }
+
+    /// Adds an empty line in place of line `number` of file `file`.
+    fn blank(&mut self, file: usize, number: u32) {
+        self.out.source.push('\n');
+        self.out.lines.push((file, number));
+    }
+
+    /// Expands the macros in the line at `index` of `lines`, given along
+    /// with the line with its comments blanked out and whether it starts in
+    /// one, and returns the index of the line after it. A macro call's
+    /// arguments may take more lines.
+    fn text(
+        &mut self,
+        file: usize,
+        lines: &[&str],
+        (index, blanked, starts_in_comment): (usize, &str, bool),
+        in_comment: &mut bool,
+    ) -> Result<usize, Error> {
+        let number = index as u32 + 1;
+        let mut text = line_text(lines[index]).to_string();
+        let mut end = index + 1;
+        let expanded = loop {
+            let error = match expand_line(&self.macros, &text, starts_in_comment) {
+                Ok(expanded) => break expanded,
+                Err(error) => error,
+            };
+            let pos = |offset| Position {
+                line: number,
+                column: offset as u32 + 1,
+            };
+            match error {
+                Expansion::Unfinished { .. } if end < lines.len() => {
+                    // Comments are blanked out so that a `//` can't swallow
+                    // the lines joined after it, except for the end of one
+                    // begun on an earlier line.
+                    if end == index + 1 {
+                        let start = comment_end(&text, starts_in_comment);
+                        text = format!("{}{}", &text[..start], line_text(&blanked[start..]));
+                    }
+                    text.push(' ');
+                    text.push_str(line_text(&blank_comments(lines[end], in_comment)));
+                    end += 1;
+                }
+                Expansion::Unfinished { offset, name } => {
+                    let message = format!("unterminated call to macro `{name}`");
+                    return Err(Error::new(pos(offset), message));
+                }
+                Expansion::Failed { offset, message } => {
+                    return Err(Error::new(pos(offset), message));
+                }
+            }
+        };
+        self.out.source.push_str(&expanded);
+        self.out.lines.push((file, number));
+        for line in index + 1..end {
+            self.out.source.push('\n');
+            self.out.lines.push((file, line as u32 + 1));
+        }
+        // The main file's last line ends as it did.
+        if lines[end - 1].ends_with('\n') || file != 0 {
+            self.out.source.push('\n');
+        }
+        Ok(end)
+    }
+
+    /// Splices in the file named by an `#include`.
+    fn include(&mut self, from: &Path, directive: &Directive) -> Result<(), Included> {
+        let Include { name, quoted } = parse_include(directive.rest)
+            .map_err(|message| Included::Here(directive.error(message)))?;
+        let found = self.preprocessor.find(from, name, quoted).ok_or_else(|| {
+            Included::Here(directive.error(format!("cannot find include file `{name}`")))
+        })?;
+        let display = found.display().to_string();
+        let text = fs::read_to_string(&found).map_err(|err| {
+            Included::Here(directive.error(format!("cannot read `{display}`: {err}")))
+        })?;
+        let canonical = canonical(&found);
+        if self.once.contains(&canonical) {
+            return Ok(());
+        }
+        if self.open.len() >= MAX_INCLUDE_DEPTH {
+            let message = match self.open.iter().rposition(|(path, _)| *path == canonical) {
+                Some(start) => {
+                    let cycle: Vec<&str> = self.open[start..]
+                        .iter()
+                        .map(|(_, name)| name.as_str())
+                        .chain([display.as_str()])
+                        .collect();
+                    format!("`#include` cycle: {}", cycle.join(" -> "))
+                }
+                None => format!("`#include` nested more than {MAX_INCLUDE_DEPTH} deep"),
+            };
+            return Err(Included::Here(directive.error(message)));
+        }
+        self.out.files.push(display.clone());
+        self.open.push((canonical, display));
+        let file = self.out.files.len() - 1;
+        self.splice(file, &found, &text).map_err(Included::Inside)?;
+        self.open.pop();
+        Ok(())
+    }
+
+    /// Carries out a directive other than `#include`, which only the
+    /// conditional ones do in a skipped branch.
+    fn directive(
+        &mut self,
+        directive: &Directive,
+        conditionals: &mut Vec<Conditional>,
+    ) -> Result<(), Error> {
+        let skipping = conditionals.last().is_some_and(|c| !c.active);
+        match directive.name {
+            "if" | "ifdef" | "ifndef" => {
+                let active = !skipping
+                    && match directive.name {
+                        "if" => self.condition(directive)?,
+                        name => {
+                            let defined = self.macros.contains_key(macro_name(directive)?);
+                            defined == (name == "ifdef")
+                        }
+                    };
+                conditionals.push(Conditional {
+                    directive: match directive.name {
+                        "if" => "#if",
+                        "ifdef" => "#ifdef",
+                        _ => "#ifndef",
+                    },
+                    pos: directive.pos,
+                    active,
+                    done: active || skipping,
+                    seen_else: false,
+                });
+            }
+            "elif" | "else" => {
+                let name = directive.name;
+                let Some(conditional) = conditionals.last_mut() else {
+                    return Err(Error::spanning(
+                        directive.span(),
+                        format!("`#{name}` without `#if`"),
+                    ));
+                };
+                if conditional.seen_else {
+                    return Err(Error::spanning(
+                        directive.span(),
+                        format!("`#{name}` after `#else`"),
+                    ));
+                }
+                conditional.active = if name == "else" {
+                    directive.expect_end()?;
+                    conditional.seen_else = true;
+                    !conditional.done
+                } else {
+                    !conditional.done && self.condition(directive)?
+                };
+                conditional.done |= conditional.active;
+            }
+            "endif" => {
+                directive.expect_end()?;
+                if conditionals.pop().is_none() {
+                    return Err(Error::spanning(directive.span(), "`#endif` without `#if`"));
+                }
+            }
+            _ if skipping => {}
+            "define" => {
+                let (name, mac) =
+                    parse_define(directive.rest).map_err(|message| directive.error(message))?;
+                self.macros.insert(name, mac);
+            }
+            "undef" => {
+                let name = macro_name(directive)?;
+                self.macros.remove(name);
+            }
+            "error" => {
+                let message = format!("#error {}", directive.rest.trim());
+                return Err(Error::spanning(directive.span(), message.trim_end()));
+            }
+            "pragma" => {
+                // Other pragmas are for other compilers.
+                if directive.rest.trim() == "once" {
+                    let (current, _) = self.open.last().expect("a file is open");
+                    self.once.insert(current.clone());
+                }
+            }
+            // A `#` on its own does nothing.
+            "" if directive.rest.trim().is_empty() => {}
+            _ => {
+                let text = format!("{}{}", directive.name, directive.rest);
+                let word = text.split_whitespace().next().unwrap_or_default();
+                return Err(Error::new(
+                    directive.pos,
+                    format!("unknown preprocessing directive `#{word}`"),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the expression of an `#if` or `#elif` is true.
+    fn condition(&self, directive: &Directive) -> Result<bool, Error> {
+        evaluate(&self.macros, directive.rest)
+            .map(|value| value != 0)
+            .map_err(|message| {
+                let name = directive.name;
+                directive.error(format!("{message} in `#{name}`"))
+            })
+    }
+}
+
+/// Why an `#include` failed: at the directive, or inside the file it
+/// names, which reports its own position.
+enum Included {
+    Here(Error),
+    Inside(FileError),
+}
+
+/// The name of the macro `#ifdef`, `#ifndef` or `#undef` is about.
+fn macro_name<'a>(directive: &Directive<'a>) -> Result<&'a str, Error> {
+    let name = directive.rest.trim();
+    if !is_identifier(name) {
+        let message = format!("expected a macro name after `#{}`", directive.name);
+        return Err(directive.error(message));
+    }
+    Ok(name)
+}
+
+/// The file named by an `#include` directive.
+struct Include<'a> {
+    /// The name between the quotes or angle brackets.
+    name: &'a str,
+    /// Whether the name was quoted rather than in angle brackets.
+    quoted: bool,
+}
+
+/// The file named by what follows `#include`, or why it is malformed.
+fn parse_include(rest: &str) -> Result<Include<'_>, String> {
+    let rest = rest.trim_start();
+    let close = match rest.chars().next() {
+        Some('"') => '"',
+        Some('<') => '>',
+        _ => return Err("expected \"file\" or <file> after `#include`".into()),
+    };
+    let Some(end) = rest[1..].find(close) else {
+        return Err(format!("missing `{close}` after the file name"));
+    };
+    if !rest[end + 2..].trim().is_empty() {
+        return Err("extra tokens after `#include`".into());
+    }
+    Ok(Include {
+        name: &rest[1..end + 1],
+        quoted: close == '"',
+    })
+}
+
+/// The name and macro defined by what follows `#define`.
+fn parse_define(text: &str) -> Result<(String, Macro), String> {
+    let text = text.trim_start();
+    let name_len = text
+        .find(|c: char| !is_identifier_char(c))
+        .unwrap_or(text.len());
+    let (name, rest) = text.split_at(name_len);
+    if !is_identifier(name) {
+        return Err("expected a macro name after `#define`".into());
+    }
+    if name == "defined" {
+        return Err("`defined` cannot be a macro name".into());
+    }
+    // Parameters follow the name with no space between.
+    let Some(rest) = rest.strip_prefix('(') else {
+        let mac = Macro {
+            params: None,
+            variadic: false,
+            body: rest.trim().to_string(),
+        };
+        return Ok((name.to_string(), mac));
+    };
+    let close = rest
+        .find(')')
+        .ok_or("missing `)` after the macro's parameters")?;
+    let mut params = Vec::new();
+    let mut variadic = false;
+    if !rest[..close].trim().is_empty() {
+        for param in rest[..close].split(',').map(str::trim) {
+            if variadic {
+                return Err("`...` must be the last parameter".into());
+            }
+            if param == "..." {
+                variadic = true;
+                params.push("__VA_ARGS__".to_string());
+            } else if !is_identifier(param) {
+                return Err(format!("expected a parameter name but found `{param}`"));
+            } else if params.iter().any(|p| p == param) {
+                return Err(format!("duplicate macro parameter `{param}`"));
+            } else {
+                params.push(param.to_string());
+            }
+        }
+    }
+    let mac = Macro {
+        params: Some(params),
+        variadic,
+        body: rest[close + 1..].trim().to_string(),
+    };
+    Ok((name.to_string(), mac))
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && text.chars().all(is_identifier_char)
+}
+
+/// `line` without its line ending.
+fn line_text(line: &str) -> &str {
+    line.trim_end_matches(['\n', '\r'])
+}
+
+/// What kind of preprocessing token something is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Identifier,
+    Number,
+    /// A string or character literal.
+    Literal,
+    Space,
+    Comment,
+    Punctuator,
+}
+
+/// The token starting at byte `start` of `text`, and where it ends.
+fn token_at(text: &str, start: usize) -> (Kind, usize) {
+    let rest = &text[start..];
+    let c = rest.chars().next().expect("a token starts before the end");
+    let end_of = |kind, len: usize| (kind, start + len);
+    let run = |accept: &dyn Fn(char) -> bool| rest.find(|c| !accept(c)).unwrap_or(rest.len());
+    if c.is_whitespace() {
+        end_of(Kind::Space, run(&|c| c.is_whitespace()))
+    } else if c.is_alphabetic() || c == '_' {
+        end_of(Kind::Identifier, run(&is_identifier_char))
+    } else if c.is_ascii_digit()
+        || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+    {
+        // A number runs on through letters, dots and exponent signs.
+        let bytes = rest.as_bytes();
+        let mut len = 1;
+        while len < bytes.len() {
+            let b = bytes[len];
+            let sign =
+                matches!(b, b'+' | b'-') && matches!(bytes[len - 1], b'e' | b'E' | b'p' | b'P');
+            if !(b.is_ascii_alphanumeric() || b == b'_' || b == b'.' || sign) {
+                break;
+            }
+            len += 1;
+        }
+        end_of(Kind::Number, len)
+    } else if c == '"' || c == '\'' {
+        let mut escaped = false;
+        let len = rest[1..]
+            .find(|d| {
+                let end = d == c && !escaped;
+                escaped = d == '\\' && !escaped;
+                end || d == '\n'
+            })
+            .map_or(rest.len(), |len| len + 2);
+        end_of(Kind::Literal, len)
+    } else if rest.starts_with("//") {
+        end_of(Kind::Comment, rest.find('\n').unwrap_or(rest.len()))
+    } else if let Some(body) = rest.strip_prefix("/*") {
+        let len = body.find("*/").map_or(rest.len(), |len| len + 4);
+        end_of(Kind::Comment, len)
+    } else if rest.starts_with("##") {
+        end_of(Kind::Punctuator, 2)
+    } else {
+        end_of(Kind::Punctuator, c.len_utf8())
+    }
+}
+
+/// `line` with its comments replaced by spaces, so that positions in it
+/// stay where they were. `in_comment` says whether a block comment is open
+/// at the start of the line, and is updated for the next one.
+fn blank_comments(line: &str, in_comment: &mut bool) -> String {
+    let mut out = String::with_capacity(line.len());
+    let blank = |out: &mut String, text: &str| {
+        out.extend(
+            text.chars()
+                .map(|c| if c == '\n' || c == '\r' { c } else { ' ' }),
+        );
+    };
+    let mut start = 0;
+    if *in_comment {
+        match line.find("*/") {
+            Some(end) => {
+                blank(&mut out, &line[..end + 2]);
+                start = end + 2;
+                *in_comment = false;
+            }
+            None => {
+                blank(&mut out, line);
+                return out;
+            }
+        }
+    }
+    while start < line.len() {
+        let (kind, end) = token_at(line, start);
+        let text = &line[start..end];
+        if kind == Kind::Comment {
+            blank(&mut out, text);
+            *in_comment = text.starts_with("/*") && (text.len() < 4 || !text.ends_with("*/"));
+        } else {
+            out.push_str(text);
+        }
+        start = end;
+    }
+    out
+}
+
+/// Why expanding macros failed, at a byte offset in the text expanded.
+enum Expansion {
+    /// A call's arguments ran off the end of the text, and may go on in
+    /// the next line.
+    Unfinished {
+        offset: usize,
+        name: String,
+    },
+    Failed {
+        offset: usize,
+        message: String,
+    },
+}
+
+impl Expansion {
+    /// This error, from expanding a macro called at `offset`, as an error
+    /// there.
+    fn at(self, offset: usize) -> Self {
+        let message = match self {
+            Expansion::Unfinished { name, .. } => format!("unterminated call to macro `{name}`"),
+            Expansion::Failed { message, .. } => message,
+        };
+        Expansion::Failed { offset, message }
+    }
+}
+
+/// Where the block comment that `line` starts in ends, if `in_comment`
+/// says it does, and otherwise 0.
+fn comment_end(line: &str, in_comment: bool) -> usize {
+    match in_comment {
+        true => line.find("*/").map_or(line.len(), |end| end + 2),
+        false => 0,
+    }
+}
+
+/// Expands the macros in a line of text, which starts inside a block
+/// comment if `in_comment` is set.
+fn expand_line(
+    macros: &HashMap<String, Macro>,
+    line: &str,
+    in_comment: bool,
+) -> Result<String, Expansion> {
+    let start = comment_end(line, in_comment);
+    let expanded = expand(macros, &line[start..], &mut Vec::new()).map_err(|err| match err {
+        Expansion::Unfinished { offset, name } => Expansion::Unfinished {
+            offset: offset + start,
+            name,
+        },
+        Expansion::Failed { offset, message } => Expansion::Failed {
+            offset: offset + start,
+            message,
+        },
+    })?;
+    Ok(format!("{}{expanded}", &line[..start]))
+}
+
+/// `text` with its macros expanded, except those in `disabled`, which are
+/// being expanded already.
+fn expand(
+    macros: &HashMap<String, Macro>,
+    text: &str,
+    disabled: &mut Vec<String>,
+) -> Result<String, Expansion> {
+    let mut out = String::with_capacity(text.len());
+    let mut start = 0;
+    while start < text.len() {
+        let (kind, end) = token_at(text, start);
+        let word = &text[start..end];
+        let found = match kind {
+            Kind::Identifier if !disabled.iter().any(|name| name == word) => macros.get(word),
+            _ => None,
+        };
+        let Some(mac) = found else {
+            out.push_str(word);
+            start = end;
+            continue;
+        };
+        let (body, next) = match &mac.params {
+            None => (mac.body.clone(), end),
+            Some(params) => {
+                let open = text[end..]
+                    .find(|c: char| !c.is_whitespace())
+                    .map_or(text.len(), |len| end + len);
+                // A function-like macro's name alone is just a name.
+                if !text[open..].starts_with('(') {
+                    out.push_str(word);
+                    start = end;
+                    continue;
+                }
+                let (args, next) =
+                    split_args(text, open + 1, mac).ok_or_else(|| Expansion::Unfinished {
+                        offset: start,
+                        name: word.to_string(),
+                    })?;
+                if args.len() != params.len() {
+                    let at_least = if mac.variadic { "at least " } else { "" };
+                    let count = params.len() - usize::from(mac.variadic);
+                    let message = format!(
+                        "macro `{word}` takes {at_least}{count} argument{}, but {} were given",
+                        if count == 1 { "" } else { "s" },
+                        args.len()
+                    );
+                    return Err(Expansion::Failed {
+                        offset: start,
+                        message,
+                    });
+                }
+                let body = substitute(macros, mac, &args, disabled).map_err(|err| err.at(start))?;
+                (body, next)
+            }
+        };
+        disabled.push(word.to_string());
+        let expanded = expand(macros, &body, disabled);
+        disabled.pop();
+        out.push_str(&expanded.map_err(|err| err.at(start))?);
+        start = next;
+    }
+    Ok(out)
+}
+
+/// The arguments of a call to `mac` starting at byte `start` of `text`,
+/// just past the `(`, and where the call ends, or `None` if the `)` is
+/// missing.
+fn split_args<'t>(text: &'t str, start: usize, mac: &Macro) -> Option<(Vec<&'t str>, usize)> {
+    let params = mac.params.as_ref().expect("the macro is function-like");
+    let mut args = Vec::new();
+    let mut depth = 0;
+    let mut arg_start = start;
+    let mut pos = start;
+    while pos < text.len() {
+        let (kind, end) = token_at(text, pos);
+        if kind == Kind::Punctuator {
+            match &text[pos..end] {
+                "(" => depth += 1,
+                ")" if depth > 0 => depth -= 1,
+                ")" => {
+                    args.push(text[arg_start..pos].trim());
+                    // `f()` passes no arguments rather than an empty one.
+                    if params.is_empty() && args == [""] {
+                        args.clear();
+                    }
+                    // The variable arguments may be left out entirely.
+                    if mac.variadic && args.len() + 1 == params.len() {
+                        args.push("");
+                    }
+                    return Some((args, end));
+                }
+                // The last of a variadic macro's arguments takes the rest,
+                // commas and all.
+                "," if depth == 0 && !(mac.variadic && args.len() + 1 == params.len()) => {
+                    args.push(text[arg_start..pos].trim());
+                    arg_start = end;
+                }
+                _ => {}
+            }
+        }
+        pos = end;
+    }
+    None
+}
+
+/// The body of `mac` with `args` in place of its parameters: expanded,
+/// or as written when stringized with `#` or pasted with `##`.
+fn substitute(
+    macros: &HashMap<String, Macro>,
+    mac: &Macro,
+    args: &[&str],
+    disabled: &mut Vec<String>,
+) -> Result<String, Expansion> {
+    let params = mac.params.as_ref().expect("the macro is function-like");
+    let param = |word: &str| params.iter().position(|param| param == word);
+    // Each token with whether space came before it.
+    let mut tokens = Vec::new();
+    let mut space = false;
+    let mut start = 0;
+    while start < mac.body.len() {
+        let (kind, end) = token_at(&mac.body, start);
+        if kind == Kind::Space {
+            space = true;
+        } else {
+            tokens.push((kind, &mac.body[start..end], space));
+            space = false;
+        }
+        start = end;
+    }
+    let mut out = String::new();
+    let mut paste = false;
+    let mut index = 0;
+    while index < tokens.len() {
+        let (kind, text, space) = tokens[index];
+        index += 1;
+        if text == "##" {
+            out.truncate(out.trim_end().len());
+            paste = true;
+            continue;
+        }
+        if space && !paste && !out.is_empty() {
+            out.push(' ');
+        }
+        let pasted = paste || tokens.get(index).is_some_and(|&(_, text, _)| text == "##");
+        paste = false;
+        let stringized = tokens.get(index).and_then(|&(_, text, _)| param(text));
+        match (kind, text) {
+            (_, "#") if stringized.is_some() => {
+                out.push_str(&stringize(args[stringized.unwrap()]));
+                index += 1;
+            }
+            (Kind::Identifier, _) => match param(text) {
+                Some(n) if pasted => out.push_str(args[n]),
+                Some(n) => out.push_str(&expand(macros, args[n], disabled)?),
+                None => out.push_str(text),
+            },
+            _ => out.push_str(text),
+        }
+    }
+    Ok(out)
+}
+
+/// `arg` as a string literal, for `#`.
+fn stringize(arg: &str) -> String {
+    let mut out = String::from('"');
+    for (n, word) in arg.split_whitespace().enumerate() {
+        if n > 0 {
+            out.push(' ');
+        }
+        for c in word.chars() {
+            if c == '"' || c == '\\' {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// The value of the expression of an `#if`, or what is wrong with it.
+fn evaluate(macros: &HashMap<String, Macro>, text: &str) -> Result<i64, String> {
+    // `defined` goes before macros are expanded, so that it sees their
+    // names.
+    let mut resolved = String::new();
+    let mut start = 0;
+    while start < text.len() {
+        let (kind, end) = token_at(text, start);
+        let word = &text[start..end];
+        start = end;
+        if kind != Kind::Identifier || word != "defined" {
+            resolved.push_str(word);
+            continue;
+        }
+        let rest = text[start..].trim_start();
+        let (name, after) = match rest.strip_prefix('(') {
+            Some(inner) => {
+                let close = inner.find(')').ok_or("missing `)` after `defined`")?;
+                (inner[..close].trim(), &inner[close + 1..])
+            }
+            None => {
+                let len = rest
+                    .find(|c: char| !is_identifier_char(c))
+                    .unwrap_or(rest.len());
+                (&rest[..len], &rest[len..])
+            }
+        };
+        if !is_identifier(name) {
+            return Err("expected a macro name after `defined`".into());
+        }
+        resolved.push_str(if macros.contains_key(name) {
+            " 1 "
+        } else {
+            " 0 "
+        });
+        start = text.len() - after.len();
+    }
+    let expanded = expand(macros, &resolved, &mut Vec::new()).map_err(|err| match err.at(0) {
+        Expansion::Failed { message, .. } => message,
+        Expansion::Unfinished { .. } => unreachable!("`at` finishes the error"),
+    })?;
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    while start < expanded.len() {
+        let (kind, mut end) = token_at(&expanded, start);
+        if kind == Kind::Punctuator {
+            // Operators of two characters.
+            let pair = expanded.get(start..start + 2).unwrap_or_default();
+            if ["||", "&&", "==", "!=", "<=", ">=", "<<", ">>"].contains(&pair) {
+                end = start + 2;
+            }
+        }
+        if kind != Kind::Space {
+            tokens.push((kind, &expanded[start..end]));
+        }
+        start = end;
+    }
+    if tokens.is_empty() {
+        return Err("missing expression".into());
+    }
+    let mut parser = Condition { tokens, next: 0 };
+    let value = parser.ternary()?;
+    match parser.tokens.get(parser.next) {
+        Some((_, text)) => Err(format!("unexpected `{text}`")),
+        None => Ok(value),
+    }
+}
+
+/// A parser for the integer constant expression of an `#if`, evaluating
+/// it as it goes. Identifiers that are left after expanding macros are 0.
+struct Condition<'t> {
+    tokens: Vec<(Kind, &'t str)>,
+    next: usize,
+}
+
+impl Condition<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.next).map(|&(_, text)| text)
+    }
+
+    fn eat(&mut self, text: &str) -> bool {
+        let found = self.peek() == Some(text);
+        self.next += usize::from(found);
+        found
+    }
+
+    fn ternary(&mut self) -> Result<i64, String> {
+        let cond = self.binary(0)?;
+        if !self.eat("?") {
+            return Ok(cond);
+        }
+        let then = self.ternary()?;
+        if !self.eat(":") {
+            return Err("expected `:`".into());
+        }
+        let otherwise = self.ternary()?;
+        Ok(if cond != 0 { then } else { otherwise })
+    }
+
+    /// A chain of binary operators binding at least as tightly as
+    /// `min_precedence`.
+    fn binary(&mut self, min_precedence: u8) -> Result<i64, String> {
+        let mut lhs = self.unary()?;
+        while let Some(op) = self.peek() {
+            let precedence = match op {
+                "||" => 0,
+                "&&" => 1,
+                "|" => 2,
+                "^" => 3,
+                "&" => 4,
+                "==" | "!=" => 5,
+                "<" | ">" | "<=" | ">=" => 6,
+                "<<" | ">>" => 7,
+                "+" | "-" => 8,
+                "*" | "/" | "%" => 9,
+                _ => break,
+            };
+            if precedence < min_precedence {
+                break;
+            }
+            let op = op.to_string();
+            self.next += 1;
+            let rhs = self.binary(precedence + 1)?;
+            lhs = match op.as_str() {
+                "||" => i64::from(lhs != 0 || rhs != 0),
+                "&&" => i64::from(lhs != 0 && rhs != 0),
+                "|" => lhs | rhs,
+                "^" => lhs ^ rhs,
+                "&" => lhs & rhs,
+                "==" => i64::from(lhs == rhs),
+                "!=" => i64::from(lhs != rhs),
+                "<" => i64::from(lhs < rhs),
+                ">" => i64::from(lhs > rhs),
+                "<=" => i64::from(lhs <= rhs),
+                ">=" => i64::from(lhs >= rhs),
+                "<<" => lhs.wrapping_shl(rhs as u32),
+                ">>" => lhs.wrapping_shr(rhs as u32),
+                "+" => lhs.wrapping_add(rhs),
+                "-" => lhs.wrapping_sub(rhs),
+                "*" => lhs.wrapping_mul(rhs),
+                _ if rhs == 0 => return Err("division by zero".into()),
+                "/" => lhs.wrapping_div(rhs),
+                _ => lhs.wrapping_rem(rhs),
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        let Some(&(kind, text)) = self.tokens.get(self.next) else {
+            return Err("expected a value".into());
+        };
+        self.next += 1;
+        match (kind, text) {
+            (_, "!") => Ok(i64::from(self.unary()? == 0)),
+            (_, "~") => Ok(!self.unary()?),
+            (_, "-") => Ok(self.unary()?.wrapping_neg()),
+            (_, "+") => self.unary(),
+            (_, "(") => {
+                let value = self.ternary()?;
+                if !self.eat(")") {
+                    return Err("expected `)`".into());
+                }
+                Ok(value)
+            }
+            (Kind::Number, _) => parse_number(text),
+            (Kind::Literal, _) if text.starts_with('\'') => parse_char(text),
+            (Kind::Identifier, _) => Ok(0),
+            _ => Err(format!("expected a value but found `{text}`")),
+        }
+    }
+}
+
+/// The path that names the file at `path` whichever way it is reached, or
+/// `path` itself if it can't be resolved.
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// The value of an integer literal, ignoring its suffix.
+fn parse_number(text: &str) -> Result<i64, String> {
+    let digits = text.trim_end_matches(['u', 'U', 'l', 'L']);
+    let value = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        u64::from_str_radix(hex, 16)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        u64::from_str_radix(&digits[1..], 8)
+    } else {
+        digits.parse()
+    };
+    value
+        .map(|value| value as i64)
+        .map_err(|_| format!("invalid integer `{text}`"))
+}
+
+/// The value of a character literal.
+fn parse_char(text: &str) -> Result<i64, String> {
+    let inner = &text[1..text.len().max(2) - 1];
+    let value = match inner.strip_prefix('\\') {
+        None if inner.chars().count() == 1 => inner.chars().next().unwrap() as i64,
+        Some("n") => 10,
+        Some("t") => 9,
+        Some("r") => 13,
+        Some("0") => 0,
+        Some("\\") => 92,
+        Some("'") => 39,
+        Some("\"") => 34,
+        _ => return Err(format!("invalid character `{text}`")),
+    };
+    Ok(value)
+}
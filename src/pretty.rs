@@ -0,0 +1,539 @@
+//! The syntax tree printed back as C (`--emit=c`), formatted one statement
+//! to a line with four-space indents.
+//!
+//! Parsing the output gives the same tree, positions aside. Parentheses
+//! are only written where precedence needs them, and `*(a + i)` is
+//! written `a[i]`, which parses to the same tree. File-scope enums come
+//! first, then globals and then functions, since nothing can use a name
+//! declared after it.
+
+use std::fmt::Write;
+
+use crate::ast::{
+    AsmOperand, BinaryOp, Enum, Expression, Function, Global, Initializer, Param, Program,
+    Statement, Type,
+};
+
+const INDENT: &str = "    ";
+
+/// Binding strengths, loosest first. An operand binding looser than its
+/// place allows is parenthesized.
+const ASSIGNMENT: u8 = 1;
+/// `||`, the loosest binary operator. The others follow in order up to
+/// `*`, `/` and `%`.
+const BINARY: u8 = 2;
+const PREFIX: u8 = 12;
+const POSTFIX: u8 = 13;
+const PRIMARY: u8 = 14;
+
+/// `program` as C source.
+pub fn emit(program: &Program) -> String {
+    let mut sections = Vec::new();
+    if !program.enums.is_empty() {
+        let mut out = String::new();
+        for decl in &program.enums {
+            write_enum(&mut out, decl);
+            out.push('\n');
+        }
+        sections.push(out);
+    }
+    if !program.globals.is_empty() {
+        let mut out = String::new();
+        for global in &program.globals {
+            write_global(&mut out, global);
+        }
+        sections.push(out);
+    }
+    sections.extend(program.functions.iter().map(function));
+    sections.join("\n")
+}
+
+/// `expr` as C, parenthesized only inside.
+pub fn expression(expr: &Expression) -> String {
+    let mut out = String::new();
+    write_expression(&mut out, expr, ASSIGNMENT);
+    out
+}
+
+fn function(function: &Function) -> String {
+    let params = if !function.prototyped {
+        "()".to_string()
+    } else if function.params.is_empty() {
+        "(void)".to_string()
+    } else {
+        let mut params: Vec<String> = function.params.iter().map(param).collect();
+        if function.variadic {
+            params.push("...".to_string());
+        }
+        format!("({})", params.join(", "))
+    };
+    let mut out = function
+        .return_type
+        .declare(&format!("{}{params}", function.name));
+    match &function.body {
+        Some(body) => {
+            out.push(' ');
+            write_block(&mut out, body, 0);
+            out.push('\n');
+        }
+        None => out.push_str(";\n"),
+    }
+    out
+}
+
+/// A parameter declared as an array is a pointer, and a pointer to an
+/// array is written as an array of unknown size, `int a[][3]`, which the
+/// parser reads back as one.
+fn param(param: &Param) -> String {
+    let name = param.name.as_deref().unwrap_or("");
+    match &param.ty {
+        Type::Pointer(pointee) if matches!(**pointee, Type::Array(..)) => {
+            pointee.declare(&format!("{name}[]"))
+        }
+        ty => ty.declare(name),
+    }
+}
+
+fn write_global(out: &mut String, global: &Global) {
+    if global.is_extern {
+        out.push_str("extern ");
+    }
+    if global.thread_local {
+        out.push_str("_Thread_local ");
+    }
+    out.push_str(&global.ty.declare(&global.name));
+    if let Some(init) = &global.init {
+        out.push_str(" = ");
+        write_initializer(out, init);
+    }
+    out.push_str(";\n");
+}
+
+fn write_enum(out: &mut String, decl: &Enum) {
+    out.push_str("enum ");
+    if let Some(tag) = &decl.tag {
+        write!(out, "{tag} ").unwrap();
+    }
+    out.push_str("{ ");
+    for (i, enumerator) in decl.enumerators.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        out.push_str(&enumerator.name);
+        if let Some(value) = &enumerator.value {
+            out.push_str(" = ");
+            write_expression(out, value, ASSIGNMENT);
+        }
+    }
+    out.push_str(" };");
+}
+
+fn write_initializer(out: &mut String, init: &Initializer) {
+    match init {
+        Initializer::Expr(expr) => write_expression(out, expr, ASSIGNMENT),
+        Initializer::List { elements, .. } => {
+            out.push('{');
+            for (i, element) in elements.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_initializer(out, element);
+            }
+            out.push('}');
+        }
+    }
+}
+
+/// `{`, each statement on a line of its own one level in from `depth`,
+/// and `}` at `depth`.
+fn write_block(out: &mut String, body: &[Statement], depth: usize) {
+    out.push_str("{\n");
+    for statement in body {
+        write_statement(out, statement, depth + 1);
+    }
+    out.push_str(&INDENT.repeat(depth));
+    out.push('}');
+}
+
+/// `statement` on lines of its own, indented to `depth`.
+fn write_statement(out: &mut String, statement: &Statement, depth: usize) {
+    write_indent(out, statement, depth);
+    write_statement_inline(out, statement, depth);
+    out.push('\n');
+}
+
+/// The indent of a statement at `depth`, which a label, `case` or
+/// `default` stands a level out from.
+fn write_indent(out: &mut String, statement: &Statement, depth: usize) {
+    let depth = match statement {
+        Statement::Label { .. } | Statement::Case { .. } | Statement::Default { .. } => {
+            depth.saturating_sub(1)
+        }
+        _ => depth,
+    };
+    out.push_str(&INDENT.repeat(depth));
+}
+
+/// `statement` from where the line already is, its first line not
+/// indented and without a newline after its last.
+fn write_statement_inline(out: &mut String, statement: &Statement, depth: usize) {
+    match statement {
+        Statement::Return { value: None, .. } => out.push_str("return;"),
+        Statement::Return {
+            value: Some(value), ..
+        } => {
+            out.push_str("return ");
+            write_expression(out, value, ASSIGNMENT);
+            out.push(';');
+        }
+        Statement::Declaration { name, ty, init, .. } => {
+            out.push_str(&ty.declare(name));
+            if let Some(init) = init {
+                out.push_str(" = ");
+                write_initializer(out, init);
+            }
+            out.push(';');
+        }
+        Statement::Expression { expr, .. } => {
+            write_expression(out, expr, ASSIGNMENT);
+            out.push(';');
+        }
+        Statement::Enum(decl) => write_enum(out, decl),
+        Statement::Block { body, .. } => write_block(out, body, depth),
+        Statement::If {
+            cond,
+            then,
+            otherwise,
+            ..
+        } => {
+            out.push_str("if (");
+            write_expression(out, cond, ASSIGNMENT);
+            out.push(')');
+            write_body(out, then, depth);
+            if let Some(otherwise) = otherwise {
+                if let Statement::Block { .. } = **then {
+                    out.push(' ');
+                } else {
+                    out.push('\n');
+                    out.push_str(&INDENT.repeat(depth));
+                }
+                out.push_str("else");
+                if let Statement::If { .. } = **otherwise {
+                    out.push(' ');
+                    write_statement_inline(out, otherwise, depth);
+                } else {
+                    write_body(out, otherwise, depth);
+                }
+            }
+        }
+        Statement::While { cond, body, .. } => {
+            out.push_str("while (");
+            write_expression(out, cond, ASSIGNMENT);
+            out.push(')');
+            write_body(out, body, depth);
+        }
+        Statement::DoWhile { body, cond, .. } => {
+            out.push_str("do");
+            write_body(out, body, depth);
+            match **body {
+                Statement::Block { .. } => out.push(' '),
+                _ => {
+                    out.push('\n');
+                    out.push_str(&INDENT.repeat(depth));
+                }
+            }
+            out.push_str("while (");
+            write_expression(out, cond, ASSIGNMENT);
+            out.push_str(");");
+        }
+        Statement::For {
+            init,
+            cond,
+            step,
+            body,
+            ..
+        } => {
+            out.push_str("for (");
+            match init {
+                // The statement ends in its own `;`.
+                Some(init) => write_statement_inline(out, init, depth),
+                None => out.push(';'),
+            }
+            if let Some(cond) = cond {
+                out.push(' ');
+                write_expression(out, cond, ASSIGNMENT);
+            }
+            out.push(';');
+            if let Some(step) = step {
+                out.push(' ');
+                write_expression(out, step, ASSIGNMENT);
+            }
+            out.push(')');
+            write_body(out, body, depth);
+        }
+        Statement::Label { name, body, .. } => {
+            writeln!(out, "{name}:").unwrap();
+            write_indent(out, body, depth);
+            write_statement_inline(out, body, depth);
+        }
+        Statement::Goto { label, .. } => write!(out, "goto {label};").unwrap(),
+        Statement::Switch { cond, body, .. } => {
+            out.push_str("switch (");
+            write_expression(out, cond, ASSIGNMENT);
+            out.push(')');
+            write_body(out, body, depth);
+        }
+        Statement::Case { value, body, .. } => {
+            out.push_str("case ");
+            write_expression(out, value, ASSIGNMENT);
+            out.push_str(":\n");
+            write_indent(out, body, depth);
+            write_statement_inline(out, body, depth);
+        }
+        Statement::Default { body, .. } => {
+            out.push_str("default:\n");
+            write_indent(out, body, depth);
+            write_statement_inline(out, body, depth);
+        }
+        Statement::Break { .. } => out.push_str("break;"),
+        Statement::Empty { .. } => out.push(';'),
+        Statement::Asm {
+            template,
+            outputs,
+            inputs,
+            clobbers,
+            ..
+        } => {
+            write!(out, "__asm__({}", quoted(template)).unwrap();
+            // Only as many sections as the last non-empty one needs.
+            let sections = if !clobbers.is_empty() {
+                3
+            } else if !inputs.is_empty() {
+                2
+            } else {
+                usize::from(!outputs.is_empty())
+            };
+            for operands in [outputs, inputs].into_iter().take(sections) {
+                out.push_str(" :");
+                write_asm_operands(out, operands);
+            }
+            if sections == 3 {
+                out.push_str(" :");
+                for (i, clobber) in clobbers.iter().enumerate() {
+                    let separator = if i == 0 { " " } else { ", " };
+                    write!(out, "{separator}{}", quoted(clobber)).unwrap();
+                }
+            }
+            out.push_str(");");
+        }
+    }
+}
+
+/// The body of a loop: a block on the same line, or any other statement
+/// on the next line, one level in.
+fn write_body(out: &mut String, body: &Statement, depth: usize) {
+    if let Statement::Block { body, .. } = body {
+        out.push(' ');
+        write_block(out, body, depth);
+    } else {
+        out.push('\n');
+        out.push_str(&INDENT.repeat(depth + 1));
+        write_statement_inline(out, body, depth + 1);
+    }
+}
+
+fn write_asm_operands(out: &mut String, operands: &[AsmOperand]) {
+    for (i, operand) in operands.iter().enumerate() {
+        let separator = if i == 0 { " " } else { ", " };
+        write!(out, "{separator}{}(", quoted(&operand.constraint)).unwrap();
+        write_expression(out, &operand.expr, ASSIGNMENT);
+        out.push(')');
+    }
+}
+
+/// How tightly `expr` binds, from `ASSIGNMENT` to `PRIMARY`.
+fn binding(expr: &Expression) -> u8 {
+    match expr {
+        Expression::Assign { .. } => ASSIGNMENT,
+        Expression::Binary { op, .. } => binary_binding(*op),
+        Expression::Deref { operand, .. } if subscript(operand).is_some() => POSTFIX,
+        Expression::Unary { .. }
+        | Expression::AddressOf { .. }
+        | Expression::Deref { .. }
+        | Expression::SizeofExpr { .. }
+        | Expression::SizeofType { .. } => PREFIX,
+        Expression::IncDec { op, .. } if !op.is_postfix() => PREFIX,
+        Expression::IncDec { .. }
+        | Expression::FunctionCall { .. }
+        | Expression::BuiltinCall { .. } => POSTFIX,
+        Expression::IntLit { .. }
+        | Expression::CharLit { .. }
+        | Expression::StringLit { .. }
+        | Expression::Variable { .. } => PRIMARY,
+    }
+}
+
+fn binary_binding(op: BinaryOp) -> u8 {
+    BINARY
+        + match op {
+            BinaryOp::LogicalOr => 0,
+            BinaryOp::LogicalAnd => 1,
+            BinaryOp::BitOr => 2,
+            BinaryOp::BitXor => 3,
+            BinaryOp::BitAnd => 4,
+            BinaryOp::Eq | BinaryOp::Ne => 5,
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => 6,
+            BinaryOp::Shl | BinaryOp::Shr => 7,
+            BinaryOp::Add | BinaryOp::Sub => 8,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 9,
+        }
+}
+
+/// The array and index of `address` if it is `a + i`, which `a[i]`
+/// dereferences.
+fn subscript(address: &Expression) -> Option<(&Expression, &Expression)> {
+    match address {
+        Expression::Binary {
+            op: BinaryOp::Add,
+            operands,
+            ..
+        } => Some((&operands[0], &operands[1])),
+        _ => None,
+    }
+}
+
+/// Writes `expr`, in parentheses if it binds looser than `min`.
+fn write_expression(out: &mut String, expr: &Expression, min: u8) {
+    let binding = binding(expr);
+    if binding < min {
+        out.push('(');
+        write_expression(out, expr, ASSIGNMENT);
+        out.push(')');
+        return;
+    }
+    match expr {
+        Expression::IntLit { value, suffix, .. } => {
+            write!(out, "{value}{}", suffix.as_str()).unwrap()
+        }
+        Expression::CharLit { value, .. } => write!(out, "'{}'", escape(&[*value], '\'')).unwrap(),
+        Expression::StringLit { value, .. } => write!(out, "\"{}\"", escape(value, '"')).unwrap(),
+        Expression::Variable { name, .. } => out.push_str(name),
+        Expression::Unary { op, operand, .. } => write_prefix(out, op.as_str(), operand),
+        Expression::AddressOf { operand, .. } => write_prefix(out, "&", operand),
+        Expression::Deref { operand, .. } => match subscript(operand) {
+            Some((array, index)) => {
+                write_expression(out, array, POSTFIX);
+                out.push('[');
+                write_expression(out, index, ASSIGNMENT);
+                out.push(']');
+            }
+            None => write_prefix(out, "*", operand),
+        },
+        Expression::IncDec { op, operand, .. } if op.is_postfix() => {
+            write_expression(out, operand, POSTFIX);
+            out.push_str(op.as_str());
+        }
+        Expression::IncDec { op, operand, .. } => write_prefix(out, op.as_str(), operand),
+        Expression::SizeofExpr { operand, .. } => {
+            out.push_str("sizeof ");
+            write_expression(out, operand, PREFIX);
+        }
+        Expression::SizeofType { ty, .. } => write!(out, "sizeof({ty})").unwrap(),
+        Expression::Binary { .. } => {
+            let (first, links) = expr.binary_chain();
+            // A left operand binding looser than its operator is in
+            // parentheses, which all open before the chain's first operand.
+            let bindings: Vec<u8> = links.iter().map(|link| binary_binding(link.op)).collect();
+            let closes: Vec<bool> = (0..links.len())
+                .map(|i| i > 0 && bindings[i - 1] < bindings[i])
+                .collect();
+            for _ in closes.iter().filter(|&&close| close) {
+                out.push('(');
+            }
+            write_expression(out, first, bindings[0]);
+            for ((link, binding), close) in links.iter().zip(bindings).zip(closes) {
+                if close {
+                    out.push(')');
+                }
+                // Binary operators group to the left, so an operand on the
+                // right binding only as tightly needs parentheses.
+                write!(out, " {} ", link.op.as_str()).unwrap();
+                write_expression(out, &link.operands[1], binding + 1);
+            }
+        }
+        Expression::Assign { op, operands, .. } => {
+            let [target, value] = &**operands;
+            write_expression(out, target, BINARY);
+            match op {
+                Some(op) => write!(out, " {}= ", op.as_str()).unwrap(),
+                None => out.push_str(" = "),
+            }
+            write_expression(out, value, ASSIGNMENT);
+        }
+        Expression::FunctionCall { name, args, .. } => write_call(out, name, args),
+        Expression::BuiltinCall { builtin, args, .. } => write_call(out, builtin.name(), args),
+    }
+}
+
+/// A prefix operator and its operand, with a space between them where
+/// they would otherwise run together into another token, as `- -x` would
+/// into `--x`.
+fn write_prefix(out: &mut String, op: &str, operand: &Expression) {
+    out.push_str(op);
+    let start = out.len();
+    write_expression(out, operand, PREFIX);
+    let last = op.chars().last();
+    if matches!(last, Some('-' | '+' | '&')) && out[start..].chars().next() == last {
+        out.insert(start, ' ');
+    }
+}
+
+fn write_call(out: &mut String, name: &str, args: &[Expression]) {
+    out.push_str(name);
+    out.push('(');
+    for (i, arg) in args.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write_expression(out, arg, ASSIGNMENT);
+    }
+    out.push(')');
+}
+
+/// `bytes` as the inside of a literal quoted with `quote`. Bytes that
+/// aren't printable ASCII are written as three-digit octal escapes, so
+/// that a digit after one can't be read as part of it.
+fn escape(bytes: &[u8], quote: char) -> String {
+    let mut out = String::new();
+    for &byte in bytes {
+        if byte.is_ascii() {
+            escape_char(&mut out, byte as char, quote);
+        } else {
+            write!(out, "\\{byte:03o}").unwrap();
+        }
+    }
+    out
+}
+
+/// A string of the `asm` statement as a literal. Its characters outside
+/// ASCII stand for themselves.
+fn quoted(text: &str) -> String {
+    let mut out = String::from('"');
+    for c in text.chars() {
+        escape_char(&mut out, c, '"');
+    }
+    out.push('"');
+    out
+}
+
+fn escape_char(out: &mut String, c: char, quote: char) {
+    match c {
+        '\n' => out.push_str("\\n"),
+        '\t' => out.push_str("\\t"),
+        '\\' => out.push_str("\\\\"),
+        c if c == quote => write!(out, "\\{c}").unwrap(),
+        ' '..='~' => out.push(c),
+        c if !c.is_ascii() => out.push(c),
+        c => write!(out, "\\{:03o}", u32::from(c)).unwrap(),
+    }
+}
@@ -0,0 +1,156 @@
+//! An interactive session (`rcc repl`) that runs C through the IR
+//! interpreter instead of generating assembly.
+//!
+//! An entry starting with a type is a function definition, an enum or a
+//! global variable, kept for every later entry and replacing any earlier
+//! entry defining the same name. Any other entry is an `int` expression: it
+//! becomes the body of a fresh function, compiled along with the
+//! definitions and interpreted, so globals start from their initial values
+//! in each one.
+
+use std::io::{self, BufRead, Write};
+
+use crate::driver::{self, Options};
+use crate::ir::interp::Interpreter;
+use crate::lexer::{Keyword, Lexer, Token, TokenKind};
+use crate::parser::Parser;
+
+/// The function an expression entry is wrapped in.
+const ENTRY: &str = "__rcc_repl_entry";
+
+/// What evaluating an entry did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The entry defined these functions, enumeration constants or
+    /// globals.
+    Defined(Vec<String>),
+    /// The entry was an expression with this value.
+    Value(i64),
+}
+
+#[derive(Debug, Default)]
+pub struct Session {
+    options: Options,
+    /// The source of each definition entry with the functions it defines,
+    /// in order.
+    definitions: Vec<(Vec<String>, String)>,
+}
+
+impl Session {
+    pub fn new(options: Options) -> Self {
+        Self {
+            options,
+            definitions: Vec::new(),
+        }
+    }
+
+    /// The names of the functions, enumeration constants and globals
+    /// defined so far.
+    pub fn functions(&self) -> impl Iterator<Item = &str> {
+        self.definitions
+            .iter()
+            .flat_map(|(names, _)| names)
+            .map(String::as_str)
+    }
+
+    /// Evaluates one complete entry; see the module docs.
+    pub fn eval(&mut self, entry: &str) -> Result<Outcome, String> {
+        let tokens = Lexer::new(entry).lex().map_err(|err| err.message)?;
+        if matches!(
+            tokens[0].kind,
+            TokenKind::Keyword(keyword) if keyword.starts_type()
+                || keyword == Keyword::ThreadLocal
+                || keyword == Keyword::Extern
+        ) {
+            self.define(entry, tokens)
+        } else {
+            self.evaluate(entry)
+        }
+    }
+
+    fn define(&mut self, entry: &str, tokens: Vec<Token>) -> Result<Outcome, String> {
+        let program = Parser::new(tokens).parse().map_err(|err| err.message)?;
+        let enumerators = program.enums.iter().flat_map(|decl| &decl.enumerators);
+        let names: Vec<String> = program
+            .functions
+            .iter()
+            .map(|f| f.name.clone())
+            .chain(enumerators.map(|enumerator| enumerator.name.clone()))
+            .chain(program.globals.iter().map(|global| global.name.clone()))
+            .collect();
+        // An entry is replaced whole when any of its functions is redefined.
+        let mut definitions = self.definitions.clone();
+        definitions.retain(|(defined, _)| !defined.iter().any(|name| names.contains(name)));
+        definitions.push((names.clone(), entry.to_string()));
+        // Check the session still compiles before keeping the entry.
+        driver::compile(&source(&definitions), &self.options).map_err(|err| err.message)?;
+        self.definitions = definitions;
+        Ok(Outcome::Defined(names))
+    }
+
+    fn evaluate(&self, expression: &str) -> Result<Outcome, String> {
+        let mut source = source(&self.definitions);
+        source.push_str(&format!("int {ENTRY}() {{ return {expression}; }}\n"));
+        let artifacts = driver::compile(&source, &self.options).map_err(|err| err.message)?;
+        let value = Interpreter::new(&artifacts.ir)
+            .call(ENTRY, &[])
+            .map_err(|trap| trap.to_string())?;
+        Ok(Outcome::Value(value.unwrap_or(0)))
+    }
+}
+
+fn source(definitions: &[(Vec<String>, String)]) -> String {
+    definitions
+        .iter()
+        .map(|(_, text)| format!("{text}\n"))
+        .collect()
+}
+
+/// Whether `entry` closes every brace it opens, so a definition spanning
+/// several lines is read whole.
+pub fn is_complete(entry: &str) -> bool {
+    let opened = entry.matches('{').count();
+    let closed = entry.matches('}').count();
+    closed >= opened
+}
+
+/// Reads entries from `input` until it ends or `:quit`, printing each
+/// outcome. `:functions` lists the definitions.
+pub fn run(input: impl BufRead, mut output: impl Write, options: Options) -> io::Result<()> {
+    let mut session = Session::new(options);
+    let mut entry = String::new();
+    write!(output, "> ")?;
+    output.flush()?;
+    for line in input.lines() {
+        let line = line?;
+        match line.trim() {
+            ":quit" | ":q" => return Ok(()),
+            ":functions" if entry.is_empty() => {
+                for name in session.functions() {
+                    writeln!(output, "{name}")?;
+                }
+            }
+            "" if entry.is_empty() => {}
+            _ => {
+                entry.push_str(&line);
+                entry.push('\n');
+                if !is_complete(&entry) {
+                    write!(output, "... ")?;
+                    output.flush()?;
+                    continue;
+                }
+                match session.eval(&entry) {
+                    Ok(Outcome::Value(value)) => writeln!(output, "{value}")?,
+                    Ok(Outcome::Defined(names)) => {
+                        writeln!(output, "defined {}", names.join(", "))?
+                    }
+                    Err(message) => writeln!(output, "error: {message}")?,
+                }
+                entry.clear();
+            }
+        }
+        write!(output, "> ")?;
+        output.flush()?;
+    }
+    writeln!(output)
+}
@@ -0,0 +1,113 @@
+//! A single self-contained HTML page showing every stage's output for one
+//! translation unit side by side (`--emit=report`), in the manner of
+//! Compiler Explorer.
+//!
+//! Every line of every pane is a span tagged with the source line it comes
+//! from, where that is known; hovering over one highlights the lines from
+//! the same source line in all the panes. Assembly lines are tagged from
+//! [`Artifacts::positions`], so the artifacts must be compiled with
+//! [`Options::source_map`](crate::driver::Options::source_map).
+
+use std::fmt::Write;
+
+use crate::ast_diff;
+use crate::driver::Artifacts;
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 1em; }
+.panes { display: grid; grid-template-columns: repeat(5, minmax(0, 1fr)); gap: 1em; }
+h2 { font-size: 1em; }
+pre { font-size: 12px; overflow-x: auto; background: #f6f6f6; padding: 0.5em; }
+pre span { display: block; min-height: 1.2em; }
+pre span.hl { background: #ffe08a; }
+";
+
+const SCRIPT: &str = "for (const span of document.querySelectorAll('span[data-line]')) {
+  const same = document.querySelectorAll(`span[data-line=\"${span.dataset.line}\"]`);
+  span.addEventListener('mouseenter', () => same.forEach(s => s.classList.add('hl')));
+  span.addEventListener('mouseleave', () => same.forEach(s => s.classList.remove('hl')));
+}
+";
+
+/// The report for `artifacts`, compiled from `source` in `file`.
+pub fn html(file: &str, source: &str, artifacts: &Artifacts) -> String {
+    let source_lines = source
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (Some(i as u32 + 1), line.to_string()));
+    let tokens = artifacts.tokens.iter().map(|token| {
+        (
+            Some(token.pos.line),
+            format!("{} {}", token.pos, token.kind),
+        )
+    });
+    let ast = artifacts.program.functions.iter().flat_map(|function| {
+        ast_diff::render_with_positions(function)
+            .into_iter()
+            .map(|(line, pos)| (Some(pos.line), line))
+    });
+    let assembly = artifacts.assembly.lines().enumerate().map(|(i, line)| {
+        let pos = artifacts.positions.get(i).copied().flatten();
+        (pos.map(|pos| pos.line), line.to_string())
+    });
+
+    let title = escape(file);
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>rcc: {title}</title>\n<style>\n{STYLE}</style>\n</head>\n<body>\n\
+         <h1>{title}</h1>\n<div class=\"panes\">\n"
+    );
+    pane(&mut out, "Source", source_lines);
+    pane(&mut out, "Tokens", tokens);
+    pane(&mut out, "Syntax tree", ast);
+    pane(&mut out, "IR", ir_lines(&artifacts.ir.to_string()));
+    pane(&mut out, "Assembly", assembly);
+    write!(
+        out,
+        "</div>\n<script>\n{SCRIPT}</script>\n</body>\n</html>\n"
+    )
+    .unwrap();
+    out
+}
+
+/// The lines of printed IR, each tagged with the source line of the last
+/// `loc` before it in its function's body.
+fn ir_lines(ir: &str) -> Vec<(Option<u32>, String)> {
+    let mut line = None;
+    ir.lines()
+        .map(|text| {
+            let trimmed = text.trim_start();
+            if let Some(pos) = trimmed.strip_prefix("loc ") {
+                line = pos.split(':').next().and_then(|line| line.parse().ok());
+            } else if trimmed.starts_with("fn ") || trimmed == "}" || trimmed.is_empty() {
+                line = None;
+            }
+            (line, text.to_string())
+        })
+        .collect()
+}
+
+fn pane(out: &mut String, title: &str, lines: impl IntoIterator<Item = (Option<u32>, String)>) {
+    write!(out, "<section>\n<h2>{title}</h2>\n<pre>").unwrap();
+    for (line, text) in lines {
+        match line {
+            Some(line) => write!(out, "<span data-line=\"{line}\">{}</span>", escape(&text)),
+            None => write!(out, "<span>{}</span>", escape(&text)),
+        }
+        .unwrap();
+    }
+    out.push_str("</pre>\n</section>\n");
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,37 @@
+//! Maps the lines of generated assembly back to the source they came from
+//! (`--emit=asm-map`), for editors and other tools.
+//!
+//! The map is JSON: the source file and one entry per line of assembly
+//! that has a source position, with 1-based line numbers throughout:
+//!
+//! ```text
+//! {"file":"a.c","lines":[
+//!   {"asm":8,"line":1,"column":5},
+//!   ...
+//! ]}
+//! ```
+
+use crate::json::Value;
+use crate::lexer::Position;
+
+/// The map of assembly whose lines have `positions`, compiled from `file`.
+pub fn to_json(file: &str, positions: &[Option<Position>]) -> String {
+    let lines: Vec<String> = positions
+        .iter()
+        .enumerate()
+        .filter_map(|(i, pos)| {
+            let pos = (*pos)?;
+            let entry = Value::object([
+                ("asm", Value::from(i as u32 + 1)),
+                ("line", pos.line.into()),
+                ("column", pos.column.into()),
+            ]);
+            Some(format!("  {entry}"))
+        })
+        .collect();
+    format!(
+        "{{\"file\":{},\"lines\":[\n{}\n]}}\n",
+        Value::from(file),
+        lines.join(",\n")
+    )
+}
@@ -0,0 +1,98 @@
+//! The C language standards rcc accepts (`--std=`).
+
+use std::fmt;
+
+use crate::error::{Error, Result};
+use crate::lexer::Position;
+
+/// An ISO C revision, ordered by publication.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Version {
+    C89,
+    C99,
+    C11,
+    C17,
+    C23,
+}
+
+impl Version {
+    pub fn name(self) -> &'static str {
+        match self {
+            Version::C89 => "C89",
+            Version::C99 => "C99",
+            Version::C11 => "C11",
+            Version::C17 => "C17",
+            Version::C23 => "C23",
+        }
+    }
+}
+
+/// The language to accept: an ISO revision, optionally with GNU
+/// extensions such as the plain `asm` keyword. Defaults to `gnu17`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Standard {
+    pub version: Version,
+    pub gnu: bool,
+}
+
+impl Default for Standard {
+    fn default() -> Self {
+        Self {
+            version: Version::C17,
+            gnu: true,
+        }
+    }
+}
+
+impl Standard {
+    /// Parses a name as given to `--std=`, such as `c99` or `gnu11`.
+    pub fn parse(name: &str) -> Option<Self> {
+        let (gnu, year) = match name.strip_prefix("gnu") {
+            Some(year) => (true, year),
+            None => (false, name.strip_prefix('c')?),
+        };
+        let version = match year {
+            "89" | "90" => Version::C89,
+            "99" => Version::C99,
+            "11" => Version::C11,
+            "17" | "18" => Version::C17,
+            "23" | "2x" => Version::C23,
+            _ => return None,
+        };
+        Some(Self { version, gnu })
+    }
+
+    /// Whether implicit `int` and implicit function declarations are
+    /// allowed, as they were until C99.
+    pub fn implicit_int(self) -> bool {
+        self.version == Version::C89
+    }
+
+    /// Whether `()` declares a function without parameters, as `(void)`
+    /// does, which it only does from C23. Before, it says nothing about
+    /// them.
+    pub fn empty_parens_mean_void(self) -> bool {
+        self.version >= Version::C23
+    }
+
+    /// Rejects `feature`, found at `pos`, unless this standard is at least
+    /// `version`.
+    pub fn require(self, version: Version, feature: &str, pos: Position) -> Result<()> {
+        if self.version >= version {
+            Ok(())
+        } else {
+            Err(Error::new(
+                pos,
+                format!("{feature} is only valid in {} and later", version.name()),
+            ))
+        }
+    }
+}
+
+impl fmt::Display for Standard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefix = if self.gnu { "gnu" } else { "c" };
+        let year = &self.version.name()[1..];
+        write!(f, "{prefix}{year}")
+    }
+}
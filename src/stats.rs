@@ -0,0 +1,221 @@
+//! Counts of what the compiler made of one translation unit (`--stats`),
+//! for seeing where code size comes from and spotting regressions in it.
+
+use std::fmt;
+
+use crate::ast::{Enum, Expression, Initializer, Program, Statement};
+use crate::codegen::regalloc::{Allocation, RegisterSet};
+use crate::driver::{Artifacts, Options};
+use crate::opt::PassManager;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub tokens: usize,
+    /// Functions, statements, expressions and asm operands.
+    pub ast_nodes: usize,
+    /// One entry per function, in the order they are defined.
+    pub functions: Vec<FunctionStats>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionStats {
+    pub name: String,
+    /// IR instructions after optimization, not counting terminators.
+    pub ir_instructions: usize,
+    pub blocks: usize,
+    /// Values the register allocator put on the stack.
+    pub spills: usize,
+    /// Machine instructions in the final assembly, including inline asm.
+    pub instructions: usize,
+}
+
+impl Stats {
+    /// The statistics of `artifacts`, compiled with `options`.
+    pub fn collect(artifacts: &Artifacts, options: &Options) -> Self {
+        let backend = options.target.backend();
+        let registers = match backend {
+            Some(backend) if PassManager::for_level(options.opt_level).allocate_registers => {
+                backend.registers()
+            }
+            _ => RegisterSet::default(),
+        };
+        let mut functions: Vec<FunctionStats> = artifacts
+            .ir
+            .functions
+            .iter()
+            .map(|function| FunctionStats {
+                name: function.name.clone(),
+                ir_instructions: function.blocks.iter().map(|b| b.instrs.len()).sum(),
+                blocks: function.blocks.len(),
+                spills: Allocation::compute(function, registers).spills(),
+                instructions: 0,
+            })
+            .collect();
+
+        // WebAssembly text has no machine instructions to count.
+        if let Some(backend) = backend {
+            let comment = backend.syntax().comment_prefix();
+            let format = options.target.object_format();
+            let mut current = None;
+            for line in artifacts.assembly.lines() {
+                if let Some(label) = line.strip_suffix(':').filter(|_| !line.starts_with(' ')) {
+                    // Local labels are blocks of the current function; any other
+                    // symbol ends it.
+                    if !label.starts_with(format.local_label_prefix()) {
+                        current = functions
+                            .iter()
+                            .position(|f| format.symbol(&f.name) == label);
+                    }
+                    continue;
+                }
+                let text = line.trim_start();
+                let is_instruction = !text.is_empty()
+                    && line.starts_with(char::is_whitespace)
+                    && !text.starts_with('.')
+                    && !text.starts_with(comment);
+                if let (true, Some(index)) = (is_instruction, current) {
+                    functions[index].instructions += 1;
+                }
+            }
+        }
+
+        Self {
+            tokens: artifacts.tokens.len(),
+            ast_nodes: ast_nodes(&artifacts.program),
+            functions,
+        }
+    }
+}
+
+/// Functions, statements, expressions and asm operands in `program`.
+pub fn ast_nodes(program: &Program) -> usize {
+    fn expression(expr: &Expression) -> usize {
+        match expr {
+            Expression::IntLit { .. }
+            | Expression::CharLit { .. }
+            | Expression::StringLit { .. }
+            | Expression::Variable { .. }
+            | Expression::SizeofType { .. } => 1,
+            Expression::Unary { operand, .. }
+            | Expression::AddressOf { operand, .. }
+            | Expression::Deref { operand, .. }
+            | Expression::IncDec { operand, .. }
+            | Expression::SizeofExpr { operand, .. } => 1 + expression(operand),
+            Expression::Binary { .. } => {
+                let (first, links) = expr.binary_chain();
+                let rights = links.iter().map(|link| 1 + expression(&link.operands[1]));
+                expression(first) + rights.sum::<usize>()
+            }
+            Expression::Assign { operands, .. } => {
+                1 + operands.iter().map(expression).sum::<usize>()
+            }
+            Expression::FunctionCall { args, .. } | Expression::BuiltinCall { args, .. } => {
+                1 + args.iter().map(expression).sum::<usize>()
+            }
+        }
+    }
+    fn initializer(init: &Initializer) -> usize {
+        match init {
+            Initializer::Expr(expr) => expression(expr),
+            Initializer::List { elements, .. } => {
+                1 + elements.iter().map(initializer).sum::<usize>()
+            }
+        }
+    }
+    // The enum and each of its enumerators.
+    fn enumeration(decl: &Enum) -> usize {
+        1 + decl
+            .enumerators
+            .iter()
+            .map(|enumerator| 1 + enumerator.value.as_ref().map_or(0, expression))
+            .sum::<usize>()
+    }
+    fn statement(stmt: &Statement) -> usize {
+        match stmt {
+            Statement::Return { value, .. } => 1 + value.as_ref().map_or(0, expression),
+            Statement::Declaration { init, .. } => 1 + init.as_ref().map_or(0, initializer),
+            Statement::Expression { expr, .. } => 1 + expression(expr),
+            Statement::Enum(decl) => enumeration(decl),
+            Statement::Block { body, .. } => 1 + body.iter().map(statement).sum::<usize>(),
+            Statement::If {
+                cond,
+                then,
+                otherwise,
+                ..
+            } => 1 + expression(cond) + statement(then) + otherwise.as_deref().map_or(0, statement),
+            Statement::While { cond, body, .. } | Statement::DoWhile { body, cond, .. } => {
+                1 + expression(cond) + statement(body)
+            }
+            Statement::For {
+                init,
+                cond,
+                step,
+                body,
+                ..
+            } => {
+                1 + init.as_deref().map_or(0, statement)
+                    + cond.as_ref().map_or(0, expression)
+                    + step.as_ref().map_or(0, expression)
+                    + statement(body)
+            }
+            Statement::Switch { cond, body, .. } => 1 + expression(cond) + statement(body),
+            Statement::Case { value, body, .. } => 1 + expression(value) + statement(body),
+            Statement::Label { body, .. } | Statement::Default { body, .. } => 1 + statement(body),
+            Statement::Goto { .. } | Statement::Break { .. } | Statement::Empty { .. } => 1,
+            Statement::Asm {
+                outputs, inputs, ..
+            } => {
+                1 + outputs
+                    .iter()
+                    .chain(inputs)
+                    .map(|operand| 1 + expression(&operand.expr))
+                    .sum::<usize>()
+            }
+        }
+    }
+    program
+        .functions
+        .iter()
+        .map(|function| 1 + function.body.iter().flatten().map(statement).sum::<usize>())
+        .sum::<usize>()
+        + program.enums.iter().map(enumeration).sum::<usize>()
+        + program
+            .globals
+            .iter()
+            .map(|global| 1 + global.init.as_ref().map_or(0, initializer))
+            .sum::<usize>()
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "tokens:    {}", self.tokens)?;
+        writeln!(f, "AST nodes: {}", self.ast_nodes)?;
+        writeln!(
+            f,
+            "{:<20} {:>9} {:>7} {:>7} {:>13}",
+            "function", "IR instrs", "blocks", "spills", "instructions"
+        )?;
+        for function in &self.functions {
+            writeln!(
+                f,
+                "{:<20} {:>9} {:>7} {:>7} {:>13}",
+                function.name,
+                function.ir_instructions,
+                function.blocks,
+                function.spills,
+                function.instructions
+            )?;
+        }
+        let total =
+            |count: fn(&FunctionStats) -> usize| self.functions.iter().map(count).sum::<usize>();
+        writeln!(
+            f,
+            "{:<20} {:>9} {:>7} {:>7} {:>13}",
+            "total",
+            total(|function| function.ir_instructions),
+            total(|function| function.blocks),
+            total(|function| function.spills),
+            total(|function| function.instructions)
+        )
+    }
+}
@@ -0,0 +1,56 @@
+//! A ctags-compatible index of what a translation unit defines
+//! (`--emit=tags`), for editors to jump to definitions with.
+//!
+//! The index is in the extended format: one line per definition with its
+//! name, file, line and kind, sorted by name as the format's header says.
+
+use std::fmt::Write;
+
+use crate::ast::Program;
+
+/// The header lines of a sorted tags file.
+const HEADER: &str = "!_TAG_FILE_FORMAT\t2\t/extended format/\n\
+                      !_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/\n\
+                      !_TAG_PROGRAM_NAME\trcc\t//\n";
+
+/// One definition: its name, line and ctags kind letter.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Tag {
+    pub name: String,
+    pub line: u32,
+    /// `f` for a function.
+    pub kind: char,
+    /// The type the definition has, such as a function's return type.
+    pub type_name: String,
+}
+
+/// The definitions at file scope in `program`, sorted by name.
+pub fn collect(program: &Program) -> Vec<Tag> {
+    let mut tags: Vec<Tag> = program
+        .functions
+        .iter()
+        .filter(|function| function.body.is_some())
+        .map(|function| Tag {
+            name: function.name.clone(),
+            line: function.pos.line,
+            kind: 'f',
+            type_name: function.return_type.to_string(),
+        })
+        .collect();
+    tags.sort();
+    tags
+}
+
+/// The tags file for `program`, compiled from `file`.
+pub fn emit(program: &Program, file: &str) -> String {
+    let mut out = HEADER.to_string();
+    for tag in collect(program) {
+        writeln!(
+            out,
+            "{}\t{file}\t{};\"\t{}\ttyperef:typename:{}",
+            tag.name, tag.line, tag.kind, tag.type_name
+        )
+        .unwrap();
+    }
+    out
+}
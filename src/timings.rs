@@ -0,0 +1,135 @@
+//! How long each compiler stage takes, how much memory it uses and how
+//! much it produces (`--timings`, or `--time-passes`).
+//!
+//! Memory is counted by [`Counting`], which wraps the system allocator and
+//! has to be installed as the program's global allocator; without it every
+//! stage reports a peak of zero.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+/// The system allocator, keeping track of the bytes in use and their peak.
+pub struct Counting;
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            grow(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new = System.realloc(ptr, layout, new_size);
+        if !new.is_null() {
+            CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+            grow(new_size);
+        }
+        new
+    }
+}
+
+fn grow(size: usize) {
+    let current = CURRENT.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK.fetch_max(current, Ordering::Relaxed);
+}
+
+/// One stage's measurements.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stage {
+    pub name: &'static str,
+    pub time: Duration,
+    /// The most heap memory in use at once during the stage, in bytes.
+    pub peak_memory: usize,
+    /// How much the stage produced, such as `(42, "tokens")`, if it
+    /// produces something countable.
+    pub output: Option<(usize, &'static str)>,
+}
+
+/// The stages of one compilation, in the order they ran.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Timings {
+    pub stages: Vec<Stage>,
+}
+
+impl Timings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `f` as the stage `name`, recording how long it took and its
+    /// peak memory.
+    pub fn time<T>(&mut self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        PEAK.store(CURRENT.load(Ordering::Relaxed), Ordering::Relaxed);
+        let start = Instant::now();
+        let value = f();
+        self.stages.push(Stage {
+            name,
+            time: start.elapsed(),
+            peak_memory: PEAK.load(Ordering::Relaxed),
+            output: None,
+        });
+        value
+    }
+
+    /// Records that the stage timed last produced `count` of `what`.
+    pub fn count(&mut self, count: usize, what: &'static str) {
+        if let Some(stage) = self.stages.last_mut() {
+            stage.output = Some((count, what));
+        }
+    }
+
+    pub fn total(&self) -> Duration {
+        self.stages.iter().map(|stage| stage.time).sum()
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<10} {:>12} {:>14}   output",
+            "stage", "time", "peak memory"
+        )?;
+        for stage in &self.stages {
+            write!(
+                f,
+                "{:<10} {:>9.3} ms {:>10.1} KiB",
+                stage.name,
+                stage.time.as_secs_f64() * 1e3,
+                stage.peak_memory as f64 / 1024.0
+            )?;
+            match stage.output {
+                Some((count, what)) => writeln!(f, "   {count} {what}")?,
+                None => writeln!(f)?,
+            }
+        }
+        let peak = self.stages.iter().map(|stage| stage.peak_memory).max();
+        writeln!(
+            f,
+            "{:<10} {:>9.3} ms {:>10.1} KiB",
+            "total",
+            self.total().as_secs_f64() * 1e3,
+            peak.unwrap_or(0) as f64 / 1024.0
+        )
+    }
+}
@@ -0,0 +1,205 @@
+//! The system tools that turn generated assembly into object files and
+//! executables.
+//!
+//! rcc has no assembler or linker of its own; it drives the C compiler
+//! (`cc`, or whatever `RCC_CC` names), which knows where the platform's
+//! assembler, startup files and libc are.
+//! When compiling for a target other than the host, the target's triple is
+//! passed with `--target`, which clang understands.
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::codegen::Target;
+
+pub struct Toolchain {
+    cc: String,
+    target: Target,
+    /// Extra arguments for linking, such as `-lm`.
+    link_args: Vec<String>,
+}
+
+impl Toolchain {
+    /// The toolchain for `target`, using `RCC_CC` if it is set and `cc`
+    /// otherwise.
+    pub fn new(target: Target) -> Self {
+        Self {
+            cc: std::env::var("RCC_CC").unwrap_or_else(|_| "cc".to_string()),
+            target,
+            link_args: Vec::new(),
+        }
+    }
+
+    pub fn with_cc(mut self, cc: impl Into<String>) -> Self {
+        self.cc = cc.into();
+        self
+    }
+
+    pub fn with_link_args(mut self, args: Vec<String>) -> Self {
+        self.link_args = args;
+        self
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.cc);
+        if Target::host() != Some(self.target) {
+            command.arg(format!("--target={}", self.target.triple()));
+        }
+        command
+    }
+
+    /// Assembles the file `asm` into the object file `object`.
+    pub fn assemble(&self, asm: &Path, object: &Path) -> Result<(), String> {
+        let mut command = self.command();
+        command.arg("-c").arg(asm).arg("-o").arg(object);
+        self.run(command)
+    }
+
+    /// Links `inputs`, assembly or object files, into the executable
+    /// `output`, with the C runtime's startup files and libc.
+    pub fn link(&self, inputs: &[PathBuf], output: &Path) -> Result<(), String> {
+        let mut command = self.command();
+        command
+            .args(inputs)
+            .args(&self.link_args)
+            .arg("-o")
+            .arg(output);
+        self.run(command)
+    }
+
+    /// Links `assembly`, generated from `input`, into a temporary
+    /// executable and runs it.
+    pub fn run_assembly(&self, input: &Path, assembly: &str) -> Result<Execution, String> {
+        let asm = temp_path(input, "s");
+        let exe = temp_path(input, "out");
+        let result = fs::write(&asm, assembly)
+            .map_err(|err| format!("cannot write `{}`: {err}", asm.display()))
+            .and_then(|()| self.link(std::slice::from_ref(&asm), &exe))
+            .and_then(|()| execute(&exe));
+        let _ = fs::remove_file(&asm);
+        let _ = fs::remove_file(&exe);
+        result
+    }
+
+    fn run(&self, mut command: Command) -> Result<(), String> {
+        let output = command
+            .output()
+            .map_err(|err| format!("cannot run `{}`: {err}", self.cc))?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "`{}` failed:\n{}",
+                self.cc,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            ))
+        }
+    }
+}
+
+/// A path in the system's temporary directory for an intermediate file
+/// made from `input`, unique within this process.
+pub fn temp_path(input: &Path, extension: &str) -> PathBuf {
+    static COUNT: AtomicU32 = AtomicU32::new(0);
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    let n = COUNT.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("rcc-{}-{n}-{stem}.{extension}", std::process::id()))
+}
+
+/// A directory of intermediate files, removed along with them when
+/// dropped.
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+    count: AtomicU32,
+}
+
+impl TempDir {
+    /// Creates a fresh directory in the system's temporary directory.
+    pub fn new() -> Result<Self, String> {
+        let path = temp_path(Path::new("build"), "d");
+        fs::create_dir(&path)
+            .map_err(|err| format!("cannot create `{}`: {err}", path.display()))?;
+        Ok(Self {
+            path,
+            count: AtomicU32::new(0),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// A path in the directory for an intermediate file made from `input`,
+    /// unique even between inputs with the same name.
+    pub fn file(&self, input: &Path, extension: &str) -> PathBuf {
+        let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+        let n = self.count.fetch_add(1, Ordering::Relaxed);
+        self.path.join(format!("{n}-{stem}.{extension}"))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// How long a program may run before it counts as hung.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How a program run ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Execution {
+    /// The exit status, or `None` if a signal ended it.
+    pub status: Option<i32>,
+    pub stdout: String,
+}
+
+impl std::fmt::Display for Execution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.status {
+            Some(code) => write!(f, "exit {code}")?,
+            None => f.write_str("killed by a signal")?,
+        }
+        write!(f, ", stdout {:?}", self.stdout)
+    }
+}
+
+/// Runs `exe`, killing it if it takes longer than ten seconds.
+pub fn execute(exe: &Path) -> Result<Execution, String> {
+    let mut child = Command::new(exe)
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("cannot run `{}`: {err}", exe.display()))?;
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let reader = thread::spawn(move || {
+        let mut output = Vec::new();
+        stdout.read_to_end(&mut output).map(|_| output)
+    });
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|err| err.to_string())? {
+            break status;
+        }
+        if start.elapsed() > TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("timed out after {}s", TIMEOUT.as_secs()));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    let output = reader
+        .join()
+        .expect("reader thread does not panic")
+        .map_err(|err| err.to_string())?;
+    Ok(Execution {
+        status: status.code(),
+        stdout: String::from_utf8_lossy(&output).into_owned(),
+    })
+}
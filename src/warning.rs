@@ -0,0 +1,184 @@
+//! Warnings: diagnostics about code that compiles but is probably wrong,
+//! each turned on and off by a `-W` flag.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::error::Error;
+use crate::lexer::Position;
+
+/// A kind of warning, named by its `-W` flag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Warning {
+    /// A local variable that is declared but never used.
+    UnusedVariable,
+    /// A label that no `goto` jumps to.
+    UnusedLabel,
+    /// A statement that follows a `return` or `goto` with no label in
+    /// between, so it never runs.
+    UnreachableCode,
+    /// A function returning a value whose end control can reach, so that
+    /// it returns without one.
+    ReturnType,
+}
+
+impl Warning {
+    pub const ALL: [Warning; 4] = [
+        Warning::UnusedVariable,
+        Warning::UnusedLabel,
+        Warning::UnreachableCode,
+        Warning::ReturnType,
+    ];
+
+    /// The name in the flag, as in `-Wunused-variable`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Warning::UnusedVariable => "unused-variable",
+            Warning::UnusedLabel => "unused-label",
+            Warning::UnreachableCode => "unreachable-code",
+            Warning::ReturnType => "return-type",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|warning| warning.name() == name)
+    }
+
+    /// Whether `-Wall` turns it on. As in GCC and Clang, it leaves out
+    /// `unreachable-code`, which is noisy in code written to be portable.
+    fn in_all(self) -> bool {
+        self != Warning::UnreachableCode
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "-W{}", self.name())
+    }
+}
+
+/// One warning about the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub warning: Warning,
+    pub pos: Position,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(warning: Warning, pos: Position, message: impl Into<String>) -> Self {
+        Self {
+            warning,
+            pos,
+            message: message.into(),
+        }
+    }
+
+    /// The warning as an [`Error`] to render, its message naming the flag
+    /// that controls it, or `-Werror=` if it is treated as an error.
+    pub fn to_error(&self, as_error: bool) -> Error {
+        let flag = if as_error {
+            format!("-Werror={}", self.warning.name())
+        } else {
+            self.warning.to_string()
+        };
+        Error::new(self.pos, format!("{} [{flag}]", self.message))
+    }
+}
+
+/// Which warnings to report and which of them are errors, as the `-W`
+/// flags set them. Every warning is off by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Warnings {
+    enabled: BTreeSet<Warning>,
+    /// The warnings that `-Werror=` makes errors even without `-Werror`.
+    errors: BTreeSet<Warning>,
+    /// The warnings that `-Wno-error=` keeps warnings under `-Werror`.
+    not_errors: BTreeSet<Warning>,
+    /// Whether every warning is an error (`-Werror`).
+    all_errors: bool,
+    /// Whether every warning is off (`-w`).
+    suppressed: bool,
+}
+
+impl Warnings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies the flag `-W{option}`: `all`, `error`, a warning's name, or
+    /// any of those after `no-`, or `error=` or `no-error=` and a name.
+    /// Returns false for an option that names no warning rcc has.
+    pub fn apply(&mut self, option: &str) -> bool {
+        let (on, option) = match option.strip_prefix("no-") {
+            Some(option) => (false, option),
+            None => (true, option),
+        };
+        if option == "all" {
+            for warning in Warning::ALL.into_iter().filter(|w| w.in_all()) {
+                self.set(warning, on);
+            }
+            return true;
+        }
+        if option == "error" {
+            self.all_errors = on;
+            return true;
+        }
+        if let Some(name) = option.strip_prefix("error=") {
+            let Some(warning) = Warning::parse(name) else {
+                return false;
+            };
+            // `-Werror=` turns the warning on too; `-Wno-error=` doesn't.
+            if on {
+                self.enabled.insert(warning);
+                self.errors.insert(warning);
+                self.not_errors.remove(&warning);
+            } else {
+                self.errors.remove(&warning);
+                self.not_errors.insert(warning);
+            }
+            return true;
+        }
+        match Warning::parse(option) {
+            Some(warning) => {
+                self.set(warning, on);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn set(&mut self, warning: Warning, on: bool) {
+        if on {
+            self.enabled.insert(warning);
+        } else {
+            self.enabled.remove(&warning);
+        }
+    }
+
+    /// Turns every warning off, whatever the other flags say (`-w`).
+    pub fn suppress(&mut self) {
+        self.suppressed = true;
+    }
+
+    /// Turns `warning` on, as `-W<name>` does.
+    pub fn enable(mut self, warning: Warning) -> Self {
+        self.set(warning, true);
+        self
+    }
+
+    /// Makes every warning an error, as `-Werror` does.
+    pub fn as_errors(mut self) -> Self {
+        self.all_errors = true;
+        self
+    }
+
+    pub fn is_enabled(&self, warning: Warning) -> bool {
+        !self.suppressed && self.enabled.contains(&warning)
+    }
+
+    /// Whether `warning`, if reported, fails the compilation.
+    pub fn is_error(&self, warning: Warning) -> bool {
+        self.errors.contains(&warning) || self.all_errors && !self.not_errors.contains(&warning)
+    }
+}
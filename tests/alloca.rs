@@ -0,0 +1,140 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use rcc::codegen::aarch64::Aarch64;
+use rcc::codegen::x86_64::X86_64;
+use rcc::codegen::{Backend, CodeGenerator};
+use rcc::ir::interp::Interpreter;
+use rcc::ir::verify::verify_module;
+use rcc::ir::{BinOp, FunctionBuilder, IrType, Module};
+
+use common::assert_assembles;
+
+/// `main` makes allocations of 1, 20 and 33 bytes and passes each to
+/// `check`, a C function, returning the sum of what it returns.
+fn allocations() -> Module {
+    let mut b = FunctionBuilder::new("main", Some(IrType::I32));
+    let mut failures = b.iconst(IrType::I32, 0);
+    for bytes in [1, 20, 33] {
+        let size = b.iconst(IrType::I32, bytes);
+        let block = b.alloca(size);
+        let result = b
+            .call("check", vec![block, size], Some(IrType::I32))
+            .unwrap();
+        failures = b.binary(BinOp::Add, failures, result);
+    }
+    b.ret(Some(failures));
+    let mut module = Module::default();
+    module.functions.push(b.finish());
+    module
+}
+
+fn generate(backend: &dyn Backend) -> String {
+    CodeGenerator::new()
+        .with_backend(backend)
+        .with_register_allocation(true)
+        .with_omit_frame_pointer(true)
+        .generate(&allocations())
+}
+
+#[test]
+fn prints_and_verifies_allocas() {
+    let module = allocations();
+    verify_module(&module).unwrap();
+    let text = module.to_string();
+    assert!(text.contains("%2 = alloca ptr %1\n"), "{text}");
+}
+
+#[test]
+fn the_interpreter_aligns_and_releases_allocations() {
+    let mut b = FunctionBuilder::new("grab", Some(IrType::Ptr));
+    let size = b.param(IrType::I32);
+    let block = b.alloca(size);
+    b.ret(Some(block));
+    let mut module = Module::default();
+    module.functions.push(b.finish());
+
+    let mut interp = Interpreter::new(&module);
+    let first = interp.call("grab", &[20]).unwrap().unwrap();
+    assert_eq!(first % 16, 0);
+    // The first frame's block was released when it returned.
+    assert_eq!(interp.call("grab", &[20]), Ok(Some(first)));
+    assert!(interp.call("grab", &[-1]).is_err());
+}
+
+#[test]
+fn aarch64_rounds_allocations_to_sixteen_bytes() {
+    let asm = generate(&Aarch64::LINUX);
+    assert!(
+        asm.contains("add x9, x9, #15\n    and x9, x9, #-16\n    sub sp, sp, x9\n"),
+        "{asm}"
+    );
+    // The stack pointer is restored from the frame pointer on return.
+    assert!(
+        asm.contains("mov sp, x29\n    ldp x29, x30, [sp], #16\n"),
+        "{asm}"
+    );
+    assert_assembles("aarch64-linux-gnu", &asm);
+}
+
+#[test]
+fn leaves_with_allocations_keep_the_frame_pointer() {
+    let mut b = FunctionBuilder::new("leaf", None);
+    let size = b.iconst(IrType::I32, 8);
+    b.alloca(size);
+    b.ret(None);
+    let mut module = Module::default();
+    module.functions.push(b.finish());
+    let asm = CodeGenerator::new()
+        .with_backend(&X86_64::LINUX)
+        .with_register_allocation(true)
+        .with_omit_frame_pointer(true)
+        .generate(&module);
+    assert!(asm.contains("pushq %rbp\n"), "{asm}");
+    assert!(asm.contains("leave\n    ret\n"), "{asm}");
+}
+
+const CHECK: &str = r#"
+#include <stdint.h>
+#include <string.h>
+
+int check(char *block, int size) {
+    memset(block, 0xff, size);
+    int misaligned = (uintptr_t)block % 16 != 0;
+    /* The frame address is 16 below the stack pointer at the call. */
+    misaligned += (uintptr_t)__builtin_frame_address(0) % 16 != 0;
+    return misaligned;
+}
+"#;
+
+#[test]
+fn x86_allocations_keep_calls_aligned() {
+    let asm = generate(&X86_64::LINUX);
+    assert!(
+        asm.contains("addq $15, %rax\n    andq $-16, %rax\n    subq %rax, %rsp\n"),
+        "{asm}"
+    );
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("rcc-alloca-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (source, check, exe) = (dir.join("main.s"), dir.join("check.c"), dir.join("main"));
+    fs::write(&source, &asm).unwrap();
+    fs::write(&check, CHECK).unwrap();
+    let Ok(linked) = Command::new("cc")
+        .arg(&source)
+        .arg(&check)
+        .arg("-o")
+        .arg(&exe)
+        .status()
+    else {
+        return;
+    };
+    assert!(linked.success());
+    let status = Command::new(&exe).status().unwrap();
+    assert_eq!(status.code(), Some(0), "{asm}");
+    fs::remove_dir_all(&dir).unwrap();
+}
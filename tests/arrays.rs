@@ -0,0 +1,185 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use rcc::codegen::Target;
+use rcc::driver::Options;
+use rcc::ir::llvm;
+use rcc::opt::OptLevel;
+
+use common::{assert_assembles, compile, run_main};
+
+/// Sums a partly initialized array through a pointer parameter, and reads
+/// a two-dimensional array and one large enough to be zeroed in a loop.
+const ARRAYS: &str = "int sum(int *a, int n) {
+    int s = 0;
+    int i;
+    for (i = 0; i < n; i = i + 1) s = s + a[i];
+    return s;
+}
+int main() {
+    int a[5] = {1, 2, 3};
+    int m[2][3] = {{1, 2, 3}, {4, 5, 6}};
+    int big[100] = {1};
+    int i;
+    for (i = 1; i < 100; i = i + 1) big[i] = big[i - 1] + i;
+    a[4] = sum(a, 3);
+    return a[4] + a[3] + m[1][2] + big[99] % 100 + big[50];
+}";
+
+#[test]
+fn arrays_are_indexed_and_initialized() {
+    let cases = [
+        (ARRAYS, 6 + 6 + 51 + 1276),
+        (
+            "int main() { int a[3]; a[0] = 4; a[2] = 5; return a[0] * a[2]; }",
+            20,
+        ),
+        (
+            "int main() { int a[] = {1, 2, 3,}; return a[2] - a[0]; }",
+            2,
+        ),
+        (
+            "int main() { int a[4] = {7}; return a[0] + a[1] + a[3]; }",
+            7,
+        ),
+        (
+            "int main() { int a[3] = {1, 2, 3}; int *p = a; return *(p + 1); }",
+            2,
+        ),
+        ("int main() { int a[3] = {1, 2, 3}; return 2[a]; }", 3),
+        ("int main() { int a[3]; return &a[2] - a; }", 2),
+        (
+            "int main() { int m[2][2] = {{1}, {2, 3}}; return m[0][1] + m[1][0]; }",
+            2,
+        ),
+        (
+            "int f(int a[], int m[][2]) { return a[1] + m[1][1]; }
+          int main() { int a[2] = {0, 5}; int m[2][2] = {{0, 0}, {0, 6}}; return f(a, m); }",
+            11,
+        ),
+        ("int main() { return \"abc\"[1]; }", 98),
+    ];
+    for (source, expected) in cases {
+        for level in [OptLevel::O0, OptLevel::O2] {
+            assert_eq!(
+                run_main(source, level),
+                Some(expected),
+                "{source} at {level:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn array_declarations_are_checked() {
+    let cases = [
+        (
+            "int main() { int a[2] = {1, 2, 3}; return 0; }",
+            "1:25: excess elements in array initializer",
+        ),
+        (
+            "int main() { int a[2]; int b[2]; a = b; return 0; }",
+            "1:34: array type `int[2]` is not assignable",
+        ),
+        (
+            "int main() { int a[2] = 1; return 0; }",
+            "1:25: expected `int[2]` but found `int`",
+        ),
+        (
+            "int main() { int x = {1}; return x; }",
+            "1:22: cannot initialize `int` with an initializer list",
+        ),
+        (
+            "int main() { int a[]; return 0; }",
+            "1:18: array `a` needs an explicit size or an initializer list",
+        ),
+        (
+            "int main() { int a[0]; return 0; }",
+            "1:20: array size must be positive",
+        ),
+        (
+            "int main() { void a[2]; return 0; }",
+            "1:19: array `a` has incomplete element type `void`",
+        ),
+        (
+            "int main() { int a[2]; int *p = &a; return 0; }",
+            "1:33: expected `int *` but found `int (*)[2]`",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(
+            compile(source, &Options::default()).unwrap_err(),
+            expected,
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn arrays_lower_to_array_slots() {
+    let ir = compile(
+        "int main() { int a[4] = {1, 2}; return a[1]; }",
+        &Options::default(),
+    )
+    .unwrap()
+    .ir;
+    let text = ir.to_string();
+    for line in [
+        "ss0 = stack_slot [4 x i32]\n",
+        "= addr ptr ss0\n",
+        "= load i32 [%",
+    ] {
+        assert!(text.contains(line), "{line} in {text}");
+    }
+    let ll = llvm::emit(&ir);
+    assert!(ll.contains("alloca [4 x i32]"), "{ll}");
+}
+
+#[test]
+fn array_code_assembles_for_aarch64() {
+    // A frame too large for an immediate `sub sp` offset.
+    let source = "int main() { int a[2000]; a[1999] = 3; return a[1999]; }";
+    for (target, triple) in [
+        (Target::AARCH64_LINUX, "aarch64-linux-gnu"),
+        (Target::AARCH64_APPLE, "arm64-apple-macos"),
+    ] {
+        for source in [ARRAYS, source] {
+            let asm = compile(
+                source,
+                &Options {
+                    target,
+                    ..Options::default()
+                },
+            )
+            .unwrap()
+            .assembly;
+            assert_assembles(triple, &asm);
+        }
+    }
+}
+
+#[test]
+fn x86_program_indexes_arrays() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let options = Options {
+        target: Target::X86_64_LINUX,
+        ..Options::default()
+    };
+    let asm = compile(ARRAYS, &options).unwrap().assembly;
+    let dir = std::env::temp_dir().join(format!("rcc-arrays-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (source, exe) = (dir.join("arrays.s"), dir.join("arrays"));
+    fs::write(&source, &asm).unwrap();
+    let Ok(linked) = Command::new("cc").arg(&source).arg("-o").arg(&exe).status() else {
+        return;
+    };
+    assert!(linked.success(), "{asm}");
+    let output = Command::new(&exe).output().unwrap();
+    // The exit status keeps the low byte of 1339.
+    assert_eq!(output.status.code(), Some(1339 % 256));
+    fs::remove_dir_all(&dir).unwrap();
+}
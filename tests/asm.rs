@@ -0,0 +1,272 @@
+use rcc::codegen::aarch64::Aarch64;
+use rcc::codegen::asm::{
+    imm, mem, reg, sym, Address, Assembly, Instruction, Line, Operand, Syntax,
+};
+use rcc::codegen::x86_64::X86_64;
+use rcc::codegen::CodeGenerator;
+use rcc::ir::{BinOp, FunctionBuilder, IrType, Module};
+
+fn printed(syntax: Syntax, instruction: Instruction) -> String {
+    let mut asm = Assembly::new(syntax);
+    asm.instruction(instruction);
+    asm.to_string()
+}
+
+fn add_one() -> Module {
+    let mut f = FunctionBuilder::new("inc", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let one = f.iconst(IrType::I32, 1);
+    let sum = f.binary(BinOp::Add, x, one);
+    f.ret(Some(sum));
+    Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    }
+}
+
+#[test]
+fn prints_operands_in_arm_syntax() {
+    let cases = [
+        (Instruction::new("ret", vec![]), "    ret\n"),
+        (
+            Instruction::new("add", vec![reg("w8"), reg("w9"), imm(-4)]),
+            "    add w8, w9, #-4\n",
+        ),
+        (
+            Instruction::new("ldr", vec![reg("w8"), mem("x29", -12)]),
+            "    ldr w8, [x29, #-12]\n",
+        ),
+        (
+            Instruction::new(
+                "movk",
+                vec![
+                    reg("w8"),
+                    Operand::ShiftedImm {
+                        value: 0x1234,
+                        shift: 16,
+                    },
+                ],
+            ),
+            "    movk w8, #0x1234, lsl #16\n",
+        ),
+        (
+            Instruction::new(
+                "ldrsw",
+                vec![
+                    reg("x16"),
+                    Operand::Mem(Address::Indexed {
+                        base: "x9".to_string(),
+                        index: "w8".to_string(),
+                        scale: 4,
+                    }),
+                ],
+            ),
+            "    ldrsw x16, [x9, w8, uxtw #2]\n",
+        ),
+        (
+            Instruction::new(
+                "ldp",
+                vec![
+                    reg("x29"),
+                    reg("x30"),
+                    Operand::Mem(Address::PostIndex {
+                        base: "sp".to_string(),
+                        offset: 16,
+                    }),
+                ],
+            ),
+            "    ldp x29, x30, [sp], #16\n",
+        ),
+    ];
+    for (instruction, expected) in cases {
+        assert_eq!(printed(Syntax::Arm, instruction), expected);
+    }
+}
+
+#[test]
+fn prints_operands_in_att_syntax() {
+    let cases = [
+        (
+            Instruction::new("addl", vec![imm(5), reg("%eax")]),
+            "    addl $5, %eax\n",
+        ),
+        (
+            Instruction::new("movl", vec![mem("%rbp", -8), reg("%ecx")]),
+            "    movl -8(%rbp), %ecx\n",
+        ),
+        (
+            Instruction::new(
+                "leaq",
+                vec![
+                    Operand::Mem(Address::Symbol {
+                        base: "%rip".to_string(),
+                        symbol: "x".to_string(),
+                    }),
+                    reg("%rax"),
+                ],
+            ),
+            "    leaq x(%rip), %rax\n",
+        ),
+        (
+            Instruction::new("jmp", vec![Operand::Indirect("%rdx".to_string())]),
+            "    jmp *%rdx\n",
+        ),
+        (
+            Instruction::new("call", vec![sym("f@PLT")]),
+            "    call f@PLT\n",
+        ),
+    ];
+    for (instruction, expected) in cases {
+        assert_eq!(printed(Syntax::Att, instruction), expected);
+    }
+}
+
+#[test]
+fn prints_operands_in_intel_syntax() {
+    let cases = [
+        (
+            Instruction::new("addl", vec![imm(5), reg("%eax")]),
+            "    add eax, 5\n",
+        ),
+        (
+            Instruction::new("movl", vec![mem("%rbp", -8), reg("%ecx")]),
+            "    mov ecx, dword ptr [rbp - 8]\n",
+        ),
+        (
+            Instruction::new("movq", vec![reg("%rdi"), mem("%rsp", 0)]),
+            "    mov qword ptr [rsp], rdi\n",
+        ),
+        (
+            Instruction::new("imull", vec![imm(6), reg("%eax"), reg("%ecx")]),
+            "    imul ecx, eax, 6\n",
+        ),
+        (
+            Instruction::new(
+                "leaq",
+                vec![
+                    Operand::Mem(Address::Symbol {
+                        base: "%rip".to_string(),
+                        symbol: "x".to_string(),
+                    }),
+                    reg("%rax"),
+                ],
+            ),
+            "    lea rax, [rip + x]\n",
+        ),
+        (
+            Instruction::new(
+                "movslq",
+                vec![
+                    Operand::Mem(Address::Indexed {
+                        base: "%rax".to_string(),
+                        index: "%rcx".to_string(),
+                        scale: 4,
+                    }),
+                    reg("%rdx"),
+                ],
+            ),
+            "    movsxd rdx, dword ptr [rax + rcx*4]\n",
+        ),
+        (
+            Instruction::new(
+                "movq",
+                vec![
+                    Operand::Mem(Address::Segment {
+                        segment: "%fs".to_string(),
+                        offset: 40,
+                    }),
+                    reg("%rax"),
+                ],
+            ),
+            "    mov rax, qword ptr fs:[40]\n",
+        ),
+        (
+            Instruction::new("movzbl", vec![reg("%al"), reg("%eax")]),
+            "    movzx eax, al\n",
+        ),
+        (Instruction::new("cltd", vec![]), "    cdq\n"),
+        (Instruction::new("setl", vec![reg("%al")]), "    setl al\n"),
+        (
+            Instruction::new("shll", vec![reg("%cl"), reg("%eax")]),
+            "    shl eax, cl\n",
+        ),
+        (
+            Instruction::new("jmp", vec![Operand::Indirect("%rdx".to_string())]),
+            "    jmp rdx\n",
+        ),
+        (
+            Instruction::new("call", vec![sym("f@PLT")]),
+            "    call f@PLT\n",
+        ),
+    ];
+    for (instruction, expected) in cases {
+        let printed = printed(Syntax::Intel, instruction);
+        assert_eq!(printed, format!("    .intel_syntax noprefix\n{expected}"));
+    }
+}
+
+#[test]
+fn intel_output_switches_to_att_around_inline_assembly() {
+    let mut asm = Assembly::new(Syntax::Intel);
+    asm.instruction(Instruction::new("pushq", vec![reg("%rbp")]));
+    asm.lines
+        .push(Line::Verbatim("movl %edi, %eax".to_string()));
+    asm.lines.push(Line::Verbatim("nop".to_string()));
+    asm.instruction(Instruction::new("ret", vec![]));
+    assert_eq!(
+        asm.to_string(),
+        "    .intel_syntax noprefix\n    push rbp\n    .att_syntax prefix\n\tmovl %edi, %eax\n\tnop\n    .intel_syntax noprefix\n    ret\n"
+    );
+}
+
+#[test]
+fn prints_labels_directives_and_comments() {
+    let mut asm = Assembly::new(Syntax::Att);
+    asm.directive(".text");
+    asm.blank();
+    asm.label("main");
+    asm.lines.push(Line::Comment("1: int main".to_string()));
+    asm.lines.push(Line::Verbatim("nop".to_string()));
+    assert_eq!(
+        asm.to_string(),
+        "    .text\n\nmain:\n    # 1: int main\n\tnop\n"
+    );
+}
+
+#[test]
+fn generated_code_can_be_inspected_without_parsing_text() {
+    let module = add_one();
+    let asm = CodeGenerator::new()
+        .with_backend(&Aarch64::APPLE)
+        .with_register_allocation(true)
+        .generate_assembly(&module);
+    // AArch64 adds registers, so the constant is materialized first.
+    let materialized = Instruction::new("mov", vec![reg("w9"), imm(1)]);
+    assert!(asm.instructions().any(|i| *i == materialized), "{asm}");
+    let add = asm
+        .instructions()
+        .find(|instruction| instruction.mnemonic == "add")
+        .expect("an add instruction");
+    assert_eq!(add.operands.last(), Some(&reg("w9")));
+
+    let asm = CodeGenerator::new()
+        .with_backend(&X86_64::LINUX)
+        .with_register_allocation(true)
+        .generate_assembly(&module);
+    assert_eq!(asm.syntax, Syntax::Att);
+    let add = asm
+        .instructions()
+        .find(|instruction| instruction.mnemonic == "addl")
+        .expect("an addl instruction");
+    assert_eq!(add.operands.first(), Some(&imm(1)));
+}
+
+#[test]
+fn generate_prints_the_typed_assembly() {
+    let module = add_one();
+    let generator = CodeGenerator::new().with_backend(&X86_64::APPLE);
+    assert_eq!(
+        generator.generate(&module),
+        generator.generate_assembly(&module).to_string()
+    );
+}
@@ -0,0 +1,149 @@
+use rcc::ast::{BinaryOp, Expression, Statement};
+use rcc::lexer::{IntSuffix, Lexer, Position};
+use rcc::parser::Parser;
+
+/// The expression `source` with every operator parenthesized, to show how
+/// it grouped.
+fn grouping(source: &str) -> Result<String, String> {
+    let source = format!("int main() {{ return {source}; }}");
+    let tokens = Lexer::new(&source).lex().map_err(|err| err.to_string())?;
+    let program = Parser::new(tokens).parse().map_err(|err| err.to_string())?;
+    let [Statement::Return {
+        value: Some(value), ..
+    }] = program.functions[0].body.as_deref().unwrap()
+    else {
+        panic!("expected one return in {source}");
+    };
+    Ok(parenthesize(value))
+}
+
+fn parenthesize(expr: &Expression) -> String {
+    match expr {
+        Expression::IntLit { value, .. } => value.to_string(),
+        Expression::Variable { name, .. } => name.clone(),
+        Expression::Unary { op, operand, .. } => {
+            format!("({}{})", op.as_str(), parenthesize(operand))
+        }
+        Expression::Deref { operand, .. } => format!("(*{})", parenthesize(operand)),
+        Expression::AddressOf { operand, .. } => format!("(&{})", parenthesize(operand)),
+        Expression::Binary { op, operands, .. } => format!(
+            "({} {} {})",
+            parenthesize(&operands[0]),
+            op.as_str(),
+            parenthesize(&operands[1])
+        ),
+        Expression::Assign { op, operands, .. } => format!(
+            "({} {}= {})",
+            parenthesize(&operands[0]),
+            op.map_or("", |op| op.as_str()),
+            parenthesize(&operands[1])
+        ),
+        other => panic!("unexpected {other:?}"),
+    }
+}
+
+#[test]
+fn binary_operands_are_left_then_right() {
+    let tokens = Lexer::new("int main() { return 1 - 2 * 3; }")
+        .lex()
+        .unwrap();
+    let program = Parser::new(tokens).parse().unwrap();
+    let [Statement::Return {
+        value: Some(Expression::Binary { op, operands, .. }),
+        ..
+    }] = program.functions[0].body.as_deref().unwrap()
+    else {
+        panic!("expected one return, found {:?}", program.functions[0].body);
+    };
+    assert_eq!(*op, BinaryOp::Sub);
+    assert_eq!(
+        operands[0],
+        Expression::IntLit {
+            value: 1,
+            suffix: IntSuffix::Plain,
+            pos: Position {
+                line: 1,
+                column: 21
+            }
+        }
+    );
+    assert_eq!(
+        operands[1].pos(),
+        Position {
+            line: 1,
+            column: 27
+        }
+    );
+    assert!(matches!(
+        &operands[1],
+        Expression::Binary {
+            op: BinaryOp::Mul,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn binary_operators_group_to_the_left() {
+    let cases = [
+        ("a - b - c", "((a - b) - c)"),
+        ("a / b * c % d", "(((a / b) * c) % d)"),
+        ("a << b >> c", "((a << b) >> c)"),
+        ("a < b >= c", "((a < b) >= c)"),
+        ("a == b != c", "((a == b) != c)"),
+        ("a & b & c", "((a & b) & c)"),
+        ("a && b && c", "((a && b) && c)"),
+        ("a || b || c", "((a || b) || c)"),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(grouping(source).as_deref(), Ok(expected), "{source}");
+    }
+}
+
+#[test]
+fn assignments_group_to_the_right() {
+    let cases = [
+        ("a = b = c", "(a = (b = c))"),
+        ("a += b -= c", "(a += (b -= c))"),
+        ("a = b + c", "(a = (b + c))"),
+        ("a <<= b || c", "(a <<= (b || c))"),
+        ("*p = a = 1", "((*p) = (a = 1))"),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(grouping(source).as_deref(), Ok(expected), "{source}");
+    }
+}
+
+#[test]
+fn each_level_binds_tighter_than_the_one_before() {
+    assert_eq!(
+        grouping("a || b && c | d ^ e & f == g < h << i + j * k").as_deref(),
+        Ok("(a || (b && (c | (d ^ (e & (f == (g < (h << (i + (j * k))))))))))")
+    );
+    assert_eq!(
+        grouping("a * b + c << d > e != f & g ^ h | i && j || k").as_deref(),
+        Ok("((((((((((a * b) + c) << d) > e) != f) & g) ^ h) | i) && j) || k)")
+    );
+    // Prefix operators bind tighter than any binary one.
+    assert_eq!(
+        grouping("-a * !b - ~*p & &c").as_deref(),
+        Ok("((((-a) * (!b)) - (~(*p))) & (&c))")
+    );
+    assert_eq!(
+        grouping("(a - b) - (c - d)").as_deref(),
+        Ok("((a - b) - (c - d))")
+    );
+}
+
+#[test]
+fn unbalanced_parentheses_are_errors() {
+    let cases = [
+        ("(a + 2", "1:27: expected `)`, found `;`"),
+        ("((a)", "1:25: expected `)`, found `;`"),
+        ("a + 2)", "1:26: expected `;`, found `)`"),
+        ("a = )", "1:25: expected an expression, found `)`"),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(grouping(source).unwrap_err(), expected, "{source}");
+    }
+}
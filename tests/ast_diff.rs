@@ -0,0 +1,97 @@
+use std::fs;
+use std::process::Command;
+
+use rcc::ast::Program;
+use rcc::ast_diff;
+use rcc::lexer::Lexer;
+use rcc::parser::Parser;
+
+fn parse(source: &str) -> Program {
+    Parser::new(Lexer::new(source).lex().unwrap())
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn trees_leave_out_positions() {
+    let program =
+        parse("int main() {\n    asm(\"nop\" : : \"r\" (1) : \"cc\");\n    return 2 * 3;\n}");
+    assert_eq!(
+        ast_diff::render(&program.functions[0]),
+        [
+            "function main -> int",
+            "  asm \"nop\"",
+            "    input \"r\"",
+            "      int 1",
+            "    clobber \"cc\"",
+            "  return",
+            "    binary *",
+            "      int 2",
+            "      int 3",
+        ]
+    );
+}
+
+#[test]
+fn formatting_changes_are_not_differences() {
+    let old = parse("int f() { return 1 + 2; }\nint main() { return f(); }");
+    let new = parse("int f()\n{\n    return 1+2;\n}\n\n\nint main(void) {return f();}");
+    assert_eq!(ast_diff::diff(&old, &new), None);
+}
+
+#[test]
+fn changed_added_and_removed_functions_are_shown() {
+    let old = parse("int f() { return 1; }\nint g() { return 2; }");
+    let new = parse("int f() { return 1 - 4; }\nint h() { return 3; }");
+    assert_eq!(
+        ast_diff::diff(&old, &new).unwrap(),
+        "changed function `f`:\n\
+         \x20 function f -> int\n\
+         \x20   return\n\
+         -     int 1\n\
+         +     binary -\n\
+         +       int 1\n\
+         +       int 4\n\
+         removed function `g`\n\
+         added function `h`:\n\
+         + function h -> int\n\
+         +   return\n\
+         +     int 3\n"
+    );
+}
+
+#[test]
+fn the_subcommand_exits_like_diff() {
+    let dir = std::env::temp_dir().join(format!("rcc-ast-diff-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.c"), "int main() { return 1; }").unwrap();
+    fs::write(dir.join("b.c"), "int main()\n{ return 1; }\n").unwrap();
+    fs::write(dir.join("c.c"), "int main() { return 2; }").unwrap();
+    fs::write(dir.join("bad.c"), "int main() { return }").unwrap();
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_rcc"))
+            .arg("ast-diff")
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .unwrap()
+    };
+    let same = run(&["a.c", "b.c"]);
+    assert_eq!(same.status.code(), Some(0), "{same:?}");
+    assert!(same.stdout.is_empty());
+    let differ = run(&["a.c", "c.c"]);
+    assert_eq!(differ.status.code(), Some(1));
+    let stdout = String::from_utf8(differ.stdout).unwrap();
+    assert!(
+        stdout.starts_with("--- a.c\n+++ c.c\nchanged function `main`:\n"),
+        "{stdout}"
+    );
+    let bad = run(&["a.c", "bad.c"]);
+    assert_eq!(bad.status.code(), Some(1));
+    assert!(String::from_utf8(bad.stderr)
+        .unwrap()
+        .starts_with("bad.c:1:21: error:"));
+    assert_eq!(run(&["a.c"]).status.code(), Some(2));
+    assert_eq!(run(&["a.c", "missing.c"]).status.code(), Some(3));
+    fs::remove_dir_all(&dir).unwrap();
+}
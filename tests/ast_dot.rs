@@ -0,0 +1,107 @@
+use rcc::ast_dot;
+use rcc::lexer::Lexer;
+use rcc::parser::Parser;
+
+fn dump(source: &str) -> String {
+    let tokens = Lexer::new(source).lex().unwrap();
+    let program = Parser::new(tokens).parse().unwrap();
+    ast_dot::emit(&program)
+}
+
+/// The label of the node numbered `id`.
+fn label(dot: &str, id: usize) -> &str {
+    let start = format!("    n{id} [label=\"");
+    let line = dot
+        .lines()
+        .find_map(|line| line.strip_prefix(start.as_str()))
+        .unwrap_or_else(|| panic!("no node {id} in\n{dot}"));
+    line.strip_suffix("\"];").unwrap()
+}
+
+#[test]
+fn the_graph_is_rooted_at_the_program() {
+    let dot = dump(
+        "enum e { A = 1 }; extern int g; int f(int, char *p, ...); int main(void) { return 0; }",
+    );
+    assert!(
+        dot.starts_with("digraph ast {\n    ordering=out;\n    node [shape=box];\n    n0 [label=\"program\"];\n"),
+        "{dot}"
+    );
+    assert!(dot.ends_with("\n}\n"), "{dot}");
+    let labels: Vec<_> = (1..11).map(|id| label(&dot, id)).collect();
+    assert_eq!(
+        labels,
+        [
+            "enum e",
+            "A",
+            "1",
+            "extern global int g",
+            "prototype int f",
+            "param int",
+            "param char * p",
+            "param ...",
+            "function int main",
+            "return",
+        ]
+    );
+    for edge in [
+        "n0 -> n1;",
+        "n1 -> n2;",
+        "n2 -> n3;",
+        "n0 -> n4;",
+        "n5 -> n8;",
+        "n0 -> n9;",
+    ] {
+        assert!(dot.contains(&format!("    {edge}\n")), "{edge} in\n{dot}");
+    }
+}
+
+#[test]
+fn operators_show_how_they_were_grouped() {
+    let dot = dump("int main() { return a - b * c - d; }");
+    // ((a - (b * c)) - d)
+    let labels: Vec<_> = (3..10).map(|id| label(&dot, id)).collect();
+    assert_eq!(labels, ["-", "-", "a", "*", "b", "c", "d"]);
+    for edge in [
+        "n3 -> n4 [label=\"lhs\"];",
+        "n4 -> n5 [label=\"lhs\"];",
+        "n4 -> n6 [label=\"rhs\"];",
+        "n6 -> n7 [label=\"lhs\"];",
+        "n3 -> n9 [label=\"rhs\"];",
+    ] {
+        assert!(dot.contains(&format!("    {edge}\n")), "{edge} in\n{dot}");
+    }
+}
+
+#[test]
+fn statements_label_the_roles_of_their_parts() {
+    let dot = dump("int main() { int s = 0; for (int i = 0; i < 3; i++) s += i; return s; }");
+    for edge in [
+        "[label=\"init\"]",
+        "[label=\"cond\"]",
+        "[label=\"step\"]",
+        "[label=\"body\"]",
+        "[label=\"target\"]",
+    ] {
+        assert!(dot.contains(edge), "{edge} in\n{dot}");
+    }
+    for node in ["declare int s", "for", "<", "postfix ++", "+=", "return"] {
+        assert!(
+            dot.contains(&format!(" [label=\"{node}\"];\n")),
+            "{node} in\n{dot}"
+        );
+    }
+}
+
+#[test]
+fn literals_are_escaped_for_graphviz() {
+    let dot = dump(
+        r#"int main() { char *s = "say \"hi\"\n"; __asm__("nop" : : "r"(s)); return '\\' + sizeof(int) + 7ul; }"#,
+    );
+    assert!(dot.contains(r#"[label="\"say \\\"hi\\\"\\n\""];"#), "{dot}");
+    assert!(dot.contains(r#"[label="'\\\\'"];"#), "{dot}");
+    assert!(dot.contains(r#"[label="asm \"nop\""];"#), "{dot}");
+    assert!(dot.contains(r#"[label="input"];"#), "{dot}");
+    assert!(dot.contains(r#"[label="sizeof(int)"];"#), "{dot}");
+    assert!(dot.contains(r#"[label="7ul"];"#), "{dot}");
+}
@@ -0,0 +1,184 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use rcc::ast_interp::Interpreter;
+use rcc::driver::{self, Options, Stage};
+use rcc::ir::interp::{self, Trap};
+use rcc::opt::OptLevel;
+
+/// `main`'s result, evaluated from the analyzed syntax tree.
+fn run_main(source: &str) -> Result<Option<i64>, Trap> {
+    let options = Options {
+        last_stage: Stage::Analyze,
+        ..Options::default()
+    };
+    let program = driver::compile(source, &options)
+        .expect("program should compile")
+        .program;
+    Interpreter::new(&program).call("main", &[])
+}
+
+/// `main`'s result, evaluated from the IR optimized at `opt_level`.
+fn run_ir(source: &str, opt_level: OptLevel) -> Result<Option<i64>, Trap> {
+    let options = Options {
+        opt_level,
+        ..Options::default()
+    };
+    let ir = driver::compile(source, &options)
+        .expect("program should compile")
+        .ir;
+    interp::Interpreter::new(&ir).call("main", &[])
+}
+
+/// A fresh directory for one test's files.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rcc-ast-interp-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// The syntax tree is the reference the IR is checked against: lowering
+/// and optimizing must not change what a program computes.
+#[test]
+fn matches_the_ir_interpreter() {
+    let programs = [
+        "int three() { return 3; } int main() { return (three() + 4) * 6 - 10 / three(); }",
+        "int fact(int n) { int r = 1; while (n > 1) r *= n--; return r; } int main() { return fact(5) % 256; }",
+        "int fib(int n) { while (n < 2) return n; return fib(n - 1) + fib(n - 2); } int main() { return fib(10); }",
+        "int main() { int a[5] = {1, 2}; int s = 0; for (int i = 0; i < 5; i++) s += a[i] * (i + 1); return s + sizeof a; }",
+        "int main() { int x = 7; int *p = &x; *p += 3; int **q = &p; return **q + (p - &x); }",
+        "int main() { unsigned u = 0; u--; return u > 5 && (u >> 28) == 15; }",
+        "int main() { unsigned char c = 250; c += 10; char d = 127; d++; return c + d; }",
+        "int main() { long big = 5000000000; return big / 1000000000 + (big % 7); }",
+        "int main() { int n = 0; int i = 0; do { n += i; } while (++i < 10); return n; }",
+        "int main() { int x = 1; { int x = 2; x++; } return x; }",
+        "enum { A = 3, B, C = B * 2 }; int main() { enum { D = C + 1 }; return A + B + C + D; }",
+        "int g = 5; long h; int bump() { return ++g; } int main() { bump(); bump(); return g + h; }",
+        "int main() { char *s = \"hello\"; int n = 0; while (*s++) n++; return n + sizeof \"hello\"; }",
+        "int main() { int a = 0; int b = 0 || (a = 4); int c = 0 && (a = 9); return a * 10 + b + c; }",
+        "int main() { int x = -17; return (x >> 2) + (x / 4) + (x % 4) + (~x) + !x; }",
+        "int main() { int a[3] = {9, 8, 7}; int *p = a + 2; int *q = &a[0]; return (p > q) + (p - q) * 10; }",
+        "int main() { short s = -2; unsigned short t = s; return t - 65000; }",
+    ];
+    for source in programs {
+        let expected = run_main(source);
+        assert!(expected.is_ok(), "{source}: {expected:?}");
+        for level in [OptLevel::O0, OptLevel::O2] {
+            assert_eq!(run_ir(source, level), expected, "{source} at {level:?}");
+        }
+    }
+}
+
+#[test]
+fn goto_jumps_into_and_out_of_blocks_and_loops() {
+    let source = "int main() {
+        int n = 0;
+        int i = 0;
+        goto inside;
+        for (i = 0; i < 100; i++) {
+            n += 100;
+        inside:
+            n++;
+            while (n > 5) goto out;
+        }
+    out:
+        return n + i * 1000;
+    }";
+    assert_eq!(run_main(source), run_ir(source, OptLevel::O0));
+    assert_eq!(run_main(source), Ok(Some(1102)));
+
+    let backwards = "int main() { int n = 0; top: n++; while (n < 10) goto top; return n; }";
+    assert_eq!(run_main(backwards), Ok(Some(10)));
+}
+
+#[test]
+fn undefined_behavior_traps() {
+    let cases = [
+        "int main() { int z = 0; return 1 / z; }",
+        "int main() { int x = -2147483647 - 1; int y = -1; return x / y; }",
+        "int main() { int s = 32; return 1 << s; }",
+        "int main() { int s = -1; return 1 >> s; }",
+        "int main() { int x; return x; }",
+        "int main() { int *p = 0; return *p; }",
+    ];
+    for source in cases {
+        assert!(
+            matches!(run_main(source), Err(Trap::UndefinedBehavior(_))),
+            "{source}: {:?}",
+            run_main(source)
+        );
+    }
+}
+
+#[test]
+fn what_cannot_be_evaluated_traps() {
+    assert_eq!(
+        run_main("int ext(int); int main() { return ext(1); }"),
+        Err(Trap::UnknownFunction("ext".to_string()))
+    );
+    assert_eq!(
+        run_main("extern int g; int main() { return g; }"),
+        Err(Trap::UnknownGlobal("g".to_string()))
+    );
+    assert_eq!(
+        run_main("int main() { __asm__(\"nop\"); return 0; }"),
+        Err(Trap::InlineAsm)
+    );
+    assert_eq!(
+        run_main("int main() { __builtin_trap(); return 0; }"),
+        Err(Trap::Abort)
+    );
+    assert_eq!(
+        run_main("int main() { __builtin_unreachable(); }"),
+        Err(Trap::Unreachable)
+    );
+    assert_eq!(run_main("int main() { for (;;) ; }"), Err(Trap::OutOfFuel));
+    assert_eq!(
+        run_main("int f(int n) { return f(n + 1); } int main() { return f(0); }"),
+        Err(Trap::StackOverflow)
+    );
+}
+
+#[test]
+fn fuel_bounds_evaluation() {
+    let options = Options {
+        last_stage: Stage::Analyze,
+        ..Options::default()
+    };
+    let source = "int main() { int n = 0; for (int i = 0; i < 1000; i++) n++; return n; }";
+    let program = driver::compile(source, &options).unwrap().program;
+    assert_eq!(
+        Interpreter::new(&program).with_fuel(100).call("main", &[]),
+        Err(Trap::OutOfFuel)
+    );
+    assert_eq!(Interpreter::new(&program).call("main", &[]), Ok(Some(1000)));
+}
+
+#[test]
+fn the_command_line_interprets_the_syntax_tree() {
+    let dir = temp_dir("cli");
+    fs::write(
+        dir.join("a.c"),
+        "int f() { return 6; } int main() { return f() * 7; }",
+    )
+    .unwrap();
+    let rcc = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_rcc"))
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .unwrap()
+    };
+    let output = rcc(&["--interpret=ast", "a.c"]);
+    assert_eq!(output.status.code(), Some(42), "{output:?}");
+    assert!(!dir.join("a.out").exists());
+    fs::write(dir.join("b.c"), "int main() { int x; return x; }").unwrap();
+    let output = rcc(&["--interpret=ast", "b.c"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("b.c: undefined behavior"), "{stderr}");
+    let output = rcc(&["--interpret=ast", "a.c", "b.c"]);
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+    fs::remove_dir_all(&dir).unwrap();
+}
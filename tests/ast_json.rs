@@ -0,0 +1,132 @@
+use rcc::ast_json;
+use rcc::json::Value;
+use rcc::lexer::Lexer;
+use rcc::parser::Parser;
+
+fn dump(source: &str) -> (String, Value) {
+    let tokens = Lexer::new(source).lex().unwrap();
+    let program = Parser::new(tokens).parse().unwrap();
+    let text = ast_json::emit(&program);
+    let value = Value::parse(&text).unwrap_or_else(|err| panic!("{err} in\n{text}"));
+    (text, value)
+}
+
+fn kind(value: &Value) -> Option<&str> {
+    value.get("kind").and_then(Value::as_str)
+}
+
+#[test]
+fn the_program_is_split_into_sections() {
+    let (text, value) = dump(
+        "enum e { A, B = 3 }; extern int g; int f(int, char *p); int main(void) { return 0; }",
+    );
+    assert!(
+        text.starts_with("{\n  \"enums\": [\n    {\"tag\":\"e\""),
+        "{text}"
+    );
+    assert!(text.ends_with("  ]\n}\n"), "{text}");
+    let enums = value.get("enums").and_then(Value::as_array).unwrap();
+    let enumerators = enums[0]
+        .get("enumerators")
+        .and_then(Value::as_array)
+        .unwrap();
+    assert_eq!(enumerators[0].get("value"), Some(&Value::Null));
+    assert_eq!(kind(enumerators[1].get("value").unwrap()), Some("integer"));
+    let globals = value.get("globals").and_then(Value::as_array).unwrap();
+    assert_eq!(globals[0].get("extern"), Some(&Value::Bool(true)));
+    assert_eq!(globals[0].get("init"), Some(&Value::Null));
+    let functions = value.get("functions").and_then(Value::as_array).unwrap();
+    // A prototype has no body, and its parameters may have no names.
+    assert_eq!(functions[0].get("body"), Some(&Value::Null));
+    let params = functions[0]
+        .get("params")
+        .and_then(Value::as_array)
+        .unwrap();
+    assert_eq!(params[0].get("name"), Some(&Value::Null));
+    assert_eq!(
+        params[1].get("type").and_then(Value::as_str),
+        Some("char *")
+    );
+    let pos = functions[1].get("pos").unwrap();
+    assert_eq!(pos.get("line").and_then(Value::as_u64), Some(1));
+    assert_eq!(pos.get("column").and_then(Value::as_u64), Some(61));
+}
+
+#[test]
+fn empty_sections_are_empty_arrays() {
+    let (text, _) = dump("int main() { return 0; }");
+    assert!(
+        text.starts_with("{\n  \"enums\": [],\n  \"globals\": [],\n  \"functions\": [\n"),
+        "{text}"
+    );
+}
+
+#[test]
+fn statements_and_expressions_are_tagged_with_their_kind() {
+    let (_, value) = dump(
+        "int main() {
+    int a[2] = {1, 2};
+    for (int i = 0; i < 2; i++) a[i] <<= 1;
+    while (0) {}
+    return sizeof(int) + -a[1];
+}",
+    );
+    let functions = value.get("functions").and_then(Value::as_array).unwrap();
+    let body = functions[0].get("body").and_then(Value::as_array).unwrap();
+    let kinds: Vec<_> = body
+        .iter()
+        .map(|statement| kind(statement).unwrap())
+        .collect();
+    assert_eq!(kinds, ["declaration", "for", "while", "return"]);
+
+    let init = body[0].get("init").unwrap();
+    assert_eq!(kind(init), Some("list"));
+    assert_eq!(body[0].get("type").and_then(Value::as_str), Some("int[2]"));
+
+    let step = body[1].get("step").unwrap();
+    assert_eq!(kind(step), Some("inc_dec"));
+    assert_eq!(step.get("op").and_then(Value::as_str), Some("++"));
+    assert_eq!(step.get("postfix"), Some(&Value::Bool(true)));
+    let assign = body[1]
+        .get("body")
+        .and_then(|body| body.get("expr"))
+        .unwrap();
+    assert_eq!(kind(assign), Some("assign"));
+    assert_eq!(assign.get("op").and_then(Value::as_str), Some("<<="));
+
+    let sum = body[3].get("value").unwrap();
+    assert_eq!(kind(sum), Some("binary"));
+    assert_eq!(sum.get("op").and_then(Value::as_str), Some("+"));
+    let pos = sum.get("pos").unwrap();
+    assert_eq!(pos.get("line").and_then(Value::as_u64), Some(5));
+    assert_eq!(pos.get("column").and_then(Value::as_u64), Some(24));
+    let size = sum.get("lhs").unwrap();
+    assert_eq!(kind(size), Some("sizeof_type"));
+    assert_eq!(size.get("type").and_then(Value::as_str), Some("int"));
+    let negated = sum.get("rhs").unwrap();
+    assert_eq!(kind(negated), Some("unary"));
+    assert_eq!(kind(negated.get("operand").unwrap()), Some("deref"));
+}
+
+#[test]
+fn strings_and_asm_operands_are_written_out() {
+    let (_, value) = dump(
+        r#"int puts(char *s); int main() { int x = 1; __asm__("nop" : "=r"(x) : "r"(x) : "cc"); return puts("hi\n"); }"#,
+    );
+    let functions = value.get("functions").and_then(Value::as_array).unwrap();
+    let body = functions[1].get("body").and_then(Value::as_array).unwrap();
+    let asm = &body[1];
+    assert_eq!(kind(asm), Some("asm"));
+    let outputs = asm.get("outputs").and_then(Value::as_array).unwrap();
+    assert_eq!(
+        outputs[0].get("constraint").and_then(Value::as_str),
+        Some("=r")
+    );
+    assert_eq!(kind(outputs[0].get("expr").unwrap()), Some("variable"));
+    let clobbers = asm.get("clobbers").and_then(Value::as_array).unwrap();
+    assert_eq!(clobbers, [Value::from("cc")]);
+    let call = body[2].get("value").unwrap();
+    assert_eq!(kind(call), Some("call"));
+    let args = call.get("args").and_then(Value::as_array).unwrap();
+    assert_eq!(args[0].get("value").and_then(Value::as_str), Some("hi\n"));
+}
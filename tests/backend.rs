@@ -0,0 +1,168 @@
+mod common;
+
+use rcc::codegen::aarch64::Aarch64;
+use rcc::codegen::asm::{reg, Assembly, Instruction, Syntax};
+use rcc::codegen::x86_64::X86_64;
+use std::collections::HashSet;
+
+use rcc::codegen::{Backend, CodeGenerator, Labels, ObjectFormat, Target};
+use rcc::driver::{self, Options};
+use rcc::ir::{BlockId, FunctionBuilder, IrType, Module};
+
+use common::assert_assembles;
+
+fn identity() -> Module {
+    let mut f = FunctionBuilder::new("id", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    f.ret(Some(x));
+    Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    }
+}
+
+#[test]
+fn targets_select_their_backends() {
+    let module = identity();
+    for (target, label) in [
+        (Target::AARCH64_APPLE, "_id:\n"),
+        (Target::X86_64_LINUX, "\nid:\n"),
+    ] {
+        let asm = CodeGenerator::new()
+            .with_backend(target.backend().unwrap())
+            .generate(&module);
+        assert!(asm.contains(label), "{target:?}:\n{asm}");
+    }
+    assert_eq!(
+        CodeGenerator::new().generate(&module),
+        CodeGenerator::new()
+            .with_backend(&Aarch64::APPLE)
+            .generate(&module)
+    );
+}
+
+#[test]
+fn calling_conventions_differ_per_target() {
+    let arm = Aarch64::APPLE.calling_convention();
+    let x86 = X86_64::LINUX.calling_convention();
+    assert_eq!((arm.arg_regs, arm.stack_arg_size), (8, 4));
+    assert_eq!((x86.arg_regs, x86.stack_arg_size), (6, 8));
+    assert_eq!(arm.stack_args_size(10), 8);
+    assert_eq!(x86.stack_args_size(10), 32);
+    assert_eq!(x86.stack_args_size(3), 0);
+}
+
+#[test]
+fn register_sets_exclude_scratch_registers() {
+    let arm = Aarch64::APPLE.registers();
+    for scratch in [8, 9, 16, 18] {
+        assert!(!arm.caller_saved.contains(&scratch) && !arm.callee_saved.contains(&scratch));
+    }
+    let x86 = X86_64::LINUX.registers();
+    // `%eax`, `%ecx`, `%edx`, `%rsp` and `%rbp`.
+    for reserved in [0, 1, 2, 4, 5] {
+        assert!(!x86.caller_saved.contains(&reserved) && !x86.callee_saved.contains(&reserved));
+    }
+}
+
+#[test]
+fn labels_follow_each_assembler_convention() {
+    let func = &identity().functions[0];
+    let mach_o = Labels::new(Aarch64::APPLE.object_format(), func);
+    let elf = Labels::new(X86_64::LINUX.object_format(), func);
+    assert_eq!(mach_o.block(BlockId(3)), "Lid_3");
+    assert_eq!(elf.block(BlockId(3)), ".Lid_3");
+    assert_eq!(elf.epilogue(), ".Lid_epilogue");
+}
+
+#[test]
+fn labels_never_collide() {
+    let mut seen = HashSet::new();
+    for name in ["f", "f_1", "f_epilogue", "Lf"] {
+        let mut f = FunctionBuilder::new(name, None);
+        for _ in 0..11 {
+            f.create_block();
+        }
+        let func = f.finish();
+        let mut labels = Labels::new(ObjectFormat::MachO, &func);
+        let fresh: Vec<_> = (0..3).map(|_| labels.fresh()).collect();
+        let all = func
+            .block_ids()
+            .map(|id| labels.block(id))
+            .chain(fresh)
+            .chain([labels.epilogue(), ObjectFormat::MachO.symbol(name)]);
+        for label in all {
+            assert!(seen.insert(label.clone()), "{label} from `{name}`");
+        }
+    }
+}
+
+#[test]
+fn only_aarch64_has_a_peephole_pass() {
+    let mut asm = Assembly::new(Syntax::Arm);
+    asm.instruction(Instruction::new("mov", vec![reg("w8"), reg("w8")]));
+    asm.instruction(Instruction::new("ret", vec![]));
+    assert_eq!(
+        Aarch64::APPLE.peephole(asm.clone()).to_string(),
+        "    ret\n"
+    );
+    assert_eq!(X86_64::LINUX.peephole(asm.clone()), asm);
+}
+
+fn compile_for(target: Target, source: &str) -> String {
+    let options = Options {
+        target,
+        ..Options::default()
+    };
+    driver::compile(source, &options).unwrap().assembly
+}
+
+#[test]
+fn linux_aarch64_uses_elf_naming_and_directives() {
+    let asm = compile_for(
+        Target::AARCH64_LINUX,
+        "int f() { return 2; } int main() { return f() * 3; }",
+    );
+    assert!(
+        asm.contains("    .globl main\n    .p2align 2\n    .type main, %function\nmain:\n"),
+        "{asm}"
+    );
+    assert!(asm.contains("    bl f\n"), "{asm}");
+    assert!(asm.contains("    ret\n    .size f, .-f\n"), "{asm}");
+    assert!(
+        asm.ends_with("    .section .note.GNU-stack,\"\",%progbits\n"),
+        "{asm}"
+    );
+    assert!(!asm.contains('_'), "{asm}");
+
+    let apple = compile_for(Target::AARCH64_APPLE, "int main() { return 0; }");
+    assert!(apple.contains("_main:\n"), "{apple}");
+    assert!(
+        !apple.contains(".type") && !apple.contains(".section"),
+        "{apple}"
+    );
+}
+
+#[test]
+fn linux_aarch64_labels_stay_local() {
+    let mut f = FunctionBuilder::new("pick", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let (then_block, else_block) = (f.create_block(), f.create_block());
+    f.branch(x, then_block, else_block);
+    f.switch_to(then_block);
+    f.ret(Some(x));
+    f.switch_to(else_block);
+    let zero = f.iconst(IrType::I32, 0);
+    f.ret(Some(zero));
+    let module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+
+    let asm = CodeGenerator::new()
+        .with_backend(&Aarch64::LINUX)
+        .generate(&module);
+    assert!(asm.contains("cbz w8, .Lpick_2\n.Lpick_1:\n"), "{asm}");
+    assert!(asm.contains("b .Lpick_epilogue\n"), "{asm}");
+    assert_assembles("aarch64-linux-gnu", &asm);
+}
@@ -0,0 +1,108 @@
+use rcc::ir::{BlockId, Function, FunctionBuilder, IrType, Module, Terminator};
+use rcc::opt::{self, OptLevel};
+
+fn optimize(func: Function) -> Function {
+    let mut module = Module {
+        functions: vec![func],
+        ..Module::default()
+    };
+    opt::optimize(&mut module, OptLevel::O1);
+    module.functions.remove(0)
+}
+
+#[test]
+fn folds_branch_on_constant_condition() {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let (then_block, else_block) = (f.create_block(), f.create_block());
+    let zero = f.iconst(IrType::I32, 0);
+    f.branch(zero, then_block, else_block);
+    f.switch_to(then_block);
+    let one = f.iconst(IrType::I32, 1);
+    f.ret(Some(one));
+    f.switch_to(else_block);
+    let two = f.iconst(IrType::I32, 2);
+    f.ret(Some(two));
+
+    let func = optimize(f.finish());
+    assert_eq!(func.blocks.len(), 1);
+    assert_eq!(func.blocks[0].term, Terminator::Ret(Some(two)));
+}
+
+#[test]
+fn threads_jump_chains() {
+    let mut f = FunctionBuilder::new("f", None);
+    let x = f.param(IrType::I32);
+    let (a, b, target, other) = (
+        f.create_block(),
+        f.create_block(),
+        f.create_block(),
+        f.create_block(),
+    );
+    f.branch(x, a, other);
+    f.switch_to(a);
+    f.jump(b);
+    f.switch_to(b);
+    f.jump(target);
+    f.switch_to(target);
+    f.call("g", vec![], None);
+    f.ret(None);
+    f.switch_to(other);
+    f.call("h", vec![], None);
+    f.jump(target);
+
+    let func = optimize(f.finish());
+    assert_eq!(func.blocks.len(), 3, "{func:#?}");
+    let Terminator::Branch { then_block, .. } = func.blocks[0].term else {
+        panic!("entry should still branch: {func:#?}");
+    };
+    assert_eq!(func.block(then_block).term, Terminator::Ret(None));
+}
+
+#[test]
+fn branch_to_the_same_block_becomes_a_jump() {
+    let mut f = FunctionBuilder::new("f", None);
+    let x = f.param(IrType::I32);
+    let (empty, join) = (f.create_block(), f.create_block());
+    f.branch(x, empty, join);
+    f.switch_to(empty);
+    f.jump(join);
+    f.switch_to(join);
+    f.call("g", vec![x], None);
+    f.ret(None);
+
+    let func = optimize(f.finish());
+    assert_eq!(func.blocks.len(), 1, "{func:#?}");
+    assert_eq!(func.blocks[0].instrs.len(), 1);
+}
+
+#[test]
+fn merges_straight_line_blocks() {
+    let mut f = FunctionBuilder::new("f", None);
+    let (second, third) = (f.create_block(), f.create_block());
+    f.call("a", vec![], None);
+    f.jump(second);
+    f.switch_to(second);
+    f.call("b", vec![], None);
+    f.jump(third);
+    f.switch_to(third);
+    f.call("c", vec![], None);
+    f.ret(None);
+
+    let func = optimize(f.finish());
+    assert_eq!(func.blocks.len(), 1);
+    assert_eq!(func.blocks[0].instrs.len(), 3);
+}
+
+#[test]
+fn keeps_loops_intact() {
+    let mut f = FunctionBuilder::new("f", None);
+    let header = f.create_block();
+    f.jump(header);
+    f.switch_to(header);
+    f.call("spin", vec![], None);
+    f.jump(header);
+
+    let func = optimize(f.finish());
+    assert_eq!(func.blocks.len(), 2, "{func:#?}");
+    assert_eq!(func.blocks[1].term, Terminator::Jump(BlockId(1)));
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use rcc::codegen::Target;
+use rcc::driver::Options;
+use rcc::ir::interp::{Interpreter, Trap};
+use rcc::opt::OptLevel;
+
+use common::compile;
+
+fn assembly(source: &str, target: Target) -> String {
+    compile(
+        source,
+        &Options {
+            target,
+            ..Options::default()
+        },
+    )
+    .expect("program should compile")
+    .assembly
+}
+
+const TRAPPING: &str = "void die(void) { return __builtin_trap(); }\n\
+                        void run(void) { return die(); }\n";
+
+#[test]
+fn expect_evaluates_to_its_first_argument() {
+    for opt_level in [OptLevel::O0, OptLevel::O2] {
+        let artifacts = compile(
+            "int main() { return __builtin_expect(6 * 7, 42); }",
+            &Options {
+                opt_level,
+                ..Options::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            Interpreter::new(&artifacts.ir).call("main", &[]),
+            Ok(Some(42))
+        );
+    }
+}
+
+#[test]
+fn unlikely_branches_are_laid_out_last() {
+    let source = "int f(int x) {\n\
+                  \x20   if (__builtin_expect(x, 0))\n\
+                  \x20       return 7;\n\
+                  \x20   return x * 3;\n\
+                  }\n\
+                  int main() { return f(0) + f(2); }\n";
+    for opt_level in [OptLevel::O0, OptLevel::O2] {
+        let options = Options {
+            opt_level,
+            target: Target::X86_64_LINUX,
+            ..Options::default()
+        };
+        let artifacts = compile(source, &options).unwrap();
+        assert_eq!(
+            Interpreter::new(&artifacts.ir).call("main", &[]),
+            Ok(Some(7))
+        );
+        let asm = artifacts.assembly;
+        let likely = asm.find("imull $3").expect(&asm);
+        let unlikely = asm.find("movl $7, %eax").expect(&asm);
+        assert!(likely < unlikely, "{asm}");
+    }
+}
+
+#[test]
+fn builtins_need_no_declaration_in_any_standard() {
+    let options = Options {
+        standard: rcc::standard::Standard::parse("c11").unwrap(),
+        ..Options::default()
+    };
+    compile("int main() { return __builtin_expect(1, 1); }", &options).unwrap();
+}
+
+#[test]
+fn builtins_check_their_arguments() {
+    let err = compile(
+        "int main() { return __builtin_expect(1); }",
+        &Options::default(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        "1:21: `__builtin_expect` takes 2 arguments, but 1 was given"
+    );
+    let err = compile(
+        "int main() { return __builtin_trap(); }",
+        &Options::default(),
+    )
+    .unwrap_err();
+    assert!(err.contains("expected `int` but found `void`"), "{err}");
+}
+
+#[test]
+fn trap_ends_the_block() {
+    let artifacts = compile(TRAPPING, &Options::default()).unwrap();
+    let ir = artifacts.ir.to_string();
+    assert!(ir.contains("fn die() {\nbb0:\n    trap\n}"), "{ir}");
+    assert_eq!(
+        Interpreter::new(&artifacts.ir).call("run", &[]),
+        Err(Trap::Abort)
+    );
+}
+
+#[test]
+fn trap_is_brk_0_on_aarch64_and_ud2_on_x86_64() {
+    let asm = assembly(TRAPPING, Target::AARCH64_APPLE);
+    assert!(asm.contains("    brk #0\n"), "{asm}");
+    let asm = assembly(TRAPPING, Target::X86_64_LINUX);
+    assert!(asm.contains("    ud2\n"), "{asm}");
+}
+
+#[test]
+fn code_after_unreachable_is_pruned() {
+    let artifacts = compile(
+        "void never(void) { return __builtin_unreachable(); __asm__(\"nop\"); }",
+        &Options::default(),
+    )
+    .unwrap();
+    let ir = artifacts.ir.to_string();
+    assert!(
+        ir.contains("fn never() {\nbb0:\n    unreachable\n}"),
+        "{ir}"
+    );
+    assert!(
+        !artifacts.assembly.contains("nop"),
+        "{}",
+        artifacts.assembly
+    );
+}
+
+#[test]
+fn llvm_declares_the_trap_intrinsic() {
+    let ir = compile(TRAPPING, &Options::default()).unwrap().ir;
+    let text = rcc::ir::llvm::emit(&ir);
+    assert!(
+        text.contains("call void @llvm.trap()\n  unreachable\n"),
+        "{text}"
+    );
+    assert!(text.contains("\ndeclare void @llvm.trap()\n"), "{text}");
+}
@@ -0,0 +1,197 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use rcc::cache::{Cache, Entry, Key, Stats};
+
+/// A fresh directory for one test's files.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rcc-cache-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn rcc(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rcc"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+/// The entries in `dir`'s cache.
+fn entries(dir: &Path) -> usize {
+    fs::read_dir(dir.join(".rcc-cache")).map_or(0, |entries| entries.count())
+}
+
+#[test]
+fn entries_round_trip() {
+    let mut entry = Entry::default();
+    entry.insert("ir", "fn main() -> i32 {\n}\n".to_string());
+    entry.insert("warnings", String::new());
+    entry.insert("s", "ret 7\n".to_string());
+    entry.insert("s", "ret 42\n".to_string());
+    let stored = entry.encode();
+    assert_eq!(Entry::decode(&stored), Some(entry.clone()));
+    assert_eq!(entry.get("s"), Some("ret 42\n"));
+    assert_eq!(entry.get("warnings"), Some(""));
+    assert_eq!(entry.get("ll"), None);
+    // A truncated or foreign file is a miss, not an error.
+    assert_eq!(Entry::decode(&stored[..stored.len() - 1]), None);
+    assert_eq!(Entry::decode("ir 3\nabc"), None);
+}
+
+#[test]
+fn keys_depend_on_every_part() {
+    let key = Key::new([&b"int main;"[..], b"-O2"]);
+    assert_eq!(key, Key::new([&b"int main;"[..], b"-O2"]));
+    assert_ne!(key, Key::new([&b"int main;"[..], b"-O1"]));
+    assert_ne!(key, Key::new([&b"int main;-"[..], b"O2"]));
+    assert_eq!(key.to_string().len(), 32);
+}
+
+#[test]
+fn the_cache_counts_hits_misses_and_entries() {
+    let dir = temp_dir("api");
+    let cache = Cache::new(dir.join("cache"));
+    let key = Key::new([&b"source"[..]]);
+    assert_eq!(cache.get(key), None);
+    let mut entry = Entry::default();
+    entry.insert("ir", "ir\n".to_string());
+    cache.put(key, &entry).unwrap();
+    assert_eq!(cache.get(key), Some(entry));
+    let stats = cache.take_stats();
+    assert_eq!(
+        stats,
+        Stats {
+            hits: 1,
+            misses: 1,
+            stored: 1
+        }
+    );
+    assert_eq!(stats.to_string(), "1 hit, 1 miss, 1 entry stored");
+    assert_eq!(cache.take_stats(), Stats::default());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn unchanged_inputs_are_not_compiled_again() {
+    let dir = temp_dir("cli");
+    fs::write(
+        dir.join("a.c"),
+        "int main() {\n    int unused = 1;\n    return 0;\n}\n",
+    )
+    .unwrap();
+    fs::write(dir.join("b.c"), "int main() { return 2; }").unwrap();
+    let build = |extra: &[&str]| {
+        let args = [
+            &["--emit=ir", "-Wall", "--cache-stats", "a.c", "b.c"],
+            extra,
+        ]
+        .concat();
+        let output = rcc(&args, &dir);
+        assert!(output.status.success(), "{output:?}");
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        let ir = fs::read_to_string(dir.join("a.ir")).unwrap();
+        (stderr, ir)
+    };
+
+    let (stderr, ir) = build(&[]);
+    assert!(
+        stderr.ends_with("rcc: cache: 0 hits, 2 misses, 2 entries stored\n"),
+        "{stderr}"
+    );
+    assert_eq!(entries(&dir), 2);
+    fs::remove_file(dir.join("a.ir")).unwrap();
+
+    // The outputs and warnings come back from the cache.
+    let (again, cached) = build(&[]);
+    assert!(
+        again.ends_with("rcc: cache: 2 hits, 0 misses, 0 entries stored\n"),
+        "{again}"
+    );
+    assert!(
+        again.contains("a.c:2:9: warning: unused variable `unused`"),
+        "{again}"
+    );
+    assert_eq!(cached, ir);
+
+    // Changing an input or the flags compiles again.
+    fs::write(dir.join("b.c"), "int main() { return 3; }").unwrap();
+    let (stderr, _) = build(&[]);
+    assert!(
+        stderr.ends_with("1 hit, 1 miss, 1 entry stored\n"),
+        "{stderr}"
+    );
+    let (stderr, _) = build(&["-O2"]);
+    assert!(
+        stderr.ends_with("0 hits, 2 misses, 2 entries stored\n"),
+        "{stderr}"
+    );
+    assert_eq!(entries(&dir), 5);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn failures_and_no_cache_leave_the_cache_alone() {
+    let dir = temp_dir("no-cache");
+    fs::write(dir.join("a.c"), "int main() { return x; }").unwrap();
+    for _ in 0..2 {
+        let output = rcc(&["--emit=ir", "a.c"], &dir);
+        assert_eq!(output.status.code(), Some(1), "{output:?}");
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("a.c:1:21: error:"), "{stderr}");
+    }
+    assert_eq!(entries(&dir), 0);
+
+    fs::write(dir.join("a.c"), "int main() { return 0; }").unwrap();
+    let output = rcc(&["--emit=ir", "--no-cache", "a.c"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    assert!(dir.join("a.ir").exists());
+    assert!(!dir.join(".rcc-cache").exists());
+    let output = rcc(&["--no-cache", "--cache-stats", "a.c"], &dir);
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn another_build_of_rcc_compiles_again() {
+    let dir = temp_dir("build");
+    fs::write(dir.join("a.c"), "int main() { return 0; }").unwrap();
+    // The same compiler with one more byte at the end, written just now,
+    // is another build, though its version is the same.
+    let mut exe = fs::read(env!("CARGO_BIN_EXE_rcc")).unwrap();
+    exe.push(0);
+    let rebuilt = dir.join("rcc");
+    fs::write(&rebuilt, exe).unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&rebuilt, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    let args = ["--emit=ir", "--cache-stats", "a.c"];
+    let output = rcc(&args, &dir);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.ends_with("0 hits, 1 miss, 1 entry stored\n"),
+        "{stderr}"
+    );
+    let output = Command::new(&rebuilt)
+        .args(args)
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.ends_with("0 hits, 1 miss, 1 entry stored\n"),
+        "{stderr}"
+    );
+    let output = rcc(&args, &dir);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.ends_with("1 hit, 0 misses, 0 entries stored\n"),
+        "{stderr}"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
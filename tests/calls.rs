@@ -0,0 +1,278 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use rcc::codegen::Target;
+use rcc::driver::Options;
+use rcc::opt::OptLevel;
+use rcc::standard::Standard;
+
+use common::{assert_assembles, compile, run_main};
+
+/// More arguments than either target has registers for, each weighted so
+/// a swapped or dropped one changes the result.
+const TEN: &str =
+    "int weigh(int a, int b, int c, int d, int e, int f, int g, int h, int i, int j) {
+    return a + 2 * b + 3 * c + 4 * d + 5 * e + 6 * f + 7 * g + 8 * h + 9 * i + 10 * j;
+}
+int main() { return weigh(1, 2, 3, 4, 5, 6, 7, 8, 9, 10) - 300; }";
+
+#[test]
+fn arguments_bind_to_parameters_in_order() {
+    let cases = [
+        ("int sub(int a, int b) { return a - b; } int main() { return sub(10, 3); }", 7),
+        (
+            "int twice(int x) { x = x * 2; return x; } int main() { int y = 4; return twice(y) + y; }",
+            12,
+        ),
+        (
+            "int fact(int n) { int r = 1; while (n) { r = r * n; n = n - 1; } return r; }\n\
+             int main() { return fact(5); }",
+            120,
+        ),
+        (
+            "int id(int x) { return x; } int main() { int y; return id(y = 6) + y; }",
+            12,
+        ),
+        ("int none(void) { return 9; } int main() { return none(); }", 9),
+        (TEN, 85),
+    ];
+    for (source, expected) in cases {
+        for level in [OptLevel::O0, OptLevel::O2] {
+            assert_eq!(
+                run_main(source, level),
+                Some(expected),
+                "{source} at {level:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn calls_are_checked_against_the_definition() {
+    let cases = [
+        (
+            "int f(int a) { return a; } int main() { return f(); }",
+            "1:48: `f` takes 1 argument, but 0 were given",
+        ),
+        (
+            "int f(int a, int b) { return a; } int main() { return f(1, 2, 3); }",
+            "1:55: `f` takes 2 arguments, but 3 were given",
+        ),
+        (
+            "int f(int a) { return a; } int main() { return f(\"x\"); }",
+            "1:50: expected `int` but found `char *`",
+        ),
+        (
+            "int f(int a, int a) { return a; }",
+            "1:18: redefinition of parameter `a`",
+        ),
+        (
+            "int f(void v) { return 0; }",
+            "1:12: parameter `v` has incomplete type `void`",
+        ),
+        (
+            "int f(int a) { int a; return a; }",
+            "1:20: redefinition of `a`",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(
+            compile(source, &Options::default()).unwrap_err(),
+            expected,
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn prototypes_declare_functions_defined_later() {
+    let source = "int add(int, int);\n\
+                  int main() { return add(40, 2); }\n\
+                  int add(int a, int b) { return a + b; }";
+    assert_eq!(run_main(source, OptLevel::O0), Some(42));
+    let artifacts = compile(source, &Options::default()).unwrap();
+    assert_eq!(artifacts.ir.functions.len(), 2);
+    let header = rcc::header::emit(&artifacts.program, "add.c");
+    assert_eq!(header.matches("int add(").count(), 1, "{header}");
+}
+
+#[test]
+fn declarations_must_agree() {
+    let cases = [
+        (
+            "int f(int a); int f(void) { return 0; }",
+            "1:19: conflicting types for `f`",
+        ),
+        (
+            "int f(void); void f(void);",
+            "1:19: conflicting types for `f`",
+        ),
+        (
+            "int f(int); int f(int) { return 0; }",
+            "1:19: parameter name omitted",
+        ),
+        (
+            "int f(int, void);",
+            "1:12: `void` must be the first and only parameter if specified",
+        ),
+        (
+            "int f(void) int g(void);",
+            "1:13: expected `{`, found `int`",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(
+            compile(source, &Options::default()).unwrap_err(),
+            expected,
+            "{source}"
+        );
+    }
+    compile(
+        "int f(int x); int f(int y); int f(int z) { return z; }",
+        &Options::default(),
+    )
+    .unwrap();
+}
+
+#[test]
+fn implicit_declarations_take_any_scalar() {
+    let options = Options {
+        standard: Standard::parse("gnu89").unwrap(),
+        ..Options::default()
+    };
+    compile(
+        "int main() { puts(\"hi\"); return add(1, 2); } int add(int a, int b) { return a + b; }",
+        &options,
+    )
+    .unwrap();
+}
+
+#[test]
+fn parameters_appear_in_headers_and_diffs() {
+    let source = "int add(int a, int b) { return a + b; } int main() { return add(1, 2); }";
+    let program = compile(source, &Options::default()).unwrap().program;
+    let header = rcc::header::emit(&program, "add.c");
+    assert!(header.contains("\nint add(int a, int b);\n"), "{header}");
+    let lines = rcc::ast_diff::render(&program.functions[0]);
+    assert_eq!(lines[1..3], ["  param int a", "  param int b"]);
+    let lines = rcc::ast_diff::render(&program.functions[1]);
+    assert_eq!(lines[2..], ["    call add", "      int 1", "      int 2"]);
+}
+
+#[test]
+fn aarch64_passes_eight_arguments_in_registers_and_the_rest_on_the_stack() {
+    // AAPCS64 gives each stack argument 8 bytes; Apple packs them.
+    for (target, triple, slots) in [
+        (
+            Target::AARCH64_LINUX,
+            "aarch64-linux-gnu",
+            ["[sp, #0]", "[sp, #8]", "[x29, #24]"],
+        ),
+        (
+            Target::AARCH64_APPLE,
+            "arm64-apple-macos",
+            ["[sp, #0]", "[sp, #4]", "[x29, #20]"],
+        ),
+    ] {
+        let asm = compile(
+            TEN,
+            &Options {
+                target,
+                ..Options::default()
+            },
+        )
+        .unwrap()
+        .assembly;
+        for register in ["w0", "w7"] {
+            assert!(asm.contains(&format!("mov {register}, ")), "{asm}");
+        }
+        assert!(asm.contains("weigh\n"), "{asm}");
+        for slot in slots {
+            assert!(asm.contains(slot), "{slot} in {asm}");
+        }
+        assert_assembles(triple, &asm);
+    }
+}
+
+#[test]
+fn x86_program_passes_arguments_to_itself_and_to_libc() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let options = Options {
+        target: Target::X86_64_LINUX,
+        standard: Standard::parse("gnu89").unwrap(),
+        ..Options::default()
+    };
+    let source = TEN.replace("int main() { return", "int main() { puts(\"ten\"); return");
+    let asm = compile(&source, &options).unwrap().assembly;
+    let dir = std::env::temp_dir().join(format!("rcc-calls-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (source, exe) = (dir.join("calls.s"), dir.join("calls"));
+    fs::write(&source, &asm).unwrap();
+    let Ok(linked) = Command::new("cc").arg(&source).arg("-o").arg(&exe).status() else {
+        return;
+    };
+    assert!(linked.success(), "{asm}");
+    let output = Command::new(&exe).output().unwrap();
+    assert_eq!(output.status.code(), Some(85));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ten\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn functions_may_be_used_above_their_definitions() {
+    let cases = [
+        (
+            "int main() { return later(4); } int later(int x) { return x * 10; }",
+            40,
+        ),
+        (
+            "int main() { return even(10) * 2 + odd(7); }
+             int even(int n) { return n == 0 || odd(n - 1); }
+             int odd(int n) { return n != 0 && even(n - 1); }",
+            3,
+        ),
+        ("int main() { return counter + 1; } int counter = 41;", 42),
+    ];
+    for (source, expected) in cases {
+        for level in [OptLevel::O0, OptLevel::O2] {
+            assert_eq!(
+                run_main(source, level),
+                Some(expected),
+                "{source} at {level:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn uses_above_a_declaration_are_checked_against_it() {
+    let cases = [
+        (
+            "int main() { return later(1); } int later(int a, int b) { return a + b; }",
+            "1:21: `later` takes 2 arguments, but 1 was given",
+        ),
+        (
+            "int main() { return later(); } void later(void) {}",
+            "1:21: expected `int` but found `void`",
+        ),
+        (
+            "int main() { return g(); } int g;",
+            "1:21: called object `g` is not a function",
+        ),
+        (
+            "int main() { return f(); } int f(void) { return 0; } long f(void);",
+            "1:59: conflicting types for `f`",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(
+            compile(source, &Options::default()).unwrap_err(),
+            expected,
+            "{source}"
+        );
+    }
+}
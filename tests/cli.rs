@@ -0,0 +1,995 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+use rcc::json::Value;
+
+/// A fresh directory for one test's files.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rcc-cli-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn rcc(args: &[&str], dir: &PathBuf) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rcc"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn compiles_each_input_to_its_own_output() {
+    let dir = temp_dir("multiple");
+    fs::write(dir.join("a.c"), "int main() { return 1; }").unwrap();
+    fs::write(dir.join("b.c"), "int main() { return 2; }").unwrap();
+    let output = rcc(&["-S", "a.c", "b.c"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    assert!(fs::read_to_string(dir.join("a.s"))
+        .unwrap()
+        .contains("main"));
+    assert!(fs::read_to_string(dir.join("b.s"))
+        .unwrap()
+        .contains("main"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn every_input_is_compiled_even_after_an_error() {
+    let dir = temp_dir("errors");
+    fs::write(dir.join("bad.c"), "int main() { return; }").unwrap();
+    fs::write(dir.join("good.c"), "int main() { return 0; }").unwrap();
+    fs::write(dir.join("worse.c"), "int main() { return 1 + ; }").unwrap();
+    let output = rcc(&["-S", "bad.c", "good.c", "worse.c"], &dir);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("bad.c:1:"), "{stderr}");
+    assert!(stderr.contains("worse.c:1:"), "{stderr}");
+    assert!(dir.join("good.s").exists());
+    assert!(!dir.join("bad.s").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn no_input_is_an_error() {
+    let dir = temp_dir("none");
+    let output = rcc(&["-O1"], &dir);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("no input file"), "{stderr}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dash_c_assembles_an_object_file() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = temp_dir("object");
+    fs::write(dir.join("a.c"), "int main() { return 1; }").unwrap();
+    let output = rcc(&["-c", "a.c"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    let object = fs::read(dir.join("a.o")).unwrap();
+    assert_eq!(&object[..4], b"\x7fELF");
+    // The assembly was only an intermediate.
+    assert!(!dir.join("a.s").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn links_an_executable_by_default() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = temp_dir("link");
+    fs::write(dir.join("main.c"), "int main() { return 42; }").unwrap();
+    fs::write(dir.join("other.c"), "int other() { return 1; }").unwrap();
+    let output = rcc(&["main.c", "other.c"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    let status = Command::new(dir.join("a.out")).status().unwrap();
+    assert_eq!(status.code(), Some(42));
+    // Nothing but the executable is left behind.
+    assert!(!dir.join("main.s").exists());
+    assert!(!dir.join("main.o").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cc_mode_builds_like_a_makefile_would() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = temp_dir("cc");
+    fs::create_dir_all(dir.join("obj")).unwrap();
+    fs::write(dir.join("main.c"), "int main() { return helper() + 1; }").unwrap();
+    fs::write(dir.join("helper.c"), "int helper() { return 41; }").unwrap();
+    let cflags = [
+        "--cc",
+        "-std=gnu89",
+        "-Wall",
+        "-Wextra",
+        "-O3",
+        "-g",
+        "-DNDEBUG",
+        "-I",
+        "include",
+        "-MMD",
+        "-MF",
+        "obj/deps.d",
+    ];
+    for name in ["main", "helper"] {
+        let (source, object) = (format!("{name}.c"), format!("obj/{name}.o"));
+        let args = [&cflags[..], &["-c", &source, "-o", &object]].concat();
+        let output = rcc(&args, &dir);
+        assert!(output.status.success(), "{output:?}");
+        assert!(dir.join(&object).exists());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains("rcc: note: ignoring `-Wextra`: rcc has no such warning\n"),
+            "{stderr}"
+        );
+        assert!(
+            stderr.contains("rcc: note: ignoring `-MMD`: rcc does not write dependency files\n"),
+            "{stderr}"
+        );
+    }
+    assert!(!dir.join("main.o").exists());
+    let output = rcc(
+        &["--cc", "obj/main.o", "obj/helper.o", "-o", "prog", "-lc"],
+        &dir,
+    );
+    assert!(output.status.success(), "{output:?}");
+    let status = Command::new(dir.join("prog")).status().unwrap();
+    assert_eq!(status.code(), Some(42));
+    assert!(!dir.join("a.out").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cc_flags_need_cc_mode() {
+    let dir = temp_dir("no-cc-mode");
+    fs::write(dir.join("a.c"), "int main() { return 1; }").unwrap();
+    let output = rcc(&["-S", "-MMD", "a.c"], &dir);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("unknown option `-MMD`"), "{stderr}");
+    let output = rcc(&["--cc", "-S", "a.c", "a.c", "-o", "b.s"], &dir);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("cannot use `-o` with multiple inputs unless linking"),
+        "{stderr}"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn exit_statuses_tell_failures_apart() {
+    let dir = temp_dir("exit-status");
+    fs::write(dir.join("good.c"), "int main() { return 1; }").unwrap();
+    fs::write(dir.join("bad.c"), "int main() { return; }").unwrap();
+    let status = |args: &[&str]| rcc(args, &dir).status.code();
+    assert_eq!(status(&["-S", "good.c"]), Some(0));
+    assert_eq!(status(&["-S", "bad.c"]), Some(1));
+    assert_eq!(status(&["-S", "--no-such-flag", "good.c"]), Some(2));
+    assert_eq!(status(&["cov"]), Some(2));
+    assert_eq!(status(&["-S", "missing.c"]), Some(3));
+    // The worst failure wins.
+    assert_eq!(status(&["-S", "bad.c", "missing.c"]), Some(3));
+    let crashed = Command::new(env!("CARGO_BIN_EXE_rcc"))
+        .args(["-S", "bad.c", "good.c"])
+        .current_dir(&dir)
+        .env("RCC_DEBUG_CRASH", "codegen")
+        .env("RUST_BACKTRACE", "0")
+        .output()
+        .unwrap();
+    assert_eq!(crashed.status.code(), Some(101));
+    let stderr = String::from_utf8(crashed.stderr).unwrap();
+    if let Some((_, rest)) = stderr.split_once("note: the compiler's state is in `") {
+        let _ = fs::remove_file(rest.split_once('`').unwrap().0);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn save_temps_keeps_intermediate_files() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = temp_dir("save-temps");
+    fs::write(
+        dir.join("main.c"),
+        "#define ANSWER 42\nint main() { return ANSWER; }",
+    )
+    .unwrap();
+    let output = rcc(&["--save-temps", "main.c"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    let preprocessed = fs::read_to_string(dir.join("main.i")).unwrap();
+    assert!(preprocessed.contains("return 42;"), "{preprocessed}");
+    assert!(!preprocessed.contains("ANSWER"), "{preprocessed}");
+    assert!(fs::read_to_string(dir.join("main.s"))
+        .unwrap()
+        .contains("main:"));
+    assert_eq!(&fs::read(dir.join("main.o")).unwrap()[..4], b"\x7fELF");
+    let status = Command::new(dir.join("a.out")).status().unwrap();
+    assert_eq!(status.code(), Some(42));
+
+    let output = rcc(&["--save-temps", "-c", "main.c"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    assert!(dir.join("main.s").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn response_files_expand_into_arguments() {
+    let dir = temp_dir("response-file");
+    fs::write(dir.join("my file.c"), "int main() { return 7; }").unwrap();
+    fs::write(dir.join("flags.txt"), "-O2\n--emit=ir @more.txt\n").unwrap();
+    fs::write(dir.join("more.txt"), "-o '-' \"my file.c\"").unwrap();
+    let output = rcc(&["@flags.txt"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("const i32 7"), "{stdout}");
+
+    fs::write(dir.join("loop.txt"), "-S @loop.txt").unwrap();
+    let output = rcc(&["@loop.txt"], &dir);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("response file `loop.txt` includes itself"),
+        "{stderr}"
+    );
+
+    // Like `cc`, an unreadable response file is an ordinary argument.
+    let output = rcc(&["-S", "@missing.txt"], &dir);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("cannot read `@missing.txt`"), "{stderr}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn nothing_is_linked_after_an_error() {
+    let dir = temp_dir("no-link");
+    fs::write(dir.join("main.c"), "int main() { return; }").unwrap();
+    let output = rcc(&["main.c"], &dir);
+    assert!(!output.status.success());
+    assert!(!dir.join("a.out").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_missing_assembler_is_reported() {
+    let dir = temp_dir("no-cc");
+    fs::write(dir.join("a.c"), "int main() { return 1; }").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rcc"))
+        .args(["-c", "a.c"])
+        .current_dir(&dir)
+        .env("RCC_CC", "/nonexistent/cc")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("cannot run `/nonexistent/cc`"), "{stderr}");
+    assert!(!dir.join("a.o").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dash_o_dash_prints_the_assembly() {
+    let dir = temp_dir("stdout");
+    fs::write(dir.join("a.c"), "int main() { return 1; }").unwrap();
+    for args in [&["-o", "-", "a.c"][..], &["--stdout", "a.c"]] {
+        let output = rcc(args, &dir);
+        assert!(output.status.success(), "{output:?}");
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("main:"), "{stdout}");
+    }
+    assert!(!dir.join("a.s").exists());
+    assert!(!dir.join("a.out").exists());
+    let output = rcc(&["-c", "-o", "-", "a.c"], &dir);
+    assert!(!output.status.success());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dash_o_names_the_output_file() {
+    let dir = temp_dir("dash-o");
+    fs::create_dir_all(dir.join("build")).unwrap();
+    fs::write(dir.join("a.c"), "int main() { return 7; }").unwrap();
+    let output = rcc(&["-S", "a.c", "-o", "build/out.s"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    let asm = fs::read_to_string(dir.join("build/out.s")).unwrap();
+    assert!(asm.contains("main:"), "{asm}");
+    assert!(!dir.join("a.s").exists());
+    // Only the last artifact goes to `-o`; the others stay next to the
+    // input.
+    let output = rcc(&["--emit=ir,asm", "a.c", "-o", "build/b.s"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    assert!(dir.join("build/b.s").exists());
+    assert!(dir.join("a.ir").exists());
+    let output = rcc(&["-S", "a.c", "a.c", "-o", "b.s"], &dir);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("cannot use `-o` with multiple inputs unless linking"),
+        "{stderr}"
+    );
+    if cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        let output = rcc(&["a.c", "-o", "build/prog"], &dir);
+        assert!(output.status.success(), "{output:?}");
+        let status = Command::new(dir.join("build/prog")).status().unwrap();
+        assert_eq!(status.code(), Some(7));
+        assert!(!dir.join("a.out").exists());
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn run_executes_the_program() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = temp_dir("run");
+    fs::write(
+        dir.join("a.c"),
+        "int puts(char *s); int main() { puts(\"hello\"); return 42; }",
+    )
+    .unwrap();
+    let output = rcc(&["--run", "a.c"], &dir);
+    assert_eq!(output.status.code(), Some(42), "{output:?}");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "hello\n");
+    assert!(!dir.join("a.out").exists());
+    let output = rcc(&["--run", "-S", "a.c"], &dir);
+    assert_eq!(output.status.code(), Some(2));
+    // Errors stop the program from running.
+    fs::write(dir.join("b.c"), "int main() { return x; }").unwrap();
+    let output = rcc(&["--run", "b.c"], &dir);
+    assert_eq!(output.status.code(), Some(1));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn reads_the_program_from_stdin() {
+    let dir = temp_dir("stdin");
+    let rcc_stdin = |args: &[&str], source: &str| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_rcc"))
+            .args(args)
+            .current_dir(&dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdin = child.stdin.take().unwrap();
+        stdin.write_all(source.as_bytes()).unwrap();
+        drop(stdin);
+        child.wait_with_output().unwrap()
+    };
+    for input in ["-", "--stdin"] {
+        let output = rcc_stdin(
+            &["--emit=ir", "--stdout", input],
+            "int main() { return 7; }",
+        );
+        assert!(output.status.success(), "{output:?}");
+        assert!(String::from_utf8(output.stdout)
+            .unwrap()
+            .contains("const i32 7"));
+    }
+    let output = rcc_stdin(&["-", "--stdout"], "int main() { return x; }");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("<stdin>:1:21: error: use of undeclared identifier `x`"));
+    let output = rcc_stdin(&["-i", "-"], "int main() { return 5; }");
+    assert_eq!(output.status.code(), Some(5), "{output:?}");
+    for args in [&["-", "--stdin"][..], &["--watch", "-"]] {
+        assert_eq!(rcc_stdin(args, "").status.code(), Some(2), "{args:?}");
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn include_directories_come_from_dash_i() {
+    let dir = temp_dir("include");
+    fs::create_dir_all(dir.join("inc")).unwrap();
+    fs::write(dir.join("inc/seven.h"), "int seven() { return 7; }\n").unwrap();
+    fs::write(
+        dir.join("a.c"),
+        "#include <seven.h>\nint main() { return seven(); }\n",
+    )
+    .unwrap();
+    for args in [&["-i", "-I", "inc", "a.c"][..], &["-i", "-Iinc", "a.c"]] {
+        assert_eq!(rcc(args, &dir).status.code(), Some(7), "{args:?}");
+    }
+    let output = rcc(&["-i", "a.c"], &dir);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "a.c:1:10: error: cannot find include file `seven.h`
+ 1 | #include <seven.h>
+   |          ^~~~~~~~~
+"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn macros_come_from_dash_d() {
+    let dir = temp_dir("defines");
+    fs::write(
+        dir.join("a.c"),
+        "#ifndef N\n#error N is needed\n#endif\nint main() { return N + ONE; }\n",
+    )
+    .unwrap();
+    let cases: [(&[&str], i32); 3] = [
+        (&["-D", "N=5", "-DONE"], 6),
+        (
+            &["-DN=(3 * 3)", "-D", "ONE=ONE", "-U", "ONE", "-DONE=2"],
+            11,
+        ),
+        (&["--cc", "-DN", "-DONE"], 2),
+    ];
+    for (defines, expected) in cases {
+        let args = [&["-i", "a.c"], defines].concat();
+        assert_eq!(rcc(&args, &dir).status.code(), Some(expected), "{args:?}");
+    }
+    let output = rcc(&["-i", "-DONE", "-UN", "a.c"], &dir);
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "a.c:2:1: error: #error N is needed
+ 2 | #error N is needed
+   | ^~~~~~~~~~~~~~~~~~
+"
+    );
+    let output = rcc(&["-i", "-D1N", "a.c"], &dir);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("rcc: invalid `-D1N`: expected a macro name after `#define`\n"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn translation_units_link_through_extern_declarations() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = temp_dir("translation-units");
+    fs::write(
+        dir.join("main.c"),
+        "extern int counter; int bump(void); int main() { bump(); bump(); return counter; }",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("counter.c"),
+        "int counter = 40; int bump(void) { counter++; return counter; }",
+    )
+    .unwrap();
+    for pic in [&[][..], &["-fPIC"]] {
+        let args = [pic, &["--run", "main.c", "counter.c"]].concat();
+        let output = rcc(&args, &dir);
+        assert_eq!(output.status.code(), Some(42), "{output:?}");
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn each_emit_kind_writes_its_own_file() {
+    let dir = temp_dir("emit");
+    fs::write(dir.join("a.c"), "int main() { return 1; }").unwrap();
+    let output = rcc(
+        &[
+            "--emit=tokens,tokens-json",
+            "--emit=ast,ast-json,ast-dot,c,ir",
+            "--emit=asm",
+            "a.c",
+        ],
+        &dir,
+    );
+    assert!(output.status.success(), "{output:?}");
+    assert!(output.stdout.is_empty());
+    let tokens = fs::read_to_string(dir.join("a.tokens")).unwrap();
+    assert!(tokens.starts_with("1:1: Keyword(Int)\n"), "{tokens}");
+    let json = fs::read_to_string(dir.join("a.tokens.json")).unwrap();
+    assert!(json.starts_with("[\n  {\"kind\":\"keyword\""), "{json}");
+    let ast = fs::read_to_string(dir.join("a.ast")).unwrap();
+    assert!(ast.starts_with("Program {"), "{ast}");
+    let ast_json = fs::read_to_string(dir.join("a.ast.json")).unwrap();
+    assert!(ast_json.contains("\"name\":\"main\""), "{ast_json}");
+    let ast_dot = fs::read_to_string(dir.join("a.ast.dot")).unwrap();
+    assert!(ast_dot.starts_with("digraph ast {\n"), "{ast_dot}");
+    let c = fs::read_to_string(dir.join("a.pretty.c")).unwrap();
+    assert_eq!(c, "int main() {\n    return 1;\n}\n");
+    let ir = fs::read_to_string(dir.join("a.ir")).unwrap();
+    assert!(ir.contains("fn main"), "{ir}");
+    assert!(dir.join("a.s").exists());
+    // Only what was asked for is produced.
+    assert!(!dir.join("a.out").exists());
+    assert!(!dir.join("a.ll").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn emit_kinds_print_in_pipeline_order() {
+    let dir = temp_dir("emit-stdout");
+    fs::write(dir.join("a.c"), "int main() { return 1; }").unwrap();
+    let output = rcc(&["--emit=asm,tokens", "-o", "-", "a.c"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let tokens = stdout.find("1:1: Keyword(Int)").unwrap();
+    let asm = stdout.find("main:").unwrap();
+    assert!(tokens < asm, "{stdout}");
+    assert!(!dir.join("a.tokens").exists());
+    let output = rcc(&["--emit=exe", "-o", "-", "a.c"], &dir);
+    assert!(!output.status.success());
+    let output = rcc(&["--emit=bogus", "a.c"], &dir);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("unknown `--emit` kind `bogus`"), "{stderr}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn emit_all_prints_every_stage() {
+    let dir = temp_dir("emit-all");
+    fs::write(dir.join("a.c"), "int main() { return 1; }").unwrap();
+    let output = rcc(&["--emit", "all", "-o", "-", "a.c"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stages = ["1:1: Keyword(Int)", "Program {", "fn main", "main:"].map(|marker| {
+        stdout
+            .find(marker)
+            .unwrap_or_else(|| panic!("{marker} in {stdout}"))
+    });
+    assert!(stages.is_sorted(), "{stdout}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn early_stages_are_emitted_despite_later_errors() {
+    let dir = temp_dir("emit-early");
+    fs::write(dir.join("a.c"), "int main() { return x; }").unwrap();
+    let output = rcc(&["--emit=tokens,ast", "-o", "-", "a.c"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Identifier(\"x\")"), "{stdout}");
+    assert!(stdout.contains("Program {"), "{stdout}");
+    // The error still stops anything that needs the program analyzed.
+    let output = rcc(&["--emit=tokens,ir", "-o", "-", "a.c"], &dir);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("use of undeclared identifier `x`"),
+        "{stderr}"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn timings_are_reported_per_input() {
+    let dir = temp_dir("timings");
+    fs::write(dir.join("a.c"), "int main() { return 1; }").unwrap();
+    let output = rcc(&["--timings", "-S", "a.c"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("rcc: timings for `a.c`:\n"), "{stderr}");
+    assert!(stderr.contains("\ncodegen "), "{stderr}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn time_passes_reports_what_each_stage_produced() {
+    let dir = temp_dir("time-passes");
+    fs::write(dir.join("a.c"), "int main() { return 1; }").unwrap();
+    let output = rcc(&["--time-passes", "-S", "a.c"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("rcc: timings for `a.c`:\n"), "{stderr}");
+    let lex = stderr
+        .lines()
+        .find(|line| line.starts_with("lex "))
+        .unwrap();
+    assert!(lex.ends_with("   10 tokens"), "{stderr}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn diagnostics_can_be_printed_as_json() {
+    let dir = temp_dir("json-diagnostics");
+    fs::write(
+        dir.join("a.c"),
+        "int main() {\n  int x = 1 2;\n  return x +;\n}\nint f() { return 0 }\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("w.c"),
+        "int main() {\n    int unused = 1;\n    return 0;\n}\n",
+    )
+    .unwrap();
+    let output = rcc(
+        &[
+            "--diagnostics-format",
+            "json",
+            "--max-errors=2",
+            "--color=always",
+            "-S",
+            "a.c",
+        ],
+        &dir,
+    );
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let diagnostics: Vec<Value> = stderr
+        .lines()
+        .map(|line| Value::parse(line).unwrap_or_else(|err| panic!("{err}: {line}")))
+        .collect();
+    assert_eq!(diagnostics.len(), 3, "{stderr}");
+    let first = &diagnostics[0];
+    assert_eq!(first.get("severity").and_then(Value::as_str), Some("error"));
+    assert_eq!(
+        first.get("message").and_then(Value::as_str),
+        Some("expected `;`, found integer literal `2`")
+    );
+    assert_eq!(first.get("file").and_then(Value::as_str), Some("a.c"));
+    assert_eq!(first.get("line").and_then(Value::as_u64), Some(2));
+    assert_eq!(first.get("column").and_then(Value::as_u64), Some(13));
+    // The note about the rest has no location.
+    let rest = &diagnostics[2];
+    assert_eq!(rest.get("severity").and_then(Value::as_str), Some("note"));
+    assert_eq!(rest.get("line"), Some(&Value::Null));
+
+    let output = rcc(&["--diagnostics-format=json", "-Wall", "-S", "w.c"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let warning = Value::parse(stderr.trim_end()).unwrap();
+    assert_eq!(
+        warning.get("severity").and_then(Value::as_str),
+        Some("warning")
+    );
+    assert_eq!(
+        warning.get("code").and_then(Value::as_str),
+        Some("unused-variable")
+    );
+    assert_eq!(
+        warning.get("message").and_then(Value::as_str),
+        Some("unused variable `unused`")
+    );
+
+    // Type errors in operators and literals point at them.
+    fs::write(
+        dir.join("t.c"),
+        "int main() {\n    int *p = 1 + 2;\n    return 0;\n}\n",
+    )
+    .unwrap();
+    let output = rcc(&["--diagnostics-format=json", "-S", "t.c"], &dir);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let error = Value::parse(stderr.trim_end()).unwrap();
+    assert_eq!(
+        error.get("message").and_then(Value::as_str),
+        Some("expected `int *` but found `int`")
+    );
+    assert_eq!(error.get("line").and_then(Value::as_u64), Some(2));
+    assert_eq!(error.get("column").and_then(Value::as_u64), Some(16));
+
+    let output = rcc(&["--diagnostics-format=xml", "w.c"], &dir);
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn check_reports_diagnostics_and_writes_nothing() {
+    let dir = temp_dir("check");
+    fs::write(
+        dir.join("a.c"),
+        "int main() {\n    int unused = 1;\n    return 0;\n}\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("b.c"),
+        "int main() {\n  int x = 1 2;\n  return x +;\n}\n",
+    )
+    .unwrap();
+    let output = rcc(&["--check", "-Wall", "a.c"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("a.c:2:9: warning: unused variable `unused`"),
+        "{stderr}"
+    );
+    let output = rcc(&["-fsyntax-only", "a.c", "b.c"], &dir);
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("b.c:2:13: error:"), "{stderr}");
+    assert!(stderr.contains("b.c:3:13: error:"), "{stderr}");
+    let written: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(written.len(), 2, "{written:?}");
+
+    let output = rcc(&["--check", "-S", "a.c"], &dir);
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("`--check` writes nothing"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn stats_are_reported_per_input() {
+    let dir = temp_dir("stats");
+    fs::write(dir.join("a.c"), "int main() { return 1; }").unwrap();
+    let output = rcc(&["--stats", "-S", "a.c"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("rcc: statistics for `a.c`:\n"), "{stderr}");
+    assert!(stderr.contains("\nmain "), "{stderr}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn deterministic_builds_compile_twice() {
+    let dir = temp_dir("deterministic");
+    fs::write(dir.join("a.c"), "int main() { return 6 * 7; }").unwrap();
+    let output = rcc(&["--deterministic", "-O2", "-g", "-S", "a.c"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    assert!(output.stderr.is_empty(), "{output:?}");
+    assert!(dir.join("a.s").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn watch_rebuilds_when_an_input_or_include_changes() {
+    let dir = temp_dir("watch");
+    fs::write(dir.join("a.c"), "int main() { return 1; }").unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rcc"))
+        .args(["--watch", "-S", "a.c"])
+        .current_dir(&dir)
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stderr = BufReader::new(child.stderr.take().unwrap()).lines();
+    let mut next_build = || {
+        let mut lines = Vec::new();
+        for line in stderr.by_ref() {
+            let line = line.unwrap();
+            if line == "rcc: watching for changes" {
+                return lines;
+            }
+            lines.push(line);
+        }
+        panic!("rcc exited: {lines:?}");
+    };
+    assert_eq!(next_build(), ["rcc: build succeeded"]);
+    assert!(fs::read_to_string(dir.join("a.s"))
+        .unwrap()
+        .contains("main"));
+
+    fs::write(dir.join("a.c"), "int main() { return; }").unwrap();
+    let lines = next_build();
+    assert!(lines[0].starts_with("a.c:1:"), "{lines:?}");
+
+    // Included files are watched too, even while they break the build.
+    // Each is replaced whole, so that rcc never reads half of one.
+    let replace = |name: &str, text: &str| {
+        fs::write(dir.join("new"), text).unwrap();
+        fs::rename(dir.join("new"), dir.join(name)).unwrap();
+    };
+    replace("a.h", "#error broken header\n");
+    replace("a.c", "#include \"a.h\"\nint main() { return ONE; }");
+    let lines = next_build();
+    assert!(lines[0].starts_with("a.h:1:"), "{lines:?}");
+    replace("a.h", "#define ONE 1\n");
+    assert_eq!(next_build(), ["rcc: build succeeded"]);
+    replace("a.h", "#define ONE (1 + 1)\n");
+    assert_eq!(next_build(), ["rcc: build succeeded"]);
+    child.kill().unwrap();
+    child.wait().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dash_i_interprets_main() {
+    let dir = temp_dir("interpret");
+    fs::write(
+        dir.join("a.c"),
+        "int f() { return 6; } int main() { return f() * 7; }",
+    )
+    .unwrap();
+    for flag in ["-i", "--interpret", "--interpret=ir", "--interpret=ast"] {
+        let output = rcc(&[flag, "a.c"], &dir);
+        assert_eq!(output.status.code(), Some(42), "{flag}");
+    }
+    assert!(!dir.join("a.out").exists());
+    let output = rcc(&["--interpret=asm", "a.c"], &dir);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("unknown interpreter `asm`"), "{stderr}");
+    fs::write(dir.join("b.c"), "int main() { return 1 / 0; }").unwrap();
+    let output = rcc(&["-i", "b.c"], &dir);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("b.c: undefined behavior"), "{stderr}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn std_selects_the_language() {
+    let dir = temp_dir("std");
+    fs::write(dir.join("a.c"), "main() { return 0; }").unwrap();
+    assert!(rcc(&["-S", "--std=c89", "a.c"], &dir).status.success());
+    let output = rcc(&["-S", "--std=c17", "a.c"], &dir);
+    assert!(!output.status.success());
+    let output = rcc(&["-S", "--std=c42", "a.c"], &dir);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("unknown language standard `c42`"),
+        "{stderr}"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn fvisibility_hides_the_defined_symbols() {
+    let dir = temp_dir("visibility");
+    fs::write(dir.join("a.c"), "int main() { return 0; }").unwrap();
+    let output = rcc(
+        &[
+            "-S",
+            "--target",
+            "x86_64-linux-gnu",
+            "-fvisibility=hidden",
+            "-o",
+            "-",
+            "a.c",
+        ],
+        &dir,
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(".hidden main\n"), "{stdout}");
+    let output = rcc(&["-S", "-fvisibility=protected", "a.c"], &dir);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("unknown visibility `protected`"),
+        "{stderr}"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn verbosity_and_rcc_log_trace_internal_decisions() {
+    let dir = temp_dir("log");
+    fs::write(
+        dir.join("a.c"),
+        "int f() { return 2; } int main() { return f() + f(); }",
+    )
+    .unwrap();
+    let output = rcc(&["-O2", "-vv", "-S", "a.c"], &dir);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("[INFO driver] compiling for"), "{stderr}");
+    assert!(
+        stderr.contains("[DEBUG inline] inlining `f` into `main`"),
+        "{stderr}"
+    );
+    assert!(!stderr.contains("[TRACE"), "{stderr}");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rcc"))
+        .args(["-O1", "-S", "a.c"])
+        .current_dir(&dir)
+        .env("RCC_LOG", "regalloc=debug")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("[DEBUG regalloc] `main`: %0 gets r"),
+        "{stderr}"
+    );
+    assert!(!stderr.contains("[INFO driver]"), "{stderr}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn parallel_builds_report_in_input_order() {
+    let dir = temp_dir("jobs");
+    let mut args = vec!["-j4".to_string(), "-o".to_string(), "-".to_string()];
+    for i in 0..12 {
+        let name = format!("f{i:02}.c");
+        let source = if i % 3 == 0 {
+            format!("int main() {{ return {i} +; }}")
+        } else {
+            format!("int main{i}() {{ return {i}; }}")
+        };
+        fs::write(dir.join(&name), source).unwrap();
+        args.push(name);
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let output = rcc(&args, &dir);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let defined: Vec<usize> = (0..12)
+        .filter(|i| i % 3 != 0)
+        .map(|i| stdout.find(&format!("main{i}:")).unwrap())
+        .collect();
+    assert!(defined.windows(2).all(|w| w[0] < w[1]), "{stdout}");
+    let errors: Vec<usize> = [0, 3, 6, 9]
+        .iter()
+        .map(|i| stderr.find(&format!("f{i:02}.c:1:")).unwrap())
+        .collect();
+    assert!(errors.windows(2).all(|w| w[0] < w[1]), "{stderr}");
+    let output = rcc(&["-j0", "f01.c"], &dir);
+    assert!(!output.status.success());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn reports_every_syntax_error_up_to_max_errors() {
+    let dir = temp_dir("max-errors");
+    fs::write(
+        dir.join("a.c"),
+        "int main() {\n  int x = 1 2;\n  return x +;\n}\nint f() { return 0 }\n",
+    )
+    .unwrap();
+    let output = rcc(&["-S", "a.c"], &dir);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let errors: Vec<_> = stderr
+        .lines()
+        .filter(|line| line.starts_with("a.c:"))
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            "a.c:2:13: error: expected `;`, found integer literal `2`",
+            "a.c:3:13: error: expected an expression, found `;`",
+            "a.c:5:20: error: expected `;`, found `}`",
+        ]
+    );
+    let output = rcc(&["-S", "--max-errors=1", "a.c"], &dir);
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "a.c:2:13: error: expected `;`, found integer literal `2`
+ 2 |   int x = 1 2;
+   |             ^
+rcc: note: 2 more errors not shown; `--max-errors=0` shows them all
+"
+    );
+    let output = rcc(&["-S", "--max-errors=lots", "a.c"], &dir);
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("invalid error count `lots`"));
+}
+
+#[test]
+fn warnings_are_printed_and_werror_fails_the_build() {
+    let dir = temp_dir("warnings");
+    fs::write(dir.join("a.c"), "int main() {\n  int x;\n  return 0;\n}\n").unwrap();
+    let output = rcc(&["-S", "-Wall", "a.c"], &dir);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "a.c:2:7: warning: unused variable `x` [-Wunused-variable]
+ 2 |   int x;
+   |       ^
+"
+    );
+    assert!(dir.join("a.s").exists());
+    fs::remove_file(dir.join("a.s")).unwrap();
+    let output = rcc(&["-S", "-Wall", "-Werror", "a.c"], &dir);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("a.c:2:7: error: unused variable `x` [-Werror=unused-variable]\n"));
+    assert!(!dir.join("a.s").exists());
+    for quiet in [&["-S", "a.c"][..], &["-S", "-Wall", "-w", "a.c"]] {
+        let output = rcc(quiet, &dir);
+        assert!(output.status.success());
+        assert!(output.stderr.is_empty(), "{quiet:?}");
+    }
+    let output = rcc(&["-S", "-Wshadow", "a.c"], &dir);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("unknown warning option `-Wshadow`"));
+}
@@ -0,0 +1,506 @@
+mod common;
+
+use rcc::codegen::aarch64::materialize;
+use rcc::codegen::asm::{imm, reg, Instruction, Operand};
+use rcc::codegen::CodeGenerator;
+use rcc::driver::{self, Options};
+use rcc::ir::interp::Interpreter;
+use rcc::ir::verify::verify_module;
+use rcc::ir::{BinOp, CmpOp, FunctionBuilder, IrType, Module};
+
+use common::compile;
+
+fn assembly(source: &str) -> String {
+    compile(source, &Options::default())
+        .expect("program should compile")
+        .assembly
+}
+
+/// Returns the body of `_name`, from its label up to and including `ret`.
+fn function_asm<'a>(asm: &'a str, name: &str) -> &'a str {
+    let start = asm
+        .find(&format!("_{name}:\n"))
+        .unwrap_or_else(|| panic!("no function `{name}` in:\n{asm}"));
+    let end = start + asm[start..].find("ret\n").expect("function returns") + "ret\n".len();
+    &asm[start..end]
+}
+
+#[test]
+fn adds_two_constants() {
+    let asm = assembly("int main() { return 1 + 2; }");
+    let expected = "\
+_main:
+    stp x29, x30, [sp, #-16]!
+    mov x29, sp
+    sub sp, sp, #16
+    mov w8, #1
+    mov w9, #2
+    add w8, w8, w9
+    str w8, [x29, #-4]
+    ldr w0, [x29, #-4]
+    mov sp, x29
+    ldp x29, x30, [sp], #16
+    ret
+";
+    assert_eq!(function_asm(&asm, "main"), expected);
+}
+
+#[test]
+fn selects_an_instruction_per_operator() {
+    for (op, mnemonic) in [
+        ("+", "add"),
+        ("-", "sub"),
+        ("*", "mul"),
+        ("/", "sdiv"),
+        ("&", "and"),
+        ("|", "orr"),
+        ("^", "eor"),
+    ] {
+        let asm = assembly(&format!("int main() {{ return 7 {op} 3; }}"));
+        assert!(
+            asm.contains(&format!("{mnemonic} w8, w8, w9")),
+            "{op}:\n{asm}"
+        );
+    }
+}
+
+#[test]
+fn negation_and_complement_have_their_own_instructions() {
+    let asm = assembly("int f() { return 3; } int main() { return -f() + ~f() + !f(); }");
+    let main = function_asm(&asm, "main");
+    assert!(main.contains("    neg w8, w8\n"), "{main}");
+    assert!(main.contains("    mvn w8, w8\n"), "{main}");
+    assert!(main.contains("    cmp w8, #0\n    cset w8, eq\n"), "{main}");
+}
+
+#[test]
+fn remainders_multiply_back_the_quotient() {
+    let asm = assembly("int f() { return 17; } int main() { return f() % 5; }");
+    assert!(
+        asm.contains("sdiv w16, w8, w9\n    msub w8, w16, w9, w8\n"),
+        "{asm}"
+    );
+    let ir = driver::compile("int main() { return 0 - 17 % 5; }", &Options::default())
+        .unwrap()
+        .ir;
+    assert_eq!(Interpreter::new(&ir).call("main", &[]).unwrap(), Some(-2));
+}
+
+#[test]
+fn keeps_intermediate_results_in_separate_slots() {
+    let asm = assembly("int main() { return (1 + 2) * (3 - 4) / (5 + 6 * 7); }");
+    let main = function_asm(&asm, "main");
+    // Six operations, each with its own 4-byte slot, rounded up to 16 bytes.
+    assert!(main.contains("sub sp, sp, #32"), "{main}");
+    assert!(
+        main.contains("ldr w8, [x29, #-4]\n    ldr w9, [x29, #-8]\n    mul w8, w8, w9"),
+        "{main}"
+    );
+    assert!(main.contains("ldr w0, [x29, #-24]"), "{main}");
+}
+
+#[test]
+fn calls_can_appear_on_either_side() {
+    let asm = assembly("int f() { return 4; } int main() { return f() - 10 / f(); }");
+    let main = function_asm(&asm, "main");
+    assert_eq!(main.matches("bl _f").count(), 2, "{main}");
+    assert!(main.contains("sdiv w8, w8, w9"), "{main}");
+    assert!(main.contains("sub w8, w8, w9"), "{main}");
+}
+
+/// `int f(int x) { int a = x; int b = a * 3; return a + b; }`, with locals in
+/// stack slots the way the frontend lowers them.
+fn locals_function() -> Module {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let (a, b) = (f.stack_slot(IrType::I32), f.stack_slot(IrType::I32));
+    f.store(a, x);
+    let loaded = f.load(a);
+    let three = f.iconst(IrType::I32, 3);
+    let product = f.binary(BinOp::Mul, loaded, three);
+    f.store(b, product);
+    let (lhs, rhs) = (f.load(a), f.load(b));
+    let sum = f.binary(BinOp::Add, lhs, rhs);
+    f.ret(Some(sum));
+    Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    }
+}
+
+#[test]
+fn stack_slots_live_below_the_spill_area() {
+    let asm = CodeGenerator::new().generate(&locals_function());
+    // Six spilled values take 24 bytes; the two locals follow at 28 and 32.
+    assert!(asm.contains("sub sp, sp, #32"), "{asm}");
+    assert!(
+        asm.contains("ldr w8, [x29, #-4]\n    str w8, [x29, #-28]\n"),
+        "{asm}"
+    );
+    assert!(asm.contains("str w8, [x29, #-32]"), "{asm}");
+    assert!(
+        asm.contains("ldr w8, [x29, #-28]\n    str w8, [x29, #-8]\n"),
+        "{asm}"
+    );
+}
+
+#[test]
+fn stack_slots_use_allocated_registers_directly() {
+    let asm = CodeGenerator::new()
+        .with_register_allocation(true)
+        .generate(&locals_function());
+    // Only the two locals need memory, rounded up to a 16-byte frame.
+    assert!(asm.contains("sub sp, sp, #16"), "{asm}");
+    assert!(
+        asm.contains("ldr w10, [x29, #-4]\n    ldr w11, [x29, #-8]\n    add w11, w10, w11\n"),
+        "{asm}"
+    );
+}
+
+#[test]
+fn stack_slots_round_trip_through_the_interpreter() {
+    let module = locals_function();
+    assert_eq!(verify_module(&module), Ok(()));
+    assert_eq!(Interpreter::new(&module).call("f", &[5]), Ok(Some(20)));
+    assert!(module.to_string().starts_with(
+        "fn f(i32 %0) -> i32 {\n    ss0 = stack_slot i32\n    ss1 = stack_slot i32\nbb0:\n    store ss0, %0\n    %1 = load i32 ss0\n"
+    ));
+}
+
+#[test]
+fn returns_share_a_single_epilogue() {
+    let mut f = FunctionBuilder::new("pick", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let (then_block, else_block) = (f.create_block(), f.create_block());
+    f.branch(x, then_block, else_block);
+    f.switch_to(then_block);
+    let one = f.iconst(IrType::I32, 1);
+    f.ret(Some(one));
+    f.switch_to(else_block);
+    let result = f.call("other", vec![x], Some(IrType::I32));
+    f.ret(result);
+    let module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+
+    let asm = CodeGenerator::new().generate(&module);
+    let pick = function_asm(&asm, "pick");
+    assert_eq!(pick.matches("ldp x29, x30").count(), 1, "{pick}");
+    assert!(
+        pick.contains("mov w0, #1\n    b Lpick_epilogue\nLpick_2:\n"),
+        "{pick}"
+    );
+    assert!(
+        pick.ends_with("ldr w0, [x29, #-8]\nLpick_epilogue:\n    mov sp, x29\n    ldp x29, x30, [sp], #16\n    ret\n"),
+        "{pick}"
+    );
+}
+
+#[test]
+fn single_return_falls_into_the_epilogue() {
+    let asm = assembly("int main() { return 3; }");
+    assert!(!asm.contains("epilogue"), "{asm}");
+    assert!(
+        asm.contains("mov w0, #3\n    ldp x29, x30, [sp], #16\n    ret\n"),
+        "{asm}"
+    );
+}
+
+/// `int sum10(int a, ..., int j)` and a caller passing `1..=10`.
+fn ten_argument_call() -> Module {
+    let mut callee = FunctionBuilder::new("sum10", Some(IrType::I32));
+    let params: Vec<_> = (0..10).map(|_| callee.param(IrType::I32)).collect();
+    let total = params[1..]
+        .iter()
+        .fold(params[0], |acc, &p| callee.binary(BinOp::Add, acc, p));
+    callee.ret(Some(total));
+
+    let mut caller = FunctionBuilder::new("main", Some(IrType::I32));
+    let args = (1..=10).map(|i| caller.iconst(IrType::I32, i)).collect();
+    let result = caller.call("sum10", args, Some(IrType::I32));
+    caller.ret(result);
+    Module {
+        functions: vec![callee.finish(), caller.finish()],
+        ..Module::default()
+    }
+}
+
+#[test]
+fn passes_the_first_eight_arguments_in_registers() {
+    let asm = CodeGenerator::new().generate(&ten_argument_call());
+    let main = function_asm(&asm, "main");
+    for i in 0..8 {
+        assert!(main.contains(&format!("mov w{i}, #{}\n", i + 1)), "{main}");
+    }
+    let sum10 = function_asm(&asm, "sum10");
+    assert!(
+        sum10.contains("str w0, [x29, #-4]\n    str w1, [x29, #-8]\n"),
+        "{sum10}"
+    );
+    assert!(sum10.contains("str w7, [x29, #-32]\n"), "{sum10}");
+}
+
+#[test]
+fn passes_remaining_arguments_on_the_stack() {
+    let asm = CodeGenerator::new().generate(&ten_argument_call());
+    let main = function_asm(&asm, "main");
+    // The outgoing area sits at the bottom of the frame, below the call result.
+    assert!(main.contains("sub sp, sp, #16\n"), "{main}");
+    assert!(
+        main.contains("mov w8, #9\n    str w8, [sp, #0]\n    mov w8, #10\n    str w8, [sp, #4]\n"),
+        "{main}"
+    );
+    assert!(
+        main.find("str w8, [sp, #4]") < main.find("mov w0, #1"),
+        "{main}"
+    );
+
+    // The callee finds them above its saved frame pointer and link register.
+    let sum10 = function_asm(&asm, "sum10");
+    assert!(
+        sum10.contains("ldr w8, [x29, #16]\n    str w8, [x29, #-36]\n"),
+        "{sum10}"
+    );
+    assert!(
+        sum10.contains("ldr w8, [x29, #20]\n    str w8, [x29, #-40]\n"),
+        "{sum10}"
+    );
+}
+
+#[test]
+fn stack_parameters_load_straight_into_registers() {
+    let mut f = FunctionBuilder::new("ninth", Some(IrType::I32));
+    let params: Vec<_> = (0..9).map(|_| f.param(IrType::I32)).collect();
+    let doubled = f.binary(BinOp::Add, params[8], params[8]);
+    f.ret(Some(doubled));
+    let module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+
+    let asm = CodeGenerator::new()
+        .with_register_allocation(true)
+        .generate(&module);
+    assert!(asm.contains("ldr w10, [x29, #16]\n"), "{asm}");
+    assert!(!asm.contains("str"), "{asm}");
+    assert_eq!(
+        Interpreter::new(&module).call("ninth", &[0, 0, 0, 0, 0, 0, 0, 0, 21]),
+        Ok(Some(42))
+    );
+    assert_eq!(
+        Interpreter::new(&ten_argument_call()).call("main", &[]),
+        Ok(Some(55))
+    );
+}
+
+/// Runs the `mov`/`movz`/`movn`/`movk` sequence `instructions` and returns
+/// the value left in the register.
+fn simulate_moves(instructions: &[Instruction]) -> u64 {
+    let mut reg = 0u64;
+    for instruction in instructions {
+        let (value, shift) = match instruction.operands[1] {
+            Operand::Imm(value) => (value as u64, 0),
+            Operand::ShiftedImm { value, shift } => (value, shift),
+            ref operand => panic!("unexpected {operand:?}"),
+        };
+        reg = match instruction.mnemonic.as_str() {
+            "mov" => value,
+            "movz" => value << shift,
+            "movn" => !(value << shift),
+            "movk" => (reg & !(0xffff << shift)) | (value << shift),
+            _ => panic!("unexpected {instruction:?}"),
+        };
+    }
+    reg
+}
+
+#[test]
+fn materializes_any_32_bit_constant() {
+    for value in [
+        0,
+        1,
+        -1,
+        65535,
+        65536,
+        -65536,
+        0x1234_5678,
+        -0x1234_5678,
+        i64::from(i32::MAX),
+        i64::from(i32::MIN),
+        0x0001_0001,
+    ] {
+        let lines = materialize("w8", value);
+        assert_eq!(simulate_moves(&lines) as u32, value as u32, "{lines:?}");
+        assert!(lines.len() <= 2, "{lines:?}");
+    }
+    assert_eq!(
+        materialize("w8", 42),
+        [Instruction::new("mov", vec![reg("w8"), imm(42)])]
+    );
+    let halfword = |value, shift| Operand::ShiftedImm { value, shift };
+    assert_eq!(
+        materialize("w8", 0x1234_5678),
+        [
+            Instruction::new("movz", vec![reg("w8"), halfword(0x5678, 0)]),
+            Instruction::new("movk", vec![reg("w8"), halfword(0x1234, 16)]),
+        ]
+    );
+}
+
+#[test]
+fn materializes_any_64_bit_constant() {
+    for value in [
+        0x1234_5678_9abc_def0_u64 as i64,
+        -2,
+        0xffff_0000_ffff_0000_u64 as i64,
+        0x0000_0001_0000_0000,
+        i64::MIN,
+        -0x1_0000_0001,
+    ] {
+        let lines = materialize("x8", value);
+        assert_eq!(simulate_moves(&lines), value as u64, "{lines:?}");
+    }
+    // Mostly-ones values start from `movn` to keep the sequence short.
+    assert_eq!(materialize("x8", -0x1_2345_0001).len(), 2);
+}
+
+#[test]
+fn large_constants_in_programs_use_movk() {
+    let asm = assembly("int main() { return 305419896; }");
+    assert!(
+        asm.contains("movz w0, #0x5678, lsl #0\n    movk w0, #0x1234, lsl #16\n"),
+        "{asm}"
+    );
+}
+
+/// `f(x, y)` returns `x op y`, or branches on it when `branch` is set and
+/// returns 1 or 2.
+fn comparison(op: CmpOp, branch: bool) -> Module {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let y = f.param(IrType::I32);
+    let cond = f.cmp(op, x, y);
+    if branch {
+        let (then_block, else_block) = (f.create_block(), f.create_block());
+        f.branch(cond, then_block, else_block);
+        for (block, value) in [(then_block, 1), (else_block, 2)] {
+            f.switch_to(block);
+            let result = f.iconst(IrType::I32, value);
+            f.ret(Some(result));
+        }
+    } else {
+        f.ret(Some(cond));
+    }
+    let module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+    verify_module(&module).unwrap();
+    module
+}
+
+#[test]
+fn comparisons_set_a_register_from_the_condition() {
+    for (op, cc) in [
+        (CmpOp::Eq, "eq"),
+        (CmpOp::Ne, "ne"),
+        (CmpOp::Slt, "lt"),
+        (CmpOp::Sle, "le"),
+        (CmpOp::Sgt, "gt"),
+        (CmpOp::Sge, "ge"),
+        (CmpOp::Ult, "lo"),
+        (CmpOp::Ule, "ls"),
+        (CmpOp::Ugt, "hi"),
+        (CmpOp::Uge, "hs"),
+    ] {
+        let asm = CodeGenerator::new().generate(&comparison(op, false));
+        assert!(asm.contains("    cmp w8, w9\n"), "{op}:\n{asm}");
+        assert!(
+            asm.contains(&format!("    cset w8, {cc}\n")),
+            "{op}:\n{asm}"
+        );
+    }
+}
+
+#[test]
+fn branches_test_the_flags_directly() {
+    let asm = CodeGenerator::new().generate(&comparison(CmpOp::Ult, true));
+    assert!(
+        asm.contains("    cmp w8, w9\n    b.hs Lf_2\nLf_1:\n"),
+        "{asm}"
+    );
+    assert!(!asm.contains("cset") && !asm.contains("cbnz"), "{asm}");
+}
+
+#[test]
+fn comparisons_used_elsewhere_are_still_materialized() {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let limit = f.iconst(IrType::I32, 10);
+    let cond = f.cmp(CmpOp::Sgt, x, limit);
+    let (then_block, else_block) = (f.create_block(), f.create_block());
+    f.branch(cond, then_block, else_block);
+    f.switch_to(then_block);
+    f.ret(Some(cond));
+    f.switch_to(else_block);
+    f.ret(Some(x));
+    let module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+
+    let asm = CodeGenerator::new().generate(&module);
+    assert!(asm.contains("    cmp w8, #10\n    cset w8, gt\n"), "{asm}");
+    assert!(asm.contains("    cbz w8, Lf_2\n"), "{asm}");
+}
+
+#[test]
+fn blocks_are_laid_out_to_fall_through() {
+    // bb0 jumps to bb2, which jumps to bb1: emitted as bb0, bb2, bb1 with no
+    // jumps at all.
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let (last, middle) = (f.create_block(), f.create_block());
+    f.jump(middle);
+    f.switch_to(middle);
+    f.jump(last);
+    f.switch_to(last);
+    let one = f.iconst(IrType::I32, 1);
+    f.ret(Some(one));
+    let module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+
+    let asm = CodeGenerator::new().generate(&module);
+    assert!(asm.contains("Lf_2:\nLf_1:\n    mov w0, #1\n"), "{asm}");
+    assert!(!asm.contains("    b "), "{asm}");
+}
+
+#[test]
+fn tests_against_zero_use_cbz() {
+    // `!x` and `x != 0` branch on the register without a compare.
+    for (op, mnemonic) in [(CmpOp::Eq, "cbnz"), (CmpOp::Ne, "cbz")] {
+        let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+        let x = f.param(IrType::I32);
+        let zero = f.iconst(IrType::I32, 0);
+        let cond = f.cmp(op, x, zero);
+        let (then_block, else_block) = (f.create_block(), f.create_block());
+        f.branch(cond, then_block, else_block);
+        f.switch_to(then_block);
+        f.ret(Some(x));
+        f.switch_to(else_block);
+        f.ret(Some(zero));
+        let module = Module {
+            functions: vec![f.finish()],
+            ..Module::default()
+        };
+
+        let asm = CodeGenerator::new().generate(&module);
+        assert!(
+            asm.contains(&format!("    {mnemonic} w8, Lf_2\nLf_1:\n")),
+            "{op}:\n{asm}"
+        );
+        assert!(!asm.contains("cmp"), "{op}:\n{asm}");
+    }
+}
@@ -0,0 +1,82 @@
+use std::process::Command;
+
+use rcc::color::{self, ColorChoice, Style};
+use rcc::error::Error;
+use rcc::lexer::Position;
+
+#[test]
+fn parses_choices() {
+    assert_eq!(ColorChoice::parse("auto"), Some(ColorChoice::Auto));
+    assert_eq!(ColorChoice::parse("always"), Some(ColorChoice::Always));
+    assert_eq!(ColorChoice::parse("never"), Some(ColorChoice::Never));
+    assert_eq!(ColorChoice::parse("yes"), None);
+    assert!(ColorChoice::Always.enabled(&std::io::stdout()));
+    assert!(!ColorChoice::Never.enabled(&std::io::stdout()));
+}
+
+#[test]
+fn colored_diagnostics_highlight_the_parts() {
+    let err = Error::new(Position { line: 2, column: 5 }, "bad thing");
+    assert_eq!(
+        err.render_colored("t.c", false),
+        "t.c:2:5: error: bad thing"
+    );
+    assert_eq!(err.render_colored("t.c", false), err.render("t.c"));
+    assert_eq!(
+        err.render_colored("t.c", true),
+        "\x1b[1mt.c:2:5:\x1b[0m \x1b[1;31merror:\x1b[0m \x1b[1mbad thing\x1b[0m"
+    );
+    assert_eq!(Style::BOLD.paint("", true), "");
+}
+
+#[test]
+fn listings_highlight_labels_comments_and_directives() {
+    let asm = "    .globl main\nmain:\n    # frame\n    ret\n";
+    assert_eq!(
+        color::highlight(asm, "#"),
+        "    \x1b[36m.globl main\x1b[0m\n\x1b[1mmain:\x1b[0m\n    \x1b[2m# frame\x1b[0m\n    ret\n"
+    );
+    // Without a comment prefix nothing counts as a comment.
+    assert_eq!(color::highlight("    # x\n", ""), "    # x\n");
+}
+
+#[test]
+fn the_flag_controls_the_command_line() {
+    let dir = std::env::temp_dir().join(format!("rcc-color-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("bad.c");
+    std::fs::write(&input, "int main() { return; }").unwrap();
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_rcc"))
+            .current_dir(&dir)
+            .args(args)
+            .arg(&input)
+            .env_remove("NO_COLOR")
+            .output()
+            .unwrap();
+        String::from_utf8(output.stderr).unwrap()
+    };
+    assert!(run(&["-S", "--color=always"]).contains("\x1b[1;31merror:\x1b[0m"));
+    assert!(!run(&["-S", "--color=never"]).contains('\x1b'));
+    // Automatic coloring is off when stderr is a pipe.
+    assert!(!run(&["-S"]).contains('\x1b'));
+    assert!(run(&["-S", "--color=sometimes"]).contains("unknown color choice `sometimes`"));
+
+    std::fs::write(&input, "int main() { return 0; }").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rcc"))
+        .current_dir(&dir)
+        .args([
+            "--color=always",
+            "--target",
+            "x86_64-linux",
+            "-S",
+            "-o",
+            "-",
+        ])
+        .arg(&input)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\x1b[1mmain:\x1b[0m\n"), "{stdout}");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
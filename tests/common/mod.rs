@@ -0,0 +1,123 @@
+//! Helpers shared by the integration tests. Each test crate uses only some
+//! of them.
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use rcc::driver::{self, Options};
+use rcc::fixtures::Fixture;
+use rcc::ir::interp::Interpreter;
+use rcc::opt::OptLevel;
+
+pub fn compile<'a>(source: &'a str, options: &Options) -> Result<driver::Artifacts<'a>, String> {
+    driver::compile(source, options).map_err(|err| err.to_string())
+}
+
+pub fn run_main(source: &str, opt_level: OptLevel) -> Option<i64> {
+    let options = Options {
+        opt_level,
+        ..Options::default()
+    };
+    let ir = compile(source, &options)
+        .expect("program should compile")
+        .ir;
+    Interpreter::new(&ir).call("main", &[]).unwrap()
+}
+
+/// `tests/fixtures/<name>.c`.
+fn fixture_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(format!("{name}.c"))
+}
+
+pub fn fixture(name: &str) -> Fixture {
+    Fixture::parse(&fs::read_to_string(fixture_path(name)).unwrap()).unwrap()
+}
+
+/// Checks `tests/fixtures/<name>.c` with the IR interpreter at -O0 and
+/// -O2, so that it runs on any host: it fails with the error it expects,
+/// or `main` returns its exit status. `rcc test` runs the same fixtures
+/// natively.
+pub fn check_fixture(name: &str) {
+    let (path, fixture) = (fixture_path(name), fixture(name));
+    for opt_level in [OptLevel::O0, OptLevel::O2] {
+        let options = Options {
+            opt_level,
+            ..Options::default()
+        };
+        let compiled = fixture.compile(&path, &options, |artifacts| artifacts.ir);
+        match fixture.expect(compiled) {
+            Ok(Some(ir)) => assert_eq!(
+                Interpreter::new(&ir).call("main", &[]),
+                Ok(Some(i64::from(fixture.exit))),
+                "{name} at {opt_level:?}"
+            ),
+            Ok(None) => {}
+            Err(reason) => panic!("{name} at {opt_level:?}: {reason}"),
+        }
+    }
+}
+
+/// Checks that `llvm-mc` accepts `asm` for `triple`, when it is installed.
+pub fn assert_assembles(triple: &str, asm: &str) {
+    let Ok(mut child) = Command::new("llvm-mc")
+        .args([
+            &format!("-triple={triple}"),
+            "-filetype=obj",
+            "-o",
+            "/dev/null",
+        ])
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    else {
+        return;
+    };
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(asm.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}\n{asm}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Assembles `asm` and links it with `inputs`, more sources given by file
+/// name and text, using the host C compiler, then runs it. Returns `None`
+/// off x86-64 Linux or without `cc`, and otherwise the exit code, which is
+/// `None` if the program was killed by a signal.
+pub fn run_linked(name: &str, asm: &str, inputs: &[(&str, &str)]) -> Option<Option<i32>> {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return None;
+    }
+    let dir = std::env::temp_dir().join(format!("rcc-run-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (source, exe) = (dir.join("main.s"), dir.join("main"));
+    fs::write(&source, asm).unwrap();
+    let mut cc = Command::new("cc");
+    cc.arg(&source);
+    for (file, text) in inputs {
+        fs::write(dir.join(file), text).unwrap();
+        cc.arg(dir.join(file));
+    }
+    let linked = cc.arg("-o").arg(&exe).status().ok()?;
+    assert!(linked.success(), "cc rejected:\n{asm}");
+    let status = Command::new(&exe).status().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    Some(status.code())
+}
+
+/// Runs `asm` as [`run_linked`] does, on its own, and returns its exit
+/// status, which it must exit with normally.
+pub fn run(name: &str, asm: &str) -> Option<i32> {
+    run_linked(name, asm, &[]).map(|code| code.expect("program exits normally"))
+}
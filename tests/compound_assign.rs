@@ -0,0 +1,145 @@
+mod common;
+
+use rcc::codegen::Target;
+use rcc::driver::Options;
+use rcc::opt::OptLevel;
+
+use common::{assert_assembles, compile, run_main};
+
+/// Every compound operator applied in turn to one variable.
+const ALL_OPERATORS: &str = "int main() {
+    int x = 5;
+    x += 10;
+    x -= 3;
+    x *= 4;
+    x /= 6;
+    x %= 5;
+    x <<= 4;
+    x >>= 1;
+    x |= 3;
+    x &= 14;
+    x ^= 5;
+    return x;
+}";
+
+/// The target is evaluated once: `next` runs a single time.
+const TARGET_ONCE: &str = "int calls;
+int next() { calls += 1; return calls; }
+int main() {
+    int a[3] = {1, 2, 3};
+    a[next()] *= 10;
+    return a[1] * 10 + calls;
+}";
+
+#[test]
+fn compound_assignments_update_their_targets() {
+    let cases = [
+        (ALL_OPERATORS, 15),
+        (TARGET_ONCE, 201),
+        (
+            "int main() { int x = 2; int y; y = x += 3; return x * 10 + y; }",
+            55,
+        ),
+        (
+            "int main() { int x = 2; int y = 3; x += y *= 4; return x; }",
+            14,
+        ),
+        ("int main() { int x = 1; x -= -x; return x; }", 2),
+        (
+            "int main() { int a[4] = {1, 2, 3, 4}; int *p = a; p += 3; p -= 1; return *p; }",
+            3,
+        ),
+        (
+            "int main() { int a[2] = {7, 8}; int *p = a; *p += 5; return a[0]; }",
+            12,
+        ),
+        ("int g = 3; int main() { g <<= 2; return g; }", 12),
+    ];
+    for (source, expected) in cases {
+        for level in [OptLevel::O0, OptLevel::O2] {
+            assert_eq!(
+                run_main(source, level),
+                Some(expected),
+                "{source} at {level:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn compound_assignment_operands_are_checked() {
+    let cases = [
+        (
+            "int main() { 3 += 1; return 0; }",
+            "1:14: expression is not assignable",
+        ),
+        (
+            "int main() { int a[2]; a += 1; return 0; }",
+            "1:24: array type `int[2]` is not assignable",
+        ),
+        (
+            "int main() { int x; int *p; x += p; return 0; }",
+            "1:31: expected `int` but found `int *`",
+        ),
+        (
+            "int main() { int *p; int *q; p -= q; return 0; }",
+            "1:32: expected `int *` but found `long`",
+        ),
+        (
+            "int main() { int *p; p *= 2; return 0; }",
+            "1:24: invalid operands to binary `*` (`int *` and `int`)",
+        ),
+        (
+            "enum { N }; int main() { N += 1; return 0; }",
+            "1:26: expression is not assignable",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(
+            compile(source, &Options::default()).unwrap_err(),
+            expected,
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn compound_assignment_reads_and_writes_through_one_address() {
+    let ir = compile(
+        "int main() { int a[2] = {1, 2}; int *p = a; *(p + 1) += 4; return a[1]; }",
+        &Options::default(),
+    )
+    .unwrap()
+    .ir
+    .to_string();
+    // The first load is `*(p + 1)` being read, and the store goes to the
+    // same address.
+    let load = ir
+        .lines()
+        .find(|line| line.contains("= load i32 ["))
+        .unwrap();
+    let address = &load[load.find('[').unwrap()..=load.find(']').unwrap()];
+    assert!(ir.contains(&format!("store {address}, ")), "{ir}");
+    assert_eq!(ir.matches("= load i32 [").count(), 2, "{ir}");
+}
+
+#[test]
+fn compound_assignment_assembles_for_aarch64() {
+    for (target, triple) in [
+        (Target::AARCH64_LINUX, "aarch64-linux-gnu"),
+        (Target::AARCH64_APPLE, "arm64-apple-macos"),
+    ] {
+        for source in [ALL_OPERATORS, TARGET_ONCE] {
+            let asm = compile(
+                source,
+                &Options {
+                    target,
+                    ..Options::default()
+                },
+            )
+            .unwrap()
+            .assembly;
+            assert_assembles(triple, &asm);
+        }
+    }
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use rcc::analyzer::Analyzer;
+use rcc::ast::{Expression, Statement, Type};
+use rcc::lexer::Lexer;
+use rcc::opt::OptLevel;
+use rcc::parser::Parser;
+
+use common::run_main;
+
+/// The type the analyzer records for `expr`, given variables of each
+/// integer type.
+fn type_of(expr: &str) -> Type {
+    let source = format!(
+        "long f(char c, short s, unsigned u, int i, long l, unsigned long ul) {{ return {expr}; }}"
+    );
+    let tokens = Lexer::new(&source).lex().unwrap();
+    let program = Parser::new(tokens).parse().unwrap();
+    Analyzer::new().analyze(&program).unwrap();
+    let [Statement::Return {
+        value: Some(value), ..
+    }] = program.functions[0].body.as_deref().unwrap()
+    else {
+        panic!("expected one return");
+    };
+    match value {
+        Expression::Unary { ty, .. } | Expression::Binary { ty, .. } => ty
+            .get()
+            .cloned()
+            .expect("the analyzer should record the type"),
+        other => panic!("expected an operator, found {other:?}"),
+    }
+}
+
+#[test]
+fn operators_record_their_converted_type() {
+    let cases = [
+        ("c + c", Type::Int),
+        ("s * c", Type::Int),
+        ("u + i", Type::UInt),
+        ("i - u", Type::UInt),
+        ("l + u", Type::Long),
+        ("ul + l", Type::ULong),
+        ("c << l", Type::Int),
+        ("u >> i", Type::UInt),
+        ("u < i", Type::Int),
+        ("l && c", Type::Int),
+        ("-c", Type::Int),
+        ("~u", Type::UInt),
+        ("!l", Type::Int),
+    ];
+    for (expr, expected) in cases {
+        assert_eq!(type_of(expr), expected, "{expr}");
+    }
+}
+
+#[test]
+fn parsing_leaves_the_type_unset() {
+    let tokens = Lexer::new("int main() { return 1 + 2; }").lex().unwrap();
+    let program = Parser::new(tokens).parse().unwrap();
+    let [Statement::Return {
+        value: Some(Expression::Binary { ty, .. }),
+        ..
+    }] = program.functions[0].body.as_deref().unwrap()
+    else {
+        panic!("expected one return");
+    };
+    assert_eq!(ty.get(), None);
+}
+
+/// Converts between signedness and widths the way C does: an `int`
+/// mixed with an `unsigned` is converted to `unsigned`, a `char` is
+/// promoted before it is negated, and a returned `int` is widened to
+/// `long` with its sign.
+const MIXED: &str = "long widen(int x) { return x; }
+int main() {
+    unsigned u = 3;
+    int i = -4;
+    char c = 100;
+    unsigned char b = 200;
+    int r = 0;
+    r += u + i > 0;
+    r += (i > u) * 2;
+    r += (-c == -100) * 4;
+    r += (b + b == 400) * 8;
+    r += (widen(-1) < 0) * 16;
+    r += (sizeof(c + c) == 4) * 32;
+    return r;
+}";
+
+#[test]
+fn conversions_hold_at_every_level() {
+    for opt_level in [OptLevel::O0, OptLevel::O2] {
+        assert_eq!(run_main(MIXED, opt_level), Some(63), "{opt_level:?}");
+    }
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use rcc::codegen::Target;
+use rcc::coverage::{self, Files, Profile};
+use rcc::driver::{self, Options};
+
+use common::assert_assembles;
+
+const SOURCE: &str = "int helper() {\n    return 6;\n}\n\nint unused() {\n    return 1;\n}\n\nint main() {\n    return helper() - 6;\n}\n";
+
+fn files() -> Files {
+    Files {
+        source: "/src/t.c".to_string(),
+        data: "/src/t.rcccov".to_string(),
+    }
+}
+
+fn assembly(target: Target) -> String {
+    driver::compile(
+        SOURCE,
+        &Options {
+            target,
+            coverage: Some(files()),
+            ..Options::default()
+        },
+    )
+    .expect("program should compile")
+    .assembly
+}
+
+/// A record as an instrumented program writes it.
+fn record(lines: &[u32], counts: &[u64]) -> Vec<u8> {
+    let mut bytes = coverage::header("/src/t.c", lines).into_bytes();
+    for count in counts {
+        bytes.extend(count.to_le_bytes());
+    }
+    bytes
+}
+
+#[test]
+fn profiles_sum_every_run() {
+    let mut data = record(&[2, 6, 10], &[1, 0, 1]);
+    data.extend(record(&[2, 6, 10], &[2, 0, 2]));
+    let profile = Profile::parse(&data).unwrap();
+    assert_eq!(profile.source, "/src/t.c");
+    assert_eq!(profile.runs, 2);
+    assert_eq!(
+        profile.counts.into_iter().collect::<Vec<_>>(),
+        [(2, 3), (6, 0), (10, 3)]
+    );
+}
+
+#[test]
+fn truncated_data_is_rejected() {
+    let data = record(&[2, 6], &[1]);
+    assert!(Profile::parse(&data).is_err());
+    assert!(Profile::parse(b"not coverage\n\n\n\n").is_err());
+}
+
+#[test]
+fn reports_mark_lines_that_never_ran() {
+    let profile = Profile::parse(&record(&[2, 6, 10], &[4, 0, 4])).unwrap();
+    let report = profile.annotate(SOURCE);
+    assert!(
+        report.contains("        4:    2:    return 6;\n"),
+        "{report}"
+    );
+    assert!(
+        report.contains("    #####:    6:    return 1;\n"),
+        "{report}"
+    );
+    assert!(
+        report.contains("        -:    9:int main() {\n"),
+        "{report}"
+    );
+}
+
+#[test]
+fn aarch64_increments_counters_in_memory() {
+    let asm = assembly(Target::AARCH64_APPLE);
+    assert!(
+        asm.contains("    ldr x17, [x16, #16]\n    add x17, x17, #1\n    str x17, [x16, #16]\n"),
+        "{asm}"
+    );
+    assert!(asm.contains("__DATA,__mod_init_func"), "{asm}");
+    assert_assembles("arm64-apple-macos", &asm);
+    assert_assembles("aarch64-linux-gnu", &assembly(Target::AARCH64_LINUX));
+}
+
+#[test]
+fn instrumented_programs_write_counts_for_the_report() {
+    let asm = assembly(Target::X86_64_LINUX);
+    assert!(asm.contains("    incq __rcc_coverage_counters_"), "{asm}");
+    assert!(asm.contains("    .section .init_array,\"aw\"\n"), "{asm}");
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("rcc-coverage-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("t.c");
+    fs::write(&input, SOURCE).unwrap();
+    let status = Command::new(env!("CARGO_BIN_EXE_rcc"))
+        .current_dir(&dir)
+        .args(["-S", "--coverage"])
+        .arg(&input)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let exe = dir.join("t");
+    let Ok(linked) = Command::new("cc")
+        .arg(dir.join("t.s"))
+        .arg("-o")
+        .arg(&exe)
+        .status()
+    else {
+        return;
+    };
+    assert!(linked.success());
+    for _ in 0..3 {
+        assert_eq!(Command::new(&exe).status().unwrap().code(), Some(0));
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rcc"))
+        .current_dir(&dir)
+        .args(["cov", "report"])
+        .arg(dir.join("t.rcccov"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let report = String::from_utf8(output.stdout).unwrap();
+    assert!(report.contains("Runs:3\n"), "{report}");
+    assert!(
+        report.contains("        3:    2:    return 6;\n"),
+        "{report}"
+    );
+    assert!(
+        report.contains("    #####:    6:    return 1;\n"),
+        "{report}"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
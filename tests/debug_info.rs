@@ -0,0 +1,364 @@
+mod common;
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use rcc::codegen::aarch64::Aarch64;
+use rcc::codegen::{CodeGenerator, SourceFile, Target};
+use rcc::driver::{self, Options};
+use rcc::ir::{FunctionBuilder, IrType, Module};
+use rcc::lexer::Position;
+use rcc::opt::OptLevel;
+
+use common::assert_assembles;
+
+const SOURCE: &str =
+    "int helper() {\n    return 6 * 7;\n}\n\nint main() {\n    return helper() - 42;\n}\n";
+
+fn source_file() -> SourceFile {
+    SourceFile {
+        name: "debug.c".to_string(),
+        directory: "/src".to_string(),
+    }
+}
+
+fn compile(target: Target, debug_info: Option<SourceFile>) -> driver::Artifacts<'static> {
+    driver::compile(
+        SOURCE,
+        &Options {
+            target,
+            debug_info,
+            ..Options::default()
+        },
+    )
+    .expect("program should compile")
+}
+
+fn line_tables(target: Target, source: SourceFile) -> String {
+    driver::compile(
+        SOURCE,
+        &Options {
+            target,
+            debug_info: Some(source),
+            line_tables_only: true,
+            ..Options::default()
+        },
+    )
+    .expect("program should compile")
+    .assembly
+}
+
+#[test]
+fn statements_get_line_directives() {
+    let asm = compile(Target::AARCH64_APPLE, Some(source_file())).assembly;
+    assert!(asm.starts_with("    .file 1 \"debug.c\"\n"), "{asm}");
+    assert!(
+        asm.contains("_main:\nLmain_begin:\n    .cfi_startproc\n    .loc 1 5 5\n    stp x29, x30"),
+        "{asm}"
+    );
+    assert!(asm.contains("    .loc 1 6 5 prologue_end\n"), "{asm}");
+    assert!(asm.contains("Lmain_end:\n"), "{asm}");
+    assert!(
+        asm.contains("    .section __DWARF,__debug_info,regular,debug\n"),
+        "{asm}"
+    );
+    assert_assembles("arm64-apple-macos", &asm);
+}
+
+#[test]
+fn calls_get_their_own_column() {
+    let asm = line_tables(Target::AARCH64_LINUX, source_file());
+    assert!(
+        asm.contains("    .loc 1 6 5 prologue_end\n    .loc 1 6 12\n    bl helper\n"),
+        "{asm}"
+    );
+}
+
+#[test]
+fn line_tables_alone_leave_out_the_debug_sections() {
+    let asm = line_tables(Target::AARCH64_APPLE, source_file());
+    assert!(asm.contains("    .file 1 \"debug.c\"\n"), "{asm}");
+    assert!(
+        asm.contains("_main:\n    .cfi_startproc\n    .loc 1 5 5\n"),
+        "{asm}"
+    );
+    assert!(!asm.contains("__DWARF"), "{asm}");
+    assert!(!asm.contains("Lmain_end"), "{asm}");
+    assert_assembles("arm64-apple-macos", &asm);
+}
+
+#[test]
+fn frames_are_described_for_unwinding() {
+    let asm = compile(Target::X86_64_LINUX, Some(source_file())).assembly;
+    assert!(
+        asm.contains(
+            "    pushq %rbp
+    .cfi_def_cfa_offset 16
+    .cfi_offset %rbp, -16
+    movq %rsp, %rbp
+    .cfi_def_cfa_register %rbp
+"
+        ),
+        "{asm}"
+    );
+    assert!(
+        asm.contains(
+            "    .cfi_remember_state
+    leave
+    .cfi_def_cfa %rsp, 8
+    ret
+    .cfi_restore_state
+    .cfi_endproc
+"
+        ),
+        "{asm}"
+    );
+    assert_eq!(asm.matches(".cfi_startproc").count(), 2, "{asm}");
+    assert_assembles("x86_64-linux-gnu", &asm);
+
+    let asm = line_tables(Target::AARCH64_LINUX, source_file());
+    assert!(
+        asm.contains(
+            "    stp x29, x30, [sp, #-16]!
+    .cfi_def_cfa_offset 16
+    .cfi_offset x30, -8
+    .cfi_offset x29, -16
+    mov x29, sp
+    .cfi_def_cfa x29, 16
+"
+        ),
+        "{asm}"
+    );
+    assert!(
+        asm.contains(
+            "    ldp x29, x30, [sp], #16
+    .cfi_def_cfa sp, 0
+    .cfi_restore x30
+    .cfi_restore x29
+    ret
+"
+        ),
+        "{asm}"
+    );
+    assert_assembles("aarch64-linux-gnu", &asm);
+}
+
+#[test]
+fn saved_registers_are_described() {
+    // The first call's result is kept in a callee-saved register across
+    // the second, which sits 16 bytes below the frame record.
+    let source = "int f(int a);\nint g(int a, int b) { return f(a) + f(b); }\n";
+    let cases = [
+        (
+            Target::X86_64_LINUX,
+            "    movq %rbx, -8(%rbp)\n    .cfi_offset %rbx, -24\n",
+        ),
+        (
+            Target::AARCH64_LINUX,
+            "    str x19, [x29, #-16]\n    .cfi_offset x19, -32\n",
+        ),
+    ];
+    for (target, save) in cases {
+        let asm = driver::compile(
+            source,
+            &Options {
+                target,
+                opt_level: OptLevel::O2,
+                debug_info: Some(source_file()),
+                ..Options::default()
+            },
+        )
+        .expect("program should compile")
+        .assembly;
+        assert!(asm.contains(save), "{asm}");
+    }
+}
+
+#[test]
+fn without_debug_info_there_is_no_cfi() {
+    let asm = compile(Target::X86_64_LINUX, None).assembly;
+    assert!(!asm.contains(".cfi_"), "{asm}");
+}
+
+#[test]
+fn objdump_interleaves_the_source_lines() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("rcc-line-tables-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (c, asm, object) = (
+        dir.join("lines.c"),
+        dir.join("lines.s"),
+        dir.join("lines.o"),
+    );
+    fs::write(&c, SOURCE).unwrap();
+    let source = SourceFile {
+        name: c.display().to_string(),
+        directory: dir.display().to_string(),
+    };
+    fs::write(&asm, line_tables(Target::X86_64_LINUX, source)).unwrap();
+    let Ok(assembled) = Command::new("cc")
+        .arg("-c")
+        .arg(&asm)
+        .arg("-o")
+        .arg(&object)
+        .status()
+    else {
+        return;
+    };
+    assert!(assembled.success());
+    if let Ok(output) = Command::new("objdump").arg("-S").arg(&object).output() {
+        let listing = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            listing.contains("    return helper() - 42;\n") && listing.contains("call"),
+            "{listing}"
+        );
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn without_debug_info_nothing_is_emitted() {
+    let artifacts = compile(Target::AARCH64_LINUX, None);
+    assert!(!artifacts.ir.to_string().contains("loc"));
+    assert!(
+        !artifacts.assembly.contains(".loc"),
+        "{}",
+        artifacts.assembly
+    );
+    assert!(
+        !artifacts.assembly.contains(".debug_"),
+        "{}",
+        artifacts.assembly
+    );
+}
+
+#[test]
+fn locations_print_in_the_ir() {
+    let ir = compile(Target::AARCH64_LINUX, Some(source_file())).ir;
+    assert!(ir.to_string().contains("bb0:\n    loc 6:5\n"), "{ir}");
+}
+
+#[test]
+fn variables_are_located_from_the_frame_pointer() {
+    let mut b = FunctionBuilder::new("main", Some(IrType::I32));
+    let pos = Position { line: 2, column: 9 };
+    b.set_pos(Position { line: 1, column: 5 });
+    let slot = b.stack_slot(IrType::I32);
+    b.variable("answer", slot, pos);
+    b.loc(pos);
+    let value = b.iconst(IrType::I32, 42);
+    b.store(slot, value);
+    let loaded = b.load(slot);
+    b.ret(Some(loaded));
+    let module = Module {
+        functions: vec![b.finish()],
+        ..Module::default()
+    };
+    let asm = CodeGenerator::new()
+        .with_backend(&Aarch64::LINUX)
+        .with_debug_info(source_file())
+        .generate(&module);
+    assert!(
+        asm.contains("    .section .debug_info,\"\",%progbits\n"),
+        "{asm}"
+    );
+    // DW_OP_fbreg -8, where the store puts it.
+    assert!(asm.contains("    str w8, [x29, #-8]\n"), "{asm}");
+    assert!(
+        asm.contains("    .asciz \"answer\"\n    .byte 1\n    .long 2\n"),
+        "{asm}"
+    );
+    assert!(
+        asm.contains("    .uleb128 2\n    .byte 0x91, 0x78\n"),
+        "{asm}"
+    );
+    assert_assembles("aarch64-linux-gnu", &asm);
+
+    let Some(dump) = dwarfdump(&asm, "aarch64-linux-gnu") else {
+        return;
+    };
+    assert!(dump.contains("DW_AT_name\t(\"answer\")"), "{dump}");
+    assert!(dump.contains("DW_AT_location\t(DW_OP_fbreg -8)"), "{dump}");
+    assert!(
+        dump.contains("DW_AT_frame_base\t(DW_OP_reg29 W29)"),
+        "{dump}"
+    );
+}
+
+#[test]
+fn linked_programs_carry_a_valid_line_table() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let asm = compile(Target::X86_64_LINUX, Some(source_file())).assembly;
+    let dir = std::env::temp_dir().join(format!("rcc-debug-info-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (source, exe) = (dir.join("debug.s"), dir.join("debug"));
+    fs::write(&source, &asm).unwrap();
+    let Ok(linked) = Command::new("cc").arg(&source).arg("-o").arg(&exe).status() else {
+        return;
+    };
+    assert!(linked.success(), "{asm}");
+    assert_eq!(Command::new(&exe).status().unwrap().code(), Some(0));
+
+    if let Ok(verify) = Command::new("llvm-dwarfdump")
+        .arg("--verify")
+        .arg(&exe)
+        .output()
+    {
+        assert!(
+            verify.status.success(),
+            "{}",
+            String::from_utf8_lossy(&verify.stdout)
+        );
+        let lines = debug_line(&exe);
+        assert!(
+            lines.contains("      2      5      1   0             0  is_stmt prologue_end"),
+            "{lines}"
+        );
+        assert!(
+            lines.contains("      6      5      1   0             0  is_stmt prologue_end"),
+            "{lines}"
+        );
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+fn debug_line(object: &Path) -> String {
+    let output = Command::new("llvm-dwarfdump")
+        .arg("--debug-line")
+        .arg(object)
+        .output()
+        .unwrap();
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+/// Assembles `asm` and dumps its `.debug_info`, if `llvm-mc` and
+/// `llvm-dwarfdump` are installed.
+fn dwarfdump(asm: &str, triple: &str) -> Option<String> {
+    let object = std::env::temp_dir().join(format!("rcc-dwarfdump-{}.o", std::process::id()));
+    let mut child = Command::new("llvm-mc")
+        .args([&format!("-triple={triple}"), "-filetype=obj", "-o"])
+        .arg(&object)
+        .stdin(Stdio::piped())
+        .spawn()
+        .ok()?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(asm.as_bytes())
+        .unwrap();
+    assert!(child.wait().unwrap().success());
+    let output = Command::new("llvm-dwarfdump")
+        .arg("--debug-info")
+        .arg(&object)
+        .output()
+        .ok()?;
+    fs::remove_file(&object).unwrap();
+    Some(String::from_utf8_lossy(&output.stdout).into_owned())
+}
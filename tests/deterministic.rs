@@ -0,0 +1,67 @@
+use rcc::codegen::{SourceFile, Target};
+use rcc::driver::{self, Artifacts, Options};
+use rcc::opt::OptLevel;
+
+const SOURCE: &str = "int square() { return 7 * 7; }\n\nvoid nothing() { return; }\n\nint main() {\n    asm volatile(\"nop\");\n    return square() + 0 - square() / 1 % 5;\n}\n";
+
+fn compile(options: &Options) -> Artifacts<'static> {
+    driver::compile(SOURCE, options).expect("program should compile")
+}
+
+#[test]
+fn every_configuration_compiles_the_same_twice() {
+    for target in [Target::X86_64_LINUX, Target::AARCH64_APPLE] {
+        for opt_level in [OptLevel::O0, OptLevel::O2, OptLevel::Os] {
+            let options = Options {
+                target,
+                opt_level,
+                debug_info: Some(SourceFile {
+                    name: "t.c".to_string(),
+                    directory: "/src".to_string(),
+                }),
+                ..Options::default()
+            };
+            let difference = driver::first_difference(&compile(&options), &compile(&options));
+            assert_eq!(difference, None, "{target:?} at {opt_level:?}");
+        }
+    }
+}
+
+#[test]
+fn differences_name_the_artifact_and_line() {
+    let options = Options::default();
+    let first = compile(&options);
+    let mut second = compile(&options);
+    second.assembly = second.assembly.replacen("main", "niam", 1);
+    let difference = driver::first_difference(&first, &second).unwrap();
+    assert!(
+        difference.starts_with("the assembly differs at line "),
+        "{difference}"
+    );
+    assert!(difference.contains("\n    first:  "), "{difference}");
+    assert!(difference.contains("niam"), "{difference}");
+
+    second.assembly = format!("{}    nop\n", first.assembly);
+    let difference = driver::first_difference(&first, &second).unwrap();
+    assert!(
+        difference.ends_with("\n    first:  <end>\n    second:     nop"),
+        "{difference}"
+    );
+}
+
+#[test]
+fn differences_in_assembly_name_the_source_line() {
+    let options = Options {
+        target: Target::X86_64_LINUX,
+        source_map: true,
+        ..Options::default()
+    };
+    let first = compile(&options);
+    let mut second = compile(&options);
+    second.assembly = second.assembly.replacen("call square", "call other", 1);
+    let difference = driver::first_difference(&first, &second).unwrap();
+    assert!(
+        difference.ends_with("second:     call other\n    from source line 7"),
+        "{difference}"
+    );
+}
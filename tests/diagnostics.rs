@@ -0,0 +1,156 @@
+use rcc::driver::{self, Options};
+use rcc::error::{Error, Severity};
+use rcc::json::Value;
+use rcc::lexer::{Lexer, Position, TokenKind};
+use rcc::preprocess::FileError;
+
+fn snippet(source: &str) -> String {
+    let err = driver::compile(source, &Options::default()).expect_err("program should not compile");
+    err.render_snippet("t.c", source, false)
+}
+
+#[test]
+fn lexer_errors_underline_the_literal() {
+    assert_eq!(
+        snippet("int main() {\n  return 0x1z;\n}\n"),
+        "t.c:2:10: error: invalid integer literal `0x1z`\n 2 |   return 0x1z;\n   |          ^~~~"
+    );
+}
+
+#[test]
+fn parser_errors_underline_the_unexpected_token() {
+    let rendered = snippet("int main() {\n  return 1 2;\n}\n");
+    assert!(
+        rendered.ends_with("\n 2 |   return 1 2;\n   |            ^"),
+        "{rendered}"
+    );
+}
+
+#[test]
+fn analyzer_errors_underline_the_identifier() {
+    let rendered = snippet("int main() {\n  return count + 1;\n}\n");
+    assert!(
+        rendered.ends_with("\n 2 |   return count + 1;\n   |          ^~~~~"),
+        "{rendered}"
+    );
+}
+
+#[test]
+fn assignment_errors_underline_the_target() {
+    assert_eq!(
+        snippet("int main() {\n  int a[2];\n  (a) = 0;\n}\n"),
+        "t.c:3:3: error: array type `int[2]` is not assignable\n 3 |   (a) = 0;\n   |   ^~~"
+    );
+    let rendered = snippet("int main() {\n  int x;\n  x + 1 = 2;\n}\n");
+    assert!(
+        rendered.ends_with("\n 3 |   x + 1 = 2;\n   |   ^~~~~"),
+        "{rendered}"
+    );
+}
+
+#[test]
+fn underlines_line_up_after_tabs() {
+    let source = "int main() {\n\treturn 1 +;\n}\n";
+    let rendered = snippet(source);
+    assert!(
+        rendered.ends_with("\n 2 | \treturn 1 +;\n   | \t          ^"),
+        "{rendered}"
+    );
+}
+
+#[test]
+fn errors_without_a_line_render_alone() {
+    let err = Error::new(Position { line: 9, column: 1 }, "far away");
+    assert_eq!(
+        err.render_snippet("t.c", "int x;\n", false),
+        err.render("t.c")
+    );
+    let err = Error::msg("nowhere");
+    assert_eq!(
+        err.render_snippet("t.c", "int x;\n", false),
+        "t.c: error: nowhere"
+    );
+}
+
+#[test]
+fn snippets_are_colored_like_the_rest() {
+    let err = Error::new(Position { line: 1, column: 5 }, "bad");
+    assert_eq!(
+        err.render_snippet("t.c", "int x;", true),
+        format!(
+            "{}\n \x1b[2m1\x1b[0m \x1b[2m|\x1b[0m int x;\n   \x1b[2m|\x1b[0m     \x1b[1;31m^\x1b[0m",
+            err.render_colored("t.c", true)
+        )
+    );
+}
+
+#[test]
+fn diagnostics_serialize_as_json() {
+    let source = "int x;\nint main() { int x = 1; int x = 2; return x; }\n";
+    let err = driver::compile(source, &Options::default()).unwrap_err();
+    let located = |error: Error| FileError {
+        file: "t.c".to_string(),
+        line: error
+            .pos
+            .and_then(|pos| source.lines().nth(pos.line as usize - 1))
+            .map(str::to_string),
+        error,
+        note: None,
+    };
+    let note = err.note.clone().map(|note| Box::new(located(*note)));
+    let err = FileError {
+        note,
+        ..located(err)
+    };
+    assert_eq!(
+        err.to_json(Severity::Error, None).to_string(),
+        concat!(
+            r#"{"severity":"error","code":null,"message":"redefinition of `x`","file":"t.c","#,
+            r#""line":2,"column":29,"span":{"start":{"line":2,"column":29},"end":{"line":2,"column":30}},"#,
+            r#""notes":[{"severity":"note","code":null,"message":"previous declaration is here","#,
+            r#""file":"t.c","line":2,"column":18,"span":{"start":{"line":2,"column":18},"#,
+            r#""end":{"line":2,"column":19}},"notes":[]}]}"#
+        )
+    );
+    let nowhere = located(Error::msg("nowhere")).to_json(Severity::Warning, Some("w"));
+    assert_eq!(nowhere.get("line"), Some(&Value::Null));
+    assert_eq!(nowhere.get("span"), Some(&Value::Null));
+    assert_eq!(nowhere.get("code").and_then(Value::as_str), Some("w"));
+}
+
+#[test]
+fn underlines_end_after_the_token_unless_the_end_is_known() {
+    let err = Error::new(Position { line: 1, column: 5 }, "bad");
+    let end = err.underline_end(Some("int count;")).unwrap();
+    assert_eq!((end.line, end.column), (1, 10));
+    assert_eq!(err.underline_end(None), None);
+    let start = Position { line: 1, column: 1 };
+    let end = Position { line: 1, column: 4 };
+    let err = Error::spanning(rcc::lexer::Span { start, end }, "bad");
+    assert_eq!(err.underline_end(None), Some(end));
+}
+
+#[test]
+fn tokens_know_where_they_end() {
+    let tokens = Lexer::new("int  main\n(").lex().unwrap();
+    let spans: Vec<_> = tokens
+        .iter()
+        .map(|token| {
+            let span = token.span();
+            (
+                (span.start.line, span.start.column),
+                (span.end.line, span.end.column),
+            )
+        })
+        .collect();
+    assert_eq!(
+        spans,
+        [
+            ((1, 1), (1, 4)),
+            ((1, 6), (1, 10)),
+            ((2, 1), (2, 2)),
+            ((2, 2), (2, 2))
+        ]
+    );
+    assert!(matches!(tokens.last().unwrap().kind, TokenKind::Eof));
+}
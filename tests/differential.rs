@@ -0,0 +1,86 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use rcc::codegen::Target;
+use rcc::differential::{self, Verdict};
+use rcc::driver::Options;
+
+/// A fresh directory for one test's files.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rcc-diff-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn rcc_diff(dir: &PathBuf, cc: Option<&str>) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rcc"));
+    command.args(["diff", "-O1", "a.c"]).current_dir(dir);
+    if let Some(cc) = cc {
+        command.env("RCC_CC", cc);
+    }
+    command.output().unwrap()
+}
+
+#[test]
+fn both_compilers_agree_on_supported_programs() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = temp_dir("same");
+    let input = dir.join("a.c");
+    let source =
+        "int f() {\n    return 7;\n}\nint main() {\n    return f() * 6 - 100 / 7 % 3;\n}\n";
+    fs::write(&input, source).unwrap();
+    let options = Options {
+        target: Target::X86_64_LINUX,
+        ..Options::default()
+    };
+    match differential::compare(&input, source, &options).unwrap() {
+        Verdict::Same(execution) => assert_eq!(execution.status, Some(40)),
+        verdict => panic!("{verdict:?}"),
+    }
+    let output = rcc_diff(&dir, None);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.contains("same a.c: exit 40"), "{stdout}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn divergences_are_reported() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = temp_dir("differ");
+    fs::write(dir.join("a.c"), "int main() {\n    return 42;\n}\n").unwrap();
+    // A system compiler that quietly adds one to every return value.
+    fs::write(dir.join("skew.h"), "#define return return 1 +\n").unwrap();
+    let cc = dir.join("skewed-cc");
+    fs::write(
+        &cc,
+        format!(
+            "#!/bin/sh\nexec cc -include {} \"$@\"\n",
+            dir.join("skew.h").display()
+        ),
+    )
+    .unwrap();
+    Command::new("chmod").arg("+x").arg(&cc).status().unwrap();
+    let output = rcc_diff(&dir, Some(cc.to_str().unwrap()));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!output.status.success(), "{stdout}");
+    assert!(stdout.contains("DIFFER a.c:\n    rcc: exit 42"), "{stdout}");
+    assert!(stdout.contains("    cc:  exit 43"), "{stdout}");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn programs_rcc_rejects_are_errors() {
+    let dir = temp_dir("error");
+    fs::write(dir.join("a.c"), "int main() { return; }").unwrap();
+    let output = rcc_diff(&dir, None);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!output.status.success());
+    assert!(stdout.contains("ERROR a.c: a.c:1:"), "{stdout}");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use rcc::driver::Options;
+use rcc::repl::{Outcome, Session};
+
+use common::{check_fixture, compile};
+
+/// File-scope enums, one using another's constants, an `enum` type for a
+/// parameter and return value, an enum in a block whose constant a local
+/// variable shadows, and constants used as an array index.
+#[test]
+fn enumerators_are_integer_constants() {
+    check_fixture("enums");
+    check_fixture("enum_constants");
+}
+
+#[test]
+fn enumerators_fold_to_constants() {
+    let ir = compile(
+        "enum { N = 42 }; int main() { return N; }",
+        &Options::default(),
+    )
+    .unwrap()
+    .ir
+    .to_string();
+    assert!(ir.contains("= const i32 42\n"), "{ir}");
+    assert!(!ir.contains("stack_slot"), "{ir}");
+}
+
+#[test]
+fn enum_declarations_are_checked() {
+    let cases = [
+        (
+            "enum { X, X }; int main() { return 0; }",
+            "1:11: redefinition of `X`",
+        ),
+        (
+            "int main() { int n = 1; enum { X = n }; return X; }",
+            "1:32: value of enumerator `X` is not an integer constant",
+        ),
+        (
+            "enum { X = 2147483647, Y }; int main() { return 0; }",
+            "1:24: value of enumerator `Y` is out of range of `int`",
+        ),
+        (
+            "enum { X = 1 / 0 }; int main() { return 0; }",
+            "1:8: value of enumerator `X` is not an integer constant",
+        ),
+        (
+            "int main() { return X; } enum { X };",
+            "1:21: use of undeclared identifier `X`",
+        ),
+        (
+            "enum { X }; int main() { X = 1; return 0; }",
+            "1:26: expression is not assignable",
+        ),
+        (
+            "enum { X }; int main() { return &X == 0; }",
+            "1:33: cannot take the address of an rvalue",
+        ),
+        (
+            "enum { X }; int main() { return X(); }",
+            "1:33: called object `X` is not a function",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(
+            compile(source, &Options::default()).unwrap_err(),
+            expected,
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn repl_keeps_enums() {
+    let mut session = Session::new(Options::default());
+    assert_eq!(
+        session.eval("enum { TEN = 10 };"),
+        Ok(Outcome::Defined(vec!["TEN".to_string()]))
+    );
+    assert_eq!(session.eval("TEN + 1"), Ok(Outcome::Value(11)));
+}
@@ -0,0 +1,102 @@
+mod common;
+
+use rcc::codegen::Target;
+use rcc::driver::Options;
+use rcc::ir::llvm;
+use rcc::opt::OptLevel;
+
+use common::{assert_assembles, compile, run_main};
+
+#[test]
+fn extern_declarations_share_the_definition() {
+    let cases = [
+        ("extern int x; int x = 3; int main() { return x; }", 3),
+        ("extern int x; int main() { return x; } int x = 5;", 5),
+        (
+            "int x = 1; extern int x; extern int x; int main() { x += 2; return x; }",
+            3,
+        ),
+        ("extern int x = 4; int main() { return x; }", 4),
+        (
+            "extern int f(int); int main() { return f(6); } extern int f(int n) { return n * 7; }",
+            42,
+        ),
+    ];
+    for (source, expected) in cases {
+        for level in [OptLevel::O0, OptLevel::O2] {
+            assert_eq!(
+                run_main(source, level),
+                Some(expected),
+                "{source} at {level:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn extern_declarations_are_checked() {
+    let cases = [
+        (
+            "extern int x; long x; int main() { return 0; }",
+            "1:20: conflicting types for `x`",
+        ),
+        (
+            "extern int x = 1; int x = 2; int main() { return 0; }",
+            "1:23: redefinition of `x`",
+        ),
+        (
+            "int f(); extern int f; int main() { return 0; }",
+            "1:21: redefinition of `f` as a different kind of symbol",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(
+            compile(source, &Options::default()).unwrap_err(),
+            expected,
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn extern_variables_are_left_to_the_linker() {
+    let artifacts = compile(
+        "extern int x; extern _Thread_local int t; int main() { return x + t; }",
+        &Options::default(),
+    )
+    .unwrap();
+    assert!(artifacts.ir.globals.is_empty(), "{}", artifacts.ir);
+    let ll = llvm::emit(&artifacts.ir);
+    assert!(ll.contains("@x = external global i8"), "{ll}");
+    assert!(ll.contains("@t = external thread_local global i8"), "{ll}");
+    for symbol in [".data", ".bss", ".tbss"] {
+        assert!(
+            !artifacts.assembly.contains(symbol),
+            "{}",
+            artifacts.assembly
+        );
+    }
+}
+
+#[test]
+fn extern_variables_assemble_for_aarch64() {
+    let source = "extern int x; extern _Thread_local int t; int main() { return x + t; }";
+    for (target, triple) in [
+        (Target::AARCH64_LINUX, "aarch64-linux-gnu"),
+        (Target::AARCH64_APPLE, "arm64-apple-macos"),
+    ] {
+        for pic in [false, true] {
+            let asm = compile(
+                source,
+                &Options {
+                    target,
+                    pic,
+                    ..Options::default()
+                },
+            )
+            .unwrap()
+            .assembly;
+            assert_assembles(triple, &asm);
+        }
+    }
+}
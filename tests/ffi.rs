@@ -0,0 +1,112 @@
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::ptr;
+
+use rcc::ffi::{
+    rcc_compile, rcc_result_assembly, rcc_result_diagnostics, rcc_result_free, rcc_version,
+};
+
+/// Compiles `source` through the C interface, returning the assembly or the
+/// diagnostics.
+fn compile(source: &str, target: Option<&str>) -> Result<String, String> {
+    let file = CString::new("t.c").unwrap();
+    let target = target.map(|triple| CString::new(triple).unwrap());
+    unsafe {
+        let result = rcc_compile(
+            source.as_ptr().cast(),
+            source.len(),
+            file.as_ptr(),
+            target
+                .as_ref()
+                .map_or(ptr::null(), |triple| triple.as_ptr()),
+            1,
+        );
+        let assembly = rcc_result_assembly(result);
+        let output = if assembly.is_null() {
+            Err(CStr::from_ptr(rcc_result_diagnostics(result))
+                .to_string_lossy()
+                .into_owned())
+        } else {
+            Ok(CStr::from_ptr(assembly).to_string_lossy().into_owned())
+        };
+        rcc_result_free(result);
+        output
+    }
+}
+
+#[test]
+fn compiles_a_buffer_to_assembly() {
+    let asm = compile("int main() { return 6 * 7; }", Some("aarch64-apple-darwin")).unwrap();
+    assert!(asm.contains("_main:"), "{asm}");
+    // The buffer need not be NUL-terminated.
+    let asm = compile("int main() { return 1; }garbage", None);
+    assert!(asm.is_err());
+}
+
+#[test]
+fn errors_come_back_as_rendered_diagnostics() {
+    let diagnostics = compile("int main() { return; }", None).unwrap_err();
+    assert!(diagnostics.starts_with("t.c:1:"), "{diagnostics}");
+    assert!(diagnostics.contains("error:"), "{diagnostics}");
+    let diagnostics = compile("int main() { return 0; }", Some("sparc-sun-solaris")).unwrap_err();
+    assert!(diagnostics.contains("unknown target"), "{diagnostics}");
+}
+
+#[test]
+fn the_version_is_the_crate_version() {
+    let version = unsafe { CStr::from_ptr(rcc_version()) };
+    assert_eq!(version.to_str().unwrap(), env!("CARGO_PKG_VERSION"));
+}
+
+#[test]
+fn c_programs_can_use_the_header_and_static_library() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let library = Path::new(env!("CARGO_BIN_EXE_rcc")).with_file_name("librcc.a");
+    let dir = std::env::temp_dir().join(format!("rcc-ffi-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let driver = dir.join("driver.c");
+    fs::write(
+        &driver,
+        r#"#include <stdio.h>
+#include <string.h>
+#include "rcc.h"
+
+int main(void) {
+    const char *source = "int main() { return 3; }";
+    rcc_result *result = rcc_compile(source, strlen(source), "in.c", NULL, RCC_O2);
+    const char *assembly = rcc_result_assembly(result);
+    if (!assembly) {
+        fputs(rcc_result_diagnostics(result), stderr);
+        return 1;
+    }
+    fputs(assembly, stdout);
+    rcc_result_free(result);
+    return 0;
+}
+"#,
+    )
+    .unwrap();
+    let exe = dir.join("driver");
+    let Ok(built) = Command::new("cc")
+        .arg("-I")
+        .arg(root.join("include"))
+        .arg(&driver)
+        .arg(&library)
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&exe)
+        .status()
+    else {
+        return;
+    };
+    assert!(built.success());
+    let output = Command::new(&exe).output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let asm = String::from_utf8(output.stdout).unwrap();
+    assert!(asm.contains("main:"), "{asm}");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,4 @@
+// expect-exit: 42
+int main() {
+    return 6 * 7;
+}
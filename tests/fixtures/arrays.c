@@ -0,0 +1,14 @@
+// expect-exit: 55
+int sum(int *values, int count) {
+    int total = 0;
+    for (int i = 0; i < count; i++)
+        total += values[i];
+    return total;
+}
+
+int main() {
+    int values[10];
+    for (int i = 0; i < 10; i++)
+        values[i] = i + 1;
+    return sum(values, 10);
+}
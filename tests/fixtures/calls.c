@@ -0,0 +1,8 @@
+int helper() {
+    return 5;
+}
+
+// expect-exit: 3
+int main() {
+    return helper() % 3 + 1;
+}
@@ -0,0 +1,17 @@
+// expect-exit: 11
+int main(void) {
+    int i;
+    int n = 0;
+    ;
+    for (i = 0; i < 10; i++)
+        ;
+    while (n++ < i)
+        ;
+    {
+        ;
+    }
+    ;
+done:
+    ;
+    return n;
+}
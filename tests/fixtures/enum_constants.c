@@ -0,0 +1,9 @@
+// expect-exit: 49
+enum { X, Y, Z, };
+enum E { P = 1 << 4, Q = P | 1 };
+
+int main() {
+    int a[3] = {1, 2, 3};
+    enum { LAST = 2 };
+    return Z + Q + a[LAST] * 10;
+}
@@ -0,0 +1,14 @@
+// expect-exit: 231
+enum Color { RED, GREEN = 5, BLUE };
+enum { A = BLUE * 2, B = -1, C, D = 'a' };
+
+enum Color scale(enum Color c) {
+    return c * GREEN;
+}
+
+int main() {
+    enum Local { X = 10, Y };
+    enum Local v = Y;
+    int RED = 100;
+    return v + BLUE + A + C + RED + scale(1) + D;
+}
@@ -0,0 +1,18 @@
+// expect-exit: 2
+int g;
+
+int bump(void) {
+    return ++g;
+}
+
+void nothing(void) {}
+
+int main() {
+    1 + 2;
+    bump();
+    bump() * 10;
+    nothing();
+    g;
+    g == 3;
+    return g;
+}
@@ -0,0 +1,17 @@
+// expect-stdout: hello
+// expect-stdout: rcc
+int putchar(int c);
+
+int print(char *s) {
+    int n = 0;
+    while (s[n]) {
+        putchar(s[n]);
+        n++;
+    }
+    putchar('\n');
+    return n;
+}
+
+int main() {
+    return print("hello") - print("rcc") - 2;
+}
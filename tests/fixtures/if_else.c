@@ -0,0 +1,38 @@
+// expect-exit: 81
+int fib(int n) {
+    if (n < 2)
+        return n;
+    return fib(n - 1) + fib(n - 2);
+}
+
+int sign(int x) {
+    if (x < 0)
+        return -1;
+    else if (x == 0)
+        return 0;
+    else
+        return 1;
+}
+
+int dangling(int a, int b) {
+    int r = 0;
+    if (a)
+        if (b)
+            r = 1;
+        else
+            r = 2;
+    return r;
+}
+
+int main() {
+    int evens = 0;
+    for (int i = 0; i < 10; i++) {
+        if (i % 2) {
+        } else {
+            evens++;
+        }
+        if (i == 8)
+            break;
+    }
+    return fib(10) + sign(-5) + sign(0) + sign(7) + dangling(0, 1) * 100 + dangling(1, 0) * 10 + dangling(1, 1) + evens;
+}
@@ -0,0 +1,6 @@
+// expect-exit: 11
+#include "include/util.h"
+  # include "include/lib.h" // the library
+int main() {
+    return twice(lib()) + more;
+}
@@ -0,0 +1,5 @@
+#ifndef A_H
+#define A_H
+#include "b.h"
+int a(void) { return 4; }
+#endif
@@ -0,0 +1 @@
+int more = 3;
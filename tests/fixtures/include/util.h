@@ -0,0 +1,2 @@
+int twice(int x) { return 2 * x; }
+#include "more.h"
\ No newline at end of file
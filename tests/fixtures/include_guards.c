@@ -0,0 +1,6 @@
+// expect-exit: 42
+#include "include/a.h"
+#include "include/b.h"
+int main() {
+    return a() * 10 + b();
+}
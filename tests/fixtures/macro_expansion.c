@@ -0,0 +1,23 @@
+// expect-exit: 91
+#define F(...) sum(0, __VA_ARGS__)
+int sum(int a, int b, int c) { return a + b + c; }
+
+#define f(x) x + f
+int f = 1;
+
+#define ONE 1
+#define TWO ONE + ONE
+#undef ONE
+#define ONE 2
+
+/* #define X 3
+#define X 4 */
+#define X /* three */ 3 // still three
+
+#define NOTHING()
+#define S "N"
+#define N 9
+
+int main() {
+    NOTHING() return F(1, 2) + f(2) + TWO + X + S[0];
+}
@@ -0,0 +1,12 @@
+// expect-exit: 39
+#define N 4
+#define SQUARE(x) ((x) * (x))
+#define ADD(a, b) \
+    ((a) + (b))
+#define CAT(a, b) a ## b
+#define STR(x) #x
+int xy = 5;
+int main() {
+    return SQUARE(N + 1) + ADD(1,
+                               2) + CAT(x, y) + sizeof(STR(a  "b"));
+}
@@ -0,0 +1,5 @@
+// expect-error: 3:5: error: expression is not assignable
+int main() {
+    1 = 2;
+    return 0;
+}
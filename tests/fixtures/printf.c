@@ -0,0 +1,10 @@
+// expect-stdout: 6 * 7 = 42
+// expect-stdout: 5000000000 bytes
+int printf(char *format, ...);
+
+int main() {
+    long big = 5000000000;
+    printf("%d * %d = %d\n", 6, 7, 6 * 7);
+    printf("%ld %s\n", big, "bytes");
+    return 0;
+}
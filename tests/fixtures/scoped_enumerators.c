@@ -0,0 +1,11 @@
+// expect-exit: 169
+enum { X = 1 };
+
+int main() {
+    int r = X;
+    { int X = 10; r = r + X; }
+    { enum { X = 100 }; r = r + X; }
+    int x = 7;
+    { enum { x = 50 }; r = r + x; }
+    return r + x + X;
+}
@@ -0,0 +1,45 @@
+// expect-exit: 42
+int inner() {
+    int x = 1;
+    { int x = 2; x = x + 1; }
+    return x;
+}
+
+int nested() {
+    int x = 1;
+    { int x = 2; { int x = 3; } return x; }
+}
+
+int before() {
+    int x = 1;
+    { x = 5; int x = 2; }
+    return x;
+}
+
+int initializer() {
+    int x = 1;
+    { int y = x + 1; int x = y * 10; return x + y; }
+}
+
+int loop() {
+    int x = 4;
+    for (int x = 0; x < 2; x++) { int x = 9; }
+    return x;
+}
+
+int types() {
+    int x = 1;
+    { char x = 300; }
+    return x;
+}
+
+int siblings() {
+    int t = 0;
+    { int a[2] = {1, 2}; t = a[1]; }
+    { int a = 5; t = t + a; }
+    return t;
+}
+
+int main() {
+    return inner() + nested() + before() + initializer() + loop() + types() + siblings();
+}
@@ -0,0 +1,14 @@
+// expect-exit: 147
+enum { N = sizeof(int) * 2 };
+
+int f();
+
+int main() {
+    int a[10];
+    int m[2][3];
+    int x;
+    int n = sizeof(int) + sizeof(int *) + sizeof(int[3][2]) + sizeof(enum E *);
+    n += sizeof a + sizeof a / sizeof a[0] + sizeof(a + 1) + sizeof m[1];
+    n += sizeof &x + sizeof x + sizeof "abc" + sizeof *"abc";
+    return n + sizeof f() + sizeof(sizeof(int)) + N;
+}
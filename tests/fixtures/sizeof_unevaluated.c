@@ -0,0 +1,8 @@
+// expect-exit: 9
+int f();
+
+int main() {
+    int x = 1;
+    int n = sizeof(x = 5);
+    return x + n + sizeof f();
+}
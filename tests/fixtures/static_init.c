@@ -0,0 +1,29 @@
+// expect-exit: 42
+int arr[5] = {1, 2, 3};
+int grid[3][2] = {{1}, {2, 3}};
+char *msg = "hi";
+char *tail = "hello" + 3;
+int x = 7;
+int *p = &x;
+int *q = arr + 1;
+int *third = &arr[2];
+int *later = &y;
+int y = 4;
+int zeros[4];
+
+int main() {
+    int sum = 0;
+    int i;
+    for (i = 0; i < 5; i = i + 1)
+        sum = sum + arr[i];
+    if (sum != 6 || zeros[3] != 0)
+        return 1;
+    if (grid[0][0] != 1 || grid[0][1] != 0 || grid[1][1] != 3 || grid[2][0] != 0)
+        return 2;
+    if (msg[0] != 'h' || msg[2] != 0 || tail[0] != 'l' || tail[1] != 'o')
+        return 3;
+    if (*q != 2 || *third != 3 || q[1] != 3)
+        return 4;
+    *p = *p + *later;
+    return x * 3 + *later - arr[0] - 4 + 10;
+}
@@ -0,0 +1,4 @@
+// expect-error: undeclared function `missing`
+int main() {
+    return missing();
+}
@@ -0,0 +1,7 @@
+void nothing() {
+    return;
+}
+
+int main() {
+    return 0;
+}
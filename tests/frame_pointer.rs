@@ -0,0 +1,107 @@
+use std::fs;
+use std::process::Command;
+
+use rcc::codegen::{SourceFile, Target};
+use rcc::driver::{self, Options};
+use rcc::opt::OptLevel;
+
+const SOURCE: &str = "int helper() { return 6 * 7; }\nint main() { return helper() - 40; }\n";
+
+fn assembly(options: Options) -> String {
+    driver::compile(SOURCE, &options)
+        .expect("program should compile")
+        .assembly
+}
+
+fn optimized(target: Target) -> Options {
+    Options {
+        opt_level: OptLevel::O1,
+        target,
+        ..Options::default()
+    }
+}
+
+/// The assembly of the function labeled `label`, up to the next blank line.
+fn function<'a>(asm: &'a str, label: &str) -> &'a str {
+    let start = asm
+        .find(&format!("\n{label}:\n"))
+        .expect("function is defined")
+        + 1;
+    let rest = &asm[start..];
+    &rest[..rest.find("\n\n").unwrap_or(rest.len())]
+}
+
+#[test]
+fn optimized_leaves_skip_the_frame_pointer() {
+    let asm = assembly(optimized(Target::AARCH64_APPLE));
+    assert_eq!(
+        function(&asm, "_helper"),
+        "_helper:\n    mov w0, #42\n    ret"
+    );
+    assert!(
+        function(&asm, "_main").contains("stp x29, x30, [sp, #-16]!"),
+        "{asm}"
+    );
+}
+
+#[test]
+fn unoptimized_code_keeps_the_frame_pointer() {
+    let asm = assembly(Options {
+        target: Target::AARCH64_APPLE,
+        ..Options::default()
+    });
+    assert!(function(&asm, "_helper").contains("mov x29, sp"), "{asm}");
+}
+
+#[test]
+fn the_flag_overrides_the_level() {
+    let asm = assembly(Options {
+        omit_frame_pointer: Some(false),
+        ..optimized(Target::AARCH64_APPLE)
+    });
+    assert!(function(&asm, "_helper").contains("mov x29, sp"), "{asm}");
+
+    let asm = assembly(Options {
+        omit_frame_pointer: Some(true),
+        target: Target::AARCH64_APPLE,
+        ..Options::default()
+    });
+    // At -O0 every value has a stack slot, so there is still a frame.
+    assert!(function(&asm, "_helper").contains("mov x29, sp"), "{asm}");
+}
+
+#[test]
+fn debug_info_keeps_the_frame_pointer() {
+    let asm = assembly(Options {
+        debug_info: Some(SourceFile {
+            name: "leaf.c".to_string(),
+            directory: "/tmp".to_string(),
+        }),
+        ..optimized(Target::AARCH64_APPLE)
+    });
+    assert!(function(&asm, "_helper").contains("mov x29, sp"), "{asm}");
+}
+
+#[test]
+fn x86_leaves_return_without_a_frame() {
+    let asm = assembly(optimized(Target::X86_64_LINUX));
+    let helper = function(&asm, "helper");
+    assert!(
+        helper.ends_with("helper:\n    movl $42, %eax\n    ret\n    .size helper, .-helper"),
+        "{asm}"
+    );
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("rcc-frame-pointer-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (source, exe) = (dir.join("leaf.s"), dir.join("leaf"));
+    fs::write(&source, &asm).unwrap();
+    let Ok(linked) = Command::new("cc").arg(&source).arg("-o").arg(&exe).status() else {
+        return;
+    };
+    assert!(linked.success());
+    let status = Command::new(&exe).status().unwrap();
+    assert_eq!(status.code(), Some(2));
+    fs::remove_dir_all(&dir).unwrap();
+}
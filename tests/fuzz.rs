@@ -0,0 +1,225 @@
+use std::thread;
+
+use rcc::ast_interp;
+use rcc::driver::{self, Options, Stage};
+use rcc::fuzz::{fuzz_lex, fuzz_parse};
+use rcc::ir::interp;
+use rcc::lexer::Lexer;
+use rcc::opt::OptLevel;
+use rcc::parser::{Parser, MAX_EXPRESSION_DEPTH, MAX_STATEMENT_DEPTH};
+use rcc::pretty;
+
+/// A xorshift generator, so that every run tries the same inputs.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+const FRAGMENTS: &[&str] = &[
+    "int",
+    "void",
+    "return",
+    "asm",
+    "volatile",
+    "main",
+    "f",
+    "(",
+    ")",
+    "{",
+    "}",
+    ";",
+    ":",
+    ",",
+    "+",
+    "-",
+    "*",
+    "/",
+    "%",
+    "0",
+    "42",
+    "4294967296",
+    "\"%0\"",
+    "\"=r\"",
+    "\"r\"",
+    "\"\\q\"",
+    "\"",
+    "@",
+    "\n",
+    " ",
+    "\u{e9}",
+];
+
+#[test]
+fn random_bytes_never_panic() {
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+    for _ in 0..2000 {
+        let len = rng.below(64);
+        let data: Vec<u8> = (0..len).map(|_| rng.next() as u8).collect();
+        fuzz_lex(&data);
+        fuzz_parse(&data);
+    }
+}
+
+#[test]
+fn random_token_soup_never_panics() {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    for _ in 0..5000 {
+        let len = rng.below(40);
+        let source: String = (0..len)
+            .map(|_| FRAGMENTS[rng.below(FRAGMENTS.len())])
+            .collect::<Vec<_>>()
+            .join(" ");
+        fuzz_parse(source.as_bytes());
+    }
+}
+
+/// Runs `f` with as much stack as rcc's main thread has, which the
+/// nesting limits are set for, rather than a test thread's.
+fn with_main_stack(f: impl FnOnce() + Send) {
+    thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(8 << 20)
+            .spawn_scoped(scope, f)
+            .unwrap()
+            .join()
+            .unwrap();
+    });
+}
+
+fn parse(source: &str) -> Result<(), String> {
+    let tokens = Lexer::new(source).lex().map_err(|err| err.to_string())?;
+    Parser::new(tokens)
+        .parse()
+        .map(drop)
+        .map_err(|err| err.to_string())
+}
+
+#[test]
+fn deeply_nested_expressions_are_errors_not_stack_overflows() {
+    let depth = 100_000;
+    let parens = format!(
+        "int main() {{ return {}1{}; }}",
+        "(".repeat(depth),
+        ")".repeat(depth)
+    );
+    let err = parse(&parens).unwrap_err();
+    assert!(err.contains("expression is nested too deeply"), "{err}");
+    let assignments = format!("int main() {{ int x; return {}1; }}", "x = ".repeat(depth));
+    let err = parse(&assignments).unwrap_err();
+    assert!(err.contains("expression is nested too deeply"), "{err}");
+    let prefixes = format!("int main() {{ return {}1; }}", "-~!".repeat(depth));
+    let err = parse(&prefixes).unwrap_err();
+    assert!(err.contains("expression is nested too deeply"), "{err}");
+    fuzz_parse(parens.as_bytes());
+    fuzz_parse(assignments.as_bytes());
+}
+
+#[test]
+fn nesting_up_to_the_limit_is_accepted() {
+    let depth = MAX_EXPRESSION_DEPTH as usize;
+    let parens = format!(
+        "int main() {{ return {}1{}; }}",
+        "(".repeat(depth),
+        ")".repeat(depth)
+    );
+    parse(&parens).unwrap();
+    let assignments = format!("int main() {{ int x; return {}1; }}", "x = ".repeat(depth));
+    parse(&assignments).unwrap();
+}
+
+/// Operators grouping to the left, like the `+`s of `a + b + c`, don't
+/// nest however many there are, since every stage walks them a link at a
+/// time.
+#[test]
+fn long_chains_of_binary_operators_are_not_nested() {
+    let terms = 100_000;
+    let chain = format!("int main() {{ return {}1; }}", "1 + ".repeat(terms));
+    parse(&chain).unwrap();
+    fuzz_parse(chain.as_bytes());
+    let terms = 2_000;
+    let source = format!(
+        "int main() {{ int x = 1; int y = 0; if (y{}) y = x{}; return y; }}",
+        " || x && x".repeat(terms),
+        " + x - x".repeat(terms)
+    );
+    for opt_level in [OptLevel::O0, OptLevel::O2] {
+        let options = Options {
+            opt_level,
+            ..Options::default()
+        };
+        let ir = driver::compile(&source, &options).unwrap().ir;
+        assert_eq!(interp::Interpreter::new(&ir).call("main", &[]), Ok(Some(1)));
+    }
+    let options = Options {
+        last_stage: Stage::Analyze,
+        ..Options::default()
+    };
+    let program = driver::compile(&source, &options).unwrap().program;
+    let result = ast_interp::Interpreter::new(&program).call("main", &[]);
+    assert_eq!(result, Ok(Some(1)));
+    let printed = pretty::emit(&program);
+    assert!(printed.contains(&format!("y = x{};", " + x - x".repeat(terms))));
+}
+
+#[test]
+fn deeply_nested_statements_are_errors_not_stack_overflows() {
+    with_main_stack(|| {
+        let depth = 20_000;
+        let unterminated = format!("int main() {{{}", "{".repeat(depth));
+        let err = parse(&unterminated).unwrap_err();
+        assert!(err.contains("statement is nested too deeply"), "{err}");
+        let loops = format!(
+            "int main() {{ int x = 0; {}; return 0; }}",
+            "while (x) do ".repeat(depth)
+        );
+        let err = parse(&loops).unwrap_err();
+        assert!(err.contains("statement is nested too deeply"), "{err}");
+        let labels = format!("int main() {{ {}return 0; }}", "l: for (;;) ".repeat(depth));
+        let err = parse(&labels).unwrap_err();
+        assert!(err.contains("statement is nested too deeply"), "{err}");
+        fuzz_parse(unterminated.as_bytes());
+        fuzz_parse(loops.as_bytes());
+    });
+}
+
+#[test]
+fn statements_nested_up_to_the_limit_are_accepted() {
+    with_main_stack(|| {
+        let depth = MAX_STATEMENT_DEPTH as usize;
+        let blocks = format!(
+            "int main() {{ {}return 0;{} }}",
+            "{".repeat(depth - 1),
+            "}".repeat(depth - 1)
+        );
+        parse(&blocks).unwrap();
+        let loops = format!(
+            "int main() {{ int x = 0; {}x++; return x; }}",
+            "while (x) ".repeat(depth - 1)
+        );
+        parse(&loops).unwrap();
+        // Every later stage walks them too, with the deepest expression
+        // innermost.
+        let deepest = format!(
+            "int main() {{ int x = 1; {}x = {}x{};{} return x; }}",
+            "{".repeat(depth - 1),
+            "(".repeat(MAX_EXPRESSION_DEPTH as usize - 1),
+            ")".repeat(MAX_EXPRESSION_DEPTH as usize - 1),
+            "}".repeat(depth - 1)
+        );
+        let options = Options {
+            opt_level: OptLevel::O2,
+            ..Options::default()
+        };
+        driver::compile(&deepest, &options).unwrap();
+    });
+}
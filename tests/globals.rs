@@ -0,0 +1,303 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use rcc::ast_interp;
+use rcc::codegen::aarch64::Aarch64;
+use rcc::codegen::x86_64::X86_64;
+use rcc::codegen::{Backend, CodeGenerator, Target};
+use rcc::driver::{Options, Stage};
+use rcc::ir::{Datum, Global, IrType, Module};
+use rcc::opt::OptLevel;
+
+use common::{assert_assembles, check_fixture, compile, fixture, run_main};
+
+fn globals() -> Module {
+    let global = |name: &str, init: Option<i64>| Global {
+        name: name.to_string(),
+        ty: IrType::I32,
+        len: 1,
+        init: init.into_iter().map(Datum::Int).collect(),
+        thread_local: false,
+    };
+    Module {
+        functions: Vec::new(),
+        globals: vec![
+            global("answer", Some(42)),
+            global("negative", Some(-7)),
+            global("counter", None),
+            global("zero", Some(0)),
+        ],
+        ..Module::default()
+    }
+}
+
+/// A counter bumped by a function called in a loop, and a pointer global
+/// aimed at it.
+const COUNTER: &str = "int count = 40;
+int *cursor;
+enum { STEP = 1 };
+void bump() { count = count + STEP; }
+int main() {
+    int i;
+    for (i = 0; i < 2; i = i + 1) bump();
+    cursor = &count;
+    *cursor = *cursor + 1;
+    return count;
+}";
+
+fn generate(backend: &dyn Backend) -> String {
+    CodeGenerator::new()
+        .with_backend(backend)
+        .generate(&globals())
+}
+
+#[test]
+fn initialized_globals_go_in_data() {
+    let asm = generate(&Aarch64::LINUX);
+    assert!(
+        asm.contains(
+            "    .data\n    .globl answer\n    .p2align 2\n    .type answer, %object\n    .size answer, 4\nanswer:\n    .word 42\n"
+        ),
+        "{asm}"
+    );
+    assert!(asm.contains("negative:\n    .word -7\n"), "{asm}");
+    assert_assembles("aarch64-linux-gnu", &asm);
+}
+
+#[test]
+fn zero_globals_go_in_bss() {
+    let asm = generate(&Aarch64::APPLE);
+    assert!(
+        asm.contains("    .bss\n    .globl _counter\n    .p2align 2\n_counter:\n    .zero 4\n"),
+        "{asm}"
+    );
+    assert!(asm.contains("    .bss\n    .globl _zero\n"), "{asm}");
+    assert!(!asm.contains(".type"), "{asm}");
+    assert_assembles("arm64-apple-macos", &asm);
+}
+
+#[test]
+fn x86_uses_long_for_four_bytes() {
+    let asm = generate(&X86_64::LINUX);
+    assert!(asm.contains("    .type answer, @object\n"), "{asm}");
+    assert!(asm.contains("answer:\n    .long 42\n"), "{asm}");
+    assert!(!asm.contains(".word"), "{asm}");
+}
+
+#[test]
+fn prints_globals_before_functions() {
+    let printed = globals().to_string();
+    assert_eq!(
+        printed,
+        "@answer = global i32 42\n@negative = global i32 -7\n@counter = global i32\n@zero = global i32 0\n"
+    );
+}
+
+#[test]
+fn c_code_sees_the_initial_values() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("rcc-globals-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (asm, main, exe) = (dir.join("globals.s"), dir.join("main.c"), dir.join("main"));
+    fs::write(&asm, generate(&X86_64::LINUX)).unwrap();
+    fs::write(
+        &main,
+        "extern int answer, negative, counter, zero;\n\
+         int main(void) { counter++; return answer + negative + counter + zero; }\n",
+    )
+    .unwrap();
+    let Ok(linked) = Command::new("cc")
+        .arg(&main)
+        .arg(&asm)
+        .arg("-o")
+        .arg(&exe)
+        .status()
+    else {
+        return;
+    };
+    assert!(linked.success());
+    let status = Command::new(&exe).status().unwrap();
+    assert_eq!(status.code(), Some(36));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn functions_share_file_scope_variables() {
+    let cases = [
+        (COUNTER, 43),
+        ("int x; int main() { return x; }", 0),
+        ("int x = -3; int main() { x = x * 2; return x; }", -6),
+        (
+            "enum { N = 4 }; int x = N * sizeof(int) + 'a'; int main() { return x; }",
+            113,
+        ),
+        ("int *p = 0; int main() { return p == 0; }", 1),
+        ("int x = 1; int main() { int x = 2; return x; }", 2),
+        (
+            "int x = 1; int f() { return x; } int main() { int x = 2; return f(); }",
+            1,
+        ),
+        (
+            "int x = 5; int main() { int r = sizeof x; int *p = &x; return r + *p; }",
+            9,
+        ),
+    ];
+    for (source, expected) in cases {
+        for level in [OptLevel::O0, OptLevel::O2] {
+            assert_eq!(
+                run_main(source, level),
+                Some(expected),
+                "{source} at {level:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn global_declarations_are_checked() {
+    let cases = [
+        (
+            "int f() { return x; } int y;",
+            "1:18: use of undeclared identifier `x`",
+        ),
+        (
+            "int x = 1; int x = 2; int main() { return 0; }",
+            "1:16: redefinition of `x`",
+        ),
+        (
+            "int main() { return 0; } int main;",
+            "1:30: redefinition of `main` as a different kind of symbol",
+        ),
+        (
+            "int y; int x = y; int main() { return 0; }",
+            "1:12: initializer of `x` is not a constant",
+        ),
+        (
+            "int f(); int x = f(); int main() { return 0; }",
+            "1:14: initializer of `x` is not a constant",
+        ),
+        ("void v;", "1:6: variable `v` has incomplete type `void`"),
+        (
+            "int a[2] = {1, a[0]}; int main() { return 0; }",
+            "1:5: initializer of `a` is not a constant",
+        ),
+        (
+            "_Thread_local int t; int *p = &t; int main() { return 0; }",
+            "1:27: initializer of `p` is not a constant",
+        ),
+        (
+            "int x; int y = &x - &x; int main() { return 0; }",
+            "1:12: initializer of `y` is not a constant",
+        ),
+        (
+            "int x = {1}; int main() { return 0; }",
+            "1:9: cannot initialize `int` with an initializer list",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(
+            compile(source, &Options::default()).unwrap_err(),
+            expected,
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn arrays_and_addresses_initialize_globals() {
+    check_fixture("static_init");
+    let source = fixture("static_init").source;
+    let options = Options {
+        last_stage: Stage::Analyze,
+        ..Options::default()
+    };
+    let program = compile(&source, &options).unwrap().program;
+    assert_eq!(
+        ast_interp::Interpreter::new(&program).call("main", &[]),
+        Ok(Some(42))
+    );
+
+    let text = compile(&source, &Options::default())
+        .unwrap()
+        .ir
+        .to_string();
+    for global in [
+        "@arr = global [5 x i32] [1, 2, 3]\n",
+        "@grid = global [6 x i32] [1, 0, 2, 3]\n",
+        "@msg = global ptr str0\n",
+        "@tail = global ptr str1+3\n",
+        "@q = global ptr @arr+4\n",
+        "@later = global ptr @y\n",
+        "@zeros = global [4 x i32]\n",
+    ] {
+        assert!(text.contains(global), "{text}");
+    }
+    for (target, triple) in [
+        (Target::X86_64_LINUX, "x86_64-linux-gnu"),
+        (Target::AARCH64_LINUX, "aarch64-linux-gnu"),
+        (Target::AARCH64_APPLE, "arm64-apple-macos"),
+    ] {
+        let options = Options {
+            target,
+            ..Options::default()
+        };
+        let asm = compile(&source, &options).unwrap().assembly;
+        assert!(asm.contains(", 0, 2, 3\n    .zero 8\n"), "{asm}");
+        assert_assembles(triple, &asm);
+    }
+}
+
+#[test]
+fn source_globals_are_emitted_and_addressed_by_page() {
+    let ir = compile(COUNTER, &Options::default()).unwrap().ir;
+    let text = ir.to_string();
+    assert!(
+        text.starts_with("@count = global i32 40\n@cursor = global ptr\n"),
+        "{text}"
+    );
+    for (target, triple) in [
+        (Target::AARCH64_LINUX, "aarch64-linux-gnu"),
+        (Target::AARCH64_APPLE, "arm64-apple-macos"),
+    ] {
+        let asm = compile(
+            COUNTER,
+            &Options {
+                target,
+                ..Options::default()
+            },
+        )
+        .unwrap()
+        .assembly;
+        assert!(asm.contains("    .data\n"), "{asm}");
+        assert!(asm.contains("    .bss\n"), "{asm}");
+        assert!(asm.contains("adrp"), "{asm}");
+        assert_assembles(triple, &asm);
+    }
+}
+
+#[test]
+fn x86_program_updates_globals() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let options = Options {
+        target: Target::X86_64_LINUX,
+        ..Options::default()
+    };
+    let asm = compile(COUNTER, &options).unwrap().assembly;
+    let dir = std::env::temp_dir().join(format!("rcc-source-globals-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (source, exe) = (dir.join("counter.s"), dir.join("counter"));
+    fs::write(&source, &asm).unwrap();
+    let Ok(linked) = Command::new("cc").arg(&source).arg("-o").arg(&exe).status() else {
+        return;
+    };
+    assert!(linked.success(), "{asm}");
+    let status = Command::new(&exe).status().unwrap();
+    assert_eq!(status.code(), Some(43));
+    fs::remove_dir_all(&dir).unwrap();
+}
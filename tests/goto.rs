@@ -0,0 +1,141 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use rcc::codegen::Target;
+use rcc::driver::Options;
+use rcc::opt::OptLevel;
+
+use common::{assert_assembles, compile, run_main};
+
+/// A loop built from a label and a backward `goto`, and a forward `goto`
+/// that skips an assignment, in two functions that both use the label
+/// `again`.
+const GOTO: &str = "int sum(int n) {
+    int s = 0;
+again:
+    s += n;
+    n--;
+    while (n > 0) goto again;
+    return s;
+}
+int main() {
+    int x = 4;
+    goto again;
+    x = 100;
+again:
+    return sum(x) * 10 + x;
+}";
+
+#[test]
+fn goto_jumps_to_labels() {
+    let cases = [
+        (GOTO, 104),
+        ("int main() { goto a; return 1; a: return 2; }", 2),
+        ("int main() { int x = 1; x: return x; }", 1),
+        (
+            "int main() { int i = 0; loop: { i++; while (i < 5) goto loop; } return i; }",
+            5,
+        ),
+        (
+            "int main() { int i = 0; while (1) { i++; while (i == 3) goto done; } done: return i; }",
+            3,
+        ),
+        ("int main() { a: b: c: return 7; }", 7),
+        ("int main() { return 3; dead: return 4; }", 3),
+    ];
+    for (source, expected) in cases {
+        for level in [OptLevel::O0, OptLevel::O2] {
+            assert_eq!(
+                run_main(source, level),
+                Some(expected),
+                "{source} at {level:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn goto_targets_are_checked() {
+    let cases = [
+        (
+            "int main() { goto out; return 0; }",
+            "1:14: use of undeclared label `out`",
+        ),
+        (
+            "int main() { a: a: return 0; }",
+            "1:17: redefinition of label `a`",
+        ),
+        (
+            "int f() { a: return 0; } int main() { goto a; }",
+            "1:39: use of undeclared label `a`",
+        ),
+        (
+            "int main() { goto 5; }",
+            "1:19: expected an identifier, found integer literal `5`",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(
+            compile(source, &Options::default()).unwrap_err(),
+            expected,
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn labels_become_blocks() {
+    let ir = compile(
+        "int main() { int i = 0; top: i++; while (i < 3) goto top; return i; }",
+        &Options::default(),
+    )
+    .unwrap()
+    .ir;
+    let text = ir.to_string();
+    // The label's block is entered by falling through and by the `goto`.
+    assert_eq!(text.matches("jump bb1\n").count(), 2, "{text}");
+}
+
+#[test]
+fn goto_code_assembles_for_aarch64() {
+    for (target, triple) in [
+        (Target::AARCH64_LINUX, "aarch64-linux-gnu"),
+        (Target::AARCH64_APPLE, "arm64-apple-macos"),
+    ] {
+        let asm = compile(
+            GOTO,
+            &Options {
+                target,
+                ..Options::default()
+            },
+        )
+        .unwrap()
+        .assembly;
+        assert_assembles(triple, &asm);
+    }
+}
+
+#[test]
+fn x86_program_uses_goto() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let options = Options {
+        target: Target::X86_64_LINUX,
+        ..Options::default()
+    };
+    let asm = compile(GOTO, &options).unwrap().assembly;
+    let dir = std::env::temp_dir().join(format!("rcc-goto-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (source, exe) = (dir.join("goto.s"), dir.join("goto"));
+    fs::write(&source, &asm).unwrap();
+    let Ok(linked) = Command::new("cc").arg(&source).arg("-o").arg(&exe).status() else {
+        return;
+    };
+    assert!(linked.success(), "{asm}");
+    let output = Command::new(&exe).output().unwrap();
+    assert_eq!(output.status.code(), Some(104));
+    fs::remove_dir_all(&dir).unwrap();
+}
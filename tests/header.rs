@@ -0,0 +1,62 @@
+use std::fs;
+use std::process::Command;
+
+use rcc::header;
+use rcc::lexer::Lexer;
+use rcc::parser::Parser;
+
+const SOURCE: &str =
+    "int answer() { return 42; }\nvoid nothing() { return; }\nint main() { return answer(); }\n";
+
+#[test]
+fn declares_every_function_but_main() {
+    let program = Parser::new(Lexer::new(SOURCE).lex().unwrap())
+        .parse()
+        .unwrap();
+    assert_eq!(
+        header::emit(&program, "lib/my-lib.c"),
+        "/* Generated by rcc from lib/my-lib.c. */\n\
+         #ifndef MY_LIB_H\n\
+         #define MY_LIB_H\n\
+         \n\
+         int answer(void);\n\
+         void nothing(void);\n\
+         \n\
+         #endif /* MY_LIB_H */\n"
+    );
+    assert!(header::emit(&program, "2d.c").contains("#ifndef _2D_H\n"));
+}
+
+#[test]
+fn other_translation_units_can_call_through_it() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("rcc-header-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("lib.c"),
+        SOURCE.replace("int main() { return answer(); }\n", ""),
+    )
+    .unwrap();
+    fs::write(
+        dir.join("use.c"),
+        "#include \"lib.h\"\nint main(void) { nothing(); return answer() - 42; }\n",
+    )
+    .unwrap();
+    let rcc = Command::new(env!("CARGO_BIN_EXE_rcc"))
+        .args(["--emit=header,obj", "lib.c"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(rcc.status.success(), "{rcc:?}");
+    let cc = Command::new("cc")
+        .args(["-Wall", "-Werror", "use.c", "lib.o", "-o", "use"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(cc.status.success(), "{cc:?}");
+    let status = Command::new(dir.join("use")).status().unwrap();
+    assert_eq!(status.code(), Some(0));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,87 @@
+use std::fs;
+use std::process::Command;
+
+use rcc::driver::Options;
+use rcc::ice;
+use rcc::lexer::Position;
+
+#[test]
+fn context_says_where_the_compiler_was() {
+    ice::set_file("t.c");
+    ice::enter_stage("lower");
+    ice::set_function("main");
+    ice::set_pos(Position { line: 2, column: 5 });
+    assert_eq!(
+        ice::context().to_string(),
+        "in stage `lower` of `t.c`, in function `main` near 2:5"
+    );
+    ice::set_file("u.c");
+    assert_eq!(ice::context().to_string(), "outside any stage of `u.c`");
+}
+
+#[test]
+fn dumps_hold_the_ast_and_ir() {
+    let dump = ice::dump("int main() { return 2 + 3; }", &Options::default());
+    assert!(dump.starts_with(&format!("rcc {} internal compiler error\n", ice::VERSION)));
+    assert!(
+        dump.contains("\n== source ==\nint main() { return 2 + 3; }\n"),
+        "{dump}"
+    );
+    assert!(dump.contains("\n== ast ==\nProgram {\n"), "{dump}");
+    assert!(dump.contains("name: \"main\""), "{dump}");
+    assert!(dump.contains("\n== ir ==\n"), "{dump}");
+    assert!(dump.contains("\n== optimized ir ==\n"), "{dump}");
+}
+
+#[test]
+fn dumps_stop_at_the_first_error() {
+    let dump = ice::dump("int main() { return f(); }", &Options::default());
+    assert!(
+        dump.contains("\n== analysis ==\nerror: 1:21: call to undeclared function `f`\n"),
+        "{dump}"
+    );
+    assert!(!dump.contains("== ir =="), "{dump}");
+}
+
+#[test]
+fn panics_are_reported_as_internal_compiler_errors() {
+    let dir = std::env::temp_dir().join(format!("rcc-ice-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("t.c");
+    fs::write(&input, "int main() { return 0; }\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rcc"))
+        .current_dir(&dir)
+        .args(["-S", "-o", "-"])
+        .arg(&input)
+        .env("RCC_DEBUG_CRASH", "codegen")
+        .env("RUST_BACKTRACE", "0")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(101));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with(
+            "rcc: internal compiler error: crashing in stage `codegen` because RCC_DEBUG_CRASH is set\n"
+        ),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains(&format!(
+            "note: rcc {} was in stage `codegen` of `{}`\n",
+            ice::VERSION,
+            input.display()
+        )),
+        "{stderr}"
+    );
+    let dump = stderr
+        .split_once("note: the compiler's state is in `")
+        .and_then(|(_, rest)| rest.split_once('`'))
+        .map(|(path, _)| path.to_string())
+        .expect("the report should name the dump");
+    let text = fs::read_to_string(&dump).unwrap();
+    assert!(text.contains("== context ==\nin stage `codegen`"), "{text}");
+    assert!(text.contains("== optimized ir =="), "{text}");
+    fs::remove_file(&dump).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use rcc::ast_interp;
+use rcc::driver::{Options, Stage};
+use rcc::opt::OptLevel;
+use rcc::pretty;
+
+use common::{check_fixture, compile, fixture, run_main};
+
+/// `main`'s result from the IR at -O0 and -O2, checked against the syntax
+/// tree's.
+fn run_checked(source: &str) -> Option<i64> {
+    let options = Options {
+        last_stage: Stage::Analyze,
+        ..Options::default()
+    };
+    let program = compile(source, &options).unwrap().program;
+    let expected = ast_interp::Interpreter::new(&program)
+        .call("main", &[])
+        .unwrap();
+    for opt_level in [OptLevel::O0, OptLevel::O2] {
+        assert_eq!(run_main(source, opt_level), expected, "{source}");
+    }
+    expected
+}
+
+#[test]
+fn if_runs_one_branch() {
+    check_fixture("if_else");
+    let source = fixture("if_else").source;
+    assert_eq!(run_checked(&source), Some(81));
+    let program = compile(&source, &Options::default()).unwrap().program;
+    assert_eq!(pretty::emit(&program), format!("{}\n", source.trim_start()));
+}
+
+#[test]
+fn jumps_into_a_branch_skip_the_condition() {
+    let source = "int main() {
+  int x = 0;
+  goto inside;
+  if (1) {
+    x = 10;
+  } else {
+  inside:
+    x = x + 1;
+  }
+  return x;
+}";
+    assert_eq!(run_checked(source), Some(1));
+    let source = "int pick(int x) {
+  int r = 0;
+  switch (x) {
+    if (0) {
+    case 1:
+      r = 1;
+    } else {
+    case 2:
+      r = r + 2;
+    }
+  }
+  return r;
+}
+int main() { return pick(1) * 10 + pick(2); }";
+    assert_eq!(run_checked(source), Some(12));
+}
+
+#[test]
+fn conditions_must_be_scalars() {
+    let source = "void f(void); int main() { if (f()) return 1; return 0; }";
+    assert_eq!(
+        compile(source, &Options::default()).err().as_deref(),
+        Some("1:32: expected `int` but found `void`")
+    );
+}
+
+#[test]
+fn if_lowers_to_a_conditional_branch() {
+    let source = "int main(int x) { if (x) return 1; return 2; }";
+    let ir = compile(source, &Options::default()).unwrap().ir.to_string();
+    assert!(ir.contains("br %"), "{ir}");
+}
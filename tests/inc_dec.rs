@@ -0,0 +1,124 @@
+mod common;
+
+use rcc::codegen::Target;
+use rcc::driver::Options;
+use rcc::opt::OptLevel;
+
+use common::{assert_assembles, compile, run_main};
+
+/// Walks an array with a postfix increment on the pointer, and counts a
+/// loop down with a prefix decrement.
+const WALK: &str = "int sum(int *p, int n) {
+    int s = 0;
+    while (n--) s += *p++;
+    return s;
+}
+int main() {
+    int a[4] = {1, 2, 3, 4};
+    int i;
+    int count = 0;
+    for (i = 10; --i > 0; ) count++;
+    return sum(a, 4) * 100 + count;
+}";
+
+#[test]
+fn increments_and_decrements_step_by_one() {
+    let cases = [
+        (WALK, 1009),
+        ("int main() { int x = 5; int y = x++; return x * 10 + y; }", 65),
+        ("int main() { int x = 5; int y = ++x; return x * 10 + y; }", 66),
+        ("int main() { int x = 5; int y = x--; return x * 10 + y; }", 45),
+        ("int main() { int x = 5; int y = --x; return x * 10 + y; }", 44),
+        ("int main() { int x = 3; return - -x; }", 3),
+        ("int main() { int x = 3; return -(--x); }", -2),
+        ("int main() { int a = 1; int b = 2; int c = a+++b; return a * 10 + c; }", 23),
+        (
+            "int main() { int a[2] = {4, 9}; int *p = a; ++*p; p++; (*p)--; return a[0] * 10 + a[1]; }",
+            58,
+        ),
+        (
+            "int main() { int a[3] = {1, 2, 3}; int *p = a + 2; --p; int x = *p--; return x * 10 + *p; }",
+            21,
+        ),
+        ("int g; int main() { g++; ++g; return g--; }", 2),
+        (
+            "int main() { int i; int s = 0; for (i = 0; i < 10; i++) s += i; return s; }",
+            45,
+        ),
+    ];
+    for (source, expected) in cases {
+        for level in [OptLevel::O0, OptLevel::O2] {
+            assert_eq!(
+                run_main(source, level),
+                Some(expected),
+                "{source} at {level:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn increment_operands_are_checked() {
+    let cases = [
+        (
+            "int main() { 5++; return 0; }",
+            "1:15: expression is not assignable",
+        ),
+        (
+            "int main() { int x; x++ ++; return 0; }",
+            "1:25: expression is not assignable",
+        ),
+        (
+            "int main() { int a[2]; a++; return 0; }",
+            "1:25: cannot increment value of type `int[2]`",
+        ),
+        (
+            "int main() { void *p; --p; return 0; }",
+            "1:23: cannot decrement value of type `void *`",
+        ),
+        (
+            "enum { N }; int main() { ++N; return 0; }",
+            "1:26: expression is not assignable",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(
+            compile(source, &Options::default()).unwrap_err(),
+            expected,
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn postfix_returns_the_loaded_value() {
+    let ir = compile("int main() { int x = 1; return x++; }", &Options::default())
+        .unwrap()
+        .ir
+        .to_string();
+    let load = ir
+        .lines()
+        .find(|line| line.contains("= load i32 ss"))
+        .unwrap();
+    let old = load.trim().split(' ').next().unwrap();
+    assert!(ir.contains(&format!("ret {old}\n")), "{ir}");
+}
+
+#[test]
+fn increments_assemble_for_aarch64() {
+    for (target, triple) in [
+        (Target::AARCH64_LINUX, "aarch64-linux-gnu"),
+        (Target::AARCH64_APPLE, "arm64-apple-macos"),
+    ] {
+        let asm = compile(
+            WALK,
+            &Options {
+                target,
+                ..Options::default()
+            },
+        )
+        .unwrap()
+        .assembly;
+        assert_assembles(triple, &asm);
+    }
+}
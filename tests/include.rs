@@ -0,0 +1,203 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rcc::driver::{self, Options};
+use rcc::ir::interp::Interpreter;
+use rcc::lexer::Position;
+use rcc::opt::OptLevel;
+use rcc::preprocess::Preprocessor;
+
+use common::check_fixture;
+
+/// A fresh directory for one test's files.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rcc-include-{name}-{}", std::process::id()));
+    fs::create_dir_all(dir.join("include")).unwrap();
+    dir
+}
+
+/// Preprocesses and compiles `main.c` in `dir`, returning what `main`
+/// returns or the first error as the driver reports it.
+fn run_main(dir: &Path, opt_level: OptLevel) -> Result<Option<i64>, String> {
+    let path = dir.join("main.c");
+    let source = fs::read_to_string(&path).unwrap();
+    let preprocessed = Preprocessor::new()
+        .with_include_dirs(vec![dir.join("include")])
+        .run(&path, "main.c", &source)
+        .map_err(|err| err.error.render(&err.file))?;
+    let options = Options {
+        opt_level,
+        ..Options::default()
+    };
+    let ir = driver::compile(&preprocessed.source, &options)
+        .map_err(|err| {
+            let err = preprocessed.locate(err);
+            err.error.render(&err.file)
+        })?
+        .ir;
+    Ok(Interpreter::new(&ir).call("main", &[]).unwrap())
+}
+
+#[test]
+fn includes_are_spliced_in() {
+    check_fixture("include");
+    let dir = temp_dir("splice");
+    fs::write(dir.join("include/lib.h"), "int lib() { return 4; }\n").unwrap();
+    fs::write(
+        dir.join("main.c"),
+        "#include <lib.h>\nint main() { return lib(); }\n",
+    )
+    .unwrap();
+    assert_eq!(run_main(&dir, OptLevel::O0), Ok(Some(4)));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn errors_point_into_the_file_they_are_in() {
+    let dir = temp_dir("errors");
+    let main = "#include \"a.h\"\nint main() {\n  return a() + x;\n}\n";
+    let cases = [
+        (
+            "int a() { return 0; }\n",
+            main,
+            "main.c:3:16: error: use of undeclared identifier `x`",
+        ),
+        (
+            "int x;\nint a() { return y; }\n",
+            main,
+            &*format!(
+                "{}:2:18: error: use of undeclared identifier `y`",
+                dir.join("a.h").display()
+            ),
+        ),
+        (
+            "int x;",
+            "#include <a.h>\nint main() { return x; }\n",
+            "main.c:1:10: error: cannot find include file `a.h`",
+        ),
+        (
+            "",
+            "#include a.h\n",
+            "main.c:1:10: error: expected \"file\" or <file> after `#include`",
+        ),
+        (
+            "",
+            "#include \"a.h\n",
+            "main.c:1:10: error: missing `\"` after the file name",
+        ),
+        (
+            "",
+            "#include \"a.h\" int\n",
+            "main.c:1:10: error: extra tokens after `#include`",
+        ),
+    ];
+    for (header, main, expected) in cases {
+        fs::write(dir.join("a.h"), header).unwrap();
+        fs::write(dir.join("main.c"), main).unwrap();
+        assert_eq!(run_main(&dir, OptLevel::O0), Err(expected.into()), "{main}");
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn include_cycles_are_reported() {
+    let dir = temp_dir("cycle");
+    let (a, b) = (dir.join("a.h"), dir.join("b.h"));
+    fs::write(&a, "#include \"b.h\"\n").unwrap();
+    fs::write(&b, "int b;\n#include \"a.h\"\n").unwrap();
+    fs::write(dir.join("main.c"), "#include \"a.h\"\n").unwrap();
+    // The cycle goes round until too many files are open, which leaves
+    // `a.h` innermost.
+    let expected = format!(
+        "{a}:1:10: error: `#include` cycle: {b} -> {a} -> {b}",
+        a = a.display(),
+        b = b.display()
+    );
+    assert_eq!(run_main(&dir, OptLevel::O0), Err(expected));
+    fs::write(dir.join("main.c"), "#include \"main.c\"\n").unwrap();
+    let main = dir.join("main.c").display().to_string();
+    let expected = format!("{main}:1:10: error: `#include` cycle: {main} -> {main}");
+    assert_eq!(run_main(&dir, OptLevel::O0), Err(expected));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn guarded_headers_can_include_each_other() {
+    check_fixture("include_guards");
+}
+
+#[test]
+fn deep_includes_stop_at_the_limit() {
+    let dir = temp_dir("deep");
+    for depth in 0..250 {
+        let body = format!("#include \"{}.h\"\n", depth + 1);
+        fs::write(dir.join(format!("{depth}.h")), body).unwrap();
+    }
+    fs::write(dir.join("main.c"), "#include \"0.h\"\n").unwrap();
+    let expected = format!(
+        "{}:1:10: error: `#include` nested more than 200 deep",
+        dir.join("198.h").display()
+    );
+    assert_eq!(run_main(&dir, OptLevel::O0), Err(expected));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn positions_map_back_to_their_files() {
+    let dir = temp_dir("origin");
+    fs::write(dir.join("a.h"), "int a;\nint b;").unwrap();
+    let path = dir.join("main.c");
+    let preprocessed = Preprocessor::new()
+        .run(&path, "main.c", "int x;\n#include \"a.h\"\nint y;")
+        .unwrap();
+    assert_eq!(preprocessed.source, "int x;\nint a;\nint b;\nint y;");
+    let header = dir.join("a.h").display().to_string();
+    for (line, file, original) in [(1, "main.c", 1), (3, &*header, 2), (4, "main.c", 3)] {
+        let pos = Position { line, column: 5 };
+        assert_eq!(
+            preprocessed.origin(pos),
+            (
+                file,
+                Position {
+                    line: original,
+                    column: 5
+                }
+            )
+        );
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn notes_point_into_the_file_they_are_in() {
+    let dir = temp_dir("notes");
+    fs::write(
+        dir.join("twice.h"),
+        "\nint twice(int x) { return 2 * x; }\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("main.c"),
+        "#include \"twice.h\"\nint twice(int x) { return x + x; }\nint main() { return twice(1); }\n",
+    )
+    .unwrap();
+    let path = dir.join("main.c");
+    let source = fs::read_to_string(&path).unwrap();
+    let preprocessed = Preprocessor::new().run(&path, "main.c", &source).unwrap();
+    let err = driver::compile(&preprocessed.source, &Options::default()).unwrap_err();
+    assert_eq!(
+        preprocessed.locate(err).render_colored(false),
+        format!(
+            "main.c:2:5: error: redefinition of `twice`
+ 2 | int twice(int x) {{ return x + x; }}
+   |     ^~~~~
+{}:2:5: note: previous definition is here
+ 2 | int twice(int x) {{ return 2 * x; }}
+   |     ^~~~~",
+            dir.join("twice.h").display()
+        )
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,128 @@
+use rcc::driver::{self, Options};
+use rcc::ir::{BinOp, FunctionBuilder, Instr, IrType, Module, Terminator};
+use rcc::opt::{self, OptLevel};
+
+fn function_asm(source: &str, opt_level: OptLevel, name: &str) -> String {
+    let asm = driver::compile(
+        source,
+        &Options {
+            opt_level,
+            ..Options::default()
+        },
+    )
+    .expect("program should compile")
+    .assembly;
+    let start = asm.find(&format!("_{name}:")).expect("function is emitted");
+    let end = asm[start..].find("ret\n").expect("function returns") + start;
+    asm[start..end].to_string()
+}
+
+fn has_call(module: &Module, function: &str) -> bool {
+    module
+        .functions
+        .iter()
+        .find(|f| f.name == function)
+        .expect("function exists")
+        .blocks
+        .iter()
+        .flat_map(|b| &b.instrs)
+        .any(|instr| matches!(instr, Instr::Call { .. }))
+}
+
+#[test]
+fn inlines_small_leaf_at_o2() {
+    let source = "int five() { return 2 + 3; } int main() { return five() * 2; }";
+    let main = function_asm(source, OptLevel::O2, "main");
+    assert!(!main.contains("bl _five"), "{main}");
+    assert!(main.contains("mov w0, #10"), "{main}");
+}
+
+#[test]
+fn keeps_calls_below_o2() {
+    let source = "int five() { return 2 + 3; } int main() { return five() * 2; }";
+    let main = function_asm(source, OptLevel::O1, "main");
+    assert!(main.contains("bl _five"), "{main}");
+}
+
+#[test]
+fn does_not_inline_non_leaf_functions() {
+    let source = "int a() { return 1; } int b() { return a() + 1; } int main() { return b(); }";
+    assert!(!function_asm(source, OptLevel::O2, "b").contains("bl _a"));
+    assert!(function_asm(source, OptLevel::O2, "main").contains("bl _b"));
+}
+
+#[test]
+fn inlines_multi_block_callee_with_arguments() {
+    // int pick(int x) { if (x) goto done; else goto done; done: return x * 3; }
+    let mut callee = FunctionBuilder::new("pick", Some(IrType::I32));
+    let x = callee.param(IrType::I32);
+    let (left, right, done) = (
+        callee.create_block(),
+        callee.create_block(),
+        callee.create_block(),
+    );
+    callee.branch(x, left, right);
+    for block in [left, right] {
+        callee.switch_to(block);
+        callee.jump(done);
+    }
+    callee.switch_to(done);
+    let three = callee.iconst(IrType::I32, 3);
+    let product = callee.binary(BinOp::Mul, x, three);
+    callee.ret(Some(product));
+
+    let mut caller = FunctionBuilder::new("main", Some(IrType::I32));
+    let seven = caller.iconst(IrType::I32, 7);
+    let result = caller
+        .call("pick", vec![seven], Some(IrType::I32))
+        .expect("pick returns a value");
+    let one = caller.iconst(IrType::I32, 1);
+    let sum = caller.binary(BinOp::Add, result, one);
+    caller.ret(Some(sum));
+
+    let mut module = Module {
+        functions: vec![callee.finish(), caller.finish()],
+        ..Module::default()
+    };
+    opt::optimize(&mut module, OptLevel::O2);
+
+    assert!(!has_call(&module, "main"));
+    let main = &module.functions[1];
+    // The branch on the constant argument folds and the copied blocks merge back.
+    assert_eq!(main.blocks.len(), 1);
+    let continuation = &main.blocks[0];
+    let Terminator::Ret(Some(returned)) = continuation.term else {
+        panic!("inlined body should return: {main:#?}");
+    };
+    // (7 * 3) + 1, folded once the argument was substituted for the parameter.
+    assert_eq!(
+        continuation.instrs,
+        [Instr::Const {
+            dst: returned,
+            value: 22
+        }]
+    );
+}
+
+#[test]
+fn does_not_inline_large_functions() {
+    let mut callee = FunctionBuilder::new("big", Some(IrType::I32));
+    let x = callee.param(IrType::I32);
+    let mut acc = x;
+    for _ in 0..20 {
+        acc = callee.binary(BinOp::Mul, acc, x);
+    }
+    callee.ret(Some(acc));
+
+    let mut caller = FunctionBuilder::new("main", Some(IrType::I32));
+    let two = caller.iconst(IrType::I32, 2);
+    let result = caller.call("big", vec![two], Some(IrType::I32));
+    caller.ret(result);
+
+    let mut module = Module {
+        functions: vec![callee.finish(), caller.finish()],
+        ..Module::default()
+    };
+    opt::optimize(&mut module, OptLevel::O2);
+    assert!(has_call(&module, "main"));
+}
@@ -0,0 +1,156 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use rcc::codegen::x86_64::X86_64;
+use rcc::codegen::{CodeGenerator, Target};
+use rcc::driver::{self, Options};
+use rcc::ir::interp::{Interpreter, Trap};
+use rcc::ir::{FunctionBuilder, IrType, Module};
+use rcc::opt::OptLevel;
+
+use common::assert_assembles;
+
+fn compile(source: &str, target: Target) -> rcc::error::Result<String> {
+    let options = Options {
+        target,
+        opt_level: OptLevel::O2,
+        ..Options::default()
+    };
+    driver::compile(source, &options).map(|artifacts| artifacts.assembly)
+}
+
+fn error(source: &str) -> String {
+    compile(source, Target::default())
+        .expect_err("program should be rejected")
+        .message
+}
+
+#[test]
+fn templates_are_emitted_line_by_line() {
+    let asm = compile(
+        "int main() { __asm__ volatile(\"nop\\n\\tnop\"); asm(\"\"); return 0; }",
+        Target::AARCH64_APPLE,
+    )
+    .unwrap();
+    assert!(asm.contains("\tnop\n\tnop\n"), "{asm}");
+    assert_assembles("arm64-apple-macos", &asm);
+}
+
+#[test]
+fn inputs_are_substituted_with_their_registers() {
+    let source = "int f() { return 7; }\n\
+                  int main() { asm(\"add %0, %1, #1\\n// 100%%\" : : \"r\"(f()), \"r\"(2 * 3) : \"memory\"); return 0; }";
+    let asm = compile(source, Target::AARCH64_LINUX).unwrap();
+    assert!(asm.contains("\tadd w0, w1, #1\n\t// 100%\n"), "{asm}");
+    assert_assembles("aarch64-linux-gnu", &asm);
+
+    let asm = compile(source, Target::X86_64_LINUX).unwrap();
+    assert!(asm.contains("\tadd %edi, %esi, #1\n"), "{asm}");
+}
+
+#[test]
+fn asm_statements_survive_optimization() {
+    let asm = compile(
+        "int main() { asm(\"mov x9, x9\"); return 0; }",
+        Target::AARCH64_LINUX,
+    )
+    .unwrap();
+    assert!(asm.contains("mov x9, x9"), "{asm}");
+}
+
+#[test]
+fn rejects_unsupported_constraints() {
+    assert_eq!(
+        error("int main() { asm(\"\" : : \"m\"(1)); return 0; }"),
+        "unsupported asm constraint \"m\", expected \"r\""
+    );
+}
+
+#[test]
+fn rejects_unassignable_outputs() {
+    assert_eq!(
+        error("int main() { asm(\"\" : \"=r\"(1 + 2)); return 0; }"),
+        "asm output operand is not assignable"
+    );
+}
+
+#[test]
+fn rejects_missing_operands() {
+    assert_eq!(
+        error("int main() { asm(\"mov %1, %0\" : : \"r\"(1)); return 0; }"),
+        "asm template refers to operand %1, which does not exist"
+    );
+}
+
+#[test]
+fn rejects_unterminated_templates() {
+    assert_eq!(
+        error("int main() { asm(\"nop); return 0; }"),
+        "unterminated string literal"
+    );
+}
+
+/// `main` adds 40 and 2 in inline assembly and returns the output.
+fn add_in_asm() -> Module {
+    let mut b = FunctionBuilder::new("main", Some(IrType::I32));
+    let lhs = b.iconst(IrType::I32, 40);
+    let rhs = b.iconst(IrType::I32, 2);
+    let sum = b
+        .inline_asm(
+            "movl %1, %0\n\taddl %2, %0",
+            vec![lhs, rhs],
+            Some(IrType::I32),
+        )
+        .unwrap();
+    b.ret(Some(sum));
+    let mut module = Module::default();
+    module.functions.push(b.finish());
+    module
+}
+
+#[test]
+fn prints_inline_asm() {
+    let module = add_in_asm();
+    let text = module.to_string();
+    assert!(
+        text.contains("%2 = asm i32 \"movl %1, %0\\n\\taddl %2, %0\"(%0, %1)"),
+        "{text}"
+    );
+}
+
+#[test]
+fn the_interpreter_cannot_run_inline_asm() {
+    let module = add_in_asm();
+    assert_eq!(
+        Interpreter::new(&module).call("main", &[]),
+        Err(Trap::InlineAsm)
+    );
+}
+
+#[test]
+fn x86_outputs_are_defined_from_their_register() {
+    let asm = CodeGenerator::new()
+        .with_backend(&X86_64::LINUX)
+        .with_register_allocation(true)
+        .generate(&add_in_asm());
+    assert!(
+        asm.contains("movl $40, %esi\n    movl $2, %edx\n\tmovl %esi, %edi\n\taddl %edx, %edi\n"),
+        "{asm}"
+    );
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("rcc-inline-asm-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (source, exe) = (dir.join("add.s"), dir.join("add"));
+    fs::write(&source, asm).unwrap();
+    let Ok(linked) = Command::new("cc").arg(&source).arg("-o").arg(&exe).status() else {
+        return;
+    };
+    assert!(linked.success());
+    let status = Command::new(&exe).status().unwrap();
+    assert_eq!(status.code(), Some(42));
+    fs::remove_dir_all(&dir).unwrap();
+}
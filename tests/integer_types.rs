@@ -0,0 +1,205 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use rcc::codegen::Target;
+use rcc::driver::Options;
+use rcc::opt::OptLevel;
+
+use common::{assert_assembles, compile, run_main};
+
+/// Mixes every width and signedness: a `long` product past 32 bits, an
+/// `unsigned` division and shift, and `char`s and `short`s that wrap when
+/// stored.
+const MIXED: &str = "long scale(long x, int n) { return x * n; }
+unsigned half(unsigned x) { return x / 2; }
+int main() {
+    long big = scale(100000, 100000);
+    unsigned u = -2;
+    unsigned char c = 300;
+    short s = 40000;
+    signed char d = 200;
+    unsigned short w = -1;
+    int r = 0;
+    r += big / 1000000000 == 10;
+    r += (half(u) == 2147483647) * 2;
+    r += (u >> 31 == 1) * 4;
+    r += (c == 44) * 8;
+    r += (s == -25536) * 16;
+    r += (d == -56) * 32;
+    r += (w == 65535) * 64;
+    return r;
+}";
+
+#[test]
+fn integer_types_have_their_sizes() {
+    let cases = [
+        ("char", 1),
+        ("signed char", 1),
+        ("unsigned char", 1),
+        ("short", 2),
+        ("short int", 2),
+        ("unsigned short", 2),
+        ("int", 4),
+        ("signed", 4),
+        ("unsigned", 4),
+        ("unsigned int", 4),
+        ("long", 8),
+        ("long int", 8),
+        ("unsigned long", 8),
+        ("long long", 8),
+        ("unsigned long long int", 8),
+        ("int long unsigned", 8),
+    ];
+    for (ty, size) in cases {
+        let source = format!("int main() {{ return sizeof({ty}); }}");
+        assert_eq!(run_main(&source, OptLevel::O0), Some(size), "{ty}");
+    }
+}
+
+#[test]
+fn integers_convert_by_width_and_signedness() {
+    let cases = [
+        (MIXED, 127),
+        ("int main() { unsigned x = 0; return x - 1 > 0; }", 1),
+        ("int main() { int x = -1; unsigned y = 1; return x < y; }", 0),
+        ("int main() { int x = -1; long y = 1; return x < y; }", 1),
+        ("int main() { unsigned x = -1; long y = x; return y > 0; }", 1),
+        (
+            "int main() { int x = -1; unsigned long y = x; unsigned m = -1; return y > m; }",
+            1,
+        ),
+        ("int main() { long x = 1; x <<= 40; return x >> 38; }", 4),
+        ("int main() { long x = -8; return x >> 1; }", -4),
+        ("int main() { unsigned x = 7; return -x > 0; }", 1),
+        ("int main() { unsigned char c = 255; return c + 1; }", 256),
+        ("int main() { char c = 127; c++; return c; }", -128),
+        ("int main() { unsigned char c = 0; c--; return c; }", 255),
+        ("int main() { unsigned x = 17; return x % 5 + ~x; }", -16),
+        (
+            "int main() { long a[3] = {1, 2, 3}; long *p = a; long i = 2; return p[i] + *(a + 1); }",
+            5,
+        ),
+        (
+            "int main() { long n = 1000000000; long x = 0; while (x < 5 * n) x += n; return x / n; }",
+            5,
+        ),
+    ];
+    for (source, expected) in cases {
+        for level in [OptLevel::O0, OptLevel::O2] {
+            assert_eq!(
+                run_main(source, level),
+                Some(expected),
+                "{source} at {level:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn type_specifiers_are_checked() {
+    let cases = [
+        (
+            "int main() { unsigned signed x; return 0; }",
+            "1:23: cannot combine `signed` with `unsigned`",
+        ),
+        (
+            "int main() { short long x; return 0; }",
+            "1:20: cannot combine `long` with `short`",
+        ),
+        (
+            "int main() { long long long x; return 0; }",
+            "1:24: cannot combine `long` with `long`",
+        ),
+        (
+            "int main() { unsigned void x; return 0; }",
+            "1:23: cannot combine `void` with `unsigned`",
+        ),
+        (
+            "int main() { char int x; return 0; }",
+            "1:19: cannot combine `int` with `char`",
+        ),
+        (
+            "int main() { long *p; int *q = p; return 0; }",
+            "1:32: expected `int *` but found `long *`",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(
+            compile(source, &Options::default()).unwrap_err(),
+            expected,
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn unsigned_operators_lower_to_unsigned_instructions() {
+    let ir = compile(
+        "unsigned f(unsigned a, unsigned b) { return a / b + a % b + (a >> b) + (a < b); }
+         long g(int x) { return x; }
+         unsigned long h(unsigned x) { return x; }
+         unsigned char i(int x) { return x; }",
+        &Options::default(),
+    )
+    .unwrap()
+    .ir;
+    let text = ir.to_string();
+    for line in [
+        "udiv i32",
+        "urem i32",
+        "lshr i32",
+        "cmp ult i32",
+        "sext i32",
+        "zext i32",
+        "trunc i32",
+    ] {
+        assert!(text.contains(line), "{line} in {text}");
+    }
+}
+
+#[test]
+fn integer_code_assembles_for_aarch64() {
+    for (target, triple) in [
+        (Target::AARCH64_LINUX, "aarch64-linux-gnu"),
+        (Target::AARCH64_APPLE, "arm64-apple-macos"),
+    ] {
+        for opt_level in [OptLevel::O0, OptLevel::O2] {
+            let asm = compile(
+                MIXED,
+                &Options {
+                    target,
+                    opt_level,
+                    ..Options::default()
+                },
+            )
+            .unwrap()
+            .assembly;
+            assert_assembles(triple, &asm);
+        }
+    }
+}
+
+#[test]
+fn x86_program_mixes_integer_types() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let options = Options {
+        target: Target::X86_64_LINUX,
+        ..Options::default()
+    };
+    let asm = compile(MIXED, &options).unwrap().assembly;
+    let dir = std::env::temp_dir().join(format!("rcc-integer-types-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (source, exe) = (dir.join("mixed.s"), dir.join("mixed"));
+    fs::write(&source, &asm).unwrap();
+    let Ok(linked) = Command::new("cc").arg(&source).arg("-o").arg(&exe).status() else {
+        return;
+    };
+    assert!(linked.success(), "{asm}");
+    let output = Command::new(&exe).output().unwrap();
+    assert_eq!(output.status.code(), Some(127));
+    fs::remove_dir_all(&dir).unwrap();
+}
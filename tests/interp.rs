@@ -0,0 +1,171 @@
+use rcc::driver::{self, Options};
+use rcc::ir::interp::{Interpreter, Trap};
+use rcc::ir::{BinOp, FunctionBuilder, IrType, Module};
+use rcc::opt::OptLevel;
+
+fn run_main(source: &str, opt_level: OptLevel) -> Result<Option<i64>, Trap> {
+    let ir = driver::compile(
+        source,
+        &Options {
+            opt_level,
+            ..Options::default()
+        },
+    )
+    .expect("program should compile")
+    .ir;
+    Interpreter::new(&ir).call("main", &[])
+}
+
+#[test]
+fn evaluates_arithmetic_and_calls() {
+    let source =
+        "int three() { return 3; } int main() { return (three() + 4) * 6 - 10 / three(); }";
+    assert_eq!(run_main(source, OptLevel::O0), Ok(Some(39)));
+}
+
+#[test]
+fn character_constants_are_ints() {
+    assert_eq!(
+        run_main(
+            r"int main() { return 'a' - '\x41' + '\n' * 2; }",
+            OptLevel::O0
+        ),
+        Ok(Some(52))
+    );
+    assert_eq!(
+        run_main(r"int main() { return '\xff'; }", OptLevel::O0),
+        Ok(Some(-1))
+    );
+}
+
+#[test]
+fn wraps_like_32_bit_integers() {
+    let source = "int main() { return 2147483647 + 1; }";
+    assert_eq!(
+        run_main(source, OptLevel::O0),
+        Ok(Some(i64::from(i32::MIN)))
+    );
+}
+
+#[test]
+fn traps_on_undefined_behavior() {
+    let err = run_main("int main() { return 1 / 0; }", OptLevel::O0).unwrap_err();
+    assert_eq!(err, Trap::UndefinedBehavior("sdiv 1, 0".to_string()));
+    assert_eq!(err.to_string(), "undefined behavior: sdiv 1, 0");
+}
+
+#[test]
+fn traps_on_unknown_functions() {
+    let mut f = FunctionBuilder::new("main", Some(IrType::I32));
+    let result = f.call("external", vec![], Some(IrType::I32));
+    f.ret(result);
+    let module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+    assert_eq!(
+        Interpreter::new(&module).call("main", &[]),
+        Err(Trap::UnknownFunction("external".to_string()))
+    );
+}
+
+#[test]
+fn runs_loops_and_stops_when_out_of_fuel() {
+    // f(n): if (n) return f(n - 1) + 2; return 0;
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let n = f.param(IrType::I32);
+    let (recurse, done) = (f.create_block(), f.create_block());
+    f.branch(n, recurse, done);
+    f.switch_to(recurse);
+    let one = f.iconst(IrType::I32, 1);
+    let smaller = f.binary(BinOp::Sub, n, one);
+    let inner = f.call("f", vec![smaller], Some(IrType::I32)).unwrap();
+    let two = f.iconst(IrType::I32, 2);
+    let sum = f.binary(BinOp::Add, inner, two);
+    f.ret(Some(sum));
+    f.switch_to(done);
+    let zero = f.iconst(IrType::I32, 0);
+    f.ret(Some(zero));
+    let module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+
+    assert_eq!(Interpreter::new(&module).call("f", &[10]), Ok(Some(20)));
+    assert_eq!(
+        Interpreter::new(&module).with_fuel(50).call("f", &[10]),
+        Err(Trap::OutOfFuel)
+    );
+    assert_eq!(
+        Interpreter::new(&module).call("f", &[5000]),
+        Err(Trap::StackOverflow)
+    );
+
+    // An infinite loop runs out of fuel rather than hanging.
+    let mut spin = FunctionBuilder::new("spin", None);
+    let header = spin.create_block();
+    spin.jump(header);
+    spin.switch_to(header);
+    spin.jump(header);
+    let module = Module {
+        functions: vec![spin.finish()],
+        ..Module::default()
+    };
+    assert_eq!(
+        Interpreter::new(&module).call("spin", &[]),
+        Err(Trap::OutOfFuel)
+    );
+}
+
+#[test]
+fn operators_follow_c_precedence() {
+    let cases = [
+        ("1 + 2 << 3", 24),
+        ("1 << 4 >> 2", 4),
+        ("0 - 16 >> 2", -4),
+        ("6 & 3 | 8 ^ 12", 6),
+        ("1 | 2 == 2", 1),
+        ("2 < 3 == 1", 1),
+        ("3 > 2 > 1", 0),
+        ("1 || 0 && 0", 1),
+        ("7 % 4 <= 3 && 2 >= 2", 1),
+        ("5 & 6 != 0", 1),
+        ("-2 * -3", 6),
+        ("- -4 - 1", 3),
+        ("~5 + 1", -5),
+        ("!0 + !7 * 10", 1),
+        ("-1 << 2", -4),
+        ("!!-3 == ~~1", 1),
+    ];
+    for (expr, expected) in cases {
+        let source = format!("int main() {{ return {expr}; }}");
+        for level in [OptLevel::O0, OptLevel::O2] {
+            assert_eq!(
+                run_main(&source, level),
+                Ok(Some(expected)),
+                "{expr} at {level:?}"
+            );
+        }
+    }
+}
+
+/// The interpreter is the oracle: every optimization level must compute
+/// what the unoptimized IR computes.
+#[test]
+fn optimizations_preserve_results() {
+    let programs = [
+        "int main() { return 2 * 3 + 4; }",
+        "int f() { return 5; } int main() { return f() * 8 + 8 * f(); }",
+        "int f() { return 0 - 7; } int main() { return f() / 4 + f() / 2 * 3; }",
+        "int f() { return 100; } int main() { return f() / 16 - f() * 1 + 0 * f(); }",
+        "int g() { return 3; } int f() { return g() * g() - 1; } int main() { return f() * f(); }",
+        "int f() { return 2147483647; } int main() { return f() + f() / 3; }",
+    ];
+    for source in programs {
+        let expected = run_main(source, OptLevel::O0);
+        assert!(expected.is_ok(), "{source}: {expected:?}");
+        for level in [OptLevel::O1, OptLevel::O2, OptLevel::Os] {
+            assert_eq!(run_main(source, level), expected, "{source} at {level:?}");
+        }
+    }
+}
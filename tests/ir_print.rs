@@ -0,0 +1,143 @@
+use std::fs;
+use std::process::Command;
+
+use rcc::driver::{self, Options};
+use rcc::ir::{BinOp, CmpOp, FunctionBuilder, IrType, Module};
+use rcc::opt::OptLevel;
+
+const SOURCE: &str = "int two() { return 2; } int main() { return two() * 4 + 3 * 5; }";
+
+fn ir(opt_level: OptLevel) -> String {
+    driver::compile(
+        SOURCE,
+        &Options {
+            opt_level,
+            ..Options::default()
+        },
+    )
+    .expect("program should compile")
+    .ir
+    .to_string()
+}
+
+#[test]
+fn prints_unoptimized_ir() {
+    let expected = "\
+fn two() -> i32 {
+bb0:
+    %0 = const i32 2
+    ret %0
+}
+
+fn main() -> i32 {
+bb0:
+    %0 = call i32 @two()
+    %1 = const i32 4
+    %2 = mul i32 %0, %1
+    %3 = const i32 3
+    %4 = const i32 5
+    %5 = mul i32 %3, %4
+    %6 = add i32 %2, %5
+    ret %6
+}
+";
+    assert_eq!(ir(OptLevel::O0), expected);
+}
+
+#[test]
+fn prints_optimized_ir() {
+    let expected = "\
+fn two() -> i32 {
+bb0:
+    %0 = const i32 2
+    ret %0
+}
+
+fn main() -> i32 {
+bb0:
+    %0 = call i32 @two()
+    %8 = const i32 2
+    %2 = shl i32 %0, %8
+    %5 = const i32 15
+    %6 = add i32 %2, %5
+    ret %6
+}
+";
+    assert_eq!(ir(OptLevel::O1), expected);
+}
+
+#[test]
+fn prints_params_void_calls_and_branches() {
+    let mut f = FunctionBuilder::new("f", None);
+    let x = f.param(IrType::I32);
+    let y = f.param(IrType::I32);
+    let (then_block, else_block) = (f.create_block(), f.create_block());
+    let diff = f.binary(BinOp::Sub, x, y);
+    f.branch(diff, then_block, else_block);
+    f.switch_to(then_block);
+    f.call("g", vec![x, diff], None);
+    f.ret(None);
+    f.switch_to(else_block);
+    f.jump(then_block);
+    let module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+
+    let expected = "\
+fn f(i32 %0, i32 %1) {
+bb0:
+    %2 = sub i32 %0, %1
+    br %2, bb1, bb2
+bb1:
+    call void @g(%0, %2)
+    ret
+bb2:
+    jump bb1
+}
+";
+    assert_eq!(module.to_string(), expected);
+}
+
+#[test]
+fn prints_comparisons_with_their_operand_type() {
+    let mut f = FunctionBuilder::new("lt", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let y = f.param(IrType::I32);
+    let signed = f.cmp(CmpOp::Slt, x, y);
+    let unsigned = f.cmp(CmpOp::Uge, x, signed);
+    f.ret(Some(unsigned));
+    let module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+
+    let expected = "\
+fn lt(i32 %0, i32 %1) -> i32 {
+bb0:
+    %2 = icmp slt i32 %0, %1
+    %3 = icmp uge i32 %0, %2
+    ret %3
+}
+";
+    assert_eq!(module.to_string(), expected);
+}
+
+#[test]
+fn cli_emits_ir_to_stdout() {
+    let dir = std::env::temp_dir().join(format!("rcc-emit-ir-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("main.c");
+    fs::write(&input, SOURCE).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rcc"))
+        .current_dir(&dir)
+        .args(["-O1", "--emit=ir", "-o", "-"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), ir(OptLevel::O1));
+    assert!(!dir.join("main.s").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,64 @@
+use rcc::json::Value;
+
+#[test]
+fn parses_every_kind_of_value() {
+    let value =
+        Value::parse(r#" {"a": [1, -2.5e1, true, false, null], "b": {"c": "d"}} "#).unwrap();
+    assert_eq!(
+        value,
+        Value::object([
+            (
+                "a",
+                Value::Array(vec![
+                    Value::Number(1.0),
+                    Value::Number(-25.0),
+                    Value::Bool(true),
+                    Value::Bool(false),
+                    Value::Null,
+                ])
+            ),
+            ("b", Value::object([("c", Value::from("d"))])),
+        ])
+    );
+    assert_eq!(
+        value
+            .get("b")
+            .and_then(|b| b.get("c"))
+            .and_then(Value::as_str),
+        Some("d")
+    );
+}
+
+#[test]
+fn strings_round_trip_through_escapes() {
+    let value = Value::parse(r#""line\n\"quoted\" \\ \u00e9 \ud83d\ude00""#).unwrap();
+    assert_eq!(value.as_str(), Some("line\n\"quoted\" \\ é 😀"));
+    assert_eq!(value.to_string(), "\"line\\n\\\"quoted\\\" \\\\ é 😀\"");
+    assert_eq!(Value::from("\u{1}").to_string(), "\"\\u0001\"");
+}
+
+#[test]
+fn prints_compactly_in_member_order() {
+    let value = Value::object([
+        ("z", Value::from(1)),
+        ("a", Value::Array(vec![Value::Number(0.5), Value::Null])),
+    ]);
+    assert_eq!(value.to_string(), r#"{"z":1,"a":[0.5,null]}"#);
+    assert_eq!(Value::parse(&value.to_string()).unwrap(), value);
+}
+
+#[test]
+fn malformed_input_is_rejected() {
+    for text in [
+        "",
+        "{",
+        "[1,]",
+        "{\"a\" 1}",
+        "tru",
+        "\"open",
+        "1 2",
+        "\"\\x\"",
+    ] {
+        assert!(Value::parse(text).is_err(), "{text:?}");
+    }
+}
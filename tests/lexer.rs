@@ -0,0 +1,258 @@
+use rcc::json::Value;
+use rcc::lexer::{self, Keyword, Lexer, Position, Token, TokenKind};
+use rcc::standard::Standard;
+
+#[test]
+fn identifiers_borrow_the_source() {
+    let source = "int main() { return helper(); }";
+    let tokens = Lexer::new(source).lex().unwrap();
+    let names: Vec<&str> = tokens
+        .iter()
+        .filter_map(|token| match token.kind {
+            TokenKind::Identifier(name) => Some(name),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(names, ["main", "helper"]);
+    let range = source.as_bytes().as_ptr_range();
+    assert!(names.iter().all(|name| range.contains(&name.as_ptr())));
+    assert_eq!(tokens[0].kind, TokenKind::Keyword(Keyword::Int));
+}
+
+#[test]
+fn positions_count_characters_not_bytes() {
+    let tokens = Lexer::new("\"é\" x\n  y").lex().unwrap();
+    assert_eq!(tokens[0].kind, TokenKind::StringLit("é"));
+    assert_eq!(tokens[1].kind, TokenKind::Identifier("x"));
+    assert_eq!(tokens[1].pos, Position { line: 1, column: 5 });
+    assert_eq!(tokens[2].pos, Position { line: 2, column: 3 });
+}
+
+#[test]
+fn characters_outside_ascii_are_read_whole() {
+    // A no-break space, then a comment and a literal of multi-byte
+    // characters and an escape after one.
+    let tokens = Lexer::new("\u{a0}a /* ü→ */ b \"日本\\n\" c")
+        .lex()
+        .unwrap();
+    let columns: Vec<u32> = tokens.iter().map(|token| token.pos.column).collect();
+    assert_eq!(columns, [2, 13, 15, 22, 23]);
+    assert_eq!(tokens[2].kind, TokenKind::StringLit("日本\\n"));
+    let err = Lexer::new("x ¿").lex().unwrap_err();
+    assert_eq!(err.to_string(), "1:3: unexpected character `¿`");
+    let err = Lexer::new("'é'").lex().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "1:1: multi-character literal 'é' is not supported"
+    );
+    let err = Lexer::new("\"\\é\"").lex().unwrap_err();
+    assert_eq!(err.to_string(), "1:2: unknown escape sequence `\\é`");
+}
+
+#[test]
+fn string_literals_keep_their_escapes_until_unescaped() {
+    let tokens = Lexer::new(r#""a\tb\n\"q\"""#).lex().unwrap();
+    let TokenKind::StringLit(raw) = tokens[0].kind else {
+        panic!("expected a string literal, found {:?}", tokens[0].kind);
+    };
+    assert_eq!(raw, r#"a\tb\n\"q\""#);
+    assert_eq!(lexer::unescape(raw), "a\tb\n\"q\"");
+    assert_eq!(
+        tokens[0].kind.to_string(),
+        r#"string literal "a\tb\n\"q\"""#
+    );
+
+    let err = Lexer::new(r#""\q""#).lex().unwrap_err();
+    assert_eq!(err.message, "unknown escape sequence `\\q`");
+}
+
+#[test]
+fn comments_are_skipped_and_keep_positions() {
+    let source = "/* a\n * block */ x // line\n/**/y/* ** */ / z";
+    let tokens = Lexer::new(source).lex().unwrap();
+    let kinds: Vec<String> = tokens.iter().map(|t| t.kind.to_string()).collect();
+    assert_eq!(
+        kinds,
+        [
+            "identifier `x`",
+            "identifier `y`",
+            "`/`",
+            "identifier `z`",
+            "end of file"
+        ]
+    );
+    assert_eq!(
+        tokens[0].pos,
+        Position {
+            line: 2,
+            column: 13
+        }
+    );
+    assert_eq!(tokens[1].pos, Position { line: 3, column: 5 });
+    assert_eq!(
+        tokens[2].pos,
+        Position {
+            line: 3,
+            column: 15
+        }
+    );
+}
+
+#[test]
+fn unterminated_block_comments_are_errors() {
+    let err = Lexer::new("x\n  /* never\nclosed *").lex().unwrap_err();
+    assert_eq!(err.to_string(), "2:3: unterminated comment");
+}
+
+#[test]
+fn line_comments_need_c99_or_gnu() {
+    let lex = |std| {
+        Lexer::new("x // note\n")
+            .with_standard(Standard::parse(std).unwrap())
+            .lex()
+    };
+    assert!(lex("c99").is_ok());
+    assert!(lex("gnu89").is_ok());
+    let err = lex("c89").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "1:3: a `//` comment is only valid in C99 and later"
+    );
+    // Inside a block comment, `//` is just text.
+    let tokens = Lexer::new("/* // */ x")
+        .with_standard(Standard::parse("c89").unwrap())
+        .lex()
+        .unwrap();
+    assert_eq!(tokens[0].kind, TokenKind::Identifier("x"));
+}
+
+#[test]
+fn character_literals_resolve_escapes() {
+    let source = r"'a' '\n' '\0' '\\' '\'' '\x41' '\101' '\?' '\xff'";
+    let tokens = Lexer::new(source).lex().unwrap();
+    let values: Vec<u8> = tokens
+        .iter()
+        .filter_map(|token| match token.kind {
+            TokenKind::CharLit(raw) => Some(lexer::char_value(raw)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        values,
+        [b'a', b'\n', 0, b'\\', b'\'', 0x41, 0x41, b'?', 0xFF]
+    );
+    assert_eq!(tokens[0].kind.to_string(), "character literal 'a'");
+    assert_eq!(tokens[5].kind, TokenKind::CharLit(r"\x41"));
+    assert_eq!(tokens[1].kind.value(), Value::from(10u32));
+}
+
+#[test]
+fn malformed_character_literals_are_errors() {
+    let error = |source| Lexer::new(source).lex().unwrap_err().to_string();
+    assert_eq!(error("''"), "1:1: empty character literal");
+    assert_eq!(
+        error("x 'ab'"),
+        "1:3: multi-character literal 'ab' is not supported"
+    );
+    assert_eq!(error("'a"), "1:1: unterminated character literal");
+    assert_eq!(error(r"'\q'"), r"1:2: unknown escape sequence `\q`");
+    assert_eq!(
+        error(r"'\x100'"),
+        r"1:2: escape sequence `\x100` is out of range"
+    );
+}
+
+#[test]
+fn strings_share_the_escape_sequences() {
+    let tokens = Lexer::new(r#""\x41\102\r\a""#).lex().unwrap();
+    let TokenKind::StringLit(raw) = tokens[0].kind else {
+        panic!("expected a string literal, found {:?}", tokens[0].kind);
+    };
+    assert_eq!(lexer::unescape(raw), "AB\r\u{7}");
+}
+
+#[test]
+fn operators_take_the_longest_match() {
+    let tokens = Lexer::new("a<<b>>c&&d||!e!=f^g|h&i==j<=k>=l<m>~n")
+        .lex()
+        .unwrap();
+    let operators: Vec<&str> = tokens
+        .iter()
+        .filter_map(|token| match token.kind {
+            TokenKind::Operator(op) => Some(op.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        operators,
+        ["<<", ">>", "&&", "||", "!", "!=", "^", "|", "&", "==", "<=", ">=", "<", ">", "~"]
+    );
+}
+
+#[test]
+fn compound_assignments_are_single_operators() {
+    let tokens = Lexer::new("a+=b-=c*=d/=e%=f&=g|=h^=i<<=j>>=k<<l= =m")
+        .lex()
+        .unwrap();
+    let operators: Vec<&str> = tokens
+        .iter()
+        .filter_map(|token| match token.kind {
+            TokenKind::Operator(op) => Some(op.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        operators,
+        ["+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "<<=", ">>=", "<<", "=", "="]
+    );
+}
+
+#[test]
+fn increments_are_read_greedily() {
+    let tokens = Lexer::new("a+++b---c- -d").lex().unwrap();
+    let operators: Vec<&str> = tokens
+        .iter()
+        .filter_map(|token| match token.kind {
+            TokenKind::Operator(op) => Some(op.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(operators, ["++", "+", "--", "-", "-", "-"]);
+}
+
+#[test]
+fn tokens_are_small() {
+    // The kind, with any text borrowed from the source, and the span.
+    assert!(std::mem::size_of::<Token>() <= 40);
+}
+
+#[test]
+fn tokens_serialize_as_json() {
+    let tokens = Lexer::new("int f() { asm(\"a\\tb\"); }").lex().unwrap();
+    let json = lexer::tokens_json(&tokens);
+    assert!(
+        json.starts_with(
+            "[\n  {\"kind\":\"keyword\",\"value\":\"int\",\"pos\":{\"line\":1,\"column\":1},\"end\":{\"line\":1,\"column\":4}},\n"
+        ),
+        "{json}"
+    );
+    assert!(
+        json.ends_with("{\"kind\":\"eof\",\"value\":null,\"pos\":{\"line\":1,\"column\":25},\"end\":{\"line\":1,\"column\":25}}\n]\n"),
+        "{json}"
+    );
+    let value = Value::parse(&json).unwrap();
+    let tokens = value.as_array().unwrap();
+    assert_eq!(
+        tokens[1].get("kind").and_then(Value::as_str),
+        Some("identifier")
+    );
+    assert_eq!(
+        tokens[2].get("kind").and_then(Value::as_str),
+        Some("punctuation")
+    );
+    assert_eq!(tokens[7].get("value").and_then(Value::as_str), Some("a\tb"));
+    let pos = tokens[1].get("pos").unwrap();
+    assert_eq!(pos.get("column").and_then(Value::as_u64), Some(5));
+    let end = tokens[1].get("end").unwrap();
+    assert_eq!(end.get("column").and_then(Value::as_u64), Some(6));
+}
@@ -0,0 +1,115 @@
+use rcc::ir::{BinOp, BlockId, FunctionBuilder, Instr, IrType, Module, Terminator, Value};
+use rcc::opt::{self, OptLevel};
+
+/// Builds `f(k, d)`: `while (more()) use(k * 3 + step(), k / d);`
+fn counted_loop() -> (Module, Value, Value) {
+    let mut f = FunctionBuilder::new("f", None);
+    let k = f.param(IrType::I32);
+    let d = f.param(IrType::I32);
+    let (header, body, exit) = (f.create_block(), f.create_block(), f.create_block());
+    f.jump(header);
+
+    f.switch_to(header);
+    let more = f.call("more", vec![], Some(IrType::I32)).unwrap();
+    f.branch(more, body, exit);
+
+    f.switch_to(body);
+    let three = f.iconst(IrType::I32, 3);
+    let scaled = f.binary(BinOp::Mul, k, three);
+    let step = f.call("step", vec![], Some(IrType::I32)).unwrap();
+    let sum = f.binary(BinOp::Add, scaled, step);
+    let quotient = f.binary(BinOp::SDiv, k, d);
+    f.call("use", vec![sum, quotient], None);
+    f.jump(header);
+
+    f.switch_to(exit);
+    f.ret(None);
+
+    let module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+    (module, scaled, sum)
+}
+
+fn defining_block(module: &Module, value: Value) -> BlockId {
+    let func = &module.functions[0];
+    func.block_ids()
+        .find(|&id| func.block(id).instrs.iter().any(|i| i.dst() == Some(value)))
+        .expect("value is defined")
+}
+
+#[test]
+fn hoists_invariant_multiply_into_preheader() {
+    let (mut module, scaled, _) = counted_loop();
+    opt::optimize(&mut module, OptLevel::O2);
+    assert_eq!(defining_block(&module, scaled), BlockId::ENTRY);
+}
+
+#[test]
+fn keeps_values_depending_on_loop_calls() {
+    let (mut module, _, sum) = counted_loop();
+    opt::optimize(&mut module, OptLevel::O2);
+    assert_ne!(defining_block(&module, sum), BlockId::ENTRY);
+}
+
+#[test]
+fn does_not_hoist_division_by_unknown_divisor() {
+    let (mut module, _, _) = counted_loop();
+    opt::optimize(&mut module, OptLevel::O2);
+    let entry = module.functions[0].block(BlockId::ENTRY);
+    assert!(!entry.instrs.iter().any(|i| matches!(
+        i,
+        Instr::Binary {
+            op: BinOp::SDiv,
+            ..
+        }
+    )));
+}
+
+#[test]
+fn licm_is_disabled_at_o1() {
+    let (mut module, scaled, _) = counted_loop();
+    opt::optimize(&mut module, OptLevel::O1);
+    assert_ne!(defining_block(&module, scaled), BlockId::ENTRY);
+}
+
+#[test]
+fn creates_preheader_when_header_has_several_entries() {
+    let mut f = FunctionBuilder::new("g", None);
+    let k = f.param(IrType::I32);
+    let (other, header, body, exit) = (
+        f.create_block(),
+        f.create_block(),
+        f.create_block(),
+        f.create_block(),
+    );
+    f.branch(k, header, other);
+    f.switch_to(other);
+    f.call("other", vec![], None);
+    f.jump(header);
+
+    f.switch_to(header);
+    let more = f.call("more", vec![], Some(IrType::I32)).unwrap();
+    f.branch(more, body, exit);
+
+    f.switch_to(body);
+    let doubled = f.binary(BinOp::Add, k, k);
+    f.call("use", vec![doubled], None);
+    f.jump(header);
+
+    f.switch_to(exit);
+    f.ret(None);
+
+    let mut module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+    opt::optimize(&mut module, OptLevel::O2);
+
+    let func = &module.functions[0];
+    let preheader = defining_block(&module, doubled);
+    assert_eq!(func.block(preheader).term, Terminator::Jump(header));
+    assert_eq!(func.block(preheader).instrs.len(), 1);
+    assert_eq!(func.blocks.len(), 6);
+}
@@ -0,0 +1,220 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use rcc::codegen::Target;
+use rcc::driver::Options;
+use rcc::lexer::{IntSuffix, Lexer, TokenKind};
+use rcc::opt::OptLevel;
+
+use common::{compile, run_main};
+
+/// Wide constants as operands of every kind of instruction, each check
+/// setting one bit of the result on failure.
+const WIDE: &str = "long g = 5000000000;
+unsigned long big = 18446744073709551615UL;
+int main() {
+    long x = 5000000000;
+    long y = x + 10000000000;
+    long z = x * 1000000000l;
+    long a[2];
+    a[0] = 123456789012;
+    int bad = 0;
+    bad = bad | (x != 5000000000);
+    bad = bad | (y >> 32 != 3) << 1;
+    bad = bad | (z / 1000000000L != 5000000000) << 2;
+    bad = bad | (big != 18446744073709551615UL) << 3;
+    bad = bad | (big + 1 != 0) << 4;
+    bad = bad | (a[0] - 123456789000 != 12) << 5;
+    bad = bad | (g * 2 != 10000000000) << 6;
+    bad = bad | (4294967295u + 1u != 0) << 7;
+    bad = bad | (-9223372036854775807L - 1 >= 0) << 8;
+    return bad;
+}";
+
+#[test]
+fn suffixes_are_lexed_in_any_case_and_order() {
+    let cases = [
+        ("7", IntSuffix::Plain),
+        ("7u", IntSuffix::Unsigned),
+        ("7L", IntSuffix::Long),
+        ("7ll", IntSuffix::Long),
+        ("7uL", IntSuffix::UnsignedLong),
+        ("7LU", IntSuffix::UnsignedLong),
+        ("7ull", IntSuffix::UnsignedLong),
+        ("7LLu", IntSuffix::UnsignedLong),
+    ];
+    for (source, suffix) in cases {
+        let tokens = Lexer::new(source).lex().unwrap();
+        assert_eq!(tokens[0].kind, TokenKind::IntLit(7, suffix), "{source}");
+    }
+    let tokens = Lexer::new("18446744073709551615u").lex().unwrap();
+    assert_eq!(
+        tokens[0].kind,
+        TokenKind::IntLit(u64::MAX, IntSuffix::Unsigned)
+    );
+}
+
+#[test]
+fn octal_and_hexadecimal_literals_are_lexed() {
+    let cases = [
+        ("0", 0, IntSuffix::Plain),
+        ("010", 8, IntSuffix::Plain),
+        ("0777u", 511, IntSuffix::Unsigned),
+        ("0x10", 16, IntSuffix::Plain),
+        ("0XfF", 255, IntSuffix::Plain),
+        ("0x1aL", 26, IntSuffix::Long),
+        // Too big for `int` but not for `unsigned int`.
+        ("0xFFFFFFFF", 0xFFFF_FFFF, IntSuffix::Unsigned),
+        ("037777777777", 0xFFFF_FFFF, IntSuffix::Unsigned),
+        ("0x100000000", 1 << 32, IntSuffix::Plain),
+        ("0xFFFFFFFFFFFFFFFF", u64::MAX, IntSuffix::UnsignedLong),
+        ("0x8000000000000000L", 1 << 63, IntSuffix::UnsignedLong),
+    ];
+    for (source, value, suffix) in cases {
+        let tokens = Lexer::new(source).lex().unwrap();
+        assert_eq!(tokens[0].kind, TokenKind::IntLit(value, suffix), "{source}");
+    }
+    assert_eq!(
+        run_main(
+            "int main() { return (010 + 0x10 + 0) * (0xFFFFFFFF > 0) + sizeof 0x80000000; }",
+            OptLevel::O0
+        ),
+        Some(28)
+    );
+}
+
+#[test]
+fn literals_take_the_first_type_that_holds_them() {
+    let cases = [
+        ("1", 4),
+        ("2147483647", 4),
+        ("2147483648", 8),
+        ("1l", 8),
+        ("1u", 4),
+        ("4294967295u", 4),
+        ("4294967296u", 8),
+        ("1ul", 8),
+    ];
+    for (literal, size) in cases {
+        let source = format!("int main() {{ return sizeof {literal}; }}");
+        assert_eq!(run_main(&source, OptLevel::O0), Some(size), "{literal}");
+    }
+    // `2147483648` is a `long`, so negating it doesn't overflow, and a
+    // `u` literal makes the comparison unsigned.
+    assert_eq!(
+        run_main(
+            "int main() { return (-2147483648 < 0) + (-1 < 0u) * 2; }",
+            OptLevel::O0
+        ),
+        Some(1)
+    );
+}
+
+#[test]
+fn out_of_range_literals_are_errors() {
+    let cases = [
+        (
+            "int main() { return 9223372036854775808; }",
+            "1:21: integer literal `9223372036854775808` does not fit in `long`; add a `u` suffix to make it `unsigned long`",
+        ),
+        (
+            "int main() { return 9223372036854775808L; }",
+            "1:21: integer literal `9223372036854775808L` does not fit in `long`; add a `u` suffix to make it `unsigned long`",
+        ),
+        (
+            "int main() { return 18446744073709551616u; }",
+            "1:21: integer literal `18446744073709551616u` does not fit in `unsigned long`",
+        ),
+        (
+            "int main() { return 08; }",
+            "1:21: invalid digit `8` in octal literal `08`",
+        ),
+        (
+            "int main() { return 0779; }",
+            "1:21: invalid digit `9` in octal literal `0779`",
+        ),
+        (
+            "int main() { return 0x; }",
+            "1:21: hexadecimal literal `0x` has no digits",
+        ),
+        (
+            "int main() { return 0x1g; }",
+            "1:21: invalid integer literal `0x1g`",
+        ),
+        (
+            "int main() { return 0x10000000000000000; }",
+            "1:21: integer literal `0x10000000000000000` does not fit in `unsigned long`",
+        ),
+        (
+            "int main() { return 1lul; }",
+            "1:21: invalid integer literal `1lul`",
+        ),
+        (
+            "int main() { int a[4294967296]; return 0; }",
+            "1:20: array size `4294967296` is too large",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(
+            compile(source, &Options::default()).unwrap_err(),
+            expected,
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn wide_constants_keep_every_bit() {
+    for level in [OptLevel::O0, OptLevel::O2] {
+        assert_eq!(run_main(WIDE, level), Some(0), "{level:?}");
+    }
+}
+
+#[test]
+fn aarch64_builds_wide_constants_a_halfword_at_a_time() {
+    let options = Options {
+        target: Target::AARCH64_LINUX,
+        ..Options::default()
+    };
+    let asm = compile("long f(void) { return 5000000000; }", &options)
+        .unwrap()
+        .assembly;
+    // 5000000000 is 0x1_2a05_f200.
+    for line in [
+        "movz x0, #0xf200, lsl #0",
+        "movk x0, #0x2a05, lsl #16",
+        "movk x0, #0x1, lsl #32",
+    ] {
+        assert!(asm.contains(line), "{line} in {asm}");
+    }
+}
+
+#[test]
+fn x86_loads_wide_operands_with_movabsq() {
+    for level in [OptLevel::O0, OptLevel::O2] {
+        let options = Options {
+            target: Target::X86_64_LINUX,
+            opt_level: level,
+            ..Options::default()
+        };
+        let asm = compile(WIDE, &options).unwrap().assembly;
+        assert!(asm.contains("movabsq $5000000000, %rcx"), "{asm}");
+        if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+            continue;
+        }
+        let dir =
+            std::env::temp_dir().join(format!("rcc-literals-{}-{level:?}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (source, exe) = (dir.join("wide.s"), dir.join("wide"));
+        fs::write(&source, &asm).unwrap();
+        let Ok(linked) = Command::new("cc").arg(&source).arg("-o").arg(&exe).status() else {
+            return;
+        };
+        assert!(linked.success(), "{asm}");
+        let output = Command::new(&exe).output().unwrap();
+        assert_eq!(output.status.code(), Some(0), "{asm}");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
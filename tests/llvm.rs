@@ -0,0 +1,193 @@
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use rcc::ir::llvm;
+use rcc::ir::{BinOp, CastOp, CmpOp, Datum, FunctionBuilder, Global, IrType, Module};
+
+/// Runs `ll` with `lli` and returns `main`'s exit status, or `None` if
+/// `lli` is not installed.
+fn run(ll: &str) -> Option<i32> {
+    let mut child = Command::new("lli")
+        .arg("-opaque-pointers")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(ll.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.stderr.is_empty(),
+        "{}\n{ll}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output.status.code()
+}
+
+/// `int sum(int n)` adding `1..=n` in a loop through a stack slot, and a
+/// `main` returning `sum(10) - 50`.
+fn loop_module() -> Module {
+    let mut f = FunctionBuilder::new("sum", Some(IrType::I32));
+    let n = f.param(IrType::I32);
+    let total = f.stack_slot(IrType::I32);
+    let i = f.stack_slot(IrType::I32);
+    let zero = f.iconst(IrType::I32, 0);
+    f.store(total, zero);
+    f.store(i, n);
+    let (header, body, exit) = (f.create_block(), f.create_block(), f.create_block());
+    f.jump(header);
+    f.switch_to(header);
+    let current = f.load(i);
+    let more = f.cmp(CmpOp::Sgt, current, zero);
+    f.branch(more, body, exit);
+    f.switch_to(body);
+    let (t, c) = (f.load(total), f.load(i));
+    let t = f.binary(BinOp::Add, t, c);
+    f.store(total, t);
+    let one = f.iconst(IrType::I32, 1);
+    let c = f.binary(BinOp::Sub, c, one);
+    f.store(i, c);
+    f.jump(header);
+    f.switch_to(exit);
+    let result = f.load(total);
+    f.ret(Some(result));
+    let sum = f.finish();
+
+    let mut f = FunctionBuilder::new("main", Some(IrType::I32));
+    let ten = f.iconst(IrType::I32, 10);
+    let result = f.call("sum", vec![ten], Some(IrType::I32)).unwrap();
+    let fifty = f.iconst(IrType::I32, 50);
+    let result = f.binary(BinOp::Sub, result, fifty);
+    f.ret(Some(result));
+    Module {
+        functions: vec![sum, f.finish()],
+        ..Module::default()
+    }
+}
+
+#[test]
+fn slots_become_allocas_in_an_entry_block() {
+    let ll = llvm::emit(&loop_module());
+    assert!(ll.contains("define i32 @sum(i32 %v0) {\nentry:\n  %ss0 = alloca i32\n  %ss1 = alloca i32\n  br label %bb0\nbb0:\n"), "{ll}");
+    assert!(ll.contains("store i32 0, ptr %ss0"), "{ll}");
+    assert!(ll.contains("icmp sgt i32 %v"), "{ll}");
+    assert!(ll.contains("%v1 = call i32 @sum(i32 10)"), "{ll}");
+    assert!(!ll.contains("declare"), "{ll}");
+}
+
+#[test]
+fn loops_and_calls_run_under_lli() {
+    if let Some(status) = run(&llvm::emit(&loop_module())) {
+        assert_eq!(status, 5);
+    }
+}
+
+#[test]
+fn switches_and_narrow_casts() {
+    let mut f = FunctionBuilder::new("main", Some(IrType::I32));
+    let value = f.iconst(IrType::I32, 300);
+    let byte = f.cast(CastOp::Trunc, value, IrType::I8);
+    let wide = f.cast(CastOp::ZExt, byte, IrType::I32);
+    let (forty_four, other) = (f.create_block(), f.create_block());
+    f.switch(wide, vec![(44, forty_four)], other);
+    f.switch_to(forty_four);
+    let seven = f.iconst(IrType::I32, 7);
+    f.ret(Some(seven));
+    f.switch_to(other);
+    let one = f.iconst(IrType::I32, 1);
+    f.ret(Some(one));
+    let module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+    let ll = llvm::emit(&module);
+    assert!(ll.contains("%v1 = trunc i32 300 to i8"), "{ll}");
+    assert!(ll.contains("%v2 = zext i8 %v1 to i32"), "{ll}");
+    assert!(
+        ll.contains("switch i32 %v2, label %bb2 [ i32 44, label %bb1 ]"),
+        "{ll}"
+    );
+    if let Some(status) = run(&ll) {
+        assert_eq!(status, 7);
+    }
+}
+
+#[test]
+fn strings_globals_and_external_functions() {
+    let mut module = Module::default();
+    let hello = module.strings.intern(b"hi \"there\"\n");
+    module.globals.push(Global {
+        name: "count".to_string(),
+        ty: IrType::I32,
+        len: 1,
+        init: vec![Datum::Int(3)],
+        thread_local: false,
+    });
+    let mut f = FunctionBuilder::new("main", Some(IrType::I32));
+    let text = f.string_addr(hello);
+    f.call("puts", vec![text], Some(IrType::I32));
+    let count = f.global_addr("count");
+    let same = f.cmp(CmpOp::Eq, count, count);
+    f.ret(Some(same));
+    module.functions.push(f.finish());
+
+    let ll = llvm::emit(&module);
+    assert!(
+        ll.contains(
+            "@.str0 = private unnamed_addr constant [12 x i8] c\"hi \\22there\\22\\0A\\00\""
+        ),
+        "{ll}"
+    );
+    assert!(ll.contains("@count = global i32 3"), "{ll}");
+    assert!(ll.contains("call i32 @puts(ptr @.str0)"), "{ll}");
+    assert!(ll.contains("icmp eq ptr @count, @count"), "{ll}");
+    assert!(ll.contains("declare i32 @puts(ptr)"), "{ll}");
+}
+
+#[test]
+fn inline_asm_operands_use_llvm_syntax() {
+    let mut f = FunctionBuilder::new("main", Some(IrType::I32));
+    let x = f.iconst(IrType::I32, 4);
+    let y = f
+        .inline_asm("movl %1, %0 # 100%% $", vec![x], Some(IrType::I32))
+        .unwrap();
+    f.ret(Some(y));
+    let module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+    let ll = llvm::emit(&module);
+    assert!(
+        ll.contains("%v1 = call i32 asm sideeffect \"movl $1, $0 # 100% $$\", \"=r,r\"(i32 4)"),
+        "{ll}"
+    );
+}
+
+#[test]
+fn emit_llvm_writes_an_ll_file() {
+    let dir = std::env::temp_dir().join(format!("rcc-emit-llvm-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("main.c");
+    fs::write(&input, "int main() { return 6 * 7; }").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rcc"))
+        .current_dir(&dir)
+        .arg("--emit=llvm")
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let ll = fs::read_to_string(dir.join("main.ll")).unwrap();
+    assert!(ll.contains("define i32 @main()"), "{ll}");
+    assert!(!dir.join("main.s").exists());
+    if let Some(status) = run(&ll) {
+        assert_eq!(status, 42);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
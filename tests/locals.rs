@@ -0,0 +1,141 @@
+mod common;
+
+use rcc::codegen::Target;
+use rcc::driver::Options;
+use rcc::opt::OptLevel;
+
+use common::{compile, run_main};
+
+#[test]
+fn variables_hold_what_was_assigned() {
+    let cases = [
+        ("int x = 3; return x + 1;", 4),
+        ("int x; x = 5; return x * x;", 25),
+        ("int x = 1; int y = x + 1; x = y * 10; return x + y;", 22),
+        ("int a; int b; a = b = 7; return a - b + a;", 7),
+        ("int x = 2; return (x = x + 3) * x;", 25),
+        ("int x = 4; x = -x; return x;", -4),
+    ];
+    for (body, expected) in cases {
+        let source = format!("int main() {{ {body} }}");
+        for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+            assert_eq!(
+                run_main(&source, level),
+                Some(expected),
+                "{body} at {level:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn assignments_can_be_statements_on_their_own() {
+    let source = "int three() { return 3; } int main() { int x; three(); x = three(); return x; }";
+    assert_eq!(run_main(source, OptLevel::O0), Some(3));
+}
+
+#[test]
+fn names_must_be_declared_once_before_use() {
+    let cases = [
+        (
+            "int main() { return x; }",
+            "1:21: use of undeclared identifier `x`",
+        ),
+        (
+            "int main() { int x; int x; return 0; }",
+            "1:25: redefinition of `x`",
+        ),
+        (
+            "int main() { void v; return 0; }",
+            "1:19: variable `v` has incomplete type `void`",
+        ),
+        (
+            "int main() { int f; return f(); }",
+            "1:28: called object `f` is not a function",
+        ),
+        (
+            "int f() { return 1; } int main() { return f + 1; }",
+            "1:43: function `f` used as a value",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(compile(source, &Options::default()).unwrap_err(), expected);
+    }
+}
+
+#[test]
+fn only_variables_are_assignable() {
+    let err = compile(
+        "int main() { int x; 3 = x; return 0; }",
+        &Options::default(),
+    )
+    .unwrap_err();
+    assert_eq!(err, "1:21: expression is not assignable");
+    let err = compile(
+        "int main() { int x; x + 1 = 2; return x; }",
+        &Options::default(),
+    )
+    .unwrap_err();
+    assert_eq!(err, "1:21: expression is not assignable");
+    let err = compile(
+        "int f() { return 0; }\nint main() { f() = 1; return 0; }",
+        &Options::default(),
+    )
+    .unwrap_err();
+    assert_eq!(err, "2:14: expression is not assignable");
+}
+
+#[test]
+fn variables_live_in_aligned_frame_slots() {
+    let asm = compile(
+        "int main() { int a = 1; int b = 2; int c = 3; return a + b + c; }",
+        &Options {
+            target: Target::AARCH64_APPLE,
+            ..Options::default()
+        },
+    )
+    .unwrap()
+    .assembly;
+    // The variables sit below the five spilled temporaries.
+    for (value, offset) in [(1, 24), (2, 28), (3, 32)] {
+        let store = format!("    mov w8, #{value}\n    str w8, [x29, #-{offset}]\n");
+        assert!(asm.contains(&store), "{asm}");
+    }
+    assert!(asm.contains("    ldr w8, [x29, #-24]\n"), "{asm}");
+    let frame = asm
+        .lines()
+        .find_map(|line| line.strip_prefix("    sub sp, sp, #"))
+        .expect("main reserves a frame");
+    assert_eq!(frame.parse::<u32>().unwrap() % 16, 0, "{asm}");
+}
+
+#[test]
+fn asm_outputs_store_into_variables() {
+    let source =
+        "int main() { int x; __asm__(\"mov %1, %0\" : \"=r\"(x) : \"r\"(6 * 7)); return x; }";
+    let options = Options {
+        target: Target::X86_64_LINUX,
+        ..Options::default()
+    };
+    let ir = compile(source, &options).unwrap().ir.to_string();
+    assert!(ir.contains("= asm i32 \"mov %1, %0\"(%"), "{ir}");
+    let err = compile(
+        "int main() { int x; int y; __asm__(\"\" : \"=r\"(x), \"=r\"(y)); return x; }",
+        &options,
+    )
+    .unwrap_err();
+    assert_eq!(err, "1:50: asm statement has more than one output operand");
+}
+
+#[test]
+fn thread_local_locals_need_static() {
+    let err = compile(
+        "int main() { _Thread_local int x; return 0; }",
+        &Options::default(),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        "1:14: `_Thread_local` local variables must also be `static`"
+    );
+}
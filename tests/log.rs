@@ -0,0 +1,28 @@
+use rcc::log::{Filter, Level};
+
+#[test]
+fn directives_set_levels_per_target() {
+    let filter = Filter::parse("warn, regalloc=trace,opt=debug").unwrap();
+    assert_eq!(filter.level("regalloc"), Some(Level::Trace));
+    assert_eq!(filter.level("opt"), Some(Level::Debug));
+    assert_eq!(filter.level("driver"), Some(Level::Warn));
+    assert_eq!(Filter::parse("").unwrap().level("opt"), None);
+    // A later directive for the same target wins.
+    let filter = Filter::parse("opt=trace,opt=info").unwrap();
+    assert_eq!(filter.level("opt"), Some(Level::Info));
+}
+
+#[test]
+fn unknown_levels_are_rejected() {
+    let err = Filter::parse("opt=loud").unwrap_err();
+    assert_eq!(err, "unknown log level `loud`");
+}
+
+#[test]
+fn each_v_shows_more_detail() {
+    assert_eq!(Level::from_verbosity(0), None);
+    assert_eq!(Level::from_verbosity(1), Some(Level::Info));
+    assert_eq!(Level::from_verbosity(2), Some(Level::Debug));
+    assert_eq!(Level::from_verbosity(5), Some(Level::Trace));
+    assert!(Level::Trace > Level::Debug && Level::Warn > Level::Error);
+}
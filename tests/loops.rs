@@ -0,0 +1,173 @@
+mod common;
+
+use rcc::codegen::Target;
+use rcc::driver::Options;
+use rcc::opt::OptLevel;
+
+use common::{assert_assembles, compile, run_main};
+
+const SUM: &str = "int main() {
+    int i = 0;
+    int sum = 0;
+    while (i < 10) {
+        sum = sum + i;
+        i = i + 1;
+    }
+    return sum;
+}";
+
+#[test]
+fn loops_run_until_their_condition_fails() {
+    let cases = [
+        (SUM, 45),
+        (
+            "int main() { int n = 1; do n = n * 3; while (n < 100); return n; }",
+            243,
+        ),
+        // A do-while body runs once even if the condition never holds.
+        (
+            "int main() { int n = 5; do { n = n + 1; } while (0); return n; }",
+            6,
+        ),
+        ("int main() { int n = 5; while (0) n = 0; return n; }", 5),
+        (
+            "int main() { int n = 0; while (1) { n = n + 7; while (n > 20) return n; } }",
+            21,
+        ),
+        (
+            "int main() { int i = 0; int j; int c = 0; \
+             while (i < 4) { j = 0; while (j < i) { c = c + 1; j = j + 1; } i = i + 1; } \
+             return c; }",
+            6,
+        ),
+    ];
+    for (source, expected) in cases {
+        for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+            assert_eq!(
+                run_main(source, level),
+                Some(expected),
+                "{source} at {level:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn for_loops_accept_any_combination_of_clauses() {
+    let clauses = [
+        ("int i = 0", "i < 5", "i = i + 1"),
+        ("i = 0", "i < 5", "i = i + 1"),
+        ("", "i < 5", "i = i + 1"),
+        ("int i = 0", "", "i = i + 1"),
+        ("int i = 0", "i < 5", ""),
+        ("", "", "i = i + 1"),
+        ("", "i < 5", ""),
+        ("", "", ""),
+    ];
+    for (init, cond, step) in clauses {
+        // The body does whatever the clauses leave out.
+        let mut body = String::from("{ ");
+        if cond.is_empty() {
+            body += "while (i >= 5) return n; ";
+        }
+        body += "n = n + i; ";
+        if step.is_empty() {
+            body += "i = i + 1; ";
+        }
+        body += "}";
+        let source = format!(
+            "int main() {{ int n = 0; int i = 0; for ({init}; {cond}; {step}) {body} return n; }}"
+        );
+        for level in [OptLevel::O0, OptLevel::O2] {
+            assert_eq!(run_main(&source, level), Some(10), "{source} at {level:?}");
+        }
+    }
+}
+
+#[test]
+fn for_declarations_are_scoped_to_the_loop() {
+    let source = "int main() {
+        int i = 100;
+        int n = 0;
+        for (int i = 0; i < 3; i = i + 1) n = n + i;
+        for (int i = 10; i < 12; i = i + 1) n = n + i;
+        return n + i;
+    }";
+    assert_eq!(run_main(source, OptLevel::O0), Some(124));
+    let err = compile(
+        "int main() { for (int i = 0; i < 3; i = i + 1) {} return i; }",
+        &Options::default(),
+    )
+    .unwrap_err();
+    assert_eq!(err, "1:58: use of undeclared identifier `i`");
+}
+
+#[test]
+fn for_declarations_need_c99() {
+    let options = Options {
+        standard: rcc::standard::Standard::parse("gnu89").unwrap(),
+        ..Options::default()
+    };
+    let err = compile("int main() { for (int i = 0; ; ) return i; }", &options).unwrap_err();
+    assert_eq!(
+        err,
+        "1:19: a declaration in a `for` loop is only valid in C99 and later"
+    );
+    compile("int main() { int i; for (i = 0; ; ) return i; }", &options).unwrap();
+}
+
+#[test]
+fn while_tests_at_the_top_and_do_while_at_the_bottom() {
+    let ir = compile(SUM, &Options::default()).unwrap().ir.to_string();
+    assert!(ir.contains("    jump bb1\nbb1:\n"), "{ir}");
+    assert!(ir.contains("    br %4, bb2, bb3\nbb2:\n"), "{ir}");
+    assert!(
+        ir.contains("    store ss0, %10\n    jump bb1\nbb3:\n"),
+        "{ir}"
+    );
+    let ir = compile(
+        "int main() { int n = 1; do n = n + n; while (n < 64); return n; }",
+        &Options::default(),
+    )
+    .unwrap()
+    .ir
+    .to_string();
+    assert!(ir.contains("    jump bb2\nbb2:\n"), "{ir}");
+    assert!(ir.contains("    br %6, bb1, bb3\n"), "{ir}");
+}
+
+#[test]
+fn conditions_must_be_scalars() {
+    let err = compile(
+        "void f(void) { return; } int main() { while (f()) {} return 0; }",
+        &Options::default(),
+    )
+    .unwrap_err();
+    assert_eq!(err, "1:46: expected `int` but found `void`");
+    let err = compile(
+        "int main() { do {} while (1) return 0; }",
+        &Options::default(),
+    )
+    .unwrap_err();
+    assert_eq!(err, "1:30: expected `;`, found `return`");
+}
+
+#[test]
+fn loops_branch_backwards_on_both_targets() {
+    for (target, triple, branch) in [
+        (Target::AARCH64_APPLE, "arm64-apple-macos", "    b "),
+        (Target::X86_64_LINUX, "x86_64-linux-gnu", "    jmp "),
+    ] {
+        let asm = compile(
+            SUM,
+            &Options {
+                target,
+                ..Options::default()
+            },
+        )
+        .unwrap()
+        .assembly;
+        assert!(asm.contains(branch), "{asm}");
+        assert_assembles(triple, &asm);
+    }
+}
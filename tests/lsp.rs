@@ -0,0 +1,211 @@
+use std::io::{BufReader, Write};
+use std::process::{Command, Stdio};
+
+use rcc::json::Value;
+use rcc::lsp::{self, Server};
+
+const URI: &str = "file:///t.c";
+
+fn request(id: u32, method: &str, params: Value) -> Value {
+    Value::object([
+        ("jsonrpc", Value::from("2.0")),
+        ("id", Value::from(id)),
+        ("method", Value::from(method)),
+        ("params", params),
+    ])
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    Value::object([
+        ("jsonrpc", Value::from("2.0")),
+        ("method", Value::from(method)),
+        ("params", params),
+    ])
+}
+
+fn open(server: &mut Server, text: &str) -> Vec<Value> {
+    server.handle(&notification(
+        "textDocument/didOpen",
+        Value::object([(
+            "textDocument",
+            Value::object([
+                ("uri", Value::from(URI)),
+                ("languageId", Value::from("c")),
+                ("version", Value::from(1)),
+                ("text", Value::from(text)),
+            ]),
+        )]),
+    ))
+}
+
+/// The messages of the diagnostics published in `replies`.
+fn diagnostics(replies: &[Value]) -> Vec<(u64, String)> {
+    let [reply] = replies else {
+        panic!("expected one notification, got {replies:?}");
+    };
+    assert_eq!(
+        reply.get("method").and_then(Value::as_str),
+        Some("textDocument/publishDiagnostics")
+    );
+    let params = reply.get("params").unwrap();
+    assert_eq!(params.get("uri").and_then(Value::as_str), Some(URI));
+    params
+        .get("diagnostics")
+        .and_then(Value::as_array)
+        .unwrap()
+        .iter()
+        .map(|d| {
+            let line = d.get("range").unwrap().get("start").unwrap().get("line");
+            (
+                line.and_then(Value::as_u64).unwrap(),
+                d.get("message")
+                    .and_then(Value::as_str)
+                    .unwrap()
+                    .to_string(),
+            )
+        })
+        .collect()
+}
+
+fn text_document() -> Value {
+    Value::object([("textDocument", Value::object([("uri", Value::from(URI))]))])
+}
+
+#[test]
+fn diagnostics_recover_to_report_every_broken_function() {
+    let mut server = Server::new();
+    let replies = open(
+        &mut server,
+        "int a() { return 1 + ; }\nint b() { return 2; }\nint c() { return }\n",
+    );
+    let found = diagnostics(&replies);
+    assert_eq!(found.len(), 2, "{found:?}");
+    assert_eq!(found[0].0, 0);
+    assert_eq!(found[1].0, 2);
+
+    let replies = server.handle(&notification(
+        "textDocument/didChange",
+        Value::object([
+            ("textDocument", Value::object([("uri", Value::from(URI))])),
+            (
+                "contentChanges",
+                Value::Array(vec![Value::object([(
+                    "text",
+                    Value::from("int a() { return c(); }\nint b() { return; }\n"),
+                )])]),
+            ),
+        ]),
+    ));
+    let found = diagnostics(&replies);
+    assert_eq!(found.len(), 2, "{found:?}");
+    assert!(found[0].1.contains("undeclared function `c`"), "{found:?}");
+    assert!(found[1].1.contains("should return a value"), "{found:?}");
+
+    let replies = server.handle(&notification("textDocument/didClose", text_document()));
+    assert!(diagnostics(&replies).is_empty());
+}
+
+#[test]
+fn goes_to_a_function_definition() {
+    let mut server = Server::new();
+    open(
+        &mut server,
+        "int helper() { return 1; }\nint main() { return helper(); }\n",
+    );
+    let mut params = text_document();
+    let Value::Object(members) = &mut params else {
+        unreachable!()
+    };
+    members.push((
+        "position".into(),
+        Value::object([("line", Value::from(1)), ("character", Value::from(22))]),
+    ));
+    let replies = server.handle(&request(7, "textDocument/definition", params));
+    let result = replies[0].get("result").unwrap();
+    assert_eq!(result.get("uri").and_then(Value::as_str), Some(URI));
+    assert_eq!(
+        result.get("range").unwrap().to_string(),
+        r#"{"start":{"line":0,"character":4},"end":{"line":0,"character":10}}"#
+    );
+}
+
+#[test]
+fn lists_functions_as_document_symbols() {
+    let mut server = Server::new();
+    open(&mut server, "int a() { return 1; }\nvoid b() { return; }\n");
+    let replies = server.handle(&request(2, "textDocument/documentSymbol", text_document()));
+    let symbols = replies[0].get("result").and_then(Value::as_array).unwrap();
+    let names: Vec<_> = symbols
+        .iter()
+        .map(|s| s.get("name").and_then(Value::as_str).unwrap())
+        .collect();
+    assert_eq!(names, ["a", "b"]);
+    assert_eq!(
+        symbols[1].get("detail").and_then(Value::as_str),
+        Some("void ()")
+    );
+}
+
+#[test]
+fn unknown_requests_are_errors_and_unknown_notifications_are_ignored() {
+    let mut server = Server::new();
+    let replies = server.handle(&request(3, "workspace/frobnicate", Value::Null));
+    let code = replies[0].get("error").unwrap().get("code").unwrap();
+    assert_eq!(*code, Value::Number(-32601.0));
+    assert!(server
+        .handle(&notification("$/cancelRequest", Value::Null))
+        .is_empty());
+}
+
+#[test]
+fn serves_a_session_over_stdio() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rcc"))
+        .arg("lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    for message in [
+        request(
+            1,
+            "initialize",
+            Value::object([("capabilities", Value::object::<&str>([]))]),
+        ),
+        notification("initialized", Value::object::<&str>([])),
+        notification(
+            "textDocument/didOpen",
+            Value::object([(
+                "textDocument",
+                Value::object([
+                    ("uri", Value::from(URI)),
+                    ("text", Value::from("int main() { return; }")),
+                ]),
+            )]),
+        ),
+        request(2, "shutdown", Value::Null),
+        notification("exit", Value::Null),
+    ] {
+        lsp::write_message(&mut stdin, &message).unwrap();
+    }
+    stdin.flush().unwrap();
+    drop(stdin);
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let initialized = lsp::read_message(&mut stdout).unwrap().unwrap();
+    let capabilities = initialized
+        .get("result")
+        .unwrap()
+        .get("capabilities")
+        .unwrap();
+    assert_eq!(
+        capabilities.get("definitionProvider"),
+        Some(&Value::Bool(true))
+    );
+    let published = lsp::read_message(&mut stdout).unwrap().unwrap();
+    assert_eq!(diagnostics(&[published]).len(), 1);
+    let shutdown = lsp::read_message(&mut stdout).unwrap().unwrap();
+    assert_eq!(shutdown.get("result"), Some(&Value::Null));
+    assert!(lsp::read_message(&mut stdout).unwrap().is_none());
+    assert!(child.wait().unwrap().success());
+}
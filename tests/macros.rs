@@ -0,0 +1,178 @@
+mod common;
+
+use std::path::Path;
+
+use rcc::driver::{self, Options};
+use rcc::ir::interp::Interpreter;
+use rcc::opt::OptLevel;
+use rcc::preprocess::Preprocessor;
+
+use common::check_fixture;
+
+/// Preprocesses and compiles `source`, returning what `main` returns or
+/// the first error as the driver reports it.
+fn run_main(
+    preprocessor: &Preprocessor,
+    source: &str,
+    opt_level: OptLevel,
+) -> Result<Option<i64>, String> {
+    let preprocessed = preprocessor
+        .run(Path::new("main.c"), "main.c", source)
+        .map_err(|err| err.error.render(&err.file))?;
+    let options = Options {
+        opt_level,
+        ..Options::default()
+    };
+    let ir = driver::compile(&preprocessed.source, &options)
+        .map_err(|err| {
+            let err = preprocessed.locate(err);
+            err.error.render(&err.file)
+        })?
+        .ir;
+    Ok(Interpreter::new(&ir).call("main", &[]).unwrap())
+}
+
+/// Object-like and function-like macros, a call spread over lines, `#`
+/// and `##`, variadic and self-referential macros, redefinition and
+/// comments.
+#[test]
+fn macros_are_expanded() {
+    check_fixture("macros");
+    check_fixture("macro_expansion");
+}
+
+/// Picks a branch by the macros given on the command line.
+const CONDITIONAL: &str = "#ifdef X
+int main() { return 1; }
+#elif Y > 2 && defined(Z)
+int main() { return 2; }
+#elif defined Y || 'a' == 97
+#  if (Y + 0) * 4 >> 1 == 0x4 && !defined X
+int main() { return 3; }
+#  else
+int main() { return 4; }
+#  endif
+#else
+int main() { return 5; }
+#endif
+";
+
+#[test]
+fn conditionals_pick_a_branch() {
+    let cases: [(&[&str], i64); 5] = [
+        (&["X"], 1),
+        (&["Y=3", "Z"], 2),
+        (&["Y=2"], 3),
+        (&["Y"], 4),
+        (&[], 4),
+    ];
+    for (defines, expected) in cases {
+        let mut preprocessor = Preprocessor::new();
+        for define in defines {
+            preprocessor.define(define).unwrap();
+        }
+        assert_eq!(
+            run_main(&preprocessor, CONDITIONAL, OptLevel::O0),
+            Ok(Some(expected)),
+            "{defines:?}"
+        );
+    }
+    let mut preprocessor = Preprocessor::new();
+    preprocessor.define("X").unwrap();
+    preprocessor.undefine("X");
+    preprocessor.define("SUM(a,b)=a+b").unwrap();
+    let source = "#ifndef X\nint main() { return SUM(2, 3); }\n#endif\n";
+    assert_eq!(run_main(&preprocessor, source, OptLevel::O0), Ok(Some(5)));
+    assert_eq!(
+        Preprocessor::new().define("1X"),
+        Err("expected a macro name after `#define`".into())
+    );
+}
+
+#[test]
+fn positions_survive_directives_and_skipped_lines() {
+    let source = "#define A 1
+#if 0
+#error skipped
+#unknown
+#if 1
+#endif
+#endif
+#define F(x) x
+int main() { return F(A +
+    1) +
+    z; }";
+    assert_eq!(
+        run_main(&Preprocessor::new(), source, OptLevel::O0),
+        Err("main.c:11:5: error: use of undeclared identifier `z`".into())
+    );
+}
+
+#[test]
+fn preprocessing_errors_are_reported() {
+    let cases = [
+        ("#if 1\nint x;\n", "main.c:1:1: error: unterminated `#if`"),
+        ("#ifdef A\n", "main.c:1:1: error: unterminated `#ifdef`"),
+        ("#endif\n", "main.c:1:1: error: `#endif` without `#if`"),
+        (
+            "#if 1\n#else\n#else\n#endif\n",
+            "main.c:3:1: error: `#else` after `#else`",
+        ),
+        (
+            "#if 0\n#else\n#elif 1\n#endif\n",
+            "main.c:3:1: error: `#elif` after `#else`",
+        ),
+        (
+            "#if 1\n#endif junk\n",
+            "main.c:2:8: error: extra tokens after `#endif`",
+        ),
+        (
+            "#ifdef 1\n#endif\n",
+            "main.c:1:8: error: expected a macro name after `#ifdef`",
+        ),
+        (
+            "  #pragmatic\n",
+            "main.c:1:3: error: unknown preprocessing directive `#pragmatic`",
+        ),
+        ("#error stop here\n", "main.c:1:1: error: #error stop here"),
+        (
+            "#if 1 / 0\n#endif\n",
+            "main.c:1:5: error: division by zero in `#if`",
+        ),
+        (
+            "#if\n#endif\n",
+            "main.c:1:4: error: missing expression in `#if`",
+        ),
+        (
+            "#if (1\n#endif\n",
+            "main.c:1:5: error: expected `)` in `#if`",
+        ),
+        (
+            "#if 1 2\n#endif\n",
+            "main.c:1:5: error: unexpected `2` in `#if`",
+        ),
+        (
+            "#define\n",
+            "main.c:1:8: error: expected a macro name after `#define`",
+        ),
+        (
+            "#define F(a, a) a\n",
+            "main.c:1:9: error: duplicate macro parameter `a`",
+        ),
+        (
+            "#define F(a) a\nint x = F(1, 2);\n",
+            "main.c:2:9: error: macro `F` takes 1 argument, but 2 were given",
+        ),
+        (
+            "#define F(a) a\nint x = F(1;\n",
+            "main.c:2:9: error: unterminated call to macro `F`",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(
+            run_main(&Preprocessor::new(), source, OptLevel::O0),
+            Err(expected.into()),
+            "{source}"
+        );
+    }
+}
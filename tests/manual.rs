@@ -0,0 +1,109 @@
+use std::fs;
+use std::process::Command;
+
+use rcc::manual::{self, FLAGS};
+
+#[test]
+fn every_documented_flag_is_accepted() {
+    // Flags such as `--stdin` compile what they read, so rcc runs where its
+    // outputs can't land in the repository.
+    let dir = std::env::temp_dir().join(format!("rcc-manual-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for flag in FLAGS {
+        for name in flag.names {
+            // Watching never returns.
+            if *name == "--watch" {
+                continue;
+            }
+            let value = match flag.value {
+                None => None,
+                Some("file") => Some("-".to_string()),
+                Some("jobs") => Some("1".to_string()),
+                Some(_) => (flag.choices)().first().cloned(),
+            };
+            let args = match value {
+                Some(value) if name.ends_with('=') => vec![format!("{name}{value}")],
+                Some(value) => vec![name.to_string(), value],
+                None => vec![name.to_string()],
+            };
+            let output = Command::new(env!("CARGO_BIN_EXE_rcc"))
+                .args(&args)
+                .current_dir(&dir)
+                .arg("/nonexistent/t.c")
+                .output()
+                .unwrap();
+            let stderr = String::from_utf8(output.stderr).unwrap();
+            assert!(
+                !stderr.contains("unknown option") && !stderr.contains("needs a value"),
+                "{args:?}: {stderr}"
+            );
+        }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn bash_completions_parse_and_offer_every_flag() {
+    let script = manual::completions("bash").unwrap();
+    assert!(script.contains(" --emit=llvm "), "{script}");
+    assert!(script.contains(" -fno-omit-frame-pointer "), "{script}");
+    assert!(script.ends_with("complete -o filenames -o bashdefault -F _rcc rcc\n"));
+    let Ok(output) = Command::new("bash").args(["-n", "-c", &script]).output() else {
+        return;
+    };
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn zsh_and_fish_completions_describe_flags() {
+    let zsh = manual::completions("zsh").unwrap();
+    assert!(zsh.starts_with("#compdef rcc\n"), "{zsh}");
+    assert!(
+        zsh.contains("  '--target+[Generate code for this target.]:triple:(aarch64-apple-darwin "),
+        "{zsh}"
+    );
+    // Quotes in descriptions are closed and reopened around an escaped one.
+    assert!(
+        zsh.contains("[Log the compiler'\\''s decisions; repeat for more.]"),
+        "{zsh}"
+    );
+
+    let fish = manual::completions("fish").unwrap();
+    assert!(
+        fish.contains("complete -c rcc -l emit -r -f -a 'tokens tokens-json ast ast-json ast-dot c tags header ir llvm wat asm asm-map report obj exe all' -d "),
+        "{fish}"
+    );
+    assert!(fish.contains("complete -c rcc -o fPIC -d "), "{fish}");
+    assert_eq!(manual::completions("tcsh"), None);
+}
+
+#[test]
+fn man_page_documents_flags_commands_and_exit_statuses() {
+    let page = manual::man_page();
+    assert!(page.starts_with(".TH RCC 1 "), "{page}");
+    assert!(
+        page.contains("\n.TP\n\\fB\\-S\\fR\nWrite assembly.\n"),
+        "{page}"
+    );
+    assert!(
+        page.contains("\\fB\\-\\-target\\fR \\fItriple\\fR\n"),
+        "{page}"
+    );
+    assert!(
+        page.contains("\\fB\\-\\-emit=\\fR\\fIkinds\\fR\n"),
+        "{page}"
+    );
+    assert!(
+        page.contains("\n.TP\n\\fBrcc completions\\fR <shell>\n"),
+        "{page}"
+    );
+    assert!(page.contains("\n.SH EXIT STATUS\n"), "{page}");
+    // No line may start with a character roff treats as a request.
+    for line in page.lines() {
+        assert!(!line.starts_with('\''), "{line}");
+    }
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use rcc::codegen::aarch64::Aarch64;
+use rcc::codegen::x86_64::X86_64;
+use rcc::codegen::{Backend, CodeGenerator};
+use rcc::ir::interp::Interpreter;
+use rcc::ir::verify::verify_module;
+use rcc::ir::{BinOp, CastOp, CmpOp, FunctionBuilder, IrType, Module};
+use rcc::opt::{self, OptLevel};
+
+use common::{assert_assembles, run};
+
+/// `main` stores `0xf0` to an `i8` and `0xfffe` to an `i16` slot, reloads
+/// them and combines signed and unsigned views of both:
+/// `-16 + 240 + 65534 / 1000 + (0xfffffffe >u 5) - (-2 <u 5) = 290`, which
+/// exits as 34.
+fn narrow() -> Module {
+    let mut b = FunctionBuilder::new("main", Some(IrType::I32));
+    let byte = b.stack_slot(IrType::I8);
+    let half = b.stack_slot(IrType::I16);
+    let value = b.iconst(IrType::I8, -16);
+    b.store(byte, value);
+    let value = b.iconst(IrType::I16, -2);
+    b.store(half, value);
+
+    let loaded = b.load(byte);
+    let signed = b.cast(CastOp::SExt, loaded, IrType::I32);
+    let unsigned = b.cast(CastOp::ZExt, loaded, IrType::I32);
+    let mut sum = b.binary(BinOp::Add, signed, unsigned);
+
+    let loaded = b.load(half);
+    let wide = b.cast(CastOp::ZExt, loaded, IrType::I32);
+    let thousand = b.iconst(IrType::I32, 1000);
+    let quotient = b.binary(BinOp::UDiv, wide, thousand);
+    sum = b.binary(BinOp::Add, sum, quotient);
+    let signed = b.cast(CastOp::SExt, loaded, IrType::I32);
+    let five = b.iconst(IrType::I32, 5);
+    let above = b.cmp(CmpOp::Ugt, signed, five);
+    sum = b.binary(BinOp::Add, sum, above);
+    let below = b.cmp(CmpOp::Ult, signed, five);
+    sum = b.binary(BinOp::Sub, sum, below);
+    b.ret(Some(sum));
+
+    let mut module = Module::default();
+    module.functions.push(b.finish());
+    module
+}
+
+fn generate(backend: &dyn Backend, allocate_registers: bool) -> String {
+    CodeGenerator::new()
+        .with_backend(backend)
+        .with_register_allocation(allocate_registers)
+        .generate(&narrow())
+}
+
+#[test]
+fn prints_casts_and_narrow_types() {
+    let module = narrow();
+    verify_module(&module).unwrap();
+    let text = module.to_string();
+    assert!(text.contains("ss0 = stack_slot i8\n"), "{text}");
+    assert!(text.contains("%3 = sext i8 %2 to i32\n"), "{text}");
+    assert!(text.contains("%4 = zext i8 %2 to i32\n"), "{text}");
+}
+
+#[test]
+fn rejects_arithmetic_on_narrow_values() {
+    let mut b = FunctionBuilder::new("f", Some(IrType::I8));
+    let one = b.iconst(IrType::I8, 1);
+    let two = b.binary(BinOp::Add, one, one);
+    b.ret(Some(two));
+    let mut module = Module::default();
+    module.functions.push(b.finish());
+    let error = verify_module(&module).unwrap_err();
+    assert_eq!(error.message, "%1 = add operates on narrow i8");
+}
+
+#[test]
+fn rejects_casts_in_the_wrong_direction() {
+    let mut b = FunctionBuilder::new("f", Some(IrType::I32));
+    let one = b.iconst(IrType::I32, 1);
+    let same = b.cast(CastOp::SExt, one, IrType::I32);
+    b.ret(Some(same));
+    let mut module = Module::default();
+    module.functions.push(b.finish());
+    let error = verify_module(&module).unwrap_err();
+    assert_eq!(error.message, "%1 = sext cannot convert i32 to i32");
+}
+
+#[test]
+fn casts_fold_and_interpret() {
+    let mut b = FunctionBuilder::new("f", Some(IrType::Ptr));
+    let value = b.iconst(IrType::I32, 300);
+    let byte = b.cast(CastOp::Trunc, value, IrType::I8);
+    let widened = b.cast(CastOp::ZExt, byte, IrType::Ptr);
+    b.ret(Some(widened));
+    let mut module = Module::default();
+    module.functions.push(b.finish());
+    assert_eq!(Interpreter::new(&module).call("f", &[]), Ok(Some(44)));
+    opt::optimize(&mut module, OptLevel::O1);
+    let text = module.to_string();
+    assert!(text.contains("const ptr 44\n"), "{text}");
+
+    let mut module = narrow();
+    assert_eq!(Interpreter::new(&module).call("main", &[]), Ok(Some(290)));
+    opt::optimize(&mut module, OptLevel::O1);
+    assert_eq!(Interpreter::new(&module).call("main", &[]), Ok(Some(290)));
+}
+
+#[test]
+fn aarch64_extends_narrow_loads_explicitly() {
+    for allocate_registers in [false, true] {
+        let asm = generate(&Aarch64::LINUX, allocate_registers);
+        assert!(asm.contains("strb w8, [x29, #-"), "{asm}");
+        assert!(asm.contains("strh w8, [x29, #-"), "{asm}");
+        assert!(asm.contains("ldrb w"), "{asm}");
+        assert!(asm.contains("ldrh w"), "{asm}");
+        assert!(asm.contains("sxtb w"), "{asm}");
+        assert!(asm.contains("uxtb w"), "{asm}");
+        assert!(asm.contains("udiv w"), "{asm}");
+        assert!(asm.contains(", hi\n"), "{asm}");
+        assert!(asm.contains(", lo\n"), "{asm}");
+        assert_assembles("aarch64-linux-gnu", &asm);
+    }
+}
+
+#[test]
+fn x86_narrow_values_run_correctly() {
+    for allocate_registers in [false, true] {
+        let asm = generate(&X86_64::LINUX, allocate_registers);
+        assert!(asm.contains("movb %al, -"), "{asm}");
+        assert!(asm.contains("movw %ax, -"), "{asm}");
+        assert!(asm.contains("movsbl %"), "{asm}");
+        assert!(asm.contains("movzbl -"), "{asm}");
+        assert!(asm.contains("seta %al"), "{asm}");
+        assert!(asm.contains("setb %al"), "{asm}");
+        assert_eq!(run("narrow", &asm), Some(290 % 256), "{asm}");
+    }
+}
@@ -0,0 +1,139 @@
+use rcc::driver::{self, Options};
+use rcc::ir::{BinOp, FunctionBuilder, Instr, IrType, Module, Value};
+use rcc::opt::{self, OptLevel};
+
+fn assembly(source: &str, opt_level: OptLevel) -> String {
+    driver::compile(
+        source,
+        &Options {
+            opt_level,
+            ..Options::default()
+        },
+    )
+    .expect("program should compile")
+    .assembly
+}
+
+/// Counts instructions `mnemonic w.., w.., #amount`, whatever registers they use.
+fn count_immediate_shifts(asm: &str, mnemonic: &str, amount: u32) -> usize {
+    asm.lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with(&format!("{mnemonic} w")))
+        .filter(|line| line.ends_with(&format!(", #{amount}")))
+        .count()
+}
+
+#[test]
+fn folds_constant_return_at_o1() {
+    let asm = assembly("int main() { return 2 * 3 + 4; }", OptLevel::O1);
+    assert!(asm.contains("mov w0, #10"), "{asm}");
+    assert!(!asm.contains("mul"), "{asm}");
+    assert!(!asm.contains("add w"), "{asm}");
+}
+
+#[test]
+fn folds_nested_parenthesized_expression() {
+    let asm = assembly(
+        "int main() { return (20 - 2) / (1 + 2) * 7; }",
+        OptLevel::O1,
+    );
+    assert!(asm.contains("mov w0, #42"), "{asm}");
+    assert!(!asm.contains("sdiv"), "{asm}");
+}
+
+#[test]
+fn o0_keeps_arithmetic() {
+    let asm = assembly("int main() { return 2 * 3 + 4; }", OptLevel::O0);
+    assert!(asm.contains("mul w8, w8, w9"), "{asm}");
+    assert!(asm.contains("add w8, w8, w9"), "{asm}");
+}
+
+#[test]
+fn does_not_fold_division_by_zero() {
+    let asm = assembly("int main() { return 1 / 0; }", OptLevel::O1);
+    assert!(asm.contains("sdiv"), "{asm}");
+}
+
+#[test]
+fn folds_around_calls() {
+    let source = "int seven() { return 3 + 4; } int main() { return seven() + 2 * 5; }";
+    let asm = assembly(source, OptLevel::O1);
+    assert!(asm.contains("mov w0, #7"), "{asm}");
+    assert!(asm.contains("bl _seven"), "{asm}");
+    assert!(asm.contains("mov w9, #10"), "{asm}");
+}
+
+#[test]
+fn multiplies_by_power_of_two_with_shift() {
+    let source = "int f() { return 5; } int main() { return f() * 8 + 8 * f(); }";
+    let asm = assembly(source, OptLevel::O1);
+    assert_eq!(count_immediate_shifts(&asm, "lsl", 3), 2, "{asm}");
+    assert!(!asm.contains("mul"), "{asm}");
+}
+
+#[test]
+fn signed_division_by_power_of_two_rounds_toward_zero() {
+    let asm = assembly(
+        "int f() { return 5; } int main() { return f() / 4; }",
+        OptLevel::O1,
+    );
+    assert!(!asm.contains("sdiv"), "{asm}");
+    assert_eq!(count_immediate_shifts(&asm, "asr", 31), 1, "{asm}");
+    assert_eq!(count_immediate_shifts(&asm, "lsr", 30), 1, "{asm}");
+    assert_eq!(count_immediate_shifts(&asm, "asr", 2), 1, "{asm}");
+}
+
+#[test]
+fn division_by_non_power_of_two_is_kept() {
+    let asm = assembly(
+        "int f() { return 5; } int main() { return f() / 6; }",
+        OptLevel::O1,
+    );
+    assert!(asm.contains("sdiv"), "{asm}");
+}
+
+fn reduce_binary(op: BinOp, rhs: i64) -> Vec<Instr> {
+    let mut builder = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = builder.param(IrType::I32);
+    let rhs = builder.iconst(IrType::I32, rhs);
+    let result = builder.binary(op, x, rhs);
+    builder.ret(Some(result));
+    let mut module = Module {
+        functions: vec![builder.finish()],
+        ..Module::default()
+    };
+    opt::optimize(&mut module, OptLevel::O1);
+    module.functions.remove(0).blocks.remove(0).instrs
+}
+
+fn ops(instrs: &[Instr]) -> Vec<BinOp> {
+    instrs
+        .iter()
+        .filter_map(|instr| match instr {
+            Instr::Binary { op, .. } => Some(*op),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn unsigned_division_and_remainder_use_shift_and_mask() {
+    assert_eq!(ops(&reduce_binary(BinOp::UDiv, 4)), [BinOp::LShr]);
+    assert_eq!(ops(&reduce_binary(BinOp::URem, 16)), [BinOp::And]);
+    assert!(reduce_binary(BinOp::URem, 16).contains(&Instr::Const {
+        dst: Value(3),
+        value: 15
+    }));
+}
+
+#[test]
+fn signed_remainder_by_power_of_two_avoids_division() {
+    let reduced = ops(&reduce_binary(BinOp::SRem, 16));
+    assert!(!reduced.contains(&BinOp::SRem));
+    assert_eq!(reduced.last(), Some(&BinOp::Sub));
+}
+
+#[test]
+fn signed_division_by_negative_power_of_two_is_kept() {
+    assert_eq!(ops(&reduce_binary(BinOp::SDiv, -4)), [BinOp::SDiv]);
+}
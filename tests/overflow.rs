@@ -0,0 +1,76 @@
+mod common;
+
+use rcc::codegen::Target;
+use rcc::driver::{self, Options};
+
+use common::run_linked;
+
+fn assembly(source: &str, target: Target) -> String {
+    driver::compile(
+        source,
+        &Options {
+            target,
+            sanitize_overflow: true,
+            ..Options::default()
+        },
+    )
+    .expect("program should compile")
+    .assembly
+}
+
+const OVERFLOWING: &str =
+    "int big() { return 2147483647; }\nint main() { return big() + big(); }\n";
+
+#[test]
+fn aarch64_sets_flags_and_branches_to_a_trap() {
+    let asm = assembly(OVERFLOWING, Target::AARCH64_APPLE);
+    assert!(asm.contains("    adds w"), "{asm}");
+    assert!(asm.contains("    b.vs Lmain_"), "{asm}");
+    assert!(asm.contains("    brk #1"), "{asm}");
+}
+
+#[test]
+fn aarch64_checks_the_full_product() {
+    let asm = assembly(
+        "int f() { return 3; }\nint main() { return f() * f(); }\n",
+        Target::AARCH64_APPLE,
+    );
+    assert!(asm.contains("    smull x16, w"), "{asm}");
+    assert!(asm.contains("    cmp x16, x17\n    b.ne "), "{asm}");
+}
+
+#[test]
+fn unchecked_code_has_no_trap() {
+    let asm = driver::compile(
+        OVERFLOWING,
+        &Options {
+            target: Target::AARCH64_APPLE,
+            ..Options::default()
+        },
+    )
+    .unwrap()
+    .assembly;
+    assert!(!asm.contains("adds"), "{asm}");
+    assert!(!asm.contains("brk"), "{asm}");
+}
+
+#[test]
+fn x86_traps_on_overflow() {
+    let asm = assembly(OVERFLOWING, Target::X86_64_LINUX);
+    assert!(asm.contains("    jo "), "{asm}");
+    assert!(asm.contains("    ud2"), "{asm}");
+    if let Some(code) = run_linked("trap", &asm, &[]) {
+        assert_eq!(code, None, "the overflow should kill the program");
+    }
+}
+
+#[test]
+fn x86_runs_normally_without_overflow() {
+    let asm = assembly(
+        "int f() { return 20; }\nint main() { return f() * 2 + 2 - f(); }\n",
+        Target::X86_64_LINUX,
+    );
+    if let Some(code) = run_linked("ok", &asm, &[]) {
+        assert_eq!(code, Some(22));
+    }
+}
@@ -0,0 +1,39 @@
+use std::thread;
+
+use rcc::codegen::Target;
+use rcc::driver::{self, Artifacts, Options};
+use rcc::error::Error;
+use rcc::opt::OptLevel;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn pipeline_types_cross_threads() {
+    assert_send_sync::<Options>();
+    assert_send_sync::<Artifacts>();
+    assert_send_sync::<Error>();
+}
+
+#[test]
+fn threads_compile_the_same_as_one() {
+    let sources: Vec<String> = (0..16)
+        .map(|i| format!("int f() {{ return {i}; }} int main() {{ return f() * {i} + 1; }}"))
+        .collect();
+    let options = Options {
+        opt_level: OptLevel::O2,
+        target: Target::AARCH64_LINUX,
+        ..Options::default()
+    };
+    let serial: Vec<String> = sources
+        .iter()
+        .map(|source| driver::compile(source, &options).unwrap().assembly)
+        .collect();
+    let parallel: Vec<String> = thread::scope(|scope| {
+        let handles: Vec<_> = sources
+            .iter()
+            .map(|source| scope.spawn(|| driver::compile(source, &options).unwrap().assembly))
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    assert_eq!(serial, parallel);
+}
@@ -0,0 +1,81 @@
+use rcc::analyzer::Analyzer;
+use rcc::ast::DataModel;
+use rcc::codegen::aarch64::Aarch64;
+use rcc::codegen::CodeGenerator;
+use rcc::driver::{self, Options};
+use rcc::ir::lower::lower_program;
+use rcc::lexer::Lexer;
+use rcc::opt::{AsmPass, IrPass, OptLevel, PassManager};
+use rcc::parser::Parser;
+
+const SOURCE: &str = "int f() { return 9; } int main() { return f() / 4 + 2 * 3; }";
+
+fn assembly(opt_level: OptLevel) -> String {
+    driver::compile(
+        SOURCE,
+        &Options {
+            opt_level,
+            ..Options::default()
+        },
+    )
+    .expect("program should compile")
+    .assembly
+}
+
+#[test]
+fn o0_runs_no_passes() {
+    assert_eq!(PassManager::for_level(OptLevel::O0), PassManager::default());
+
+    let tokens = Lexer::new(SOURCE).lex().unwrap();
+    let program = Parser::new(tokens).parse().unwrap();
+    Analyzer::new().analyze(&program).unwrap();
+    let straight = CodeGenerator::new().generate(&lower_program(&program, DataModel::Lp64));
+    assert_eq!(assembly(OptLevel::O0), straight);
+}
+
+#[test]
+fn pipelines_are_ordered_per_level() {
+    use IrPass::*;
+    let o1 = PassManager::for_level(OptLevel::O1);
+    assert_eq!(
+        o1.ir_passes,
+        [ConstFold, Simplify, BranchFold, StrengthReduce, Dce]
+    );
+    assert_eq!(o1.asm_passes, [AsmPass::Peephole]);
+    assert!(o1.allocate_registers);
+    assert!(o1.omit_frame_pointer);
+    assert!(!PassManager::for_level(OptLevel::O0).omit_frame_pointer);
+
+    let o2 = PassManager::for_level(OptLevel::O2);
+    assert_eq!(o2.ir_passes.first(), Some(&Inline));
+    assert!(o2.ir_passes.contains(&Licm));
+    assert_eq!(o2.ir_passes.last(), Some(&Dce));
+
+    let os = PassManager::for_level(OptLevel::Os);
+    assert!(!os.ir_passes.contains(&Inline));
+    assert!(!os.ir_passes.contains(&StrengthReduce));
+}
+
+#[test]
+fn os_keeps_compact_division() {
+    assert!(assembly(OptLevel::Os).contains("sdiv"));
+    assert!(!assembly(OptLevel::O1).contains("sdiv"));
+}
+
+#[test]
+fn custom_pipeline_runs_only_selected_passes() {
+    let tokens = Lexer::new(SOURCE).lex().unwrap();
+    let program = Parser::new(tokens).parse().unwrap();
+    let mut module = lower_program(&program, DataModel::Lp64);
+    let passes = PassManager {
+        ir_passes: vec![IrPass::ConstFold, IrPass::Dce],
+        asm_passes: vec![],
+        allocate_registers: false,
+        omit_frame_pointer: false,
+    };
+    passes.run_ir(&mut module);
+    let asm = CodeGenerator::new().generate_assembly(&module);
+    let asm = passes.run_asm(asm, &Aarch64::APPLE).to_string();
+    assert!(asm.contains("sdiv"), "{asm}");
+    assert!(asm.contains("mov w9, #6"), "{asm}");
+}
@@ -0,0 +1,144 @@
+use rcc::codegen::asm::{
+    imm, mem, reg, sym, Address, Assembly, Instruction, Line, Operand, Syntax,
+};
+use rcc::codegen::peephole;
+use rcc::driver::{self, Options};
+use rcc::opt::OptLevel;
+
+fn assembly(lines: Vec<Line>) -> Assembly {
+    Assembly {
+        lines,
+        ..Assembly::new(Syntax::Arm)
+    }
+}
+
+fn instr(mnemonic: &str, operands: Vec<Operand>) -> Line {
+    Line::Instruction(Instruction::new(mnemonic, operands))
+}
+
+fn label(name: &str) -> Line {
+    Line::Label(name.to_string())
+}
+
+#[test]
+fn removes_self_moves() {
+    let asm = assembly(vec![
+        instr("mov", vec![reg("w0"), reg("w0")]),
+        instr("mov", vec![reg("x1"), reg("x1")]),
+        instr("mov", vec![reg("w0"), reg("w1")]),
+        instr("ret", vec![]),
+    ]);
+    assert_eq!(
+        peephole::optimize(asm).lines,
+        [
+            instr("mov", vec![reg("w0"), reg("w1")]),
+            instr("ret", vec![])
+        ]
+    );
+}
+
+#[test]
+fn removes_branch_to_next_instruction() {
+    let asm = assembly(vec![
+        instr("b", vec![sym("Lmain_1")]),
+        label("Lmain_1"),
+        instr("ret", vec![]),
+    ]);
+    assert_eq!(
+        peephole::optimize(asm).lines,
+        [label("Lmain_1"), instr("ret", vec![])]
+    );
+}
+
+#[test]
+fn keeps_branch_over_code() {
+    let asm = assembly(vec![
+        instr("b", vec![sym("Lmain_2")]),
+        label("Lmain_1"),
+        instr("ret", vec![]),
+        label("Lmain_2"),
+        instr("ret", vec![]),
+    ]);
+    assert_eq!(peephole::optimize(asm.clone()), asm);
+}
+
+#[test]
+fn forwards_stored_register_to_reload() {
+    let asm = assembly(vec![
+        instr("str", vec![reg("w0"), mem("x29", -4)]),
+        instr("ldr", vec![reg("w8"), mem("x29", -4)]),
+        instr("str", vec![reg("w8"), mem("x29", -8)]),
+        instr("ldr", vec![reg("w8"), mem("x29", -8)]),
+    ]);
+    assert_eq!(
+        peephole::optimize(asm).lines,
+        [
+            instr("str", vec![reg("w0"), mem("x29", -4)]),
+            instr("mov", vec![reg("w8"), reg("w0")]),
+            instr("str", vec![reg("w8"), mem("x29", -8)]),
+        ]
+    );
+}
+
+#[test]
+fn does_not_forward_across_labels_or_slots() {
+    let asm = assembly(vec![
+        instr("str", vec![reg("w0"), mem("x29", -4)]),
+        label("Lf_1"),
+        instr("ldr", vec![reg("w8"), mem("x29", -4)]),
+        instr("str", vec![reg("w8"), mem("x29", -8)]),
+        instr("ldr", vec![reg("w9"), mem("x29", -12)]),
+    ]);
+    assert_eq!(peephole::optimize(asm.clone()), asm);
+}
+
+#[test]
+fn removes_adjacent_push_pop_pair() {
+    let push = Operand::Mem(Address::PreIndex {
+        base: "sp".to_string(),
+        offset: -16,
+    });
+    let pop = Operand::Mem(Address::PostIndex {
+        base: "sp".to_string(),
+        offset: 16,
+    });
+    let asm = assembly(vec![
+        instr("stp", vec![reg("x29"), reg("x30"), push]),
+        instr("ldp", vec![reg("x29"), reg("x30"), pop]),
+        instr("bl", vec![sym("_leaf")]),
+    ]);
+    assert_eq!(
+        peephole::optimize(asm).lines,
+        [instr("bl", vec![sym("_leaf")])]
+    );
+}
+
+#[test]
+fn leaves_inline_assembly_alone() {
+    let asm = assembly(vec![
+        Line::Verbatim("mov x9, x9".to_string()),
+        instr("mov", vec![reg("w8"), imm(1)]),
+    ]);
+    assert_eq!(peephole::optimize(asm.clone()), asm);
+}
+
+#[test]
+fn o1_output_does_not_reload_call_results() {
+    let source = "int f() { return 5; } int main() { return f() * 3; }";
+    let o1 = driver::compile(
+        source,
+        &Options {
+            opt_level: OptLevel::O1,
+            ..Options::default()
+        },
+    )
+    .unwrap()
+    .assembly;
+    assert!(o1.contains("bl _f\n    mov w10, w0\n"), "{o1}");
+    assert!(!o1.contains("ldr"), "{o1}");
+
+    let o0 = driver::compile(source, &Options::default())
+        .unwrap()
+        .assembly;
+    assert!(o0.contains("ldr w8, [x29, #-4]"), "{o0}");
+}
@@ -0,0 +1,211 @@
+mod common;
+
+use std::fs;
+use std::process::{Command, Stdio};
+
+use rcc::codegen::aarch64::Aarch64;
+use rcc::codegen::x86_64::X86_64;
+use rcc::codegen::{Backend, CodeGenerator, Visibility};
+use rcc::ir::interp::{Interpreter, Trap};
+use rcc::ir::{Datum, FunctionBuilder, Global, IrType, Module};
+
+use common::assert_assembles;
+
+/// `entry` passes the addresses of a global defined in the module and one
+/// defined elsewhere to `consume`, then returns what `helper` returns.
+fn library() -> Module {
+    let mut module = Module::default();
+    module.globals.push(Global {
+        name: "counter".to_string(),
+        ty: IrType::I32,
+        len: 1,
+        init: vec![Datum::Int(5)],
+        thread_local: false,
+    });
+
+    let mut b = FunctionBuilder::new("helper", Some(IrType::I32));
+    let value = b.iconst(IrType::I32, 7);
+    b.ret(Some(value));
+    module.functions.push(b.finish());
+
+    let mut b = FunctionBuilder::new("entry", Some(IrType::I32));
+    let counter = b.global_addr("counter");
+    let external = b.global_addr("external");
+    b.call("consume", vec![counter, external], None);
+    let result = b.call("helper", Vec::new(), Some(IrType::I32));
+    b.ret(result);
+    module.functions.push(b.finish());
+    module
+}
+
+fn generate(backend: &dyn Backend, pic: bool) -> String {
+    CodeGenerator::new()
+        .with_backend(backend)
+        .with_pic(pic)
+        .generate(&library())
+}
+
+#[test]
+fn prints_global_addresses() {
+    let text = library().to_string();
+    assert!(text.contains("%0 = addr ptr @counter\n"), "{text}");
+}
+
+#[test]
+fn the_interpreter_hands_out_addresses_for_defined_globals() {
+    let mut b = FunctionBuilder::new("main", Some(IrType::I32));
+    b.global_addr("counter");
+    let zero = b.iconst(IrType::I32, 0);
+    b.ret(Some(zero));
+    let mut module = library();
+    module.functions.push(b.finish());
+    assert_eq!(Interpreter::new(&module).call("main", &[]), Ok(Some(0)));
+    assert_eq!(
+        Interpreter::new(&module).call("entry", &[]),
+        Err(Trap::UnknownGlobal("external".to_string()))
+    );
+}
+
+#[test]
+fn elf_globals_are_pc_relative_without_pic() {
+    let asm = generate(&Aarch64::LINUX, false);
+    assert!(
+        asm.contains("adrp x8, counter\n    add x8, x8, :lo12:counter\n"),
+        "{asm}"
+    );
+    assert!(!asm.contains(":got:"), "{asm}");
+    assert_assembles("aarch64-linux-gnu", &asm);
+
+    let asm = generate(&X86_64::LINUX, false);
+    assert!(asm.contains("leaq counter(%rip), "), "{asm}");
+    assert!(asm.contains("call helper\n"), "{asm}");
+}
+
+#[test]
+fn elf_pic_goes_through_the_got_and_plt() {
+    let asm = generate(&Aarch64::LINUX, true);
+    assert!(
+        asm.contains("adrp x8, :got:counter\n    ldr x8, [x8, :got_lo12:counter]\n"),
+        "{asm}"
+    );
+    assert!(asm.contains(":got:external"), "{asm}");
+    assert_assembles("aarch64-linux-gnu", &asm);
+
+    let asm = generate(&X86_64::LINUX, true);
+    assert!(asm.contains("movq counter@GOTPCREL(%rip), "), "{asm}");
+    assert!(asm.contains("movq external@GOTPCREL(%rip), "), "{asm}");
+    assert!(asm.contains("call helper@PLT\n"), "{asm}");
+    assert!(asm.contains("call consume@PLT\n"), "{asm}");
+    assert_assembles("x86_64-linux-gnu", &asm);
+}
+
+#[test]
+fn macho_uses_the_got_only_for_external_globals() {
+    for pic in [false, true] {
+        let asm = generate(&Aarch64::APPLE, pic);
+        assert!(
+            asm.contains("adrp x8, _counter@PAGE\n    add x8, x8, _counter@PAGEOFF\n"),
+            "{asm}"
+        );
+        assert!(
+            asm.contains("adrp x8, _external@GOTPAGE\n    ldr x8, [x8, _external@GOTPAGEOFF]\n"),
+            "{asm}"
+        );
+        assert_assembles("arm64-apple-macos", &asm);
+
+        let asm = generate(&X86_64::APPLE, pic);
+        assert!(asm.contains("leaq _counter(%rip), "), "{asm}");
+        assert!(asm.contains("movq _external@GOTPCREL(%rip), "), "{asm}");
+        assert!(!asm.contains("@PLT"), "{asm}");
+        assert_assembles("x86_64-apple-macos", &asm);
+    }
+}
+
+fn generate_hidden(backend: &dyn Backend) -> String {
+    CodeGenerator::new()
+        .with_backend(backend)
+        .with_pic(true)
+        .with_visibility(Visibility::Hidden)
+        .generate(&library())
+}
+
+#[test]
+fn parses_visibilities() {
+    assert_eq!(Visibility::parse("default"), Some(Visibility::Default));
+    assert_eq!(Visibility::parse("hidden"), Some(Visibility::Hidden));
+    assert_eq!(Visibility::parse("protected"), None);
+}
+
+#[test]
+fn default_visibility_only_exports_symbols() {
+    let asm = generate(&X86_64::LINUX, true);
+    assert!(asm.contains(".globl entry\n"), "{asm}");
+    assert!(!asm.contains(".hidden"), "{asm}");
+    let asm = generate(&Aarch64::APPLE, true);
+    assert!(asm.contains(".globl _entry\n"), "{asm}");
+    assert!(!asm.contains(".private_extern"), "{asm}");
+}
+
+#[test]
+fn hidden_symbols_are_marked_per_object_format() {
+    let asm = generate_hidden(&X86_64::LINUX);
+    assert!(asm.contains(".globl entry\n    .hidden entry\n"), "{asm}");
+    assert!(
+        asm.contains(".globl counter\n    .hidden counter\n"),
+        "{asm}"
+    );
+    assert_assembles("x86_64-linux-gnu", &asm);
+
+    let asm = generate_hidden(&Aarch64::APPLE);
+    assert!(
+        asm.contains(".globl _entry\n    .private_extern _entry\n"),
+        "{asm}"
+    );
+    assert!(
+        asm.contains(".globl _counter\n    .private_extern _counter\n"),
+        "{asm}"
+    );
+    assert_assembles("arm64-apple-macos", &asm);
+}
+
+#[test]
+fn hidden_globals_defined_here_skip_the_got() {
+    let asm = generate_hidden(&X86_64::LINUX);
+    assert!(asm.contains("leaq counter(%rip), "), "{asm}");
+    assert!(asm.contains("movq external@GOTPCREL(%rip), "), "{asm}");
+
+    let asm = generate_hidden(&Aarch64::LINUX);
+    assert!(
+        asm.contains("adrp x8, counter\n    add x8, x8, :lo12:counter\n"),
+        "{asm}"
+    );
+    assert!(asm.contains(":got:external"), "{asm}");
+    assert_assembles("aarch64-linux-gnu", &asm);
+}
+
+#[test]
+fn only_pic_links_into_a_shared_object() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("rcc-pic-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let link = |name: &str, pic: bool| {
+        let source = dir.join(format!("{name}.s"));
+        fs::write(&source, generate(&X86_64::LINUX, pic)).unwrap();
+        Command::new("cc")
+            .arg("-shared")
+            .arg(&source)
+            .arg("-o")
+            .arg(dir.join(format!("lib{name}.so")))
+            .stderr(Stdio::null())
+            .status()
+            .ok()
+    };
+    let Some(pic) = link("pic", true) else {
+        return;
+    };
+    assert!(pic.success());
+    assert!(!link("nopic", false).unwrap().success());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,201 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use rcc::codegen::Target;
+use rcc::driver::Options;
+use rcc::ir::interp::{Interpreter, Trap};
+use rcc::opt::OptLevel;
+
+use common::{assert_assembles, compile};
+
+fn run_main(source: &str, opt_level: OptLevel) -> Result<Option<i64>, Trap> {
+    let options = Options {
+        opt_level,
+        ..Options::default()
+    };
+    let ir = compile(source, &options)
+        .expect("program should compile")
+        .ir;
+    Interpreter::new(&ir).call("main", &[])
+}
+
+/// Swaps two locals through pointers to them, and reads one back through
+/// a pointer to a pointer.
+const SWAP: &str = "void swap(int *a, int *b) { int t = *a; *a = *b; *b = t; }
+int main() {
+    int x = 3;
+    int y = 40;
+    int *p = &x;
+    int **pp = &p;
+    swap(&x, &y);
+    **pp = **pp + 2;
+    return x - y;
+}";
+
+#[test]
+fn pointers_read_and_write_what_they_point_at() {
+    let cases = [
+        (SWAP, 39),
+        (
+            "int main() { int x = 5; int *p = &x; *p = 7; return x; }",
+            7,
+        ),
+        ("int main() { int x = 5; return *&x; }", 5),
+        ("int main() { return *\"abc\"; }", 97),
+        ("int main() { return *(\"abc\" + 2) - *\"abc\"; }", 2),
+        ("int main() { int x; int *p = &x; return (p + 1) - p; }", 1),
+        // The difference is a `long`, wide enough for any distance.
+        ("int main() { int *p = 0; return sizeof (p - p); }", 8),
+        (
+            "int main() { char *p = 0; char *q = p + 5000000000; return (q - p) / 1000000000; }",
+            5,
+        ),
+        (
+            "int main() { int x; int *p = &x; return (p < p + 1) + (p == p) + (p != 0); }",
+            3,
+        ),
+        ("int main() { int *p = 0; return !p; }", 1),
+        (
+            "int main() { int x = 0; int *p = &x; while (p) { p = 0; x = x + 1; } return x; }",
+            1,
+        ),
+    ];
+    for (source, expected) in cases {
+        for level in [OptLevel::O0, OptLevel::O2] {
+            assert_eq!(
+                run_main(source, level),
+                Ok(Some(expected)),
+                "{source} at {level:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn null_pointers_trap_in_the_interpreter() {
+    assert_eq!(
+        run_main("int main() { int *p = 0; return *p; }", OptLevel::O0),
+        Err(Trap::UndefinedBehavior(
+            "dereference of a null pointer".to_string()
+        ))
+    );
+}
+
+#[test]
+fn pointer_operands_are_checked() {
+    let cases = [
+        (
+            "int main() { return *1; }",
+            "1:21: indirection requires a pointer operand (`int` invalid)",
+        ),
+        (
+            "int main() { return &1 == 0; }",
+            "1:21: cannot take the address of an rvalue",
+        ),
+        (
+            "int main() { int x; int *p = &x; return p + p == 0; }",
+            "1:43: invalid operands to binary `+` (`int *` and `int *`)",
+        ),
+        (
+            "int main() { int x; int *p = &x; int *q = p; return p + q; }",
+            "1:55: invalid operands to binary `+` (`int *` and `int *`)",
+        ),
+        (
+            "int main() { int *p = 1 + 2; return 0; }",
+            "1:25: expected `int *` but found `int`",
+        ),
+        (
+            "int main() { int *p = -1; return 0; }",
+            "1:23: expected `int *` but found `int`",
+        ),
+        (
+            "int main() { int x; int *p = x; return 0; }",
+            "1:30: expected `int *` but found `int`",
+        ),
+        (
+            "int main() { int x; int *p = &x; p = \"x\"; return 0; }",
+            "1:38: expected `int *` but found `char *`",
+        ),
+        (
+            "int main() { int x; int **p = &x; return 0; }",
+            "1:31: expected `int **` but found `int *`",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(
+            compile(source, &Options::default()).unwrap_err(),
+            expected,
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn pointers_lower_to_addresses_loads_and_stores() {
+    let ir = compile(
+        "int main() { int x = 5; int *p = &x; *p = 7; return *p; }",
+        &Options::default(),
+    )
+    .unwrap()
+    .ir
+    .to_string();
+    for line in ["= addr ptr ss0\n", "    store [%", "= load i32 [%"] {
+        assert!(ir.contains(line), "{line} in {ir}");
+    }
+
+    let ir = compile(
+        "long f(int *p, int *q) { return p - q; }",
+        &Options::default(),
+    )
+    .unwrap()
+    .ir
+    .to_string();
+    assert!(ir.contains(" = sdiv ptr %"), "{ir}");
+    assert!(!ir.contains("trunc"), "{ir}");
+}
+
+#[test]
+fn pointer_code_assembles_for_aarch64() {
+    for (target, triple) in [
+        (Target::AARCH64_LINUX, "aarch64-linux-gnu"),
+        (Target::AARCH64_APPLE, "arm64-apple-macos"),
+    ] {
+        let asm = compile(
+            SWAP,
+            &Options {
+                target,
+                ..Options::default()
+            },
+        )
+        .unwrap()
+        .assembly;
+        assert!(asm.contains("sub x"), "{asm}");
+        assert_assembles(triple, &asm);
+    }
+}
+
+#[test]
+fn x86_program_swaps_through_pointers() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let options = Options {
+        target: Target::X86_64_LINUX,
+        ..Options::default()
+    };
+    let asm = compile(SWAP, &options).unwrap().assembly;
+    assert!(asm.contains("leaq -"), "{asm}");
+    let dir = std::env::temp_dir().join(format!("rcc-pointers-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (source, exe) = (dir.join("pointers.s"), dir.join("pointers"));
+    fs::write(&source, &asm).unwrap();
+    let Ok(linked) = Command::new("cc").arg(&source).arg("-o").arg(&exe).status() else {
+        return;
+    };
+    assert!(linked.success(), "{asm}");
+    let output = Command::new(&exe).output().unwrap();
+    assert_eq!(output.status.code(), Some(39));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,133 @@
+use std::fs;
+
+use rcc::ast::{Program, Statement};
+use rcc::ast_diff;
+use rcc::lexer::Lexer;
+use rcc::parser::Parser;
+use rcc::preprocess::Preprocessor;
+use rcc::pretty;
+
+fn parse(source: &str) -> Program {
+    let tokens = Lexer::new(source).lex().unwrap();
+    Parser::new(tokens)
+        .parse()
+        .unwrap_or_else(|err| panic!("{err} in\n{source}"))
+}
+
+/// Checks that printing `source` and parsing it again gives the same tree,
+/// positions aside, and that printing that tree gives the same text.
+fn round_trip(source: &str) -> String {
+    let program = parse(source);
+    let printed = pretty::emit(&program);
+    let reparsed = parse(&printed);
+    let trees = |program: &Program| {
+        let functions: Vec<_> = program.functions.iter().map(ast_diff::render).collect();
+        (functions, program.enums.len(), program.globals.len())
+    };
+    assert_eq!(trees(&reparsed), trees(&program), "{printed}");
+    assert_eq!(pretty::emit(&reparsed), printed);
+    printed
+}
+
+#[test]
+fn programs_print_as_formatted_c() {
+    let source = "enum color{RED,GREEN=4};int g[2]={1,2};extern int e;
+int sum(int a[][3],int n){int s=0;for(int i=0;i<n;i++)s+=a[i][0];return s;}
+int main(void){int x=1;do{x<<=1;}while(x<100);out:return x+GREEN;}";
+    assert_eq!(
+        round_trip(source),
+        "enum color { RED, GREEN = 4 };
+
+int g[2] = {1, 2};
+extern int e;
+
+int sum(int a[][3], int n) {
+    int s = 0;
+    for (int i = 0; i < n; i++)
+        s += a[i][0];
+    return s;
+}
+
+int main(void) {
+    int x = 1;
+    do {
+        x <<= 1;
+    } while (x < 100);
+out:
+    return x + GREEN;
+}
+"
+    );
+}
+
+#[test]
+fn parentheses_are_kept_only_where_needed() {
+    let cases = [
+        ("(a + b) * c", "(a + b) * c"),
+        ("a + (b * c)", "a + b * c"),
+        ("a - (b - c)", "a - (b - c)"),
+        ("(a - b) - c", "a - b - c"),
+        ("a = (b = c)", "a = b = c"),
+        ("-(-a)", "- -a"),
+        ("-(--a)", "- --a"),
+        ("&*p", "&*p"),
+        ("*(p + 1)", "p[1]"),
+        ("(*p)++", "(*p)++"),
+        ("*p++", "*p++"),
+        ("sizeof(a + b)", "sizeof (a + b)"),
+        ("sizeof(int *) + sizeof a", "sizeof(int *) + sizeof a"),
+        ("!(a && b) || c", "!(a && b) || c"),
+    ];
+    for (expr, expected) in cases {
+        let source = format!("int main() {{ return {expr}; }}");
+        let program = parse(&source);
+        let [Statement::Return {
+            value: Some(value), ..
+        }] = program.functions[0].body.as_deref().unwrap()
+        else {
+            panic!("expected a return");
+        };
+        assert_eq!(pretty::expression(value), expected, "{expr}");
+        round_trip(&source);
+    }
+}
+
+#[test]
+fn literals_are_escaped() {
+    let source =
+        r#"int main() { char *s = "tab\there \"quoted\"\n\351\0012"; return '\'' + '\\' + '\0'; }"#;
+    let printed = round_trip(source);
+    assert!(
+        printed.contains(r#""tab\there \"quoted\"\n\351\0012""#),
+        "{printed}"
+    );
+    assert!(printed.contains(r"'\'' + '\\' + '\000'"), "{printed}");
+}
+
+#[test]
+fn asm_statements_keep_their_operands() {
+    let source = r#"int main() { int x = 1; int y; __asm__("add %0, %1" : "=r"(y) : "r"(x) : "cc", "memory"); __asm__("nop" : : "r"(x)); __asm__("nop"); return y; }"#;
+    let printed = round_trip(source);
+    assert!(
+        printed.contains(r#"__asm__("add %0, %1" : "=r"(y) : "r"(x) : "cc", "memory");"#),
+        "{printed}"
+    );
+    assert!(
+        printed.contains(r#"__asm__("nop" : : "r"(x));"#),
+        "{printed}"
+    );
+}
+
+#[test]
+fn the_fixtures_round_trip() {
+    for entry in fs::read_dir("tests/fixtures").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "c") {
+            continue;
+        }
+        let source = fs::read_to_string(&path).unwrap();
+        let name = path.display().to_string();
+        let preprocessed = Preprocessor::new().run(&path, &name, &source).unwrap();
+        round_trip(&preprocessed.source);
+    }
+}
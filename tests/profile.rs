@@ -0,0 +1,106 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use rcc::codegen::aarch64::Aarch64;
+use rcc::codegen::{CodeGenerator, Target};
+use rcc::driver::{self, Options};
+use rcc::ir::{BinOp, FunctionBuilder, IrType, Module};
+use rcc::opt::OptLevel;
+
+use common::assert_assembles;
+
+const SOURCE: &str = "int helper() { return 6 * 7; }\nint main() { return helper() - 42; }\n";
+
+fn assembly(target: Target) -> String {
+    driver::compile(
+        SOURCE,
+        &Options {
+            target,
+            opt_level: OptLevel::O1,
+            profile: true,
+            ..Options::default()
+        },
+    )
+    .expect("program should compile")
+    .assembly
+}
+
+/// The assembly of the function labeled `label`, up to the next blank line.
+fn function<'a>(asm: &'a str, label: &str) -> &'a str {
+    let start = asm
+        .find(&format!("\n{label}:\n"))
+        .expect("function is defined")
+        + 1;
+    let rest = &asm[start..];
+    &rest[..rest.find("\n\n").unwrap_or(rest.len())]
+}
+
+#[test]
+fn leaves_keep_a_frame_to_call_mcount() {
+    let asm = assembly(Target::AARCH64_LINUX);
+    assert!(
+        function(&asm, "helper").starts_with(
+            "helper:\n    stp x29, x30, [sp, #-16]!\n    mov x29, sp\n    mov x0, x30\n    bl _mcount\n"
+        ),
+        "{asm}"
+    );
+    assert_assembles("aarch64-linux-gnu", &asm);
+}
+
+#[test]
+fn aarch64_saves_parameters_around_the_call() {
+    let mut b = FunctionBuilder::new("add3", Some(IrType::I32));
+    let (x, y, z) = (
+        b.param(IrType::I32),
+        b.param(IrType::I32),
+        b.param(IrType::I32),
+    );
+    let sum = b.binary(BinOp::Add, x, y);
+    let sum = b.binary(BinOp::Add, sum, z);
+    b.ret(Some(sum));
+    let mut module = Module::default();
+    module.functions.push(b.finish());
+    let asm = CodeGenerator::new()
+        .with_backend(&Aarch64::LINUX)
+        .with_profiling(true)
+        .generate(&module);
+    assert!(
+        asm.contains(
+            "    stp x0, x1, [sp, #-32]!\n    stp x2, x3, [sp, #16]\n    mov x0, x30\n    bl _mcount\n    ldp x2, x3, [sp, #16]\n    ldp x0, x1, [sp], #32\n"
+        ),
+        "{asm}"
+    );
+    assert_assembles("aarch64-linux-gnu", &asm);
+}
+
+#[test]
+fn x86_profiled_programs_write_gmon_out() {
+    let asm = assembly(Target::X86_64_LINUX);
+    assert!(
+        function(&asm, "helper").contains("movq %rsp, %rbp\n    call mcount\n"),
+        "{asm}"
+    );
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("rcc-profile-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (source, exe) = (dir.join("main.s"), dir.join("main"));
+    fs::write(&source, &asm).unwrap();
+    let Ok(linked) = Command::new("cc")
+        .arg("-pg")
+        .arg(&source)
+        .arg("-o")
+        .arg(&exe)
+        .status()
+    else {
+        return;
+    };
+    assert!(linked.success());
+    let status = Command::new(&exe).current_dir(&dir).status().unwrap();
+    assert_eq!(status.code(), Some(0));
+    assert!(dir.join("gmon.out").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,113 @@
+use rcc::ir::range::{Range, Ranges};
+use rcc::ir::{BinOp, CmpOp, FunctionBuilder, IrType, Module, Terminator, Value};
+use rcc::opt::{self, OptLevel};
+
+fn range(min: i64, max: i64) -> Range {
+    Range { min, max }
+}
+
+/// Computes the range of `build(x)` for an unknown parameter `x`.
+fn range_of(build: impl FnOnce(&mut FunctionBuilder, Value) -> Value) -> Range {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let result = build(&mut f, x);
+    f.ret(Some(result));
+    let func = f.finish();
+    Ranges::compute(&func).range(&func, result)
+}
+
+fn with_const(op: BinOp, rhs: i64) -> impl FnOnce(&mut FunctionBuilder, Value) -> Value {
+    move |f, x| {
+        let c = f.iconst(IrType::I32, rhs);
+        f.binary(op, x, c)
+    }
+}
+
+#[test]
+fn parameters_are_unknown() {
+    assert_eq!(range_of(|_, x| x), Range::full(IrType::I32));
+}
+
+#[test]
+fn masks_and_remainders_bound_unknown_values() {
+    assert_eq!(range_of(with_const(BinOp::And, 7)), range(0, 7));
+    assert_eq!(range_of(with_const(BinOp::SRem, 10)), range(-9, 9));
+    assert_eq!(
+        range_of(with_const(BinOp::URem, 10)),
+        Range::full(IrType::I32)
+    );
+    assert_eq!(range_of(with_const(BinOp::AShr, 24)), range(-128, 127));
+    assert_eq!(
+        range_of(with_const(BinOp::SDiv, 1 << 16)),
+        range(-32768, 32767)
+    );
+}
+
+#[test]
+fn arithmetic_propagates_bounds() {
+    let r = range_of(|f, x| {
+        let seven = f.iconst(IrType::I32, 7);
+        let masked = f.binary(BinOp::And, x, seven);
+        let three = f.iconst(IrType::I32, 3);
+        let scaled = f.binary(BinOp::Mul, masked, three);
+        let one = f.iconst(IrType::I32, 1);
+        f.binary(BinOp::Add, scaled, one)
+    });
+    assert_eq!(r, range(1, 22));
+    assert!(!r.contains(0));
+}
+
+#[test]
+fn overflow_widens_to_the_full_range() {
+    let max = i64::from(i32::MAX);
+    let r = range_of(|f, _| {
+        let big = f.iconst(IrType::I32, max);
+        let one = f.iconst(IrType::I32, 1);
+        f.binary(BinOp::Add, big, one)
+    });
+    assert_eq!(r, Range::full(IrType::I32));
+}
+
+#[test]
+fn comparisons_decided_by_bounds_are_constant() {
+    let compare = |op, rhs| {
+        range_of(move |f, x| {
+            let seven = f.iconst(IrType::I32, 7);
+            let masked = f.binary(BinOp::And, x, seven);
+            let c = f.iconst(IrType::I32, rhs);
+            f.cmp(op, masked, c)
+        })
+    };
+    assert_eq!(compare(CmpOp::Slt, 8), range(1, 1));
+    assert_eq!(compare(CmpOp::Sgt, 7), range(0, 0));
+    assert_eq!(compare(CmpOp::Eq, 9), range(0, 0));
+    assert_eq!(compare(CmpOp::Sle, 3), range(0, 1));
+    assert_eq!(compare(CmpOp::Uge, 0), range(1, 1));
+}
+
+#[test]
+fn folds_branches_on_conditions_that_cannot_be_zero() {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let (then_block, else_block) = (f.create_block(), f.create_block());
+    let seven = f.iconst(IrType::I32, 7);
+    let masked = f.binary(BinOp::And, x, seven);
+    let one = f.iconst(IrType::I32, 1);
+    let cond = f.binary(BinOp::Add, masked, one);
+    f.branch(cond, then_block, else_block);
+    f.switch_to(then_block);
+    f.ret(Some(x));
+    f.switch_to(else_block);
+    let zero = f.iconst(IrType::I32, 0);
+    f.ret(Some(zero));
+
+    let mut module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+    opt::optimize(&mut module, OptLevel::O1);
+    let func = &module.functions[0];
+    assert_eq!(func.blocks.len(), 1, "{func:#?}");
+    assert_eq!(func.blocks[0].term, Terminator::Ret(Some(x)));
+    assert!(func.blocks[0].instrs.is_empty(), "{func:#?}");
+}
@@ -0,0 +1,92 @@
+use rcc::driver;
+use rcc::lexer::Lexer;
+use rcc::parser::Parser;
+use rcc::standard::Standard;
+
+fn parse_recovering(source: &str) -> (Vec<String>, Vec<String>) {
+    let tokens = Lexer::new(source).lex().unwrap();
+    let (program, errors) = Parser::new(tokens).parse_recovering();
+    let names = program.functions.iter().map(|f| f.name.clone()).collect();
+    (names, errors.iter().map(|err| err.to_string()).collect())
+}
+
+#[test]
+fn statements_with_syntax_errors_are_skipped() {
+    let source = "int f() {\n  int x = 1 2;\n  x = ;\n  return x;\n}\nint g() {\n  return 3 +;\n}\nint main() { return 0; }\n";
+    let (names, errors) = parse_recovering(source);
+    assert_eq!(names, ["f", "g", "main"]);
+    assert_eq!(
+        errors,
+        [
+            "2:13: expected `;`, found integer literal `2`",
+            "3:7: expected an expression, found `;`",
+            "7:13: expected an expression, found `;`",
+        ]
+    );
+}
+
+#[test]
+fn the_statements_around_an_error_are_kept() {
+    let tokens = Lexer::new("int main() { int x = 1; x = (; return x; }")
+        .lex()
+        .unwrap();
+    let (program, errors) = Parser::new(tokens).parse_recovering();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(program.functions[0].body.as_deref().unwrap().len(), 2);
+}
+
+#[test]
+fn a_block_with_an_error_skips_to_its_end() {
+    let source = "int main() {\n  while (1) { int = 2; return 1; }\n  return 0 0;\n}\n";
+    let (names, errors) = parse_recovering(source);
+    assert_eq!(names, ["main"]);
+    assert_eq!(
+        errors,
+        [
+            "2:19: expected an identifier, found `=`",
+            "3:12: expected `;`, found integer literal `0`",
+        ]
+    );
+}
+
+#[test]
+fn a_missing_brace_stops_at_the_end_of_the_file() {
+    let (names, errors) = parse_recovering("int main() {\n  return 0;\n");
+    assert!(names.is_empty());
+    assert_eq!(errors, ["3:1: expected an expression, found end of file"]);
+}
+
+#[test]
+fn parse_reports_the_first_error() {
+    let tokens = Lexer::new("int main() { return 1 2; return 3 4; }")
+        .lex()
+        .unwrap();
+    let err = Parser::new(tokens).parse().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "1:23: expected `;`, found integer literal `2`"
+    );
+}
+
+#[test]
+fn diagnose_collects_every_error() {
+    let errors = |source| {
+        driver::diagnose(source, Standard::default())
+            .iter()
+            .map(|err| err.to_string())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        errors("int main() { return 1 2; return 3 4; }"),
+        [
+            "1:23: expected `;`, found integer literal `2`",
+            "1:35: expected `;`, found integer literal `4`"
+        ]
+    );
+    // Without syntax errors, each declaration reports its first error.
+    assert_eq!(
+        errors("int f() { return a; }\nint g() { return b; }\n").len(),
+        2
+    );
+    assert_eq!(errors("int main() { return 0; }"), Vec::<String>::new());
+}
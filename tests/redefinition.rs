@@ -0,0 +1,148 @@
+use rcc::ast_interp;
+use rcc::driver::{self, Options};
+use rcc::error::Error;
+use rcc::ir::interp::Interpreter;
+use rcc::ir::Datum;
+
+fn error(source: &str) -> Error {
+    driver::compile(source, &Options::default()).expect_err("program should not compile")
+}
+
+/// The error and its note, as `line:col: message`.
+fn error_and_note(source: &str) -> (String, String) {
+    let err = error(source);
+    let note = err.note.as_ref().expect("the error should have a note");
+    (err.to_string(), note.to_string())
+}
+
+#[test]
+fn functions_are_defined_once() {
+    let source = "int main() { return 0; }\nint main() { return 1; }\n";
+    assert_eq!(
+        error_and_note(source),
+        (
+            "2:5: redefinition of `main`".to_string(),
+            "1:5: previous definition is here".to_string()
+        )
+    );
+    // Any number of prototypes may go with the definition.
+    let source =
+        "int f(int a);\nint f(int a) { return a; }\nint f(int b);\nint main() { return f(0); }";
+    assert!(driver::compile(source, &Options::default()).is_ok());
+}
+
+#[test]
+fn globals_may_be_tentatively_defined_again() {
+    let source = "int x;\nint x = 3;\nint x;\nint main() { return x; }";
+    let artifacts = driver::compile(source, &Options::default()).unwrap();
+    assert_eq!(artifacts.ir.globals.len(), 1);
+    assert_eq!(artifacts.ir.globals[0].init, [Datum::Int(3)]);
+    assert_eq!(
+        Interpreter::new(&artifacts.ir).call("main", &[]),
+        Ok(Some(3))
+    );
+    assert_eq!(
+        ast_interp::Interpreter::new(&artifacts.program).call("main", &[]),
+        Ok(Some(3))
+    );
+    // Only one of them may have an initializer.
+    assert_eq!(
+        error_and_note("int x = 1;\nint x;\nint x = 2;\nint main() { return 0; }"),
+        (
+            "3:5: redefinition of `x`".to_string(),
+            "1:5: previous definition is here".to_string()
+        )
+    );
+}
+
+#[test]
+fn redeclarations_must_agree() {
+    let cases = [
+        (
+            "int f(int a);\nlong f(int a);\nint main() { return 0; }",
+            "2:6: conflicting types for `f`",
+            "1:5: previous declaration is here",
+        ),
+        (
+            "int x;\nint x() { return 0; }\nint main() { return 0; }",
+            "2:5: redefinition of `x` as a different kind of symbol",
+            "1:5: previous declaration is here",
+        ),
+        (
+            "extern int x;\nlong x;\nint main() { return 0; }",
+            "2:6: conflicting types for `x`",
+            "1:12: previous declaration is here",
+        ),
+        (
+            "int x = 1;\nextern int x;\nint x = 2;\nint main() { return 0; }",
+            "3:5: redefinition of `x`",
+            "1:5: previous definition is here",
+        ),
+    ];
+    for (source, message, note) in cases {
+        assert_eq!(
+            error_and_note(source),
+            (message.to_string(), note.to_string()),
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn names_in_one_scope_are_declared_once() {
+    let cases = [
+        (
+            "int main() {\n  int x = 1;\n  int x = 2;\n  return x;\n}",
+            "3:7: redefinition of `x`",
+            "2:7: previous declaration is here",
+        ),
+        (
+            "int f(int a) {\n  int a;\n  return 0;\n}\nint main() { return 0; }",
+            "2:7: redefinition of `a`",
+            "1:11: previous declaration is here",
+        ),
+        (
+            "int f(int a, int a) { return 0; }\nint main() { return 0; }",
+            "1:18: redefinition of parameter `a`",
+            "1:11: previous declaration is here",
+        ),
+        (
+            "enum { A, B, A };\nint main() { return 0; }",
+            "1:14: redefinition of `A`",
+            "1:8: previous declaration is here",
+        ),
+        (
+            "int main() {\nout:\nout:\n  return 0;\n}",
+            "3:1: redefinition of label `out`",
+            "2:1: previous definition is here",
+        ),
+    ];
+    for (source, message, note) in cases {
+        assert_eq!(
+            error_and_note(source),
+            (message.to_string(), note.to_string()),
+            "{source}"
+        );
+    }
+    // An inner scope may reuse a name.
+    let source = "int main() { int i = 0; for (int i = 0; i < 3; i++) {} return i; }";
+    assert!(driver::compile(source, &Options::default()).is_ok());
+}
+
+#[test]
+fn notes_render_after_the_error() {
+    let source = "int main() { return 0; }\nint main() { return 1; }\n";
+    assert_eq!(
+        error(source).render_snippet("t.c", source, false),
+        "t.c:2:5: error: redefinition of `main`
+ 2 | int main() { return 1; }
+   |     ^~~~
+t.c:1:5: note: previous definition is here
+ 1 | int main() { return 0; }
+   |     ^~~~"
+    );
+    assert_eq!(
+        error(source).render("t.c"),
+        "t.c:2:5: error: redefinition of `main`\nt.c:1:5: note: previous definition is here"
+    );
+}
@@ -0,0 +1,275 @@
+use rcc::codegen::regalloc::{build_intervals, Allocation, Location, RegisterSet};
+use rcc::codegen::CodeGenerator;
+use rcc::ir::liveness::Liveness;
+use rcc::ir::{BinOp, BlockId, FunctionBuilder, IrType, Module, Value};
+
+const REGS: RegisterSet = RegisterSet {
+    caller_saved: &[10, 11, 12, 13, 14, 15],
+    callee_saved: &[],
+};
+
+#[test]
+fn liveness_flows_through_branches() {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let y = f.param(IrType::I32);
+    let (then_block, else_block) = (f.create_block(), f.create_block());
+    f.branch(x, then_block, else_block);
+    f.switch_to(then_block);
+    let sum = f.binary(BinOp::Add, x, y);
+    f.ret(Some(sum));
+    f.switch_to(else_block);
+    f.ret(Some(y));
+    let func = f.finish();
+
+    let liveness = Liveness::compute(&func);
+    assert!(liveness.is_live_out(BlockId::ENTRY, x));
+    assert!(liveness.is_live_out(BlockId::ENTRY, y));
+    assert!(liveness.is_live_in(then_block, x));
+    assert!(!liveness.is_live_in(else_block, x));
+    assert!(liveness.is_live_in(else_block, y));
+    assert!(!liveness.is_live_out(then_block, sum));
+}
+
+#[test]
+fn intervals_span_first_to_last_use() {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let a = f.binary(BinOp::Add, x, x);
+    let b = f.binary(BinOp::Mul, a, a);
+    let c = f.binary(BinOp::Sub, b, x);
+    f.ret(Some(c));
+    let func = f.finish();
+
+    let (intervals, calls) = build_intervals(&func);
+    let span = |value| {
+        let i = intervals.iter().find(|i| i.value == value).unwrap();
+        (i.start, i.end)
+    };
+    assert_eq!(span(x), (0, 3));
+    assert_eq!(span(a), (1, 2));
+    assert_eq!(span(b), (2, 3));
+    assert_eq!(span(c), (3, 4));
+    assert!(calls.is_empty());
+}
+
+#[test]
+fn registers_are_reused_after_intervals_end() {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let mut acc = x;
+    for _ in 0..10 {
+        acc = f.binary(BinOp::Add, acc, acc);
+    }
+    f.ret(Some(acc));
+    let func = f.finish();
+
+    let alloc = Allocation::compute(&func, REGS);
+    assert_eq!(alloc.spill_size, 0);
+    assert!(alloc.used_registers().len() <= 2);
+}
+
+#[test]
+fn spills_when_registers_run_out() {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let live: Vec<_> = (0..8).map(|_| f.binary(BinOp::Add, x, x)).collect();
+    let mut acc = x;
+    for &value in &live {
+        acc = f.binary(BinOp::Add, acc, value);
+    }
+    f.ret(Some(acc));
+    let func = f.finish();
+
+    let alloc = Allocation::compute(&func, REGS);
+    let spilled = live
+        .iter()
+        .filter(|&&v| matches!(alloc.location(v), Location::Stack(_)))
+        .count();
+    assert!(spilled >= 3, "{alloc:?}");
+    assert_eq!(alloc.spill_size as usize, 4 * spilled);
+    assert_eq!(alloc.used_registers(), REGS.caller_saved);
+}
+
+#[test]
+fn values_live_across_calls_are_spilled_without_callee_saved_registers() {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let before = f.binary(BinOp::Add, x, x);
+    let result = f.call("g", vec![], Some(IrType::I32)).unwrap();
+    let sum = f.binary(BinOp::Add, before, result);
+    f.ret(Some(sum));
+    let func = f.finish();
+
+    let alloc = Allocation::compute(&func, REGS);
+    assert!(matches!(alloc.location(before), Location::Stack(_)));
+    assert!(matches!(alloc.location(result), Location::Reg(_)));
+    assert!(matches!(alloc.location(sum), Location::Reg(_)));
+}
+
+#[test]
+fn constants_are_never_allocated() {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let c = f.iconst(IrType::I32, 42);
+    f.ret(Some(c));
+    let func = f.finish();
+
+    assert_eq!(
+        Allocation::compute(&func, REGS).location(c),
+        Location::Imm(42)
+    );
+}
+
+#[test]
+fn generated_code_uses_allocated_registers() {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let y = f.param(IrType::I32);
+    let sum = f.binary(BinOp::Add, x, y);
+    f.ret(Some(sum));
+    let module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+
+    let asm = CodeGenerator::new()
+        .with_register_allocation(true)
+        .generate(&module);
+    assert!(asm.contains("mov w10, w0\n    mov w11, w1\n"), "{asm}");
+    assert!(asm.contains("add w11, w10, w11"), "{asm}");
+    assert!(!asm.contains("sub sp"), "{asm}");
+
+    let stack_only = CodeGenerator::new().generate(&module);
+    assert!(stack_only.contains("str w0, [x29, #-4]"), "{stack_only}");
+    assert!(!stack_only.contains("w10"), "{stack_only}");
+}
+
+const WITH_CALLEE_SAVED: RegisterSet = RegisterSet {
+    caller_saved: &[10, 11],
+    callee_saved: &[19, 20, 21],
+};
+
+#[test]
+fn values_live_across_calls_use_callee_saved_registers() {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let before = f.binary(BinOp::Add, x, x);
+    let result = f.call("g", vec![], Some(IrType::I32)).unwrap();
+    let sum = f.binary(BinOp::Add, before, result);
+    f.ret(Some(sum));
+    let func = f.finish();
+
+    let alloc = Allocation::compute(&func, WITH_CALLEE_SAVED);
+    assert_eq!(alloc.location(before), Location::Reg(19));
+    // Values that never cross a call stay out of callee-saved registers.
+    assert!(matches!(alloc.location(result), Location::Reg(10 | 11)));
+    assert_eq!(alloc.spill_size, 0);
+}
+
+#[test]
+fn saves_and_restores_used_callee_saved_registers() {
+    let source = "int f() { return 5; } int main() { return f() * 2 + (f() * 3 + f()); }";
+    let asm = rcc::driver::compile(
+        source,
+        &rcc::driver::Options {
+            opt_level: rcc::opt::OptLevel::O1,
+            ..rcc::driver::Options::default()
+        },
+    )
+    .unwrap()
+    .assembly;
+    let main = &asm[asm.find("_main:").unwrap()..];
+    assert!(main.contains("stp x19, x20, [x29, #-16]\n"), "{main}");
+    assert!(
+        main.contains("ldp x19, x20, [x29, #-16]\n    mov sp, x29\n"),
+        "{main}"
+    );
+    assert!(!main.contains("[x29, #-4]"), "{main}");
+    // `f` uses no callee-saved registers, so it saves nothing.
+    let f = &asm[asm.find("_f:").unwrap()..asm.find("_main:").unwrap()];
+    assert!(!f.contains("x19"), "{f}");
+}
+
+#[test]
+fn an_odd_number_of_saved_registers_uses_a_single_store() {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let result = f.call("g", vec![], Some(IrType::I32)).unwrap();
+    let sum = f.binary(BinOp::Add, x, result);
+    f.ret(Some(sum));
+    let module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+
+    let asm = CodeGenerator::new()
+        .with_register_allocation(true)
+        .generate(&module);
+    assert!(
+        asm.contains("sub sp, sp, #16\n    str x19, [x29, #-16]\n    mov w19, w0\n"),
+        "{asm}"
+    );
+    assert!(
+        asm.contains("ldr x19, [x29, #-16]\n    mov sp, x29\n"),
+        "{asm}"
+    );
+}
+
+/// Sums `count` values that are all computed before the first addition, so
+/// all of them are live at once.
+fn wide_sum(f: &mut FunctionBuilder, x: Value, count: usize) -> Value {
+    let live: Vec<_> = (0..count).map(|_| f.binary(BinOp::Mul, x, x)).collect();
+    live.into_iter()
+        .fold(x, |acc, value| f.binary(BinOp::Add, acc, value))
+}
+
+#[test]
+fn spill_slots_are_reused_once_their_values_die() {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let first = wide_sum(&mut f, x, 10);
+    let second = wide_sum(&mut f, first, 10);
+    f.ret(Some(second));
+    let func = f.finish();
+
+    let alloc = Allocation::compute(&func, REGS);
+    let spilled = func
+        .value_types
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| matches!(alloc.location(Value(i as u32)), Location::Stack(_)))
+        .count();
+    assert!(spilled >= 8, "{alloc:?}");
+    assert!((alloc.spill_size as usize) < 4 * spilled, "{alloc:?}");
+
+    // Without registers every value keeps a slot of its own.
+    let stack_only = Allocation::compute(&func, RegisterSet::default());
+    assert_eq!(stack_only.spill_size as usize, 4 * func.value_types.len());
+}
+
+#[test]
+fn high_pressure_code_computes_the_same_result() {
+    let source = "int f() { return 3; } int main() { return (f() + 1) * (f() + 2) * (f() + 3) * (f() + 4) * (f() + 5) * (f() + 6) * (f() + 7) * (f() + 8) - f() * (f() + f() * (f() + f())); }";
+    let compile = |opt_level| {
+        rcc::driver::compile(
+            source,
+            &rcc::driver::Options {
+                opt_level,
+                ..rcc::driver::Options::default()
+            },
+        )
+        .unwrap()
+        .ir
+    };
+    let o0 = compile(rcc::opt::OptLevel::O0);
+    let o1 = compile(rcc::opt::OptLevel::O1);
+    let expected = rcc::ir::interp::Interpreter::new(&o0).call("main", &[]);
+    assert_eq!(
+        rcc::ir::interp::Interpreter::new(&o1).call("main", &[]),
+        expected
+    );
+    let asm = CodeGenerator::new()
+        .with_register_allocation(true)
+        .generate(&o1);
+    assert!(asm.contains("x19"), "{asm}");
+}
@@ -0,0 +1,60 @@
+use rcc::driver::Options;
+use rcc::repl::{self, is_complete, Outcome, Session};
+
+#[test]
+fn expressions_see_earlier_definitions() {
+    let mut session = Session::new(Options::default());
+    assert_eq!(
+        session.eval("int six() { return 6; }"),
+        Ok(Outcome::Defined(vec!["six".to_string()]))
+    );
+    assert_eq!(session.eval("six() * 7"), Ok(Outcome::Value(42)));
+    assert_eq!(
+        session.eval("2147483647 + 1"),
+        Ok(Outcome::Value(-2147483648))
+    );
+}
+
+#[test]
+fn redefining_a_function_replaces_it() {
+    let mut session = Session::new(Options::default());
+    session
+        .eval("int f() { return 1; } int g() { return f(); }")
+        .unwrap();
+    session.eval("int f() { return 2; }").unwrap();
+    // `g` went with the entry that defined the old `f`.
+    assert_eq!(session.functions().collect::<Vec<_>>(), ["f"]);
+    assert_eq!(session.eval("f()"), Ok(Outcome::Value(2)));
+}
+
+#[test]
+fn errors_leave_the_session_unchanged() {
+    let mut session = Session::new(Options::default());
+    session.eval("int f() { return 1; }").unwrap();
+    assert!(session
+        .eval("int g() { return h(); }")
+        .unwrap_err()
+        .contains("undeclared function `h`"));
+    assert!(session.eval("1 +").is_err());
+    assert_eq!(
+        session.eval("10 % 0").unwrap_err(),
+        "undefined behavior: srem 10, 0"
+    );
+    assert_eq!(session.functions().collect::<Vec<_>>(), ["f"]);
+}
+
+#[test]
+fn entries_continue_until_their_braces_close() {
+    assert!(!is_complete("int f() {\n"));
+    assert!(is_complete("int f() {\n  return 1;\n}\n"));
+    assert!(is_complete("1 + 2"));
+}
+
+#[test]
+fn runs_a_session_from_input_to_output() {
+    let input = "int f() {\n  return 40;\n}\nf() + 2\n:functions\n:quit\n1\n";
+    let mut output = Vec::new();
+    repl::run(input.as_bytes(), &mut output, Options::default()).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(output, "> ... ... defined f\n> 42\n> f\n> ");
+}
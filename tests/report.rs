@@ -0,0 +1,49 @@
+use rcc::codegen::Target;
+use rcc::driver::{self, Options};
+use rcc::report;
+
+const SOURCE: &str = "int helper() { return 1; }\nint main() {\n    return helper() + 2;\n}\n";
+
+fn html(source: &str) -> String {
+    let options = Options {
+        target: Target::X86_64_LINUX,
+        source_map: true,
+        ..Options::default()
+    };
+    let artifacts = driver::compile(source, &options).unwrap();
+    report::html("a&b.c", source, &artifacts)
+}
+
+#[test]
+fn every_stage_has_a_pane() {
+    let page = html(SOURCE);
+    assert!(page.starts_with("<!DOCTYPE html>\n"), "{page}");
+    assert!(page.contains("<title>rcc: a&amp;b.c</title>"), "{page}");
+    for title in ["Source", "Tokens", "Syntax tree", "IR", "Assembly"] {
+        assert!(page.contains(&format!("<h2>{title}</h2>")), "{title}");
+    }
+    // Nothing is loaded from elsewhere.
+    assert!(!page.contains(" src="), "{page}");
+    assert!(page.ends_with("</html>\n"));
+}
+
+#[test]
+fn lines_are_tagged_with_their_source_line() {
+    let page = html(SOURCE);
+    assert!(page.contains("<span data-line=\"3\">    return helper() + 2;</span>"));
+    assert!(page.contains("<span data-line=\"3\">3:12 identifier `helper`</span>"));
+    assert!(page.contains("<span data-line=\"3\">      call helper</span>"));
+    assert!(page.contains("<span data-line=\"3\">    %0 = call i32 @helper()</span>"));
+    assert!(page.contains("<span data-line=\"3\">    call helper</span>"));
+    // Lines from no particular place aren't tagged.
+    assert!(page.contains("<span>main:</span>"));
+    assert!(page.contains("<span>}</span>"));
+    assert!(page.contains("<span>fn main() -&gt; i32 {</span>"));
+}
+
+#[test]
+fn text_is_escaped() {
+    let page = html("int main() { __asm__(\"# <a>\"); return 0; }\n");
+    assert!(page.contains("__asm__(&quot;# &lt;a&gt;&quot;);"), "{page}");
+    assert!(!page.contains("<a>"));
+}
@@ -0,0 +1,36 @@
+mod common;
+
+use rcc::driver::Options;
+
+use common::{check_fixture, compile};
+
+#[test]
+fn inner_declarations_shadow_outer_ones() {
+    check_fixture("scopes");
+}
+
+#[test]
+fn enumerators_are_scoped_like_variables() {
+    check_fixture("scoped_enumerators");
+}
+
+#[test]
+fn names_declared_in_a_block_end_with_it() {
+    assert_eq!(
+        compile(
+            "int main() { { int inner = 1; } return inner; }",
+            &Options::default()
+        )
+        .unwrap_err(),
+        "1:40: use of undeclared identifier `inner`"
+    );
+    // Redeclaring in the same block is still an error.
+    assert_eq!(
+        compile(
+            "int main() { { int x; int x; } return 0; }",
+            &Options::default()
+        )
+        .unwrap_err(),
+        "1:27: redefinition of `x`"
+    );
+}
@@ -0,0 +1,126 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use rcc::codegen::Target;
+use rcc::driver::Options;
+use rcc::opt::OptLevel;
+
+use common::{assert_assembles, compile, run_main};
+
+/// Counts the calls each operator makes: the right operand only runs when
+/// the left one doesn't decide the result, and a null pointer is never
+/// dereferenced behind a guard.
+const SHORT_CIRCUIT: &str = "int calls;
+int yes() { calls++; return 1; }
+int no() { calls++; return 0; }
+int first(int *p) { return p && *p; }
+int main() {
+    int r = 0;
+    int x = 7;
+    r += no() && yes();
+    r += (yes() || no()) * 2;
+    r += (no() || yes() && yes()) * 4;
+    r += first(0) * 8;
+    r += first(&x) * 16;
+    return r * 10 + calls;
+}";
+
+#[test]
+fn logical_operators_short_circuit() {
+    let cases = [
+        (SHORT_CIRCUIT, 225),
+        ("int main() { int x = 0; 0 && (x = 1); return x; }", 0),
+        ("int main() { int x = 0; 1 || (x = 1); return x; }", 0),
+        ("int main() { int x = 0; 1 && (x = 2); return x; }", 2),
+        ("int main() { int x = 0; 0 || (x = 3); return x; }", 3),
+        ("int main() { return 5 && 6; }", 1),
+        ("int main() { return 0 || -2; }", 1),
+        ("int main() { long big = 65536; big *= big; return big && 1; }", 1),
+        (
+            "int main() { int i = 0; int n = 0; while (i < 10 && n < 3) { i++; n += i % 2; } return i; }",
+            5,
+        ),
+        (
+            "int main() { int a[3] = {1, 2, 0}; int *p = a; int n = 0; while (p && *p++) n++; return n; }",
+            2,
+        ),
+    ];
+    for (source, expected) in cases {
+        for level in [OptLevel::O0, OptLevel::O2] {
+            assert_eq!(
+                run_main(source, level),
+                Some(expected),
+                "{source} at {level:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn logical_operators_branch() {
+    let ir = compile(
+        "int f(int a, int b) { return a && b; }",
+        &Options::default(),
+    )
+    .unwrap()
+    .ir;
+    let text = ir.to_string();
+    assert_eq!(text.matches(" br ").count(), 2, "{text}");
+    assert!(!text.contains(" and "), "{text}");
+}
+
+#[test]
+fn logical_operands_must_be_scalars() {
+    assert_eq!(
+        compile(
+            "void f(); int main() { return 1 && f(); }",
+            &Options::default()
+        )
+        .unwrap_err(),
+        "1:36: expected `int` but found `void`"
+    );
+}
+
+#[test]
+fn short_circuit_code_assembles_for_aarch64() {
+    for (target, triple) in [
+        (Target::AARCH64_LINUX, "aarch64-linux-gnu"),
+        (Target::AARCH64_APPLE, "arm64-apple-macos"),
+    ] {
+        let asm = compile(
+            SHORT_CIRCUIT,
+            &Options {
+                target,
+                ..Options::default()
+            },
+        )
+        .unwrap()
+        .assembly;
+        assert_assembles(triple, &asm);
+    }
+}
+
+#[test]
+fn x86_program_short_circuits() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let options = Options {
+        target: Target::X86_64_LINUX,
+        ..Options::default()
+    };
+    let asm = compile(SHORT_CIRCUIT, &options).unwrap().assembly;
+    let dir = std::env::temp_dir().join(format!("rcc-short-circuit-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (source, exe) = (dir.join("short_circuit.s"), dir.join("short_circuit"));
+    fs::write(&source, &asm).unwrap();
+    let Ok(linked) = Command::new("cc").arg(&source).arg("-o").arg(&exe).status() else {
+        return;
+    };
+    assert!(linked.success(), "{asm}");
+    let output = Command::new(&exe).output().unwrap();
+    assert_eq!(output.status.code(), Some(225));
+    fs::remove_dir_all(&dir).unwrap();
+}
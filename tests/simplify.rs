@@ -0,0 +1,117 @@
+use rcc::driver::{self, Options};
+use rcc::ir::{BinOp, FunctionBuilder, Instr, IrType, Module, Terminator, Value};
+use rcc::opt::{self, OptLevel};
+
+/// Builds `f(x) = build(x)` and returns its optimized entry block.
+fn simplify(build: impl FnOnce(&mut FunctionBuilder, Value) -> Value) -> (Vec<Instr>, Terminator) {
+    let mut builder = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = builder.param(IrType::I32);
+    let result = build(&mut builder, x);
+    builder.ret(Some(result));
+    let mut module = Module {
+        functions: vec![builder.finish()],
+        ..Module::default()
+    };
+    opt::optimize(&mut module, OptLevel::O1);
+    let block = module.functions.remove(0).blocks.remove(0);
+    (block.instrs, block.term)
+}
+
+fn with_const(op: BinOp, rhs: i64) -> impl FnOnce(&mut FunctionBuilder, Value) -> Value {
+    move |f, x| {
+        let c = f.iconst(IrType::I32, rhs);
+        f.binary(op, x, c)
+    }
+}
+
+#[test]
+fn identities_return_the_operand() {
+    for (op, rhs) in [
+        (BinOp::Add, 0),
+        (BinOp::Sub, 0),
+        (BinOp::Mul, 1),
+        (BinOp::SDiv, 1),
+        (BinOp::And, -1),
+        (BinOp::Shl, 0),
+    ] {
+        let (instrs, term) = simplify(with_const(op, rhs));
+        assert!(instrs.is_empty(), "{op:?} {rhs}: {instrs:?}");
+        assert_eq!(term, Terminator::Ret(Some(Value(0))), "{op:?} {rhs}");
+    }
+}
+
+#[test]
+fn constant_on_the_left_is_also_simplified() {
+    let (instrs, term) = simplify(|f, x| {
+        let one = f.iconst(IrType::I32, 1);
+        f.binary(BinOp::Mul, one, x)
+    });
+    assert!(instrs.is_empty(), "{instrs:?}");
+    assert_eq!(term, Terminator::Ret(Some(Value(0))));
+}
+
+#[test]
+fn annihilators_produce_zero() {
+    for (op, rhs) in [(BinOp::Mul, 0), (BinOp::And, 0), (BinOp::SRem, 1)] {
+        let (instrs, _) = simplify(with_const(op, rhs));
+        assert_eq!(
+            instrs,
+            [Instr::Const {
+                dst: Value(2),
+                value: 0
+            }],
+            "{op:?}"
+        );
+    }
+}
+
+#[test]
+fn self_subtraction_is_zero() {
+    let (instrs, _) = simplify(|f, x| f.binary(BinOp::Sub, x, x));
+    assert_eq!(
+        instrs,
+        [Instr::Const {
+            dst: Value(1),
+            value: 0
+        }]
+    );
+}
+
+#[test]
+fn double_negation_cancels() {
+    let (instrs, term) = simplify(|f, x| {
+        let zero = f.iconst(IrType::I32, 0);
+        let neg = f.binary(BinOp::Sub, zero, x);
+        f.binary(BinOp::Sub, zero, neg)
+    });
+    assert!(instrs.is_empty(), "{instrs:?}");
+    assert_eq!(term, Terminator::Ret(Some(Value(0))));
+}
+
+#[test]
+fn single_negation_is_kept() {
+    let (instrs, _) = simplify(|f, x| {
+        let zero = f.iconst(IrType::I32, 0);
+        f.binary(BinOp::Sub, zero, x)
+    });
+    assert!(instrs
+        .iter()
+        .any(|i| matches!(i, Instr::Binary { op: BinOp::Sub, .. })));
+}
+
+#[test]
+fn simplifies_source_identities() {
+    let source = "int f() { return 5; } int main() { return f() * 1 + 0 + f() * 0; }";
+    let asm = driver::compile(
+        source,
+        &Options {
+            opt_level: OptLevel::O1,
+            ..Options::default()
+        },
+    )
+    .unwrap()
+    .assembly;
+    assert!(!asm.contains("mul"), "{asm}");
+    assert!(!asm.contains("add"), "{asm}");
+    assert_eq!(asm.matches("    bl _f\n").count(), 2, "{asm}");
+}
@@ -0,0 +1,53 @@
+mod common;
+
+use rcc::driver::Options;
+
+use common::{check_fixture, compile};
+
+#[test]
+fn sizeof_gives_the_size_of_types_and_expressions() {
+    check_fixture("sizeof");
+}
+
+#[test]
+fn sizeof_does_not_evaluate_its_operand() {
+    check_fixture("sizeof_unevaluated");
+    let ir = compile(
+        "int f(); int main() { return sizeof f(); }",
+        &Options::default(),
+    )
+    .unwrap()
+    .ir
+    .to_string();
+    assert!(!ir.contains("call"), "{ir}");
+    assert!(ir.contains("= const i32 4\n"), "{ir}");
+}
+
+#[test]
+fn sizeof_operands_are_checked() {
+    let cases = [
+        (
+            "int main() { return sizeof(void); }",
+            "1:21: invalid application of `sizeof` to incomplete type `void`",
+        ),
+        (
+            "void f(); int main() { return sizeof f(); }",
+            "1:31: invalid application of `sizeof` to incomplete type `void`",
+        ),
+        (
+            "int main() { return sizeof(int[1000000000]); }",
+            "1:21: size of `int[1000000000]` does not fit in `int`",
+        ),
+        (
+            "int main() { return sizeof y; }",
+            "1:28: use of undeclared identifier `y`",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(
+            compile(source, &Options::default()).unwrap_err(),
+            expected,
+            "{source}"
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use rcc::codegen::Target;
+use rcc::driver::{self, Options};
+use rcc::json::Value;
+use rcc::lexer::Position;
+use rcc::opt::OptLevel;
+use rcc::source_map;
+
+const SOURCE: &str = "int zeta() { return 1; }\nint main() {\n    return zeta();\n}\n";
+
+fn options(target: Target, intel_syntax: bool) -> Options {
+    Options {
+        target,
+        intel_syntax,
+        source_map: true,
+        ..Options::default()
+    }
+}
+
+#[test]
+fn every_line_of_assembly_has_an_entry() {
+    for target in [Target::X86_64_LINUX, Target::AARCH64_APPLE] {
+        for intel_syntax in [false, target == Target::X86_64_LINUX] {
+            let artifacts = driver::compile(SOURCE, &options(target, intel_syntax)).unwrap();
+            let lines: Vec<&str> = artifacts.assembly.lines().collect();
+            assert_eq!(artifacts.positions.len(), lines.len(), "{target:?}");
+            // Instructions have positions; labels and directives don't.
+            for (line, pos) in lines.iter().zip(&artifacts.positions) {
+                if line.ends_with(':') || line.trim_start().starts_with('.') || line.is_empty() {
+                    assert_eq!(*pos, None, "{line}");
+                }
+            }
+            // The call is part of the return on line 3.
+            let call = lines
+                .iter()
+                .position(|line| {
+                    let line = line.trim_start();
+                    line.starts_with("call ") || line.starts_with("bl ")
+                })
+                .unwrap();
+            assert_eq!(
+                artifacts.positions[call],
+                Some(Position {
+                    line: 3,
+                    column: 12
+                }),
+                "{target:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn positions_do_not_change_the_code() {
+    for opt_level in [OptLevel::O0, OptLevel::O2] {
+        let plain = Options {
+            opt_level,
+            ..Options::default()
+        };
+        let mapped = Options {
+            source_map: true,
+            ..plain.clone()
+        };
+        let without = driver::compile(SOURCE, &plain).unwrap();
+        let with = driver::compile(SOURCE, &mapped).unwrap();
+        assert_eq!(without.assembly, with.assembly);
+        assert!(without.positions.is_empty());
+    }
+}
+
+#[test]
+fn the_map_is_json() {
+    let artifacts = driver::compile(SOURCE, &options(Target::X86_64_LINUX, false)).unwrap();
+    let json = source_map::to_json("src/t.c", &artifacts.positions);
+    let map = Value::parse(&json).unwrap();
+    assert_eq!(map.get("file").and_then(Value::as_str), Some("src/t.c"));
+    let entries = map.get("lines").and_then(Value::as_array).unwrap();
+    let mapped = artifacts.positions.iter().flatten().count();
+    assert_eq!(entries.len(), mapped);
+    let first = &entries[0];
+    let asm = first.get("asm").and_then(Value::as_u64).unwrap() as usize;
+    assert_eq!(
+        artifacts.positions[asm - 1].map(|pos| u64::from(pos.line)),
+        first.get("line").and_then(Value::as_u64)
+    );
+}
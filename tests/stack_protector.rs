@@ -0,0 +1,103 @@
+mod common;
+
+use rcc::codegen::aarch64::Aarch64;
+use rcc::codegen::x86_64::X86_64;
+use rcc::codegen::{Backend, CodeGenerator};
+use rcc::ir::{FunctionBuilder, IrType, Module};
+
+use common::{assert_assembles, run_linked};
+
+/// `main` allocates a 16-byte buffer and has `fill`, a C function, write
+/// `bytes` bytes into it.
+fn filling(bytes: i64) -> Module {
+    let mut b = FunctionBuilder::new("main", Some(IrType::I32));
+    let size = b.iconst(IrType::I32, 16);
+    let buffer = b.alloca(size);
+    let bytes = b.iconst(IrType::I32, bytes);
+    b.call("fill", vec![buffer, bytes], None);
+    let zero = b.iconst(IrType::I32, 0);
+    b.ret(Some(zero));
+    let mut module = Module::default();
+    module.functions.push(b.finish());
+    module
+}
+
+fn generate(backend: &dyn Backend, module: &Module) -> String {
+    CodeGenerator::new()
+        .with_backend(backend)
+        .with_register_allocation(true)
+        .with_stack_protector(true)
+        .generate(module)
+}
+
+#[test]
+fn functions_without_buffers_are_not_protected() {
+    let mut b = FunctionBuilder::new("main", Some(IrType::I32));
+    let zero = b.iconst(IrType::I32, 0);
+    b.ret(Some(zero));
+    let mut module = Module::default();
+    module.functions.push(b.finish());
+    let asm = generate(&Aarch64::APPLE, &module);
+    assert!(!asm.contains("stack_chk"), "{asm}");
+}
+
+#[test]
+fn aarch64_checks_the_canary_before_returning() {
+    let asm = generate(&Aarch64::APPLE, &filling(16));
+    assert!(
+        asm.contains(
+            "    adrp x16, ___stack_chk_guard@GOTPAGE\n    ldr x16, [x16, ___stack_chk_guard@GOTPAGEOFF]\n    ldr x16, [x16, #0]\n    str x16, [x29, #-8]\n"
+        ),
+        "{asm}"
+    );
+    assert!(
+        asm.contains("    ldr x17, [x29, #-8]\n    cmp x16, x17\n    b.ne "),
+        "{asm}"
+    );
+    assert!(asm.contains("    bl ___stack_chk_fail\n"), "{asm}");
+    assert_assembles("arm64-apple-macos", &asm);
+
+    let asm = generate(&Aarch64::LINUX, &filling(16));
+    assert!(asm.contains(":got:__stack_chk_guard"), "{asm}");
+    assert_assembles("aarch64-linux-gnu", &asm);
+}
+
+#[test]
+fn x86_darwin_reads_the_exported_guard() {
+    let asm = generate(&X86_64::APPLE, &filling(16));
+    assert!(
+        asm.contains("    movq ___stack_chk_guard@GOTPCREL(%rip), %rax\n    movq 0(%rax), %rax\n"),
+        "{asm}"
+    );
+    assert!(asm.contains("    call ___stack_chk_fail\n"), "{asm}");
+    assert_assembles("x86_64-apple-macos", &asm);
+}
+
+const FILL: &str = r#"
+#include <string.h>
+
+void fill(char *buffer, int bytes) {
+    memset(buffer, 'x', bytes);
+}
+"#;
+
+#[test]
+fn x86_linux_catches_a_smashed_stack() {
+    let asm = generate(&X86_64::LINUX, &filling(16));
+    assert!(
+        asm.contains("    movq %fs:40, %rax\n    movq %rax, -8(%rbp)\n"),
+        "{asm}"
+    );
+    assert!(
+        asm.contains("    movq %fs:40, %rcx\n    cmpq -8(%rbp), %rcx\n    jne "),
+        "{asm}"
+    );
+    if let Some(code) = run_linked("fits", &asm, &[("fill.c", FILL)]) {
+        assert_eq!(code, Some(0));
+    }
+
+    let asm = generate(&X86_64::LINUX, &filling(64));
+    if let Some(code) = run_linked("smashed", &asm, &[("fill.c", FILL)]) {
+        assert_eq!(code, None, "__stack_chk_fail should abort");
+    }
+}
@@ -0,0 +1,122 @@
+use rcc::driver::{self, Options};
+use rcc::ir::interp::Interpreter;
+use rcc::lexer::Position;
+use rcc::standard::{Standard, Version};
+
+fn compile<'a>(source: &'a str, std: &str) -> Result<driver::Artifacts<'a>, String> {
+    driver::compile(
+        source,
+        &Options {
+            standard: Standard::parse(std).unwrap(),
+            ..Options::default()
+        },
+    )
+    .map_err(|err| err.to_string())
+}
+
+#[test]
+fn parses_standard_names() {
+    assert_eq!(
+        Standard::parse("c99"),
+        Some(Standard {
+            version: Version::C99,
+            gnu: false
+        })
+    );
+    assert_eq!(Standard::parse("gnu18").unwrap().to_string(), "gnu17");
+    assert_eq!(Standard::parse("c90").unwrap().version, Version::C89);
+    assert_eq!(Standard::parse("c2x").unwrap().version, Version::C23);
+    assert_eq!(Standard::parse("c98"), None);
+    assert_eq!(Standard::parse("ansi"), None);
+    assert_eq!(Standard::default().to_string(), "gnu17");
+}
+
+#[test]
+fn c89_allows_implicit_int_and_implicit_declarations() {
+    let source = "main() { return later() + 1; }\nint later() { return 41; }\n";
+    let artifacts = compile(source, "c89").unwrap();
+    assert_eq!(
+        Interpreter::new(&artifacts.ir).call("main", &[]),
+        Ok(Some(42))
+    );
+
+    let err = compile(source, "c99").unwrap_err();
+    assert!(err.contains("implicit `int` was removed in C99"), "{err}");
+    let err = compile("int main() { return nowhere(); }", "c11").unwrap_err();
+    assert!(
+        err.contains("call to undeclared function `nowhere`"),
+        "{err}"
+    );
+}
+
+#[test]
+fn calls_above_a_definition_use_its_signature_in_c89() {
+    let err = compile(
+        "int main() { return later(); }\nvoid later() { return; }",
+        "c89",
+    )
+    .unwrap_err();
+    assert_eq!(err, "1:21: expected `int` but found `void`");
+}
+
+#[test]
+fn plain_asm_is_a_gnu_keyword() {
+    let source = "int main() { asm(\"nop\"); return 0; }";
+    compile(source, "gnu99").unwrap();
+    let err = compile(source, "c99").unwrap_err();
+    assert!(
+        err.contains("`asm` is a GNU extension; use `__asm__` in ISO C"),
+        "{err}"
+    );
+    compile("int main() { __asm__ volatile(\"nop\"); return 0; }", "c99").unwrap();
+}
+
+#[test]
+fn features_name_the_standard_they_need() {
+    let pos = Position::START;
+    let c89 = Standard::parse("c89").unwrap();
+    let err = c89
+        .require(Version::C99, "a `//` comment", pos)
+        .unwrap_err();
+    assert_eq!(err.message, "a `//` comment is only valid in C99 and later");
+    assert!(Standard::default()
+        .require(Version::C11, "`_Atomic`", pos)
+        .is_ok());
+}
+
+#[test]
+fn empty_parentheses_leave_the_parameters_out_until_c23() {
+    let source =
+        "int f();\nint main() { return f(40, 2); }\nint f(int a, int b) { return a + b; }\n";
+    for std in ["c89", "gnu17"] {
+        let artifacts = compile(source, std).unwrap();
+        assert_eq!(
+            Interpreter::new(&artifacts.ir).call("main", &[]),
+            Ok(Some(42)),
+            "{std}"
+        );
+    }
+    // A declaration with parameters is checked against one without them,
+    // and what it says is kept.
+    let err = compile(
+        "int f(int a);\nint f();\nint main() { return f(); }",
+        "gnu17",
+    )
+    .unwrap_err();
+    assert_eq!(err, "3:21: `f` takes 1 argument, but 0 were given");
+    for conflicting in [
+        "int f();\nlong f(int a);\nint main() { return 0; }",
+        "int f();\nint f(char c);\nint main() { return 0; }",
+        "int f();\nint f(int a, ...);\nint main() { return 0; }",
+    ] {
+        let err = compile(conflicting, "gnu17").unwrap_err();
+        assert!(err.starts_with("2:"), "{conflicting}: {err}");
+        assert!(err.ends_with("conflicting types for `f`"), "{err}");
+    }
+
+    // In C23, `()` is `(void)`.
+    let err = compile(source, "c23").unwrap_err();
+    assert_eq!(err, "2:21: `f` takes 0 arguments, but 2 were given");
+    let err = compile("int f() { return 0; }\nint main() { return f(1); }", "c23").unwrap_err();
+    assert_eq!(err, "2:21: `f` takes 0 arguments, but 1 was given");
+}
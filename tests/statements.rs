@@ -0,0 +1,49 @@
+mod common;
+
+use rcc::ast::Statement;
+use rcc::driver::Options;
+use rcc::pretty;
+
+use common::{check_fixture, compile, fixture};
+
+/// Empty statements where a statement may go, and loops that do all their
+/// work in their clauses.
+#[test]
+fn empty_statements_do_nothing() {
+    check_fixture("empty_statements");
+    let source = fixture("empty_statements").source;
+    let program = compile(&source, &Options::default()).unwrap().program;
+    let body = program.functions[0].body.as_deref().unwrap();
+    assert!(matches!(body[2], Statement::Empty { .. }), "{body:?}");
+    assert_eq!(pretty::emit(&program), format!("{}\n", source.trim_start()));
+}
+
+#[test]
+fn expression_statements_discard_their_values() {
+    check_fixture("expression_statements");
+}
+
+#[test]
+fn expression_statements_are_still_checked() {
+    let cases = [
+        (
+            "int main() { x; return 0; }",
+            "1:14: use of undeclared identifier `x`",
+        ),
+        (
+            "void f(void); int main() { f() + 1; return 0; }",
+            "1:28: expected `int` but found `void`",
+        ),
+        (
+            "int main() { 1 = 2; return 0; }",
+            "1:14: expression is not assignable",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(
+            compile(source, &Options::default()).unwrap_err(),
+            expected,
+            "{source}"
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use rcc::codegen::Target;
+use rcc::driver::{self, Options};
+use rcc::opt::OptLevel;
+use rcc::stats::Stats;
+
+const SOURCE: &str = "int helper() { return 2; }\nint main() { return helper() * 3 + 1; }\n";
+
+fn stats(options: &Options) -> Stats {
+    let artifacts = driver::compile(SOURCE, options).expect("program should compile");
+    Stats::collect(&artifacts, options)
+}
+
+#[test]
+fn counts_tokens_and_syntax_tree_nodes() {
+    let stats = stats(&Options::default());
+    // Including the end of input.
+    assert_eq!(stats.tokens, 25);
+    // `helper`: the function, its return and the literal; `main`: the
+    // function, its return, two operators, the call and two literals.
+    assert_eq!(stats.ast_nodes, 10);
+    let names: Vec<&str> = stats.functions.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, ["helper", "main"]);
+}
+
+#[test]
+fn unoptimized_code_spills_every_value() {
+    for target in [Target::X86_64_LINUX, Target::AARCH64_APPLE] {
+        let stats = stats(&Options {
+            target,
+            ..Options::default()
+        });
+        let main = &stats.functions[1];
+        assert_eq!(main.ir_instructions, 5, "{target:?}");
+        assert_eq!(main.blocks, 1, "{target:?}");
+        assert_eq!(main.spills, 3, "{target:?}");
+        assert!(main.instructions > main.ir_instructions, "{target:?}");
+    }
+}
+
+#[test]
+fn optimization_shrinks_the_counts() {
+    for target in [Target::X86_64_LINUX, Target::AARCH64_APPLE] {
+        let unoptimized = stats(&Options {
+            target,
+            ..Options::default()
+        });
+        let optimized = stats(&Options {
+            target,
+            opt_level: OptLevel::O2,
+            ..Options::default()
+        });
+        let main = &optimized.functions[1];
+        assert_eq!(main.spills, 0, "{target:?}");
+        assert!(
+            main.instructions < unoptimized.functions[1].instructions,
+            "{target:?}"
+        );
+        assert!(main.instructions > 0, "{target:?}");
+    }
+}
+
+#[test]
+fn prints_a_row_per_function_and_a_total() {
+    let text = stats(&Options::default()).to_string();
+    assert!(text.starts_with("tokens:    25\nAST nodes: 10\n"), "{text}");
+    assert!(
+        text.contains("\nhelper                       1       1       0 "),
+        "{text}"
+    );
+    assert!(
+        text.contains("\ntotal                        6       2       3 "),
+        "{text}"
+    );
+}
@@ -0,0 +1,174 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use rcc::codegen::aarch64::Aarch64;
+use rcc::codegen::x86_64::X86_64;
+use rcc::codegen::{Backend, CodeGenerator, Target};
+use rcc::driver::{self, Options};
+use rcc::ir::verify::verify_module;
+use rcc::ir::{FunctionBuilder, IrType, Module, StringId};
+
+use common::assert_assembles;
+
+/// `main` passes two pool strings to `puts` and returns 0.
+fn greeting() -> Module {
+    let mut module = Module::default();
+    let hello = module.strings.intern(b"hello, \"pool\"");
+    let again = module.strings.intern(b"tab\there");
+    assert_eq!(module.strings.intern(b"hello, \"pool\""), hello);
+
+    let mut b = FunctionBuilder::new("main", Some(IrType::I32));
+    for string in [hello, again, hello] {
+        let addr = b.string_addr(string);
+        b.call("puts", vec![addr], Some(IrType::I32));
+    }
+    let zero = b.iconst(IrType::I32, 0);
+    b.ret(Some(zero));
+    module.functions.push(b.finish());
+    module
+}
+
+fn generate(backend: &dyn Backend) -> String {
+    CodeGenerator::new()
+        .with_backend(backend)
+        .generate(&greeting())
+}
+
+#[test]
+fn interning_deduplicates() {
+    let module = greeting();
+    let strings: Vec<_> = module.strings.iter().collect();
+    assert_eq!(
+        strings,
+        [
+            (StringId(0), &b"hello, \"pool\""[..]),
+            (StringId(1), &b"tab\there"[..])
+        ]
+    );
+    assert_eq!(verify_module(&module), Ok(()));
+}
+
+#[test]
+fn prints_strings_before_functions() {
+    let printed = greeting().to_string();
+    assert!(
+        printed
+            .starts_with("str0 = string \"hello, \\\"pool\\\"\"\nstr1 = string \"tab\\there\"\n\n"),
+        "{printed}"
+    );
+    assert!(printed.contains("= addr ptr str0\n"), "{printed}");
+}
+
+#[test]
+fn rejects_strings_missing_from_the_pool() {
+    let mut module = greeting();
+    module.strings = Default::default();
+    let error = verify_module(&module).unwrap_err();
+    assert_eq!(error.message, "str0 is not in the string pool");
+}
+
+#[test]
+fn macho_addresses_strings_by_page() {
+    let asm = generate(&Aarch64::APPLE);
+    assert!(
+        asm.contains("    .section __TEXT,__cstring,cstring_literals\nl_.str.0:\n    .asciz \"hello, \\\"pool\\\"\"\nl_.str.1:\n    .asciz \"tab\\there\"\n"),
+        "{asm}"
+    );
+    assert!(
+        asm.contains("    adrp x8, l_.str.0@PAGE\n    add x8, x8, l_.str.0@PAGEOFF\n    str x8, [x29, #-8]\n"),
+        "{asm}"
+    );
+    assert_eq!(asm.matches("l_.str.0:").count(), 1, "{asm}");
+    assert_assembles("arm64-apple-macos", &asm);
+}
+
+#[test]
+fn elf_addresses_strings_with_lo12() {
+    let asm = generate(&Aarch64::LINUX);
+    assert!(
+        asm.contains("    .section .rodata.str1.1,\"aMS\",%progbits,1\n.L.str.0:\n"),
+        "{asm}"
+    );
+    assert!(
+        asm.contains("    adrp x8, .L.str.0\n    add x8, x8, :lo12:.L.str.0\n"),
+        "{asm}"
+    );
+    assert_assembles("aarch64-linux-gnu", &asm);
+}
+
+#[test]
+fn x86_program_prints_the_strings() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let asm = generate(&X86_64::LINUX);
+    assert!(
+        asm.contains("leaq .L.str.1(%rip), %rax\n    movq %rax, -"),
+        "{asm}"
+    );
+    let dir = std::env::temp_dir().join(format!("rcc-strings-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (source, exe) = (dir.join("strings.s"), dir.join("strings"));
+    fs::write(&source, asm).unwrap();
+    let Ok(linked) = Command::new("cc").arg(&source).arg("-o").arg(&exe).status() else {
+        return;
+    };
+    assert!(linked.success());
+    let output = Command::new(&exe).output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "hello, \"pool\"\ntab\there\nhello, \"pool\"\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Passes two string literals, one of them split in two, to an asm
+/// statement.
+const LITERALS: &str = r#"int main() {
+    __asm__("" : : "r"("caf\xc3\xa9\n" "olé"));
+    __asm__("" : : "r"("caf\303\251\nolé"));
+    return 0;
+}"#;
+
+#[test]
+fn literals_are_joined_unescaped_and_pooled() {
+    let ir = driver::compile(LITERALS, &Options::default()).unwrap().ir;
+    let strings: Vec<_> = ir.strings.iter().collect();
+    assert_eq!(strings, [(StringId(0), "café\nolé".as_bytes())]);
+    assert_eq!(
+        ir.to_string().matches("= addr ptr str0\n").count(),
+        2,
+        "{ir}"
+    );
+}
+
+#[test]
+fn literals_land_in_the_cstring_sections() {
+    for (target, section) in [
+        (Target::X86_64_LINUX, ".rodata.str1.1"),
+        (Target::AARCH64_APPLE, "__TEXT,__cstring"),
+    ] {
+        let asm = driver::compile(
+            LITERALS,
+            &Options {
+                target,
+                ..Options::default()
+            },
+        )
+        .unwrap()
+        .assembly;
+        assert!(asm.contains(section), "{asm}");
+        assert_eq!(asm.matches(".asciz").count(), 1, "{asm}");
+    }
+}
+
+#[test]
+fn a_literal_is_a_char_pointer() {
+    let err = driver::compile("int main() { return \"x\"; }", &Options::default())
+        .unwrap_err()
+        .to_string();
+    assert_eq!(err, "1:21: expected `int` but found `char *`");
+}
@@ -0,0 +1,337 @@
+mod common;
+
+use rcc::ast_interp;
+use rcc::codegen::aarch64::Aarch64;
+use rcc::codegen::x86_64::X86_64;
+use rcc::codegen::CodeGenerator;
+use rcc::driver::{Options, Stage};
+use rcc::ir::interp::Interpreter;
+use rcc::ir::verify::verify_module;
+use rcc::ir::{BinOp, FunctionBuilder, IrType, Module, Terminator};
+use rcc::opt::{self, OptLevel};
+
+use common::{assert_assembles, compile, run};
+
+/// `f(x)` returns `10 + i` for the `i`th of `cases` and 1 otherwise; `main`
+/// folds `f` over `inputs` into a checksum.
+fn switch_module(cases: &[i64], inputs: &[i64]) -> Module {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let targets: Vec<_> = cases.iter().map(|_| f.create_block()).collect();
+    let default = f.create_block();
+    let arms = cases.iter().copied().zip(targets.iter().copied()).collect();
+    f.switch(x, arms, default);
+    for (i, &block) in targets.iter().enumerate() {
+        f.switch_to(block);
+        let result = f.iconst(IrType::I32, 10 + i as i64);
+        f.ret(Some(result));
+    }
+    f.switch_to(default);
+    let one = f.iconst(IrType::I32, 1);
+    f.ret(Some(one));
+
+    let mut main = FunctionBuilder::new("main", Some(IrType::I32));
+    let acc = main.stack_slot(IrType::I32);
+    let zero = main.iconst(IrType::I32, 0);
+    main.store(acc, zero);
+    for &input in inputs {
+        let arg = main.iconst(IrType::I32, input);
+        let result = main.call("f", vec![arg], Some(IrType::I32)).unwrap();
+        let sum = main.load(acc);
+        let three = main.iconst(IrType::I32, 3);
+        let scaled = main.binary(BinOp::Mul, sum, three);
+        let sum = main.binary(BinOp::Add, scaled, result);
+        main.store(acc, sum);
+    }
+    let result = main.load(acc);
+    main.ret(Some(result));
+
+    let module = Module {
+        functions: vec![f.finish(), main.finish()],
+        ..Module::default()
+    };
+    verify_module(&module).unwrap();
+    module
+}
+
+fn aarch64(module: &Module) -> String {
+    CodeGenerator::new()
+        .with_backend(&Aarch64::LINUX)
+        .generate(module)
+}
+
+const DENSE: [i64; 6] = [3, 4, 5, 7, 8, 9];
+const SPARSE: [i64; 6] = [-1000, 1, 64, 4096, 70000, 1 << 30];
+
+#[test]
+fn dense_switches_use_a_bounds_checked_jump_table() {
+    let asm = aarch64(&switch_module(&DENSE, &[]));
+    assert!(
+        asm.contains("    sub w8, w8, #3\n    cmp w8, #6\n    b.hi .Lf_7\n    adr x9, .Lf_8\n"),
+        "{asm}"
+    );
+    // 6 is missing and goes to the default block.
+    assert!(
+        asm.contains(".Lf_8:\n    .long .Lf_1-.Lf_8\n    .long .Lf_2-.Lf_8\n    .long .Lf_3-.Lf_8\n    .long .Lf_7-.Lf_8\n"),
+        "{asm}"
+    );
+    assert_assembles("aarch64-linux-gnu", &asm);
+}
+
+#[test]
+fn sparse_switches_use_a_comparison_tree() {
+    let asm = aarch64(&switch_module(&SPARSE, &[]));
+    assert!(!asm.contains("adr"), "{asm}");
+    // The tree splits at the fourth case and tests each half linearly.
+    assert!(
+        asm.contains("    mov w9, #4096\n    cmp w8, w9\n    b.ge .Lf_8\n"),
+        "{asm}"
+    );
+    assert!(asm.contains("    cmn w8, #1000\n    b.eq .Lf_1\n"), "{asm}");
+    assert!(asm.contains("    movk w9, #0x1, lsl #16\n"), "{asm}");
+    assert_eq!(asm.matches("b.eq").count(), SPARSE.len(), "{asm}");
+    assert_assembles("aarch64-linux-gnu", &asm);
+}
+
+#[test]
+fn small_switches_compare_each_case() {
+    let asm = aarch64(&switch_module(&[1, 2, 3], &[]));
+    assert!(!asm.contains("adr") && !asm.contains("b.ge"), "{asm}");
+    assert_eq!(asm.matches("b.eq").count(), 3, "{asm}");
+}
+
+#[test]
+fn switches_match_the_interpreter_on_x86_64() {
+    let inputs: Vec<i64> = (-3..12)
+        .chain(SPARSE)
+        .chain([i64::from(i32::MIN), 4095, 69999])
+        .collect();
+    for (name, cases) in [
+        ("dense", &DENSE[..]),
+        ("sparse", &SPARSE[..]),
+        ("negative", &[-5, -4, -3, -2, -1][..]),
+    ] {
+        let module = switch_module(cases, &inputs);
+        let expected = Interpreter::new(&module)
+            .call("main", &[])
+            .unwrap()
+            .unwrap();
+        for allocate in [false, true] {
+            let asm = CodeGenerator::new()
+                .with_backend(&X86_64::LINUX)
+                .with_register_allocation(allocate)
+                .generate(&module);
+            let Some(status) = run(&format!("{name}_{allocate}"), &asm) else {
+                return;
+            };
+            assert_eq!(i64::from(status), expected & 0xff, "{name}:\n{asm}");
+        }
+    }
+}
+
+#[test]
+fn prints_and_folds_switches() {
+    let module = switch_module(&[1, 2], &[]);
+    let printed = module.to_string();
+    assert!(
+        printed.contains("    switch %0, bb3 [1: bb1, 2: bb2]\n"),
+        "{printed}"
+    );
+
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let two = f.iconst(IrType::I32, 2);
+    let (one_block, two_block) = (f.create_block(), f.create_block());
+    f.switch(two, vec![(1, one_block), (2, two_block)], one_block);
+    f.switch_to(one_block);
+    f.ret(Some(two));
+    f.switch_to(two_block);
+    let seven = f.iconst(IrType::I32, 7);
+    f.ret(Some(seven));
+    let mut module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+    opt::optimize(&mut module, OptLevel::O1);
+    let func = &module.functions[0];
+    assert_eq!(func.blocks.len(), 1, "{module}");
+    assert!(
+        matches!(func.blocks[0].term, Terminator::Ret(Some(_))),
+        "{module}"
+    );
+}
+
+#[test]
+fn rejects_duplicate_cases() {
+    let mut f = FunctionBuilder::new("f", None);
+    let x = f.param(IrType::I32);
+    let target = f.create_block();
+    f.switch(x, vec![(1, target), (1, target)], target);
+    f.switch_to(target);
+    f.ret(None);
+    let module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+    let err = verify_module(&module).unwrap_err();
+    assert!(err.to_string().contains("duplicate switch case 1"), "{err}");
+}
+
+/// `main`'s result from the IR at `opt_level`, checked against the
+/// syntax tree's.
+fn run_main(source: &str, opt_level: OptLevel) -> Option<i64> {
+    let options = Options {
+        opt_level,
+        ..Options::default()
+    };
+    let ir = compile(source, &options)
+        .expect("program should compile")
+        .ir;
+    let result = Interpreter::new(&ir).call("main", &[]).unwrap();
+    let options = Options {
+        last_stage: Stage::Analyze,
+        ..Options::default()
+    };
+    let program = compile(source, &options).unwrap().program;
+    let expected = ast_interp::Interpreter::new(&program).call("main", &[]);
+    assert_eq!(Ok(result), expected, "{source}");
+    result
+}
+
+/// Counts how many of 0 to 5 reach each arm of a `switch`, weighting the
+/// arms so that the sum tells which ran.
+const FALLTHROUGH: &str = "int classify(int x) {
+    int n = 0;
+    switch (x) {
+    case 0:
+        n += 1;
+    case 1:
+        n += 10;
+        break;
+    case 2:
+    case 3:
+        n += 100;
+        break;
+    default:
+        n += 1000;
+    }
+    return n;
+}
+int main() {
+    int sum = 0;
+    for (int i = 0; i < 6; i++) sum += classify(i);
+    return sum % 251;
+}";
+
+#[test]
+fn switch_statements_run_the_matching_case() {
+    let cases = [
+        // 11 + 10 + 100 + 100 + 1000 + 1000, modulo 251.
+        (FALLTHROUGH, 2221 % 251),
+        ("int main() { switch (3) { case 1: return 1; } return 2; }", 2),
+        ("int main() { switch (3) default: return 4; }", 4),
+        ("int main() { switch (5) { default: return 4; case 5: return 5; } }", 5),
+        (
+            "enum color { RED = 1, GREEN = RED + 3 }; int main() { enum color c = GREEN; switch (c) { case RED: return 1; case GREEN: return 2; } return 3; }",
+            2,
+        ),
+        (
+            "int main() { char c = 'b'; switch (c) { case 'a': return 1; case 'b': return 2; } return 3; }",
+            2,
+        ),
+        (
+            "int main() { unsigned u = -1; switch (u) { case -1: return 1; } return 2; }",
+            1,
+        ),
+        (
+            "int main() { long big = 5000000000; switch (big) { case 705032704: return 1; case 5000000000: return 2; } return 3; }",
+            2,
+        ),
+        // `break` leaves the innermost loop or `switch`.
+        (
+            "int main() { int n = 0; for (int i = 0; i < 10; i++) { switch (i % 3) { case 0: n++; break; case 1: break; } while (i == 7) break; n += 10; } return n; }",
+            104,
+        ),
+        (
+            "int main() { int i = 0; for (;;) { switch (++i) { case 4: goto out; } } out: do break; while (1); return i; }",
+            4,
+        ),
+        (
+            "int main() { int n = 0; switch (1) { case 1: switch (2) { case 1: n = 10; break; default: n = 20; } n++; } return n; }",
+            21,
+        ),
+        // A case can be inside a loop in the body, which it jumps into.
+        (
+            "int main() { int n = 0; int i = 2; switch (1) { while (i--) { case 1: n += 5; } } return n; }",
+            15,
+        ),
+    ];
+    for (source, expected) in cases {
+        for level in [OptLevel::O0, OptLevel::O2] {
+            assert_eq!(
+                run_main(source, level),
+                Some(expected),
+                "{source} at {level:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn switch_statements_are_checked() {
+    let cases = [
+        (
+            "int main() { break; }",
+            "1:14: `break` statement not in loop or switch statement",
+        ),
+        (
+            "int main() { case 1: return 0; }",
+            "1:14: `case` statement not in switch statement",
+        ),
+        (
+            "int main() { default: return 0; }",
+            "1:14: `default` statement not in switch statement",
+        ),
+        (
+            "int main() { int x = 1; switch (x) { case x: return 0; } }",
+            "1:38: `case` value is not an integer constant",
+        ),
+        (
+            "enum { A = 1 }; int main() { switch (1) { case 1: case A: return 0; } }",
+            "1:51: duplicate case value `1`",
+        ),
+        (
+            "int main() { switch (1) { default: default: return 0; } }",
+            "1:36: multiple default labels in one switch",
+        ),
+        (
+            "int main() { int *p = 0; switch (p) { } return 0; }",
+            "1:34: expected `int` but found `int *`",
+        ),
+        (
+            "int main() { switch (1) { case 1 return 0; } }",
+            "1:34: expected `:`, found `return`",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(
+            compile(source, &Options::default()).unwrap_err(),
+            expected,
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn switch_statements_lower_to_a_switch_terminator() {
+    let ir = compile(FALLTHROUGH, &Options::default()).unwrap().ir;
+    let text = ir.to_string();
+    assert_eq!(text.matches("    switch ").count(), 1, "{text}");
+    assert!(text.contains(" [0: bb"), "{text}");
+    let asm = CodeGenerator::new()
+        .with_backend(&X86_64::LINUX)
+        .generate(&ir);
+    let Some(status) = run("fallthrough", &asm) else {
+        return;
+    };
+    assert_eq!(i64::from(status), 2221 % 251);
+}
@@ -0,0 +1,47 @@
+use rcc::lexer::Lexer;
+use rcc::parser::Parser;
+use rcc::tags::{self, Tag};
+
+const SOURCE: &str =
+    "int zeta() { return 1; }\n\nvoid alpha() { return; }\nint main() {\n    return zeta();\n}\n";
+
+fn program() -> rcc::ast::Program {
+    Parser::new(Lexer::new(SOURCE).lex().unwrap())
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn definitions_are_sorted_by_name() {
+    let names: Vec<(String, u32)> = tags::collect(&program())
+        .into_iter()
+        .map(|tag| (tag.name, tag.line))
+        .collect();
+    assert_eq!(
+        names,
+        [
+            ("alpha".to_string(), 3),
+            ("main".to_string(), 4),
+            ("zeta".to_string(), 1)
+        ]
+    );
+    assert_eq!(
+        tags::collect(&program())[0],
+        Tag {
+            name: "alpha".to_string(),
+            line: 3,
+            kind: 'f',
+            type_name: "void".to_string(),
+        }
+    );
+}
+
+#[test]
+fn the_file_is_in_extended_ctags_format() {
+    let file = tags::emit(&program(), "src/t.c");
+    let lines: Vec<&str> = file.lines().collect();
+    assert_eq!(lines[0], "!_TAG_FILE_FORMAT\t2\t/extended format/");
+    assert!(lines[1].starts_with("!_TAG_FILE_SORTED\t1\t"));
+    assert_eq!(lines[3], "alpha\tsrc/t.c\t3;\"\tf\ttyperef:typename:void");
+    assert_eq!(lines.len(), 6);
+}
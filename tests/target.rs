@@ -0,0 +1,145 @@
+use std::fs;
+use std::process::Command;
+
+use rcc::codegen::{Arch, ObjectFormat, Os, Target};
+use rcc::driver::{self, Options};
+
+#[test]
+fn parses_full_triples() {
+    for (triple, target) in [
+        ("aarch64-apple-darwin", Target::AARCH64_APPLE),
+        ("arm64-apple-macosx14.0.0", Target::AARCH64_APPLE),
+        ("aarch64-unknown-linux-gnu", Target::AARCH64_LINUX),
+        ("x86_64-unknown-linux-gnu", Target::X86_64_LINUX),
+        ("x86_64-pc-linux-musl", Target::X86_64_LINUX),
+        ("x86_64-apple-darwin23.1.0", Target::X86_64_APPLE),
+        ("wasm32-unknown-unknown", Target::WASM32),
+        (
+            "x86_64-unknown-none",
+            Target::new(Arch::X86_64, Os::Unknown),
+        ),
+    ] {
+        assert_eq!(Target::from_triple(triple), Some(target), "{triple}");
+    }
+}
+
+#[test]
+fn fills_in_missing_components() {
+    assert_eq!(Target::from_triple("aarch64"), Some(Target::AARCH64_APPLE));
+    assert_eq!(
+        Target::from_triple("aarch64-linux"),
+        Some(Target::AARCH64_LINUX)
+    );
+    assert_eq!(Target::from_triple("x86_64"), Some(Target::X86_64_LINUX));
+    assert_eq!(
+        Target::from_triple("x86_64-apple"),
+        Some(Target::X86_64_APPLE)
+    );
+    assert_eq!(Target::from_triple("wasm32"), Some(Target::WASM32));
+}
+
+#[test]
+fn rejects_unsupported_triples() {
+    for triple in [
+        "",
+        "riscv64-unknown-linux-gnu",
+        "x86_64-pc-windows-msvc",
+        "aarch64-linux-android",
+        "wasm32-unknown-linux",
+        "wasm32-wasi",
+    ] {
+        assert_eq!(Target::from_triple(triple), None, "{triple}");
+    }
+}
+
+#[test]
+fn canonical_triples_round_trip() {
+    for target in [
+        Target::AARCH64_APPLE,
+        Target::AARCH64_LINUX,
+        Target::X86_64_APPLE,
+        Target::X86_64_LINUX,
+        Target::WASM32,
+        Target::new(Arch::Aarch64, Os::Unknown),
+    ] {
+        assert_eq!(Target::from_triple(target.triple()), Some(target));
+        assert_eq!(target.to_string(), target.triple());
+    }
+}
+
+#[test]
+fn detects_the_host() {
+    let host = Target::host();
+    if cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        assert_eq!(host, Some(Target::X86_64_LINUX));
+    } else if cfg!(all(target_arch = "aarch64", target_vendor = "apple")) {
+        assert_eq!(host, Some(Target::AARCH64_APPLE));
+    }
+    if let Some(host) = host {
+        assert_eq!(
+            host.object_format() == ObjectFormat::MachO,
+            host.os == Os::Darwin
+        );
+    }
+}
+
+#[test]
+fn the_os_decides_symbol_naming_and_directives() {
+    let source = "int f() { return 1; } int main() { return f(); }";
+    let compile = |target| {
+        let options = Options {
+            target,
+            ..Options::default()
+        };
+        driver::compile(source, &options).unwrap().assembly
+    };
+
+    let mac = compile(Target::X86_64_APPLE);
+    assert!(mac.contains("    .globl _main\n"), "{mac}");
+    assert!(mac.contains("    call _f\n"), "{mac}");
+    assert!(!mac.contains(".type") && !mac.contains(".section"), "{mac}");
+
+    let linux = compile(Target::X86_64_LINUX);
+    assert!(linux.contains("    .type main, @function\n"), "{linux}");
+    assert!(linux.contains("    call f\n"), "{linux}");
+    assert_eq!(Target::X86_64_LINUX.arch, Arch::X86_64);
+}
+
+#[test]
+fn command_line_defaults_to_the_host() {
+    let Some(host) = Target::host() else {
+        return;
+    };
+    let dir = std::env::temp_dir().join(format!("rcc-target-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("main.c");
+    fs::write(&input, "int main() { return 0; }").unwrap();
+
+    let status = Command::new(env!("CARGO_BIN_EXE_rcc"))
+        .current_dir(&dir)
+        .arg("-S")
+        .arg(&input)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let expected = driver::compile(
+        "int main() { return 0; }",
+        &Options {
+            target: host,
+            ..Options::default()
+        },
+    )
+    .unwrap()
+    .assembly;
+    assert_eq!(fs::read_to_string(dir.join("main.s")).unwrap(), expected);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_rcc"))
+        .current_dir(&dir)
+        .args(["--target", "sparc-sun-solaris"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown target `sparc-sun-solaris`"));
+    fs::remove_dir_all(&dir).unwrap();
+}
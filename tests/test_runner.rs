@@ -0,0 +1,98 @@
+use std::fs;
+use std::process::Command;
+
+use rcc::fixtures::Fixture;
+
+#[test]
+fn directives_become_expectations_and_blank_lines() {
+    let fixture = Fixture::parse(
+        "// expect-exit: 7\n// expect-stdout: hello\n//expect-stdout:  two\nint main() { return 7; }\n",
+    )
+    .unwrap();
+    assert_eq!(fixture.exit, 7);
+    assert_eq!(fixture.stdout.as_deref(), Some("hello\n two\n"));
+    assert_eq!(fixture.error, None);
+    // Diagnostics keep their line numbers.
+    assert_eq!(fixture.source, "\n\n\nint main() { return 7; }");
+}
+
+#[test]
+fn malformed_directives_are_rejected() {
+    assert!(Fixture::parse("// expect-exit: seven\n").is_err());
+    assert!(Fixture::parse("// expect-nothing: 1\n").is_err());
+    assert!(Fixture::parse("// just a comment\n").is_err());
+}
+
+#[test]
+fn runs_the_repository_fixtures() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("rcc-test-runner-fixtures-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for level in ["-O0", "-O2"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_rcc"))
+            .current_dir(&dir)
+            .args([
+                "test",
+                level,
+                concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures"),
+            ])
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(output.status.success(), "{stdout}");
+        assert!(stdout.contains("test result: ok."), "{stdout}");
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn reports_fixtures_that_fall_short() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("rcc-test-runner-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("exit.c"),
+        "// expect-exit: 2\nint main() { return 3; }\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("error.c"),
+        "// expect-error: nope\nint main() { return 0; }\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("stdout.c"),
+        "// expect-stdout: a\nint putchar(int c);\nint main() { putchar('b'); putchar('\\n'); return 0; }\n",
+    )
+    .unwrap();
+    fs::write(dir.join("pass.c"), "int main() { return 0; }\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rcc"))
+        .current_dir(&dir)
+        .arg("test")
+        .arg(&dir)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("exit.c: expected exit 2, got 3"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("error.c: expected an error containing `nope`, but it compiled"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains(r#"stdout.c: expected stdout "a\n", got "b\n""#),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("test result: FAILED. 1 passed; 3 failed"),
+        "{stdout}"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,231 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use rcc::codegen::aarch64::Aarch64;
+use rcc::codegen::x86_64::X86_64;
+use rcc::codegen::{Backend, CodeGenerator};
+use rcc::driver::{self, Options};
+use rcc::ir::interp::Interpreter;
+use rcc::ir::{Datum, FunctionBuilder, Global, IrType, Module};
+use rcc::standard::Standard;
+
+use common::assert_assembles;
+
+/// `main` passes the addresses of this thread's `counter`, defined here,
+/// and `errno_value`, defined elsewhere, to `read_both`.
+fn module(init: Option<i64>) -> Module {
+    let mut module = Module::default();
+    module.globals.push(Global {
+        name: "counter".to_string(),
+        ty: IrType::I32,
+        len: 1,
+        init: init.into_iter().map(Datum::Int).collect(),
+        thread_local: true,
+    });
+    let mut b = FunctionBuilder::new("main", Some(IrType::I32));
+    let counter = b.thread_local_addr("counter");
+    let external = b.thread_local_addr("errno_value");
+    let result = b.call("read_both", vec![counter, external], Some(IrType::I32));
+    b.ret(result);
+    module.functions.push(b.finish());
+    module
+}
+
+fn generate(backend: &dyn Backend, pic: bool) -> String {
+    CodeGenerator::new()
+        .with_backend(backend)
+        .with_pic(pic)
+        .generate(&module(Some(5)))
+}
+
+fn compile(source: &str, std: &str) -> Result<(), String> {
+    driver::compile(
+        source,
+        &Options {
+            standard: Standard::parse(std).unwrap(),
+            ..Options::default()
+        },
+    )
+    .map(drop)
+    .map_err(|err| err.to_string())
+}
+
+#[test]
+fn prints_thread_local_globals_and_addresses() {
+    let text = module(Some(5)).to_string();
+    assert!(
+        text.contains("@counter = thread_local global i32 5"),
+        "{text}"
+    );
+    assert!(
+        text.contains("%0 = addr ptr thread_local @counter\n"),
+        "{text}"
+    );
+    let llvm = rcc::ir::llvm::emit(&module(Some(5)));
+    assert!(
+        llvm.contains("@counter = thread_local global i32 5"),
+        "{llvm}"
+    );
+}
+
+#[test]
+fn the_interpreter_has_one_thread() {
+    let mut b = FunctionBuilder::new("entry", Some(IrType::I32));
+    b.thread_local_addr("counter");
+    let zero = b.iconst(IrType::I32, 0);
+    b.ret(Some(zero));
+    let mut module = module(None);
+    module.functions.push(b.finish());
+    assert_eq!(Interpreter::new(&module).call("entry", &[]), Ok(Some(0)));
+}
+
+#[test]
+fn elf_defines_thread_locals_in_tdata_and_tbss() {
+    let asm = generate(&X86_64::LINUX, false);
+    assert!(
+        asm.contains("    .section .tdata,\"awT\",@progbits\n    .globl counter\n"),
+        "{asm}"
+    );
+    assert!(asm.contains(".type counter, @tls_object\n"), "{asm}");
+    let asm = CodeGenerator::new()
+        .with_backend(&Aarch64::LINUX)
+        .generate(&module(None));
+    assert!(
+        asm.contains("    .section .tbss,\"awT\",%nobits\n    .globl counter\n"),
+        "{asm}"
+    );
+    assert_assembles("aarch64-linux-gnu", &asm);
+}
+
+#[test]
+fn elf_uses_local_exec_for_defined_and_initial_exec_otherwise() {
+    let asm = generate(&X86_64::LINUX, false);
+    assert!(asm.contains("movq %fs:0, "), "{asm}");
+    assert!(asm.contains("leaq counter@TPOFF(%"), "{asm}");
+    assert!(asm.contains("addq errno_value@GOTTPOFF(%rip), "), "{asm}");
+    assert_assembles("x86_64-linux-gnu", &asm);
+
+    let asm = generate(&X86_64::LINUX, true);
+    assert!(asm.contains("addq counter@GOTTPOFF(%rip), "), "{asm}");
+    assert!(!asm.contains("@TPOFF"), "{asm}");
+
+    let asm = generate(&Aarch64::LINUX, false);
+    assert!(asm.contains("mrs x8, tpidr_el0\n"), "{asm}");
+    assert!(asm.contains(":tprel_hi12:counter\n"), "{asm}");
+    assert!(asm.contains(":tprel_lo12_nc:counter\n"), "{asm}");
+    assert!(asm.contains(":gottprel:errno_value\n"), "{asm}");
+    assert_assembles("aarch64-linux-gnu", &asm);
+}
+
+#[test]
+fn macho_calls_the_descriptor_accessor() {
+    let asm = generate(&Aarch64::APPLE, false);
+    assert!(
+        asm.contains(
+            "    .section __DATA,__thread_vars,thread_local_variables\n    .globl _counter\n_counter:\n    .quad __tlv_bootstrap\n    .quad 0\n    .quad _counter$tlv$init\n"
+        ),
+        "{asm}"
+    );
+    assert!(asm.contains("_counter$tlv$init:\n    .word 5\n"), "{asm}");
+    assert!(
+        asm.contains("adrp x0, _counter@TLVPPAGE\n    ldr x0, [x0, _counter@TLVPPAGEOFF]\n    ldr x16, [x0, #0]\n    blr x16\n"),
+        "{asm}"
+    );
+    assert_assembles("arm64-apple-macos", &asm);
+
+    let asm = CodeGenerator::new()
+        .with_backend(&X86_64::APPLE)
+        .generate(&module(None));
+    assert!(asm.contains(".tbss _counter$tlv$init, 4, 2\n"), "{asm}");
+    assert!(
+        asm.contains("movq _counter@TLVP(%rip), %rdi\n    movq 0(%rdi), %rax\n    call *%rax\n"),
+        "{asm}"
+    );
+    assert_assembles("x86_64-apple-macos", &asm);
+}
+
+#[test]
+fn each_thread_sees_its_own_instance() {
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("rcc-tls-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("main.s"), generate(&X86_64::LINUX, false)).unwrap();
+    fs::write(
+        dir.join("read.c"),
+        "_Thread_local int errno_value = 2;\n\
+         int read_both(int *counter, int *errno_value) { return *counter * 10 + *errno_value; }\n",
+    )
+    .unwrap();
+    let exe = dir.join("main");
+    let Ok(linked) = Command::new("cc")
+        .arg(dir.join("main.s"))
+        .arg(dir.join("read.c"))
+        .arg("-o")
+        .arg(&exe)
+        .status()
+    else {
+        return;
+    };
+    assert!(linked.success());
+    let status = Command::new(&exe).status().unwrap();
+    assert_eq!(status.code(), Some(52));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn file_scope_variables_can_be_thread_local() {
+    let source = "_Thread_local int depth = 3; int main() { depth = depth + 1; return depth; }";
+    let ir = driver::compile(source, &Options::default()).unwrap().ir;
+    let text = ir.to_string();
+    assert!(
+        text.contains("@depth = thread_local global i32 3\n"),
+        "{text}"
+    );
+    assert!(text.contains("= addr ptr thread_local @depth\n"), "{text}");
+    assert_eq!(Interpreter::new(&ir).call("main", &[]), Ok(Some(4)));
+    let err = compile("_Thread_local int depth;", "c99").unwrap_err();
+    assert_eq!(err, "1:1: `_Thread_local` is only valid in C11 and later");
+}
+
+/// Accesses to them would compile to plain loads and stores, so they are
+/// rejected rather than silently not atomic.
+#[test]
+fn atomic_objects_are_not_supported_yet() {
+    let cases = [
+        "_Atomic int c; int main() { c++; return c; }",
+        "int main() { _Atomic(int) c = 0; return c; }",
+        "int f(_Atomic int *p) { *p = 3; return 0; }",
+        "_Atomic int *f(void);",
+    ];
+    for source in cases {
+        let err = compile(source, "c11").unwrap_err();
+        assert!(
+            err.ends_with("`_Atomic` objects are not supported yet"),
+            "{source}: {err}"
+        );
+    }
+    compile("int main() { return sizeof(_Atomic int); }", "c11").unwrap();
+}
+
+#[test]
+fn thread_local_and_atomic_need_c11() {
+    compile("_Atomic int main() { return 0; }", "c11").unwrap();
+    compile("_Atomic(int) main() { return 0; }", "c17").unwrap();
+    let err = compile("_Atomic int main() { return 0; }", "c99").unwrap_err();
+    assert_eq!(err, "1:1: `_Atomic` is only valid in C11 and later");
+    let err = compile("_Atomic void f() { return; }", "c11").unwrap_err();
+    assert_eq!(err, "1:1: `_Atomic` cannot qualify `void`");
+    let err = compile("_Thread_local int f() { return 0; }", "c99").unwrap_err();
+    assert_eq!(err, "1:1: `_Thread_local` is only valid in C11 and later");
+    let err = compile("_Thread_local int f() { return 0; }", "c11").unwrap_err();
+    assert_eq!(
+        err,
+        "1:1: `_Thread_local` only applies to variables, not functions"
+    );
+    let err = compile("thread_local int f() { return 0; }", "c23").unwrap_err();
+    assert!(err.contains("only applies to variables"), "{err}");
+}
@@ -0,0 +1,104 @@
+use rcc::driver::{self, Options, Stage};
+use rcc::stats;
+use rcc::timings::{Counting, Timings};
+use rcc::warning::{Warning, Warnings};
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+const SOURCE: &str = "int add() { return 1 + 2; }\nint main() { return add() * 3; }\n";
+
+#[test]
+fn every_stage_is_timed_in_order() {
+    let mut timings = Timings::new();
+    driver::compile_timed(SOURCE, &Options::default(), &mut timings).unwrap();
+    let names: Vec<_> = timings.stages.iter().map(|stage| stage.name).collect();
+    assert_eq!(
+        names,
+        ["lex", "parse", "analyze", "lower", "optimize", "codegen"]
+    );
+    // Each stage holds at least the source's tokens.
+    assert!(timings.stages.iter().all(|stage| stage.peak_memory > 0));
+    assert_eq!(
+        timings.total(),
+        timings.stages.iter().map(|stage| stage.time).sum()
+    );
+}
+
+#[test]
+fn stages_count_what_they_produce() {
+    let mut timings = Timings::new();
+    let artifacts = driver::compile_timed(SOURCE, &Options::default(), &mut timings).unwrap();
+    let outputs: Vec<_> = timings.stages.iter().map(|stage| stage.output).collect();
+    let instructions = artifacts.assembly.lines().filter(|line| {
+        let text = line.trim_start();
+        line.starts_with("    ") && !text.starts_with('.') && !text.is_empty()
+    });
+    assert_eq!(
+        outputs,
+        [
+            Some((artifacts.tokens.len(), "tokens")),
+            Some((stats::ast_nodes(&artifacts.program), "AST nodes")),
+            None,
+            outputs[3],
+            outputs[4],
+            Some((instructions.count(), "instructions")),
+        ]
+    );
+    let Some((lowered, "IR instructions")) = outputs[3] else {
+        panic!("{timings:?}");
+    };
+    let Some((optimized, "IR instructions")) = outputs[4] else {
+        panic!("{timings:?}");
+    };
+    assert!(optimized <= lowered, "{timings:?}");
+}
+
+#[test]
+fn checking_stops_after_analysis() {
+    let mut timings = Timings::new();
+    let options = Options {
+        last_stage: Stage::Analyze,
+        warnings: Warnings::new().enable(Warning::UnusedVariable),
+        ..Options::default()
+    };
+    let artifacts = driver::compile_timed(
+        "int main() { int unused; return 0; }",
+        &options,
+        &mut timings,
+    )
+    .unwrap();
+    let names: Vec<_> = timings.stages.iter().map(|stage| stage.name).collect();
+    assert_eq!(names, ["lex", "parse", "analyze"]);
+    assert_eq!(artifacts.warnings.len(), 1);
+    assert!(artifacts.assembly.is_empty());
+}
+
+#[test]
+fn stages_before_an_error_are_still_timed() {
+    let mut timings = Timings::new();
+    assert!(driver::compile_timed(
+        "int main() { return 1 +; }",
+        &Options::default(),
+        &mut timings
+    )
+    .is_err());
+    let names: Vec<_> = timings.stages.iter().map(|stage| stage.name).collect();
+    assert_eq!(names, ["lex", "parse"]);
+}
+
+#[test]
+fn the_report_has_a_row_per_stage_and_a_total() {
+    let mut timings = Timings::new();
+    driver::compile_timed(SOURCE, &Options::default(), &mut timings).unwrap();
+    let report = timings.to_string();
+    let lines: Vec<_> = report.lines().collect();
+    assert_eq!(lines.len(), 8, "{report}");
+    assert!(lines[0].starts_with("stage"), "{report}");
+    assert!(
+        lines[1].starts_with("lex ") && lines[1].ends_with(" KiB   25 tokens"),
+        "{report}"
+    );
+    assert!(lines[3].starts_with("analyze ") && lines[3].ends_with(" KiB"));
+    assert!(lines[7].starts_with("total "), "{report}");
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use std::fs;
+use std::process::Command;
+
+use rcc::codegen::Target;
+use rcc::driver::Options;
+use rcc::ir::llvm;
+use rcc::opt::OptLevel;
+use rcc::pretty;
+
+use common::compile;
+
+/// Calls `printf` with every kind of variable argument, and with more than
+/// fit in registers.
+const PRINTF: &str = r#"int printf(char *format, ...);
+int main() {
+    char c = 'x';
+    long big = 5000000000;
+    printf("%d %s %c %ld\n", 42, "hi", c, big);
+    printf("no arguments\n");
+    return printf("%d %d %d %d %d %d %d %d\n", 1, 2, 3, 4, 5, 6, 7, 8) - 16;
+}"#;
+
+#[test]
+fn variadic_prototypes_print_their_ellipsis() {
+    let program = compile(PRINTF, &Options::default()).unwrap().program;
+    assert!(program.functions[0].variadic);
+    assert!(!program.functions[1].variadic);
+    assert_eq!(program.functions[0].param_list(), "(char *format, ...)");
+    let printed = pretty::emit(&program);
+    assert!(
+        printed.starts_with("int printf(char *format, ...);\n"),
+        "{printed}"
+    );
+}
+
+#[test]
+fn variable_arguments_are_checked() {
+    let cases = [
+        (
+            "int printf(char *format, ...); int main() { return printf(); }",
+            "1:52: `printf` takes at least 1 argument, but 0 were given",
+        ),
+        (
+            "int printf(char *format, ...); void f(void); int main() { return printf(\"\", f()); }",
+            "1:77: expected `int` but found `void`",
+        ),
+        (
+            "int printf(char *format, ...); int printf(char *format); int main() { return 0; }",
+            "1:36: conflicting types for `printf`",
+        ),
+        (
+            "int log(int level, ...) { return level; } int main() { return 0; }",
+            "1:5: defining variadic function `log` is not supported yet",
+        ),
+        (
+            "int f(...); int main() { return 0; }",
+            "1:7: expected a type, found `...`",
+        ),
+        (
+            "int f(int, ..., int); int main() { return 0; }",
+            "1:15: expected `)`, found `,`",
+        ),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(
+            compile(source, &Options::default()).unwrap_err(),
+            expected,
+            "{source}"
+        );
+    }
+}
+
+#[test]
+fn the_ir_marks_where_variable_arguments_start() {
+    let artifacts = compile(PRINTF, &Options::default()).unwrap();
+    let ir = artifacts.ir.to_string();
+    assert!(
+        ir.contains(" = call i32 @printf(%4, ..., %5, %6, %8, %9)\n"),
+        "{ir}"
+    );
+    let ll = llvm::emit(&artifacts.ir);
+    assert!(ll.contains("\ndeclare i32 @printf(ptr, ...)\n"), "{ll}");
+    assert!(
+        ll.contains(" = call i32 (ptr, ...) @printf(ptr @.str2)\n"),
+        "{ll}"
+    );
+}
+
+#[test]
+fn apple_arm64_passes_variable_arguments_on_the_stack() {
+    let source = "int printf(char *format, ...); int main() { return printf(\"\", 1, 2L); }";
+    let options = |target| Options {
+        target,
+        opt_level: OptLevel::O2,
+        ..Options::default()
+    };
+    let asm = compile(source, &options(Target::AARCH64_APPLE))
+        .unwrap()
+        .assembly;
+    assert!(asm.contains("str w8, [sp, #0]"), "{asm}");
+    assert!(asm.contains("str x8, [sp, #8]"), "{asm}");
+    // Elsewhere they take the argument registers like any other.
+    let asm = compile(source, &options(Target::AARCH64_LINUX))
+        .unwrap()
+        .assembly;
+    assert!(asm.contains("mov w1, #1"), "{asm}");
+    assert!(!asm.contains("[sp, #0]"), "{asm}");
+}
+
+#[test]
+fn x86_program_calls_printf() {
+    for opt_level in [OptLevel::O0, OptLevel::O2] {
+        let options = Options {
+            target: Target::X86_64_LINUX,
+            opt_level,
+            ..Options::default()
+        };
+        let asm = compile(PRINTF, &options).unwrap().assembly;
+        // `%al` says that no vector registers hold arguments.
+        assert!(asm.contains("xorl %eax, %eax\n    call printf"), "{asm}");
+        if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+            continue;
+        }
+        let dir =
+            std::env::temp_dir().join(format!("rcc-variadic-{}-{opt_level:?}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (source, exe) = (dir.join("printf.s"), dir.join("printf"));
+        fs::write(&source, &asm).unwrap();
+        let Ok(linked) = Command::new("cc").arg(&source).arg("-o").arg(&exe).status() else {
+            return;
+        };
+        assert!(linked.success(), "{asm}");
+        let output = Command::new(&exe).output().unwrap();
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "42 hi x 5000000000\nno arguments\n1 2 3 4 5 6 7 8\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
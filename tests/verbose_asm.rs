@@ -0,0 +1,54 @@
+mod common;
+
+use rcc::codegen::Target;
+use rcc::driver::{self, Options};
+
+use common::assert_assembles;
+
+const SOURCE: &str =
+    "int helper() {\n    return 6 * 7;\n}\n\nint main() {\n    return helper() - 42;\n}\n";
+
+fn verbose(target: Target) -> String {
+    driver::compile(
+        SOURCE,
+        &Options {
+            target,
+            verbose_asm: true,
+            ..Options::default()
+        },
+    )
+    .expect("program should compile")
+    .assembly
+}
+
+#[test]
+fn statements_are_quoted_above_their_code() {
+    let asm = verbose(Target::AARCH64_APPLE);
+    assert!(
+        asm.contains("_main:\n    // 5: int main() {\n    stp x29, x30, [sp, #-16]!\n"),
+        "{asm}"
+    );
+    assert!(
+        asm.contains("    // 6: return helper() - 42;\n    bl _helper\n"),
+        "{asm}"
+    );
+    assert_eq!(asm.matches("// 6:").count(), 1, "{asm}");
+    assert!(!asm.contains(".loc"), "{asm}");
+    assert_assembles("arm64-apple-macos", &asm);
+}
+
+#[test]
+fn x86_comments_start_with_a_hash() {
+    let asm = verbose(Target::X86_64_LINUX);
+    assert!(asm.contains("    # 2: return 6 * 7;\n"), "{asm}");
+    assert!(!asm.contains("//"), "{asm}");
+    assert_assembles("x86_64-linux-gnu", &asm);
+}
+
+#[test]
+fn plain_assembly_has_no_comments() {
+    let asm = driver::compile(SOURCE, &Options::default())
+        .unwrap()
+        .assembly;
+    assert!(!asm.contains("//"), "{asm}");
+}
@@ -0,0 +1,136 @@
+use rcc::driver::{self, Options};
+use rcc::ir::verify::{verify_function, verify_module};
+use rcc::ir::{BinOp, BlockId, FunctionBuilder, Instr, IrType, Module, Terminator, Value};
+use rcc::opt::{OptLevel, PassManager};
+
+#[test]
+fn accepts_compiled_programs_at_every_level() {
+    let source = "int f() { return 9; } int main() { return f() / 4 + 2 * f(); }";
+    for opt_level in [OptLevel::O0, OptLevel::O1, OptLevel::O2, OptLevel::Os] {
+        let ir = driver::compile(
+            source,
+            &Options {
+                opt_level,
+                ..Options::default()
+            },
+        )
+        .unwrap()
+        .ir;
+        assert_eq!(verify_module(&ir), Ok(()), "{opt_level:?}");
+    }
+}
+
+#[test]
+fn rejects_use_before_definition() {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let sum = f.binary(BinOp::Add, x, x);
+    f.ret(Some(sum));
+    let mut func = f.finish();
+    func.blocks[0].instrs.insert(
+        0,
+        Instr::Binary {
+            dst: Value(2),
+            op: BinOp::Mul,
+            lhs: sum,
+            rhs: x,
+        },
+    );
+    func.value_types.push(IrType::I32);
+
+    let err = verify_function(&func).unwrap_err();
+    assert_eq!(err.block, Some(BlockId::ENTRY));
+    assert!(err.message.contains("not dominated"), "{err}");
+}
+
+#[test]
+fn rejects_use_from_a_sibling_branch() {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let (left, right) = (f.create_block(), f.create_block());
+    f.branch(x, left, right);
+    f.switch_to(left);
+    let doubled = f.binary(BinOp::Add, x, x);
+    f.ret(Some(doubled));
+    f.switch_to(right);
+    f.ret(Some(x));
+    let mut func = f.finish();
+    func.block_mut(right).term = Terminator::Ret(Some(doubled));
+
+    let err = verify_function(&func).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "in function `f`, bb2: use of %1 is not dominated by its definition in bb1"
+    );
+}
+
+#[test]
+fn rejects_duplicate_definitions() {
+    let mut f = FunctionBuilder::new("f", None);
+    let c = f.iconst(IrType::I32, 1);
+    f.ret(None);
+    let mut func = f.finish();
+    func.blocks[0]
+        .instrs
+        .push(Instr::Const { dst: c, value: 2 });
+
+    let err = verify_function(&func).unwrap_err();
+    assert!(err.message.contains("defined more than once"), "{err}");
+}
+
+#[test]
+fn rejects_bad_returns_and_jump_targets() {
+    let mut f = FunctionBuilder::new("f", Some(IrType::I32));
+    f.ret(None);
+    let func = f.finish();
+    assert!(verify_function(&func)
+        .unwrap_err()
+        .message
+        .contains("return type"));
+
+    let mut func = func;
+    func.blocks[0].term = Terminator::Jump(BlockId(7));
+    assert!(verify_function(&func)
+        .unwrap_err()
+        .message
+        .contains("nonexistent block bb7"));
+}
+
+#[test]
+fn rejects_calls_with_the_wrong_arity() {
+    let mut callee = FunctionBuilder::new("two", Some(IrType::I32));
+    let x = callee.param(IrType::I32);
+    let y = callee.param(IrType::I32);
+    let sum = callee.binary(BinOp::Add, x, y);
+    callee.ret(Some(sum));
+
+    let mut caller = FunctionBuilder::new("main", Some(IrType::I32));
+    let one = caller.iconst(IrType::I32, 1);
+    let result = caller.call("two", vec![one], Some(IrType::I32));
+    caller.ret(result);
+
+    let module = Module {
+        functions: vec![callee.finish(), caller.finish()],
+        ..Module::default()
+    };
+    let err = verify_module(&module).unwrap_err();
+    assert_eq!(err.function, "main");
+    assert!(
+        err.message.contains("passes 1 arguments, expected 2"),
+        "{err}"
+    );
+}
+
+#[test]
+#[should_panic(expected = "internal compiler error: invalid IR after lowering")]
+fn pass_manager_reports_invalid_ir() {
+    let mut f = FunctionBuilder::new("f", None);
+    f.ret(None);
+    let mut func = f.finish();
+    func.blocks[0].term = Terminator::Ret(Some(Value(5)));
+    let mut module = Module {
+        functions: vec![func],
+        ..Module::default()
+    };
+    PassManager::for_level(OptLevel::O1).run_ir(&mut module);
+}
@@ -0,0 +1,196 @@
+use rcc::driver::{self, Options};
+use rcc::warning::{Warning, Warnings};
+
+/// The warnings `flags` turn on for `source`, as `line:col: message`.
+fn warnings(source: &str, flags: &[&str]) -> Vec<String> {
+    let mut warnings = Warnings::new();
+    for flag in flags {
+        assert!(warnings.apply(flag), "{flag}");
+    }
+    let options = Options {
+        warnings,
+        ..Options::default()
+    };
+    driver::compile(source, &options)
+        .expect("program should compile")
+        .warnings
+        .iter()
+        .map(|diagnostic| format!("{}: {}", diagnostic.pos, diagnostic.message))
+        .collect()
+}
+
+const UNUSED: &str = "int main() {
+  int unused;
+  int x = 1;
+  for (int i = 0; x < 0; x++) {}
+  return x;
+}
+";
+
+#[test]
+fn warnings_are_off_by_default() {
+    assert!(warnings(UNUSED, &[]).is_empty());
+}
+
+#[test]
+fn unused_variables_are_reported_where_they_are_declared() {
+    assert_eq!(
+        warnings(UNUSED, &["unused-variable"]),
+        ["2:7: unused variable `unused`", "4:12: unused variable `i`"]
+    );
+    // Assigning to a variable or taking its size is a use.
+    let source = "int main() { int a; int b; a = 1; return sizeof b; }";
+    assert!(warnings(source, &["all"]).is_empty());
+}
+
+#[test]
+fn unused_labels_are_reported() {
+    let source = "int main() {\n  goto used;\nused:\nunused:\n  return 0;\n}\n";
+    assert_eq!(
+        warnings(source, &["all"]),
+        ["4:1: label `unused` defined but not used"]
+    );
+}
+
+#[test]
+fn code_after_return_or_goto_is_unreachable_until_a_label() {
+    let source = "int main() {
+  int x = 0;
+  while (x < 3) {
+    x++;
+    goto out;
+    x = 10;
+    x = 20;
+  }
+out:
+  return x;
+  x = 5;
+}
+";
+    assert_eq!(
+        warnings(source, &["unreachable-code"]),
+        [
+            "6:5: code will never be executed",
+            "11:3: code will never be executed"
+        ]
+    );
+    // `-Wall` leaves unreachable code alone.
+    assert!(warnings(source, &["all"]).is_empty());
+    let source = "int main(int x) {
+  if (x)
+    return 1;
+  else
+    return 2;
+  x = 3;
+}
+";
+    assert_eq!(
+        warnings(source, &["unreachable-code"]),
+        ["6:3: code will never be executed"]
+    );
+}
+
+#[test]
+fn later_flags_override_earlier_ones() {
+    assert_eq!(warnings(UNUSED, &["all", "no-unused-variable"]).len(), 0);
+    assert_eq!(warnings(UNUSED, &["no-all", "unused-variable"]).len(), 2);
+}
+
+#[test]
+fn werror_makes_warnings_errors() {
+    let mut warnings = Warnings::new().enable(Warning::UnusedLabel);
+    assert!(!warnings.is_error(Warning::UnusedLabel));
+    assert!(warnings.apply("error"));
+    assert!(warnings.is_error(Warning::UnusedLabel));
+    assert!(warnings.apply("no-error=unused-label"));
+    assert!(!warnings.is_error(Warning::UnusedLabel));
+    assert!(warnings.apply("error=unused-variable"));
+    assert!(warnings.is_enabled(Warning::UnusedVariable));
+    assert!(warnings.is_error(Warning::UnusedVariable));
+    assert!(!warnings.apply("error=shadow"));
+    assert!(!warnings.apply("shadow"));
+    warnings.suppress();
+    assert!(!warnings.is_enabled(Warning::UnusedVariable));
+}
+
+#[test]
+fn warnings_name_their_flag() {
+    let options = Options {
+        warnings: Warnings::new().enable(Warning::UnusedVariable),
+        ..Options::default()
+    };
+    let artifacts = driver::compile("int main() { int x; return 0; }", &options).unwrap();
+    let warning = &artifacts.warnings[0];
+    assert_eq!(
+        warning.to_error(false).to_string(),
+        "1:18: unused variable `x` [-Wunused-variable]"
+    );
+    assert_eq!(
+        warning.to_error(true).to_string(),
+        "1:18: unused variable `x` [-Werror=unused-variable]"
+    );
+}
+
+#[test]
+fn functions_that_can_end_without_a_return_are_reported() {
+    let source = "int none(int x) {
+  x = 1;
+}
+int some(int x) {
+  while (x < 3) {
+    return 1;
+  }
+}
+int label(int x) {
+  return 0;
+again:
+  x = 2;
+}
+int all(int x) {
+  { return x; }
+}
+int forever(int x) {
+  for (;;) x = x + 1;
+}
+int once(int x) {
+  do { return x; } while (0);
+}
+int cases(int x) {
+  switch (x) { case 1: return 1; default: return 2; }
+}
+int no_default(int x) {
+  switch (x) { case 1: return 1; }
+}
+int broken(int x) {
+  for (;;) { break; }
+}
+int both(int x) {
+  if (x) return 1; else return 2;
+}
+int then_only(int x) {
+  if (x) return 1;
+}
+void nothing(int x) {}
+int main() {}
+";
+    assert_eq!(
+        warnings(source, &["all", "no-unused-label"]),
+        [
+            "1:5: non-void function `none` does not return a value in all control paths",
+            "4:5: non-void function `some` does not return a value in all control paths",
+            "9:5: non-void function `label` does not return a value in all control paths",
+            "26:5: non-void function `no_default` does not return a value in all control paths",
+            "29:5: non-void function `broken` does not return a value in all control paths",
+            "35:5: non-void function `then_only` does not return a value in all control paths",
+        ]
+    );
+}
+
+#[test]
+fn void_functions_cannot_return_values() {
+    let err = driver::compile("void f() { return 1; }", &Options::default()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "1:12: void function `f` should not return a value"
+    );
+}
@@ -0,0 +1,230 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use rcc::codegen::{wasm, Target};
+use rcc::driver::{self, Options};
+use rcc::ir::{FunctionBuilder, IrType, Module};
+use rcc::opt::OptLevel;
+
+fn wat(source: &str, opt_level: OptLevel) -> Result<String, String> {
+    let options = Options {
+        target: Target::WASM32,
+        opt_level,
+        ..Options::default()
+    };
+    driver::compile(source, &options)
+        .map(|artifacts| artifacts.assembly)
+        .map_err(|err| err.to_string())
+}
+
+/// A fresh directory for one test's files.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rcc-wasm-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+const PROGRAM: &str = "int printf(char *format, ...);
+extern int counter;
+long big = 50000;
+int square(int x) { return x * x; }
+int main(void) {
+    int sum = 0;
+    for (int i = 0; i < 4; i++)
+        sum += square(i);
+    printf(\"%d %s\\n\", sum, \"squares\");
+    return sum + counter + big / 10000;
+}";
+
+#[test]
+fn functions_become_wasm_functions_and_main_is_exported() {
+    for level in [OptLevel::O0, OptLevel::O2] {
+        let wat = wat(PROGRAM, level).unwrap();
+        assert!(wat.starts_with("(module\n"), "{wat}");
+        assert!(wat.ends_with("  )\n)\n"), "{wat}");
+        assert!(
+            wat.contains("\n  (func $square (param $v0 i32) (result i32)\n"),
+            "{wat}"
+        );
+        assert!(
+            wat.contains("\n  (func $main (export \"main\") (result i32)\n"),
+            "{wat}"
+        );
+        assert!(wat.contains("    call $printf\n"), "{wat}");
+        assert_eq!(wat.matches("(export ").count(), 2, "{wat}");
+        assert!(wat.contains("  (memory (export \"memory\") 2)\n"), "{wat}");
+    }
+}
+
+#[test]
+fn values_are_locals_and_variables_live_in_the_frame() {
+    let wat = wat(PROGRAM, OptLevel::O0).unwrap();
+    let square = &wat[wat.find("(func $square").unwrap()..wat.find("(func $main").unwrap()];
+    assert!(square.contains("    (local $v1 i32)\n"), "{square}");
+    assert!(square.contains("    (local $fp i32)\n"), "{square}");
+    // `x` is spilled to the frame below the stack pointer and read back.
+    assert!(square.contains("    local.tee $fp\n"), "{square}");
+    assert!(square.contains("    i32.store offset=0\n"), "{square}");
+    assert!(square.contains("    i32.load offset=0\n"), "{square}");
+    assert!(!square.contains("$block"), "{square}");
+}
+
+#[test]
+fn undefined_functions_and_globals_are_imported() {
+    let wat = wat(PROGRAM, OptLevel::O2).unwrap();
+    // The variable arguments are passed in memory, after the format.
+    assert!(
+        wat.contains("  (import \"env\" \"printf\" (func $printf (param i32 i32) (result i32)))\n"),
+        "{wat}"
+    );
+    assert!(
+        wat.contains("  (import \"env\" \"counter\" (global $counter i32))\n"),
+        "{wat}"
+    );
+    assert!(
+        wat.contains("  (global $__stack_pointer (mut i32) (i32.const 131072))\n"),
+        "{wat}"
+    );
+    assert!(
+        wat.contains("  (data (i32.const 16) \"%d %s\\0a\\00\")\n"),
+        "{wat}"
+    );
+    assert!(
+        wat.contains("  (data (i32.const 23) \"squares\\00\")\n"),
+        "{wat}"
+    );
+    assert!(
+        wat.contains("  (global $big i32 (i32.const 32))\n"),
+        "{wat}"
+    );
+    assert!(
+        wat.contains("  (data (i32.const 32) \"P\\c3\\00\\00\")\n"),
+        "{wat}"
+    );
+}
+
+#[test]
+fn long_and_pointers_are_four_bytes() {
+    let source = "char *names[3] = {\"ab\", \"cd\", 0};
+int main(void) {
+    long x = -1;
+    unsigned u = 1;
+    return (x < u) * 100 + sizeof(long) * 10 + sizeof(int *);
+}
+char *second(void) { return names[1]; }";
+    let wat = wat(source, OptLevel::O0).unwrap();
+    // Under ILP32 `long` can't hold every `unsigned`, so the two compare
+    // as `unsigned long`.
+    let main = &wat[wat.find("(func $main").unwrap()..wat.find("(func $second").unwrap()];
+    assert!(main.contains("    i32.lt_u\n"), "{main}");
+    assert!(main.contains("    i32.const 4\n"), "{main}");
+    assert!(!main.contains("    i32.const 8\n"), "{main}");
+    assert!(!wat.contains("i64"), "{wat}");
+    let second = &wat[wat.find("(func $second").unwrap()..];
+    assert!(second.contains("    i32.const 4\n"), "{second}");
+    assert!(
+        wat.contains(
+            "  (data (i32.const 24) \"\\10\\00\\00\\00\\13\\00\\00\\00\\00\\00\\00\\00\")\n"
+        ),
+        "{wat}"
+    );
+}
+
+#[test]
+fn blocks_are_dispatched_from_a_loop() {
+    let mut f = FunctionBuilder::new("pick", Some(IrType::I32));
+    let x = f.param(IrType::I32);
+    let one = f.create_block();
+    let other = f.create_block();
+    f.switch(x, vec![(7, one)], other);
+    f.switch_to(one);
+    let size = f.iconst(IrType::I32, 24);
+    let block = f.alloca(size);
+    f.store_ptr(block, x);
+    let value = f.load_ptr(IrType::I32, block);
+    f.ret(Some(value));
+    f.switch_to(other);
+    f.jump(one);
+    let module = Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    };
+    let wat = wasm::emit(&module).unwrap();
+    let body = "    loop $dispatch
+    block $bb2
+    block $bb1
+    block $bb0
+    local.get $block
+    br_table $bb0 $bb1 $bb2 $bb0
+    end
+    ;; bb0
+";
+    assert!(wat.contains(body), "{wat}");
+    assert!(
+        wat.contains("    i32.const 7\n    i32.eq\n    if\n    i32.const 1\n    local.set $block\n    br $dispatch\n    end\n    i32.const 2\n"),
+        "{wat}"
+    );
+    // The block is carved from the stack, which the return gives back.
+    assert!(
+        wat.contains("    i32.sub\n    i32.const -16\n    i32.and\n"),
+        "{wat}"
+    );
+    assert!(
+        wat.contains("    i32.add\n    global.set $__stack_pointer\n    return\n"),
+        "{wat}"
+    );
+    assert!(wat.ends_with("    end\n    unreachable\n  )\n)\n"), "{wat}");
+}
+
+#[test]
+fn inline_assembly_cannot_be_compiled_to_wasm() {
+    let err = wat(
+        "int main(void) { __asm__(\"nop\"); return 0; }",
+        OptLevel::O0,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err,
+        "inline assembly in `main` cannot be compiled to WebAssembly"
+    );
+}
+
+#[test]
+fn the_command_line_writes_wat_for_wasm32() {
+    let dir = temp_dir("cli");
+    fs::write(dir.join("a.c"), "int main(void) { return 42; }").unwrap();
+    let rcc = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_rcc"))
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .unwrap()
+    };
+    let output = rcc(&["--target", "wasm32", "a.c"]);
+    assert!(output.status.success(), "{output:?}");
+    let wat = fs::read_to_string(dir.join("a.wat")).unwrap();
+    assert!(wat.contains("(export \"main\")"), "{wat}");
+    for args in [
+        &["--target", "wasm32", "-S", "a.c"][..],
+        &["--target", "wasm32", "-c", "a.c"],
+        &["--target", "wasm32", "--coverage", "a.c"],
+        &["--target", "wasm32", "--interpret", "a.c"],
+        &["--emit=wat", "a.c"],
+    ] {
+        let output = rcc(args);
+        assert_eq!(output.status.code(), Some(2), "{args:?}: {output:?}");
+    }
+
+    // Run the module if wasmtime is installed.
+    if Command::new("wasmtime").arg("--version").output().is_ok() {
+        let output = Command::new("wasmtime")
+            .args(["run", "--invoke", "main", "a.wat"])
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "42");
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
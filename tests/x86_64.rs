@@ -0,0 +1,328 @@
+mod common;
+
+use rcc::codegen::x86_64::X86_64;
+use rcc::codegen::{CodeGenerator, Target};
+use rcc::driver::{self, Options};
+use rcc::ir::interp::Interpreter;
+use rcc::ir::{BinOp, CmpOp, FunctionBuilder, IrType, Module};
+use rcc::opt::OptLevel;
+
+use common::{compile, run};
+
+fn assembly(source: &str, opt_level: OptLevel) -> String {
+    let options = Options {
+        opt_level,
+        target: Target::X86_64_LINUX,
+        ..Options::default()
+    };
+    compile(source, &options)
+        .expect("program should compile")
+        .assembly
+}
+
+#[test]
+fn selects_x86_instructions() {
+    let asm = assembly("int main() { return 7 * 3 - 1; }", OptLevel::O0);
+    assert!(asm.contains(".globl main\n"), "{asm}");
+    assert!(
+        asm.contains("main:\n    pushq %rbp\n    movq %rsp, %rbp\n"),
+        "{asm}"
+    );
+    assert!(asm.contains("imull $3, %eax, %eax"), "{asm}");
+    assert!(asm.contains("subl $1, %eax"), "{asm}");
+    assert!(asm.contains("    leave\n    ret\n"), "{asm}");
+    assert!(!asm.contains("_main"), "{asm}");
+}
+
+#[test]
+fn divides_through_edx_eax() {
+    let asm = assembly("int main() { return 7 / 2; }", OptLevel::O0);
+    assert!(
+        asm.contains("movl $7, %eax\n    cltd\n    movl $2, %ecx\n    idivl %ecx\n"),
+        "{asm}"
+    );
+}
+
+#[test]
+fn shares_the_ir_with_aarch64() {
+    let source = "int f() { return 6; } int main() { return f() * f(); }";
+    let aarch64 = driver::compile(source, &Options::default()).unwrap();
+    let x86 = driver::compile(
+        source,
+        &Options {
+            target: Target::X86_64_LINUX,
+            ..Options::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(aarch64.ir.to_string(), x86.ir.to_string());
+    assert!(x86.assembly.contains("call f\n"), "{}", x86.assembly);
+}
+
+#[test]
+fn compiled_programs_match_the_interpreter() {
+    let programs = [
+        "int main() { return 2 * 3 + 4; }",
+        "int f() { return 5; } int main() { return f() * 8 + 8 * f(); }",
+        "int f() { return 0 - 7; } int main() { return f() / 4 + f() / 2 * 3 + 20; }",
+        "int g() { return 3; } int f() { return g() * g() - 1; } int main() { return f() * f(); }",
+        "int f() { return 100; } int main() { return f() / 16 - f() * 1 + 200; }",
+        "int f() { return 0 - 47; } int main() { return f() % 10 + f() / 10 * 3 + 100; }",
+        "int f() { return 1000; } int main() { return f() % 7 * 10 + f() % 256 % 9; }",
+        "int f() { return 0 - 100; } int main() { return (f() >> 2 ^ f() << 1 | 3) & 255; }",
+        "int f() { return 6; } int main() { return (f() < 7) + (f() >= 6) * 2 + (f() != 6) * 4; }",
+        "int f() { return 5; } int main() { return (f() && 0) + (f() || 0) * 2 + (f() == 5) * 4; }",
+        "int f() { return 9; } int main() { return -f() * ~f() + !f() + !!f() * 3; }",
+    ];
+    for (i, source) in programs.iter().enumerate() {
+        for level in [OptLevel::O0, OptLevel::O1, OptLevel::O2] {
+            let ir = driver::compile(source, &Options::default()).unwrap().ir;
+            let expected = Interpreter::new(&ir).call("main", &[]).unwrap().unwrap();
+            let Some(status) = run(&format!("p{i}_{level:?}"), &assembly(source, level)) else {
+                return;
+            };
+            assert_eq!(i64::from(status), expected & 0xff, "{source} at {level:?}");
+        }
+    }
+}
+
+#[test]
+fn intel_syntax_programs_run_the_same() {
+    let source = "int f() { return 0 - 47; } int main() { return f() % 10 + f() / 10 * 3 + 100; }";
+    for level in [OptLevel::O0, OptLevel::O2] {
+        let options = Options {
+            opt_level: level,
+            target: Target::X86_64_LINUX,
+            intel_syntax: true,
+            ..Options::default()
+        };
+        let asm = driver::compile(source, &options).unwrap().assembly;
+        assert!(asm.starts_with("    .intel_syntax noprefix\n"), "{asm}");
+        assert!(!asm.contains('%'), "{asm}");
+        let Some(status) = run(&format!("intel_{level:?}"), &asm) else {
+            return;
+        };
+        assert_eq!(status, 81, "{asm}");
+    }
+
+    // Loops and comparisons exercise jumps, `set` and `movzx`.
+    let mut asm = CodeGenerator::new()
+        .with_backend(&X86_64::LINUX)
+        .with_register_allocation(true)
+        .generate_assembly(&counting_loop());
+    asm.syntax = rcc::codegen::asm::Syntax::Intel;
+    if let Some(status) = run("intel_loop", &asm.to_string()) {
+        assert_eq!(status, 45, "{asm}");
+    }
+}
+
+/// `main` passes `1..=8` to `sum8`, which needs two stack arguments, and
+/// `sum8` keeps a running total in a stack slot.
+fn eight_argument_call() -> Module {
+    let mut callee = FunctionBuilder::new("sum8", Some(IrType::I32));
+    let params: Vec<_> = (0..8).map(|_| callee.param(IrType::I32)).collect();
+    let total = callee.stack_slot(IrType::I32);
+    callee.store(total, params[0]);
+    for &param in &params[1..] {
+        let acc = callee.load(total);
+        let sum = callee.binary(BinOp::Add, acc, param);
+        callee.store(total, sum);
+    }
+    let result = callee.load(total);
+    callee.ret(Some(result));
+
+    let mut caller = FunctionBuilder::new("main", Some(IrType::I32));
+    let args = (1..=8).map(|i| caller.iconst(IrType::I32, i)).collect();
+    let result = caller.call("sum8", args, Some(IrType::I32));
+    caller.ret(result);
+    Module {
+        functions: vec![callee.finish(), caller.finish()],
+        ..Module::default()
+    }
+}
+
+#[test]
+fn passes_arguments_beyond_the_sixth_on_the_stack() {
+    let module = eight_argument_call();
+    for allocate in [false, true] {
+        let asm = CodeGenerator::new()
+            .with_backend(&X86_64::LINUX)
+            .with_register_allocation(allocate)
+            .generate(&module);
+        assert!(
+            asm.contains("movl $7, %eax\n    movl %eax, 0(%rsp)\n    movl $8, %eax\n    movl %eax, 8(%rsp)\n"),
+            "{asm}"
+        );
+        assert!(asm.contains("movl 24(%rbp), "), "{asm}");
+        if let Some(status) = run(&format!("sum8_{allocate}"), &asm) {
+            assert_eq!(status, 36, "{asm}");
+        }
+    }
+}
+
+/// `main` returns a bit per comparison of `lhs` against `rhs`, half of them
+/// as values and half through branches.
+fn comparisons(lhs: i64, rhs: i64) -> Module {
+    let ops = [
+        CmpOp::Eq,
+        CmpOp::Ne,
+        CmpOp::Slt,
+        CmpOp::Sle,
+        CmpOp::Sgt,
+        CmpOp::Sge,
+        CmpOp::Ult,
+        CmpOp::Ule,
+        CmpOp::Ugt,
+        CmpOp::Uge,
+    ];
+    let mut f = FunctionBuilder::new("main", Some(IrType::I32));
+    let bits = f.stack_slot(IrType::I32);
+    let zero = f.iconst(IrType::I32, 0);
+    f.store(bits, zero);
+    for (i, op) in ops.into_iter().enumerate() {
+        let x = f.iconst(IrType::I32, lhs);
+        let y = f.iconst(IrType::I32, rhs);
+        let x = f.call("id", vec![x], Some(IrType::I32)).unwrap();
+        let y = f.call("id", vec![y], Some(IrType::I32)).unwrap();
+        let cond = f.cmp(op, x, y);
+        let bit = if i % 2 == 0 {
+            cond
+        } else {
+            let (then_block, join) = (f.create_block(), f.create_block());
+            let slot = f.stack_slot(IrType::I32);
+            f.store(slot, zero);
+            f.branch(cond, then_block, join);
+            f.switch_to(then_block);
+            let one = f.iconst(IrType::I32, 1);
+            f.store(slot, one);
+            f.jump(join);
+            f.switch_to(join);
+            f.load(slot)
+        };
+        let shift = f.iconst(IrType::I32, i as i64 % 8);
+        let bit = f.binary(BinOp::Shl, bit, shift);
+        let acc = f.load(bits);
+        let acc = f.binary(BinOp::Add, acc, bit);
+        f.store(bits, acc);
+    }
+    let result = f.load(bits);
+    f.ret(Some(result));
+
+    let mut id = FunctionBuilder::new("id", Some(IrType::I32));
+    let x = id.param(IrType::I32);
+    id.ret(Some(x));
+    Module {
+        functions: vec![id.finish(), f.finish()],
+        ..Module::default()
+    }
+}
+
+#[test]
+fn comparisons_match_the_interpreter() {
+    for (i, (lhs, rhs)) in [(3, 3), (-1, 2), (2, -1), (5, 9)].into_iter().enumerate() {
+        let module = comparisons(lhs, rhs);
+        let expected = Interpreter::new(&module)
+            .call("main", &[])
+            .unwrap()
+            .unwrap();
+        for allocate in [false, true] {
+            let asm = CodeGenerator::new()
+                .with_backend(&X86_64::LINUX)
+                .with_register_allocation(allocate)
+                .generate(&module);
+            assert!(asm.contains("movzbl %al, "), "{asm}");
+            let Some(status) = run(&format!("cmp{i}_{allocate}"), &asm) else {
+                return;
+            };
+            assert_eq!(i64::from(status), expected & 0xff, "{lhs} vs {rhs}:\n{asm}");
+        }
+    }
+}
+
+/// `main` sums `0..10` in a loop whose header tests the counter.
+fn counting_loop() -> Module {
+    let mut f = FunctionBuilder::new("main", Some(IrType::I32));
+    let (i, sum) = (f.stack_slot(IrType::I32), f.stack_slot(IrType::I32));
+    let zero = f.iconst(IrType::I32, 0);
+    f.store(i, zero);
+    f.store(sum, zero);
+    let (header, body, exit) = (f.create_block(), f.create_block(), f.create_block());
+    f.jump(header);
+    f.switch_to(header);
+    let counter = f.load(i);
+    let ten = f.iconst(IrType::I32, 10);
+    let more = f.cmp(CmpOp::Slt, counter, ten);
+    f.branch(more, body, exit);
+    f.switch_to(body);
+    let counter = f.load(i);
+    let acc = f.load(sum);
+    let acc = f.binary(BinOp::Add, acc, counter);
+    f.store(sum, acc);
+    let one = f.iconst(IrType::I32, 1);
+    let counter = f.binary(BinOp::Add, counter, one);
+    f.store(i, counter);
+    f.jump(header);
+    f.switch_to(exit);
+    let result = f.load(sum);
+    f.ret(Some(result));
+    Module {
+        functions: vec![f.finish()],
+        ..Module::default()
+    }
+}
+
+#[test]
+fn loops_exit_on_the_inverted_condition() {
+    let module = counting_loop();
+    for allocate in [false, true] {
+        let asm = CodeGenerator::new()
+            .with_backend(&X86_64::LINUX)
+            .with_register_allocation(allocate)
+            .generate(&module);
+        assert!(asm.contains("    jge .Lmain_3\n.Lmain_2:\n"), "{asm}");
+        assert!(asm.contains("    jmp .Lmain_1\n.Lmain_3:\n"), "{asm}");
+        if let Some(status) = run(&format!("loop_{allocate}"), &asm) {
+            assert_eq!(status, 45, "{asm}");
+        }
+    }
+}
+
+/// Builds `((v0+v1)*(v2+v3))*((v4+v5)*(v6+v7))` and so on over `count`
+/// variables, which alternate between `+` and `*` at each level.
+fn balanced(names: &[String], sum: bool) -> String {
+    match names {
+        [name] => name.clone(),
+        _ => {
+            let (left, right) = names.split_at(names.len() / 2);
+            let op = if sum { '+' } else { '*' };
+            format!("({} {op} {})", balanced(left, !sum), balanced(right, !sum))
+        }
+    }
+}
+
+#[test]
+fn nested_expressions_spill_what_does_not_fit() {
+    // Every variable comes from a call, so each is live until the
+    // expression reads it, far more than there are registers.
+    for count in [8, 16, 32] {
+        let names: Vec<String> = (0..count).map(|i| format!("v{i}")).collect();
+        let declarations: String = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| format!("int {name} = f({i});\n"))
+            .collect();
+        let source = format!(
+            "int f(int x) {{ return x % 3 + 1; }}\nint main() {{\n{declarations}return {} & 255;\n}}",
+            balanced(&names, count % 2 == 0)
+        );
+        let ir = driver::compile(&source, &Options::default()).unwrap().ir;
+        let expected = Interpreter::new(&ir).call("main", &[]).unwrap().unwrap();
+        for level in [OptLevel::O0, OptLevel::O2] {
+            let asm = assembly(&source, level);
+            let Some(status) = run(&format!("nested{count}_{level:?}"), &asm) else {
+                return;
+            };
+            assert_eq!(i64::from(status), expected, "{source} at {level:?}");
+        }
+    }
+}